@@ -0,0 +1,154 @@
+//! Benchmarks for the paths every request goes through, to judge changes
+//! such as caching, memory mapping, or parallel directory walking.
+//!
+//! ```bash
+//! cargo bench --bench hot_paths
+//! cargo bench --bench hot_paths -- policy   # only the policy group
+//! ```
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use filejack::{AccessPolicy, McpServer, RateLimiter, WriteOperation};
+use serde_json::json;
+use std::fs;
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Size of the file read in the `read_file` group
+const LARGE_FILE_BYTES: usize = 4 * 1024 * 1024;
+
+/// Directories per level and files per directory of the walked tree
+const TREE_WIDTH: usize = 8;
+const TREE_DEPTH: usize = 3;
+const FILES_PER_DIR: usize = 10;
+
+/// A temporary directory and a server restricted to it, without a rate
+/// limit so the limiter does not throttle the measurements
+struct Fixture {
+    dir: TempDir,
+    server: McpServer,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        // The default temp dir prefix is hidden, which the policy rejects
+        let dir = tempfile::Builder::new().prefix("filejack-bench").tempdir().unwrap();
+        let policy = AccessPolicy::restricted(dir.path().to_path_buf());
+        let server = McpServer::with_rate_limiter(policy, RateLimiter::new(u32::MAX));
+        Self { dir, server }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    fn call(&self, id: u64, tool: &str, arguments: serde_json::Value) -> String {
+        json!({"jsonrpc": "2.0", "id": id, "method": "tools/call", "params": {"name": tool, "arguments": arguments}})
+            .to_string()
+    }
+
+    /// Fail early if `request` errors, so the error path is not what gets measured
+    fn check(&self, request: &str) {
+        let response = self.server.process_request(request);
+        assert!(!response.contains(r#""error""#), "{}", &response[..response.len().min(500)]);
+    }
+}
+
+fn build_tree(root: &Path, depth: usize) -> usize {
+    let mut files = 0;
+    for i in 0..FILES_PER_DIR {
+        fs::write(root.join(format!("file{}.txt", i)), "line\n").unwrap();
+        files += 1;
+    }
+    if depth > 0 {
+        for i in 0..TREE_WIDTH {
+            let sub = root.join(format!("dir{}", i));
+            fs::create_dir(&sub).unwrap();
+            files += build_tree(&sub, depth - 1);
+        }
+    }
+    files
+}
+
+fn dispatch(c: &mut Criterion) {
+    let fixture = Fixture::new();
+    fs::write(fixture.path("small.txt"), "hello").unwrap();
+    let exists = fixture.call(3, "file_exists", json!({"path": fixture.path("small.txt")}));
+    fixture.check(&exists);
+
+    let mut group = c.benchmark_group("dispatch");
+    group.bench_function("ping", |b| {
+        b.iter(|| fixture.server.process_request(black_box(r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#)))
+    });
+    group.bench_function("tools_list", |b| {
+        b.iter(|| fixture.server.process_request(black_box(r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#)))
+    });
+    group.bench_function("file_exists", |b| b.iter(|| fixture.server.process_request(black_box(&exists))));
+    group.bench_function("parse_error", |b| b.iter(|| fixture.server.process_request(black_box("{not json"))));
+    group.finish();
+}
+
+fn policy(c: &mut Criterion) {
+    let dir = tempfile::Builder::new().prefix("filejack-bench").tempdir().unwrap();
+    let nested = dir.path().join("a/b/c/d");
+    fs::create_dir_all(&nested).unwrap();
+    let file = nested.join("file.txt");
+    fs::write(&file, "x").unwrap();
+    let mut policy = AccessPolicy::restricted(dir.path().to_path_buf());
+    policy.denied_paths = vec![dir.path().join("a/secret")];
+    let missing = nested.join("new/deeper/file.txt");
+
+    let mut group = c.benchmark_group("policy");
+    group.bench_function("validate_read_cached", |b| b.iter(|| policy.validate_read(black_box(&file)).unwrap()));
+    group.bench_function("validate_read_uncached", |b| {
+        b.iter(|| {
+            policy.clear_cache();
+            policy.validate_read(black_box(&file)).unwrap()
+        })
+    });
+    group.bench_function("validate_write_new_file", |b| b.iter(|| policy.validate_write(black_box(&missing), WriteOperation::Write).unwrap()));
+    group.bench_function("reject_traversal", |b| {
+        let escape = nested.join("../../../../../../etc/passwd");
+        b.iter(|| policy.validate_read(black_box(&escape)).unwrap_err())
+    });
+    group.finish();
+}
+
+fn read_file(c: &mut Criterion) {
+    let fixture = Fixture::new();
+    let line = "The quick brown fox jumps over the lazy dog, \"quoted\" and \\escaped\\.\n";
+    let content: String = line.repeat(LARGE_FILE_BYTES / line.len());
+    fs::write(fixture.path("large.txt"), &content).unwrap();
+    let text = fixture.call(1, "read_file", json!({"path": fixture.path("large.txt")}));
+    let base64 = fixture.call(2, "read_file", json!({"path": fixture.path("large.txt"), "encoding": "base64"}));
+    fixture.check(&text);
+    fixture.check(&base64);
+
+    let mut group = c.benchmark_group("read_file");
+    group.throughput(Throughput::Bytes(content.len() as u64));
+    group.sample_size(20);
+    group.bench_function("text", |b| b.iter(|| fixture.server.process_request(black_box(&text))));
+    group.bench_function("base64", |b| b.iter(|| fixture.server.process_request(black_box(&base64))));
+    group.finish();
+}
+
+fn directory_walk(c: &mut Criterion) {
+    let fixture = Fixture::new();
+    let root = fixture.path("tree");
+    fs::create_dir(&root).unwrap();
+    let files = build_tree(&root, TREE_DEPTH);
+    let list = fixture.call(1, "list_directory", json!({"path": root, "recursive": true}));
+    let search = fixture.call(2, "search_files", json!({"path": root, "pattern": "file7.txt"}));
+    fixture.check(&list);
+    fixture.check(&search);
+
+    let mut group = c.benchmark_group("directory_walk");
+    group.throughput(Throughput::Elements(files as u64));
+    group.sample_size(20);
+    group.bench_function("list_directory_recursive", |b| b.iter(|| fixture.server.process_request(black_box(&list))));
+    group.bench_function("search_files", |b| b.iter(|| fixture.server.process_request(black_box(&search))));
+    group.finish();
+}
+
+criterion_group!(benches, dispatch, policy, read_file, directory_walk);
+criterion_main!(benches);
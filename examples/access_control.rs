@@ -148,7 +148,9 @@ fn example_config_file() {
         server: filejack::ServerConfig {
             name: "MyFileJackServer".to_string(),
             version: "1.0.0".to_string(),
+            ..Default::default()
         },
+        ..Default::default()
     };
     
     // Save to file (in real usage)
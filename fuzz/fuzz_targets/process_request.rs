@@ -0,0 +1,17 @@
+//! Arbitrary bytes as a JSON-RPC request: every input must be answered
+//! without panicking
+
+#![no_main]
+
+#[path = "../oracle.rs"]
+mod oracle;
+
+use libfuzzer_sys::fuzz_target;
+use oracle::Sandbox;
+use std::sync::OnceLock;
+
+static SANDBOX: OnceLock<Sandbox> = OnceLock::new();
+
+fuzz_target!(|data: &[u8]| {
+    SANDBOX.get_or_init(Sandbox::new).check_request(&String::from_utf8_lossy(data));
+});
@@ -0,0 +1,17 @@
+//! Arbitrary path strings through `AccessPolicy::validate_read` and
+//! `validate_write`: an accepted path must stay inside the allowed root
+
+#![no_main]
+
+#[path = "../oracle.rs"]
+mod oracle;
+
+use libfuzzer_sys::fuzz_target;
+use oracle::Sandbox;
+use std::sync::OnceLock;
+
+static SANDBOX: OnceLock<Sandbox> = OnceLock::new();
+
+fuzz_target!(|data: &[u8]| {
+    SANDBOX.get_or_init(Sandbox::new).check_path(&String::from_utf8_lossy(data));
+});
@@ -0,0 +1,111 @@
+//! Checks shared by the fuzz targets and `tests/fuzz_corpus.rs`, which
+//! replays the seed corpus on every `cargo test`.
+//!
+//! Inputs may contain `{root}`, replaced by the sandbox's allowed directory
+//! so the corpus can name paths inside and around it.
+
+#![allow(dead_code)]
+
+use filejack::{AccessPolicy, McpServer, RateLimiter, WriteOperation};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// A directory tree with files inside, beside, and (through a symlink)
+/// outside the allowed root, and a policy that allows only the root minus
+/// its `denied` subdirectory
+pub struct Sandbox {
+    _dir: tempfile::TempDir,
+    root: PathBuf,
+    denied: PathBuf,
+    policy: AccessPolicy,
+    read_only: AccessPolicy,
+    server: McpServer,
+}
+
+impl Sandbox {
+    pub fn new() -> Self {
+        let dir = tempfile::Builder::new().prefix("filejack-fuzz").tempdir().unwrap();
+        let base = dir.path().canonicalize().unwrap();
+        let root = base.join("allowed");
+        let denied = root.join("denied");
+        for sub in [root.join("sub"), denied.clone(), base.join("allowed-evil")] {
+            fs::create_dir_all(sub).unwrap();
+        }
+        fs::write(root.join("a.txt"), "inside\n").unwrap();
+        fs::write(root.join("sub/b.txt"), "inside\n").unwrap();
+        fs::write(root.join(".hidden"), "hidden\n").unwrap();
+        fs::write(denied.join("secret.txt"), "secret\n").unwrap();
+        fs::write(base.join("outside.txt"), "outside\n").unwrap();
+        fs::write(base.join("allowed-evil/x.txt"), "outside\n").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(base.join("outside.txt"), root.join("escape")).unwrap();
+
+        let mut policy = AccessPolicy::restricted(root.clone());
+        policy.denied_paths = vec![denied.clone()];
+        let mut read_only = policy.clone();
+        read_only.read_only = true;
+        let server = McpServer::with_rate_limiter(policy.clone(), RateLimiter::new(u32::MAX));
+        Self { _dir: dir, root, denied, policy, read_only, server }
+    }
+
+    fn expand(&self, input: &str) -> String {
+        input.replace("{root}", &self.root.to_string_lossy())
+    }
+
+    /// Validate `input` as a path for reading and writing, and panic if an
+    /// accepted path lies outside the root or inside the denied directory,
+    /// or if the read-only policy accepts a write
+    pub fn check_path(&self, input: &str) {
+        let input = self.expand(input);
+        let path = Path::new(&input);
+        let outcomes = [("read", self.policy.validate_read(path)), ("write", self.policy.validate_write(path, WriteOperation::Write))];
+        for (access, outcome) in outcomes {
+            if outcome.is_err() {
+                continue;
+            }
+            let Some(location) = real_location(path) else { continue };
+            assert!(
+                location.starts_with(&self.root) && !location.starts_with(&self.denied),
+                "{} of {:?} allowed, but it resolves to {:?}",
+                access,
+                input,
+                location
+            );
+        }
+        assert!(self.read_only.validate_write(path, WriteOperation::Write).is_err(), "read-only policy allowed writing {:?}", input);
+    }
+
+    /// Process `input` as a request, and panic unless the answer is empty
+    /// (a notification) or a JSON-RPC message other than the internal error
+    /// a caught panic becomes
+    pub fn check_request(&self, input: &str) {
+        let request = self.expand(input);
+        let response = self.server.process_request(&request);
+        if response.is_empty() {
+            return;
+        }
+        let response: serde_json::Value = serde_json::from_str(&response)
+            .unwrap_or_else(|e| panic!("invalid response to {:?}: {}", request, e));
+        assert_eq!(response["jsonrpc"], "2.0", "{}", request);
+        assert_ne!(response["error"]["code"], -32603, "request panicked: {}", request);
+    }
+}
+
+/// Where `path` points: its deepest existing ancestor with symlinks
+/// resolved, followed by the rest of the path applied lexically
+pub fn real_location(path: &Path) -> Option<PathBuf> {
+    let absolute = std::env::current_dir().ok()?.join(path);
+    let (mut location, rest) = absolute
+        .ancestors()
+        .find_map(|ancestor| Some((ancestor.canonicalize().ok()?, absolute.strip_prefix(ancestor).ok()?)))?;
+    for component in rest.components() {
+        match component {
+            Component::ParentDir => {
+                location.pop();
+            }
+            Component::Normal(name) => location.push(name),
+            _ => {}
+        }
+    }
+    Some(location)
+}
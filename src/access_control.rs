@@ -1,9 +1,36 @@
-use crate::error::{FileJackError, Result};
+use crate::error::{Denial, FileJackError, PolicyRule, Result, RuleCategory};
+use crate::overlay;
+use crate::vfs::{self, FileSystem, StdFileSystem};
+use lru::LruCache;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::{Path, PathBuf};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use unicode_normalization::{is_nfc, is_nfd, UnicodeNormalization as _};
+
+/// Default number of canonical paths whose rule checks the path cache remembers
+pub const DEFAULT_PATH_CACHE_CAPACITY: usize = 1024;
+
+/// Default lifetime of a cached rule check
+pub const DEFAULT_PATH_CACHE_TTL: Duration = Duration::from_secs(1);
+
+/// The kind of change a write makes to a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOperation {
+    /// Create, replace, or modify the contents
+    Write,
+    /// Add to the end of a file
+    Append,
+    /// Delete, or move away
+    Delete,
+}
 
 /// Access control policy for filesystem operations
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AccessPolicy {
     /// List of allowed directories (whitelist)
     #[serde(default)]
@@ -12,6 +39,24 @@ pub struct AccessPolicy {
     /// List of explicitly denied paths (blacklist, takes precedence)
     #[serde(default)]
     pub denied_paths: Vec<PathBuf>,
+
+    /// Files and directories that may only be appended to, such as logs:
+    /// they cannot be overwritten, moved, or deleted
+    #[serde(default)]
+    pub append_only_paths: Vec<PathBuf>,
+
+    /// Glob patterns of files that can be read but never written, moved, or
+    /// deleted, whatever the other rules say (e.g. `Cargo.lock`, `.github/**`).
+    /// Relative patterns match at any depth.
+    #[serde(default)]
+    pub protected_paths: Vec<String>,
+
+    /// Glob patterns, matched like `protected_paths`, of dotfiles that
+    /// agents must never write: SSH and GnuPG keys, Git configuration and
+    /// hooks, shell startup files, and `.env` files. They apply even when
+    /// `allow_hidden_files` is set; reads are left to the other rules.
+    #[serde(default = "default_sensitive_paths")]
+    pub sensitive_paths: Vec<String>,
     
     /// List of allowed file extensions (e.g., ["txt", "md", "json"])
     /// Empty means all extensions are allowed
@@ -30,13 +75,395 @@ pub struct AccessPolicy {
     #[serde(default)]
     pub allow_symlinks: bool,
     
-    /// Whether hidden files (starting with .) are allowed
+    /// Whether hidden files are allowed
     #[serde(default)]
     pub allow_hidden_files: bool,
+
+    /// What makes a file hidden for `allow_hidden_files`
+    #[serde(default)]
+    pub hidden_file_detection: HiddenFileDetection,
+
+    /// Whether NTFS alternate data streams such as `notes.txt:secret` may
+    /// be used on Windows. Their names hide the real extension, so only the
+    /// part before the stream name is checked against the extension rules.
+    #[serde(default)]
+    pub allow_alternate_streams: bool,
+
+    /// Unicode normalization form paths are brought to before lookup and
+    /// before they are compared with the configured paths and patterns
+    #[serde(default)]
+    pub unicode_normalization: UnicodeNormalization,
+
+    /// Offer `get_extended_attributes`, which reads a file's extended
+    /// attributes on Unix
+    #[serde(default)]
+    pub extended_attributes: bool,
+
+    /// Refuse files owned by any user but the one the server runs as, for
+    /// servers shared by several users. Applies on Unix, to files that exist.
+    #[serde(default)]
+    pub deny_files_not_owned_by_user: bool,
+
+    /// Whether FIFOs, device nodes, and pseudo-files such as those under
+    /// `/proc` may be read. They have no fixed size, so reads stop after
+    /// `special_file_max_bytes` and never wait for data.
+    #[serde(default)]
+    pub allow_special_files: bool,
+
+    /// Most bytes read from one special file; 0 refuses them all
+    #[serde(default = "default_special_file_max_bytes")]
+    pub special_file_max_bytes: u64,
+    
+    /// Permission bits of files FileJack creates, such as `0o600`, instead
+    /// of what the process umask leaves. Written as a number or an octal
+    /// string such as `"0600"`.
+    #[serde(default, deserialize_with = "deserialize_mode")]
+    pub file_mode: Option<u32>,
+
+    /// Permission bits of directories FileJack creates, such as `0o700`
+    #[serde(default, deserialize_with = "deserialize_mode")]
+    pub directory_mode: Option<u32>,
     
     /// Read-only mode (no write operations allowed)
     #[serde(default)]
     pub read_only: bool,
+
+    /// Honor `.filejack.json` overlays that tighten the rules for a subtree
+    #[serde(default = "default_directory_overlays")]
+    pub directory_overlays: bool,
+
+    /// Present zip and tar archives as read-only directories that tools can
+    /// read, list, and search inside
+    #[serde(default)]
+    pub browse_archives: bool,
+
+    /// Read every file back after writing it and fail the write unless it
+    /// holds what was written, for file systems that lose writes silently
+    #[serde(default)]
+    pub verify_writes: bool,
+
+    /// How the allowed roots are written in tool results and error
+    /// messages, so they do not reveal where files live on the host
+    #[serde(default)]
+    pub redact_roots: RootRedaction,
+
+    /// Names for rules, keyed by the ids derived from where they are
+    /// configured (see [`Self::derived_rule_ids`]), reported in denials in
+    /// place of the derived ids
+    #[serde(default)]
+    pub rule_ids: BTreeMap<String, String>,
+
+    /// Cache of canonicalized roots and recently validated paths
+    #[serde(skip)]
+    cache: PolicyCache,
+}
+
+fn default_directory_overlays() -> bool {
+    true
+}
+
+/// Files whose modification would let an agent run code or take
+/// credentials beyond the session: keys and agent configuration, Git hooks
+/// and configuration, shell startup files, and environment files
+pub const DEFAULT_SENSITIVE_PATHS: &[&str] = &[
+    ".ssh", ".ssh/**", ".gnupg", ".gnupg/**", ".git/config", ".git/hooks", ".git/hooks/**",
+    ".bashrc", ".bash_profile", ".bash_login", ".bash_logout", ".profile", ".zshrc", ".zshenv", ".zprofile",
+    ".env*",
+];
+
+fn default_sensitive_paths() -> Vec<String> {
+    DEFAULT_SENSITIVE_PATHS.iter().map(|pattern| pattern.to_string()).collect()
+}
+
+fn default_special_file_max_bytes() -> u64 {
+    DEFAULT_SPECIAL_FILE_MAX_BYTES
+}
+
+/// Permission bits given as a number, or as an octal string such as
+/// `"0600"` or `"0o600"` since JSON has no octal literals
+fn deserialize_mode<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<u32>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Mode {
+        Number(u32),
+        Octal(String),
+    }
+
+    let mode = match Option::<Mode>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(Mode::Number(mode)) => mode,
+        Some(Mode::Octal(text)) => {
+            let digits = text.strip_prefix("0o").unwrap_or(&text);
+            u32::from_str_radix(digits, 8)
+                .map_err(|_| serde::de::Error::custom(format!("invalid octal mode {:?}", text)))?
+        }
+    };
+    if mode > 0o7777 {
+        return Err(serde::de::Error::custom(format!("mode {:o} has bits beyond 0o7777", mode)));
+    }
+    Ok(Some(mode))
+}
+
+/// The rules [`AccessPolicy::trace`] checked for a path and what they decided
+#[derive(Debug)]
+pub struct AccessTrace {
+    /// The path as given
+    pub path: PathBuf,
+    /// The path the rules were checked against, or `None` if it could not
+    /// be resolved, in which case no rules were checked
+    pub resolved: Option<PathBuf>,
+    pub steps: Vec<TraceStep>,
+    /// What validation decides: the resolved path, or why access is refused
+    pub decision: Result<PathBuf>,
+}
+
+/// One rule in an [`AccessTrace`]
+#[derive(Debug)]
+pub struct TraceStep {
+    /// The setting the rule comes from, such as `denied_paths`
+    pub rule: &'static str,
+    pub outcome: Result<()>,
+}
+
+/// What `allow_hidden_files` treats as a hidden file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HiddenFileDetection {
+    /// The host's convention: a leading dot on Unix, the hidden attribute on
+    /// Windows, and either on macOS
+    #[default]
+    Platform,
+    /// A name starting with `.`
+    DotPrefix,
+    /// The file system's hidden flag, `FILE_ATTRIBUTE_HIDDEN` on Windows or
+    /// `UF_HIDDEN` on macOS; files that do not exist yet have none
+    Attribute,
+    /// Either of them
+    Both,
+}
+
+impl HiddenFileDetection {
+    /// Whether `path` on `fs` is hidden
+    pub fn is_hidden(self, fs: &dyn FileSystem, path: &Path) -> bool {
+        let dot_prefix = || path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        let attribute = || fs.metadata(path).is_ok_and(|metadata| metadata.hidden);
+        match self.for_host() {
+            HiddenFileDetection::DotPrefix => dot_prefix(),
+            HiddenFileDetection::Attribute => attribute(),
+            _ => dot_prefix() || attribute(),
+        }
+    }
+
+    /// `Platform` replaced by what it means on this host
+    fn for_host(self) -> Self {
+        match self {
+            HiddenFileDetection::Platform if cfg!(windows) => HiddenFileDetection::Attribute,
+            HiddenFileDetection::Platform if cfg!(target_os = "macos") => HiddenFileDetection::Both,
+            HiddenFileDetection::Platform => HiddenFileDetection::DotPrefix,
+            other => other,
+        }
+    }
+}
+
+/// Which Unicode normalization form `unicode_normalization` brings paths to.
+///
+/// The same name can be written with precomposed characters (NFC, as most
+/// clients send it) or decomposed ones (NFD, as HFS+ stores it). Without
+/// normalization the two are different paths.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UnicodeNormalization {
+    /// Compare and look up paths exactly as written
+    #[default]
+    None,
+    /// Composed: `é` as one code point
+    Nfc,
+    /// Decomposed: `é` as `e` and a combining accent
+    Nfd,
+}
+
+impl UnicodeNormalization {
+    /// `path` in this form. Paths that are not UTF-8 are left as they are.
+    pub fn apply(self, path: &Path) -> Cow<'_, Path> {
+        match path.to_str().map(|text| self.apply_str(text)) {
+            Some(Cow::Owned(text)) => Cow::Owned(PathBuf::from(text)),
+            _ => Cow::Borrowed(path),
+        }
+    }
+
+    /// `text` in this form
+    pub fn apply_str(self, text: &str) -> Cow<'_, str> {
+        match self {
+            UnicodeNormalization::Nfc if !is_nfc(text) => Cow::Owned(text.nfc().collect()),
+            UnicodeNormalization::Nfd if !is_nfd(text) => Cow::Owned(text.nfd().collect()),
+            _ => Cow::Borrowed(text),
+        }
+    }
+}
+
+/// How `redact_roots` writes the allowed roots
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RootRedaction {
+    /// As they are
+    #[default]
+    Off,
+    /// Left out, so paths under a root read as relative paths
+    Strip,
+    /// As `@` and the root's directory name, which tools also accept
+    Alias,
+}
+
+/// Default for `special_file_max_bytes` (1 MiB)
+pub const DEFAULT_SPECIAL_FILE_MAX_BYTES: u64 = 1024 * 1024;
+
+impl Default for AccessPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_paths: vec![],
+            denied_paths: vec![],
+            append_only_paths: vec![],
+            protected_paths: vec![],
+            sensitive_paths: default_sensitive_paths(),
+            allowed_extensions: vec![],
+            denied_extensions: vec![],
+            max_file_size: 0,
+            allow_symlinks: false,
+            allow_hidden_files: false,
+            hidden_file_detection: HiddenFileDetection::Platform,
+            allow_alternate_streams: false,
+            unicode_normalization: UnicodeNormalization::None,
+            deny_files_not_owned_by_user: false,
+            extended_attributes: false,
+            allow_special_files: false,
+            special_file_max_bytes: DEFAULT_SPECIAL_FILE_MAX_BYTES,
+            file_mode: None,
+            directory_mode: None,
+            read_only: false,
+            directory_overlays: default_directory_overlays(),
+            browse_archives: false,
+            verify_writes: false,
+            redact_roots: RootRedaction::Off,
+            rule_ids: BTreeMap::new(),
+            cache: PolicyCache::default(),
+        }
+    }
+}
+
+/// Canonical forms of the configured allowed/denied paths, together with the
+/// configured values they were computed from so that edits to the public
+/// fields are detected.
+#[derive(Debug)]
+struct CanonicalRoots {
+    normalization: UnicodeNormalization,
+    allowed_source: Vec<PathBuf>,
+    denied_source: Vec<PathBuf>,
+    append_only_source: Vec<PathBuf>,
+    protected_source: Vec<String>,
+    sensitive_source: Vec<String>,
+    allowed: Vec<PathBuf>,
+    /// Each denied and append-only path that exists, with its index in the
+    /// configured list
+    denied: Vec<(usize, PathBuf)>,
+    append_only: Vec<(usize, PathBuf)>,
+    /// Compiled `protected_paths`, or the index of the first invalid one and
+    /// its message
+    protected: std::result::Result<Vec<glob::Pattern>, (usize, String)>,
+    /// Compiled `sensitive_paths`, likewise
+    sensitive: std::result::Result<Vec<glob::Pattern>, (usize, String)>,
+}
+
+/// A canonical path that passed the path-string rules, and the rules it
+/// passed under
+#[derive(Debug)]
+struct CachedValidation {
+    validated_at: Instant,
+    rules: u64,
+}
+
+/// Per-policy cache of canonical roots and of which canonical paths passed
+/// the rules that depend only on the path string. Nothing that can change on
+/// disk, such as symlinks, owners or overlays, is cached.
+///
+/// Clones start with an empty cache so that a cloned and then modified policy
+/// never observes results validated under the original rules.
+struct PolicyCache {
+    roots: Mutex<Option<Arc<CanonicalRoots>>>,
+    validated: Option<Mutex<LruCache<PathBuf, CachedValidation>>>,
+    ttl: Duration,
+}
+
+impl PolicyCache {
+    fn new(capacity: usize, ttl: Duration) -> Self {
+        let validated = match NonZeroUsize::new(capacity) {
+            Some(capacity) if !ttl.is_zero() => Some(Mutex::new(LruCache::new(capacity))),
+            _ => None,
+        };
+        Self {
+            roots: Mutex::new(None),
+            validated,
+            ttl,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.validated.as_ref()
+            .map(|cache| cache.lock().map(|c| c.cap().get()).unwrap_or(0))
+            .unwrap_or(0)
+    }
+
+    fn passed(&self, canonical: &Path, rules: u64) -> bool {
+        let Some(Ok(mut cache)) = self.validated.as_ref().map(|c| c.lock()) else {
+            return false;
+        };
+        match cache.get(canonical) {
+            Some(entry) if entry.rules == rules && entry.validated_at.elapsed() < self.ttl => true,
+            Some(_) => {
+                cache.pop(canonical);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&self, canonical: &Path, rules: u64) {
+        if let Some(Ok(mut cache)) = self.validated.as_ref().map(|c| c.lock()) {
+            cache.put(canonical.to_path_buf(), CachedValidation {
+                validated_at: Instant::now(),
+                rules,
+            });
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut roots) = self.roots.lock() {
+            *roots = None;
+        }
+        if let Some(Ok(mut cache)) = self.validated.as_ref().map(|c| c.lock()) {
+            cache.clear();
+        }
+    }
+}
+
+impl Default for PolicyCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_PATH_CACHE_CAPACITY, DEFAULT_PATH_CACHE_TTL)
+    }
+}
+
+impl Clone for PolicyCache {
+    fn clone(&self) -> Self {
+        Self::new(self.capacity(), self.ttl)
+    }
+}
+
+impl std::fmt::Debug for PolicyCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PolicyCache")
+            .field("capacity", &self.capacity())
+            .field("ttl", &self.ttl)
+            .finish()
+    }
 }
 
 impl AccessPolicy {
@@ -45,12 +472,30 @@ impl AccessPolicy {
         Self {
             allowed_paths: vec![],
             denied_paths: vec![],
+            append_only_paths: vec![],
+            protected_paths: vec![],
+            sensitive_paths: vec![],
             allowed_extensions: vec![],
             denied_extensions: vec![],
             max_file_size: 0,
             allow_symlinks: true,
             allow_hidden_files: true,
+            hidden_file_detection: HiddenFileDetection::Platform,
+            allow_alternate_streams: false,
+            unicode_normalization: UnicodeNormalization::None,
+            deny_files_not_owned_by_user: false,
+            extended_attributes: false,
+            allow_special_files: false,
+            special_file_max_bytes: DEFAULT_SPECIAL_FILE_MAX_BYTES,
+            file_mode: None,
+            directory_mode: None,
             read_only: false,
+            directory_overlays: true,
+            browse_archives: false,
+            verify_writes: false,
+            redact_roots: RootRedaction::Off,
+            rule_ids: BTreeMap::new(),
+            cache: PolicyCache::default(),
         }
     }
 
@@ -59,12 +504,30 @@ impl AccessPolicy {
         Self {
             allowed_paths: vec![allowed_path],
             denied_paths: vec![],
+            append_only_paths: vec![],
+            protected_paths: vec![],
+            sensitive_paths: default_sensitive_paths(),
             allowed_extensions: vec![],
             denied_extensions: vec![],
             max_file_size: 10 * 1024 * 1024, // 10MB default
             allow_symlinks: false,
             allow_hidden_files: false,
+            hidden_file_detection: HiddenFileDetection::Platform,
+            allow_alternate_streams: false,
+            unicode_normalization: UnicodeNormalization::None,
+            deny_files_not_owned_by_user: false,
+            extended_attributes: false,
+            allow_special_files: false,
+            special_file_max_bytes: DEFAULT_SPECIAL_FILE_MAX_BYTES,
+            file_mode: Some(0o600),
+            directory_mode: Some(0o700),
             read_only: false,
+            directory_overlays: true,
+            browse_archives: false,
+            verify_writes: false,
+            redact_roots: RootRedaction::Off,
+            rule_ids: BTreeMap::new(),
+            cache: PolicyCache::default(),
         }
     }
 
@@ -75,43 +538,183 @@ impl AccessPolicy {
         policy
     }
 
-    /// Validate a path for read access
+    /// Configure the path cache.
+    ///
+    /// Canonical paths that passed the allowed, denied and extension rules
+    /// are remembered for `ttl`, up to `capacity` entries. Every read is still
+    /// canonicalized and checked for symlinks, owners, hidden files and
+    /// overlays. A capacity or TTL of zero disables the cache.
+    pub fn with_path_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.cache = PolicyCache::new(capacity, ttl);
+        self
+    }
+
+    /// Drop all cached canonicalizations and validations
+    pub fn clear_cache(&self) {
+        self.cache.clear();
+    }
+
+    /// Validate a path for read access on the host file system
     pub fn validate_read(&self, path: &Path) -> Result<PathBuf> {
-        let canonical = self.canonicalize_path(path)?;
-        
-        // Check if path is denied
-        self.check_denied_paths(&canonical)?;
-        
-        // Check if path is in allowed directories
-        self.check_allowed_paths(&canonical)?;
-        
-        // Check file extension
-        self.check_extension(&canonical)?;
+        self.validate_read_on(&StdFileSystem, path)
+    }
+
+    /// Validate a path for read access on `fs`. A policy's caches assume it
+    /// is always checked against the same file system.
+    pub fn validate_read_on(&self, fs: &dyn FileSystem, path: &Path) -> Result<PathBuf> {
+        let path: &Path = &self.lookup_path(path);
+        let canonical = self.canonicalize_path(fs, path)?;
+
+        let rules = self.rules_fingerprint();
+        if self.cache.passed(&canonical, rules) {
+            // The name passed, but an archive browsed as a directory is
+            // looked up on disk
+            self.check_containing_archive(fs, &canonical)?;
+        } else {
+            // Check if path is denied
+            self.check_denied_paths(fs, &canonical)?;
+
+            // Check if path is in allowed directories
+            self.check_allowed_paths(fs, &canonical)?;
+
+            // Check file extension
+            self.check_extension(fs, &canonical)?;
+
+            // Directories skip the extension check, so only remember paths
+            // whose name passes it too
+            if self.check_file_extension(&canonical).is_ok() {
+                self.cache.insert(&canonical, rules);
+            }
+        }
         
         // Check hidden files
-        self.check_hidden_files(&canonical)?;
+        self.check_hidden_files(fs, &canonical)?;
+
+        // Check alternate data streams
+        self.check_alternate_streams(path)?;
+
+        // Check who owns the file
+        self.check_owner(fs, &canonical)?;
         
         // Check symlinks
-        self.check_symlinks(path, &canonical)?;
+        self.check_symlinks(fs, path, &canonical)?;
+
+        // Check per-directory overlays
+        self.check_overlays(fs, &canonical, false)?;
         
         Ok(canonical)
     }
 
-    /// Validate a path for write access
-    pub fn validate_write(&self, path: &Path) -> Result<PathBuf> {
+    /// Hash of every rule whose result the path cache holds, so cached
+    /// results are not reused after the public fields are edited in place
+    fn rules_fingerprint(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.allowed_paths.hash(&mut hasher);
+        self.denied_paths.hash(&mut hasher);
+        self.allowed_extensions.hash(&mut hasher);
+        self.denied_extensions.hash(&mut hasher);
+        self.unicode_normalization.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Validate a path for a write of kind `operation` on the host file system
+    pub fn validate_write(&self, path: &Path, operation: WriteOperation) -> Result<PathBuf> {
+        self.validate_write_on(&StdFileSystem, path, operation)
+    }
+
+    /// Validate a path for a write of kind `operation` on `fs`
+    pub fn validate_write_on(&self, fs: &dyn FileSystem, path: &Path, operation: WriteOperation) -> Result<PathBuf> {
+        let path: &Path = &self.lookup_path(path);
         // Check read-only mode
-        if self.read_only {
-            return Err(FileJackError::PermissionDenied(
-                "Write operations are disabled in read-only mode".to_string()
-            ));
+        self.check_read_only()?;
+
+        let full_canonical = self.validate_missing_on(fs, path, true)?;
+        self.check_protected_paths(fs, &full_canonical)?;
+        self.check_sensitive_paths(fs, &full_canonical)?;
+        if operation != WriteOperation::Append {
+            self.check_append_only_paths(fs, &full_canonical)?;
+        }
+        Ok(path.to_path_buf())
+    }
+
+    /// Validate a path for reading that may not exist, such as a file only
+    /// found in version control history. Returns the canonical path.
+    pub fn validate_read_or_missing(&self, path: &Path) -> Result<PathBuf> {
+        match self.validate_read(path) {
+            Err(FileJackError::FileNotFound(_)) => self.validate_missing_on(&StdFileSystem, path, false),
+            result => result,
         }
+    }
+
+    /// Every rule checked for a read, or a write if `write`, of `path` on
+    /// the host file system and what each decided, with the overall decision.
+    /// Unlike validation it does not stop at the first denial.
+    pub fn trace(&self, path: &Path, write: bool) -> AccessTrace {
+        self.trace_on(&StdFileSystem, path, write)
+    }
+
+    /// [`Self::trace`] on `fs`
+    pub fn trace_on(&self, fs: &dyn FileSystem, path: &Path, write: bool) -> AccessTrace {
+        let path: &Path = &self.lookup_path(path);
+        let decision = if write {
+            self.validate_write_on(fs, path, WriteOperation::Write)
+        } else {
+            self.validate_read_on(fs, path)
+        };
+        let resolved = match self.canonicalize_path(fs, path) {
+            Err(FileJackError::FileNotFound(_)) if write => self.resolve_missing(fs, path),
+            resolved => resolved,
+        };
+        let canonical = match resolved {
+            Ok(canonical) => canonical,
+            Err(_) => return AccessTrace { path: path.to_path_buf(), resolved: None, steps: vec![], decision },
+        };
 
-        // For write operations, we need to handle non-existent files
-        // Find the first existing ancestor directory
+        let mut steps = Vec::new();
+        let mut step = |rule: &'static str, outcome: Result<()>| steps.push(TraceStep { rule, outcome });
+        if write {
+            step("read_only", self.check_read_only());
+        }
+        step("denied_paths", self.check_denied_paths(fs, &canonical));
+        step("allowed_paths", self.check_allowed_paths(fs, &canonical));
+        // Writes check the name they were given, which may not exist yet
+        let named = if write { path } else { &canonical };
+        step("extensions", self.check_extension(fs, named));
+        step("allow_hidden_files", self.check_hidden_files(fs, named));
+        step("allow_alternate_streams", self.check_alternate_streams(path));
+        step("deny_files_not_owned_by_user", self.check_owner(fs, &canonical));
+        if !write {
+            step("allow_symlinks", self.check_symlinks(fs, path, &canonical));
+        }
+        let overlay_file = if write { self.check_overlay_file(path) } else { Ok(()) };
+        step("directory_overlays", overlay_file.and_then(|()| self.check_overlays(fs, &canonical, write)));
+        if write {
+            step("protected_paths", self.check_protected_paths(fs, &canonical));
+            step("sensitive_paths", self.check_sensitive_paths(fs, &canonical));
+            step("append_only_paths", self.check_append_only_paths(fs, &canonical));
+        }
+        AccessTrace { path: path.to_path_buf(), resolved: Some(canonical), steps, decision }
+    }
+
+    /// `path` made absolute through its first existing ancestor, for a path
+    /// whose trailing components may not exist yet
+    fn resolve_missing(&self, fs: &dyn FileSystem, path: &Path) -> Result<PathBuf> {
+        // Find the first existing ancestor directory. A dangling symlink
+        // counts as existing: writing through it would create its target.
         let mut path_to_check = path.to_path_buf();
         let mut non_existent_parts = Vec::new();
         
-        while !path_to_check.exists() {
+        while fs.symlink_metadata(&path_to_check).is_err() {
+            // `..` after a missing directory cannot be resolved against the
+            // file system, and dropping it would check the wrong path
+            if path_to_check.ends_with("..") {
+                return Err(FileJackError::InvalidPath(format!(
+                    "{} leaves a directory that does not exist",
+                    path.display()
+                )));
+            }
             if let Some(file_name) = path_to_check.file_name() {
                 non_existent_parts.push(file_name.to_os_string());
             }
@@ -123,8 +726,18 @@ impl AccessPolicy {
             };
         }
 
+        // Every existing part of the path must be a real directory, or a
+        // link the policy allows, before anything beneath it is created
+        self.check_ancestor_symlinks(fs, &path_to_check)?;
+
         // Canonicalize the existing ancestor
-        let canonical = self.canonicalize_path(&path_to_check)?;
+        let canonical = match self.canonicalize_path(fs, &path_to_check) {
+            Err(FileJackError::FileNotFound(_)) => return Err(FileJackError::InvalidPath(format!(
+                "{} is a symbolic link to a path that does not exist",
+                path_to_check.display()
+            ))),
+            canonical => canonical?,
+        };
         
         // Reconstruct the full path by appending non-existent parts
         let mut full_canonical = canonical;
@@ -132,34 +745,115 @@ impl AccessPolicy {
         for part in non_existent_parts {
             full_canonical.push(part);
         }
-        
+        Ok(full_canonical)
+    }
+
+    /// Validate a path whose trailing components may not exist yet, by
+    /// checking the rules against its first existing ancestor
+    fn validate_missing_on(&self, fs: &dyn FileSystem, path: &Path, write: bool) -> Result<PathBuf> {
+        let path: &Path = &self.lookup_path(path);
+        let full_canonical = self.resolve_missing(fs, path)?;
+
         // Check if reconstructed path is denied
-        self.check_denied_paths(&full_canonical)?;
+        self.check_denied_paths(fs, &full_canonical)?;
         
         // Check if reconstructed path is in allowed directories
-        self.check_allowed_paths(&full_canonical)?;
+        self.check_allowed_paths(fs, &full_canonical)?;
         
         // Check file extension on the original path (which has the filename)
-        self.check_extension(path)?;
+        self.check_extension(fs, path)?;
         
         // Check hidden files on the original path
-        self.check_hidden_files(path)?;
-        
-        Ok(path.to_path_buf())
+        self.check_hidden_files(fs, path)?;
+
+        // Check alternate data streams on the original path
+        self.check_alternate_streams(path)?;
+
+        // Check who owns the file, if it exists
+        self.check_owner(fs, &full_canonical)?;
+
+        // Check per-directory overlays; the overlay files themselves are off limits
+        if write {
+            self.check_overlay_file(path)?;
+        }
+        self.check_overlays(fs, &full_canonical, write)?;
+        Ok(full_canonical)
     }
 
     /// Validate file size
     pub fn validate_file_size(&self, size: u64) -> Result<()> {
         if self.max_file_size > 0 && size > self.max_file_size {
-            return Err(FileJackError::PermissionDenied(
-                format!("File size {} exceeds maximum allowed size {}", size, self.max_file_size)
+            return Err(self.deny(
+                "max_file_size".to_string(),
+                RuleCategory::FileSize,
+                format!("File size {} exceeds maximum allowed size {}", size, self.max_file_size),
             ));
         }
         Ok(())
     }
 
-    fn canonicalize_path(&self, path: &Path) -> Result<PathBuf> {
-        path.canonicalize().map_err(|e| {
+    /// Ids of every rule this policy could deny with, as derived from where
+    /// each is configured: the list and index for entries of
+    /// `denied_paths`, `append_only_paths`, `protected_paths`,
+    /// `sensitive_paths`, and `denied_extensions`, and the setting's name for
+    /// the rest
+    pub fn derived_rule_ids(&self) -> Vec<String> {
+        let settings = [
+            "read_only", "allowed_paths", "allowed_extensions", "allow_hidden_files", "allow_alternate_streams",
+            "deny_files_not_owned_by_user", "allow_symlinks", "max_file_size", "directory_overlays",
+        ];
+        let lists = [
+            ("denied_paths", self.denied_paths.len()),
+            ("append_only_paths", self.append_only_paths.len()),
+            ("protected_paths", self.protected_paths.len()),
+            ("sensitive_paths", self.sensitive_paths.len()),
+            ("denied_extensions", self.denied_extensions.len()),
+        ];
+        settings.iter()
+            .map(|setting| setting.to_string())
+            .chain(lists.iter().flat_map(|(list, len)| (0..*len).map(move |index| format!("{}[{}]", list, index))))
+            .collect()
+    }
+
+    /// A denial by the rule with the derived id `id`, reported under the
+    /// name `rule_ids` gives it, if any
+    pub(crate) fn deny(&self, id: String, category: RuleCategory, message: String) -> FileJackError {
+        let id = self.rule_ids.get(&id).cloned().unwrap_or(id);
+        FileJackError::PermissionDenied(Denial::by(PolicyRule::new(id, category), message))
+    }
+
+    /// Refuse, unless `allow_symlinks`, a symlink at `existing` or any of its
+    /// ancestors that lies inside an allowed path, since it could send a
+    /// write somewhere the policy never checked. Links at or above an allowed
+    /// path are the administrator's own; with no allowed paths, no link can
+    /// lead outside them.
+    fn check_ancestor_symlinks(&self, fs: &dyn FileSystem, existing: &Path) -> Result<()> {
+        if self.allow_symlinks || self.allowed_paths.is_empty() {
+            return Ok(());
+        }
+        let roots = self.canonical_roots(fs);
+        for link in existing.ancestors().filter(|ancestor| fs.symlink_metadata(ancestor).is_ok_and(|m| m.is_symlink())) {
+            // Where the link itself is, with the links above it resolved
+            let (Some(parent), Some(name)) = (link.parent(), link.file_name()) else {
+                continue;
+            };
+            let Ok(location) = fs.canonicalize(parent).map(|parent| parent.join(name)) else {
+                continue;
+            };
+            let location = self.unicode_normalization.apply(&location);
+            if roots.allowed.iter().any(|root| location.starts_with(root) && *location != **root) {
+                return Err(self.deny(
+                    "allow_symlinks".to_string(),
+                    RuleCategory::Symlink,
+                    format!("{} is a symbolic link, which is not allowed", link.display()),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn canonicalize_path(&self, fs: &dyn FileSystem, path: &Path) -> Result<PathBuf> {
+        fs.canonicalize(path).map_err(|e| {
             if e.kind() == std::io::ErrorKind::NotFound {
                 FileJackError::FileNotFound(path.display().to_string())
             } else {
@@ -168,98 +862,322 @@ impl AccessPolicy {
         })
     }
 
-    fn check_denied_paths(&self, canonical: &Path) -> Result<()> {
-        for denied in &self.denied_paths {
-            if let Ok(denied_canonical) = denied.canonicalize() {
-                if canonical.starts_with(&denied_canonical) || canonical == denied_canonical {
-                    return Err(FileJackError::PermissionDenied(
-                        format!("Access to {} is explicitly denied", canonical.display())
-                    ));
-                }
+    /// Canonicalized allowed/denied roots, recomputed only when the configured
+    /// paths change. Roots that cannot be canonicalized (e.g. do not exist) are
+    /// left out, matching the behavior of checking them individually.
+    fn canonical_roots(&self, fs: &dyn FileSystem) -> Arc<CanonicalRoots> {
+        let mut roots = match self.cache.roots.lock() {
+            Ok(roots) => roots,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if let Some(cached) = roots.as_ref() {
+            if cached.normalization == self.unicode_normalization
+                && cached.allowed_source == self.allowed_paths
+                && cached.denied_source == self.denied_paths
+                && cached.append_only_source == self.append_only_paths
+                && cached.protected_source == self.protected_paths
+                && cached.sensitive_source == self.sensitive_paths
+            {
+                return Arc::clone(cached);
+            }
+        }
+
+        // Configured paths are looked up, and their canonical forms
+        // compared, in the normalization form too
+        let form = self.unicode_normalization;
+        let lookup = |paths: &[PathBuf]| -> Vec<PathBuf> {
+            paths.iter().map(|path| form.apply(path).into_owned()).collect()
+        };
+        let normalized = |roots: Vec<(usize, PathBuf)>| -> Vec<(usize, PathBuf)> {
+            roots.into_iter().map(|(index, root)| (index, form.apply(&root).into_owned())).collect()
+        };
+        let computed = Arc::new(CanonicalRoots {
+            normalization: form,
+            allowed_source: self.allowed_paths.clone(),
+            denied_source: self.denied_paths.clone(),
+            append_only_source: self.append_only_paths.clone(),
+            protected_source: self.protected_paths.clone(),
+            sensitive_source: self.sensitive_paths.clone(),
+            allowed: lookup(&self.allowed_paths).iter()
+                .filter_map(|p| fs.canonicalize(p).ok())
+                .map(|root| form.apply(&root).into_owned())
+                .collect(),
+            denied: normalized(canonical_indexed(fs, &lookup(&self.denied_paths))),
+            append_only: normalized(canonical_indexed(fs, &lookup(&self.append_only_paths))),
+            protected: compile_patterns(form, "Protected", &self.protected_paths),
+            sensitive: compile_patterns(form, "Sensitive", &self.sensitive_paths),
+        });
+        *roots = Some(Arc::clone(&computed));
+        computed
+    }
+
+    fn check_denied_paths(&self, fs: &dyn FileSystem, canonical: &Path) -> Result<()> {
+        let compared = self.unicode_normalization.apply(canonical);
+        for (index, denied_canonical) in &self.canonical_roots(fs).denied {
+            if compared.starts_with(denied_canonical) {
+                return Err(self.deny(
+                    format!("denied_paths[{}]", index),
+                    RuleCategory::DeniedPath,
+                    format!("Access to {} is explicitly denied", canonical.display()),
+                ));
             }
         }
         Ok(())
     }
 
-    fn check_allowed_paths(&self, canonical: &Path) -> Result<()> {
+    /// Check that deleting the existing `canonical` path would not remove a
+    /// protected or append-only file, for entries inside a directory being
+    /// removed. The other rules were checked on the directory itself.
+    pub(crate) fn check_removable_on(&self, fs: &dyn FileSystem, canonical: &Path) -> Result<()> {
+        self.check_protected_paths(fs, canonical)?;
+        self.check_sensitive_paths(fs, canonical)?;
+        self.check_append_only_paths(fs, canonical)
+    }
+
+    /// Whether any rule keeps files from being deleted
+    pub(crate) fn keeps_files(&self) -> bool {
+        !self.protected_paths.is_empty() || !self.sensitive_paths.is_empty() || !self.append_only_paths.is_empty()
+    }
+
+    /// Refuse `canonical` if it, or any trailing part of it, matches a
+    /// protected pattern. An invalid pattern refuses every write, so a typo
+    /// never leaves files unprotected.
+    fn check_protected_paths(&self, fs: &dyn FileSystem, canonical: &Path) -> Result<()> {
+        if self.protected_paths.is_empty() {
+            return Ok(());
+        }
+        let roots = self.canonical_roots(fs);
+        let patterns = roots.protected.as_ref().map_err(|(index, message)| {
+            self.deny(format!("protected_paths[{}]", index), RuleCategory::Protected, message.clone())
+        })?;
+        match matching_pattern(patterns, &self.unicode_normalization.apply(canonical)) {
+            Some(index) => Err(self.deny(
+                format!("protected_paths[{}]", index),
+                RuleCategory::Protected,
+                format!("{} is a protected file and cannot be modified or deleted", canonical.display()),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Refuse `canonical` if it matches a sensitive pattern, as
+    /// `check_protected_paths` does for protected ones
+    fn check_sensitive_paths(&self, fs: &dyn FileSystem, canonical: &Path) -> Result<()> {
+        if self.sensitive_paths.is_empty() {
+            return Ok(());
+        }
+        let roots = self.canonical_roots(fs);
+        let patterns = roots.sensitive.as_ref().map_err(|(index, message)| {
+            self.deny(format!("sensitive_paths[{}]", index), RuleCategory::Sensitive, message.clone())
+        })?;
+        match matching_pattern(patterns, &self.unicode_normalization.apply(canonical)) {
+            Some(index) => Err(self.deny(
+                format!("sensitive_paths[{}]", index),
+                RuleCategory::Sensitive,
+                format!("{} is a sensitive file that agents may not modify", canonical.display()),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn check_append_only_paths(&self, fs: &dyn FileSystem, canonical: &Path) -> Result<()> {
+        let compared = self.unicode_normalization.apply(canonical);
+        for (index, append_only) in &self.canonical_roots(fs).append_only {
+            if compared.starts_with(append_only) {
+                return Err(self.deny(
+                    format!("append_only_paths[{}]", index),
+                    RuleCategory::AppendOnly,
+                    format!("{} is append-only; use append_file", canonical.display()),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_allowed_paths(&self, fs: &dyn FileSystem, canonical: &Path) -> Result<()> {
         // If allowed_paths is empty, all paths are allowed (unless denied)
         if self.allowed_paths.is_empty() {
             return Ok(());
         }
 
-        for allowed in &self.allowed_paths {
-            if let Ok(allowed_canonical) = allowed.canonicalize() {
-                if canonical.starts_with(&allowed_canonical) || canonical == allowed_canonical {
-                    return Ok(());
-                }
+        let compared = self.unicode_normalization.apply(canonical);
+        for allowed_canonical in &self.canonical_roots(fs).allowed {
+            if compared.starts_with(allowed_canonical) {
+                return Ok(());
             }
         }
 
-        Err(FileJackError::PermissionDenied(
-            format!("Path {} is not in any allowed directory", canonical.display())
+        Err(self.deny(
+            "allowed_paths".to_string(),
+            RuleCategory::AllowedPaths,
+            format!("Path {} is not in any allowed directory", canonical.display()),
         ))
     }
 
-    fn check_extension(&self, path: &Path) -> Result<()> {
+    fn check_extension(&self, fs: &dyn FileSystem, path: &Path) -> Result<()> {
+        self.check_containing_archive(fs, path)?;
+
         // Skip extension check for directories
-        if path.is_dir() {
+        let is_dir = fs.metadata(path).is_ok_and(|m| m.is_dir());
+        if is_dir {
             return Ok(());
         }
-        
+        self.check_file_extension(path)
+    }
+
+    /// An archive browsed as a directory must itself be an allowed file
+    fn check_containing_archive(&self, fs: &dyn FileSystem, path: &Path) -> Result<()> {
+        match fs.containing_archive(path) {
+            Some(archive) => self.check_file_extension(&archive),
+            None => Ok(()),
+        }
+    }
+
+    fn check_file_extension(&self, path: &Path) -> Result<()> {
+        // `payload.exe:stream` is an .exe, not a `.exe:stream`
+        let without_stream;
+        let path = match path.file_name().and_then(|name| host_alternate_stream(Path::new(name))) {
+            Some((file, _)) => {
+                without_stream = path.with_file_name(file);
+                &without_stream
+            }
+            None => path,
+        };
         if let Some(ext) = path.extension() {
-            let ext_str = ext.to_string_lossy().to_lowercase();
+            let form = self.unicode_normalization;
+            let ext_str = form.apply_str(&ext.to_string_lossy().to_lowercase()).into_owned();
             
             // Check denied extensions first
-            if !self.denied_extensions.is_empty() {
-                for denied_ext in &self.denied_extensions {
-                    if ext_str == denied_ext.to_lowercase() {
-                        return Err(FileJackError::PermissionDenied(
-                            format!("File extension .{} is not allowed", ext_str)
-                        ));
-                    }
+            for (index, denied_ext) in self.denied_extensions.iter().enumerate() {
+                if ext_str == form.apply_str(&denied_ext.to_lowercase()) {
+                    return Err(self.deny(
+                        format!("denied_extensions[{}]", index),
+                        RuleCategory::Extension,
+                        format!("File extension .{} is not allowed", ext_str),
+                    ));
                 }
             }
             
             // Check allowed extensions
             if !self.allowed_extensions.is_empty() {
                 let allowed = self.allowed_extensions.iter()
-                    .any(|allowed_ext| ext_str == allowed_ext.to_lowercase());
+                    .any(|allowed_ext| ext_str == form.apply_str(&allowed_ext.to_lowercase()));
                 
                 if !allowed {
-                    return Err(FileJackError::PermissionDenied(
-                        format!("File extension .{} is not in allowed extensions", ext_str)
+                    return Err(self.deny(
+                        "allowed_extensions".to_string(),
+                        RuleCategory::Extension,
+                        format!("File extension .{} is not in allowed extensions", ext_str),
                     ));
                 }
             }
-        } else if !self.allowed_extensions.is_empty() && !path.is_dir() {
+        } else if !self.allowed_extensions.is_empty() {
             // File has no extension but allowed_extensions is specified
-            return Err(FileJackError::PermissionDenied(
-                "Files without extensions are not allowed".to_string()
+            return Err(self.deny(
+                "allowed_extensions".to_string(),
+                RuleCategory::Extension,
+                "Files without extensions are not allowed".to_string(),
             ));
         }
         
         Ok(())
     }
 
-    fn check_hidden_files(&self, path: &Path) -> Result<()> {
-        if !self.allow_hidden_files {
-            if let Some(filename) = path.file_name() {
-                if filename.to_string_lossy().starts_with('.') {
-                    return Err(FileJackError::PermissionDenied(
-                        "Access to hidden files is not allowed".to_string()
-                    ));
-                }
-            }
+    fn check_overlays(&self, fs: &dyn FileSystem, canonical: &Path, write: bool) -> Result<()> {
+        if !self.directory_overlays {
+            return Ok(());
+        }
+        // Walk up to the outermost allowed root containing the path
+        let roots = self.canonical_roots(fs);
+        let root = roots.allowed.iter()
+            .filter(|root| canonical.starts_with(root))
+            .min_by_key(|root| root.components().count());
+        overlay::check_overlays(fs, canonical, root.map(PathBuf::as_path), write)
+    }
+
+    fn check_read_only(&self) -> Result<()> {
+        if self.read_only {
+            return Err(self.deny(
+                "read_only".to_string(),
+                RuleCategory::ReadOnly,
+                "Write operations are disabled in read-only mode".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Refuse writes to overlay files, which would loosen their own rules
+    fn check_overlay_file(&self, path: &Path) -> Result<()> {
+        if self.directory_overlays && overlay::is_overlay_file(path) {
+            return Err(self.deny(
+                "directory_overlays".to_string(),
+                RuleCategory::Overlay,
+                format!("{} policy overlays cannot be modified", overlay::OVERLAY_FILE_NAME),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_hidden_files(&self, fs: &dyn FileSystem, path: &Path) -> Result<()> {
+        if !self.allow_hidden_files && self.hidden_file_detection.is_hidden(fs, path) {
+            return Err(self.deny(
+                "allow_hidden_files".to_string(),
+                RuleCategory::HiddenFile,
+                "Access to hidden files is not allowed".to_string(),
+            ));
         }
         Ok(())
     }
 
-    fn check_symlinks(&self, original: &Path, canonical: &Path) -> Result<()> {
+    /// `path` as given in a tool argument, made ready for lookup: escaped
+    /// bytes decoded, then brought to `unicode_normalization`'s form
+    fn lookup_path<'a>(&self, path: &'a Path) -> Cow<'a, Path> {
+        match vfs::unescape_path(path) {
+            Cow::Borrowed(path) => self.unicode_normalization.apply(path),
+            Cow::Owned(path) => Cow::Owned(self.unicode_normalization.apply(&path).into_owned()),
+        }
+    }
+
+    fn check_owner(&self, fs: &dyn FileSystem, path: &Path) -> Result<()> {
+        if !self.deny_files_not_owned_by_user {
+            return Ok(());
+        }
+        let (Some(user), Some(owner)) = (fs.user_id(), fs.metadata(path).ok().and_then(|m| m.owner)) else {
+            return Ok(());
+        };
+        if owner != user {
+            return Err(self.deny(
+                "deny_files_not_owned_by_user".to_string(),
+                RuleCategory::Owner,
+                format!("File is owned by user {}, not the server's user {}", owner, user),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_alternate_streams(&self, path: &Path) -> Result<()> {
+        if self.allow_alternate_streams {
+            return Ok(());
+        }
+        match host_alternate_stream(path) {
+            Some((file, stream)) => Err(self.deny(
+                "allow_alternate_streams".to_string(),
+                RuleCategory::AlternateStream,
+                format!("Alternate data stream {} of {} is not allowed", stream, file),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn check_symlinks(&self, fs: &dyn FileSystem, original: &Path, canonical: &Path) -> Result<()> {
         if !self.allow_symlinks && original != canonical {
             // Path was resolved from a symlink
-            if original.read_link().is_ok() {
-                return Err(FileJackError::PermissionDenied(
-                    "Symbolic links are not allowed".to_string()
+            if fs.symlink_metadata(original).is_ok_and(|m| m.is_symlink()) {
+                return Err(self.deny(
+                    "allow_symlinks".to_string(),
+                    RuleCategory::Symlink,
+                    "Symbolic links are not allowed".to_string(),
                 ));
             }
         }
@@ -267,6 +1185,62 @@ impl AccessPolicy {
     }
 }
 
+/// The file and stream names of an NTFS alternate data stream path such as
+/// `C:\data\notes.txt:secret` or `notes.txt::$DATA`. Any file name holding a
+/// colon names a stream, since Windows allows no colon in a file name; the
+/// drive prefix is not a file name.
+pub fn alternate_stream(path: &Path) -> Option<(String, String)> {
+    path.components().find_map(|component| match component {
+        Component::Normal(name) => {
+            let name = name.to_string_lossy();
+            name.split_once(':').map(|(file, stream)| (file.to_string(), stream.to_string()))
+        }
+        _ => None,
+    })
+}
+
+/// [`alternate_stream`] on Windows; elsewhere a colon is part of the name
+fn host_alternate_stream(path: &Path) -> Option<(String, String)> {
+    if cfg!(windows) {
+        alternate_stream(path)
+    } else {
+        None
+    }
+}
+
+/// `patterns` compiled in normalization form `form`, or the index of the
+/// first invalid one and why, naming it as one of the `kind` patterns
+fn compile_patterns(
+    form: UnicodeNormalization,
+    kind: &str,
+    patterns: &[String],
+) -> std::result::Result<Vec<glob::Pattern>, (usize, String)> {
+    patterns.iter()
+        .enumerate()
+        .map(|(index, pattern)| glob::Pattern::new(&form.apply_str(pattern))
+            .map_err(|e| (index, format!("{} path pattern {:?} is invalid: {}", kind, pattern, e))))
+        .collect()
+}
+
+/// Index of the first of `patterns` that `path`, or any trailing part of it,
+/// matches; `*` does not cross `/`
+fn matching_pattern(patterns: &[glob::Pattern], path: &Path) -> Option<usize> {
+    let options = glob::MatchOptions { require_literal_separator: true, ..Default::default() };
+    let components: Vec<_> = path.components().collect();
+    (0..components.len()).find_map(|start| {
+        let tail: PathBuf = components[start..].iter().collect();
+        patterns.iter().position(|pattern| pattern.matches_path_with(&tail, options))
+    })
+}
+
+/// Canonical forms of `paths` that exist, with their indices in `paths`
+fn canonical_indexed(fs: &dyn FileSystem, paths: &[PathBuf]) -> Vec<(usize, PathBuf)> {
+    paths.iter()
+        .enumerate()
+        .filter_map(|(index, path)| fs.canonicalize(path).ok().map(|canonical| (index, canonical)))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -318,6 +1292,202 @@ mod tests {
         assert!(policy.validate_read(&test_file).is_ok());
     }
 
+    #[test]
+    fn test_validated_paths_have_no_verbatim_prefix() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        fs::write(&test_file, "test").unwrap();
+
+        // Windows canonicalizes to \\?\C:\..., which a configured C:\... never prefixes
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let canonical = policy.validate_read(&test_file).unwrap();
+        assert!(!canonical.to_string_lossy().starts_with(r"\\?\"));
+        assert_eq!(canonical, crate::vfs::canonicalize(temp_dir.path()).unwrap().join("test.txt"));
+    }
+
+    #[test]
+    fn test_hidden_file_detection() {
+        let temp_dir = TempDir::new().unwrap();
+        let dotfile = temp_dir.path().join(".env");
+        fs::write(&dotfile, "KEY=value").unwrap();
+
+        let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        policy.hidden_file_detection = HiddenFileDetection::DotPrefix;
+        assert!(policy.validate_read(&dotfile).is_err());
+        assert!(policy.validate_write(&temp_dir.path().join(".new"), WriteOperation::Write).is_err());
+
+        // No file here carries the hidden flag, whatever its name
+        policy.hidden_file_detection = HiddenFileDetection::Attribute;
+        assert!(policy.validate_read(&dotfile).is_ok());
+        policy.hidden_file_detection = HiddenFileDetection::Both;
+        assert!(policy.validate_read(&dotfile).is_err());
+
+        let parsed: AccessPolicy = serde_json::from_str(r#"{"hidden_file_detection": "dot_prefix"}"#).unwrap();
+        assert_eq!(parsed.hidden_file_detection, HiddenFileDetection::DotPrefix);
+        assert_eq!(AccessPolicy::default().hidden_file_detection, HiddenFileDetection::Platform);
+    }
+
+    #[test]
+    fn test_alternate_streams() {
+        assert_eq!(
+            alternate_stream(Path::new("/data/notes.txt:secret.exe")),
+            Some(("notes.txt".to_string(), "secret.exe".to_string()))
+        );
+        assert_eq!(alternate_stream(Path::new("notes.txt::$DATA")), Some(("notes.txt".to_string(), ":$DATA".to_string())));
+        assert_eq!(alternate_stream(Path::new("/data/notes.txt")), None);
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "notes").unwrap();
+        let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        policy.allowed_extensions = vec!["txt".to_string()];
+        let stream = temp_dir.path().join("notes.txt:payload.exe");
+        if cfg!(windows) {
+            assert!(policy.validate_read(&stream).is_err());
+            assert!(policy.validate_write(&stream, WriteOperation::Write).is_err());
+            policy.allow_alternate_streams = true;
+            assert!(policy.validate_write(&stream, WriteOperation::Write).is_ok());
+        } else {
+            // An ordinary file name, held to the extension rule like any other
+            assert!(policy.validate_write(&stream, WriteOperation::Write).is_err());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_deny_files_not_owned_by_user() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let own = temp_dir.path().join("own.txt");
+        fs::write(&own, "mine").unwrap();
+        let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        policy.deny_files_not_owned_by_user = true;
+        assert!(policy.validate_read(&own).is_ok());
+        assert!(policy.validate_write(&temp_dir.path().join("new.txt"), WriteOperation::Write).is_ok());
+
+        // Only root can give a file away
+        let other = temp_dir.path().join("other.txt");
+        fs::write(&other, "theirs").unwrap();
+        let path = std::ffi::CString::new(other.as_os_str().as_encoded_bytes()).unwrap();
+        if unsafe { libc::chown(path.as_ptr(), 65534, u32::MAX) } != 0 {
+            return;
+        }
+        assert_eq!(fs::metadata(&other).unwrap().uid(), 65534);
+        let err = policy.validate_read(&other).unwrap_err();
+        assert!(err.to_string().contains("deny_files_not_owned_by_user"));
+        assert!(policy.validate_write(&other, WriteOperation::Write).is_err());
+        policy.deny_files_not_owned_by_user = false;
+        assert!(policy.validate_read(&other).is_ok());
+    }
+
+    #[test]
+    fn test_creation_modes_parse() {
+        let parsed: AccessPolicy = serde_json::from_str(r#"{"file_mode": "0640", "directory_mode": 488}"#).unwrap();
+        assert_eq!(parsed.file_mode, Some(0o640));
+        assert_eq!(parsed.directory_mode, Some(0o750));
+        let parsed: AccessPolicy = toml::from_str("file_mode = \"0o600\"\ndirectory_mode = 0o700").unwrap();
+        assert_eq!((parsed.file_mode, parsed.directory_mode), (Some(0o600), Some(0o700)));
+        assert!(serde_json::from_str::<AccessPolicy>(r#"{"file_mode": "rw-r--r--"}"#).is_err());
+        assert!(serde_json::from_str::<AccessPolicy>(r#"{"file_mode": "17777"}"#).is_err());
+
+        assert_eq!(AccessPolicy::default().file_mode, None);
+        assert_eq!(AccessPolicy::restricted(PathBuf::from("/data")).directory_mode, Some(0o700));
+    }
+
+    #[test]
+    fn test_unicode_normalization() {
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        assert_eq!(UnicodeNormalization::Nfd.apply_str(composed), decomposed);
+        assert_eq!(UnicodeNormalization::Nfc.apply_str(decomposed), composed);
+        assert!(matches!(UnicodeNormalization::None.apply_str(decomposed), Cow::Borrowed(_)));
+
+        // Stored decomposed, as HFS+ does; named composed, as clients do
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join(decomposed);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("menu.txt"), "soup").unwrap();
+        fs::write(dir.join("secret.txt"), "recipe").unwrap();
+        let named = temp_dir.path().join(composed);
+
+        let mut policy = AccessPolicy::restricted(named.clone());
+        policy.denied_paths = vec![named.join("secret.txt")];
+        policy.unicode_normalization = UnicodeNormalization::Nfd;
+        assert_eq!(policy.validate_read(&named.join("menu.txt")).unwrap(), dir.join("menu.txt"));
+        assert!(policy.validate_read(&named.join("secret.txt")).is_err());
+        assert!(policy.validate_read(&dir.join("secret.txt")).is_err());
+
+        // Host file systems that keep both forms apart need the form they store
+        if !temp_dir.path().join(composed).exists() {
+            policy.unicode_normalization = UnicodeNormalization::None;
+            assert!(policy.validate_read(&named.join("menu.txt")).is_err());
+        }
+        let parsed: AccessPolicy = serde_json::from_str(r#"{"unicode_normalization": "nfc"}"#).unwrap();
+        assert_eq!(parsed.unicode_normalization, UnicodeNormalization::Nfc);
+    }
+
+    #[test]
+    fn test_sensitive_paths_refuse_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let home = temp_dir.path().join("home");
+        fs::create_dir_all(home.join(".git")).unwrap();
+        fs::write(home.join(".bashrc"), "export PATH").unwrap();
+        let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        policy.allow_hidden_files = true;
+
+        for path in [".ssh/authorized_keys", ".ssh", ".git/config", ".git/hooks/pre-commit", ".bashrc", ".env.local"] {
+            match policy.validate_write(&home.join(path), WriteOperation::Write) {
+                Err(FileJackError::PermissionDenied(Denial { rule: Some(rule), .. })) => {
+                    assert_eq!(rule.category, RuleCategory::Sensitive, "{}", path);
+                }
+                other => panic!("{} was not refused as sensitive: {:?}", path, other),
+            }
+        }
+        assert!(policy.validate_write(&home.join(".bashrc"), WriteOperation::Delete).is_err());
+        assert!(policy.validate_write(&home.join(".git/HEAD"), WriteOperation::Write).is_ok());
+        assert!(policy.validate_write(&home.join("notes.env"), WriteOperation::Write).is_ok());
+        assert!(policy.validate_read(&home.join(".bashrc")).is_ok());
+
+        policy.sensitive_paths.clear();
+        assert!(policy.validate_write(&home.join(".bashrc"), WriteOperation::Write).is_ok());
+        let parsed: AccessPolicy = serde_json::from_str(r#"{"sensitive_paths": ["id_*"]}"#).unwrap();
+        assert_eq!(parsed.sensitive_paths, ["id_*"]);
+        assert_eq!(AccessPolicy::default().sensitive_paths.len(), DEFAULT_SENSITIVE_PATHS.len());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_parent_directories_refuse_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let outside = temp_dir.path().join("outside");
+        fs::create_dir_all(root.join("real")).unwrap();
+        fs::create_dir(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("escape")).unwrap();
+        std::os::unix::fs::symlink(root.join("real"), root.join("inner")).unwrap();
+        std::os::unix::fs::symlink(outside.join("new.txt"), root.join("dangling")).unwrap();
+        let mut policy = AccessPolicy::restricted(root.clone());
+        policy.allow_hidden_files = true;
+
+        for path in ["escape/file.txt", "escape/new/dir/file.txt", "inner/file.txt", "dangling"] {
+            match policy.validate_write(&root.join(path), WriteOperation::Write) {
+                Err(FileJackError::PermissionDenied(Denial { rule: Some(rule), .. })) => {
+                    assert_eq!(rule.category, RuleCategory::Symlink, "{}", path);
+                }
+                other => panic!("{} was not refused as a symlink: {:?}", path, other),
+            }
+        }
+        assert!(policy.validate_write(&root.join("real/file.txt"), WriteOperation::Write).is_ok());
+
+        policy.allow_symlinks = true;
+        assert!(policy.validate_write(&root.join("inner/file.txt"), WriteOperation::Write).is_ok());
+        assert!(policy.validate_write(&root.join("escape/file.txt"), WriteOperation::Write).is_err());
+        assert!(matches!(
+            policy.validate_write(&root.join("dangling"), WriteOperation::Write),
+            Err(FileJackError::InvalidPath(_))
+        ));
+    }
+
     #[test]
     fn test_validate_read_denied_path() {
         let temp_dir = TempDir::new().unwrap();
@@ -335,13 +1505,121 @@ mod tests {
         assert!(policy.validate_read(&denied_file).is_err());
     }
 
+    #[test]
+    fn test_trace_reports_every_rule() {
+        let temp_dir = TempDir::new().unwrap();
+        let denied_dir = temp_dir.path().join("denied");
+        fs::create_dir(&denied_dir).unwrap();
+        fs::write(denied_dir.join(".secret"), "secret").unwrap();
+        let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        policy.denied_paths = vec![denied_dir.clone()];
+        policy.protected_paths = vec!["*.lock".to_string()];
+
+        let trace = policy.trace(&denied_dir.join(".secret"), false);
+        let denied: Vec<_> = trace.steps.iter().filter(|step| step.outcome.is_err()).map(|step| step.rule).collect();
+        assert_eq!(denied, ["denied_paths", "allow_hidden_files"]);
+        assert_eq!(trace.steps.len(), 8);
+        assert!(trace.decision.unwrap_err().to_string().contains("denied_paths[0]"));
+
+        let lock = temp_dir.path().join("new").join("Cargo.lock");
+        let trace = policy.trace(&lock, true);
+        assert_eq!(trace.resolved, Some(temp_dir.path().canonicalize().unwrap().join("new").join("Cargo.lock")));
+        let denied: Vec<_> = trace.steps.iter().filter(|step| step.outcome.is_err()).map(|step| step.rule).collect();
+        assert_eq!(denied, ["protected_paths"]);
+        assert!(trace.decision.is_err());
+
+        assert!(policy.trace(&temp_dir.path().join("notes.txt"), true).decision.is_ok());
+        let missing = policy.trace(&temp_dir.path().join("missing.txt"), false);
+        assert!(missing.resolved.is_none() && missing.steps.is_empty());
+        assert!(matches!(missing.decision, Err(FileJackError::FileNotFound(_))));
+    }
+
+    #[test]
+    fn test_denials_name_their_rule() {
+        let temp_dir = TempDir::new().unwrap();
+        let denied_dir = temp_dir.path().join("denied");
+        fs::create_dir(&denied_dir).unwrap();
+        fs::write(denied_dir.join("secret.txt"), "secret").unwrap();
+        let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        policy.denied_paths = vec![temp_dir.path().join("gone"), denied_dir.clone()];
+        policy.protected_paths = vec!["*.lock".to_string()];
+
+        let rule = |result: Result<PathBuf>| match result {
+            Err(FileJackError::PermissionDenied(Denial { rule: Some(rule), .. })) => (rule.id, rule.category),
+            other => panic!("not a denial by a rule: {:?}", other),
+        };
+        assert_eq!(rule(policy.validate_read(&denied_dir.join("secret.txt"))), ("denied_paths[1]".to_string(), RuleCategory::DeniedPath));
+        assert_eq!(rule(policy.validate_read(Path::new("/"))), ("allowed_paths".to_string(), RuleCategory::AllowedPaths));
+        let lock = temp_dir.path().join("Cargo.lock");
+        assert_eq!(rule(policy.validate_write(&lock, WriteOperation::Write)), ("protected_paths[0]".to_string(), RuleCategory::Protected));
+
+        // Named rules are reported by name
+        policy.rule_ids.insert("denied_paths[1]".to_string(), "no-secrets".to_string());
+        let err = policy.validate_read(&denied_dir.join("secret.txt")).unwrap_err();
+        assert!(err.to_string().ends_with("(rule no-secrets)"), "{}", err);
+        assert!(policy.derived_rule_ids().contains(&"denied_paths[1]".to_string()));
+    }
+
     #[test]
     fn test_validate_write_read_only() {
         let temp_dir = TempDir::new().unwrap();
         let test_file = temp_dir.path().join("test.txt");
 
         let policy = AccessPolicy::read_only(temp_dir.path().to_path_buf());
-        assert!(policy.validate_write(&test_file).is_err());
+        assert!(policy.validate_write(&test_file, WriteOperation::Write).is_err());
+    }
+
+    #[test]
+    fn test_validate_write_append_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = temp_dir.path().join("app.log");
+        fs::write(&log, "").unwrap();
+        let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        policy.append_only_paths = vec![log.clone()];
+
+        assert!(policy.validate_write(&log, WriteOperation::Append).is_ok());
+        for operation in [WriteOperation::Write, WriteOperation::Delete] {
+            let err = policy.validate_write(&log, operation).unwrap_err();
+            assert!(err.to_string().contains("append-only"), "{}", err);
+        }
+        assert!(policy.validate_write(&temp_dir.path().join("other.log"), WriteOperation::Write).is_ok());
+        assert!(policy.validate_read(&log).is_ok());
+    }
+
+    #[test]
+    fn test_validate_write_protected() {
+        let temp_dir = TempDir::new().unwrap();
+        let workflows = temp_dir.path().join(".github/workflows");
+        fs::create_dir_all(&workflows).unwrap();
+        fs::write(temp_dir.path().join("Cargo.lock"), "").unwrap();
+        let mut policy = AccessPolicy::permissive();
+        policy.protected_paths = vec!["Cargo.lock".to_string(), ".github/**".to_string()];
+
+        for path in [temp_dir.path().join("Cargo.lock"), workflows.join("ci.yml"), temp_dir.path().join("sub/Cargo.lock")] {
+            for operation in [WriteOperation::Write, WriteOperation::Append, WriteOperation::Delete] {
+                let err = policy.validate_write(&path, operation).unwrap_err();
+                assert!(err.to_string().contains("is a protected file"), "{}", err);
+            }
+        }
+        assert!(policy.validate_read(&temp_dir.path().join("Cargo.lock")).is_ok());
+        assert!(policy.validate_write(&temp_dir.path().join("Cargo.toml"), WriteOperation::Write).is_ok());
+        assert!(policy.validate_write(&temp_dir.path().join("my-Cargo.lock"), WriteOperation::Write).is_ok());
+
+        policy.protected_paths.push("[".to_string());
+        let err = policy.validate_write(&temp_dir.path().join("Cargo.toml"), WriteOperation::Write).unwrap_err();
+        assert!(err.to_string().contains("is invalid"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_write_parent_of_missing_directory() {
+        let temp_dir = tempfile::Builder::new().prefix("policy").tempdir().unwrap();
+        let allowed_dir = temp_dir.path().join("allowed");
+        fs::create_dir(&allowed_dir).unwrap();
+
+        let policy = AccessPolicy::restricted(allowed_dir.clone());
+        assert!(policy.validate_write(&allowed_dir.join("new/file.txt"), WriteOperation::Write).is_ok());
+        assert!(policy.validate_write(&allowed_dir.join("missing/../../outside.txt"), WriteOperation::Write).is_err());
+        assert!(policy.validate_write(&allowed_dir.join("missing/../file.txt"), WriteOperation::Write).is_err());
     }
 
     #[test]
@@ -389,6 +1667,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_file_size_validation() {
         let mut policy = AccessPolicy::default();
         policy.max_file_size = 1024; // 1KB
@@ -398,6 +1677,87 @@ mod tests {
         assert!(policy.validate_file_size(2048).is_err());
     }
 
+    #[test]
+    fn test_canonical_roots_follow_field_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("first");
+        let second = temp_dir.path().join("second");
+        fs::create_dir(&first).unwrap();
+        fs::create_dir(&second).unwrap();
+        fs::write(second.join("file.txt"), "test").unwrap();
+
+        let mut policy = AccessPolicy::restricted(first);
+        assert!(policy.validate_read(&second.join("file.txt")).is_err());
+
+        policy.allowed_paths = vec![second.clone()];
+        assert!(policy.validate_read(&second.join("file.txt")).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_cache_refuses_a_swapped_in_symlink() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        let test_file = root.join("cached.txt");
+        let target = root.join("target.txt");
+        fs::write(&test_file, "test").unwrap();
+        fs::write(&target, "target").unwrap();
+
+        let policy = AccessPolicy::restricted(root.clone())
+            .with_path_cache(16, Duration::from_secs(60));
+        policy.validate_read(&test_file).unwrap();
+        policy.validate_read(&target).unwrap();
+
+        // Both paths are cached, yet the symlink is refused at once
+        fs::remove_file(&test_file).unwrap();
+        std::os::unix::fs::symlink(&target, &test_file).unwrap();
+        assert!(matches!(
+            policy.validate_read(&test_file),
+            Err(FileJackError::PermissionDenied(_))
+        ));
+
+        // And a file that is gone is gone
+        fs::remove_file(&target).unwrap();
+        assert!(matches!(
+            policy.validate_read(&target),
+            Err(FileJackError::FileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_path_cache_respects_rule_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("file.exe");
+        fs::write(&test_file, "test").unwrap();
+
+        let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        assert!(policy.validate_read(&test_file).is_ok());
+
+        policy.denied_extensions = vec!["exe".to_string()];
+        assert!(policy.validate_read(&test_file).is_err());
+    }
+
+    #[test]
+    fn test_path_cache_disabled_and_not_shared_by_clones() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("file.txt");
+        fs::write(&test_file, "test").unwrap();
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        policy.validate_read(&test_file).unwrap();
+
+        let mut clone = policy.clone();
+        clone.denied_extensions = vec!["txt".to_string()];
+        assert!(clone.validate_read(&test_file).is_err());
+
+        let uncached = AccessPolicy::restricted(temp_dir.path().to_path_buf())
+            .with_path_cache(0, DEFAULT_PATH_CACHE_TTL);
+        uncached.validate_read(&test_file).unwrap();
+        fs::remove_file(&test_file).unwrap();
+        assert!(uncached.validate_read(&test_file).is_err());
+    }
+
     #[test]
     fn test_path_outside_allowed() {
         let temp_dir = TempDir::new().unwrap();
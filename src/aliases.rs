@@ -0,0 +1,231 @@
+//! Short names standing in for host directories.
+//!
+//! With an `aliases` table in the config, such as `"@workspace" =
+//! "/home/user/project"`, a tool argument like `@workspace/src/main.rs`
+//! names a file in the aliased directory, and the directory is written as
+//! its alias wherever it appears in tool results, error messages, and
+//! `tail_follow` notifications. Transcripts of a session can then be shared
+//! without showing where its files live on the host.
+//!
+//! The policy's `redact_roots` does the same for the allowed roots, either
+//! leaving them out or giving each an alias of its own. The results of
+//! tools that return file contents are left alone, so that text read from
+//! a file can be written back unchanged.
+
+use crate::access_control::{AccessPolicy, RootRedaction};
+use crate::error::{FileJackError, Result};
+use crate::mounts::PATH_ARGUMENTS;
+use crate::vfs;
+use serde_json::Value;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Tools whose results are file contents, which are never rewritten
+const CONTENT_TOOLS: &[&str] = &["read_file", "read_lines", "read_range", "pack_directory"];
+
+/// What a directory is written as
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Replacement {
+    Alias(String),
+    /// Nothing, along with the separator after it
+    Strip,
+}
+
+/// The configured aliases
+#[derive(Debug, Default)]
+pub struct Aliases {
+    /// Each alias and the directory it names
+    aliases: Vec<(String, PathBuf)>,
+    /// Ways each directory may be written, with what replaces them,
+    /// longest first so a nested directory wins over its parent
+    spellings: Vec<(String, Replacement)>,
+}
+
+impl Aliases {
+    /// The aliases in `config`, and those `policy.redact_roots` asks for.
+    /// Without allowed paths, the server's working directory stands in for
+    /// the roots.
+    pub fn new(config: &BTreeMap<String, PathBuf>, policy: &AccessPolicy) -> Self {
+        let mut aliases = Self::default();
+        for (alias, dir) in config {
+            aliases.add(dir, Replacement::Alias(alias.clone()));
+        }
+        let roots = match policy.allowed_paths.is_empty() {
+            true if policy.redact_roots != RootRedaction::Off => std::env::current_dir().into_iter().collect(),
+            true => Vec::new(),
+            false => policy.allowed_paths.clone(),
+        };
+        for root in &roots {
+            match policy.redact_roots {
+                RootRedaction::Off => {}
+                RootRedaction::Strip => aliases.add(root, Replacement::Strip),
+                RootRedaction::Alias if aliases.aliases.iter().any(|(_, dir)| dir == root) => {}
+                RootRedaction::Alias => {
+                    let name = root.file_name().map_or("root".into(), |name| name.to_string_lossy());
+                    let alias = std::iter::once(format!("@{}", name))
+                        .chain((2..).map(|n| format!("@{}-{}", name, n)))
+                        .find(|alias| !aliases.aliases.iter().any(|(taken, _)| taken == alias))
+                        .unwrap_or_default();
+                    aliases.add(root, Replacement::Alias(alias));
+                }
+            }
+        }
+        aliases.spellings.sort_by_key(|(form, _)| Reverse(form.len()));
+        aliases.spellings.dedup();
+        aliases
+    }
+
+    fn add(&mut self, dir: &Path, replacement: Replacement) {
+        if let Replacement::Alias(alias) = &replacement {
+            self.aliases.push((alias.clone(), dir.to_path_buf()));
+        }
+        let mut forms = vec![dir.display().to_string()];
+        // Results name files by their resolved paths
+        if let Ok(canonical) = vfs::canonicalize(dir) {
+            forms.push(canonical.display().to_string());
+        }
+        for form in forms {
+            let form = form.trim_end_matches(['/', '\\']).to_string();
+            if form.is_empty() {
+                continue;
+            }
+            // As written inside JSON text, where `\` is escaped
+            if form.contains('\\') {
+                self.spellings.push((form.replace('\\', "\\\\"), replacement.clone()));
+            }
+            self.spellings.push((form, replacement.clone()));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spellings.is_empty()
+    }
+
+    /// Whether the result of `tool` is rewritten by [`Self::hide_in`]
+    pub fn hides_result_of(&self, tool: &str) -> bool {
+        !self.is_empty() && !CONTENT_TOOLS.contains(&tool)
+    }
+
+    /// `arguments` with each path that starts with an alias made a real one.
+    /// Paths starting with `@` that name no alias are left as they are.
+    pub fn expand(&self, mut arguments: Value) -> Result<Value> {
+        for key in PATH_ARGUMENTS {
+            if let Some(Value::String(path)) = arguments.get_mut(key) {
+                if let Some(real) = self.resolve(path)? {
+                    *path = real;
+                }
+            }
+        }
+        Ok(arguments)
+    }
+
+    fn resolve(&self, path: &str) -> Result<Option<String>> {
+        if !path.starts_with('@') {
+            return Ok(None);
+        }
+        let (alias, rest) = path.split_once(['/', '\\']).unwrap_or((path, ""));
+        let Some((_, dir)) = self.aliases.iter().find(|(name, _)| name == alias) else {
+            return Ok(None);
+        };
+        if Path::new(rest).components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return Err(FileJackError::InvalidPath(format!("Path {} escapes alias {}", path, alias)));
+        }
+        Ok(Some(dir.join(rest).display().to_string()))
+    }
+
+    /// `text` with every aliased directory written as its alias
+    pub fn hide(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (spelling, replacement) in &self.spellings {
+            if text.contains(spelling.as_str()) {
+                text = replace_dir(&text, spelling, replacement);
+            }
+        }
+        text
+    }
+
+    /// Pass every string in `value` through [`Self::hide`]
+    pub fn hide_in(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.hide(text),
+            Value::Array(items) => items.iter_mut().for_each(|item| self.hide_in(item)),
+            Value::Object(map) => map.values_mut().for_each(|item| self.hide_in(item)),
+            _ => {}
+        }
+    }
+}
+
+/// `text` with `dir` replaced wherever it is a whole path or the start of
+/// one, and not part of a longer name such as `dir2`
+fn replace_dir(text: &str, dir: &str, replacement: &Replacement) -> String {
+    let is_name = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find(dir) {
+        let mut after = &rest[at + dir.len()..];
+        out.push_str(&rest[..at]);
+        if rest[..at].ends_with(is_name) || after.starts_with(is_name) {
+            out.push_str(dir);
+        } else {
+            match replacement {
+                Replacement::Alias(alias) => out.push_str(alias),
+                Replacement::Strip => match ["\\\\", "/", "\\"].iter().find_map(|sep| after.strip_prefix(sep)) {
+                    Some(below) => after = below,
+                    None => out.push('.'),
+                },
+            }
+        }
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_expand_and_hide() {
+        let aliases = Aliases::new(&BTreeMap::from([
+            ("@work".to_string(), PathBuf::from("/home/user/project")),
+            ("@docs".to_string(), PathBuf::from("/home/user/project/docs/")),
+        ]), &AccessPolicy::permissive());
+        let real = |rest: &str| Path::new("/home/user/project").join(rest).display().to_string();
+
+        let arguments = aliases.expand(json!({"from": "@work/src/main.rs", "to": "@work", "content": "@work/x"})).unwrap();
+        assert_eq!(arguments, json!({"from": real("src/main.rs"), "to": real(""), "content": "@work/x"}));
+        assert_eq!(aliases.expand(json!({"path": "@notes.txt"})).unwrap(), json!({"path": "@notes.txt"}));
+        assert!(aliases.expand(json!({"path": "@work/../secrets"})).is_err());
+
+        assert_eq!(aliases.hide("/home/user/project/src/main.rs: not found"), "@work/src/main.rs: not found");
+        assert_eq!(aliases.hide("/home/user/project/docs/a.md"), "@docs/a.md");
+        assert_eq!(aliases.hide("/home/user/project2/a and /home/user/project"), "/home/user/project2/a and @work");
+
+        let mut result = json!({"content": [{"type": "text", "text": "[\"/home/user/project/a\"]"}]});
+        aliases.hide_in(&mut result);
+        assert_eq!(result["content"][0]["text"], "[\"@work/a\"]");
+        assert!(aliases.hides_result_of("list_directory") && !aliases.hides_result_of("read_file"));
+    }
+
+    #[test]
+    fn test_redact_roots() {
+        let mut policy = AccessPolicy::restricted(PathBuf::from("/srv/app"));
+        policy.allowed_paths.push(PathBuf::from("/data/app"));
+        assert!(Aliases::new(&BTreeMap::new(), &policy).is_empty());
+
+        policy.redact_roots = RootRedaction::Strip;
+        let stripped = Aliases::new(&BTreeMap::new(), &policy);
+        assert_eq!(stripped.hide("/srv/app/src/main.rs not found"), "src/main.rs not found");
+        assert_eq!(stripped.hide("allowed: /srv/app"), "allowed: .");
+
+        policy.redact_roots = RootRedaction::Alias;
+        let configured = BTreeMap::from([("@data".to_string(), PathBuf::from("/data/app"))]);
+        let aliased = Aliases::new(&configured, &policy);
+        assert_eq!(aliased.hide("/srv/app/a and /data/app/b"), "@app/a and @data/b");
+        let real = Path::new("/srv/app").join("x").display().to_string();
+        assert_eq!(aliased.expand(json!({"path": "@app/x"})).unwrap(), json!({"path": real}));
+    }
+}
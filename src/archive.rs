@@ -0,0 +1,557 @@
+use crate::vfs::{DirEntry, FileHandle, FileKind, FileSystem, MemoryHandle, Metadata, WalkOptions};
+use flate2::read::GzDecoder;
+use lru::LruCache;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::num::NonZeroUsize;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Number of archive indexes kept in memory
+const INDEX_CACHE_CAPACITY: usize = 32;
+
+/// Archive layouts that can be browsed, recognized by file name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl Format {
+    fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") || name.ends_with(".jar") {
+            Some(Format::Zip)
+        } else if name.ends_with(".tar") {
+            Some(Format::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Format::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Where an entry's data starts
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    Dir,
+    /// Index into the zip central directory
+    Zip(usize),
+    /// Byte offset of the data in an uncompressed tar
+    Tar(u64),
+    /// Found by decompressing up to the entry
+    TarGz,
+}
+
+#[derive(Debug, Clone)]
+struct IndexEntry {
+    kind: FileKind,
+    len: u64,
+    modified: Option<SystemTime>,
+    location: Location,
+}
+
+/// The entries of one archive, by path relative to the archive
+#[derive(Debug)]
+struct ArchiveIndex {
+    modified: Option<SystemTime>,
+    entries: BTreeMap<PathBuf, IndexEntry>,
+}
+
+impl ArchiveIndex {
+    fn build(fs: &dyn FileSystem, archive: &Path, format: Format, modified: Option<SystemTime>) -> io::Result<Self> {
+        let mut index = Self { modified, entries: BTreeMap::new() };
+        let handle = fs.open(archive)?;
+        match format {
+            Format::Zip => {
+                let mut zip = zip::ZipArchive::new(handle)?;
+                for i in 0..zip.len() {
+                    let file = zip.by_index_raw(i)?;
+                    let Some(path) = file.enclosed_name() else {
+                        continue;
+                    };
+                    let (kind, location) = if file.is_dir() {
+                        (FileKind::Dir, Location::Dir)
+                    } else if file.is_file() {
+                        (FileKind::File, Location::Zip(i))
+                    } else {
+                        continue;
+                    };
+                    let modified = file.last_modified().and_then(|time| zip_time(&time));
+                    index.insert(path, IndexEntry { kind, len: file.size(), modified, location });
+                }
+            }
+            Format::Tar => {
+                let mut tar = tar::Archive::new(handle);
+                for entry in tar.entries_with_seek()? {
+                    let entry = entry?;
+                    let offset = entry.raw_file_position();
+                    index.insert_tar(&entry, Location::Tar(offset))?;
+                }
+            }
+            Format::TarGz => {
+                let mut tar = tar::Archive::new(GzDecoder::new(handle));
+                for entry in tar.entries()? {
+                    index.insert_tar(&entry?, Location::TarGz)?;
+                }
+            }
+        }
+        Ok(index)
+    }
+
+    fn insert_tar<R: Read>(&mut self, entry: &tar::Entry<'_, R>, location: Location) -> io::Result<()> {
+        let Some(path) = entry_path(&entry.path()?) else {
+            return Ok(());
+        };
+        let header = entry.header();
+        let (kind, location) = match header.entry_type() {
+            tar::EntryType::Directory => (FileKind::Dir, Location::Dir),
+            tar::EntryType::Regular | tar::EntryType::Continuous => (FileKind::File, location),
+            _ => return Ok(()),
+        };
+        let modified = header.mtime().ok().map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+        self.insert(path, IndexEntry { kind, len: entry.size(), modified, location });
+        Ok(())
+    }
+
+    /// Add an entry and any parent directories the archive leaves implicit
+    fn insert(&mut self, path: PathBuf, mut entry: IndexEntry) {
+        if path.as_os_str().is_empty() {
+            return;
+        }
+        for parent in path.ancestors().skip(1).filter(|p| !p.as_os_str().is_empty()) {
+            self.entries.entry(parent.to_path_buf()).or_insert(IndexEntry {
+                kind: FileKind::Dir,
+                len: 0,
+                modified: self.modified,
+                location: Location::Dir,
+            });
+        }
+        if entry.kind == FileKind::Dir {
+            entry.len = 0;
+        }
+        self.entries.insert(path, entry);
+    }
+
+    /// Metadata of `path` inside the archive; the empty path is the archive
+    /// itself, seen as a directory
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let (kind, len, modified) = match self.entries.get(path) {
+            Some(entry) => (entry.kind, entry.len, entry.modified),
+            None if path.as_os_str().is_empty() => (FileKind::Dir, 0, self.modified),
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is not in the archive", path.display()))),
+        };
+        Ok(Metadata { kind, len, modified, created: None, readonly: true, hidden: false, owner: None, group: None, mode: None })
+    }
+
+    /// Entries beneath `dir` inside the archive, at most `max_depth` levels
+    /// down, as paths under `archive`
+    fn walk(&self, archive: &Path, dir: &Path, max_depth: usize, skip_hidden: bool) -> Vec<DirEntry> {
+        self.entries.iter()
+            .filter_map(|(path, entry)| {
+                let relative = path.strip_prefix(dir).ok()?;
+                let depth = relative.components().count();
+                if depth == 0 || depth > max_depth {
+                    return None;
+                }
+                if skip_hidden && relative.components().any(|c| c.as_os_str().to_string_lossy().starts_with('.')) {
+                    return None;
+                }
+                Some(DirEntry { path: archive.join(path), kind: entry.kind })
+            })
+            .collect()
+    }
+}
+
+/// A relative path inside an archive, or None if it would leave the archive
+fn entry_path(path: &Path) -> Option<PathBuf> {
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => clean.push(name),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(clean)
+}
+
+/// Zip timestamps are local time with no zone; they are read as UTC
+fn zip_time(time: &zip::DateTime) -> Option<SystemTime> {
+    // Days since the epoch of a civil date (Howard Hinnant's algorithm)
+    let (year, month, day) = (i64::from(time.year()), i64::from(time.month()), i64::from(time.day()));
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let secs = days * 86_400 + i64::from(time.hour()) * 3600 + i64::from(time.minute()) * 60 + i64::from(time.second());
+    u64::try_from(secs).ok().map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// An index cached along with the archive state it was built from. Files
+/// that turn out not to be archives are cached too, without an index.
+struct CachedIndex {
+    len: u64,
+    modified: Option<SystemTime>,
+    index: Option<Arc<ArchiveIndex>>,
+}
+
+/// Presents zip and tar archives on another file system as read-only
+/// directories, so `/data/release.zip/docs/README.md` reads a file inside
+/// `release.zip` without extracting it.
+///
+/// Archives are recognized by name (`.zip`, `.jar`, `.tar`, `.tar.gz`,
+/// `.tgz`); one that cannot be parsed stays a plain file. Archives inside
+/// archives are not opened. Entry indexes are cached and rebuilt when the
+/// archive changes.
+pub struct ArchiveFileSystem {
+    inner: Arc<dyn FileSystem>,
+    indexes: Mutex<LruCache<PathBuf, CachedIndex>>,
+}
+
+impl std::fmt::Debug for ArchiveFileSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArchiveFileSystem").field("inner", &self.inner).finish()
+    }
+}
+
+impl ArchiveFileSystem {
+    pub fn new(inner: Arc<dyn FileSystem>) -> Self {
+        let capacity = NonZeroUsize::new(INDEX_CACHE_CAPACITY).expect("capacity is not zero");
+        Self { inner, indexes: Mutex::new(LruCache::new(capacity)) }
+    }
+
+    /// Split `path` into the archive it passes through, that archive's
+    /// index, and the remainder inside it
+    fn split(&self, path: &Path) -> io::Result<Option<(PathBuf, Arc<ArchiveIndex>, PathBuf)>> {
+        let mut prefix = PathBuf::new();
+        let mut components = path.components();
+        while let Some(component) = components.next() {
+            prefix.push(component);
+            let Some(index) = self.index(&prefix) else {
+                continue;
+            };
+            let rest = entry_path(components.as_path()).ok_or_else(|| io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Path {} leaves the archive {}", path.display(), prefix.display()),
+            ))?;
+            return Ok(Some((prefix, index, rest)));
+        }
+        Ok(None)
+    }
+
+    /// The index of `path` if it is a readable archive
+    fn index(&self, path: &Path) -> Option<Arc<ArchiveIndex>> {
+        let format = Format::of(path)?;
+        let metadata = self.inner.metadata(path).ok().filter(Metadata::is_file)?;
+        if let Some(cached) = self.indexes().get(path) {
+            if cached.len == metadata.len && cached.modified == metadata.modified {
+                return cached.index.clone();
+            }
+        }
+        let index = ArchiveIndex::build(self.inner.as_ref(), path, format, metadata.modified).ok().map(Arc::new);
+        self.indexes().put(path.to_path_buf(), CachedIndex {
+            len: metadata.len,
+            modified: metadata.modified,
+            index: index.clone(),
+        });
+        index
+    }
+
+    fn indexes(&self) -> MutexGuard<'_, LruCache<PathBuf, CachedIndex>> {
+        self.indexes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn refuse_in_archive(&self, path: &Path) -> io::Result<()> {
+        match self.split(path)? {
+            Some((archive, ..)) => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is inside the read-only archive {}", path.display(), archive.display()),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Report archives among `entries` as directories
+    fn mark_archives(&self, entries: &mut [DirEntry]) {
+        for entry in entries.iter_mut().filter(|entry| entry.kind == FileKind::File) {
+            if self.index(&entry.path).is_some() {
+                entry.kind = FileKind::Dir;
+            }
+        }
+    }
+
+    fn read_entry(&self, archive: &Path, path: &Path, entry: &IndexEntry) -> io::Result<Vec<u8>> {
+        let mut handle = self.inner.open(archive)?;
+        // The declared size bounds the read, whatever the data expands to
+        let mut contents = Vec::new();
+        match entry.location {
+            Location::Dir => return Err(io::Error::new(io::ErrorKind::IsADirectory, format!("{} is a directory", path.display()))),
+            Location::Zip(i) => {
+                let mut zip = zip::ZipArchive::new(handle)?;
+                zip.by_index(i)?.take(entry.len).read_to_end(&mut contents)?;
+            }
+            Location::Tar(offset) => {
+                handle.seek(SeekFrom::Start(offset))?;
+                handle.take(entry.len).read_to_end(&mut contents)?;
+            }
+            Location::TarGz => {
+                let mut tar = tar::Archive::new(GzDecoder::new(handle));
+                for found in tar.entries()? {
+                    let found = found?;
+                    if entry_path(&found.path()?).as_deref() == Some(path) {
+                        found.take(entry.len).read_to_end(&mut contents)?;
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(contents)
+    }
+}
+
+impl FileSystem for ArchiveFileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+        let Some((archive, index, rest)) = self.split(path)? else {
+            return self.inner.open(path);
+        };
+        let metadata = index.metadata(&rest)?;
+        let entry = index.entries.get(&rest)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::IsADirectory, format!("{} is an archive", path.display())))?;
+        let contents = self.read_entry(&archive, &rest, entry)?;
+        Ok(Box::new(MemoryHandle::new(contents, metadata)))
+    }
+
+    fn open_special(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+        match self.split(path)? {
+            None => self.inner.open_special(path),
+            Some(_) => self.open(path),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: &[u8], sync: bool) -> io::Result<()> {
+        self.refuse_in_archive(path)?;
+        self.inner.write(path, contents, sync)
+    }
+
+    fn write_in_place(&self, path: &Path, contents: &[u8], sync: bool) -> io::Result<()> {
+        self.refuse_in_archive(path)?;
+        self.inner.write_in_place(path, contents, sync)
+    }
+
+    fn write_at(&self, path: &Path, offset: u64, contents: &[u8], sync: bool) -> io::Result<u64> {
+        self.refuse_in_archive(path)?;
+        self.inner.write_at(path, offset, contents, sync)
+    }
+
+    fn append(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.refuse_in_archive(path)?;
+        self.inner.append(path, contents)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        match self.split(path)? {
+            Some((_, index, rest)) => index.metadata(&rest),
+            None => self.inner.metadata(path),
+        }
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        match self.split(path)? {
+            Some((_, index, rest)) => index.metadata(&rest),
+            None => self.inner.symlink_metadata(path),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.split(path)? {
+            Some((archive, index, rest)) => {
+                index.metadata(&rest)?;
+                Ok(self.inner.canonicalize(&archive)?.join(rest))
+            }
+            None => self.inner.canonicalize(path),
+        }
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let Some((archive, index, rest)) = self.split(path)? else {
+            let mut entries = self.inner.list(path)?;
+            self.mark_archives(&mut entries);
+            return Ok(entries);
+        };
+        if !index.metadata(&rest)?.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::NotADirectory, format!("{} is not a directory", path.display())));
+        }
+        Ok(index.walk(&archive, &rest, 1, false))
+    }
+
+    /// Walks the underlying file system, then the archives found there
+    fn walk(&self, root: &Path, options: &WalkOptions) -> io::Result<Vec<DirEntry>> {
+        if let Some((archive, index, rest)) = self.split(root)? {
+            return Ok(index.walk(&archive, &rest, options.max_depth, options.skip_hidden));
+        }
+
+        let mut entries = self.inner.walk(root, options)?;
+        let mut inside = Vec::new();
+        for entry in entries.iter_mut().filter(|entry| entry.kind == FileKind::File) {
+            let Some(index) = self.index(&entry.path) else {
+                continue;
+            };
+            entry.kind = FileKind::Dir;
+            let depth = entry.path.strip_prefix(root).map_or(0, |p| p.components().count());
+            let remaining = options.max_depth.saturating_sub(depth);
+            inside.extend(index.walk(&entry.path, Path::new(""), remaining, options.skip_hidden));
+        }
+        entries.extend(inside);
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+
+    fn create_dir(&self, path: &Path, recursive: bool) -> io::Result<()> {
+        self.refuse_in_archive(path)?;
+        self.inner.create_dir(path, recursive)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.refuse_in_archive(path)?;
+        self.inner.remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path, recursive: bool) -> io::Result<()> {
+        self.refuse_in_archive(path)?;
+        self.inner.remove_dir(path, recursive)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.refuse_in_archive(from)?;
+        self.refuse_in_archive(to)?;
+        self.inner.rename(from, to)
+    }
+
+    /// Copying out of an archive extracts the entry
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        self.refuse_in_archive(to)?;
+        if self.split(from)?.is_none() {
+            return self.inner.copy(from, to);
+        }
+        let contents = self.read(from)?;
+        self.inner.write(to, &contents, false)?;
+        Ok(contents.len() as u64)
+    }
+
+    /// Entries extracted from an archive keep the attributes a new file gets
+    fn copy_attributes(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.refuse_in_archive(to)?;
+        match self.split(from)? {
+            None => self.inner.copy_attributes(from, to),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn containing_archive(&self, path: &Path) -> Option<PathBuf> {
+        self.split(path).ok().flatten().map(|(archive, ..)| archive)
+    }
+
+    fn user_id(&self) -> Option<u32> {
+        self.inner.user_id()
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+        self.refuse_in_archive(path)?;
+        self.inner.set_mode(path, mode)
+    }
+
+    /// Entries inside an archive have none
+    fn extended_attributes(&self, path: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+        match self.split(path)? {
+            None => self.inner.extended_attributes(path),
+            Some(_) => Ok(Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemoryFileSystem;
+    use std::io::{Cursor, Write};
+
+    fn zip_bytes(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn tar_bytes(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(1_700_000_000);
+            header.set_cksum();
+            builder.append_data(&mut header, name, contents.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn archives() -> ArchiveFileSystem {
+        let memory = MemoryFileSystem::new();
+        let files = [("docs/readme.md", "hello"), ("src/main.rs", "fn main() {}")];
+        memory.insert_file("/data/release.zip", zip_bytes(&files)).unwrap();
+        memory.insert_file("/data/release.tar", tar_bytes(&files)).unwrap();
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gz.write_all(&tar_bytes(&files)).unwrap();
+        memory.insert_file("/data/release.tgz", gz.finish().unwrap()).unwrap();
+        memory.insert_file("/data/broken.zip", "not a zip").unwrap();
+        ArchiveFileSystem::new(Arc::new(memory))
+    }
+
+    #[test]
+    fn test_archives_read_as_directories() {
+        let fs = archives();
+        for name in ["release.zip", "release.tar", "release.tgz"] {
+            let archive = Path::new("/data").join(name);
+            assert!(fs.metadata(&archive).unwrap().is_dir(), "{}", name);
+            assert_eq!(fs.read(&archive.join("docs/readme.md")).unwrap(), b"hello", "{}", name);
+            assert_eq!(fs.metadata(&archive.join("src/main.rs")).unwrap().len, 12);
+
+            let listed: Vec<_> = fs.list(&archive).unwrap().into_iter().map(|e| e.path).collect();
+            assert_eq!(listed, vec![archive.join("docs"), archive.join("src")]);
+            assert!(fs.metadata(&archive.join("missing.txt")).is_err());
+        }
+
+        // A file that only looks like an archive stays a file
+        assert!(fs.metadata(Path::new("/data/broken.zip")).unwrap().is_file());
+        assert_eq!(fs.read(Path::new("/data/broken.zip")).unwrap(), b"not a zip");
+
+        let walked = fs.walk(Path::new("/data"), &WalkOptions::default()).unwrap();
+        assert!(walked.iter().any(|e| e.path == Path::new("/data/release.tgz/src/main.rs")));
+        assert!(walked.iter().any(|e| e.path == Path::new("/data/release.zip") && e.kind == FileKind::Dir));
+        assert_eq!(fs.containing_archive(Path::new("/data/release.tar/src")), Some(PathBuf::from("/data/release.tar")));
+    }
+
+    #[test]
+    fn test_archives_are_read_only() {
+        let fs = archives();
+        let inside = Path::new("/data/release.zip/docs/readme.md");
+        let err = fs.write(inside, b"changed", false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(fs.remove_dir(Path::new("/data/release.zip"), true).is_err());
+        assert!(fs.rename(inside, Path::new("/data/out.md")).is_err());
+        assert!(fs.read(Path::new("/data/release.zip/../release.tar/src/main.rs")).is_err());
+
+        // Copying out extracts the entry
+        assert_eq!(fs.copy(inside, Path::new("/data/readme.md")).unwrap(), 5);
+        assert_eq!(fs.read(Path::new("/data/readme.md")).unwrap(), b"hello");
+    }
+}
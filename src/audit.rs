@@ -0,0 +1,155 @@
+use crate::error::{Denial, FileJackError, PolicyRule, Result};
+use crate::logging;
+use crate::middleware::{Call, Middleware};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// A tool call as recorded in an audit log
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditEvent<'a> {
+    /// Seconds since the Unix epoch when the call finished
+    pub timestamp: u64,
+    pub session: &'a str,
+    pub tool: &'a str,
+    /// Arguments with file contents replaced by their length
+    pub arguments: Value,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The policy rule that denied the call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<PolicyRule>,
+    pub duration_ms: u64,
+}
+
+impl<'a> AuditEvent<'a> {
+    /// An event for a call that failed with `error`, or succeeded without one
+    pub fn new(
+        session: &'a str,
+        tool: &'a str,
+        arguments: &Value,
+        error: Option<&FileJackError>,
+        duration: Duration,
+    ) -> Self {
+        Self {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            session,
+            tool,
+            arguments: logging::redact(arguments),
+            success: error.is_none(),
+            error: error.map(FileJackError::to_string),
+            rule: match error {
+                Some(FileJackError::PermissionDenied(Denial { rule, .. })) => rule.clone(),
+                _ => None,
+            },
+            duration_ms: duration.as_millis() as u64,
+        }
+    }
+}
+
+/// Receives a record of every tool call a server handles, successful or
+/// not. Implementations must not block for long, since they run on the
+/// request path.
+pub trait AuditLog: Send + Sync {
+    fn record(&self, event: &AuditEvent<'_>);
+}
+
+/// Appends each event to a file as one line of JSON
+#[derive(Debug)]
+pub struct JsonLinesAuditLog {
+    file: Mutex<File>,
+}
+
+impl JsonLinesAuditLog {
+    /// Append to `path`, creating it if missing
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+}
+
+impl AuditLog for JsonLinesAuditLog {
+    fn record(&self, event: &AuditEvent<'_>) {
+        let mut line = serde_json::to_vec(event).unwrap_or_default();
+        line.push(b'\n');
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = file.write_all(&line) {
+            warn!(error = %e, "Could not write audit log entry");
+        }
+    }
+}
+
+/// Records every tool call that reaches it in an [`AuditLog`]
+pub struct AuditMiddleware {
+    log: Arc<dyn AuditLog>,
+}
+
+impl AuditMiddleware {
+    pub fn new(log: Arc<dyn AuditLog>) -> Self {
+        Self { log }
+    }
+
+    fn record(&self, call: &Call<'_>, arguments: &Value, error: Option<&FileJackError>) {
+        if let Some(tool) = call.tool() {
+            self.log.record(&AuditEvent::new(call.session().id(), tool, arguments, error, call.elapsed()));
+        }
+    }
+}
+
+impl Middleware for AuditMiddleware {
+    fn after_call(&self, call: &Call<'_>, params: &Value, _result: &mut Value) -> Result<()> {
+        self.record(call, params, None);
+        Ok(())
+    }
+
+    fn on_error(&self, call: &Call<'_>, params: &Value, error: &FileJackError) {
+        self.record(call, params, Some(error));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_lines_audit_log() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("audit.jsonl");
+        let log = JsonLinesAuditLog::open(&path).unwrap();
+
+        let arguments = json!({"path": "/a.txt", "content": "secret"});
+        log.record(&AuditEvent::new("s1", "write_file", &arguments, None, Duration::from_millis(3)));
+        let failed = FileJackError::FileNotFound("/b.txt".to_string());
+        log.record(&AuditEvent::new("s1", "read_file", &json!({"path": "/b.txt"}), Some(&failed), Duration::ZERO));
+
+        let lines: Vec<Value> = std::fs::read_to_string(&path).unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["arguments"], json!({"path": "/a.txt", "content": "<redacted 6 bytes>"}));
+        assert_eq!((lines[0]["success"].as_bool(), lines[0]["duration_ms"].as_u64()), (Some(true), Some(3)));
+        assert!(lines[0].get("error").is_none());
+        assert_eq!(lines[1]["success"], false);
+        assert!(lines[1]["error"].as_str().unwrap().contains("/b.txt"));
+        assert!(lines[1].get("rule").is_none());
+    }
+
+    #[test]
+    fn test_denials_record_their_rule() {
+        let denied = FileJackError::PermissionDenied(Denial::by(
+            PolicyRule::new("no-secrets", crate::error::RuleCategory::DeniedPath),
+            "Access to /srv/secrets is explicitly denied",
+        ));
+        let event = AuditEvent::new("s1", "read_file", &json!({}), Some(&denied), Duration::ZERO);
+        let line = serde_json::to_value(&event).unwrap();
+        assert_eq!(line["rule"], json!({"id": "no-secrets", "category": "denied_path"}));
+    }
+}
@@ -0,0 +1,179 @@
+//! Titles and descriptions shown for tools in `tools/list`.
+//!
+//! Every built-in tool has a title in the embedded catalog, such as "Read a
+//! file" for `read_file`, which host UIs can show instead of its name. The
+//! config's `tool_metadata` table sets the title and description of any
+//! tool, including plugin and custom ones, and may give them per locale:
+//!
+//! ```toml
+//! [server]
+//! locale = "de-AT"
+//!
+//! [tool_metadata.read_file]
+//! title = "Read a file"
+//!
+//! [tool_metadata.read_file.locales.de]
+//! title = "Datei lesen"
+//! description = "Liest den Inhalt einer Datei"
+//! ```
+//!
+//! Text for the server's `locale` is used first, then text for its language
+//! alone (`de` for `de-AT`), then the untranslated entry. Config entries win
+//! over the catalog field by field, and a tool without either keeps the
+//! description it was registered with.
+
+use crate::error::{FileJackError, Result};
+use crate::protocol::McpTool;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// Title and description of a tool in one language
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolText {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// What `tools/list` shows for a tool, from the catalog or `tool_metadata`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolMetadata {
+    #[serde(flatten)]
+    pub text: ToolText,
+    /// Translations, by locale such as `de` or `pt-BR`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub locales: BTreeMap<String, ToolText>,
+}
+
+impl ToolMetadata {
+    /// The text for `locale`, falling back to its language and then to the
+    /// untranslated text, field by field
+    fn text_for(&self, locale: Option<&str>) -> ToolText {
+        let mut text = self.text.clone();
+        let Some(locale) = locale else { return text };
+        let language = locale.split(['-', '_']).next().unwrap_or(locale);
+        for candidate in [language, locale] {
+            if let Some(translated) = self.locales.get(candidate) {
+                text.merge(translated);
+            }
+        }
+        text
+    }
+}
+
+impl ToolText {
+    /// Take the fields `other` sets
+    fn merge(&mut self, other: &ToolText) {
+        if other.title.is_some() {
+            self.title = other.title.clone();
+        }
+        if other.description.is_some() {
+            self.description = other.description.clone();
+        }
+    }
+}
+
+/// Titles of the built-in tools, embedded in the binary
+const BUILTIN_CATALOG: &str = include_str!("catalog.toml");
+
+fn builtin_entries() -> &'static BTreeMap<String, ToolMetadata> {
+    static ENTRIES: OnceLock<BTreeMap<String, ToolMetadata>> = OnceLock::new();
+    ENTRIES.get_or_init(|| parse(BUILTIN_CATALOG).expect("embedded tool catalog is valid"))
+}
+
+fn parse(catalog: &str) -> Result<BTreeMap<String, ToolMetadata>> {
+    toml::from_str(catalog).map_err(|e| FileJackError::ConfigError(format!("Invalid tool catalog: {}", e)))
+}
+
+/// The embedded catalog with the configured `tool_metadata` over it, for
+/// one locale
+#[derive(Debug, Default)]
+pub struct ToolCatalog {
+    configured: BTreeMap<String, ToolMetadata>,
+    locale: Option<String>,
+}
+
+impl ToolCatalog {
+    pub fn new(configured: &BTreeMap<String, ToolMetadata>, locale: Option<&str>) -> Self {
+        Self { configured: configured.clone(), locale: locale.map(str::to_string) }
+    }
+
+    /// The title and description shown for the tool `name`
+    pub fn text(&self, name: &str) -> ToolText {
+        let locale = self.locale.as_deref();
+        let mut text = builtin_entries().get(name).map(|entry| entry.text_for(locale)).unwrap_or_default();
+        if let Some(configured) = self.configured.get(name) {
+            text.merge(&configured.text_for(locale));
+        }
+        text
+    }
+
+    /// Fill in the titles and descriptions of `tools`
+    pub fn apply(&self, tools: &mut [McpTool]) {
+        for tool in tools {
+            let text = self.text(&tool.name);
+            if text.title.is_some() {
+                tool.title = text.title;
+            }
+            if let Some(description) = text.description {
+                tool.description = description;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access_control::AccessPolicy;
+    use crate::mcp::McpServer;
+
+    #[test]
+    fn test_every_builtin_tool_has_a_title() {
+        let server = McpServer::new(AccessPolicy::permissive());
+        let catalog = ToolCatalog::default();
+        for tool in server.list_tools() {
+            assert!(catalog.text(&tool.name).title.is_some(), "{} has no title", tool.name);
+        }
+        assert_eq!(catalog.text("read_file").title.as_deref(), Some("Read a file"));
+        assert_eq!(catalog.text("read_file").description, None);
+    }
+
+    #[test]
+    fn test_configured_text_and_locale_fallback() {
+        let configured = parse(
+            r#"
+            [read_file]
+            description = "Read one file"
+
+            [read_file.locales.de]
+            title = "Datei lesen"
+
+            [read_file.locales.de-AT]
+            description = "Eine Datei lesen"
+
+            [word_count]
+            title = "Count words"
+            "#,
+        )
+        .unwrap();
+
+        let english = ToolCatalog::new(&configured, None);
+        assert_eq!(english.text("read_file"), ToolText {
+            title: Some("Read a file".to_string()),
+            description: Some("Read one file".to_string()),
+        });
+        assert_eq!(english.text("word_count").title.as_deref(), Some("Count words"));
+
+        let austrian = ToolCatalog::new(&configured, Some("de-AT"));
+        assert_eq!(austrian.text("read_file"), ToolText {
+            title: Some("Datei lesen".to_string()),
+            description: Some("Eine Datei lesen".to_string()),
+        });
+        let swiss = ToolCatalog::new(&configured, Some("de_CH"));
+        assert_eq!(swiss.text("read_file").description.as_deref(), Some("Read one file"));
+        assert_eq!(swiss.text("read_file").title.as_deref(), Some("Datei lesen"));
+    }
+}
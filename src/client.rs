@@ -0,0 +1,271 @@
+use crate::error::{FileJackError, Result};
+use crate::mcp::McpServer;
+use crate::protocol::McpTool;
+use crate::transport::{self, ChannelTransport, Framing, StreamTransport, Transport};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::io::BufReader;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Name the client reports in `initialize`
+pub const CLIENT_NAME: &str = "filejack-client";
+
+/// A minimal MCP client, for driving FileJack (or any MCP server) from
+/// tests or from another server.
+///
+/// Requests are answered in order; notifications that arrive while waiting
+/// for a response are queued for [`McpClient::take_notifications`].
+pub struct McpClient {
+    transport: Box<dyn Transport + Send>,
+    next_id: u64,
+    notifications: VecDeque<Value>,
+    /// The server process, when this client started it
+    child: Option<Child>,
+}
+
+impl McpClient {
+    /// Talk to a server over an already connected transport
+    pub fn new(transport: impl Transport + Send + 'static) -> Self {
+        Self {
+            transport: Box::new(transport),
+            next_id: 1,
+            notifications: VecDeque::new(),
+            child: None,
+        }
+    }
+
+    /// Start `command` and talk to it over its stdin and stdout. The
+    /// server's stderr is passed through.
+    pub fn spawn(mut command: Command) -> Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            let _ = child.kill();
+            return Err(FileJackError::ProtocolError("Server process has no stdio pipes".to_string()));
+        };
+        let transport = StreamTransport::new(BufReader::new(stdout), stdin).with_framing(Framing::Newline);
+        let mut client = Self::new(transport);
+        client.child = Some(child);
+        Ok(client)
+    }
+
+    /// Connect to a server listening on a Unix domain socket
+    #[cfg(unix)]
+    pub fn connect_socket(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let stream = std::os::unix::net::UnixStream::connect(path)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(Self::new(StreamTransport::new(reader, stream).with_framing(Framing::Newline)))
+    }
+
+    /// Serve `server` on a background thread in a session of its own and
+    /// connect to it
+    pub fn in_process(server: Arc<McpServer>) -> Self {
+        let (mut server_end, client_end) = ChannelTransport::pair();
+        std::thread::spawn(move || {
+            let session = server.open_session("in-process");
+            if let Err(e) = transport::serve_session(&mut server_end, &server, &session) {
+                debug!("In-process server stopped: {}", e);
+            }
+        });
+        Self::new(client_end)
+    }
+
+    /// Send a request and wait for its result. JSON-RPC errors become
+    /// [`FileJackError::ProtocolError`].
+    pub fn request(&mut self, method: &str, params: Option<Value>) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut request = json!({"jsonrpc": "2.0", "id": id, "method": method});
+        if let Some(params) = params {
+            request["params"] = params;
+        }
+        self.transport.write_message(&request.to_string())?;
+
+        loop {
+            let Some(message) = self.transport.read_message()? else {
+                return Err(FileJackError::Io(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!("Server closed the connection before answering {}", method),
+                )));
+            };
+            let message: Value = serde_json::from_str(&message)?;
+            if message.get("id") != Some(&json!(id)) {
+                self.receive_other(message)?;
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                let code = error.get("code").and_then(Value::as_i64).unwrap_or_default();
+                let text = error.get("message").and_then(Value::as_str).unwrap_or("unknown error");
+                return Err(FileJackError::ProtocolError(format!("{} (code {})", text, code)));
+            }
+            return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    /// Send a notification, which gets no response
+    pub fn notify(&mut self, method: &str, params: Option<Value>) -> Result<()> {
+        let mut notification = json!({"jsonrpc": "2.0", "method": method});
+        if let Some(params) = params {
+            notification["params"] = params;
+        }
+        self.transport.write_message(&notification.to_string())
+    }
+
+    /// A message that is not the awaited response: queue notifications and
+    /// turn down requests from the server, which this client does not serve
+    fn receive_other(&mut self, message: Value) -> Result<()> {
+        match (message.get("id"), message.get("method")) {
+            (Some(id), Some(method)) => {
+                warn!(method = %method, "Declining request from server");
+                let reply = json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": -32601, "message": format!("Method not found: {}", method)}
+                });
+                self.transport.write_message(&reply.to_string())
+            }
+            (None, Some(_)) => {
+                self.notifications.push_back(message);
+                Ok(())
+            }
+            _ => {
+                debug!("Ignoring unexpected response: {}", message);
+                Ok(())
+            }
+        }
+    }
+
+    /// Notifications received so far, oldest first
+    pub fn take_notifications(&mut self) -> Vec<Value> {
+        self.notifications.drain(..).collect()
+    }
+
+    /// Perform the `initialize` handshake; returns the server's result
+    pub fn initialize(&mut self) -> Result<Value> {
+        let result = self.request("initialize", Some(json!({
+            "protocolVersion": "2025-03-26",
+            "capabilities": {},
+            "clientInfo": {"name": CLIENT_NAME, "version": env!("CARGO_PKG_VERSION")}
+        })))?;
+        self.notify("notifications/initialized", None)?;
+        Ok(result)
+    }
+
+    pub fn list_tools(&mut self) -> Result<Vec<McpTool>> {
+        let result = self.request("tools/list", None)?;
+        let tools = result.get("tools").cloned().unwrap_or_else(|| json!([]));
+        Ok(serde_json::from_value(tools)?)
+    }
+
+    /// Call a tool and return its result object. A result flagged
+    /// `isError` becomes [`FileJackError::ProtocolError`].
+    pub fn call_tool(&mut self, name: &str, arguments: Value) -> Result<Value> {
+        let result = self.request("tools/call", Some(json!({"name": name, "arguments": arguments})))?;
+        if result.get("isError").and_then(Value::as_bool) == Some(true) {
+            return Err(FileJackError::ProtocolError(content_text(&result)));
+        }
+        Ok(result)
+    }
+
+    /// Call a tool and return the text of its content blocks, joined
+    pub fn call_tool_text(&mut self, name: &str, arguments: Value) -> Result<String> {
+        self.call_tool(name, arguments).map(|result| content_text(&result))
+    }
+
+    pub fn read_file(&mut self, path: &str) -> Result<String> {
+        self.call_tool_text("read_file", json!({"path": path}))
+    }
+
+    pub fn write_file(&mut self, path: &str, content: &str) -> Result<()> {
+        self.call_tool("write_file", json!({"path": path, "content": content})).map(drop)
+    }
+
+    pub fn append_file(&mut self, path: &str, content: &str) -> Result<()> {
+        self.call_tool("append_file", json!({"path": path, "content": content})).map(drop)
+    }
+
+    pub fn delete_file(&mut self, path: &str) -> Result<()> {
+        self.call_tool("delete_file", json!({"path": path})).map(drop)
+    }
+
+    pub fn file_exists(&mut self, path: &str) -> Result<bool> {
+        Ok(self.call_tool_text("file_exists", json!({"path": path}))?.trim() == "true")
+    }
+
+    /// Entries of a directory, as the JSON the server returns
+    pub fn list_directory(&mut self, path: &str, recursive: bool) -> Result<Value> {
+        let text = self.call_tool_text("list_directory", json!({"path": path, "recursive": recursive}))?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Close the connection and, if this client started the server, wait
+    /// for it to exit
+    pub fn close(mut self) -> Result<()> {
+        // Dropping the transport closes the server's stdin, which tells a
+        // stdio server to exit
+        self.transport = Box::new(ChannelTransport::pair().0);
+        if let Some(mut child) = self.child.take() {
+            child.wait()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for McpClient {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Text of every `text` content block in a tool result, joined by newlines
+fn content_text(result: &Value) -> String {
+    result.get("content")
+        .and_then(Value::as_array)
+        .map(|blocks| blocks.iter()
+            .filter_map(|block| block.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access_control::AccessPolicy;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_in_process_client() {
+        let temp_dir = TempDir::new().unwrap();
+        let server = Arc::new(McpServer::new(AccessPolicy::restricted(temp_dir.path().to_path_buf())));
+        let mut client = McpClient::in_process(Arc::clone(&server));
+
+        let info = client.initialize().unwrap();
+        assert_eq!(info["serverInfo"]["name"], "FileJack");
+        assert!(client.list_tools().unwrap().iter().any(|tool| tool.name == "read_file"));
+
+        let path = temp_dir.path().join("notes.txt");
+        let path = path.to_str().unwrap();
+        client.write_file(path, "hello").unwrap();
+        client.append_file(path, " world").unwrap();
+        assert_eq!(client.read_file(path).unwrap(), "hello world");
+        assert!(client.file_exists(path).unwrap());
+
+        let err = client.read_file("/etc/passwd").unwrap_err();
+        assert!(matches!(err, FileJackError::ProtocolError(_)), "{}", err);
+        assert!(err.to_string().contains("code -32000"));
+
+        client.delete_file(path).unwrap();
+        assert!(!client.file_exists(path).unwrap());
+        client.close().unwrap();
+    }
+}
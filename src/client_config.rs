@@ -0,0 +1,153 @@
+use crate::error::{FileJackError, Result};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Name FileJack is registered under in generated client configurations
+pub const CLIENT_SERVER_NAME: &str = "filejack";
+
+/// MCP host applications that configuration snippets can be generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientKind {
+    ClaudeDesktop,
+    VsCode,
+    Cursor,
+    Generic,
+}
+
+impl ClientKind {
+    /// Every supported client, in display order
+    pub const ALL: [ClientKind; 4] = [
+        ClientKind::ClaudeDesktop,
+        ClientKind::VsCode,
+        ClientKind::Cursor,
+        ClientKind::Generic,
+    ];
+
+    /// Human-readable client name
+    pub fn display_name(self) -> &'static str {
+        match self {
+            ClientKind::ClaudeDesktop => "Claude Desktop",
+            ClientKind::VsCode => "VS Code",
+            ClientKind::Cursor => "Cursor",
+            ClientKind::Generic => "Generic MCP host",
+        }
+    }
+
+    /// Where the snippet belongs
+    pub fn config_location(self) -> &'static str {
+        match self {
+            ClientKind::ClaudeDesktop => {
+                "claude_desktop_config.json (macOS: ~/Library/Application Support/Claude/, \
+                 Windows: %APPDATA%\\Claude\\)"
+            }
+            ClientKind::VsCode => ".vscode/mcp.json in your workspace",
+            ClientKind::Cursor => ".cursor/mcp.json in your project, or ~/.cursor/mcp.json",
+            ClientKind::Generic => "your MCP host's stdio server settings",
+        }
+    }
+}
+
+impl std::str::FromStr for ClientKind {
+    type Err = FileJackError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "claude" | "claude-desktop" => Ok(ClientKind::ClaudeDesktop),
+            "vscode" | "vs-code" | "code" => Ok(ClientKind::VsCode),
+            "cursor" => Ok(ClientKind::Cursor),
+            "generic" => Ok(ClientKind::Generic),
+            other => Err(FileJackError::InvalidParameters(format!(
+                "Unknown client '{}', expected claude, vscode, cursor, or generic", other
+            ))),
+        }
+    }
+}
+
+/// How an MCP host should start FileJack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchSpec {
+    pub command: PathBuf,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+}
+
+impl LaunchSpec {
+    /// Launch `command` with no arguments or environment
+    pub fn new(command: PathBuf) -> Self {
+        Self {
+            command,
+            args: Vec::new(),
+            env: BTreeMap::new(),
+        }
+    }
+
+    /// Add a command-line argument
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Set an environment variable
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(name.into(), value.into());
+        self
+    }
+
+    /// Ready-to-paste configuration for `client`
+    pub fn snippet(&self, client: ClientKind) -> Value {
+        let mut server = json!({
+            "command": self.command.display().to_string(),
+            "args": self.args,
+            "env": self.env,
+        });
+        match client {
+            ClientKind::ClaudeDesktop | ClientKind::Cursor => json!({
+                "mcpServers": { CLIENT_SERVER_NAME: server }
+            }),
+            ClientKind::VsCode => {
+                server["type"] = json!("stdio");
+                json!({ "servers": { CLIENT_SERVER_NAME: server } })
+            }
+            ClientKind::Generic => server,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> LaunchSpec {
+        LaunchSpec::new(PathBuf::from("/opt/filejack/bin/filejack"))
+            .arg("--read-only")
+            .env("FILEJACK_CONFIG", "/home/user/project/filejack.json")
+    }
+
+    #[test]
+    fn test_client_snippet_shapes() {
+        let claude = spec().snippet(ClientKind::ClaudeDesktop);
+        let server = &claude["mcpServers"]["filejack"];
+        assert_eq!(server["command"], "/opt/filejack/bin/filejack");
+        assert_eq!(server["args"], json!(["--read-only"]));
+        assert_eq!(server["env"]["FILEJACK_CONFIG"], "/home/user/project/filejack.json");
+
+        assert_eq!(spec().snippet(ClientKind::Cursor), claude);
+
+        let vscode = spec().snippet(ClientKind::VsCode);
+        assert_eq!(vscode["servers"]["filejack"]["type"], "stdio");
+        assert_eq!(vscode["servers"]["filejack"]["command"], "/opt/filejack/bin/filejack");
+
+        let generic = spec().snippet(ClientKind::Generic);
+        assert_eq!(generic["args"], json!(["--read-only"]));
+        assert!(generic.get("mcpServers").is_none());
+    }
+
+    #[test]
+    fn test_client_kind_parsing() {
+        assert_eq!("Claude".parse::<ClientKind>().unwrap(), ClientKind::ClaudeDesktop);
+        assert_eq!("vscode".parse::<ClientKind>().unwrap(), ClientKind::VsCode);
+        assert_eq!("cursor".parse::<ClientKind>().unwrap(), ClientKind::Cursor);
+        assert!("zed".parse::<ClientKind>().is_err());
+    }
+}
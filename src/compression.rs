@@ -0,0 +1,254 @@
+use crate::error::{FileJackError, Result};
+use crate::file_ops::{FileReader, FileWriter};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Most bytes `decompress_file` writes when no limit is given
+pub const DEFAULT_MAX_DECOMPRESSED_BYTES: u64 = 256 * 1024 * 1024;
+
+/// A single-file compression format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionFormat {
+    #[default]
+    Gzip,
+    /// Zstandard, in builds with the `zstd` feature
+    Zstd,
+}
+
+impl CompressionFormat {
+    /// Formats this build can read and write
+    pub fn available() -> &'static [CompressionFormat] {
+        if cfg!(feature = "zstd") {
+            &[CompressionFormat::Gzip, CompressionFormat::Zstd]
+        } else {
+            &[CompressionFormat::Gzip]
+        }
+    }
+
+    /// File extension, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            CompressionFormat::Gzip => "gz",
+            CompressionFormat::Zstd => "zst",
+        }
+    }
+
+    /// Recognize a compressed stream by its magic number
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            Some(CompressionFormat::Gzip)
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(CompressionFormat::Zstd)
+        } else {
+            None
+        }
+    }
+
+    fn default_level(self) -> i32 {
+        match self {
+            CompressionFormat::Gzip => 6,
+            CompressionFormat::Zstd => 3,
+        }
+    }
+
+    fn levels(self) -> std::ops::RangeInclusive<i32> {
+        match self {
+            CompressionFormat::Gzip => 0..=9,
+            CompressionFormat::Zstd => 1..=22,
+        }
+    }
+}
+
+/// Lists only the formats this build supports, so clients are not offered
+/// `zstd` when it would be refused
+impl JsonSchema for CompressionFormat {
+    fn schema_name() -> Cow<'static, str> {
+        "CompressionFormat".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "enum": Self::available(),
+        })
+    }
+}
+
+/// Result of `compress_file` and `decompress_file`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CompressionSummary {
+    pub source: String,
+    pub destination: String,
+    pub format: CompressionFormat,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+/// Compress the file at `from` into `to`, which defaults to `from` with the
+/// format's extension appended. The source is left in place and an existing
+/// destination is replaced.
+pub fn compress_file(
+    reader: &FileReader,
+    writer: &FileWriter,
+    from: &Path,
+    to: Option<&Path>,
+    format: CompressionFormat,
+    level: Option<i32>,
+) -> Result<CompressionSummary> {
+    let level = level.unwrap_or(format.default_level());
+    if !format.levels().contains(&level) {
+        return Err(FileJackError::InvalidParameters(format!(
+            "Compression level for {:?} must be between {} and {}",
+            format,
+            format.levels().start(),
+            format.levels().end()
+        )));
+    }
+    let to = to.map(Path::to_path_buf).unwrap_or_else(|| compressed_path(from, format));
+
+    let input = reader.read_to_bytes(from)?;
+    let output = compress(&input, format, level)?;
+    writer.write_bytes(&to, &output)?;
+    Ok(summary(from, &to, format, &input, &output))
+}
+
+/// Decompress the file at `from` into `to`, which defaults to
+/// [`decompressed_path`]. The format is recognized from the file's contents
+/// when not given.
+///
+/// Fails without writing anything once the output would exceed `max_size`
+/// bytes or the policy's file size limit, whichever is smaller, so a small
+/// archive cannot expand into a huge file.
+pub fn decompress_file(
+    reader: &FileReader,
+    writer: &FileWriter,
+    from: &Path,
+    to: Option<&Path>,
+    format: Option<CompressionFormat>,
+    max_size: Option<u64>,
+) -> Result<CompressionSummary> {
+    let to = match to {
+        Some(to) => to.to_path_buf(),
+        None => decompressed_path(from).ok_or_else(|| {
+            FileJackError::InvalidParameters(format!(
+                "{} does not end in .gz or .zst; give a destination",
+                from.display()
+            ))
+        })?,
+    };
+    let input = reader.read_to_bytes(from)?;
+    let format = format.or_else(|| CompressionFormat::detect(&input)).ok_or_else(|| {
+        FileJackError::InvalidParameters(format!("{} is not a gzip or zstd file", from.display()))
+    })?;
+
+    let mut limit = max_size.unwrap_or(DEFAULT_MAX_DECOMPRESSED_BYTES);
+    if writer.policy().max_file_size > 0 {
+        limit = limit.min(writer.policy().max_file_size);
+    }
+    let output = decompress(&input, format, limit)?;
+    writer.write_bytes(&to, &output)?;
+    Ok(summary(from, &to, format, &input, &output))
+}
+
+/// Where `compress_file` writes by default: `from` with the format's
+/// extension appended
+pub fn compressed_path(from: &Path, format: CompressionFormat) -> PathBuf {
+    let mut name = from.as_os_str().to_owned();
+    name.push(".");
+    name.push(format.extension());
+    PathBuf::from(name)
+}
+
+/// Where `decompress_file` writes by default: `from` without its `.gz` or
+/// `.zst` extension, or `None` when it has neither
+pub fn decompressed_path(from: &Path) -> Option<PathBuf> {
+    let extension = from.extension()?;
+    [CompressionFormat::Gzip, CompressionFormat::Zstd]
+        .iter()
+        .any(|format| extension == format.extension())
+        .then(|| from.with_extension(""))
+}
+
+fn summary(from: &Path, to: &Path, format: CompressionFormat, input: &[u8], output: &[u8]) -> CompressionSummary {
+    CompressionSummary {
+        source: from.display().to_string(),
+        destination: to.display().to_string(),
+        format,
+        bytes_in: input.len() as u64,
+        bytes_out: output.len() as u64,
+    }
+}
+
+/// Compress `bytes` at `level`
+pub fn compress(bytes: &[u8], format: CompressionFormat, level: i32) -> Result<Vec<u8>> {
+    match format {
+        CompressionFormat::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level as u32));
+            encoder.write_all(bytes)?;
+            Ok(encoder.finish()?)
+        }
+        #[cfg(feature = "zstd")]
+        CompressionFormat::Zstd => Ok(zstd::encode_all(bytes, level)?),
+        #[cfg(not(feature = "zstd"))]
+        CompressionFormat::Zstd => Err(zstd_unavailable()),
+    }
+}
+
+/// Decompress `bytes`, failing once the output passes `limit` bytes
+pub fn decompress(bytes: &[u8], format: CompressionFormat, limit: u64) -> Result<Vec<u8>> {
+    let decoder: Box<dyn Read + '_> = match format {
+        CompressionFormat::Gzip => Box::new(flate2::read::MultiGzDecoder::new(bytes)),
+        #[cfg(feature = "zstd")]
+        CompressionFormat::Zstd => Box::new(zstd::Decoder::with_buffer(bytes)?),
+        #[cfg(not(feature = "zstd"))]
+        CompressionFormat::Zstd => return Err(zstd_unavailable()),
+    };
+
+    let mut output = Vec::new();
+    decoder.take(limit.saturating_add(1)).read_to_end(&mut output).map_err(|e| {
+        FileJackError::InvalidParameters(format!("Could not decompress {:?} data: {}", format, e))
+    })?;
+    if output.len() as u64 > limit {
+        return Err(FileJackError::PermissionDenied(format!(
+            "Decompressed size exceeds the limit of {} bytes",
+            limit
+        ).into()));
+    }
+    Ok(output)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_unavailable() -> FileJackError {
+    FileJackError::InvalidParameters("zstd support is not built in (enable the `zstd` feature)".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = "log line\n".repeat(1000);
+        for &format in CompressionFormat::available() {
+            let compressed = compress(text.as_bytes(), format, format.default_level()).unwrap();
+            assert_eq!(CompressionFormat::detect(&compressed), Some(format));
+            assert!(compressed.len() < text.len() / 10);
+            assert_eq!(decompress(&compressed, format, text.len() as u64).unwrap(), text.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_decompress_limit() {
+        let zeros = vec![0u8; 1024 * 1024];
+        for &format in CompressionFormat::available() {
+            let compressed = compress(&zeros, format, format.default_level()).unwrap();
+            let result = decompress(&compressed, format, 1024);
+            assert!(matches!(result, Err(FileJackError::PermissionDenied(_))), "{:?}", format);
+        }
+        assert!(decompress(b"not compressed", CompressionFormat::Gzip, 1024).is_err());
+    }
+}
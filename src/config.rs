@@ -1,18 +1,132 @@
-use crate::access_control::AccessPolicy;
-use crate::error::Result;
+use crate::access_control::{AccessPolicy, DEFAULT_PATH_CACHE_CAPACITY, DEFAULT_PATH_CACHE_TTL};
+use crate::catalog::ToolMetadata;
+use crate::error::{FileJackError, Result};
+use crate::file_ops::{DEFAULT_MMAP_THRESHOLD, DEFAULT_READ_LIMIT};
+use crate::logging::LogFormat;
+use crate::mcp::{DEFAULT_MAX_JSON_DEPTH, DEFAULT_MAX_REQUEST_BYTES, DEFAULT_MAX_RESPONSE_BYTES};
+use crate::mounts::MountConfig;
+use crate::rate_limit::DEFAULT_REQUESTS_PER_SECOND;
+use crate::schema::{self, CURRENT_SCHEMA_VERSION};
+use crate::vfs;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+/// Environment variable selecting a configuration profile
+pub const PROFILE_ENV_VAR: &str = "FILEJACK_PROFILE";
+
 /// Configuration for FileJack server
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Layout version of the configuration document; older layouts are
+    /// migrated when loaded
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+
     /// Access control policy
     pub access_policy: AccessPolicy,
     
     /// Server settings
     #[serde(default)]
     pub server: ServerConfig,
+
+    /// Performance tuning
+    #[serde(default)]
+    pub performance: PerformanceConfig,
+
+    /// Log output settings
+    #[serde(default)]
+    pub logging: LoggingConfig,
+
+    /// Metrics export
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+
+    /// Identity used by the `git_snapshot` tool
+    #[serde(default)]
+    pub git: GitConfig,
+
+    /// Backends served under virtual path prefixes, routed to by the paths
+    /// in each tool call
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mounts: Vec<MountConfig>,
+
+    /// Names such as `@workspace` accepted in place of a directory in tool
+    /// arguments, and written instead of it in responses
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, PathBuf>,
+
+    /// Titles and descriptions shown for tools in `tools/list`, by tool
+    /// name, over the built-in catalog
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tool_metadata: BTreeMap<String, ToolMetadata>,
+
+    /// Tools offered to each client, by the name it gives in `initialize`;
+    /// an entry named `*` applies to clients without one of their own
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub client_tools: BTreeMap<String, ClientTools>,
+
+    /// Serve a directory on another host over SFTP instead of the local
+    /// file system; the access policy's paths are then remote paths
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sftp: Option<SftpConfig>,
+
+    /// Keep a SQLite index of the files under the allowed paths, so
+    /// `search_files` and `recent_files` need not walk large trees
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<IndexConfig>,
+
+    /// Load extra tools from sandboxed WebAssembly plugins
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugins: Option<PluginsConfig>,
+
+    /// Scratch directories the `create_temp_workspace` tool hands out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspaces: Option<WorkspacesConfig>,
+
+    /// Named overrides of the settings above, selected with `--profile` or
+    /// `FILEJACK_PROFILE`. A profile may name another profile in `extends`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Value>,
+
+    /// Profile that was applied when this configuration was loaded
+    #[serde(skip)]
+    pub active_profile: Option<String>,
+
+    /// Migrations applied and unknown settings ignored while loading
+    #[serde(skip)]
+    pub load_warnings: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            access_policy: AccessPolicy::default(),
+            server: ServerConfig::default(),
+            performance: PerformanceConfig::default(),
+            logging: LoggingConfig::default(),
+            metrics: MetricsConfig::default(),
+            git: GitConfig::default(),
+            mounts: Vec::new(),
+            aliases: BTreeMap::new(),
+            tool_metadata: BTreeMap::new(),
+            client_tools: BTreeMap::new(),
+            sftp: None,
+            index: None,
+            plugins: None,
+            workspaces: None,
+            profiles: BTreeMap::new(),
+            active_profile: None,
+            load_warnings: Vec::new(),
+        }
+    }
+}
+
+fn default_schema_version() -> u32 {
+    CURRENT_SCHEMA_VERSION
 }
 
 /// Server configuration
@@ -25,6 +139,37 @@ pub struct ServerConfig {
     /// Server version
     #[serde(default = "default_server_version")]
     pub version: String,
+
+    /// Largest accepted JSON-RPC request in bytes
+    #[serde(default = "default_max_request_bytes")]
+    pub max_request_bytes: usize,
+
+    /// Deepest accepted nesting of JSON arrays and objects in a request
+    #[serde(default = "default_max_json_depth")]
+    pub max_json_depth: usize,
+
+    /// Largest tool result payload in bytes before it is truncated (0 disables)
+    #[serde(default = "default_max_response_bytes")]
+    pub max_response_bytes: usize,
+
+    /// Requests accepted per second before clients are rate limited
+    #[serde(default = "default_requests_per_second")]
+    pub requests_per_second: u32,
+
+    /// Milliseconds an over-limit request may wait for the rate limiter
+    /// before it is refused (0 refuses at once)
+    #[serde(default)]
+    pub rate_limit_wait_ms: u64,
+
+    /// Locale of tool titles and descriptions, such as `de` or `pt-BR`,
+    /// where `tool_metadata` translates them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+
+    /// File each session's usage report is appended to, as a line of JSON,
+    /// when its connection closes or the server shuts down
+    #[serde(default)]
+    pub usage_report: Option<PathBuf>,
 }
 
 impl Default for ServerConfig {
@@ -32,10 +177,392 @@ impl Default for ServerConfig {
         Self {
             name: default_server_name(),
             version: default_server_version(),
+            max_request_bytes: default_max_request_bytes(),
+            max_json_depth: default_max_json_depth(),
+            max_response_bytes: default_max_response_bytes(),
+            requests_per_second: default_requests_per_second(),
+            rate_limit_wait_ms: 0,
+            locale: None,
+            usage_report: None,
+        }
+    }
+}
+
+fn default_max_request_bytes() -> usize {
+    DEFAULT_MAX_REQUEST_BYTES
+}
+
+fn default_max_json_depth() -> usize {
+    DEFAULT_MAX_JSON_DEPTH
+}
+
+fn default_max_response_bytes() -> usize {
+    DEFAULT_MAX_RESPONSE_BYTES
+}
+
+fn default_requests_per_second() -> u32 {
+    DEFAULT_REQUESTS_PER_SECOND
+}
+
+/// Performance tuning knobs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerformanceConfig {
+    /// Files at or above this size in bytes are memory-mapped for
+    /// line-oriented reads and searches (0 disables memory mapping)
+    #[serde(default = "default_mmap_threshold")]
+    pub mmap_threshold: u64,
+
+    /// Most bytes `read_file` and `read_range` return from one call, so
+    /// reading a huge file does not hold all of it in memory (0 disables)
+    #[serde(default = "default_read_limit")]
+    pub read_limit: u64,
+
+    /// Flush written files and their directory entries to disk before
+    /// reporting success
+    #[serde(default = "default_fsync")]
+    pub fsync: bool,
+
+    /// Number of threads used by recursive searches (0 picks automatically)
+    #[serde(default)]
+    pub search_threads: usize,
+
+    /// Number of canonical paths whose path-string rule checks are remembered
+    /// (0 disables the cache). Symlinks, owners and overlays are always checked.
+    #[serde(default = "default_path_cache_capacity")]
+    pub path_cache_capacity: usize,
+
+    /// How long a cached rule check stays valid, in milliseconds
+    #[serde(default = "default_path_cache_ttl_ms")]
+    pub path_cache_ttl_ms: u64,
+
+    /// Byte budget for caching the contents of frequently read files
+    /// (0 disables the content cache)
+    #[serde(default)]
+    pub content_cache_bytes: usize,
+
+    /// Skip `write_file` calls whose content matches the file on disk, so
+    /// re-saving an unchanged file does not touch its modification time
+    #[serde(default)]
+    pub skip_unchanged_writes: bool,
+}
+
+impl Default for PerformanceConfig {
+    fn default() -> Self {
+        Self {
+            mmap_threshold: default_mmap_threshold(),
+            read_limit: default_read_limit(),
+            fsync: default_fsync(),
+            search_threads: 0,
+            path_cache_capacity: default_path_cache_capacity(),
+            path_cache_ttl_ms: default_path_cache_ttl_ms(),
+            content_cache_bytes: 0,
+            skip_unchanged_writes: false,
+        }
+    }
+}
+
+fn default_path_cache_capacity() -> usize {
+    DEFAULT_PATH_CACHE_CAPACITY
+}
+
+fn default_path_cache_ttl_ms() -> u64 {
+    DEFAULT_PATH_CACHE_TTL.as_millis() as u64
+}
+
+fn default_fsync() -> bool {
+    true
+}
+
+fn default_mmap_threshold() -> u64 {
+    DEFAULT_MMAP_THRESHOLD
+}
+
+fn default_read_limit() -> u64 {
+    DEFAULT_READ_LIMIT
+}
+
+/// Log output settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Minimum level or `RUST_LOG`-style directives (e.g. `info`,
+    /// `filejack=debug`); `RUST_LOG` overrides this when set
+    #[serde(default = "default_log_level")]
+    pub level: String,
+
+    /// Output format written to stderr
+    #[serde(default)]
+    pub format: LogFormat,
+
+    /// Include tool arguments and request/response bodies (with file
+    /// contents redacted) in debug logs; when false only their size is logged
+    #[serde(default = "default_log_bodies")]
+    pub log_bodies: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            format: LogFormat::default(),
+            log_bodies: default_log_bodies(),
         }
     }
 }
 
+/// Metrics export settings; metrics are always collected
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    /// Address for an HTTP listener serving Prometheus metrics at `/metrics`
+    /// (e.g. `127.0.0.1:9464`); no listener is started when unset
+    #[serde(default)]
+    pub listen_address: Option<String>,
+
+    /// Offer a `get_metrics` tool returning the same metrics to clients
+    #[serde(default)]
+    pub expose_tool: bool,
+}
+
+/// Tools offered to a client, as glob patterns over tool names such as
+/// `read_*`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientTools {
+    /// Tools offered; when empty, every tool is
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    /// Tools withheld even when `allow` matches them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+}
+
+impl ClientTools {
+    /// Whether the client may list and call the tool `name`
+    pub fn permits(&self, name: &str) -> bool {
+        let matches = |patterns: &[String]| {
+            patterns.iter().any(|pattern| glob::Pattern::new(pattern).is_ok_and(|pattern| pattern.matches(name)))
+        };
+        (self.allow.is_empty() || matches(&self.allow)) && !matches(&self.deny)
+    }
+
+    /// The entry of `client_tools` for the client called `name`
+    pub fn for_client<'a>(client_tools: &'a BTreeMap<String, ClientTools>, name: Option<&str>) -> Option<&'a ClientTools> {
+        name.and_then(|name| client_tools.get(name)).or_else(|| client_tools.get("*"))
+    }
+}
+
+/// Commits made by the `git_snapshot` tool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GitConfig {
+    /// Author and committer name
+    #[serde(default = "default_git_author_name")]
+    pub author_name: String,
+
+    /// Author and committer email
+    #[serde(default = "default_git_author_email")]
+    pub author_email: String,
+
+    /// Text put before every snapshot commit message
+    #[serde(default = "default_git_message_prefix")]
+    pub message_prefix: String,
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        Self {
+            author_name: default_git_author_name(),
+            author_email: default_git_author_email(),
+            message_prefix: default_git_message_prefix(),
+        }
+    }
+}
+
+fn default_git_author_name() -> String {
+    "FileJack".to_string()
+}
+
+fn default_git_author_email() -> String {
+    "filejack@localhost".to_string()
+}
+
+fn default_git_message_prefix() -> String {
+    "[filejack] ".to_string()
+}
+
+/// Connection to the host served by the `sftp` backend
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SftpConfig {
+    /// Host name or address of the SSH server
+    pub host: String,
+
+    #[serde(default = "default_ssh_port")]
+    pub port: u16,
+
+    /// User to log in as
+    pub user: String,
+
+    /// Private key to log in with; the SSH agent is asked when unset
+    #[serde(default)]
+    pub identity_file: Option<PathBuf>,
+
+    /// OpenSSH `known_hosts` file that must list the server's host key
+    #[serde(default = "default_known_hosts")]
+    pub known_hosts: PathBuf,
+}
+
+impl Default for SftpConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: default_ssh_port(),
+            user: String::new(),
+            identity_file: None,
+            known_hosts: default_known_hosts(),
+        }
+    }
+}
+
+/// File index kept by the `index` feature
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexConfig {
+    /// SQLite database file; created if missing
+    #[serde(default = "default_index_database")]
+    pub database: PathBuf,
+
+    /// Seconds between rescans that pick up changes made outside FileJack
+    #[serde(default = "default_index_refresh_secs")]
+    pub refresh_interval_secs: u64,
+
+    /// Files larger than this many bytes are indexed without a content hash
+    /// (0 disables hashing)
+    #[serde(default = "default_index_hash_max_bytes")]
+    pub hash_max_bytes: u64,
+
+    /// UTF-8 files of at most this many bytes are indexed for
+    /// `search_content` (0 disables content indexing)
+    #[serde(default = "default_index_content_max_bytes")]
+    pub content_max_bytes: u64,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            database: default_index_database(),
+            refresh_interval_secs: default_index_refresh_secs(),
+            hash_max_bytes: default_index_hash_max_bytes(),
+            content_max_bytes: default_index_content_max_bytes(),
+        }
+    }
+}
+
+/// WebAssembly plugins loaded by the `wasm` feature
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginsConfig {
+    /// Directory whose `.wasm` files are loaded as plugins
+    #[serde(default = "default_plugins_directory")]
+    pub directory: PathBuf,
+
+    /// Fuel a single tool call may burn, roughly one unit per Wasm
+    /// instruction; a call that runs out is stopped
+    #[serde(default = "default_plugin_fuel")]
+    pub fuel: u64,
+
+    /// Most linear memory a plugin may grow to during one call
+    #[serde(default = "default_plugin_max_memory_bytes")]
+    pub max_memory_bytes: u64,
+}
+
+impl Default for PluginsConfig {
+    fn default() -> Self {
+        Self {
+            directory: default_plugins_directory(),
+            fuel: default_plugin_fuel(),
+            max_memory_bytes: default_plugin_max_memory_bytes(),
+        }
+    }
+}
+
+/// Scratch directories for `create_temp_workspace`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkspacesConfig {
+    /// Directory the workspaces are created in; a restricted policy
+    /// allows it, but each workspace only to the session that created it
+    #[serde(default = "default_workspaces_root")]
+    pub root: PathBuf,
+
+    /// Seconds a workspace lives before it is deleted
+    #[serde(default = "default_workspace_ttl_secs")]
+    pub ttl_secs: u64,
+
+    /// Most workspaces one session may hold at a time
+    #[serde(default = "default_max_workspaces_per_session")]
+    pub max_per_session: usize,
+}
+
+impl Default for WorkspacesConfig {
+    fn default() -> Self {
+        Self {
+            root: default_workspaces_root(),
+            ttl_secs: default_workspace_ttl_secs(),
+            max_per_session: default_max_workspaces_per_session(),
+        }
+    }
+}
+
+fn default_workspaces_root() -> PathBuf {
+    std::env::temp_dir().join("filejack-workspaces")
+}
+
+fn default_workspace_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_max_workspaces_per_session() -> usize {
+    4
+}
+
+fn default_plugins_directory() -> PathBuf {
+    PathBuf::from("~/.config/filejack/plugins")
+}
+
+fn default_plugin_fuel() -> u64 {
+    1_000_000_000
+}
+
+fn default_plugin_max_memory_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_index_database() -> PathBuf {
+    PathBuf::from("~/.cache/filejack/index.db")
+}
+
+fn default_index_refresh_secs() -> u64 {
+    60
+}
+
+fn default_index_hash_max_bytes() -> u64 {
+    16 * 1024 * 1024
+}
+
+fn default_index_content_max_bytes() -> u64 {
+    1024 * 1024
+}
+
+fn default_ssh_port() -> u16 {
+    22
+}
+
+fn default_known_hosts() -> PathBuf {
+    PathBuf::from("~/.ssh/known_hosts")
+}
+
+fn default_log_bodies() -> bool {
+    true
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
 fn default_server_name() -> String {
     "FileJack".to_string()
 }
@@ -44,26 +571,548 @@ fn default_server_version() -> String {
     env!("CARGO_PKG_VERSION").to_string()
 }
 
+/// How serious a problem found by [`Config::validate`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueSeverity {
+    /// The configuration works but is probably not what was intended
+    Warning,
+    /// The configuration is broken or contradicts itself
+    Error,
+}
+
+/// A problem found while validating a configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub severity: IssueSeverity,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn warning(message: String) -> Self {
+        Self { severity: IssueSeverity::Warning, message }
+    }
+
+    fn error(message: String) -> Self {
+        Self { severity: IssueSeverity::Error, message }
+    }
+
+    /// Whether this issue should make validation fail
+    pub fn is_error(&self) -> bool {
+        self.severity == IssueSeverity::Error
+    }
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.severity {
+            IssueSeverity::Warning => write!(f, "warning: {}", self.message),
+            IssueSeverity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+/// File names searched for in the working directory, in order of preference
+pub const DEFAULT_CONFIG_FILE_NAMES: &[&str] = &[
+    "filejack.json",
+    "filejack.toml",
+    "filejack.yaml",
+    "filejack.yml",
+];
+
+/// Serialization format of a configuration file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Detect the format from a file extension, defaulting to JSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().map(|e| e.to_string_lossy().to_lowercase()).as_deref() {
+            Some("toml") => ConfigFormat::Toml,
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Json,
+        }
+    }
+}
+
+impl std::str::FromStr for ConfigFormat {
+    type Err = FileJackError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(ConfigFormat::Json),
+            "toml" => Ok(ConfigFormat::Toml),
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            other => Err(FileJackError::ConfigError(
+                format!("Unknown config format '{}', expected json, toml, or yaml", other)
+            )),
+        }
+    }
+}
+
 impl Config {
-    /// Load configuration from a JSON file
+    /// Load configuration from a file, choosing the format from its extension
+    /// (`.toml`, `.yaml`/`.yml`, anything else is read as JSON)
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_file_with_profile(path, None)
+    }
+
+    /// Load configuration from a file and apply the named profile, if any
+    pub fn from_file_with_profile<P: AsRef<Path>>(path: P, profile: Option<&str>) -> Result<Self> {
+        let format = ConfigFormat::from_path(path.as_ref());
+        let content = fs::read_to_string(path.as_ref())?;
+        Self::from_value(parse_value(&content, format)?, profile)
+    }
+
+    /// Load configuration from a file in an explicit format
+    pub fn from_file_with_format<P: AsRef<Path>>(path: P, format: ConfigFormat) -> Result<Self> {
         let content = fs::read_to_string(path.as_ref())?;
-        let config: Config = serde_json::from_str(&content)?;
+        Self::from_str_with_format(&content, format)
+    }
+
+    /// Parse configuration text in the given format
+    pub fn from_str_with_format(content: &str, format: ConfigFormat) -> Result<Self> {
+        Self::from_value(parse_value(content, format)?, None)
+    }
+
+    /// Build a configuration from its parsed document, applying the named
+    /// profile (and the profiles it extends) on top of the base settings.
+    ///
+    /// Older layouts are migrated first; what was migrated, and any setting
+    /// that was not recognized, is reported in [`Config::load_warnings`].
+    pub fn from_value(mut value: Value, profile: Option<&str>) -> Result<Self> {
+        let mut warnings = schema::migrate(&mut value)?;
+        warnings.extend(schema::unknown_fields(&value).into_iter()
+            .map(|field| format!("Unknown setting '{}' is ignored", field)));
+
+        if let Some(name) = profile {
+            let profiles = value.get("profiles")
+                .and_then(Value::as_object)
+                .cloned()
+                .unwrap_or_default();
+            let overlay = resolve_profile(&profiles, name, &mut Vec::new())?;
+            merge_values(&mut value, overlay);
+        }
+
+        let mut config: Config = serde_json::from_value(value)
+            .map_err(|e| FileJackError::ConfigError(format!("Invalid configuration: {}", e)))?;
+        config.active_profile = profile.map(str::to_string);
+        config.load_warnings = warnings;
+        config.expand_paths()?;
         Ok(config)
     }
 
-    /// Save configuration to a JSON file
+    /// Expand `~` and environment variables in the allowed, denied, and
+    /// append-only paths, the local files the SFTP connection uses, the
+    /// index database, the plugins directory, the workspaces root, and the
+    /// usage report
+    fn expand_paths(&mut self) -> Result<()> {
+        let lookup = |name: &str| std::env::var(name).ok();
+        let policy = &mut self.access_policy;
+        let policy_paths = policy.allowed_paths.iter_mut()
+            .chain(policy.denied_paths.iter_mut())
+            .chain(policy.append_only_paths.iter_mut());
+        for path in policy_paths {
+            *path = expand_path(path, lookup)?;
+        }
+        if let Some(sftp) = &mut self.sftp {
+            for path in sftp.identity_file.iter_mut().chain(Some(&mut sftp.known_hosts)) {
+                *path = expand_path(path, lookup)?;
+            }
+        }
+        if let Some(index) = &mut self.index {
+            index.database = expand_path(&index.database, lookup)?;
+        }
+        if let Some(plugins) = &mut self.plugins {
+            plugins.directory = expand_path(&plugins.directory, lookup)?;
+        }
+        if let Some(workspaces) = &mut self.workspaces {
+            workspaces.root = expand_path(&workspaces.root, lookup)?;
+        }
+        if let Some(report) = &mut self.server.usage_report {
+            *report = expand_path(report, lookup)?;
+        }
+        Ok(())
+    }
+
+    /// Serialize configuration in the given format
+    pub fn to_string_with_format(&self, format: ConfigFormat) -> Result<String> {
+        match format {
+            ConfigFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            ConfigFormat::Toml => toml::to_string_pretty(self)
+                .map_err(|e| FileJackError::ConfigError(format!("Cannot serialize TOML: {}", e))),
+            ConfigFormat::Yaml => serde_yaml::to_string(self)
+                .map_err(|e| FileJackError::ConfigError(format!("Cannot serialize YAML: {}", e))),
+        }
+    }
+
+    /// Save configuration to a file, choosing the format from its extension
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        let json = serde_json::to_string_pretty(self)?;
-        fs::write(path.as_ref(), json)?;
+        let content = self.to_string_with_format(ConfigFormat::from_path(path.as_ref()))?;
+        fs::write(path.as_ref(), content)?;
         Ok(())
     }
 
+    /// Check the configuration for missing paths and contradictory rules.
+    ///
+    /// Paths are canonicalized before they are compared, so `/srv/../srv/data`
+    /// and `/srv/data` are treated as the same directory.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let policy = &self.access_policy;
+        let mut issues: Vec<ConfigIssue> = self.load_warnings.iter()
+            .map(|warning| ConfigIssue::warning(warning.clone()))
+            .collect();
+
+        // Remote paths cannot be checked without connecting; see health_check
+        if self.sftp.is_none() {
+            self.check_local_paths(&mut issues);
+        }
+
+        for ext in &policy.allowed_extensions {
+            if policy.denied_extensions.iter().any(|denied| denied.eq_ignore_ascii_case(ext)) {
+                issues.push(ConfigIssue::error(
+                    format!("extension '{}' is both allowed and denied", ext)
+                ));
+            }
+        }
+
+        let patterns = [("protected", &policy.protected_paths), ("sensitive", &policy.sensitive_paths)];
+        for (kind, pattern) in patterns.iter().flat_map(|(kind, list)| list.iter().map(move |pattern| (kind, pattern))) {
+            if let Err(e) = glob::Pattern::new(pattern) {
+                issues.push(ConfigIssue::error(format!(
+                    "{} path pattern '{}' is invalid, so every write is refused: {}",
+                    kind, pattern, e
+                )));
+            }
+        }
+
+        let rules = policy.derived_rule_ids();
+        for id in policy.rule_ids.keys().filter(|id| !rules.contains(id)) {
+            issues.push(ConfigIssue::warning(format!(
+                "rule_ids names '{}', which is not a rule of this policy; ids are settings such as 'read_only' or list entries such as 'denied_paths[0]'",
+                id
+            )));
+        }
+
+        if policy.allow_special_files && policy.special_file_max_bytes == 0 {
+            issues.push(ConfigIssue::error(
+                "allow_special_files is set but special_file_max_bytes is 0, so special files still cannot be read".to_string()
+            ));
+        }
+
+        if policy.allowed_paths.is_empty() {
+            issues.push(ConfigIssue::warning(
+                "allowed_paths is empty, so every path on the system is accessible".to_string()
+            ));
+        }
+        if self.server.max_request_bytes == 0 {
+            issues.push(ConfigIssue::error("server.max_request_bytes must be greater than 0".to_string()));
+        }
+        if self.server.max_json_depth == 0 {
+            issues.push(ConfigIssue::error("server.max_json_depth must be greater than 0".to_string()));
+        }
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.logging.level) {
+            issues.push(ConfigIssue::error(format!("logging.level '{}' is invalid: {}", self.logging.level, e)));
+        }
+        for (i, mount) in self.mounts.iter().enumerate() {
+            if let Some(problem) = mount.problem() {
+                issues.push(ConfigIssue::error(problem));
+            }
+            if self.mounts[..i].iter().any(|other| other.prefix == mount.prefix) {
+                issues.push(ConfigIssue::error(format!("mount prefix {} is used more than once", mount.prefix)));
+            }
+        }
+        for (alias, dir) in &self.aliases {
+            if alias.len() < 2 || !alias.starts_with('@') || alias.contains(['/', '\\']) {
+                issues.push(ConfigIssue::error(format!(
+                    "alias '{}' must start with '@' and contain no path separators",
+                    alias
+                )));
+            }
+            if !dir.is_absolute() || !dir.components().any(|c| matches!(c, std::path::Component::Normal(_))) {
+                issues.push(ConfigIssue::error(format!(
+                    "alias {} must name an absolute directory other than the root",
+                    alias
+                )));
+            }
+        }
+        for (client, tools) in &self.client_tools {
+            for pattern in tools.allow.iter().chain(&tools.deny) {
+                if let Err(e) = glob::Pattern::new(pattern) {
+                    issues.push(ConfigIssue::error(format!(
+                        "client_tools.{} pattern '{}' is invalid: {}",
+                        client, pattern, e
+                    )));
+                }
+            }
+        }
+        if self.git != GitConfig::default() && !cfg!(feature = "git") {
+            issues.push(ConfigIssue::warning(
+                "git is configured, but FileJack was built without the `git` feature".to_string()
+            ));
+        }
+        if self.git.author_name.trim().is_empty() || self.git.author_email.trim().is_empty() {
+            issues.push(ConfigIssue::error("git.author_name and git.author_email must not be empty".to_string()));
+        }
+        if let Some(index) = &self.index {
+            if !cfg!(feature = "index") {
+                issues.push(ConfigIssue::error(
+                    "index is configured, but FileJack was built without the `index` feature".to_string()
+                ));
+            }
+            if self.sftp.is_some() {
+                issues.push(ConfigIssue::error("index cannot be combined with sftp".to_string()));
+            }
+            if self.access_policy.allowed_paths.is_empty() {
+                issues.push(ConfigIssue::warning(
+                    "index has nothing to cover: access_policy.allowed_paths is empty".to_string()
+                ));
+            }
+            if index.refresh_interval_secs == 0 {
+                issues.push(ConfigIssue::error("index.refresh_interval_secs must be at least 1".to_string()));
+            }
+        }
+        if let Some(plugins) = &self.plugins {
+            if !cfg!(feature = "wasm") {
+                issues.push(ConfigIssue::error(
+                    "plugins is configured, but FileJack was built without the `wasm` feature".to_string()
+                ));
+            }
+            if !plugins.directory.is_dir() {
+                issues.push(ConfigIssue::warning(format!(
+                    "plugins.directory {} does not exist; no plugins will be loaded",
+                    plugins.directory.display()
+                )));
+            }
+            if plugins.fuel == 0 {
+                issues.push(ConfigIssue::error("plugins.fuel must be at least 1".to_string()));
+            }
+        }
+        if let Some(workspaces) = &self.workspaces {
+            if workspaces.ttl_secs == 0 || workspaces.max_per_session == 0 {
+                issues.push(ConfigIssue::error(
+                    "workspaces.ttl_secs and workspaces.max_per_session must be at least 1".to_string()
+                ));
+            }
+            if self.sftp.is_some() {
+                issues.push(ConfigIssue::error("workspaces cannot be combined with sftp".to_string()));
+            }
+        }
+        if let Some(sftp) = &self.sftp {
+            if !cfg!(feature = "sftp") {
+                issues.push(ConfigIssue::error(
+                    "sftp is configured, but FileJack was built without the `sftp` feature".to_string()
+                ));
+            }
+            if sftp.host.is_empty() || sftp.user.is_empty() {
+                issues.push(ConfigIssue::error("sftp.host and sftp.user must be set".to_string()));
+            }
+            if self.mounts.iter().any(|mount| !mount.is_remote()) {
+                issues.push(ConfigIssue::error("local mounts cannot be combined with sftp".to_string()));
+            }
+        }
+
+        issues
+    }
+
+    /// Allowed, denied, and append-only paths that do not exist, overlap, or
+    /// have no effect
+    fn check_local_paths(&self, issues: &mut Vec<ConfigIssue>) {
+        let policy = &self.access_policy;
+        let mut allowed = Vec::new();
+        for path in &policy.allowed_paths {
+            match vfs::canonicalize(path) {
+                Ok(canonical) if canonical.is_dir() => allowed.push(canonical),
+                Ok(_) => issues.push(ConfigIssue::error(
+                    format!("allowed path {} is not a directory", path.display())
+                )),
+                Err(_) => issues.push(ConfigIssue::error(
+                    format!("allowed path {} does not exist", path.display())
+                )),
+            }
+        }
+
+        for (i, path) in allowed.iter().enumerate() {
+            if allowed[..i].contains(path) {
+                issues.push(ConfigIssue::warning(
+                    format!("allowed path {} is listed more than once", path.display())
+                ));
+            }
+        }
+
+        for path in &policy.denied_paths {
+            let canonical = match vfs::canonicalize(path) {
+                Ok(canonical) => canonical,
+                Err(_) => {
+                    issues.push(ConfigIssue::warning(
+                        format!("denied path {} does not exist", path.display())
+                    ));
+                    continue;
+                }
+            };
+            let covered: Vec<&PathBuf> = allowed.iter()
+                .filter(|root| root.starts_with(&canonical))
+                .collect();
+            for root in &covered {
+                issues.push(ConfigIssue::error(format!(
+                    "denied path {} covers the whole allowed path {}",
+                    path.display(),
+                    root.display()
+                )));
+            }
+            if covered.is_empty() && !allowed.is_empty()
+                && !allowed.iter().any(|root| canonical.starts_with(root))
+            {
+                issues.push(ConfigIssue::warning(format!(
+                    "denied path {} is outside every allowed path and has no effect",
+                    path.display()
+                )));
+            }
+        }
+
+        // Like denied paths, append-only paths are matched by their
+        // canonical form, so one that does not exist yet protects nothing
+        for path in &policy.append_only_paths {
+            if !path.exists() {
+                issues.push(ConfigIssue::warning(format!(
+                    "append-only path {} does not exist, so it can still be created with write_file",
+                    path.display()
+                )));
+            }
+        }
+    }
+
+    /// Check that the server could run with this configuration: everything
+    /// [`Config::validate`] reports, plus an error for each allowed path that
+    /// cannot be listed by this process. With `sftp` the paths are listed on
+    /// the remote host, which needs a working connection.
+    pub fn health_check(&self) -> Vec<ConfigIssue> {
+        let mut issues = self.validate();
+        if self.sftp.is_none() {
+            for path in self.access_policy.allowed_paths.iter().filter(|p| p.is_dir()) {
+                if let Err(e) = fs::read_dir(path) {
+                    issues.push(ConfigIssue::error(format!("Allowed path {} is not readable: {}", path.display(), e)));
+                }
+            }
+        }
+        #[cfg(feature = "sftp")]
+        if let Some(sftp) = &self.sftp {
+            use crate::vfs::FileSystem;
+            let remote = crate::sftp::SftpFileSystem::new(sftp.clone());
+            let root = [PathBuf::from("/")];
+            let paths = match self.access_policy.allowed_paths.as_slice() {
+                [] => &root[..],
+                paths => paths,
+            };
+            for path in paths {
+                if let Err(e) = remote.list(path) {
+                    issues.push(ConfigIssue::error(format!(
+                        "Allowed path {} on {} is not readable: {}", path.display(), sftp.host, e
+                    )));
+                }
+            }
+        }
+        issues
+    }
+
+    /// Render a commented starter configuration restricted to `dir`.
+    ///
+    /// JSON has no comments, so the JSON starter is the plain restricted config.
+    pub fn starter_template(dir: &Path, format: ConfigFormat) -> Result<String> {
+        // JSON string escaping is valid for both TOML basic strings and YAML
+        // double-quoted strings
+        let quoted = serde_json::to_string(&dir.display().to_string())?;
+        let template = match format {
+            ConfigFormat::Json => return Self::default_restricted(dir.to_path_buf())
+                .to_string_with_format(ConfigFormat::Json),
+            ConfigFormat::Toml => format!(r#"# FileJack configuration, generated by `filejack init`.
+# See ACCESS_CONTROL.md for every option.
+
+# Layout version of this file; older layouts are migrated automatically
+schema_version = {CURRENT_SCHEMA_VERSION}
+
+[access_policy]
+# Directories the server may access; everything else is denied
+allowed_paths = [{quoted}]
+# Paths that stay off limits even inside allowed_paths
+denied_paths = []
+# When non-empty, only files with these extensions are accessible
+allowed_extensions = []
+# Extensions that are always denied
+denied_extensions = ["exe", "dll", "so", "dylib"]
+# Largest file that may be read or written, in bytes (0 means no limit)
+max_file_size = 10485760
+allow_symlinks = false
+allow_hidden_files = false
+# Reject every write operation
+read_only = false
+
+[server]
+name = "FileJack"
+
+[logging]
+# Minimum level (error, warn, info, debug, trace); RUST_LOG overrides it
+level = "info"
+# "pretty" or "json"
+format = "pretty"
+# Log tool arguments and message bodies at debug level (file contents are
+# always redacted); set to false to keep paths and queries out of logs
+log_bodies = true
+"#),
+            ConfigFormat::Yaml => format!(r#"# FileJack configuration, generated by `filejack init`.
+# See ACCESS_CONTROL.md for every option.
+
+# Layout version of this file; older layouts are migrated automatically
+schema_version: {CURRENT_SCHEMA_VERSION}
+
+access_policy:
+  # Directories the server may access; everything else is denied
+  allowed_paths:
+    - {quoted}
+  # Paths that stay off limits even inside allowed_paths
+  denied_paths: []
+  # When non-empty, only files with these extensions are accessible
+  allowed_extensions: []
+  # Extensions that are always denied
+  denied_extensions: [exe, dll, so, dylib]
+  # Largest file that may be read or written, in bytes (0 means no limit)
+  max_file_size: 10485760
+  allow_symlinks: false
+  allow_hidden_files: false
+  # Reject every write operation
+  read_only: false
+
+server:
+  name: FileJack
+
+logging:
+  # Minimum level (error, warn, info, debug, trace); RUST_LOG overrides it
+  level: info
+  # pretty or json
+  format: pretty
+  # Log tool arguments and message bodies at debug level (file contents are
+  # always redacted); set to false to keep paths and queries out of logs
+  log_bodies: true
+"#),
+        };
+        Ok(template)
+    }
+
+    /// Find a config file in `dir` using [`DEFAULT_CONFIG_FILE_NAMES`]
+    pub fn find_in_dir(dir: &Path) -> Option<PathBuf> {
+        DEFAULT_CONFIG_FILE_NAMES.iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    }
+
     /// Create a default configuration with restricted access to a single directory
     pub fn default_restricted(allowed_path: PathBuf) -> Self {
         Self {
             access_policy: AccessPolicy::restricted(allowed_path),
-            server: ServerConfig::default(),
+            ..Self::default()
         }
     }
 
@@ -71,7 +1120,7 @@ impl Config {
     pub fn permissive() -> Self {
         Self {
             access_policy: AccessPolicy::permissive(),
-            server: ServerConfig::default(),
+            ..Self::default()
         }
     }
 
@@ -79,9 +1128,133 @@ impl Config {
     pub fn read_only(allowed_path: PathBuf) -> Self {
         Self {
             access_policy: AccessPolicy::read_only(allowed_path),
-            server: ServerConfig::default(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Parse configuration text into a JSON document regardless of its format
+pub(crate) fn parse_value(content: &str, format: ConfigFormat) -> Result<Value> {
+    match format {
+        ConfigFormat::Json => Ok(serde_json::from_str(content)?),
+        ConfigFormat::Toml => toml::from_str(content)
+            .map_err(|e| FileJackError::ConfigError(format!("Invalid TOML: {}", e))),
+        ConfigFormat::Yaml => serde_yaml::from_str(content)
+            .map_err(|e| FileJackError::ConfigError(format!("Invalid YAML: {}", e))),
+    }
+}
+
+/// Merge `overlay` into `base`: objects are merged key by key, while arrays
+/// and scalars in the overlay replace the base value
+pub(crate) fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
         }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Flatten a profile and the chain of profiles it `extends` into one overlay
+pub(crate) fn resolve_profile(
+    profiles: &serde_json::Map<String, Value>,
+    name: &str,
+    chain: &mut Vec<String>,
+) -> Result<Value> {
+    if chain.iter().any(|seen| seen == name) {
+        chain.push(name.to_string());
+        return Err(FileJackError::ConfigError(
+            format!("Profile inheritance cycle: {}", chain.join(" -> "))
+        ));
     }
+    chain.push(name.to_string());
+
+    let mut profile = match profiles.get(name) {
+        Some(Value::Object(profile)) => profile.clone(),
+        Some(_) => return Err(FileJackError::ConfigError(
+            format!("Profile '{}' must be an object", name)
+        )),
+        None => {
+            let mut known: Vec<&str> = profiles.keys().map(String::as_str).collect();
+            known.sort_unstable();
+            return Err(FileJackError::ConfigError(format!(
+                "Unknown profile '{}' (available: {})",
+                name,
+                if known.is_empty() { "none".to_string() } else { known.join(", ") }
+            )));
+        }
+    };
+    profile.remove("profiles");
+
+    let mut overlay = match profile.remove("extends") {
+        Some(Value::String(parent)) => resolve_profile(profiles, &parent, chain)?,
+        Some(_) => return Err(FileJackError::ConfigError(
+            format!("'extends' in profile '{}' must be a profile name", name)
+        )),
+        None => Value::Object(Default::default()),
+    };
+    merge_values(&mut overlay, Value::Object(profile));
+    Ok(overlay)
+}
+
+/// Expand a leading `~` to the home directory and `$VAR` / `${VAR}` to the
+/// value of the variable returned by `lookup`. A `$` not followed by a
+/// variable name is kept as is.
+fn expand_path(path: &Path, lookup: impl Fn(&str) -> Option<String>) -> Result<PathBuf> {
+    let raw = match path.to_str() {
+        Some(raw) if raw.contains('$') || raw.starts_with('~') => raw,
+        _ => return Ok(path.to_path_buf()),
+    };
+
+    let undefined = |name: &str| FileJackError::ConfigError(format!(
+        "Environment variable '{}' used in path \"{}\" is not defined", name, raw
+    ));
+
+    let mut expanded = String::with_capacity(raw.len());
+    let mut rest = raw;
+    if let Some(after) = rest.strip_prefix('~') {
+        if after.is_empty() || after.starts_with('/') || after.starts_with(std::path::MAIN_SEPARATOR) {
+            let home = lookup("HOME").or_else(|| lookup("USERPROFILE"))
+                .ok_or_else(|| FileJackError::ConfigError(format!(
+                    "Cannot expand '~' in path \"{}\": home directory is not known", raw
+                )))?;
+            expanded.push_str(&home);
+            rest = after;
+        }
+    }
+
+    while let Some(pos) = rest.find('$') {
+        expanded.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let (name, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            let end = braced.find('}').ok_or_else(|| FileJackError::ConfigError(
+                format!("Unterminated '${{' in path \"{}\"", raw)
+            ))?;
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            let end = after.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+
+        if name.is_empty() {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        expanded.push_str(&lookup(name).ok_or_else(|| undefined(name))?);
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+
+    Ok(PathBuf::from(expanded))
 }
 
 #[cfg(test)]
@@ -96,6 +1269,30 @@ mod tests {
         assert!(!config.version.is_empty());
     }
 
+    #[test]
+    fn test_performance_config_defaults_when_missing() {
+        let json = r#"{"access_policy": {}}"#;
+        let config: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(config.performance.mmap_threshold, DEFAULT_MMAP_THRESHOLD);
+        assert!(config.performance.fsync);
+
+        let json = r#"{"access_policy": {}, "performance": {"mmap_threshold": 0}}"#;
+        let config: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(config.performance.mmap_threshold, 0);
+    }
+
+    #[test]
+    fn test_server_config_request_limits() {
+        let config = ServerConfig::default();
+        assert_eq!(config.max_request_bytes, DEFAULT_MAX_REQUEST_BYTES);
+        assert_eq!(config.max_json_depth, DEFAULT_MAX_JSON_DEPTH);
+
+        let json = r#"{"access_policy": {}, "server": {"max_json_depth": 8}}"#;
+        let config: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(config.server.max_json_depth, 8);
+        assert_eq!(config.server.max_request_bytes, DEFAULT_MAX_REQUEST_BYTES);
+    }
+
     #[test]
     fn test_config_default_restricted() {
         let temp_dir = TempDir::new().unwrap();
@@ -147,13 +1344,343 @@ mod tests {
         
         let config = Config {
             access_policy: policy,
-            server: ServerConfig::default(),
+            ..Default::default()
         };
         
         assert_eq!(config.access_policy.allowed_extensions.len(), 2);
         assert_eq!(config.access_policy.max_file_size, 5 * 1024 * 1024);
     }
 
+    #[test]
+    fn test_config_toml_and_yaml_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let original = Config::read_only(temp_dir.path().to_path_buf());
+
+        for name in ["config.toml", "config.yaml", "config.yml", "config.json"] {
+            let path = temp_dir.path().join(name);
+            original.to_file(&path).unwrap();
+            let loaded = Config::from_file(&path).unwrap();
+            assert!(loaded.access_policy.read_only, "{}", name);
+            assert_eq!(loaded.access_policy.allowed_paths, original.access_policy.allowed_paths);
+        }
+    }
+
+    #[test]
+    fn test_config_parse_toml_and_yaml_text() {
+        let toml_text = r#"
+            [access_policy]
+            allowed_paths = ["/srv/data"]
+            denied_extensions = ["exe"]
+
+            [server]
+            name = "TomlJack"
+        "#;
+        let config = Config::from_str_with_format(toml_text, ConfigFormat::Toml).unwrap();
+        assert_eq!(config.server.name, "TomlJack");
+        assert_eq!(config.access_policy.denied_extensions, vec!["exe"]);
+
+        let yaml_text = "access_policy:\n  allowed_paths:\n    - /srv/data\n  read_only: true\n";
+        let config = Config::from_str_with_format(yaml_text, ConfigFormat::Yaml).unwrap();
+        assert!(config.access_policy.read_only);
+
+        let err = Config::from_str_with_format("not = [valid", ConfigFormat::Toml).unwrap_err();
+        assert!(matches!(err, FileJackError::ConfigError(_)));
+    }
+
+    #[test]
+    fn test_config_format_detection() {
+        assert_eq!(ConfigFormat::from_path(Path::new("a.toml")), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path(Path::new("a.YML")), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path(Path::new("a.json")), ConfigFormat::Json);
+        assert_eq!(ConfigFormat::from_path(Path::new("filejack")), ConfigFormat::Json);
+        assert_eq!("yaml".parse::<ConfigFormat>().unwrap(), ConfigFormat::Yaml);
+        assert!("ini".parse::<ConfigFormat>().is_err());
+    }
+
+    #[test]
+    fn test_find_config_in_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(Config::find_in_dir(temp_dir.path()).is_none());
+
+        fs::write(temp_dir.path().join("filejack.toml"), "[access_policy]\n").unwrap();
+        assert_eq!(Config::find_in_dir(temp_dir.path()).unwrap(), temp_dir.path().join("filejack.toml"));
+
+        fs::write(temp_dir.path().join("filejack.json"), "{}").unwrap();
+        assert_eq!(Config::find_in_dir(temp_dir.path()).unwrap(), temp_dir.path().join("filejack.json"));
+    }
+
+    #[test]
+    fn test_validate_reports_problems() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::default_restricted(temp_dir.path().to_path_buf());
+        assert!(config.validate().is_empty());
+
+        config.access_policy.allowed_paths.push(temp_dir.path().join("missing"));
+        config.access_policy.denied_paths.push(temp_dir.path().join("."));
+        config.access_policy.allowed_extensions = vec!["txt".to_string()];
+        config.access_policy.denied_extensions = vec!["TXT".to_string()];
+        config.logging.level = "filejack=loud".to_string();
+        config.client_tools.insert("editor".to_string(), ClientTools { allow: vec!["read_[".to_string()], deny: Vec::new() });
+
+        let issues = config.validate();
+        let errors: Vec<_> = issues.iter().filter(|i| i.is_error()).collect();
+        assert_eq!(errors.len(), 5, "{:?}", issues);
+        assert!(errors.iter().any(|i| i.message.contains("client_tools.editor pattern 'read_['")));
+        assert!(errors.iter().any(|i| i.message.contains("does not exist")));
+        assert!(errors.iter().any(|i| i.message.contains("covers the whole allowed path")));
+        assert!(errors.iter().any(|i| i.message.contains("both allowed and denied")));
+        assert!(errors.iter().any(|i| i.message.contains("logging.level")));
+    }
+
+    #[test]
+    fn test_validate_warnings_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::default_restricted(temp_dir.path().to_path_buf());
+        config.access_policy.denied_paths.push(temp_dir.path().join("gone"));
+        config.access_policy.append_only_paths.push(temp_dir.path().join("CHANGELOG.md"));
+
+        let issues = config.validate();
+        assert_eq!(issues.len(), 2);
+        assert!(issues.iter().all(|i| !i.is_error()));
+        assert!(issues[1].message.contains("append-only path"));
+
+        config.access_policy.protected_paths.push(".github/[".to_string());
+        let issues = config.validate();
+        assert!(issues.iter().any(|i| i.is_error() && i.message.contains("protected path pattern '.github/['")));
+
+        config.access_policy.protected_paths.clear();
+        config.access_policy.sensitive_paths.push("[".to_string());
+        let issues = config.validate();
+        assert!(issues.iter().any(|i| i.is_error() && i.message.contains("sensitive path pattern '['")));
+
+        config.access_policy.sensitive_paths.pop();
+        config.access_policy.allow_special_files = true;
+        config.access_policy.special_file_max_bytes = 0;
+        let issues = config.validate();
+        assert!(issues.iter().any(|i| i.is_error() && i.message.contains("special_file_max_bytes")));
+
+        config.access_policy.rule_ids.insert("denied_paths[0]".to_string(), "no-gone".to_string());
+        config.access_policy.rule_ids.insert("denied_paths[1]".to_string(), "typo".to_string());
+        let issues = config.validate();
+        assert!(issues.iter().any(|i| !i.is_error() && i.message.contains("rule_ids names 'denied_paths[1]'")));
+        assert!(!issues.iter().any(|i| i.message.contains("rule_ids names 'denied_paths[0]'")));
+
+        let issues = Config::permissive().validate();
+        assert!(issues.iter().all(|i| !i.is_error()));
+    }
+
+    #[test]
+    fn test_sftp_section() {
+        let toml = r#"
+            [access_policy]
+            allowed_paths = ["/home/ci/workspace"]
+
+            [sftp]
+            host = "build.example.com"
+            user = "ci"
+        "#;
+        let config = Config::from_str_with_format(toml, ConfigFormat::Toml).unwrap();
+        let sftp = config.sftp.as_ref().unwrap();
+        assert_eq!(sftp.port, 22);
+        assert!(sftp.identity_file.is_none());
+        assert!(!sftp.known_hosts.starts_with("~"));
+        assert!(config.load_warnings.is_empty(), "{:?}", config.load_warnings);
+
+        // The allowed path is on the remote host, so it is not looked up here
+        let issues = config.validate();
+        assert!(!issues.iter().any(|i| i.message.contains("does not exist")), "{:?}", issues);
+        assert_eq!(issues.iter().any(|i| i.is_error()), !cfg!(feature = "sftp"), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_git_section() {
+        let toml = r#"
+            [access_policy]
+            allowed_paths = []
+
+            [git]
+            author_name = "Agent"
+            author_email = ""
+        "#;
+        let config = Config::from_str_with_format(toml, ConfigFormat::Toml).unwrap();
+        assert_eq!(config.git.author_name, "Agent");
+        assert_eq!(config.git.message_prefix, "[filejack] ");
+        assert!(config.load_warnings.is_empty(), "{:?}", config.load_warnings);
+        assert!(config.validate().iter().any(|i| i.is_error() && i.message.contains("git.author_email")));
+    }
+
+    #[test]
+    fn test_index_section() {
+        let toml = r#"
+            [access_policy]
+            allowed_paths = []
+
+            [index]
+            database = "~/filejack-index.db"
+        "#;
+        let config = Config::from_str_with_format(toml, ConfigFormat::Toml).unwrap();
+        let index = config.index.as_ref().unwrap();
+        assert!(!index.database.starts_with("~"));
+        assert_eq!(index.refresh_interval_secs, 60);
+        assert!(config.load_warnings.is_empty(), "{:?}", config.load_warnings);
+
+        let issues = config.validate();
+        assert!(issues.iter().any(|i| i.message.contains("index has nothing to cover")), "{:?}", issues);
+        assert_eq!(issues.iter().any(|i| i.is_error()), !cfg!(feature = "index"), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_plugins_section() {
+        let toml = r#"
+            [access_policy]
+            allowed_paths = []
+
+            [plugins]
+            directory = "~/filejack-plugins-that-do-not-exist"
+        "#;
+        let config = Config::from_str_with_format(toml, ConfigFormat::Toml).unwrap();
+        let plugins = config.plugins.as_ref().unwrap();
+        assert!(!plugins.directory.starts_with("~"));
+        assert_eq!(plugins.max_memory_bytes, 64 * 1024 * 1024);
+        assert!(config.load_warnings.is_empty(), "{:?}", config.load_warnings);
+
+        let issues = config.validate();
+        assert!(issues.iter().any(|i| i.message.contains("no plugins will be loaded")), "{:?}", issues);
+        assert_eq!(issues.iter().any(|i| i.is_error()), !cfg!(feature = "wasm"), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_health_check() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::default_restricted(temp_dir.path().to_path_buf());
+        assert!(config.health_check().iter().all(|i| !i.is_error()));
+
+        config.access_policy.allowed_paths.push(temp_dir.path().join("gone"));
+        assert!(config.health_check().iter().any(|i| i.is_error()));
+    }
+
+    #[test]
+    fn test_starter_templates_parse() {
+        let temp_dir = TempDir::new().unwrap();
+        for format in [ConfigFormat::Json, ConfigFormat::Toml, ConfigFormat::Yaml] {
+            let text = Config::starter_template(temp_dir.path(), format).unwrap();
+            let config = Config::from_str_with_format(&text, format).unwrap();
+            assert_eq!(config.access_policy.allowed_paths, vec![temp_dir.path().to_path_buf()]);
+            assert!(config.validate().is_empty(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_expand_path() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/jack".to_string()),
+            "WORKSPACE" => Some("/srv/work".to_string()),
+            _ => None,
+        };
+        let expand = |p: &str| expand_path(Path::new(p), lookup);
+
+        assert_eq!(expand("~/projects").unwrap(), PathBuf::from("/home/jack/projects"));
+        assert_eq!(expand("~").unwrap(), PathBuf::from("/home/jack"));
+        assert_eq!(expand("$WORKSPACE/data").unwrap(), PathBuf::from("/srv/work/data"));
+        assert_eq!(expand("${WORKSPACE}_old/x").unwrap(), PathBuf::from("/srv/work_old/x"));
+        assert_eq!(expand("/plain/path").unwrap(), PathBuf::from("/plain/path"));
+        assert_eq!(expand("/cost/$/x").unwrap(), PathBuf::from("/cost/$/x"));
+        assert_eq!(expand("/a/~b").unwrap(), PathBuf::from("/a/~b"));
+        assert_eq!(expand("~other/x").unwrap(), PathBuf::from("~other/x"));
+
+        let err = expand("$MISSING/data").unwrap_err().to_string();
+        assert!(err.contains("'MISSING'"), "{}", err);
+        assert!(expand("${WORKSPACE/data").is_err());
+    }
+
+    #[test]
+    fn test_config_load_expands_paths() {
+        std::env::set_var("FILEJACK_TEST_EXPAND_ROOT", "/srv/expanded");
+        let json = r#"{"access_policy": {
+            "allowed_paths": ["$FILEJACK_TEST_EXPAND_ROOT/data"],
+            "denied_paths": ["${FILEJACK_TEST_EXPAND_ROOT}/data/secret"]
+        }}"#;
+        let config = Config::from_str_with_format(json, ConfigFormat::Json).unwrap();
+        assert_eq!(config.access_policy.allowed_paths, vec![PathBuf::from("/srv/expanded/data")]);
+        assert_eq!(config.access_policy.denied_paths, vec![PathBuf::from("/srv/expanded/data/secret")]);
+
+        let json = r#"{"access_policy": {"allowed_paths": ["$FILEJACK_TEST_UNDEFINED_VAR"]}}"#;
+        let err = Config::from_str_with_format(json, ConfigFormat::Json).unwrap_err();
+        assert!(matches!(err, FileJackError::ConfigError(_)));
+    }
+
+    #[test]
+    fn test_profiles_inherit_and_override() {
+        let json = r#"{
+            "access_policy": {"allowed_paths": ["/srv/work"], "denied_extensions": ["exe"]},
+            "profiles": {
+                "dev": {"access_policy": {"allow_hidden_files": true}},
+                "ci": {"extends": "dev", "access_policy": {"read_only": true}},
+                "strict": {"access_policy": {"denied_extensions": ["exe", "sh"]}, "server": {"requests_per_second": 5}}
+            }
+        }"#;
+
+        let base = Config::from_str_with_format(json, ConfigFormat::Json).unwrap();
+        assert!(!base.access_policy.allow_hidden_files);
+        assert_eq!(base.profiles.len(), 3);
+        assert!(base.active_profile.is_none());
+
+        let value: Value = serde_json::from_str(json).unwrap();
+        let ci = Config::from_value(value.clone(), Some("ci")).unwrap();
+        assert!(ci.access_policy.allow_hidden_files);
+        assert!(ci.access_policy.read_only);
+        assert_eq!(ci.access_policy.allowed_paths, vec![PathBuf::from("/srv/work")]);
+        assert_eq!(ci.active_profile.as_deref(), Some("ci"));
+
+        let strict = Config::from_value(value.clone(), Some("strict")).unwrap();
+        assert_eq!(strict.access_policy.denied_extensions, vec!["exe", "sh"]);
+        assert_eq!(strict.server.requests_per_second, 5);
+        assert!(!strict.access_policy.read_only);
+
+        let err = Config::from_value(value, Some("prod")).unwrap_err().to_string();
+        assert!(err.contains("Unknown profile 'prod'") && err.contains("ci, dev, strict"), "{}", err);
+    }
+
+    #[test]
+    fn test_profile_cycle_and_yaml_profiles() {
+        let json = r#"{"access_policy": {}, "profiles": {"a": {"extends": "b"}, "b": {"extends": "a"}}}"#;
+        let value: Value = serde_json::from_str(json).unwrap();
+        let err = Config::from_value(value, Some("a")).unwrap_err().to_string();
+        assert!(err.contains("a -> b -> a"), "{}", err);
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("filejack.yaml");
+        fs::write(&path, "access_policy: {}\nprofiles:\n  locked:\n    access_policy:\n      read_only: true\n").unwrap();
+        let config = Config::from_file_with_profile(&path, Some("locked")).unwrap();
+        assert!(config.access_policy.read_only);
+
+        // Profiles survive a save and load
+        config.to_file(&path).unwrap();
+        assert!(Config::from_file(&path).unwrap().profiles.contains_key("locked"));
+    }
+
+    #[test]
+    fn test_load_migrates_and_warns() {
+        let json = r#"{"base_path": "/workspace", "read_only": true, "access_policy": {"max_file_sise": 1}}"#;
+        let config = Config::from_str_with_format(json, ConfigFormat::Json).unwrap();
+        assert_eq!(config.schema_version, CURRENT_SCHEMA_VERSION);
+        assert_eq!(config.access_policy.allowed_paths, vec![PathBuf::from("/workspace")]);
+        assert!(config.access_policy.read_only);
+        assert_eq!(config.load_warnings.len(), 3, "{:?}", config.load_warnings);
+        assert!(config.load_warnings.iter().any(|w| w.contains("access_policy.max_file_sise")));
+
+        let issues = config.validate();
+        assert!(issues.iter().any(|i| !i.is_error() && i.message.contains("max_file_sise")));
+
+        // Saving writes the current layout, which loads without warnings
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("migrated.json");
+        config.to_file(&path).unwrap();
+        let reloaded = Config::from_file(&path).unwrap();
+        assert!(reloaded.load_warnings.is_empty(), "{:?}", reloaded.load_warnings);
+        assert!(reloaded.access_policy.read_only);
+    }
+
     #[test]
     fn test_config_json_serialization() {
         let temp_dir = TempDir::new().unwrap();
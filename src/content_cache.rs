@@ -0,0 +1,196 @@
+use crate::vfs::{self, Metadata};
+use lru::LruCache;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Cached file contents and the metadata they were read under
+struct CachedContent {
+    modified: Option<SystemTime>,
+    size: u64,
+    bytes: Arc<Vec<u8>>,
+}
+
+struct CacheState {
+    entries: LruCache<PathBuf, CachedContent>,
+    used_bytes: usize,
+}
+
+/// In-memory cache of recently read files with a total byte budget.
+///
+/// Entries are keyed by canonical path and are only served while the file's
+/// modification time and size still match, so external edits are picked up on
+/// the next read. Writes made through a [`crate::FileWriter`] sharing the cache,
+/// or by a file watcher, invalidate entries explicitly via [`ContentCache::invalidate`].
+pub struct ContentCache {
+    budget_bytes: usize,
+    state: Mutex<CacheState>,
+}
+
+impl ContentCache {
+    /// Create a cache holding at most `budget_bytes` of file contents
+    pub fn new(budget_bytes: usize) -> Self {
+        Self {
+            budget_bytes,
+            state: Mutex::new(CacheState {
+                entries: LruCache::unbounded(),
+                used_bytes: 0,
+            }),
+        }
+    }
+
+    /// Maximum number of bytes held by the cache
+    pub fn budget_bytes(&self) -> usize {
+        self.budget_bytes
+    }
+
+    /// Number of bytes currently held by the cache
+    pub fn used_bytes(&self) -> usize {
+        self.lock().used_bytes
+    }
+
+    /// Return cached contents if they still match the file's metadata
+    pub fn get(&self, path: &Path, metadata: &Metadata) -> Option<Arc<Vec<u8>>> {
+        let mut state = self.lock();
+        let fresh = match state.entries.get(path) {
+            Some(entry) => entry.size == metadata.len && entry.modified == metadata.modified,
+            None => return None,
+        };
+
+        if fresh {
+            state.entries.get(path).map(|entry| Arc::clone(&entry.bytes))
+        } else {
+            Self::remove_entry(&mut state, path);
+            None
+        }
+    }
+
+    /// Store file contents, evicting least recently used entries to stay within budget.
+    /// Files larger than the whole budget are not cached.
+    pub fn insert(&self, path: &Path, metadata: &Metadata, bytes: Arc<Vec<u8>>) {
+        if bytes.len() > self.budget_bytes {
+            return;
+        }
+
+        let mut state = self.lock();
+        Self::remove_entry(&mut state, path);
+
+        while state.used_bytes + bytes.len() > self.budget_bytes {
+            match state.entries.pop_lru() {
+                Some((_, evicted)) => state.used_bytes -= evicted.bytes.len(),
+                None => break,
+            }
+        }
+
+        state.used_bytes += bytes.len();
+        state.entries.put(path.to_path_buf(), CachedContent {
+            modified: metadata.modified,
+            size: metadata.len,
+            bytes,
+        });
+    }
+
+    /// Drop the entry for a path (and, for directories, everything beneath it)
+    pub fn invalidate(&self, path: &Path) {
+        let canonical = vfs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut state = self.lock();
+        let stale: Vec<PathBuf> = state.entries.iter()
+            .map(|(key, _)| key)
+            .filter(|key| key.starts_with(&canonical) || key.as_path() == path)
+            .cloned()
+            .collect();
+        for key in stale {
+            Self::remove_entry(&mut state, &key);
+        }
+    }
+
+    /// Drop every cached entry
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.entries.clear();
+        state.used_bytes = 0;
+    }
+
+    fn remove_entry(state: &mut CacheState, path: &Path) {
+        if let Some(entry) = state.entries.pop(path) {
+            state.used_bytes -= entry.bytes.len();
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl std::fmt::Debug for ContentCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContentCache")
+            .field("budget_bytes", &self.budget_bytes)
+            .field("used_bytes", &self.used_bytes())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cache_hit_and_metadata_invalidation() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("hot.txt");
+        fs::write(&path, "v1").unwrap();
+
+        let cache = ContentCache::new(1024);
+        let metadata = Metadata::from(fs::metadata(&path).unwrap());
+        cache.insert(&path, &metadata, Arc::new(b"v1".to_vec()));
+        assert_eq!(cache.get(&path, &metadata).unwrap().as_slice(), b"v1");
+
+        fs::write(&path, "v2 longer").unwrap();
+        let metadata = Metadata::from(fs::metadata(&path).unwrap());
+        assert!(cache.get(&path, &metadata).is_none());
+        assert_eq!(cache.used_bytes(), 0);
+    }
+
+    #[test]
+    fn test_cache_respects_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = ContentCache::new(10);
+
+        let mut paths = Vec::new();
+        for i in 0..3 {
+            let path = temp_dir.path().join(format!("f{}.txt", i));
+            fs::write(&path, "12345").unwrap();
+            let metadata = Metadata::from(fs::metadata(&path).unwrap());
+            cache.insert(&path, &metadata, Arc::new(b"12345".to_vec()));
+            paths.push((path, metadata));
+        }
+
+        assert_eq!(cache.used_bytes(), 10);
+        assert!(cache.get(&paths[0].0, &paths[0].1).is_none());
+        assert!(cache.get(&paths[2].0, &paths[2].1).is_some());
+
+        // Larger than the whole budget: never cached
+        cache.insert(&paths[0].0, &paths[0].1, Arc::new(vec![0; 11]));
+        assert!(cache.get(&paths[0].0, &paths[0].1).is_none());
+    }
+
+    #[test]
+    fn test_cache_invalidate_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let sub = temp_dir.path().join("sub");
+        fs::create_dir(&sub).unwrap();
+        let path = sub.join("a.txt");
+        fs::write(&path, "a").unwrap();
+        let path = path.canonicalize().unwrap();
+
+        let cache = ContentCache::new(1024);
+        let metadata = Metadata::from(fs::metadata(&path).unwrap());
+        cache.insert(&path, &metadata, Arc::new(b"a".to_vec()));
+
+        cache.invalidate(&sub);
+        assert!(cache.get(&path, &metadata).is_none());
+    }
+}
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// Errors that can occur in FileJack operations
@@ -13,7 +14,7 @@ pub enum FileJackError {
     FileNotFound(String),
 
     #[error("Permission denied: {0}")]
-    PermissionDenied(String),
+    PermissionDenied(Denial),
 
     #[error("Invalid path: {0}")]
     InvalidPath(String),
@@ -26,6 +27,164 @@ pub enum FileJackError {
 
     #[error("Invalid parameters: {0}")]
     InvalidParameters(String),
+
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+
+    #[error("Rate limit exceeded. Please slow down requests.")]
+    RateLimited {
+        /// Milliseconds until the request would be accepted
+        retry_after_ms: u64,
+    },
+
+    #[error("Transport error: {0}")]
+    Transport(String),
+
+    #[error("Conflict: {path} changed since it was read (current hash {hash})")]
+    Conflict {
+        path: String,
+        /// BLAKE3 hash of the file's current contents
+        hash: String,
+        /// Current modification time, in seconds since the Unix epoch
+        modified: Option<u64>,
+    },
+}
+
+/// Why a request was refused, and the policy rule that refused it if one
+/// did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Denial {
+    pub message: String,
+    pub rule: Option<PolicyRule>,
+}
+
+impl Denial {
+    /// A denial by `rule`
+    pub fn by(rule: PolicyRule, message: impl Into<String>) -> Self {
+        Self { message: message.into(), rule: Some(rule) }
+    }
+}
+
+impl From<String> for Denial {
+    fn from(message: String) -> Self {
+        Self { message, rule: None }
+    }
+}
+
+impl From<&str> for Denial {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl std::fmt::Display for Denial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.rule {
+            Some(rule) => write!(f, "{} (rule {})", self.message, rule.id),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// A rule of the access policy. Its `id` is the name given to it in the
+/// policy's `rule_ids`, or else derived from where it is configured, such as
+/// `denied_paths[2]` for the third denied path or `read_only` for a setting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyRule {
+    pub id: String,
+    pub category: RuleCategory,
+}
+
+impl PolicyRule {
+    pub fn new(id: impl Into<String>, category: RuleCategory) -> Self {
+        Self { id: id.into(), category }
+    }
+}
+
+/// The kind of rule behind a denial
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleCategory {
+    ReadOnly,
+    /// The path is outside every allowed path
+    AllowedPaths,
+    DeniedPath,
+    AppendOnly,
+    Protected,
+    /// A dotfile on the `sensitive_paths` list
+    Sensitive,
+    Extension,
+    HiddenFile,
+    /// An NTFS alternate data stream, such as `notes.txt:secret`
+    AlternateStream,
+    /// A file owned by another user
+    Owner,
+    Symlink,
+    FileSize,
+    /// A `.filejack.json` directory overlay
+    Overlay,
+}
+
+/// How far the damage of an error reaches, for deciding whether to carry on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    /// Only the request that caused it failed; the server keeps serving
+    Recoverable,
+    /// The connection to the client failed; a restarted server can serve a
+    /// new one
+    Transport,
+    /// The configuration is unusable; restarting with it fails the same way
+    Fatal,
+}
+
+impl Severity {
+    /// Status the `filejack` binary exits with when an error of this
+    /// severity stops it: 1 for a recoverable error, 74 (`EX_IOERR`) for a
+    /// transport failure, 78 (`EX_CONFIG`) for a fatal configuration error.
+    /// Usage errors exit with 2.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Severity::Recoverable => 1,
+            Severity::Transport => 74,
+            Severity::Fatal => 78,
+        }
+    }
+}
+
+impl FileJackError {
+    pub fn severity(&self) -> Severity {
+        match self {
+            FileJackError::Transport(_) => Severity::Transport,
+            FileJackError::ConfigError(_) => Severity::Fatal,
+            _ => Severity::Recoverable,
+        }
+    }
+
+    /// Whether the server can go on serving after this error, so a wrapper
+    /// need not restart it
+    pub fn is_recoverable(&self) -> bool {
+        self.severity() == Severity::Recoverable
+    }
+
+    /// Details for clients to act on, sent as the JSON-RPC error's `data`
+    pub fn data(&self) -> Option<serde_json::Value> {
+        match self {
+            FileJackError::Conflict { hash, modified, .. } => {
+                Some(serde_json::json!({"hash": hash, "modified": modified}))
+            }
+            FileJackError::PermissionDenied(Denial { rule: Some(rule), .. }) => Some(serde_json::json!({"rule": rule})),
+            FileJackError::RateLimited { retry_after_ms } => Some(serde_json::json!({"retry_after_ms": retry_after_ms})),
+            _ => None,
+        }
+    }
+
+    /// This error as a failure of the connection it happened on
+    pub(crate) fn into_transport(self) -> Self {
+        match self {
+            FileJackError::Transport(_) => self,
+            other => FileJackError::Transport(other.to_string()),
+        }
+    }
 }
 
 /// Result type alias for FileJack operations
@@ -60,15 +219,53 @@ mod tests {
     fn test_error_types() {
         let errors = vec![
             FileJackError::FileNotFound("test".to_string()),
-            FileJackError::PermissionDenied("test".to_string()),
+            FileJackError::PermissionDenied("test".into()),
             FileJackError::InvalidPath("test".to_string()),
             FileJackError::ProtocolError("test".to_string()),
             FileJackError::ToolNotFound("test".to_string()),
             FileJackError::InvalidParameters("test".to_string()),
+            FileJackError::ConfigError("test".to_string()),
+            FileJackError::RateLimited { retry_after_ms: 10 },
+            FileJackError::Transport("test".to_string()),
+            FileJackError::Conflict { path: "test".to_string(), hash: "0".repeat(64), modified: None },
         ];
 
         for err in errors {
             assert!(!err.to_string().is_empty());
         }
     }
+
+    #[test]
+    fn test_conflict_data() {
+        let err = FileJackError::Conflict { path: "a.txt".to_string(), hash: "ab".to_string(), modified: Some(7) };
+        assert_eq!(err.data(), Some(serde_json::json!({"hash": "ab", "modified": 7})));
+        assert_eq!(FileJackError::RateLimited { retry_after_ms: 40 }.data(), Some(serde_json::json!({"retry_after_ms": 40})));
+    }
+
+    #[test]
+    fn test_denial_names_its_rule() {
+        let err = FileJackError::PermissionDenied(Denial::by(
+            PolicyRule::new("denied_paths[1]", RuleCategory::DeniedPath),
+            "Access to /srv/secrets is explicitly denied",
+        ));
+        assert_eq!(err.to_string(), "Permission denied: Access to /srv/secrets is explicitly denied (rule denied_paths[1])");
+        assert_eq!(err.data(), Some(serde_json::json!({"rule": {"id": "denied_paths[1]", "category": "denied_path"}})));
+        assert_eq!(FileJackError::PermissionDenied("no".into()).data(), None);
+    }
+
+    #[test]
+    fn test_severity() {
+        assert!(FileJackError::PermissionDenied("x".into()).is_recoverable());
+        assert!(FileJackError::RateLimited { retry_after_ms: 1 }.is_recoverable());
+        assert_eq!(FileJackError::ConfigError("x".to_string()).severity(), Severity::Fatal);
+
+        let io = FileJackError::Io(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "pipe closed"));
+        assert!(io.is_recoverable());
+        let transport = io.into_transport();
+        assert_eq!(transport.severity(), Severity::Transport);
+        assert_eq!(transport.to_string(), "Transport error: IO error: pipe closed");
+
+        let codes = [Severity::Recoverable, Severity::Transport, Severity::Fatal].map(Severity::exit_code);
+        assert_eq!(codes, [1, 74, 78]);
+    }
 }
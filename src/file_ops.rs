@@ -1,178 +1,513 @@
-use crate::access_control::AccessPolicy;
-use crate::error::{FileJackError, Result};
+use crate::access_control::{AccessPolicy, WriteOperation};
+use crate::content_cache::ContentCache;
+use crate::error::{FileJackError, Result, RuleCategory};
+#[cfg(feature = "index")]
+use crate::index::FileIndex;
+use crate::overlay::OVERLAY_FILE_NAME;
+use crate::text_style::TextStyle;
+use crate::vfs::{self, FileHandle, FileKind, FileSystem, Metadata, WalkOptions};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::fs::{self, File, OpenOptions};
-use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use base64::write::EncoderStringWriter;
+use memmap2::Mmap;
+use rayon::prelude::*;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, ThreadId};
+
+/// Size of the buffer used for streaming reads (64 KiB)
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Files at or above this size are memory-mapped instead of read (8 MiB)
+pub const DEFAULT_MMAP_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+/// Most bytes `read_file` returns from one call (8 MiB)
+pub const DEFAULT_READ_LIMIT: u64 = 8 * 1024 * 1024;
+
+/// Files returned by `recent_files` when no limit is given
+pub const DEFAULT_RECENT_LIMIT: usize = 20;
+
+/// Matching lines returned by `search_content` when no limit is given
+#[cfg(feature = "index")]
+pub const DEFAULT_CONTENT_RESULTS: usize = 50;
+
+/// Whole-file contents, either memory-mapped or read into a buffer.
+///
+/// A mapping lives as long as the value. If another process truncates the
+/// file meanwhile, touching the lost pages raises SIGBUS, which ends the
+/// server; `mmap_threshold = 0` avoids mapping where that can happen.
+enum FileBytes {
+    Mapped(Mmap),
+    Buffered(Vec<u8>),
+}
+
+impl std::ops::Deref for FileBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileBytes::Mapped(map) => map,
+            FileBytes::Buffered(buf) => buf,
+        }
+    }
+}
+
+/// Interpret file bytes as UTF-8 text without copying
+fn as_text(bytes: &[u8]) -> Result<&str> {
+    std::str::from_utf8(bytes).map_err(|e| {
+        FileJackError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    })
+}
 
 /// FileReader handles reading operations from the filesystem
 #[derive(Debug, Clone)]
 pub struct FileReader {
     policy: AccessPolicy,
+    fs: Arc<dyn FileSystem>,
+    mmap_threshold: u64,
+    read_limit: u64,
+    /// Shared by every search, and by the walks they make; None uses the
+    /// global rayon pool
+    search_pool: Option<Arc<rayon::ThreadPool>>,
+    content_cache: Option<Arc<ContentCache>>,
+    #[cfg(feature = "index")]
+    index: Option<Arc<FileIndex>>,
 }
 
 impl FileReader {
     /// Create a new FileReader with an access policy
     pub fn new(policy: AccessPolicy) -> Self {
-        Self { policy }
+        Self {
+            policy,
+            fs: vfs::default_file_system(),
+            mmap_threshold: DEFAULT_MMAP_THRESHOLD,
+            read_limit: DEFAULT_READ_LIMIT,
+            search_pool: None,
+            content_cache: None,
+            #[cfg(feature = "index")]
+            index: None,
+        }
+    }
+
+    /// Read from `fs` instead of the host file system
+    pub fn with_file_system(mut self, fs: Arc<dyn FileSystem>) -> Self {
+        self.fs = fs;
+        self
+    }
+
+    /// Serve repeated whole-file reads from a shared content cache
+    pub fn with_content_cache(mut self, cache: Arc<ContentCache>) -> Self {
+        self.content_cache = Some(cache);
+        self
+    }
+
+    /// Answer `search_files` and `recent_files` from a file index for the
+    /// directories it has scanned
+    #[cfg(feature = "index")]
+    pub fn with_index(mut self, index: Arc<FileIndex>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// The index, brought up to date with recent writes, if it covers `dir`
+    #[cfg(feature = "index")]
+    fn index_for(&self, dir: &Path) -> Option<&FileIndex> {
+        let index = self.index.as_deref().filter(|index| index.covers(dir))?;
+        index.flush(self.fs.as_ref(), &self.walk_options(usize::MAX)).ok()?;
+        Some(index)
+    }
+
+    /// Set the number of threads used by recursive searches (0 picks automatically).
+    /// The pool is built once here and shared by every search.
+    pub fn with_search_threads(mut self, threads: usize) -> Self {
+        self.search_pool = match threads {
+            0 => None,
+            threads => match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+                Ok(pool) => Some(Arc::new(pool)),
+                Err(e) => {
+                    tracing::warn!(threads, error = %e, "Could not build the search thread pool; using the global pool");
+                    None
+                }
+            },
+        };
+        self
+    }
+
+    /// Set the file size at which reads switch to memory mapping (0 disables mmap)
+    pub fn with_mmap_threshold(mut self, threshold: u64) -> Self {
+        self.mmap_threshold = threshold;
+        self
+    }
+
+    /// Set the most bytes `read_page` returns at once (0 reads whole files)
+    pub fn with_read_limit(mut self, limit: u64) -> Self {
+        self.read_limit = limit;
+        self
+    }
+
+    /// Access policy enforced by this reader
+    pub fn policy(&self) -> &AccessPolicy {
+        &self.policy
+    }
+
+    /// File system this reader reads from
+    pub fn file_system(&self) -> &Arc<dyn FileSystem> {
+        &self.fs
     }
 
     /// Validate that the path is within allowed bounds
     fn validate_path(&self, path: &Path) -> Result<PathBuf> {
-        self.policy.validate_read(path)
+        self.policy.validate_read_on(self.fs.as_ref(), path)
     }
 
     /// Read file contents as a string with atomic validation
     pub fn read_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
-        let validated_path = self.validate_path(path.as_ref())?;
-        
-        // Open file first to get a file descriptor, preventing TOCTOU
-        let mut file = File::open(&validated_path).map_err(|e| {
-            match e.kind() {
-                std::io::ErrorKind::NotFound => {
-                    FileJackError::FileNotFound(validated_path.display().to_string())
-                }
-                std::io::ErrorKind::PermissionDenied => {
-                    FileJackError::PermissionDenied(validated_path.display().to_string())
-                }
-                _ => FileJackError::Io(e),
+        let bytes = self.read_to_bytes(path)?;
+        String::from_utf8(bytes).map_err(|e| {
+            FileJackError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        })
+    }
+
+    /// Read file contents as bytes with atomic validation
+    pub fn read_to_bytes<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        let (file, metadata, canonical) = self.open_regular_file(path.as_ref())?;
+        self.load_cached(file, metadata, &canonical)
+    }
+
+    /// Read an opened file to the end, through the content cache if there is one
+    fn load_cached(&self, file: Box<dyn FileHandle>, metadata: Metadata, canonical: &Path) -> Result<Vec<u8>> {
+        // Special files change with every read
+        let cache = self.content_cache.as_ref().filter(|_| metadata.is_file());
+
+        if let Some(cache) = cache {
+            if let Some(bytes) = cache.get(canonical, &metadata) {
+                return Ok(bytes.as_ref().clone());
             }
-        })?;
-        
-        // Validate file metadata using the file descriptor
-        let metadata = file.metadata()?;
-        self.policy.validate_file_size(metadata.len())?;
-        
-        // Verify it's still a regular file (not replaced with symlink)
-        if !metadata.is_file() {
-            return Err(FileJackError::InvalidPath(
-                "Path is not a regular file".to_string()
-            ));
         }
-        
-        // Read from the already-opened file descriptor
-        let mut content = String::new();
-        file.read_to_string(&mut content)?;
+
+        // Size the buffer up front so the content is not reallocated while growing
+        let mut content = Vec::with_capacity(usize::try_from(metadata.len).unwrap_or(0));
+        BufReader::with_capacity(DEFAULT_CHUNK_SIZE, file).read_to_end(&mut content)?;
+
+        if let Some(cache) = cache {
+            cache.insert(canonical, &metadata, Arc::new(content.clone()));
+        }
         Ok(content)
     }
 
-    /// Read file contents as bytes with atomic validation
-    pub fn read_to_bytes<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
-        let validated_path = self.validate_path(path.as_ref())?;
-        
+    /// Open a validated path as a regular file, returning the handle, its
+    /// metadata, and the canonical path. Special files the policy allows are
+    /// read up front, as far as the limit, and returned as a handle on what
+    /// was read.
+    fn open_regular_file(&self, path: &Path) -> Result<(Box<dyn FileHandle>, Metadata, PathBuf)> {
+        let validated_path = self.validate_path(path)?;
+        let open_error = |e: std::io::Error| match e.kind() {
+            std::io::ErrorKind::NotFound => FileJackError::FileNotFound(validated_path.display().to_string()),
+            std::io::ErrorKind::PermissionDenied => FileJackError::PermissionDenied(validated_path.display().to_string().into()),
+            _ => FileJackError::Io(e),
+        };
+
+        // Look before opening: opening a FIFO waits for a writer
+        let metadata = self.fs.metadata(&validated_path).map_err(open_error)?;
+        if is_special_file(&metadata, &validated_path) {
+            return self.open_special_file(validated_path);
+        }
+
         // Open file first to get a file descriptor, preventing TOCTOU
-        let mut file = File::open(&validated_path).map_err(|e| {
-            match e.kind() {
-                std::io::ErrorKind::NotFound => {
-                    FileJackError::FileNotFound(validated_path.display().to_string())
-                }
-                std::io::ErrorKind::PermissionDenied => {
-                    FileJackError::PermissionDenied(validated_path.display().to_string())
-                }
-                _ => FileJackError::Io(e),
-            }
-        })?;
-        
-        // Validate file metadata using the file descriptor
+        let file = self.fs.open(&validated_path).map_err(open_error)?;
+
         let metadata = file.metadata()?;
-        self.policy.validate_file_size(metadata.len())?;
-        
-        // Verify it's still a regular file
+        self.policy.validate_file_size(metadata.len)?;
+
         if !metadata.is_file() {
             return Err(FileJackError::InvalidPath(
                 "Path is not a regular file".to_string()
             ));
         }
-        
-        // Read from the already-opened file descriptor
-        let mut content = Vec::new();
-        file.read_to_end(&mut content)?;
-        Ok(content)
+
+        Ok((file, metadata, validated_path))
+    }
+
+    /// Read at most the policy's `special_file_max_bytes` from a FIFO, device,
+    /// or pseudo-file, stopping early rather than waiting for more data
+    fn open_special_file(&self, path: PathBuf) -> Result<(Box<dyn FileHandle>, Metadata, PathBuf)> {
+        let mut limit = self.policy.special_file_max_bytes;
+        if self.policy.max_file_size > 0 {
+            limit = limit.min(self.policy.max_file_size);
+        }
+        if !self.policy.allow_special_files || limit == 0 {
+            return Err(FileJackError::InvalidPath(format!(
+                "{} is a FIFO, device, or pseudo-file, which allow_special_files must permit reading",
+                path.display()
+            )));
+        }
+
+        let mut handle = self.fs.open_special(&path)?.take(limit);
+        let mut contents = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            match handle.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => contents.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let metadata = Metadata {
+            kind: vfs::FileKind::Other,
+            len: contents.len() as u64,
+            modified: None,
+            created: None,
+            readonly: true,
+            hidden: false,
+            owner: None,
+            group: None,
+            mode: None,
+        };
+        Ok((Box::new(vfs::MemoryHandle::new(contents, metadata.clone())), metadata, path))
     }
 
-    /// Check if a file exists
+    /// Load a whole file, memory-mapping it when it exceeds the mmap threshold
+    fn load_bytes(&self, path: &Path) -> Result<FileBytes> {
+        let (mut file, metadata, _) = self.open_regular_file(path)?;
+        let size = metadata.len;
+
+        if let Some(os_file) = file.as_file().filter(|_| self.mmap_threshold > 0 && size >= self.mmap_threshold) {
+            // SAFETY: the mapping is read-only and is handed to the caller inside
+            // `FileBytes`, which owns it until the caller drops it; nothing here
+            // writes through it. Its contents can still change under it, and if
+            // another process truncates the file while it is mapped, reading past
+            // the new end raises SIGBUS, as with any mmap-based reader. Callers
+            // only hold it for one read_lines or grep call.
+            let map = unsafe { Mmap::map(os_file)? };
+            return Ok(FileBytes::Mapped(map));
+        }
+
+        let mut buf = Vec::with_capacity(usize::try_from(size).unwrap_or(0));
+        BufReader::with_capacity(DEFAULT_CHUNK_SIZE, &mut file).read_to_end(&mut buf)?;
+        Ok(FileBytes::Buffered(buf))
+    }
+
+    /// Stream file contents through `f` in chunks of at most `chunk_size` bytes.
+    ///
+    /// Only one chunk is held in memory at a time. Returns the total number of
+    /// bytes read.
+    pub fn read_chunked<P, F>(&self, path: P, chunk_size: usize, mut f: F) -> Result<u64>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&[u8]) -> Result<()>,
+    {
+        let (file, _, _) = self.open_regular_file(path.as_ref())?;
+        let mut reader = BufReader::with_capacity(chunk_size.max(1), file);
+        let mut total = 0u64;
+
+        loop {
+            let chunk = reader.fill_buf()?;
+            if chunk.is_empty() {
+                break;
+            }
+            let len = chunk.len();
+            f(chunk)?;
+            reader.consume(len);
+            total += len as u64;
+        }
+
+        Ok(total)
+    }
+
+    /// Read at most the reader's read limit from byte `offset`, so memory
+    /// use is bounded whatever the file's size.
+    ///
+    /// Returns the bytes and, when the file goes on past them, the offset to
+    /// continue from. A whole file that fits is read through the content cache.
+    pub fn read_page<P: AsRef<Path>>(&self, path: P, offset: u64) -> Result<(Vec<u8>, Option<u64>)> {
+        let (mut file, metadata, canonical) = self.open_regular_file(path.as_ref())?;
+        let size = metadata.len;
+        if offset == 0 && (self.read_limit == 0 || size <= self.read_limit) {
+            return Ok((self.load_cached(file, metadata, &canonical)?, None));
+        }
+        if offset >= size {
+            return Ok((Vec::new(), None));
+        }
+
+        let length = match self.read_limit {
+            0 => size - offset,
+            limit => limit.min(size - offset),
+        };
+        let mut page = Vec::with_capacity(usize::try_from(length).unwrap_or(0));
+        file.seek(SeekFrom::Start(offset))?;
+        BufReader::with_capacity(DEFAULT_CHUNK_SIZE, file).take(length).read_to_end(&mut page)?;
+
+        let end = offset + page.len() as u64;
+        Ok((page, (end < size).then_some(end)))
+    }
+
+    /// Read file contents as base64, encoding incrementally while streaming
+    pub fn read_to_base64<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        let (file, metadata, _) = self.open_regular_file(path.as_ref())?;
+        let size = metadata.len;
+
+        // Encoded output is 4/3 of the input, rounded up to a whole quantum
+        let encoded_len = usize::try_from(size.div_ceil(3) * 4).unwrap_or(0);
+        let mut encoder = EncoderStringWriter::from_consumer(String::with_capacity(encoded_len), &BASE64);
+        let mut reader = BufReader::with_capacity(DEFAULT_CHUNK_SIZE, file);
+        std::io::copy(&mut reader, &mut encoder)?;
+
+        Ok(encoder.into_inner())
+    }
+
+    /// Read up to `length` bytes starting at byte `offset`, and no more than
+    /// the reader's read limit.
+    ///
+    /// Returns the bytes read and whether the end of the file was reached.
+    pub fn read_range<P: AsRef<Path>>(&self, path: P, offset: u64, length: usize) -> Result<(Vec<u8>, bool)> {
+        self.policy.validate_file_size(length as u64)?;
+        let length = match usize::try_from(self.read_limit) {
+            Ok(0) | Err(_) => length,
+            Ok(limit) => length.min(limit),
+        };
+        let (mut file, metadata, _) = self.open_regular_file(path.as_ref())?;
+        let size = metadata.len;
+
+        if offset >= size {
+            return Ok((Vec::new(), true));
+        }
+
+        let available = usize::try_from(size - offset).unwrap_or(usize::MAX);
+        let mut buf = vec![0u8; length.min(available)];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buf)?;
+
+        let eof = offset + buf.len() as u64 >= size;
+        Ok((buf, eof))
+    }
+
+    /// Check if a file exists.
+    ///
+    /// Paths the policy does not allow reading are reported as missing, so
+    /// this reveals nothing about the tree outside the allowed paths.
     pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
-        path.as_ref().exists()
+        self.validate_path(path.as_ref())
+            .is_ok_and(|validated_path| self.fs.exists(&validated_path))
     }
 
     /// Get file metadata
     pub fn get_metadata<P: AsRef<Path>>(&self, path: P) -> Result<FileMetadata> {
         let validated_path = self.validate_path(path.as_ref())?;
-        let metadata = fs::metadata(&validated_path)?;
-        
+        let metadata = self.fs.metadata(&validated_path)?;
+        let seconds = |time: Option<std::time::SystemTime>| time
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+
         Ok(FileMetadata {
-            size: metadata.len(),
+            size: metadata.len,
             is_file: metadata.is_file(),
             is_dir: metadata.is_dir(),
             is_symlink: metadata.is_symlink(),
-            modified: metadata.modified().ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs()),
-            created: metadata.created().ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs()),
-            readonly: metadata.permissions().readonly(),
+            modified: seconds(metadata.modified),
+            created: seconds(metadata.created),
+            readonly: metadata.readonly,
+            owner: metadata.owner,
+            group: metadata.group,
+            mode: metadata.mode,
+            hash: None,
+            style: None,
         })
     }
 
+    /// Names and values of a file's extended attributes, sorted by name
+    pub fn extended_attributes<P: AsRef<Path>>(&self, path: P) -> Result<Vec<(String, Vec<u8>)>> {
+        let validated_path = self.validate_path(path.as_ref())?;
+        Ok(self.fs.extended_attributes(&validated_path)?)
+    }
+
     /// List directory contents
     pub fn list_directory<P: AsRef<Path>>(&self, path: P, recursive: bool) -> Result<Vec<DirectoryEntry>> {
+        self.list_directory_with(path, &ListOptions { recursive, ..Default::default() })
+    }
+
+    /// List directory contents, filtered, sorted, and paged by `options`
+    pub fn list_directory_with<P: AsRef<Path>>(&self, path: P, options: &ListOptions) -> Result<Vec<DirectoryEntry>> {
         let validated_path = self.validate_path(path.as_ref())?;
-        
-        if !validated_path.is_dir() {
+
+        if !self.fs.metadata(&validated_path).is_ok_and(|m| m.is_dir()) {
             return Err(FileJackError::InvalidPath(
                 "Path is not a directory".to_string()
             ));
         }
+        let pattern = options.pattern.as_deref().map(PathGlob::new).transpose()?;
+        let recursive = options.recursive;
 
-        let mut entries = Vec::new();
+        let found = if recursive {
+            let options = WalkOptions { skip_hidden: false, ..self.walk_options(usize::MAX) };
+            self.fs.walk(&validated_path, &options)?
+        } else {
+            self.fs.list(&validated_path)?
+        };
+        // Sizes come from the entry itself unless the walk followed links
+        let follows_links = recursive && self.policy.allow_symlinks;
 
-        if recursive {
-            for entry in WalkDir::new(&validated_path)
-                .follow_links(self.policy.allow_symlinks)
-                .into_iter()
-                .filter_map(|e| e.ok())
-            {
-                let path = entry.path();
-                if path == validated_path {
-                    continue; // Skip the root directory itself
-                }
-                
-                // Validate each entry against policy
-                if self.validate_path(path).is_ok() {
-                    entries.push(DirectoryEntry {
-                        path: path.display().to_string(),
-                        name: path.file_name()
-                            .and_then(|n| n.to_str())
-                            .unwrap_or("")
-                            .to_string(),
-                        is_file: entry.file_type().is_file(),
-                        is_dir: entry.file_type().is_dir(),
-                        size: entry.metadata().ok().map(|m| m.len()),
-                    });
-                }
+        let mut entries = Vec::new();
+        for entry in found {
+            // Validate each entry against policy
+            if self.validate_path(&entry.path).is_err() {
+                continue;
             }
-        } else {
-            for entry in fs::read_dir(&validated_path)? {
-                let entry = entry?;
-                let path = entry.path();
-                
-                // Validate each entry against policy
-                if self.validate_path(&path).is_ok() {
-                    let metadata = entry.metadata()?;
-                    entries.push(DirectoryEntry {
-                        path: path.display().to_string(),
-                        name: entry.file_name().to_string_lossy().to_string(),
-                        is_file: metadata.is_file(),
-                        is_dir: metadata.is_dir(),
-                        size: Some(metadata.len()),
-                    });
+            if options.entry_type.is_some_and(|entry_type| !entry_type.matches(entry.kind)) {
+                continue;
+            }
+            if let Some(pattern) = &pattern {
+                let relative = entry.path.strip_prefix(&validated_path).map(slash_path).unwrap_or_default();
+                if !pattern.matches(&relative) {
+                    continue;
                 }
             }
+            let metadata = if follows_links {
+                self.fs.metadata(&entry.path)
+            } else {
+                self.fs.symlink_metadata(&entry.path)
+            };
+            let (size, modified) = match metadata {
+                Ok(metadata) => (Some(metadata.len), metadata.modified),
+                Err(_) if recursive => (None, None),
+                Err(e) => return Err(e.into()),
+            };
+            let file_name = entry.path.file_name().unwrap_or_default();
+            let unicode = file_name.to_str().is_some();
+            entries.push((modified, DirectoryEntry {
+                path: vfs::escape_path(&entry.path),
+                name: vfs::escape_path(Path::new(file_name)),
+                display_name: (!unicode).then(|| file_name.to_string_lossy().into_owned()),
+                raw_name: (!unicode).then(|| BASE64.encode(file_name.as_encoded_bytes())),
+                is_file: entry.kind == vfs::FileKind::File,
+                is_dir: entry.kind == vfs::FileKind::Dir,
+                size,
+                modified: modified
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs()),
+            }));
         }
 
-        Ok(entries)
+        // Ties fall back to the path, so pages do not overlap
+        match options.sort_by {
+            SortBy::Path => entries.sort_by(|a, b| a.1.path.cmp(&b.1.path)),
+            SortBy::Name => entries.sort_by(|a, b| a.1.name.cmp(&b.1.name).then_with(|| a.1.path.cmp(&b.1.path))),
+            SortBy::Size => entries.sort_by(|a, b| a.1.size.cmp(&b.1.size).then_with(|| a.1.path.cmp(&b.1.path))),
+            SortBy::Mtime => entries.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.path.cmp(&b.1.path))),
+        }
+        if options.order == SortOrder::Desc {
+            entries.reverse();
+        }
+        Ok(entries.into_iter()
+            .skip(options.offset)
+            .take(options.limit.unwrap_or(usize::MAX))
+            .map(|(_, entry)| entry)
+            .collect())
     }
 
     /// Read specific lines from a file
@@ -183,58 +518,32 @@ impl FileReader {
         end_line: Option<usize>,
         tail: Option<usize>,
     ) -> Result<Vec<String>> {
-        let validated_path = self.validate_path(path.as_ref())?;
-        
-        // Open file first to get a file descriptor, preventing TOCTOU
-        let file = File::open(&validated_path).map_err(|e| {
-            match e.kind() {
-                std::io::ErrorKind::NotFound => {
-                    FileJackError::FileNotFound(validated_path.display().to_string())
-                }
-                std::io::ErrorKind::PermissionDenied => {
-                    FileJackError::PermissionDenied(validated_path.display().to_string())
-                }
-                _ => FileJackError::Io(e),
-            }
-        })?;
-        
-        // Validate file metadata using the file descriptor
-        let metadata = file.metadata()?;
-        self.policy.validate_file_size(metadata.len())?;
-        
-        // Verify it's a regular file
-        if !metadata.is_file() {
-            return Err(FileJackError::InvalidPath(
-                "Path is not a regular file".to_string()
-            ));
-        }
-        
-        use std::io::BufRead;
-        let reader = std::io::BufReader::new(file);
-        let all_lines: Vec<String> = reader.lines().collect::<std::io::Result<Vec<_>>>()?;
-        
+        let bytes = self.load_bytes(path.as_ref())?;
+        let text = as_text(&bytes)?;
+
+        // Only the selected lines are copied out of the file contents
+        let all_lines: Vec<&str> = text.lines().collect();
+
         // Handle tail mode
         if let Some(n) = tail {
-            let start = if all_lines.len() > n {
-                all_lines.len() - n
-            } else {
-                0
-            };
-            return Ok(all_lines[start..].to_vec());
+            let start = all_lines.len().saturating_sub(n);
+            return Ok(all_lines[start..].iter().map(|l| l.to_string()).collect());
         }
-        
+
         // Handle line range
         let start_idx = start_line.unwrap_or(1).saturating_sub(1); // Convert to 0-based
         let end_idx = end_line.unwrap_or(all_lines.len()).min(all_lines.len());
-        
-        if start_idx >= all_lines.len() {
+
+        if start_idx >= end_idx {
             return Ok(Vec::new());
         }
-        
-        Ok(all_lines[start_idx..end_idx].to_vec())
+
+        Ok(all_lines[start_idx..end_idx].iter().map(|l| l.to_string()).collect())
     }
 
     /// Search for files matching a glob pattern
+    ///
+    /// Directories are walked in parallel; results are returned in sorted order.
     pub fn search_files<P: AsRef<Path>>(
         &self,
         base_path: P,
@@ -243,44 +552,158 @@ impl FileReader {
         max_results: Option<usize>,
     ) -> Result<Vec<String>> {
         let validated_path = self.validate_path(base_path.as_ref())?;
-        
-        if !validated_path.is_dir() {
+
+        if !self.fs.metadata(&validated_path).is_ok_and(|m| m.is_dir()) {
             return Err(FileJackError::InvalidPath(
                 "Base path must be a directory".to_string()
             ));
         }
-        
+
         let glob_pattern = glob::Pattern::new(pattern)
             .map_err(|e| FileJackError::InvalidParameters(format!("Invalid glob pattern: {}", e)))?;
-        
+
+        #[cfg(feature = "index")]
+        if let Some(index) = self.index_for(&validated_path) {
+            let matches = index.list(&validated_path, recursive)?
+                .into_iter()
+                .map(|file| file.path)
+                .filter(|path| {
+                    path.file_name()
+                        .is_some_and(|name| glob_pattern.matches(&vfs::escape_path(Path::new(name))))
+                })
+                .filter(|path| self.validate_path(path).is_ok())
+                .take(max_results.unwrap_or(usize::MAX))
+                .map(|path| vfs::escape_path(&path));
+            return Ok(matches.collect());
+        }
+
         let mut results = Vec::new();
-        let walker = if recursive {
-            WalkDir::new(&validated_path).follow_links(self.policy.allow_symlinks)
-        } else {
-            WalkDir::new(&validated_path).max_depth(1).follow_links(self.policy.allow_symlinks)
-        };
-        
-        for entry in walker.into_iter().filter_map(|e| e.ok()) {
+        let max_depth = if recursive { usize::MAX } else { 1 };
+
+        for entry in self.fs.walk(&validated_path, &self.walk_options(max_depth))? {
             if let Some(max) = max_results {
                 if results.len() >= max {
                     break;
                 }
             }
-            
-            let path = entry.path();
+
+            // Names that are not UTF-8 are matched in their escaped form
+            let path = entry.path;
             if let Some(file_name) = path.file_name() {
-                if let Some(name_str) = file_name.to_str() {
-                    if glob_pattern.matches(name_str) && self.validate_path(path).is_ok() {
-                        results.push(path.display().to_string());
-                    }
+                if glob_pattern.matches(&vfs::escape_path(Path::new(file_name))) && self.validate_path(&path).is_ok() {
+                    results.push(vfs::escape_path(&path));
                 }
             }
         }
-        
+
         Ok(results)
     }
 
+    /// The most recently modified files beneath a directory, newest first
+    pub fn recent_files<P: AsRef<Path>>(&self, base_path: P, limit: Option<usize>) -> Result<Vec<RecentFile>> {
+        let validated_path = self.validate_path(base_path.as_ref())?;
+        if !self.fs.metadata(&validated_path).is_ok_and(|m| m.is_dir()) {
+            return Err(FileJackError::InvalidPath(
+                "Base path must be a directory".to_string()
+            ));
+        }
+        let limit = limit.unwrap_or(DEFAULT_RECENT_LIMIT);
+
+        #[cfg(feature = "index")]
+        if let Some(index) = self.index_for(&validated_path) {
+            let files = index.recent(&validated_path, limit, |path| self.validate_path(path).is_ok())?;
+            return Ok(files.into_iter()
+                .map(|file| RecentFile {
+                    path: vfs::escape_path(&file.path),
+                    size: file.size,
+                    modified: file.modified(),
+                    hash: file.hash,
+                })
+                .collect());
+        }
+
+        let mut files: Vec<(Option<std::time::SystemTime>, RecentFile)> = self.fs
+            .walk(&validated_path, &self.walk_options(usize::MAX))?
+            .into_iter()
+            .filter(|entry| entry.kind == vfs::FileKind::File)
+            .filter(|entry| self.validate_path(&entry.path).is_ok())
+            .filter_map(|entry| {
+                let metadata = self.fs.metadata(&entry.path).ok()?;
+                let modified = metadata.modified
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs());
+                Some((metadata.modified, RecentFile {
+                    path: vfs::escape_path(&entry.path),
+                    size: metadata.len,
+                    modified,
+                    hash: None,
+                }))
+            })
+            .collect();
+        files.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.path.cmp(&b.1.path)));
+        Ok(files.into_iter().take(limit).map(|(_, file)| file).collect())
+    }
+
+    /// Lines beneath a directory containing `query`, ignoring case, grouped
+    /// by file with the best-matching files first.
+    ///
+    /// Answered from the file index's full-text table when it covers the
+    /// directory, where files are ranked by BM25; otherwise every file is
+    /// read and files are ranked by how many lines match.
+    #[cfg(feature = "index")]
+    pub fn search_content<P: AsRef<Path>>(
+        &self,
+        base_path: P,
+        query: &str,
+        max_results: Option<usize>,
+    ) -> Result<Vec<crate::protocol::GrepMatch>> {
+        let validated_path = self.validate_path(base_path.as_ref())?;
+        if !self.fs.metadata(&validated_path).is_ok_and(|m| m.is_dir()) {
+            return Err(FileJackError::InvalidPath(
+                "Base path must be a directory".to_string()
+            ));
+        }
+        if query.chars().count() < crate::index::MIN_CONTENT_QUERY_CHARS {
+            return Err(FileJackError::InvalidParameters(format!(
+                "Query must be at least {} characters",
+                crate::index::MIN_CONTENT_QUERY_CHARS
+            )));
+        }
+        let limit = max_results.unwrap_or(DEFAULT_CONTENT_RESULTS);
+        let regex = regex::Regex::new(&format!("(?i){}", regex::escape(query)))
+            .map_err(|e| FileJackError::InvalidParameters(format!("Invalid query: {}", e)))?;
+
+        if let Some(index) = self.index_for(&validated_path) {
+            let mut matches = Vec::new();
+            // Every matching file contributes at least one line, so `limit`
+            // files are always enough
+            for (path, text) in index.search_text(&validated_path, query, limit, |path| self.validate_path(path).is_ok())? {
+                let file_name = vfs::escape_path(&path);
+                for mut m in grep_text(&regex, &text, Some(limit - matches.len()), 0) {
+                    m.file = Some(file_name.clone());
+                    matches.push(m);
+                }
+                if matches.len() >= limit {
+                    break;
+                }
+            }
+            return Ok(matches);
+        }
+
+        let mut matches = self.grep_directory(&validated_path, &regex, None, 0)?;
+        let mut counts = std::collections::HashMap::new();
+        for m in &matches {
+            *counts.entry(m.file.clone()).or_insert(0usize) += 1;
+        }
+        matches.sort_by(|a, b| counts[&b.file].cmp(&counts[&a.file]).then_with(|| a.file.cmp(&b.file)));
+        matches.truncate(limit);
+        Ok(matches)
+    }
+
     /// Search for pattern in file contents using regex
+    ///
+    /// If `path` is a directory, every readable file beneath it is searched in
+    /// parallel and each match records the file it came from.
     pub fn grep_file<P: AsRef<Path>>(
         &self,
         path: P,
@@ -288,69 +711,146 @@ impl FileReader {
         max_matches: Option<usize>,
         context_lines: Option<usize>,
     ) -> Result<Vec<crate::protocol::GrepMatch>> {
-        let validated_path = self.validate_path(path.as_ref())?;
-        
-        // Open file first
-        let file = File::open(&validated_path).map_err(|e| {
-            match e.kind() {
-                std::io::ErrorKind::NotFound => {
-                    FileJackError::FileNotFound(validated_path.display().to_string())
-                }
-                std::io::ErrorKind::PermissionDenied => {
-                    FileJackError::PermissionDenied(validated_path.display().to_string())
-                }
-                _ => FileJackError::Io(e),
-            }
-        })?;
-        
-        let metadata = file.metadata()?;
-        self.policy.validate_file_size(metadata.len())?;
-        
-        if !metadata.is_file() {
-            return Err(FileJackError::InvalidPath(
-                "Path is not a regular file".to_string()
-            ));
-        }
-        
         let regex = regex::Regex::new(pattern)
             .map_err(|e| FileJackError::InvalidParameters(format!("Invalid regex pattern: {}", e)))?;
-        
-        use std::io::BufRead;
-        let reader = std::io::BufReader::new(file);
-        let all_lines: Vec<String> = reader.lines().collect::<std::io::Result<Vec<_>>>()?;
-        
-        let mut matches = Vec::new();
         let context = context_lines.unwrap_or(0);
-        
-        for (line_num, line) in all_lines.iter().enumerate() {
-            if regex.is_match(line) {
-                if let Some(max) = max_matches {
-                    if matches.len() >= max {
-                        break;
+
+        let validated_path = self.validate_path(path.as_ref())?;
+        if self.fs.metadata(&validated_path).is_ok_and(|m| m.is_dir()) {
+            return self.grep_directory(&validated_path, &regex, max_matches, context);
+        }
+
+        let bytes = self.load_bytes(path.as_ref())?;
+        let text = as_text(&bytes)?;
+        Ok(grep_text(&regex, text, max_matches, context))
+    }
+
+    /// Search the files under `validated_path`, which must already have
+    /// passed validation
+    fn grep_directory(
+        &self,
+        validated_path: &Path,
+        regex: &regex::Regex,
+        max_matches: Option<usize>,
+        context: usize,
+    ) -> Result<Vec<crate::protocol::GrepMatch>> {
+        let files: Vec<PathBuf> = self.fs.walk(validated_path, &self.walk_options(usize::MAX))?
+            .into_iter()
+            .filter(|e| e.kind == vfs::FileKind::File)
+            .map(|e| e.path)
+            .collect();
+
+        // Files that fail policy checks or are not valid UTF-8 are skipped
+        let search = |batch: &[PathBuf]| -> Vec<crate::protocol::GrepMatch> {
+            batch.par_iter()
+                .filter_map(|file| {
+                    let bytes = self.load_bytes(file).ok()?;
+                    let text = as_text(&bytes).ok()?;
+                    let mut matches = grep_text(regex, text, max_matches, context);
+                    let file_name = vfs::escape_path(file);
+                    for m in &mut matches {
+                        m.file = Some(file_name.clone());
                     }
-                }
-                
-                let start_context = line_num.saturating_sub(context);
-                let end_context = (line_num + context + 1).min(all_lines.len());
-                
-                let context_before = all_lines[start_context..line_num].to_vec();
-                let context_after = all_lines[line_num + 1..end_context].to_vec();
-                
-                matches.push(crate::protocol::GrepMatch {
-                    line_number: line_num + 1, // 1-based line numbers
-                    line_content: line.clone(),
-                    context_before,
-                    context_after,
-                });
+                    Some(matches)
+                })
+                .flatten()
+                .collect()
+        };
+
+        // Files are searched a batch at a time, in walk order, so the search
+        // stops soon after `max_matches` and the matches kept are the first ones
+        let batch_size = match max_matches {
+            Some(_) => self.search_pool.as_ref().map_or_else(rayon::current_num_threads, |pool| pool.current_num_threads()) * 4,
+            None => files.len(),
+        };
+        let mut matches = Vec::new();
+        for batch in files.chunks(batch_size.max(1)) {
+            matches.extend(match &self.search_pool {
+                Some(pool) => pool.install(|| search(batch)),
+                None => search(batch),
+            });
+            if let Some(max) = max_matches.filter(|max| matches.len() >= *max) {
+                matches.truncate(max);
+                break;
             }
         }
-        
         Ok(matches)
     }
+
+    /// How searches walk a tree: honoring the symlink policy and thread
+    /// setting, and leaving out hidden entries such as `.git`
+    pub(crate) fn walk_options(&self, max_depth: usize) -> WalkOptions {
+        WalkOptions {
+            max_depth,
+            follow_links: self.policy.allow_symlinks,
+            skip_hidden: true,
+            pool: self.search_pool.clone(),
+        }
+    }
+}
+
+/// Collect regex matches (with surrounding context) from text
+fn grep_text(
+    regex: &regex::Regex,
+    text: &str,
+    max_matches: Option<usize>,
+    context: usize,
+) -> Vec<crate::protocol::GrepMatch> {
+    let all_lines: Vec<&str> = text.lines().collect();
+    let mut matches = Vec::new();
+
+    for (line_num, line) in all_lines.iter().enumerate() {
+        if regex.is_match(line) {
+            if let Some(max) = max_matches {
+                if matches.len() >= max {
+                    break;
+                }
+            }
+
+            let start_context = line_num.saturating_sub(context);
+            let end_context = (line_num + context + 1).min(all_lines.len());
+
+            let context_before = all_lines[start_context..line_num].iter().map(|l| l.to_string()).collect();
+            let context_after = all_lines[line_num + 1..end_context].iter().map(|l| l.to_string()).collect();
+
+            matches.push(crate::protocol::GrepMatch {
+                file: None,
+                line_number: line_num + 1, // 1-based line numbers
+                line_content: line.to_string(),
+                context_before,
+                context_after,
+            });
+        }
+    }
+
+    matches
+}
+
+/// Directories of Linux pseudo-files, which claim to be regular files but
+/// are generated as they are read
+#[cfg(target_os = "linux")]
+const PSEUDO_FILE_ROOTS: &[&str] = &["/proc", "/sys"];
+
+/// Whether `path` is a FIFO, device, socket, or pseudo-file rather than a
+/// regular file or directory
+fn is_special_file(metadata: &Metadata, path: &Path) -> bool {
+    #[cfg(target_os = "linux")]
+    if PSEUDO_FILE_ROOTS.iter().any(|root| path.starts_with(root)) && !metadata.is_dir() {
+        return true;
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = path;
+    !metadata.is_file() && !metadata.is_dir()
+}
+
+/// BLAKE3 hash of file contents as lowercase hex, the form `get_metadata`
+/// reports and `if_match` accepts
+pub fn content_hash(bytes: &[u8]) -> String {
+    blake3::hash(bytes).to_hex().to_string()
 }
 
 /// File metadata information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FileMetadata {
     pub size: u64,
     pub is_file: bool,
@@ -359,23 +859,237 @@ pub struct FileMetadata {
     pub modified: Option<u64>,
     pub created: Option<u64>,
     pub readonly: bool,
+    /// User id of the owner, on Unix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<u32>,
+    /// Group id of the owner, on Unix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<u32>,
+    /// Permission bits, such as `0o644` reported as 420, on Unix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// [`content_hash`] of a file, when asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Byte order mark and line ending of a text file, reported with `hash`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<TextStyle>,
 }
 
 /// Directory entry information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DirectoryEntry {
+    /// Written as [`vfs::escape_path`] does, so tools accept it back
     pub path: String,
     pub name: String,
+    /// For a name that is not UTF-8: the name for showing to people, with
+    /// U+FFFD for the bytes that are not
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// For a name that is not UTF-8: base64 of its bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_name: Option<String>,
     pub is_file: bool,
     pub is_dir: bool,
     pub size: Option<u64>,
+    /// Modification time in seconds since the Unix epoch
+    pub modified: Option<u64>,
+}
+
+/// Which entries `list_directory_with` returns, and in what order
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    pub recursive: bool,
+    /// Glob for the entries to include, as [`PathGlob`] matches it
+    pub pattern: Option<String>,
+    pub entry_type: Option<EntryType>,
+    pub sort_by: SortBy,
+    pub order: SortOrder,
+    /// Entries to skip once filtered and sorted
+    pub offset: usize,
+    /// Most entries to return after `offset`
+    pub limit: Option<usize>,
+}
+
+/// Kind of entry a listing is limited to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryType {
+    File,
+    Dir,
+}
+
+impl EntryType {
+    fn matches(self, kind: FileKind) -> bool {
+        match self {
+            EntryType::File => kind == FileKind::File,
+            EntryType::Dir => kind == FileKind::Dir,
+        }
+    }
+}
+
+/// Key a listing is sorted by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    /// Full path, so a recursive listing keeps each directory's entries together
+    #[default]
+    Path,
+    /// Entry name alone
+    Name,
+    /// Size in bytes
+    Size,
+    /// Modification time
+    Mtime,
+}
+
+/// Direction a listing is sorted in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// A glob matched against file names, or against paths relative to a base
+/// directory when it contains `/`, so `*.rs` finds Rust files at any depth
+/// and `src/**/*.rs` only those under `src`
+pub(crate) struct PathGlob {
+    glob: glob::Pattern,
+    whole_path: bool,
+}
+
+impl PathGlob {
+    pub(crate) fn new(pattern: &str) -> Result<Self> {
+        let glob = glob::Pattern::new(pattern)
+            .map_err(|e| FileJackError::InvalidParameters(format!("Invalid glob pattern: {}", e)))?;
+        Ok(Self { glob, whole_path: pattern.contains('/') })
+    }
+
+    /// Whether `relative`, a path with `/` separators, matches
+    pub(crate) fn matches(&self, relative: &str) -> bool {
+        if self.whole_path {
+            let options = glob::MatchOptions { require_literal_separator: true, ..Default::default() };
+            self.glob.matches_with(relative, options)
+        } else {
+            self.glob.matches(relative.rsplit('/').next().unwrap_or_default())
+        }
+    }
+}
+
+/// `path` with `/` between its components on every platform
+pub(crate) fn slash_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(vfs::escape_path(Path::new(name))),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// A file returned by `recent_files`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentFile {
+    pub path: String,
+    pub size: u64,
+    /// Modification time in seconds since the Unix epoch
+    pub modified: Option<u64>,
+    /// BLAKE3 hash of the contents, when answered from the file index
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+/// What copying a directory tree copied
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CopySummary {
+    pub files: u64,
+    pub directories: u64,
+    pub bytes: u64,
+}
+
+/// An entry of a tree being copied or moved, and where it goes
+struct TreeEntry {
+    from: PathBuf,
+    to: PathBuf,
+    kind: FileKind,
+}
+
+/// Directories whose files a thread of this process is updating, with the
+/// thread and how many of its updates are under way
+static UPDATING: Mutex<BTreeMap<PathBuf, (ThreadId, usize)>> = Mutex::new(BTreeMap::new());
+
+/// Signalled whenever a directory in [`UPDATING`] is released
+static UPDATED: Condvar = Condvar::new();
+
+/// Held while a file is read, changed, and written back, so that updates to
+/// files in its directory, from this process or another, take turns.
+///
+/// The lock is on the directory rather than the file, which writes replace
+/// by renaming. A thread may take it again while it holds it.
+#[derive(Debug)]
+pub struct UpdateLock {
+    dir: PathBuf,
+    directory_lock: Option<vfs::DirectoryLock>,
+}
+
+impl UpdateLock {
+    fn acquire(fs: &dyn FileSystem, path: &Path) -> Result<Self> {
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let dir = fs.canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        let current = thread::current().id();
+
+        let mut updating = UPDATING.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            match updating.get_mut(&dir) {
+                Some((owner, depth)) if *owner == current => {
+                    *depth += 1;
+                    return Ok(Self { dir, directory_lock: None });
+                }
+                Some(_) => updating = UPDATED.wait(updating).unwrap_or_else(|e| e.into_inner()),
+                None => break,
+            }
+        }
+        updating.insert(dir.clone(), (current, 1));
+        drop(updating);
+
+        // Only the outermost lock waits for other processes
+        match fs.lock_directory(&dir) {
+            Ok(directory_lock) => Ok(Self { dir, directory_lock }),
+            Err(e) => {
+                drop(Self { dir, directory_lock: None });
+                Err(FileJackError::Io(e))
+            }
+        }
+    }
+}
+
+impl Drop for UpdateLock {
+    fn drop(&mut self) {
+        // Other processes first, then this one
+        drop(self.directory_lock.take());
+        let mut updating = UPDATING.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, depth)) = updating.get_mut(&self.dir) {
+            *depth -= 1;
+            if *depth == 0 {
+                updating.remove(&self.dir);
+                UPDATED.notify_all();
+            }
+        }
+    }
 }
 
 /// FileWriter handles writing operations to the filesystem
 #[derive(Debug, Clone)]
 pub struct FileWriter {
     policy: AccessPolicy,
+    fs: Arc<dyn FileSystem>,
     create_dirs: bool,
+    fsync: bool,
+    content_cache: Option<Arc<ContentCache>>,
+    #[cfg(feature = "index")]
+    index: Option<Arc<FileIndex>>,
 }
 
 impl FileWriter {
@@ -383,211 +1097,571 @@ impl FileWriter {
     pub fn new(policy: AccessPolicy, create_dirs: bool) -> Self {
         Self {
             policy,
+            fs: vfs::default_file_system(),
             create_dirs,
+            fsync: true,
+            content_cache: None,
+            #[cfg(feature = "index")]
+            index: None,
         }
     }
 
-    /// Validate that the path is within allowed bounds
-    fn validate_path(&self, path: &Path) -> Result<PathBuf> {
-        self.policy.validate_write(path)
+    /// Write to `fs` instead of the host file system
+    pub fn with_file_system(mut self, fs: Arc<dyn FileSystem>) -> Self {
+        self.fs = fs;
+        self
     }
 
-    /// Write string content to a file atomically
-    pub fn write_string<P: AsRef<Path>>(&self, path: P, content: &str) -> Result<()> {
-        let validated_path = self.validate_path(path.as_ref())?;
+    /// Invalidate entries in a shared content cache when files are modified
+    pub fn with_content_cache(mut self, cache: Arc<ContentCache>) -> Self {
+        self.content_cache = Some(cache);
+        self
+    }
 
-        // Check file size before writing
-        self.policy.validate_file_size(content.len() as u64)?;
+    /// Mark paths in a shared file index dirty when they are modified
+    #[cfg(feature = "index")]
+    pub fn with_index(mut self, index: Arc<FileIndex>) -> Self {
+        self.index = Some(index);
+        self
+    }
 
-        if self.create_dirs {
-            if let Some(parent) = validated_path.parent() {
-                fs::create_dir_all(parent)?;
+    /// Drop cached contents for a path that is about to change
+    pub(crate) fn invalidate(&self, path: &Path) {
+        if let Some(cache) = &self.content_cache {
+            cache.invalidate(path);
+        }
+        #[cfg(feature = "index")]
+        if let Some(index) = &self.index {
+            index.mark_dirty(path);
+        }
+    }
+
+    /// Set whether writes are flushed to disk before returning
+    pub fn with_fsync(mut self, fsync: bool) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    /// Access policy enforced by this writer
+    pub fn policy(&self) -> &AccessPolicy {
+        &self.policy
+    }
+
+    /// File system this writer writes to
+    pub fn file_system(&self) -> &Arc<dyn FileSystem> {
+        &self.fs
+    }
+
+    /// Check that the policy allows writing `path` on this writer's file system
+    pub fn validate_path(&self, path: &Path) -> Result<PathBuf> {
+        self.validate_operation(path, WriteOperation::Write)
+    }
+
+    /// Check that the policy allows a write of kind `operation` to `path`
+    /// on this writer's file system
+    pub fn validate_operation(&self, path: &Path, operation: WriteOperation) -> Result<PathBuf> {
+        self.policy.validate_write_on(self.fs.as_ref(), path, operation)
+    }
+
+    /// Check that the policy allows copying from `path`, which only reads it
+    fn validate_source(&self, path: &Path) -> Result<PathBuf> {
+        self.policy.validate_read_on(self.fs.as_ref(), path)
+    }
+
+    /// Whether `path` is a regular file holding exactly `content`.
+    ///
+    /// The path is validated for writing, so this reveals nothing a write
+    /// would not. Files of a different size are not read.
+    pub fn holds<P: AsRef<Path>>(&self, path: P, content: &[u8]) -> Result<bool> {
+        let validated_path = self.validate_path(path.as_ref())?;
+        match self.fs.symlink_metadata(&validated_path) {
+            Ok(metadata) if metadata.is_file() && metadata.len == content.len() as u64 => {
+                Ok(self.fs.read(&validated_path)? == content)
             }
+            _ => Ok(false),
         }
+    }
 
-        // Open with explicit options to prevent TOCTOU
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&validated_path)
-            .map_err(|e| {
-                match e.kind() {
-                    std::io::ErrorKind::PermissionDenied => {
-                        FileJackError::PermissionDenied(validated_path.display().to_string())
-                    }
-                    std::io::ErrorKind::NotFound => {
-                        FileJackError::FileNotFound(
-                            format!("Parent directory does not exist: {}", validated_path.display())
-                        )
-                    }
-                    _ => FileJackError::Io(e),
-                }
-            })?;
-        
-        // Verify we opened a regular file, not a symlink or special file
-        let metadata = file.metadata()?;
+    /// Fail with [`FileJackError::Conflict`] unless `path` still matches
+    /// `if_match`: either the [`content_hash`] of its contents or its
+    /// modification time in seconds, as reported by `get_metadata`
+    pub fn check_if_match<P: AsRef<Path>>(&self, path: P, if_match: &str) -> Result<()> {
+        let validated_path = self.validate_path(path.as_ref())?;
+        let metadata = self.fs.metadata(&validated_path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FileJackError::FileNotFound(validated_path.display().to_string()),
+            _ => FileJackError::Io(e),
+        })?;
         if !metadata.is_file() {
-            return Err(FileJackError::InvalidPath(
-                "Cannot write to non-regular file".to_string()
-            ));
+            return Err(FileJackError::InvalidPath("Path is not a regular file".to_string()));
         }
-        
-        // Write using the file descriptor
-        file.write_all(content.as_bytes())?;
-        file.sync_all()?; // Ensure data is written to disk
-        Ok(())
+        let modified = metadata.modified
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+        let if_match = if_match.trim();
+        if if_match.parse::<u64>().is_ok_and(|seconds| modified == Some(seconds)) {
+            return Ok(());
+        }
+        let hash = content_hash(&self.fs.read(&validated_path)?);
+        if hash.eq_ignore_ascii_case(if_match) {
+            return Ok(());
+        }
+        Err(FileJackError::Conflict { path: validated_path.display().to_string(), hash, modified })
+    }
+
+    /// Lock `path` for an update of kind `operation` that reads it and
+    /// writes it back, until the returned lock is dropped. Writes through
+    /// this writer take the lock themselves, so holding it across the read
+    /// is what keeps another update from slipping in between.
+    pub fn lock_for_update<P: AsRef<Path>>(&self, path: P, operation: WriteOperation) -> Result<UpdateLock> {
+        let validated_path = self.validate_operation(path.as_ref(), operation)?;
+        UpdateLock::acquire(self.fs.as_ref(), &validated_path)
+    }
+
+    /// Write string content to a file atomically
+    pub fn write_string<P: AsRef<Path>>(&self, path: P, content: &str) -> Result<()> {
+        self.write_bytes(path, content.as_bytes())
     }
 
     /// Write bytes to a file atomically
+    ///
+    /// On the host file system the content is written to a temporary file in
+    /// the same directory and renamed over the target, so readers never
+    /// observe a partially written file.
     pub fn write_bytes<P: AsRef<Path>>(&self, path: P, content: &[u8]) -> Result<()> {
-        let validated_path = self.validate_path(path.as_ref())?;
+        let validated_path = self.prepare_write(path.as_ref(), content.len())?;
+        let _lock = UpdateLock::acquire(self.fs.as_ref(), &validated_path)?;
+        self.invalidate(&validated_path);
+        self.create_or_write(&validated_path, || self.fs.write(&validated_path, content, self.fsync))
+            .map_err(|e| write_error(e, &validated_path))?;
+        self.verify(&validated_path, |written| written == content)
+    }
+
+    /// Write bytes directly into the target file, truncating it first.
+    ///
+    /// Unlike [`FileWriter::write_bytes`] this is not crash-safe, but it works on
+    /// filesystems that do not support rename and preserves the file's inode.
+    pub fn write_bytes_in_place<P: AsRef<Path>>(&self, path: P, content: &[u8]) -> Result<()> {
+        let validated_path = self.prepare_write(path.as_ref(), content.len())?;
+        let _lock = UpdateLock::acquire(self.fs.as_ref(), &validated_path)?;
+        self.invalidate(&validated_path);
+        self.create_or_write(&validated_path, || self.fs.write_in_place(&validated_path, content, self.fsync))
+            .map_err(|e| write_error(e, &validated_path))?;
+        self.verify(&validated_path, |written| written == content)
+    }
+
+    /// Run `write`, which creates `path` if it is missing. A new file gets
+    /// the policy's `file_mode` before it holds anything: it starts out
+    /// empty, and writes keep the permissions of the file they fill.
+    fn create_or_write<T>(&self, path: &Path, write: impl FnOnce() -> std::io::Result<T>) -> std::io::Result<T> {
+        if let Some(mode) = self.policy.file_mode {
+            if self.fs.symlink_metadata(path).is_err() {
+                self.fs.append(path, b"")?;
+                self.fs.set_mode(path, mode)?;
+            }
+        }
+        write()
+    }
+
+    /// Create a directory as [`FileSystem::create_dir`] does, giving each
+    /// directory it creates the policy's `directory_mode`
+    fn make_dir(&self, path: &Path, recursive: bool) -> std::io::Result<()> {
+        let Some(mode) = self.policy.directory_mode else {
+            return self.fs.create_dir(path, recursive);
+        };
+        let missing: Vec<PathBuf> = path.ancestors()
+            .take(if recursive { usize::MAX } else { 1 })
+            .take_while(|dir| !dir.as_os_str().is_empty() && !self.fs.exists(dir))
+            .map(Path::to_path_buf)
+            .collect();
+        self.fs.create_dir(path, recursive)?;
+        for dir in missing.iter().rev() {
+            self.fs.set_mode(dir, mode)?;
+        }
+        Ok(())
+    }
+
+    /// Give a file copied to `to` the policy's `file_mode`, unless it was
+    /// already there
+    fn set_copy_mode(&self, to: &Path, existed: bool) -> std::io::Result<()> {
+        match self.policy.file_mode {
+            Some(mode) if !existed => self.fs.set_mode(to, mode),
+            _ => Ok(()),
+        }
+    }
+
+    /// Validate a write target and create parent directories if configured
+    fn prepare_write(&self, path: &Path, len: usize) -> Result<PathBuf> {
+        let validated_path = self.validate_path(path)?;
 
         // Check file size before writing
-        self.policy.validate_file_size(content.len() as u64)?;
+        self.policy.validate_file_size(len as u64)?;
 
         if self.create_dirs {
-            if let Some(parent) = validated_path.parent() {
-                fs::create_dir_all(parent)?;
+            if let Some(parent) = validated_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                self.make_dir(parent, true)?;
             }
         }
 
-        // Open with explicit options to prevent TOCTOU
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&validated_path)
-            .map_err(|e| {
-                match e.kind() {
-                    std::io::ErrorKind::PermissionDenied => {
-                        FileJackError::PermissionDenied(validated_path.display().to_string())
-                    }
-                    std::io::ErrorKind::NotFound => {
-                        FileJackError::FileNotFound(
-                            format!("Parent directory does not exist: {}", validated_path.display())
-                        )
-                    }
-                    _ => FileJackError::Io(e),
-                }
-            })?;
-        
-        // Verify we opened a regular file
-        let metadata = file.metadata()?;
-        if !metadata.is_file() {
-            return Err(FileJackError::InvalidPath(
-                "Cannot write to non-regular file".to_string()
-            ));
+        // Refuse to replace directories, symlinks, or special files
+        if let Ok(metadata) = self.fs.symlink_metadata(&validated_path) {
+            if !metadata.is_file() {
+                return Err(FileJackError::InvalidPath(
+                    "Cannot write to non-regular file".to_string()
+                ));
+            }
         }
-        
-        // Write using the file descriptor
-        file.write_all(content)?;
-        file.sync_all()?; // Ensure data is written to disk
-        Ok(())
+
+        Ok(validated_path)
+    }
+
+    /// Write bytes at `offset` without truncating the rest of the file.
+    ///
+    /// The file is created if missing; writing past the end leaves a hole that
+    /// reads back as zeros. Returns the resulting file size.
+    pub fn write_range<P: AsRef<Path>>(&self, path: P, offset: u64, content: &[u8]) -> Result<u64> {
+        let end = offset.checked_add(content.len() as u64)
+            .ok_or_else(|| FileJackError::InvalidParameters("Offset out of range".to_string()))?;
+        let validated_path = self.prepare_write(path.as_ref(), 0)?;
+        let _lock = UpdateLock::acquire(self.fs.as_ref(), &validated_path)?;
+        self.invalidate(&validated_path);
+
+        let current = self.fs.metadata(&validated_path).map(|m| m.len).unwrap_or(0);
+        self.policy.validate_file_size(end.max(current))?;
+
+        let size = self.create_or_write(&validated_path, || self.fs.write_at(&validated_path, offset, content, self.fsync))
+            .map_err(|e| write_error(e, &validated_path))?;
+        let range = offset as usize..end as usize;
+        self.verify(&validated_path, |written| written.get(range) == Some(content))?;
+        Ok(size)
     }
 
     /// Append string content to a file
     pub fn append_string<P: AsRef<Path>>(&self, path: P, content: &str) -> Result<()> {
-        let validated_path = self.validate_path(path.as_ref())?;
+        let validated_path = self.validate_operation(path.as_ref(), WriteOperation::Append)?;
+        let _lock = UpdateLock::acquire(self.fs.as_ref(), &validated_path)?;
+        self.invalidate(&validated_path);
+        let before = self.fs.metadata(&validated_path).map(|m| m.len as usize).unwrap_or(0);
+        // The limit applies to the file the append leaves behind
+        self.policy.validate_file_size((before + content.len()) as u64)?;
+        self.create_or_write(&validated_path, || self.fs.append(&validated_path, content.as_bytes()))?;
+        // Another client may have appended after us, but not before
+        self.verify(&validated_path, |written| {
+            written.get(before..).is_some_and(|added| added.starts_with(content.as_bytes()))
+        })
+    }
 
-        use std::io::Write;
-        let mut file = fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&validated_path)?;
-        
-        file.write_all(content.as_bytes())?;
-        Ok(())
+    /// With the policy's `verify_writes`, read `path` back and fail unless
+    /// `check` accepts what it holds
+    fn verify(&self, path: &Path, check: impl FnOnce(&[u8]) -> bool) -> Result<()> {
+        if !self.policy.verify_writes {
+            return Ok(());
+        }
+        if check(&self.fs.read(path)?) {
+            Ok(())
+        } else {
+            Err(FileJackError::Io(std::io::Error::other(format!(
+                "Write verification failed: {} does not hold what was written",
+                path.display()
+            ))))
+        }
+    }
+
+    /// [`content_hash`] of `path` as it is now, when the policy sets
+    /// `verify_writes`, to report alongside a verified write
+    pub fn verified_hash<P: AsRef<Path>>(&self, path: P) -> Result<Option<String>> {
+        if !self.policy.verify_writes {
+            return Ok(None);
+        }
+        let validated_path = self.validate_operation(path.as_ref(), WriteOperation::Append)?;
+        Ok(Some(content_hash(&self.fs.read(&validated_path)?)))
     }
 
     /// Delete a file
     pub fn delete_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        let validated_path = self.validate_path(path.as_ref())?;
-        
-        if !validated_path.is_file() {
+        let validated_path = self.validate_operation(path.as_ref(), WriteOperation::Delete)?;
+        self.invalidate(&validated_path);
+
+        if !self.fs.metadata(&validated_path).is_ok_and(|m| m.is_file()) {
             return Err(FileJackError::InvalidPath(
                 "Path is not a file or does not exist".to_string()
             ));
         }
-        
-        fs::remove_file(&validated_path)?;
+
+        self.fs.remove_file(&validated_path)?;
         Ok(())
     }
 
     /// Move/rename a file
     pub fn move_file<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> Result<()> {
-        let validated_from = self.validate_path(from.as_ref())?;
+        let validated_from = self.validate_operation(from.as_ref(), WriteOperation::Delete)?;
         let validated_to = self.validate_path(to.as_ref())?;
-        
-        if !validated_from.exists() {
+        self.invalidate(&validated_from);
+        self.invalidate(&validated_to);
+
+        let Ok(metadata) = self.fs.symlink_metadata(&validated_from) else {
             return Err(FileJackError::FileNotFound(
                 validated_from.display().to_string()
             ));
+        };
+
+        // A directory takes everything in it along, so each entry must be
+        // one the policy lets us move
+        let entries = match metadata.is_dir() {
+            true => self.tree_entries(&validated_from, &validated_to, true)?,
+            false => Vec::new(),
+        };
+        for entry in &entries {
+            self.invalidate(&entry.from);
+            self.invalidate(&entry.to);
+        }
+
+        match self.fs.rename(&validated_from, &validated_to) {
+            // Renames cannot cross file systems; copy, then delete the source
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+                if metadata.is_dir() {
+                    if self.fs.exists(&validated_to) {
+                        return Err(FileJackError::InvalidPath("Destination already exists".to_string()));
+                    }
+                    // The files are copied, so they are held to the size limit
+                    for entry in entries.iter().filter(|entry| entry.kind == FileKind::File) {
+                        self.policy.validate_file_size(self.fs.metadata(&entry.from)?.len)?;
+                    }
+                    self.copy_entries(&validated_from, &validated_to, &entries, true, &mut |_, _| {})?;
+                    self.fs.remove_dir(&validated_from, true)?;
+                } else {
+                    self.policy.validate_file_size(metadata.len)?;
+                    self.fs.copy(&validated_from, &validated_to)?;
+                    self.fs.copy_attributes(&validated_from, &validated_to)?;
+                    self.fs.remove_file(&validated_from)?;
+                }
+                Ok(())
+            }
+            result => Ok(result?),
         }
-        
-        fs::rename(&validated_from, &validated_to)?;
-        Ok(())
     }
 
     /// Copy a file
     pub fn copy_file<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> Result<u64> {
-        let validated_from = self.validate_path(from.as_ref())?;
+        let validated_from = self.validate_source(from.as_ref())?;
         let validated_to = self.validate_path(to.as_ref())?;
-        
-        if !validated_from.is_file() {
+        self.invalidate(&validated_to);
+
+        match self.fs.metadata(&validated_from) {
+            Ok(metadata) if metadata.is_file() => self.policy.validate_file_size(metadata.len)?,
+            Ok(metadata) if metadata.is_dir() => {
+                return Err(FileJackError::InvalidPath(
+                    "Source path is a directory; copy it with recursive".to_string()
+                ));
+            }
+            _ => {
+                return Err(FileJackError::InvalidPath(
+                    "Source path is not a file".to_string()
+                ));
+            }
+        }
+
+        let canonical = |path: &Path| self.fs.canonicalize(path).ok();
+        if canonical(&validated_to).is_some_and(|to| canonical(&validated_from) == Some(to)) {
             return Err(FileJackError::InvalidPath(
-                "Source path is not a file".to_string()
+                "Source and destination are the same file".to_string()
             ));
         }
-        
-        let bytes_copied = fs::copy(&validated_from, &validated_to)?;
+
+        let existed = self.fs.symlink_metadata(&validated_to).is_ok();
+        let bytes_copied = self.fs.copy(&validated_from, &validated_to)?;
+        self.set_copy_mode(&validated_to, existed)?;
         Ok(bytes_copied)
     }
 
+    /// Copy the directory `from` and everything beneath it to `to`, which
+    /// must not exist yet.
+    ///
+    /// Every entry, and the path it is copied to, is checked against the
+    /// policy before anything is written. `progress` is called after each
+    /// file with the summary so far and the number of files in the tree.
+    /// With `preserve`, copies get the permissions and modification times of
+    /// their sources.
+    pub fn copy_tree<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        from: P,
+        to: Q,
+        preserve: bool,
+        progress: &mut dyn FnMut(&CopySummary, u64),
+    ) -> Result<CopySummary> {
+        let validated_from = self.validate_source(from.as_ref())?;
+        let validated_to = self.validate_path(to.as_ref())?;
+
+        if !self.fs.metadata(&validated_from).is_ok_and(|m| m.is_dir()) {
+            return Err(FileJackError::InvalidPath(
+                "Source path is not a directory".to_string()
+            ));
+        }
+        if self.fs.exists(&validated_to) {
+            return Err(FileJackError::InvalidPath(
+                "Destination already exists".to_string()
+            ));
+        }
+        let parent = validated_to.parent().and_then(|parent| self.fs.canonicalize(parent).ok());
+        let target = parent.zip(validated_to.file_name()).map(|(parent, name)| parent.join(name));
+        if target.is_some_and(|target| self.fs.canonicalize(&validated_from).is_ok_and(|from| target.starts_with(from))) {
+            return Err(FileJackError::InvalidPath(
+                "Cannot copy a directory into itself".to_string()
+            ));
+        }
+
+        let entries = self.tree_entries(&validated_from, &validated_to, false)?;
+        self.copy_entries(&validated_from, &validated_to, &entries, preserve, progress)
+    }
+
+    /// The entries beneath `from` paired with where they go beneath `to`.
+    /// Fails if the policy forbids any of them, or the tree holds anything
+    /// but files and directories. Sources are checked for deletion when
+    /// `moving`, and otherwise for reading and against `max_file_size`,
+    /// since each file will be copied.
+    fn tree_entries(&self, from: &Path, to: &Path, moving: bool) -> Result<Vec<TreeEntry>> {
+        let entries = self.fs.walk(from, &WalkOptions::default())?;
+        entries.into_iter()
+            .map(|entry| {
+                if !matches!(entry.kind, FileKind::File | FileKind::Dir) {
+                    return Err(FileJackError::InvalidPath(format!(
+                        "{} is not a regular file or directory",
+                        entry.path.display()
+                    )));
+                }
+                match moving {
+                    true => self.validate_operation(&entry.path, WriteOperation::Delete)?,
+                    false => self.validate_source(&entry.path)?,
+                };
+                if !moving && entry.kind == FileKind::File {
+                    self.policy.validate_file_size(self.fs.metadata(&entry.path)?.len)?;
+                }
+                let relative = entry.path.strip_prefix(from).map_err(|_| {
+                    FileJackError::InvalidPath(format!("{} is outside {}", entry.path.display(), from.display()))
+                })?;
+                let target = to.join(relative);
+                self.validate_path(&target)?;
+                Ok(TreeEntry { from: entry.path, to: target, kind: entry.kind })
+            })
+            .collect()
+    }
+
+    /// Create `to` and copy `entries` into it, parents first
+    fn copy_entries(
+        &self,
+        from: &Path,
+        to: &Path,
+        entries: &[TreeEntry],
+        preserve: bool,
+        progress: &mut dyn FnMut(&CopySummary, u64),
+    ) -> Result<CopySummary> {
+        let total = entries.iter().filter(|entry| entry.kind == FileKind::File).count() as u64;
+        let mut summary = CopySummary::default();
+        self.make_dir(to, false)?;
+        for entry in entries {
+            self.invalidate(&entry.to);
+            if entry.kind == FileKind::Dir {
+                self.make_dir(&entry.to, false)?;
+                summary.directories += 1;
+                continue;
+            }
+            summary.bytes += self.fs.copy(&entry.from, &entry.to)?;
+            summary.files += 1;
+            if preserve {
+                self.fs.copy_attributes(&entry.from, &entry.to)?;
+            } else {
+                self.set_copy_mode(&entry.to, false)?;
+            }
+            progress(&summary, total);
+        }
+
+        // Filling a directory changes its modification time, so directories
+        // are done last, deepest first
+        if preserve {
+            for entry in entries.iter().rev().filter(|entry| entry.kind == FileKind::Dir) {
+                self.fs.copy_attributes(&entry.from, &entry.to)?;
+            }
+            self.fs.copy_attributes(from, to)?;
+        }
+        Ok(summary)
+    }
+
     /// Create a directory
     pub fn create_directory<P: AsRef<Path>>(&self, path: P, recursive: bool) -> Result<()> {
         let validated_path = self.validate_path(path.as_ref())?;
-        
-        if validated_path.exists() {
+
+        if self.fs.exists(&validated_path) {
             return Err(FileJackError::InvalidPath(
                 "Directory already exists".to_string()
             ));
         }
-        
-        if recursive {
-            fs::create_dir_all(&validated_path)?;
-        } else {
-            fs::create_dir(&validated_path)?;
+
+        if let Some(topmost) = validated_path.ancestors().take_while(|p| !self.fs.exists(p)).last() {
+            self.invalidate(topmost);
         }
-        
+        self.make_dir(&validated_path, recursive)?;
         Ok(())
     }
 
     /// Remove a directory
     pub fn remove_directory<P: AsRef<Path>>(&self, path: P, recursive: bool) -> Result<()> {
-        let validated_path = self.validate_path(path.as_ref())?;
-        
-        if !validated_path.is_dir() {
+        let validated_path = self.validate_operation(path.as_ref(), WriteOperation::Delete)?;
+        self.invalidate(&validated_path);
+
+        if !self.fs.metadata(&validated_path).is_ok_and(|m| m.is_dir()) {
             return Err(FileJackError::InvalidPath(
                 "Path is not a directory or does not exist".to_string()
             ));
         }
-        
-        if recursive {
-            fs::remove_dir_all(&validated_path)?;
-        } else {
-            // Only remove if empty
-            fs::remove_dir(&validated_path)?;
+
+        // Removing the tree would also remove the restrictions it carries
+        if recursive && self.policy().directory_overlays && self.contains_overlay(&validated_path) {
+            return Err(self.policy.deny(
+                "directory_overlays".to_string(),
+                RuleCategory::Overlay,
+                format!("{} contains a {} policy overlay and cannot be removed", validated_path.display(), OVERLAY_FILE_NAME),
+            ));
         }
-        
+        // Nor may it take protected or append-only files with it
+        if recursive && self.policy().keeps_files() {
+            let root = self.fs.canonicalize(&validated_path)?;
+            for entry in self.fs.walk(&root, &WalkOptions::default())? {
+                self.policy.check_removable_on(self.fs.as_ref(), &entry.path)?;
+            }
+        }
+        // Without `recursive` only an empty directory is removed
+        self.fs.remove_dir(&validated_path, recursive)?;
         Ok(())
     }
+
+    /// Whether any directory in the tree at `dir` holds a policy overlay
+    fn contains_overlay(&self, dir: &Path) -> bool {
+        self.fs.walk(dir, &WalkOptions::default())
+            .map(|entries| entries.iter().any(|entry| entry.path.file_name() == Some(OVERLAY_FILE_NAME.as_ref())))
+            .unwrap_or(true)
+    }
+}
+
+/// Map an error from writing a file to a FileJackError
+fn write_error(e: std::io::Error, path: &Path) -> FileJackError {
+    match e.kind() {
+        std::io::ErrorKind::PermissionDenied => {
+            FileJackError::PermissionDenied(path.display().to_string().into())
+        }
+        std::io::ErrorKind::NotFound => {
+            FileJackError::FileNotFound(
+                format!("Parent directory does not exist: {}", path.display())
+            )
+        }
+        std::io::ErrorKind::InvalidInput => FileJackError::InvalidPath(e.to_string()),
+        _ => FileJackError::Io(e),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::access_control::AccessPolicy;
+    use std::fs;
     use tempfile::TempDir;
 
     #[test]
@@ -623,6 +1697,294 @@ mod tests {
         assert_eq!(content, data);
     }
 
+    #[test]
+    fn test_file_reader_read_chunked() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("chunks.bin");
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&file_path, &data).unwrap();
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let reader = FileReader::new(policy);
+        let mut collected = Vec::new();
+        let mut largest_chunk = 0;
+        let total = reader.read_chunked(&file_path, 1024, |chunk| {
+            largest_chunk = largest_chunk.max(chunk.len());
+            collected.extend_from_slice(chunk);
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(total, data.len() as u64);
+        assert!(largest_chunk <= 1024);
+        assert_eq!(collected, data);
+    }
+
+    #[test]
+    fn test_file_reader_read_page() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("pages.bin");
+        let data: Vec<u8> = (0..2_500u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&file_path, &data).unwrap();
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let reader = FileReader::new(policy).with_read_limit(1024);
+        let mut collected = Vec::new();
+        let mut offset = Some(0);
+        while let Some(start) = offset {
+            let (page, next) = reader.read_page(&file_path, start).unwrap();
+            assert!(page.len() <= 1024);
+            collected.extend_from_slice(&page);
+            offset = next;
+        }
+        assert_eq!(collected, data);
+        assert_eq!(reader.read_range(&file_path, 0, 4096).unwrap().0.len(), 1024);
+
+        let reader = reader.with_read_limit(0);
+        assert_eq!(reader.read_page(&file_path, 0).unwrap(), (data, None));
+    }
+
+    #[test]
+    fn test_file_reader_read_to_base64() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.bin");
+        fs::write(&file_path, b"Hello, World!").unwrap();
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let reader = FileReader::new(policy);
+        let encoded = reader.read_to_base64(&file_path).unwrap();
+        assert_eq!(encoded, "SGVsbG8sIFdvcmxkIQ==");
+    }
+
+    #[test]
+    fn test_file_reader_mmap_read_lines_and_grep() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("large.log");
+        let content: String = (1..=200).map(|i| format!("line {}\n", i)).collect();
+        fs::write(&file_path, &content).unwrap();
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        // Threshold of 1 byte forces the memory-mapped path
+        let mapped = FileReader::new(policy.clone()).with_mmap_threshold(1);
+        let buffered = FileReader::new(policy).with_mmap_threshold(0);
+
+        for reader in [&mapped, &buffered] {
+            let tail = reader.read_lines(&file_path, None, None, Some(2)).unwrap();
+            assert_eq!(tail, vec!["line 199", "line 200"]);
+
+            let range = reader.read_lines(&file_path, Some(10), Some(11), None).unwrap();
+            assert_eq!(range, vec!["line 10", "line 11"]);
+
+            let matches = reader.grep_file(&file_path, r"^line 15\d$", None, Some(1)).unwrap();
+            assert_eq!(matches.len(), 10);
+            assert_eq!(matches[0].line_number, 150);
+            assert_eq!(matches[0].context_before, vec!["line 149"]);
+        }
+    }
+
+    #[test]
+    fn test_file_reader_search_files_parallel() {
+        let temp_dir = TempDir::new().unwrap();
+        for dir in ["a", "b", "c"] {
+            fs::create_dir(temp_dir.path().join(dir)).unwrap();
+            fs::write(temp_dir.path().join(dir).join("match.rs"), "").unwrap();
+            fs::write(temp_dir.path().join(dir).join("other.txt"), "").unwrap();
+        }
+
+        let mut policy = AccessPolicy::permissive();
+        policy.allowed_paths = vec![temp_dir.path().to_path_buf()];
+        let reader = FileReader::new(policy).with_search_threads(2);
+        let results = reader.search_files(temp_dir.path(), "*.rs", true, None).unwrap();
+
+        assert_eq!(results.len(), 3);
+        let mut sorted = results.clone();
+        sorted.sort();
+        assert_eq!(results, sorted);
+
+        let limited = reader.search_files(temp_dir.path(), "*.rs", true, Some(2)).unwrap();
+        assert_eq!(limited.len(), 2);
+    }
+
+    #[test]
+    fn test_file_reader_grep_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src").join("lib.rs"), "fn main() {}\n// TODO: fix\n").unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "TODO: write docs\ndone\n").unwrap();
+        fs::write(temp_dir.path().join("data.bin"), [0xffu8, 0xfe, 0x00]).unwrap();
+
+        let mut policy = AccessPolicy::permissive();
+        policy.allowed_paths = vec![temp_dir.path().to_path_buf()];
+        let reader = FileReader::new(policy);
+        let matches = reader.grep_file(temp_dir.path(), "TODO", None, None).unwrap();
+
+        assert_eq!(matches.len(), 2);
+        assert!(matches.iter().all(|m| m.file.is_some()));
+        assert!(matches.iter().any(|m| m.file.as_deref().unwrap().ends_with("notes.txt")));
+
+        let limited = reader.grep_file(temp_dir.path(), "TODO", Some(1), None).unwrap();
+        assert_eq!(limited.len(), 1);
+    }
+
+    #[test]
+    fn test_grep_directory_keeps_the_first_matches_on_a_shared_pool() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("logs");
+        fs::create_dir(&dir).unwrap();
+        for i in 0..50 {
+            fs::write(dir.join(format!("{:02}.log", i)), "error\nerror\n").unwrap();
+        }
+
+        let reader = FileReader::new(AccessPolicy::restricted(temp_dir.path().to_path_buf())).with_search_threads(2);
+        let clone = reader.clone();
+        let pool = |reader: &FileReader| reader.walk_options(1).pool.unwrap();
+        assert!(Arc::ptr_eq(&pool(&reader), &pool(&clone)));
+
+        let matches = clone.grep_file(&dir, "error", Some(5), None).unwrap();
+        let files: Vec<_> = matches.iter().map(|m| Path::new(m.file.as_deref().unwrap()).file_name().unwrap().to_owned()).collect();
+        assert_eq!(files, ["00.log", "00.log", "01.log", "01.log", "02.log"]);
+
+        // A path outside the policy is refused before it is looked at
+        assert!(matches!(reader.grep_file("/etc", "root", None, None), Err(FileJackError::PermissionDenied(_))));
+    }
+
+    #[test]
+    fn test_content_cache_shared_with_writer() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("hot.txt");
+
+        let cache = Arc::new(ContentCache::new(1024));
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let reader = FileReader::new(policy.clone()).with_content_cache(Arc::clone(&cache));
+        let writer = FileWriter::new(policy, false).with_content_cache(Arc::clone(&cache));
+
+        writer.write_string(&file_path, "first").unwrap();
+        assert_eq!(reader.read_to_string(&file_path).unwrap(), "first");
+        assert_eq!(cache.used_bytes(), 5);
+
+        // Same size rewrite is still picked up because the writer invalidates
+        writer.write_string(&file_path, "other").unwrap();
+        assert_eq!(cache.used_bytes(), 0);
+        assert_eq!(reader.read_to_string(&file_path).unwrap(), "other");
+
+        writer.delete_file(&file_path).unwrap();
+        assert_eq!(cache.used_bytes(), 0);
+        assert!(reader.read_to_string(&file_path).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_non_utf8_file_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = &temp_dir.path().join("files");
+        fs::create_dir(dir).unwrap();
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9.txt");
+        fs::write(dir.join(name), "latin-1").unwrap();
+        let reader = FileReader::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()));
+
+        let entries = reader.list_directory(dir, false).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "caf%E9.txt");
+        assert_eq!(entries[0].display_name.as_deref(), Some("caf\u{FFFD}.txt"));
+        assert_eq!(BASE64.decode(entries[0].raw_name.as_ref().unwrap()).unwrap(), b"caf\xe9.txt");
+
+        let found = reader.search_files(dir, "*.txt", true, None).unwrap();
+        assert_eq!(found, [entries[0].path.clone()]);
+        assert_eq!(reader.read_to_string(&entries[0].path).unwrap(), "latin-1");
+        let writer = FileWriter::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()), false);
+        writer.write_string(&entries[0].path, "rewritten").unwrap();
+        assert_eq!(fs::read(dir.join(name)).unwrap(), b"rewritten");
+    }
+
+    #[test]
+    fn test_list_directory_options() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = &temp_dir.path().join("project");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("b.rs"), "fn b() {}").unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("src/c.rs"), "c").unwrap();
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        fs::File::options().write(true).open(root.join("b.rs")).unwrap().set_modified(old).unwrap();
+        let reader = FileReader::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()));
+        let names = |options: ListOptions| -> Vec<String> {
+            reader.list_directory_with(root, &options).unwrap().into_iter().map(|e| e.name).collect()
+        };
+
+        assert_eq!(names(ListOptions::default()), ["a.txt", "b.rs", "src"]);
+        assert_eq!(names(ListOptions { recursive: true, pattern: Some("*.rs".to_string()), ..Default::default() }), ["b.rs", "c.rs"]);
+        assert_eq!(names(ListOptions { recursive: true, pattern: Some("src/*".to_string()), ..Default::default() }), ["c.rs"]);
+        assert_eq!(names(ListOptions { entry_type: Some(EntryType::Dir), ..Default::default() }), ["src"]);
+        let by_size = ListOptions { entry_type: Some(EntryType::File), sort_by: SortBy::Size, order: SortOrder::Desc, ..Default::default() };
+        assert_eq!(names(by_size), ["b.rs", "a.txt"]);
+        assert_eq!(names(ListOptions { entry_type: Some(EntryType::File), sort_by: SortBy::Mtime, ..Default::default() }), ["b.rs", "a.txt"]);
+
+        // Pages follow the sorted order
+        assert_eq!(names(ListOptions { recursive: true, sort_by: SortBy::Name, limit: Some(2), ..Default::default() }), ["a.txt", "b.rs"]);
+        assert_eq!(names(ListOptions { recursive: true, sort_by: SortBy::Name, offset: 2, limit: Some(2), ..Default::default() }), ["c.rs", "src"]);
+        assert!(names(ListOptions { offset: 10, ..Default::default() }).is_empty());
+
+        let invalid = ListOptions { pattern: Some("[".to_string()), ..Default::default() };
+        assert!(matches!(reader.list_directory_with(root, &invalid), Err(FileJackError::InvalidParameters(_))));
+    }
+
+    #[test]
+    fn test_reader_and_writer_on_memory_file_system() {
+        let fs: Arc<dyn FileSystem> = Arc::new(vfs::MemoryFileSystem::new());
+        let policy = AccessPolicy::restricted(PathBuf::from("/work"));
+        fs.create_dir(Path::new("/work"), false).unwrap();
+        let reader = FileReader::new(policy.clone()).with_file_system(Arc::clone(&fs));
+        let writer = FileWriter::new(policy, true).with_file_system(Arc::clone(&fs));
+
+        writer.write_string("/work/src/main.rs", "fn main() {}\n// TODO\n").unwrap();
+        writer.append_string("/work/src/main.rs", "// done\n").unwrap();
+        assert_eq!(reader.read_lines("/work/src/main.rs", None, None, Some(2)).unwrap(), vec!["// TODO", "// done"]);
+        assert_eq!(reader.grep_file("/work", "TODO", None, None).unwrap().len(), 1);
+        assert_eq!(reader.search_files("/work", "*.rs", true, None).unwrap(), vec!["/work/src/main.rs"]);
+        assert_eq!(reader.list_directory("/work", true).unwrap().len(), 2);
+
+        assert!(matches!(writer.write_string("/etc/passwd", "x"), Err(FileJackError::PermissionDenied(_))));
+        assert!(matches!(reader.read_to_string("/work/missing.txt"), Err(FileJackError::FileNotFound(_))));
+
+        writer.copy_file("/work/src/main.rs", "/work/copy.rs").unwrap();
+        writer.remove_directory("/work/src", true).unwrap();
+        assert!(reader.exists("/work/copy.rs"));
+        assert!(!reader.exists("/work/src"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_special_files_are_refused_or_bounded() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let fifo = temp_dir.path().join("pipe");
+        let name = std::ffi::CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        // SAFETY: `name` is a valid NUL-terminated path
+        assert_eq!(unsafe { libc::mkfifo(name.as_ptr(), 0o600) }, 0);
+
+        // Refused without being opened, so there is no writer to wait for
+        let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let reader = FileReader::new(policy.clone());
+        assert!(reader.read_to_bytes(&fifo).unwrap_err().to_string().contains("allow_special_files"));
+
+        policy.allow_special_files = true;
+        let reader = FileReader::new(policy.clone());
+        assert_eq!(reader.read_to_bytes(&fifo).unwrap(), b"");
+
+        #[cfg(target_os = "linux")]
+        {
+            let mut policy = AccessPolicy::permissive();
+            policy.allow_special_files = true;
+            policy.special_file_max_bytes = 10;
+            let status = FileReader::new(policy.clone()).read_to_bytes("/proc/self/status").unwrap();
+            assert_eq!(status.len(), 10);
+            policy.allow_special_files = false;
+            assert!(FileReader::new(policy).read_to_bytes("/proc/self/status").is_err());
+        }
+    }
+
     #[test]
     fn test_file_reader_file_not_found() {
         let temp_dir = TempDir::new().unwrap();
@@ -647,6 +2009,19 @@ mod tests {
         assert!(!reader.exists(temp_dir.path().join("nonexistent.txt")));
     }
 
+    #[test]
+    fn test_file_reader_exists_hides_denied_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let allowed = temp_dir.path().join("allowed");
+        fs::create_dir(&allowed).unwrap();
+        fs::write(allowed.join("inside.txt"), "in").unwrap();
+        fs::write(temp_dir.path().join("outside.txt"), "out").unwrap();
+
+        let reader = FileReader::new(AccessPolicy::restricted(allowed.clone()));
+        assert!(reader.exists(allowed.join("inside.txt")));
+        assert!(!reader.exists(temp_dir.path().join("outside.txt")));
+    }
+
     #[test]
     fn test_file_writer_new() {
         let temp_dir = TempDir::new().unwrap();
@@ -696,6 +2071,375 @@ mod tests {
         assert_eq!(content, "Nested content");
     }
 
+    #[test]
+    fn test_file_writer_atomic_leaves_no_temp_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("atomic.txt");
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let writer = FileWriter::new(policy, false);
+        writer.write_string(&file_path, "first").unwrap();
+        writer.write_string(&file_path, "second").unwrap();
+
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "second");
+        let names: Vec<_> = fs::read_dir(temp_dir.path()).unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec![std::ffi::OsString::from("atomic.txt")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_writer_atomic_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("script.txt");
+        fs::write(&file_path, "old").unwrap();
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let writer = FileWriter::new(policy, false);
+        writer.write_string(&file_path, "new").unwrap();
+
+        let mode = fs::metadata(&file_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_writer_creation_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let writer = FileWriter::new(policy.clone(), true).with_fsync(false);
+
+        let nested = temp_dir.path().join("a/b/new.txt");
+        writer.write_string(&nested, "secret").unwrap();
+        assert_eq!(mode(&nested), 0o600);
+        assert_eq!(mode(&temp_dir.path().join("a")), 0o700);
+        assert_eq!(mode(&temp_dir.path().join("a/b")), 0o700);
+        writer.append_string(temp_dir.path().join("log.txt"), "line\n").unwrap();
+        assert_eq!(mode(&temp_dir.path().join("log.txt")), 0o600);
+
+        // Existing files keep their permissions
+        let shared = temp_dir.path().join("shared.txt");
+        fs::write(&shared, "old").unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o644)).unwrap();
+        writer.write_string(&shared, "new").unwrap();
+        assert_eq!(mode(&shared), 0o644);
+        writer.copy_file(&shared, temp_dir.path().join("copy.txt")).unwrap();
+        assert_eq!(mode(&temp_dir.path().join("copy.txt")), 0o600);
+
+        let mut inherit = policy;
+        inherit.file_mode = None;
+        inherit.directory_mode = Some(0o750);
+        let writer = FileWriter::new(inherit, false);
+        writer.create_directory(temp_dir.path().join("team"), false).unwrap();
+        assert_eq!(mode(&temp_dir.path().join("team")), 0o750);
+        writer.copy_file(&shared, temp_dir.path().join("team/copy.txt")).unwrap();
+        assert_eq!(mode(&temp_dir.path().join("team/copy.txt")), 0o644);
+    }
+
+    #[test]
+    fn test_file_writer_in_place() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("inplace.txt");
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let writer = FileWriter::new(policy, false).with_fsync(false);
+        writer.write_bytes_in_place(&file_path, b"Original").unwrap();
+        writer.write_bytes_in_place(&file_path, b"New").unwrap();
+
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "New");
+    }
+
+    #[test]
+    fn test_read_and_write_range() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("blob.bin");
+        fs::write(&file_path, b"0123456789").unwrap();
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let reader = FileReader::new(policy.clone());
+        let writer = FileWriter::new(policy, false);
+
+        let (bytes, eof) = reader.read_range(&file_path, 2, 3).unwrap();
+        assert_eq!(bytes, b"234");
+        assert!(!eof);
+
+        let (bytes, eof) = reader.read_range(&file_path, 8, 10).unwrap();
+        assert_eq!(bytes, b"89");
+        assert!(eof);
+
+        let (bytes, eof) = reader.read_range(&file_path, 50, 10).unwrap();
+        assert!(bytes.is_empty());
+        assert!(eof);
+
+        let size = writer.write_range(&file_path, 4, b"ab").unwrap();
+        assert_eq!(size, 10);
+        assert_eq!(fs::read(&file_path).unwrap(), b"0123ab6789");
+
+        let size = writer.write_range(&file_path, 12, b"z").unwrap();
+        assert_eq!(size, 13);
+        assert_eq!(fs::read(&file_path).unwrap(), b"0123ab6789\0\0z");
+    }
+
+    #[test]
+    fn test_write_range_respects_max_file_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("limited.bin");
+
+        let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        policy.max_file_size = 8;
+        let writer = FileWriter::new(policy, false);
+
+        assert!(writer.write_range(&file_path, 0, b"12345678").is_ok());
+        assert!(writer.write_range(&file_path, 6, b"abc").is_err());
+    }
+
+    #[test]
+    fn test_append_and_copy_respect_max_file_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("log.txt");
+        let large = temp_dir.path().join("large.bin");
+        fs::write(&large, [0u8; 16]).unwrap();
+
+        let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        policy.max_file_size = 8;
+        let writer = FileWriter::new(policy, false);
+
+        writer.append_string(&file_path, "12345").unwrap();
+        assert!(writer.append_string(&file_path, "6789").is_err());
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "12345");
+        assert!(writer.copy_file(&large, temp_dir.path().join("copy.bin")).is_err());
+    }
+
+    #[test]
+    fn test_copy_file_contents_and_same_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("source.bin");
+        let dest = temp_dir.path().join("dest.bin");
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 253) as u8).collect();
+        fs::write(&source, &data).unwrap();
+        fs::write(&dest, b"stale content that is longer than nothing").unwrap();
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let writer = FileWriter::new(policy, false);
+        assert_eq!(writer.copy_file(&source, &dest).unwrap(), data.len() as u64);
+        assert_eq!(fs::read(&dest).unwrap(), data);
+
+        assert!(writer.copy_file(&source, &source).is_err());
+        assert_eq!(fs::read(&source).unwrap(), data);
+    }
+
+    #[test]
+    fn test_copy_tree() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("src");
+        fs::create_dir_all(source.join("nested/deeper")).unwrap();
+        fs::write(source.join("a.txt"), "alpha").unwrap();
+        fs::write(source.join("nested/b.txt"), "beta").unwrap();
+        fs::write(source.join("nested/deeper/c.txt"), "gamma").unwrap();
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+        fs::File::options().write(true).open(source.join("a.txt")).unwrap().set_modified(old).unwrap();
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let writer = FileWriter::new(policy, false);
+        let dest = temp_dir.path().join("dest");
+        let mut reports = Vec::new();
+        let summary = writer.copy_tree(&source, &dest, true, &mut |summary, total| reports.push((summary.files, total))).unwrap();
+        assert_eq!(summary, CopySummary { files: 3, directories: 2, bytes: 14 });
+        assert_eq!(reports, vec![(1, 3), (2, 3), (3, 3)]);
+        assert_eq!(fs::read_to_string(dest.join("nested/deeper/c.txt")).unwrap(), "gamma");
+        assert_eq!(fs::metadata(dest.join("a.txt")).unwrap().modified().unwrap(), old);
+
+        assert!(writer.copy_tree(&source, &dest, false, &mut |_, _| {}).unwrap_err().to_string().contains("already exists"));
+        let inside = source.join("nested/copy");
+        assert!(writer.copy_tree(&source, &inside, false, &mut |_, _| {}).unwrap_err().to_string().contains("into itself"));
+        assert!(writer.copy_file(&source, temp_dir.path().join("flat")).unwrap_err().to_string().contains("recursive"));
+
+        // One entry the policy refuses stops the copy before anything is written
+        fs::write(source.join("nested/.secret"), "hidden").unwrap();
+        let refused = temp_dir.path().join("refused");
+        assert!(writer.copy_tree(&source, &refused, false, &mut |_, _| {}).is_err());
+        assert!(!refused.exists());
+    }
+
+    #[test]
+    fn test_copy_tree_refuses_an_oversized_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("src");
+        fs::create_dir_all(source.join("nested")).unwrap();
+        fs::write(source.join("a.txt"), "small").unwrap();
+        fs::write(source.join("nested/big.txt"), "x".repeat(2048)).unwrap();
+
+        let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        policy.max_file_size = 1024;
+        let writer = FileWriter::new(policy, false);
+        let dest = temp_dir.path().join("dest");
+        let err = writer.copy_tree(&source, &dest, false, &mut |_, _| {}).unwrap_err();
+        assert!(matches!(err, FileJackError::PermissionDenied(_)), "{}", err);
+        assert!(!dest.exists());
+    }
+
+    /// Memory storage whose renames fail as they do between file systems
+    #[derive(Debug)]
+    struct SeparateDevices(vfs::MemoryFileSystem);
+
+    impl FileSystem for SeparateDevices {
+        fn open(&self, path: &Path) -> std::io::Result<Box<dyn FileHandle>> {
+            self.0.open(path)
+        }
+
+        fn write(&self, path: &Path, contents: &[u8], sync: bool) -> std::io::Result<()> {
+            self.0.write(path, contents, sync)
+        }
+
+        fn write_at(&self, path: &Path, offset: u64, contents: &[u8], sync: bool) -> std::io::Result<u64> {
+            self.0.write_at(path, offset, contents, sync)
+        }
+
+        fn append(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+            self.0.append(path, contents)
+        }
+
+        fn metadata(&self, path: &Path) -> std::io::Result<Metadata> {
+            self.0.metadata(path)
+        }
+
+        fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> {
+            self.0.canonicalize(path)
+        }
+
+        fn list(&self, path: &Path) -> std::io::Result<Vec<vfs::DirEntry>> {
+            self.0.list(path)
+        }
+
+        fn create_dir(&self, path: &Path, recursive: bool) -> std::io::Result<()> {
+            self.0.create_dir(path, recursive)
+        }
+
+        fn remove_file(&self, path: &Path) -> std::io::Result<()> {
+            self.0.remove_file(path)
+        }
+
+        fn remove_dir(&self, path: &Path, recursive: bool) -> std::io::Result<()> {
+            self.0.remove_dir(path, recursive)
+        }
+
+        fn rename(&self, _from: &Path, _to: &Path) -> std::io::Result<()> {
+            Err(std::io::ErrorKind::CrossesDevices.into())
+        }
+    }
+
+    /// Memory storage that silently loses the last byte of every write, as a
+    /// flaky network file system might
+    #[derive(Debug)]
+    struct LosesBytes(vfs::MemoryFileSystem);
+
+    impl FileSystem for LosesBytes {
+        fn open(&self, path: &Path) -> std::io::Result<Box<dyn FileHandle>> {
+            self.0.open(path)
+        }
+
+        fn write(&self, path: &Path, contents: &[u8], sync: bool) -> std::io::Result<()> {
+            self.0.write(path, &contents[..contents.len().saturating_sub(1)], sync)
+        }
+
+        fn write_at(&self, path: &Path, offset: u64, contents: &[u8], sync: bool) -> std::io::Result<u64> {
+            self.0.write_at(path, offset, &contents[..contents.len().saturating_sub(1)], sync)
+        }
+
+        fn append(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+            self.0.append(path, &contents[..contents.len().saturating_sub(1)])
+        }
+
+        fn metadata(&self, path: &Path) -> std::io::Result<Metadata> {
+            self.0.metadata(path)
+        }
+
+        fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> {
+            self.0.canonicalize(path)
+        }
+
+        fn list(&self, path: &Path) -> std::io::Result<Vec<vfs::DirEntry>> {
+            self.0.list(path)
+        }
+
+        fn create_dir(&self, path: &Path, recursive: bool) -> std::io::Result<()> {
+            self.0.create_dir(path, recursive)
+        }
+
+        fn remove_file(&self, path: &Path) -> std::io::Result<()> {
+            self.0.remove_file(path)
+        }
+
+        fn remove_dir(&self, path: &Path, recursive: bool) -> std::io::Result<()> {
+            self.0.remove_dir(path, recursive)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+            self.0.rename(from, to)
+        }
+    }
+
+    #[test]
+    fn test_verify_writes() {
+        let mut policy = AccessPolicy::restricted(PathBuf::from("/work"));
+        let flaky: Arc<dyn FileSystem> = Arc::new(LosesBytes(vfs::MemoryFileSystem::new()));
+        flaky.create_dir(Path::new("/work"), false).unwrap();
+        // Without verification the loss goes unnoticed
+        let trusting = FileWriter::new(policy.clone(), true).with_file_system(Arc::clone(&flaky));
+        trusting.write_string("/work/a.txt", "hello").unwrap();
+        assert_eq!(trusting.verified_hash("/work/a.txt").unwrap(), None);
+
+        policy.verify_writes = true;
+        let verifying = FileWriter::new(policy.clone(), true).with_file_system(Arc::clone(&flaky));
+        for result in [
+            verifying.write_string("/work/a.txt", "hello"),
+            verifying.write_bytes_in_place("/work/a.txt", b"hello"),
+            verifying.append_string("/work/a.txt", "!!"),
+            verifying.write_range("/work/a.txt", 0, b"HE").map(|_| ()),
+        ] {
+            assert!(result.unwrap_err().to_string().contains("verification failed"));
+        }
+
+        let memory: Arc<dyn FileSystem> = Arc::new(vfs::MemoryFileSystem::new());
+        memory.create_dir(Path::new("/work"), false).unwrap();
+        let writer = FileWriter::new(policy, true).with_file_system(memory);
+        writer.write_string("/work/a.txt", "hello").unwrap();
+        writer.append_string("/work/a.txt", " world").unwrap();
+        writer.write_range("/work/a.txt", 0, b"H").unwrap();
+        assert_eq!(writer.verified_hash("/work/a.txt").unwrap(), Some(content_hash(b"Hello world")));
+    }
+
+    #[test]
+    fn test_move_across_devices_copies_then_deletes() {
+        let fs: Arc<dyn FileSystem> = Arc::new(SeparateDevices(vfs::MemoryFileSystem::new()));
+        fs.create_dir(Path::new("/work"), false).unwrap();
+        let writer = FileWriter::new(AccessPolicy::restricted(PathBuf::from("/work")), true).with_file_system(Arc::clone(&fs));
+        writer.write_string("/work/project/src/main.rs", "fn main() {}\n").unwrap();
+        writer.write_string("/work/notes.txt", "notes").unwrap();
+
+        writer.move_file("/work/project", "/work/moved").unwrap();
+        writer.move_file("/work/notes.txt", "/work/moved/notes.txt").unwrap();
+        assert_eq!(fs.read(Path::new("/work/moved/src/main.rs")).unwrap(), b"fn main() {}\n");
+        assert_eq!(fs.read(Path::new("/work/moved/notes.txt")).unwrap(), b"notes");
+        assert!(!fs.exists(Path::new("/work/project")));
+        assert!(!fs.exists(Path::new("/work/notes.txt")));
+
+        // Every entry of a directory is checked before it is moved
+        writer.write_string("/work/moved/src/lib.rs", "").unwrap();
+        let mut policy = AccessPolicy::restricted(PathBuf::from("/work"));
+        policy.denied_extensions = vec!["rs".to_string()];
+        let strict = FileWriter::new(policy, true).with_file_system(Arc::clone(&fs));
+        assert!(matches!(strict.move_file("/work/moved", "/work/elsewhere"), Err(FileJackError::PermissionDenied(_))));
+        assert!(fs.exists(Path::new("/work/moved/src/lib.rs")));
+    }
+
     #[test]
     fn test_file_writer_append_string() {
         let temp_dir = TempDir::new().unwrap();
@@ -735,6 +2479,50 @@ mod tests {
         assert!(reader.read_to_string(&allowed_file).is_ok());
     }
 
+    #[test]
+    fn test_file_writer_holds() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("holds.txt");
+        fs::write(&file_path, "content").unwrap();
+
+        let writer = FileWriter::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()), false);
+        assert!(writer.holds(&file_path, b"content").unwrap());
+        assert!(!writer.holds(&file_path, b"CONTENT").unwrap());
+        assert!(!writer.holds(&file_path, b"content!").unwrap());
+        assert!(!writer.holds(temp_dir.path().join("missing.txt"), b"").unwrap());
+        fs::create_dir(temp_dir.path().join("dir")).unwrap();
+        assert!(!writer.holds(temp_dir.path().join("dir"), b"").unwrap());
+
+        let read_only = FileWriter::new(AccessPolicy::read_only(temp_dir.path().to_path_buf()), false);
+        assert!(read_only.holds(&file_path, b"content").is_err());
+    }
+
+    #[test]
+    fn test_file_writer_check_if_match() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("guarded.txt");
+        fs::write(&file_path, "v1").unwrap();
+        let writer = FileWriter::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()), false);
+
+        let hash = content_hash(b"v1");
+        writer.check_if_match(&file_path, &hash).unwrap();
+        writer.check_if_match(&file_path, &hash.to_uppercase()).unwrap();
+        let modified = fs::metadata(&file_path).unwrap().modified().unwrap()
+            .duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        writer.check_if_match(&file_path, &modified.to_string()).unwrap();
+
+        fs::write(&file_path, "v2").unwrap();
+        match writer.check_if_match(&file_path, &hash) {
+            Err(FileJackError::Conflict { hash, .. }) => assert_eq!(hash, content_hash(b"v2")),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+        assert!(matches!(writer.check_if_match(&file_path, "0"), Err(FileJackError::Conflict { .. })));
+        assert!(matches!(
+            writer.check_if_match(temp_dir.path().join("missing.txt"), &hash),
+            Err(FileJackError::FileNotFound(_))
+        ));
+    }
+
     #[test]
     fn test_file_writer_overwrite() {
         let temp_dir = TempDir::new().unwrap();
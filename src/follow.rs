@@ -0,0 +1,206 @@
+//! Following a growing file, as `tail -f` does.
+//!
+//! `tail_follow` starts a [`Follower`] that polls a file for new content and
+//! sends each batch of complete lines to the session that asked, as an MCP
+//! `notifications/message` from the `tail_follow` logger. It keeps going
+//! until `tail_cancel`, until the session closes, or until the file can no
+//! longer be read. A file that shrinks is taken to have been truncated or
+//! rotated and is followed again from its start.
+
+use crate::error::{FileJackError, Result};
+use crate::file_ops::FileReader;
+use crate::session::Notifier;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+
+/// How often a followed file is checked by default
+pub const DEFAULT_FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Shortest and longest intervals a client may ask for
+pub const MIN_FOLLOW_INTERVAL: Duration = Duration::from_millis(50);
+pub const MAX_FOLLOW_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Most files one session may follow at once
+pub const MAX_FOLLOWERS_PER_SESSION: usize = 8;
+
+/// Most new bytes read per check; a file growing faster is caught up over
+/// the following checks
+const MAX_READ_PER_CHECK: usize = 256 * 1024;
+
+/// Source of follower IDs, unique for the life of the process
+static FOLLOWER_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+/// Result of `tail_follow`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FollowInfo {
+    /// ID to pass to `tail_cancel`, also sent with every notification
+    pub follower: u64,
+    pub path: String,
+    /// Size of the file when following started; lines written after this
+    /// point are sent
+    pub offset: u64,
+    pub interval_ms: u64,
+}
+
+/// A thread sending lines appended to a file; it stops when dropped
+pub struct Follower {
+    id: u64,
+    stop: Arc<AtomicBool>,
+}
+
+impl Follower {
+    /// Follow `path` from its current end, checking every `interval`
+    pub fn spawn(reader: FileReader, path: PathBuf, interval: Duration, notifier: Notifier) -> Result<(Self, FollowInfo)> {
+        let metadata = reader.get_metadata(&path)?;
+        if !metadata.is_file {
+            return Err(FileJackError::InvalidPath("Path to follow must be a regular file".to_string()));
+        }
+        let interval = interval.clamp(MIN_FOLLOW_INTERVAL, MAX_FOLLOW_INTERVAL);
+        let id = FOLLOWER_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let info = FollowInfo {
+            follower: id,
+            path: path.display().to_string(),
+            offset: metadata.size,
+            interval_ms: interval.as_millis() as u64,
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let mut tail = Tail { id, reader, path, offset: metadata.size, pending: Vec::new() };
+        std::thread::Builder::new()
+            .name(format!("filejack-tail-{}", id))
+            .spawn(move || {
+                while !thread_stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(interval);
+                    if thread_stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    match tail.check() {
+                        Ok(Some(message)) => notifier(message),
+                        Ok(None) => {}
+                        Err(e) => {
+                            debug!(follower = tail.id, error = %e, "Stopped following");
+                            notifier(tail.message("warning", json!({"stopped": e.to_string()})));
+                            break;
+                        }
+                    }
+                }
+            })?;
+        Ok((Self { id, stop }, info))
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl Drop for Follower {
+    fn drop(&mut self) {
+        // The thread notices within one interval; there is no need to wait
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A follower's position in its file
+struct Tail {
+    id: u64,
+    reader: FileReader,
+    path: PathBuf,
+    offset: u64,
+    /// Bytes of a line whose end has not been written yet
+    pending: Vec<u8>,
+}
+
+impl Tail {
+    /// Read what was appended since the last check; returns the notification
+    /// to send, if any complete lines arrived
+    fn check(&mut self) -> Result<Option<String>> {
+        let metadata = self.reader.get_metadata(&self.path)?;
+        if !metadata.is_file {
+            return Err(FileJackError::InvalidPath(format!("{} is no longer a regular file", self.path.display())));
+        }
+        let truncated = metadata.size < self.offset;
+        if truncated {
+            self.offset = 0;
+            self.pending.clear();
+        }
+        if metadata.size > self.offset {
+            let length = usize::try_from(metadata.size - self.offset).unwrap_or(usize::MAX).min(MAX_READ_PER_CHECK);
+            let (bytes, _) = self.reader.read_range(&self.path, self.offset, length)?;
+            self.offset += bytes.len() as u64;
+            self.pending.extend_from_slice(&bytes);
+        }
+
+        let Some(end) = self.pending.iter().rposition(|&b| b == b'\n') else {
+            return Ok(truncated.then(|| self.message("info", json!({"lines": [], "truncated": true}))));
+        };
+        let complete: Vec<u8> = self.pending.drain(..=end).collect();
+        let lines: Vec<String> = String::from_utf8_lossy(&complete)
+            .lines()
+            .map(str::to_string)
+            .collect();
+        let mut data = json!({"lines": lines});
+        if truncated {
+            data["truncated"] = json!(true);
+        }
+        Ok(Some(self.message("info", data)))
+    }
+
+    /// A `notifications/message` carrying `data` along with the follower's ID
+    /// and path
+    fn message(&self, level: &str, mut data: Value) -> String {
+        data["follower"] = json!(self.id);
+        data["path"] = json!(self.path.display().to_string());
+        json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/message",
+            "params": {"level": level, "logger": "tail_follow", "data": data}
+        })
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access_control::AccessPolicy;
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_tail_sends_complete_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = temp_dir.path().join("app.log");
+        fs::write(&log, "old\n").unwrap();
+        let reader = FileReader::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()));
+        let mut tail = Tail { id: 3, reader, path: log.clone(), offset: 4, pending: Vec::new() };
+        assert_eq!(tail.check().unwrap(), None);
+
+        let mut file = OpenOptions::new().append(true).open(&log).unwrap();
+        file.write_all(b"one\ntwo\npart").unwrap();
+        let message: Value = serde_json::from_str(&tail.check().unwrap().unwrap()).unwrap();
+        assert_eq!(message["method"], "notifications/message");
+        assert_eq!(message["params"]["data"]["lines"], json!(["one", "two"]));
+        assert_eq!(message["params"]["data"]["follower"], 3);
+
+        file.write_all(b"ial\n").unwrap();
+        let message: Value = serde_json::from_str(&tail.check().unwrap().unwrap()).unwrap();
+        assert_eq!(message["params"]["data"]["lines"], json!(["partial"]));
+
+        // A rotated file is read again from the start
+        fs::write(&log, "new\n").unwrap();
+        let message: Value = serde_json::from_str(&tail.check().unwrap().unwrap()).unwrap();
+        assert_eq!(message["params"]["data"]["lines"], json!(["new"]));
+        assert_eq!(message["params"]["data"]["truncated"], true);
+
+        fs::remove_file(&log).unwrap();
+        assert!(tail.check().is_err());
+    }
+}
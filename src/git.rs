@@ -0,0 +1,470 @@
+use crate::access_control::AccessPolicy;
+use crate::config::GitConfig;
+use crate::error::{FileJackError, Result, RuleCategory};
+use crate::vfs;
+use git2::{
+    BlameOptions, DiffOptions, ErrorCode, IndexAddOption, Repository, Signature, Sort, Status, StatusOptions,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Commits returned by `git_log` when no limit is given
+pub const DEFAULT_LOG_LIMIT: usize = 20;
+
+/// Most commits `git_log` returns, whatever the limit asked for
+pub const MAX_LOG_LIMIT: usize = 1000;
+
+/// A changed path reported by `git_status`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusEntry {
+    pub path: String,
+    /// Change staged in the index, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub staged: Option<String>,
+    /// Change in the working tree not yet staged, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unstaged: Option<String>,
+}
+
+/// A commit reported by `git_log`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitInfo {
+    pub id: String,
+    pub author: String,
+    pub email: String,
+    /// Commit time in seconds since the Unix epoch
+    pub time: i64,
+    pub summary: String,
+}
+
+/// A run of lines last changed by the same commit, from `git_blame`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlameHunk {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub commit: String,
+    pub author: String,
+    pub time: i64,
+    pub summary: String,
+}
+
+/// Result of `git_snapshot`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Snapshot {
+    /// The new commit, or `None` when there was nothing to commit
+    pub commit: Option<String>,
+    /// Files added, changed, or removed by the commit
+    pub files: Vec<String>,
+}
+
+/// Views of the git repository around a path, plus [`GitInspector::snapshot`],
+/// the one operation that writes to it. Every path is checked against the
+/// policy first, and results about other files are limited to the ones the
+/// policy would let a client read.
+pub struct GitInspector<'a> {
+    policy: &'a AccessPolicy,
+}
+
+/// A validated path and the repository containing it
+struct Located {
+    repo: Repository,
+    workdir: PathBuf,
+    /// The path relative to the working directory, `/`-separated
+    relative: String,
+}
+
+impl<'a> GitInspector<'a> {
+    pub fn new(policy: &'a AccessPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// Uncommitted changes at or beneath `path`, untracked files included
+    pub fn status(&self, path: &Path) -> Result<Vec<StatusEntry>> {
+        let located = self.locate(path)?;
+        let mut options = StatusOptions::new();
+        options.include_untracked(true).recurse_untracked_dirs(true).include_ignored(false);
+        if !located.relative.is_empty() {
+            options.pathspec(&located.relative);
+        }
+
+        let statuses = located.repo.statuses(Some(&mut options)).map_err(git_error)?;
+        Ok(statuses.iter()
+            .filter_map(|entry| {
+                let path = located.workdir.join(entry.path().ok()?);
+                self.policy.validate_read_or_missing(&path).ok()?;
+                let status = entry.status();
+                Some(StatusEntry {
+                    path: path.to_string_lossy().into_owned(),
+                    staged: staged_change(status).map(str::to_string),
+                    unstaged: unstaged_change(status).map(str::to_string),
+                })
+            })
+            .collect())
+    }
+
+    /// Commits reachable from HEAD that changed `path`, newest first
+    pub fn log(&self, path: &Path, max_count: usize) -> Result<Vec<CommitInfo>> {
+        let located = self.locate(path)?;
+        let repo = &located.repo;
+        let mut walk = repo.revwalk().map_err(git_error)?;
+        walk.set_sorting(Sort::TIME).map_err(git_error)?;
+        walk.push_head().map_err(git_error)?;
+
+        let mut commits = Vec::new();
+        for id in walk {
+            if commits.len() >= max_count.min(MAX_LOG_LIMIT) {
+                break;
+            }
+            let commit = repo.find_commit(id.map_err(git_error)?).map_err(git_error)?;
+            if !located.relative.is_empty() && !touches(repo, &commit, &located.relative)? {
+                continue;
+            }
+            commits.push(commit_info(&commit));
+        }
+        Ok(commits)
+    }
+
+    /// Contents of the file at `path` as of `revision` (a commit, branch,
+    /// tag, or expression such as `HEAD~2`)
+    pub fn show(&self, path: &Path, revision: &str) -> Result<String> {
+        let located = self.locate(path)?;
+        let repo = &located.repo;
+        let commit = repo.revparse_single(revision)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(git_error)?;
+        let entry = commit.tree().map_err(git_error)?
+            .get_path(Path::new(&located.relative))
+            .map_err(|_| FileJackError::FileNotFound(format!("{} at {}", path.display(), revision)))?;
+        let blob = entry.to_object(repo).and_then(|object| object.peel_to_blob()).map_err(|_| {
+            FileJackError::InvalidParameters(format!("{} is not a file at {}", path.display(), revision))
+        })?;
+
+        self.policy.validate_file_size(blob.size() as u64)?;
+        String::from_utf8(blob.content().to_vec()).map_err(|_| {
+            FileJackError::InvalidParameters(format!("{} is not UTF-8 text at {}", path.display(), revision))
+        })
+    }
+
+    /// The commit that last changed each line of the committed file,
+    /// grouped into runs; `start_line` and `end_line` are 1-based and
+    /// inclusive
+    pub fn blame(&self, path: &Path, start_line: Option<usize>, end_line: Option<usize>) -> Result<Vec<BlameHunk>> {
+        let located = self.locate(path)?;
+        let repo = &located.repo;
+        let mut options = BlameOptions::new();
+        if let Some(start) = start_line {
+            options.min_line(start);
+        }
+        if let Some(end) = end_line {
+            options.max_line(end);
+        }
+        let blame = repo.blame_file(Path::new(&located.relative), Some(&mut options)).map_err(git_error)?;
+
+        blame.iter()
+            .map(|hunk| {
+                let start = hunk.final_start_line();
+                let commit = repo.find_commit(hunk.final_commit_id()).map_err(git_error)?;
+                let info = commit_info(&commit);
+                Ok(BlameHunk {
+                    start_line: start,
+                    end_line: start + hunk.lines_in_hunk().saturating_sub(1),
+                    commit: info.id,
+                    author: info.author,
+                    time: info.time,
+                    summary: info.summary,
+                })
+            })
+            .collect()
+    }
+
+    /// Stage the paths in `changed` that lie in the repository holding
+    /// `path` and commit them on the current branch as `config`'s identity.
+    ///
+    /// Committed paths are removed from `changed`; paths in other
+    /// repositories are left for a later snapshot. Refuses to run when the
+    /// index already has changes staged for other paths, so a snapshot never
+    /// commits work that did not come through FileJack.
+    pub fn snapshot(
+        &self,
+        path: &Path,
+        changed: &mut BTreeSet<PathBuf>,
+        config: &GitConfig,
+        message: Option<&str>,
+    ) -> Result<Snapshot> {
+        if self.policy.read_only {
+            return Err(self.policy.deny(
+                "read_only".to_string(),
+                RuleCategory::ReadOnly,
+                "Read-only mode: git_snapshot is not allowed".to_string(),
+            ));
+        }
+        let located = self.locate(path)?;
+        let repo = &located.repo;
+
+        let mut taken = Vec::new();
+        let mut pathspecs = BTreeSet::new();
+        for candidate in changed.iter() {
+            let Ok(resolved) = self.policy.validate_read_or_missing(candidate) else { continue };
+            let Ok(relative) = resolved.strip_prefix(&located.workdir) else { continue };
+            taken.push(candidate.clone());
+            if !relative.starts_with(".git") && relative.components().next().is_some() {
+                pathspecs.insert(relative.to_string_lossy().replace('\\', "/"));
+            }
+        }
+        if pathspecs.is_empty() {
+            return Ok(Snapshot { commit: None, files: Vec::new() });
+        }
+
+        let head = match repo.head() {
+            Ok(head) => Some(head.peel_to_commit().map_err(git_error)?),
+            Err(e) if e.code() == ErrorCode::UnbornBranch => None,
+            Err(e) => return Err(git_error(e)),
+        };
+        let head_tree = head.as_ref().map(|commit| commit.tree()).transpose().map_err(git_error)?;
+        let mut index = repo.index().map_err(git_error)?;
+
+        let staged = repo.diff_tree_to_index(head_tree.as_ref(), Some(&index), None).map_err(git_error)?;
+        if let Some(other) = delta_paths(&staged).into_iter().find(|p| !covered(p, &pathspecs)) {
+            return Err(FileJackError::InvalidParameters(format!(
+                "The index already has changes staged for {}; commit or unstage them first", other
+            )));
+        }
+
+        index.add_all(&pathspecs, IndexAddOption::DEFAULT, None).map_err(git_error)?;
+        index.update_all(&pathspecs, None).map_err(git_error)?;
+        index.write().map_err(git_error)?;
+        let tree = repo.find_tree(index.write_tree().map_err(git_error)?).map_err(git_error)?;
+
+        let diff = repo.diff_tree_to_tree(head_tree.as_ref(), Some(&tree), None).map_err(git_error)?;
+        let files: Vec<String> = delta_paths(&diff).into_iter()
+            .map(|p| located.workdir.join(p).to_string_lossy().into_owned())
+            .collect();
+        for candidate in &taken {
+            changed.remove(candidate);
+        }
+        if files.is_empty() {
+            return Ok(Snapshot { commit: None, files });
+        }
+
+        let signature = Signature::now(&config.author_name, &config.author_email).map_err(git_error)?;
+        let message = match message {
+            Some(message) => format!("{}{}", config.message_prefix, message),
+            None => format!("{}Snapshot of {} file(s)", config.message_prefix, files.len()),
+        };
+        let parents: Vec<_> = head.iter().collect();
+        let id = repo.commit(Some("HEAD"), &signature, &signature, &message, &tree, &parents).map_err(git_error)?;
+        Ok(Snapshot { commit: Some(id.to_string()), files })
+    }
+
+    /// Check `path` against the policy and open the repository holding it
+    fn locate(&self, path: &Path) -> Result<Located> {
+        let validated = self.policy.validate_read_or_missing(path)?;
+        let start = validated.ancestors()
+            .find(|dir| dir.is_dir())
+            .ok_or_else(|| FileJackError::InvalidPath(format!("No directory above {}", path.display())))?;
+        let repo = Repository::discover(start).map_err(|_| {
+            FileJackError::InvalidPath(format!("{} is not inside a git repository", path.display()))
+        })?;
+        let workdir = repo.workdir()
+            .ok_or_else(|| FileJackError::InvalidPath("Bare repositories are not supported".to_string()))?;
+        let workdir = vfs::canonicalize(workdir)?;
+        let relative = validated.strip_prefix(&workdir)
+            .map_err(|_| FileJackError::InvalidPath(format!("{} is outside the repository", path.display())))?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        Ok(Located { repo, workdir, relative })
+    }
+}
+
+/// Whether `commit` changed anything at `pathspec` compared to its first
+/// parent
+fn touches(repo: &Repository, commit: &git2::Commit<'_>, pathspec: &str) -> Result<bool> {
+    let tree = commit.tree().map_err(git_error)?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree().map_err(git_error)?),
+        Err(_) => None,
+    };
+    let mut options = DiffOptions::new();
+    options.pathspec(pathspec);
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut options))
+        .map_err(git_error)?;
+    Ok(diff.deltas().len() > 0)
+}
+
+/// Paths touched by each delta of `diff`, relative to the working directory
+fn delta_paths(diff: &git2::Diff<'_>) -> Vec<String> {
+    diff.deltas()
+        .filter_map(|delta| delta.new_file().path().or_else(|| delta.old_file().path()).map(Path::to_path_buf))
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .collect()
+}
+
+/// Whether `path` is one of `pathspecs` or lies beneath one of them
+fn covered(path: &str, pathspecs: &BTreeSet<String>) -> bool {
+    pathspecs.iter().any(|spec| path == spec || path.strip_prefix(spec.as_str()).is_some_and(|rest| rest.starts_with('/')))
+}
+
+fn commit_info(commit: &git2::Commit<'_>) -> CommitInfo {
+    let author = commit.author();
+    CommitInfo {
+        id: commit.id().to_string(),
+        author: author.name().unwrap_or_default().to_string(),
+        email: author.email().unwrap_or_default().to_string(),
+        time: commit.time().seconds(),
+        summary: commit.summary().ok().flatten().unwrap_or_default().to_string(),
+    }
+}
+
+fn staged_change(status: Status) -> Option<&'static str> {
+    if status.is_index_new() {
+        Some("added")
+    } else if status.is_index_modified() {
+        Some("modified")
+    } else if status.is_index_deleted() {
+        Some("deleted")
+    } else if status.is_index_renamed() {
+        Some("renamed")
+    } else if status.is_index_typechange() {
+        Some("typechange")
+    } else {
+        None
+    }
+}
+
+fn unstaged_change(status: Status) -> Option<&'static str> {
+    if status.is_conflicted() {
+        Some("conflicted")
+    } else if status.is_wt_new() {
+        Some("untracked")
+    } else if status.is_wt_modified() {
+        Some("modified")
+    } else if status.is_wt_deleted() {
+        Some("deleted")
+    } else if status.is_wt_renamed() {
+        Some("renamed")
+    } else if status.is_wt_typechange() {
+        Some("typechange")
+    } else {
+        None
+    }
+}
+
+fn git_error(e: git2::Error) -> FileJackError {
+    match e.code() {
+        ErrorCode::NotFound | ErrorCode::UnbornBranch => FileJackError::FileNotFound(e.message().to_string()),
+        ErrorCode::InvalidSpec | ErrorCode::Ambiguous => FileJackError::InvalidParameters(e.message().to_string()),
+        _ => FileJackError::Io(std::io::Error::other(e.message().to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+    use std::fs;
+
+    /// A repository with one file committed twice, then edited
+    fn repository() -> (tempfile::TempDir, PathBuf) {
+        let temp_dir = tempfile::Builder::new().prefix("repo").tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        let repo = Repository::init(&root).unwrap();
+        let signature = Signature::now("Ada", "ada@example.com").unwrap();
+
+        let mut parent = None;
+        for (message, contents) in [("Add notes", "one\n"), ("Extend notes", "one\ntwo\n")] {
+            fs::write(root.join("notes.txt"), contents).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("notes.txt")).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents: Vec<_> = parent.iter().collect();
+            let id = repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap();
+            parent = Some(repo.find_commit(id).unwrap());
+        }
+        fs::write(root.join("notes.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(root.join("scratch.md"), "new").unwrap();
+        (temp_dir, root)
+    }
+
+    #[test]
+    fn test_status_log_show_blame() {
+        let (_temp_dir, root) = repository();
+        let policy = AccessPolicy::restricted(root.clone());
+        let git = GitInspector::new(&policy);
+        let notes = root.join("notes.txt");
+
+        let status = git.status(&root).unwrap();
+        assert_eq!(status.len(), 2, "{:?}", status);
+        assert!(status.iter().any(|e| e.path.ends_with("notes.txt") && e.unstaged.as_deref() == Some("modified")));
+        assert!(status.iter().any(|e| e.path.ends_with("scratch.md") && e.unstaged.as_deref() == Some("untracked")));
+
+        let log = git.log(&notes, 10).unwrap();
+        let summaries: Vec<_> = log.iter().map(|c| c.summary.as_str()).collect();
+        assert_eq!(summaries, ["Extend notes", "Add notes"]);
+        assert_eq!(git.log(&notes, 1).unwrap().len(), 1);
+
+        assert_eq!(git.show(&notes, "HEAD").unwrap(), "one\ntwo\n");
+        assert_eq!(git.show(&notes, "HEAD~1").unwrap(), "one\n");
+        assert!(matches!(git.show(&root.join("scratch.md"), "HEAD"), Err(FileJackError::FileNotFound(_))));
+
+        let blame = git.blame(&notes, None, None).unwrap();
+        assert_eq!(blame.len(), 2);
+        assert_eq!((blame[1].start_line, blame[1].end_line), (2, 2));
+        assert_eq!(blame[1].summary, "Extend notes");
+    }
+
+    #[test]
+    fn test_snapshot_commits_only_recorded_paths() {
+        let (_temp_dir, root) = repository();
+        let policy = AccessPolicy::restricted(root.clone());
+        let git = GitInspector::new(&policy);
+        let config = GitConfig::default();
+        let mut changed = BTreeSet::from([root.join("notes.txt")]);
+
+        let snapshot = git.snapshot(&root, &mut changed, &config, Some("Edit notes")).unwrap();
+        assert!(snapshot.commit.is_some());
+        assert_eq!(snapshot.files.len(), 1);
+        assert!(changed.is_empty());
+        let repo = Repository::open(&root).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message().ok(), Some("[filejack] Edit notes"));
+        assert_eq!(head.author().name().ok(), Some("FileJack"));
+        let status = git.status(&root).unwrap();
+        assert_eq!(status.len(), 1);
+        assert!(status[0].path.ends_with("scratch.md"));
+
+        // Nothing recorded, nothing committed
+        assert_eq!(git.snapshot(&root, &mut changed, &config, None).unwrap().commit, None);
+
+        // Someone else's staged work is never swept into a snapshot
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("scratch.md")).unwrap();
+        index.write().unwrap();
+        fs::write(root.join("notes.txt"), "rewritten\n").unwrap();
+        changed.insert(root.join("notes.txt"));
+        let result = git.snapshot(&root, &mut changed, &config, None);
+        assert!(matches!(result, Err(FileJackError::InvalidParameters(_))), "{:?}", result);
+        assert_eq!(changed.len(), 1);
+
+        let read_only = AccessPolicy::read_only(root.clone());
+        let result = GitInspector::new(&read_only).snapshot(&root, &mut changed, &config, None);
+        assert!(matches!(result, Err(FileJackError::PermissionDenied(_))));
+    }
+
+    #[test]
+    fn test_policy_applies_to_git_paths() {
+        let (_temp_dir, root) = repository();
+        let mut policy = AccessPolicy::restricted(root.clone());
+        policy.denied_paths.push(root.join("scratch.md"));
+        let git = GitInspector::new(&policy);
+
+        let status = git.status(&root).unwrap();
+        assert!(status.iter().all(|e| !e.path.ends_with("scratch.md")), "{:?}", status);
+        assert!(git.show(&root.join("scratch.md"), "HEAD").is_err());
+        assert!(git.log(Path::new("/etc/passwd"), 5).is_err());
+    }
+}
@@ -0,0 +1,634 @@
+use crate::error::{FileJackError, Result};
+use crate::mcp::McpServer;
+use crate::session::Session;
+use axum::extract::{DefaultBodyLimit, Query, State};
+use axum::http::header::{ACCEPT, CONTENT_TYPE, ORIGIN};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
+use tracing::{debug, info, warn};
+
+/// Port used by `--transport http` when `--port` is not given
+pub const DEFAULT_HTTP_PORT: u16 = 8090;
+
+/// Path of the streamable HTTP endpoint
+pub const MCP_ENDPOINT: &str = "/mcp";
+
+/// Header carrying the session ID assigned at initialization
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Path of the legacy HTTP+SSE event stream
+pub const SSE_ENDPOINT: &str = "/sse";
+
+/// Path legacy HTTP+SSE clients POST their messages to
+pub const MESSAGES_ENDPOINT: &str = "/messages";
+
+/// Server-initiated messages buffered per listening client before the
+/// oldest are dropped
+const EVENT_BUFFER: usize = 64;
+
+/// How long a session may go without a request before it is ended, as if
+/// the client had sent DELETE (30 minutes)
+pub const DEFAULT_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Most sessions kept at once; initializing another ends the one idle longest
+pub const DEFAULT_MAX_SESSIONS: usize = 1024;
+
+/// MCP streamable HTTP transport.
+///
+/// Clients POST JSON-RPC messages to [`MCP_ENDPOINT`] and receive the
+/// responses as JSON (or as a one-event SSE stream if they accept only
+/// `text/event-stream`); a GET on the same path opens an SSE stream carrying
+/// server-initiated messages such as `notifications/tools/list_changed`.
+/// `initialize` assigns a session ID that later requests echo back and
+/// DELETE ends; each ID has its own [`Session`]. A session idle for longer
+/// than the idle timeout is ended too, and past the session limit a new one
+/// ends the session idle longest, so clients that never send DELETE cannot
+/// make the server hold sessions without bound.
+///
+/// Requests without an ID share the server's default session, the one the
+/// stdio transport uses. This serves stateless clients, such as scripts
+/// that POST a single `tools/call` without initializing; they get no
+/// per-client state, and anything one of them leaves in the default
+/// session, such as an open transaction, is seen by the others.
+///
+/// The older HTTP+SSE transport is served alongside: a GET on
+/// [`SSE_ENDPOINT`] opens a stream whose first `endpoint` event names the
+/// URL to POST messages to, and responses arrive on that stream instead of
+/// in the POST response. Each stream is a session of its own.
+///
+/// Requests from browsers are only accepted from localhost origins, to
+/// prevent DNS rebinding attacks against a local server.
+#[derive(Clone)]
+pub struct HttpTransport {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    server: Arc<McpServer>,
+    sessions: Mutex<HashMap<String, HttpSession>>,
+    idle_timeout: Duration,
+    max_sessions: usize,
+    /// Open legacy SSE streams by session ID
+    legacy_sessions: Mutex<HashMap<String, LegacySession>>,
+    events: broadcast::Sender<String>,
+    /// Set when the server stops, so open event streams end and graceful
+    /// shutdown does not wait for them forever
+    closing: watch::Sender<bool>,
+}
+
+/// A session of the streamable transport
+struct HttpSession {
+    session: Arc<Session>,
+    last_used: Instant,
+}
+
+/// A client of the legacy HTTP+SSE transport
+#[derive(Clone)]
+struct LegacySession {
+    session: Arc<Session>,
+    /// Responses for the client's event stream
+    stream: mpsc::Sender<String>,
+}
+
+impl HttpTransport {
+    /// Serve `server` over HTTP
+    pub fn new(server: Arc<McpServer>) -> Self {
+        Self::with_session_limits(server, DEFAULT_SESSION_IDLE_TIMEOUT, DEFAULT_MAX_SESSIONS)
+    }
+
+    /// Serve `server` over HTTP, ending sessions idle for `idle_timeout` and
+    /// keeping at most `max_sessions` at once
+    pub fn with_session_limits(server: Arc<McpServer>, idle_timeout: Duration, max_sessions: usize) -> Self {
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            inner: Arc::new(Inner {
+                server,
+                sessions: Mutex::new(HashMap::new()),
+                idle_timeout,
+                max_sessions: max_sessions.max(1),
+                legacy_sessions: Mutex::new(HashMap::new()),
+                events,
+                closing: watch::Sender::new(false),
+            }),
+        }
+    }
+
+    /// Send a server-initiated message to every client with an open GET stream
+    pub fn notify(&self, message: String) {
+        // No listeners is not an error; the message is simply not delivered
+        let _ = self.inner.events.send(message);
+    }
+
+    /// Routes for the transport, for mounting into a larger application
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route(MCP_ENDPOINT, post(handle_post).get(handle_get).delete(handle_delete))
+            .route(SSE_ENDPOINT, get(handle_legacy_stream))
+            .route(MESSAGES_ENDPOINT, post(handle_legacy_message))
+            .layer(DefaultBodyLimit::max(self.inner.server.max_request_bytes()))
+            .with_state(self.clone())
+    }
+
+    /// Accept connections on `listener` until `shutdown` completes, then
+    /// finish the requests in flight and return
+    pub async fn serve(
+        self,
+        listener: tokio::net::TcpListener,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> Result<()> {
+        if let Ok(addr) = listener.local_addr() {
+            info!("Serving MCP over HTTP at http://{}{}", addr, MCP_ENDPOINT);
+        }
+        let router = self.router();
+        let shutdown = async move {
+            shutdown.await;
+            self.inner.closing.send_replace(true);
+        };
+        axum::serve(listener, router)
+            .with_graceful_shutdown(shutdown)
+            .await
+            .map_err(FileJackError::Io)
+    }
+
+    fn sessions(&self) -> std::sync::MutexGuard<'_, HashMap<String, HttpSession>> {
+        self.inner.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn legacy_sessions(&self) -> std::sync::MutexGuard<'_, HashMap<String, LegacySession>> {
+        self.inner.legacy_sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Open a stream for one SSE client that receives every broadcast until
+    /// the client disconnects or the server stops. Returns a sender for
+    /// messages addressed to this client only. When `legacy_session` is
+    /// given, that session is forgotten once the stream ends.
+    fn open_event_stream(&self, legacy_session: Option<String>) -> (mpsc::Sender<String>, ReceiverStream<String>) {
+        let (tx, rx) = mpsc::channel(EVENT_BUFFER);
+        let forward = tx.clone();
+        let transport = self.clone();
+        let mut events = self.inner.events.subscribe();
+        let mut closing = self.inner.closing.subscribe();
+        tokio::spawn(async move {
+            while !*closing.borrow_and_update() {
+                tokio::select! {
+                    _ = closing.changed() => break,
+                    _ = forward.closed() => break,
+                    message = events.recv() => match message {
+                        Ok(message) => {
+                            if forward.send(message).await.is_err() {
+                                break;
+                            }
+                        }
+                        Err(RecvError::Lagged(skipped)) => warn!(skipped, "Event stream client fell behind"),
+                        Err(RecvError::Closed) => break,
+                    },
+                }
+            }
+            // Dropping the last sender ends the client's stream
+            if let Some(id) = legacy_session {
+                let ended = transport.legacy_sessions().remove(&id);
+                if let Some(ended) = ended {
+                    transport.inner.server.report_usage(&ended.session);
+                }
+                debug!(session = %id, "SSE session ended");
+            }
+        });
+        (tx, ReceiverStream::new(rx))
+    }
+
+    /// The session a request belongs to, or `None` when it carries no
+    /// session ID
+    fn find_session(&self, headers: &HeaderMap) -> std::result::Result<Option<Arc<Session>>, UnknownSession> {
+        let Some(id) = headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok()) else {
+            return Ok(None);
+        };
+        self.end_idle_sessions();
+        let mut sessions = self.sessions();
+        let entry = sessions.get_mut(id).ok_or(UnknownSession)?;
+        entry.last_used = Instant::now();
+        Ok(Some(Arc::clone(&entry.session)))
+    }
+
+    /// Keep `session`, first ending idle sessions and, at the limit, the
+    /// one idle longest
+    fn add_session(&self, session: Arc<Session>) {
+        self.end_idle_sessions();
+        let mut sessions = self.sessions();
+        let evicted = if sessions.len() >= self.inner.max_sessions {
+            let oldest = sessions.iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(id, _)| id.clone());
+            oldest.and_then(|id| sessions.remove(&id))
+        } else {
+            None
+        };
+        sessions.insert(session.id().to_string(), HttpSession { session, last_used: Instant::now() });
+        drop(sessions);
+        if let Some(evicted) = evicted {
+            debug!(session = evicted.session.id(), "HTTP session ended to stay within the session limit");
+            self.inner.server.report_usage(&evicted.session);
+        }
+    }
+
+    /// End every session idle for longer than the idle timeout
+    fn end_idle_sessions(&self) {
+        let mut expired = Vec::new();
+        self.sessions().retain(|_, entry| {
+            let idle = entry.last_used.elapsed() > self.inner.idle_timeout;
+            if idle {
+                expired.push(Arc::clone(&entry.session));
+            }
+            !idle
+        });
+        for session in expired {
+            debug!(session = session.id(), "Idle HTTP session ended");
+            self.inner.server.report_usage(&session);
+        }
+    }
+}
+
+async fn handle_post(State(transport): State<HttpTransport>, headers: HeaderMap, body: String) -> Response {
+    if let Some(rejection) = reject_foreign_origin(&headers) {
+        return rejection;
+    }
+
+    let parsed: Option<Value> = serde_json::from_str(&body).ok();
+    let initializing = parsed.as_ref()
+        .is_some_and(|message| message.get("method").and_then(Value::as_str) == Some("initialize"));
+    let session = if initializing {
+        Some(transport.inner.server.open_session(uuid::Uuid::new_v4().to_string()))
+    } else {
+        match transport.find_session(&headers) {
+            Ok(session) => session,
+            Err(rejection) => return rejection.into_response(),
+        }
+    };
+
+    let output = match process(&transport, session.clone(), parsed, body).await {
+        Ok(output) => output,
+        Err(response) => return response,
+    };
+
+    // Notifications and responses from the client get no body
+    if output.is_empty() {
+        return StatusCode::ACCEPTED.into_response();
+    }
+
+    let mut response = if accepts_only_event_stream(&headers) {
+        let event = Event::default().event("message").data(output);
+        Sse::new(tokio_stream::once(Ok::<_, Infallible>(event))).into_response()
+    } else {
+        ([(CONTENT_TYPE, "application/json")], output).into_response()
+    };
+
+    if let Some(session) = session.filter(|_| initializing) {
+        if let Ok(value) = HeaderValue::from_str(session.id()) {
+            response.headers_mut().insert(SESSION_HEADER, value);
+        }
+        transport.add_session(session);
+    }
+    response
+}
+
+async fn handle_get(State(transport): State<HttpTransport>, headers: HeaderMap) -> Response {
+    if let Some(rejection) = reject_foreign_origin(&headers) {
+        return rejection;
+    }
+    if let Err(rejection) = transport.find_session(&headers) {
+        return rejection.into_response();
+    }
+    if !accepts(&headers, "text/event-stream") {
+        return (StatusCode::NOT_ACCEPTABLE, "GET requires Accept: text/event-stream").into_response();
+    }
+
+    let (_, events) = transport.open_event_stream(None);
+    let events = events.map(|message| Ok::<_, Infallible>(Event::default().event("message").data(message)));
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+async fn handle_legacy_stream(State(transport): State<HttpTransport>, headers: HeaderMap) -> Response {
+    if let Some(rejection) = reject_foreign_origin(&headers) {
+        return rejection;
+    }
+
+    let session = transport.inner.server.open_session(uuid::Uuid::new_v4().to_string());
+    let id = session.id().to_string();
+    let (stream, messages) = transport.open_event_stream(Some(id.clone()));
+    // Progress for this client's requests goes to its stream; a full stream drops it
+    let notifications = stream.clone();
+    session.set_notifier(move |message| {
+        let _ = notifications.try_send(message);
+    });
+    transport.legacy_sessions().insert(id.clone(), LegacySession { session, stream });
+
+    let endpoint = Event::default()
+        .event("endpoint")
+        .data(format!("{}?session_id={}", MESSAGES_ENDPOINT, id));
+    let events = tokio_stream::once(endpoint)
+        .chain(messages.map(|message| Event::default().event("message").data(message)))
+        .map(Ok::<_, Infallible>);
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+#[derive(Deserialize)]
+struct MessagesQuery {
+    session_id: String,
+}
+
+async fn handle_legacy_message(
+    State(transport): State<HttpTransport>,
+    Query(query): Query<MessagesQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if let Some(rejection) = reject_foreign_origin(&headers) {
+        return rejection;
+    }
+    let Some(LegacySession { session, stream }) = transport.legacy_sessions().get(&query.session_id).cloned() else {
+        return UnknownSession.into_response();
+    };
+
+    let parsed = serde_json::from_str(&body).ok();
+    let output = match process(&transport, Some(session), parsed, body).await {
+        Ok(output) => output,
+        Err(response) => return response,
+    };
+    // The response travels over the client's event stream
+    if !output.is_empty() && stream.send(output).await.is_err() {
+        return UnknownSession.into_response();
+    }
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// Run a message (or batch) through the server off the async runtime, in
+/// `session` or else the default session. Returns the response text, empty
+/// when nothing needs answering.
+async fn process(
+    transport: &HttpTransport,
+    session: Option<Arc<Session>>,
+    parsed: Option<Value>,
+    body: String,
+) -> std::result::Result<String, Response> {
+    let server = Arc::clone(&transport.inner.server);
+    let processed = tokio::task::spawn_blocking(move || {
+        let session = session.as_deref().unwrap_or(server.default_session());
+        match parsed {
+            // Batches are answered with an array of the non-empty responses
+            Some(Value::Array(messages)) if !messages.is_empty() => {
+                let responses: Vec<Value> = messages.iter()
+                    .map(|message| server.process_session_request(session, &message.to_string()))
+                    .filter(|response| !response.is_empty())
+                    .filter_map(|response| serde_json::from_str(&response).ok())
+                    .collect();
+                if responses.is_empty() { String::new() } else { Value::Array(responses).to_string() }
+            }
+            _ => server.process_session_request(session, &body),
+        }
+    })
+    .await;
+
+    processed.map_err(|e| {
+        warn!("HTTP request handler failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })
+}
+
+async fn handle_delete(State(transport): State<HttpTransport>, headers: HeaderMap) -> Response {
+    if let Some(rejection) = reject_foreign_origin(&headers) {
+        return rejection;
+    }
+    let Some(id) = headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok()) else {
+        return (StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header").into_response();
+    };
+    let ended = transport.sessions().remove(id);
+    if let Some(HttpSession { session, .. }) = ended {
+        transport.inner.server.report_usage(&session);
+        debug!(session = %id, "HTTP session ended");
+        StatusCode::OK.into_response()
+    } else {
+        UnknownSession.into_response()
+    }
+}
+
+/// Rejection for requests naming a session this server does not know
+struct UnknownSession;
+
+impl IntoResponse for UnknownSession {
+    fn into_response(self) -> Response {
+        (StatusCode::NOT_FOUND, "Unknown or expired session").into_response()
+    }
+}
+
+/// Browsers always send `Origin`; only pages served from this machine may
+/// talk to the server. Non-browser clients send no `Origin` and are allowed.
+fn reject_foreign_origin(headers: &HeaderMap) -> Option<Response> {
+    let origin = headers.get(ORIGIN)?;
+    let host = origin.to_str().ok()
+        .and_then(|origin| origin.split_once("://"))
+        .map(|(_, rest)| rest.split('/').next().unwrap_or(rest))
+        .map(|authority| match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => host,
+            _ => authority,
+        });
+    match host {
+        Some("localhost" | "127.0.0.1" | "[::1]") => None,
+        _ => {
+            warn!(origin = ?origin, "Rejected HTTP request from foreign origin");
+            Some((StatusCode::FORBIDDEN, "Origin not allowed").into_response())
+        }
+    }
+}
+
+fn accepts(headers: &HeaderMap, media_type: &str) -> bool {
+    headers.get_all(ACCEPT).iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| v.split(';').next().is_some_and(|t| t.trim() == media_type))
+}
+
+fn accepts_only_event_stream(headers: &HeaderMap) -> bool {
+    accepts(headers, "text/event-stream") && !accepts(headers, "application/json") && !accepts(headers, "*/*")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access_control::AccessPolicy;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    /// Send a raw HTTP/1.1 request and return the status line, headers, and body
+    async fn send(addr: std::net::SocketAddr, method: &str, headers: &[(&str, &str)], body: &str) -> (u16, String, String) {
+        send_to(addr, method, MCP_ENDPOINT, headers, body).await
+    }
+
+    async fn send_to(addr: std::net::SocketAddr, method: &str, path: &str, headers: &[(&str, &str)], body: &str) -> (u16, String, String) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut request = format!("{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n", method, path, body.len());
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        request.push_str(body);
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head[9..12].parse().unwrap();
+        (status, head.to_lowercase(), body.to_string())
+    }
+
+    fn session_id(head: &str) -> String {
+        head.lines()
+            .find_map(|line| line.strip_prefix("mcp-session-id: "))
+            .unwrap()
+            .trim()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_round_trip() {
+        let server = Arc::new(McpServer::new(AccessPolicy::permissive()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let serving = tokio::spawn(HttpTransport::new(server).serve(listener, async {
+            let _ = stopped.await;
+        }));
+
+        let json = [("Content-Type", "application/json"), ("Accept", "application/json, text/event-stream")];
+        let (status, head, body) = send(addr, "POST", &json, r#"{"jsonrpc":"2.0","id":1,"method":"initialize"}"#).await;
+        assert_eq!(status, 200);
+        assert!(body.contains("serverInfo"));
+        let session = session_id(&head);
+
+        let with_session = [json[0], json[1], ("Mcp-Session-Id", session.as_str())];
+        let (status, _, body) = send(addr, "POST", &with_session, r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#).await;
+        assert_eq!(status, 200);
+        assert!(body.contains("read_file"));
+
+        let (status, _, body) = send(addr, "POST", &with_session, r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#).await;
+        assert_eq!(status, 202);
+        assert!(body.is_empty());
+
+        let (status, _, _) = send(addr, "POST", &[json[0], ("Origin", "https://evil.example")], r#"{"jsonrpc":"2.0","id":3,"method":"tools/list"}"#).await;
+        assert_eq!(status, 403);
+        let (status, _, _) = send(addr, "POST", &[json[0], ("Origin", "http://localhost:5173")], r#"{"jsonrpc":"2.0","id":3,"method":"tools/list"}"#).await;
+        assert_eq!(status, 200);
+
+        let (status, _, _) = send(addr, "DELETE", &[("Mcp-Session-Id", session.as_str())], "").await;
+        assert_eq!(status, 200);
+        let (status, _, _) = send(addr, "POST", &with_session, r#"{"jsonrpc":"2.0","id":4,"method":"tools/list"}"#).await;
+        assert_eq!(status, 404);
+
+        stop.send(()).unwrap();
+        serving.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_idle_and_excess_sessions_are_ended() {
+        let server = Arc::new(McpServer::new(AccessPolicy::permissive()));
+        let transport = HttpTransport::with_session_limits(server, Duration::from_millis(200), 2);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let serving = tokio::spawn(transport.clone().serve(listener, async {
+            let _ = stopped.await;
+        }));
+
+        let json = [("Content-Type", "application/json"), ("Accept", "application/json, text/event-stream")];
+        let mut ids = Vec::new();
+        for id in 1..=3 {
+            let (_, head, _) = send(addr, "POST", &json, &format!(r#"{{"jsonrpc":"2.0","id":{},"method":"initialize"}}"#, id)).await;
+            ids.push(session_id(&head));
+        }
+        // The first session was idle longest when the third arrived
+        assert_eq!(transport.sessions().len(), 2);
+        let list = |id: String| async move {
+            let headers = [json[0], json[1], ("Mcp-Session-Id", id.as_str())];
+            send(addr, "POST", &headers, r#"{"jsonrpc":"2.0","id":9,"method":"tools/list"}"#).await.0
+        };
+        assert_eq!(list(ids[0].clone()).await, 404);
+        assert_eq!(list(ids[2].clone()).await, 200);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(list(ids[2].clone()).await, 404);
+        assert!(transport.sessions().is_empty());
+
+        stop.send(()).unwrap();
+        serving.await.unwrap().unwrap();
+    }
+
+    /// Read from an open event stream until `needle` has arrived
+    async fn read_until(stream: &mut TcpStream, received: &mut String, needle: &str) {
+        let mut buffer = [0u8; 4096];
+        while !received.contains(needle) {
+            let read = tokio::time::timeout(std::time::Duration::from_secs(5), stream.read(&mut buffer))
+                .await
+                .expect("timed out waiting for event")
+                .unwrap();
+            assert!(read > 0, "stream closed before {:?} arrived", needle);
+            received.push_str(&String::from_utf8_lossy(&buffer[..read]));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_legacy_sse_round_trip() {
+        let server = Arc::new(McpServer::new(AccessPolicy::permissive()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let serving = tokio::spawn(HttpTransport::new(server).serve(listener, async {
+            let _ = stopped.await;
+        }));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET /sse HTTP/1.1\r\nHost: localhost\r\nAccept: text/event-stream\r\n\r\n").await.unwrap();
+        let mut received = String::new();
+        read_until(&mut stream, &mut received, "session_id=").await;
+        read_until(&mut stream, &mut received, "\n\n").await;
+        assert!(received.contains("event: endpoint"));
+        let endpoint = received.lines()
+            .find_map(|line| line.strip_prefix("data: /messages"))
+            .map(|query| format!("/messages{}", query.trim()))
+            .unwrap();
+
+        let json = [("Content-Type", "application/json")];
+        let (status, _, _) = send_to(addr, "POST", &endpoint, &json, r#"{"jsonrpc":"2.0","id":7,"method":"tools/list"}"#).await;
+        assert_eq!(status, 202);
+        read_until(&mut stream, &mut received, r#""id":7"#).await;
+        assert!(received.contains("event: message"));
+
+        let (status, _, _) = send_to(addr, "POST", "/messages?session_id=nope", &json, r#"{"jsonrpc":"2.0","id":8,"method":"tools/list"}"#).await;
+        assert_eq!(status, 404);
+
+        drop(stream);
+        stop.send(()).unwrap();
+        serving.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_accept_negotiation() {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static("text/event-stream"));
+        assert!(accepts_only_event_stream(&headers));
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json, text/event-stream;q=0.9"));
+        assert!(!accepts_only_event_stream(&headers));
+        assert!(accepts(&headers, "text/event-stream"));
+    }
+}
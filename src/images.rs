@@ -0,0 +1,132 @@
+use crate::error::{FileJackError, Result};
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+/// Longest side of a thumbnail when no size is given
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+
+/// Largest thumbnail side `image_info` will produce
+pub const MAX_THUMBNAIL_SIZE: u32 = 1024;
+
+/// Format and dimensions of an image, from `image_info`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ImageInfo {
+    /// Lower-case format name, such as `png` or `jpeg`
+    pub format: String,
+    pub mime_type: String,
+    pub width: u32,
+    pub height: u32,
+    /// Size of the file in bytes
+    pub size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<Thumbnail>,
+}
+
+/// A downscaled copy of an image, always encoded as PNG
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    /// The encoded PNG, returned to clients as separate image content
+    #[serde(skip)]
+    pub png: Vec<u8>,
+}
+
+impl Thumbnail {
+    pub const MIME_TYPE: &'static str = "image/png";
+}
+
+/// Read the format and dimensions of the image in `bytes` from its header,
+/// and decode it into a thumbnail no larger than `thumbnail_size` on either
+/// side when one is asked for.
+///
+/// Decoding is bounded by the image crate's default allocation limit, so a
+/// small file claiming enormous dimensions is refused rather than expanded.
+pub fn inspect(bytes: &[u8], thumbnail_size: Option<u32>) -> Result<ImageInfo> {
+    let format = image::guess_format(bytes)
+        .map_err(|_| FileJackError::InvalidParameters("Not a supported image format".to_string()))?;
+    let (width, height) = reader(bytes, format).into_dimensions().map_err(image_error)?;
+
+    let thumbnail = match thumbnail_size {
+        Some(size) => Some(thumbnail(bytes, format, size.clamp(1, MAX_THUMBNAIL_SIZE))?),
+        None => None,
+    };
+    Ok(ImageInfo {
+        format: format!("{:?}", format).to_lowercase(),
+        mime_type: format.to_mime_type().to_string(),
+        width,
+        height,
+        size: bytes.len() as u64,
+        thumbnail,
+    })
+}
+
+fn reader(bytes: &[u8], format: ImageFormat) -> ImageReader<Cursor<&[u8]>> {
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(Limits::default());
+    reader
+}
+
+fn thumbnail(bytes: &[u8], format: ImageFormat, size: u32) -> Result<Thumbnail> {
+    let image = reader(bytes, format).decode().map_err(image_error)?;
+    // Never scale up: an image that already fits is only re-encoded
+    let image = if image.width() > size || image.height() > size {
+        image.thumbnail(size, size)
+    } else {
+        image
+    };
+    let image = DynamicImage::ImageRgba8(image.to_rgba8());
+
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png).map_err(image_error)?;
+    Ok(Thumbnail { width: image.width(), height: image.height(), png })
+}
+
+fn image_error(e: image::ImageError) -> FileJackError {
+    match e {
+        image::ImageError::IoError(e) => FileJackError::Io(e),
+        e => FileJackError::InvalidParameters(format!("Could not read image: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(width, height))
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_inspect_and_thumbnail() {
+        let bytes = png(400, 100);
+        let info = inspect(&bytes, None).unwrap();
+        assert_eq!((info.format.as_str(), info.mime_type.as_str()), ("png", "image/png"));
+        assert_eq!((info.width, info.height, info.size), (400, 100, bytes.len() as u64));
+        assert!(info.thumbnail.is_none());
+
+        let thumbnail = inspect(&bytes, Some(100)).unwrap().thumbnail.unwrap();
+        assert_eq!((thumbnail.width, thumbnail.height), (100, 25));
+        let decoded = image::load_from_memory(&thumbnail.png).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (100, 25));
+
+        // Small images are not enlarged
+        let thumbnail = inspect(&png(10, 20), Some(100)).unwrap().thumbnail.unwrap();
+        assert_eq!((thumbnail.width, thumbnail.height), (10, 20));
+    }
+
+    #[test]
+    fn test_inspect_rejects_non_images() {
+        assert!(matches!(inspect(b"plain text", None), Err(FileJackError::InvalidParameters(_))));
+        let mut truncated = png(50, 50);
+        truncated.truncate(60);
+        assert!(inspect(&truncated, Some(10)).is_err());
+    }
+}
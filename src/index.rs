@@ -0,0 +1,659 @@
+use crate::error::{FileJackError, Result};
+use crate::vfs::{FileKind, FileSystem, Metadata, WalkOptions};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{BTreeSet, HashMap};
+use std::io::Read;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+/// Files larger than this are indexed without a content hash by default
+pub const DEFAULT_HASH_MAX_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Text files larger than this are left out of content search by default
+pub const DEFAULT_CONTENT_MAX_BYTES: u64 = 1024 * 1024;
+
+/// Shortest query the trigram content index can answer
+pub const MIN_CONTENT_QUERY_CHARS: usize = 3;
+
+/// Bumped when the tables change shape; an older index is emptied and
+/// filled again by the next scan
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS files (
+        path TEXT PRIMARY KEY,
+        parent TEXT NOT NULL,
+        is_dir INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        modified_ns INTEGER,
+        hash TEXT,
+        generation INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS files_parent ON files(parent);
+    CREATE INDEX IF NOT EXISTS files_modified ON files(modified_ns);
+    CREATE TABLE IF NOT EXISTS roots (
+        path TEXT PRIMARY KEY,
+        scanned_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS texts (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        body TEXT NOT NULL
+    );
+    CREATE VIRTUAL TABLE IF NOT EXISTS contents USING fts5(
+        body, content = 'texts', content_rowid = 'id', tokenize = 'trigram'
+    );
+    CREATE TRIGGER IF NOT EXISTS texts_insert AFTER INSERT ON texts BEGIN
+        INSERT INTO contents (rowid, body) VALUES (new.id, new.body);
+    END;
+    CREATE TRIGGER IF NOT EXISTS texts_delete AFTER DELETE ON texts BEGIN
+        INSERT INTO contents (contents, rowid, body) VALUES ('delete', old.id, old.body);
+    END;
+";
+
+/// A file or directory recorded in the index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedFile {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch
+    pub modified_ns: Option<i64>,
+    /// BLAKE3 hash of the contents, for files no larger than the hashing limit
+    pub hash: Option<String>,
+}
+
+impl IndexedFile {
+    /// Modification time in whole seconds since the Unix epoch
+    pub fn modified(&self) -> Option<u64> {
+        self.modified_ns.map(|ns| (ns / 1_000_000_000) as u64)
+    }
+}
+
+/// SQLite index of the paths, sizes, modification times, and content hashes
+/// of every file beneath a set of roots, plus a trigram full-text index of
+/// the smaller UTF-8 text files among them.
+///
+/// Roots are filled by [`FileIndex::rescan`], usually from an
+/// [`IndexRefresher`]. Between scans, a [`crate::FileWriter`] sharing the
+/// index marks the paths it changes as dirty, and readers call
+/// [`FileIndex::flush`] to look at those paths again before querying, so
+/// changes made through FileJack show up at once and other changes by the
+/// next scan. A directory is only answered from the index once a root
+/// containing it has been scanned completely.
+pub struct FileIndex {
+    connection: Mutex<Connection>,
+    hash_max_bytes: u64,
+    content_max_bytes: u64,
+    dirty: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl std::fmt::Debug for FileIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileIndex")
+            .field("hash_max_bytes", &self.hash_max_bytes)
+            .field("content_max_bytes", &self.content_max_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FileIndex {
+    /// Open the index stored in `database`, creating it if missing
+    pub fn open(database: &Path) -> Result<Self> {
+        if let Some(parent) = database.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let connection = Connection::open(database).map_err(index_error)?;
+        connection.pragma_update(None, "journal_mode", "WAL").map_err(index_error)?;
+        Self::with_connection(connection)
+    }
+
+    /// An index kept only in memory, for tests and short-lived servers
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory().map_err(index_error)?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self> {
+        connection.execute_batch(SCHEMA).map_err(index_error)?;
+        let version: i64 = connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(index_error)?;
+        if version < SCHEMA_VERSION {
+            connection
+                .execute_batch("DELETE FROM files; DELETE FROM roots; DELETE FROM texts;")
+                .map_err(index_error)?;
+            connection.pragma_update(None, "user_version", SCHEMA_VERSION).map_err(index_error)?;
+        }
+        Ok(Self {
+            connection: Mutex::new(connection),
+            hash_max_bytes: DEFAULT_HASH_MAX_BYTES,
+            content_max_bytes: DEFAULT_CONTENT_MAX_BYTES,
+            dirty: Mutex::new(BTreeSet::new()),
+        })
+    }
+
+    /// Hash only files of at most `bytes` bytes (0 disables hashing)
+    pub fn with_hash_limit(mut self, bytes: u64) -> Self {
+        self.hash_max_bytes = bytes;
+        self
+    }
+
+    /// Index the text of files of at most `bytes` bytes for
+    /// [`FileIndex::search_text`] (0 disables content indexing)
+    pub fn with_content_limit(mut self, bytes: u64) -> Self {
+        self.content_max_bytes = bytes;
+        self
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Note that `path` is about to change, so the next [`FileIndex::flush`]
+    /// looks at it again
+    pub fn mark_dirty(&self, path: &Path) {
+        self.dirty.lock().unwrap_or_else(|e| e.into_inner()).insert(path.to_path_buf());
+    }
+
+    /// Whether `dir` lies within a root that has been scanned completely
+    pub fn covers(&self, dir: &Path) -> bool {
+        self.roots().is_ok_and(|roots| roots.iter().any(|root| dir.starts_with(root)))
+    }
+
+    /// Roots that have been scanned completely
+    fn roots(&self) -> Result<Vec<String>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare_cached("SELECT path FROM roots").map_err(index_error)?;
+        let rows = statement.query_map([], |row| row.get(0)).map_err(index_error)?;
+        rows.collect::<rusqlite::Result<_>>().map_err(index_error)
+    }
+
+    /// Forget roots other than `roots`, and the entries beneath them
+    pub fn retain_roots(&self, roots: &[PathBuf]) -> Result<()> {
+        for stale in self.roots()?.iter().filter(|root| !roots.iter().any(|r| key(r) == **root)) {
+            self.connection()
+                .execute("DELETE FROM roots WHERE path = ?1", params![stale])
+                .map_err(index_error)?;
+            if !roots.iter().any(|r| Path::new(stale).starts_with(r)) {
+                self.remove(Path::new(stale))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Walk `root` and bring its entries up to date, hashing only files
+    /// whose size or modification time changed. Entries no longer present
+    /// are removed. Returns the number of entries indexed.
+    pub fn rescan(&self, fs: &dyn FileSystem, root: &Path, options: &WalkOptions) -> Result<usize> {
+        let started = SystemTime::now();
+        let count = self.scan(fs, root, options)?;
+        let scanned_at = started.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+        self.connection()
+            .execute(
+                "INSERT OR REPLACE INTO roots (path, scanned_at) VALUES (?1, ?2)",
+                params![key(root), scanned_at],
+            )
+            .map_err(index_error)?;
+        Ok(count)
+    }
+
+    fn scan(&self, fs: &dyn FileSystem, dir: &Path, options: &WalkOptions) -> Result<usize> {
+        let known = self.known_under(dir)?;
+        let mut rows = Vec::new();
+        for entry in fs.walk(dir, options)? {
+            let Ok(metadata) = fs.metadata(&entry.path) else { continue };
+            let previous = known.get(&key(&entry.path));
+            rows.push(self.entry(fs, &entry.path, &metadata, previous));
+        }
+
+        let mut connection = self.connection();
+        let transaction = connection.transaction().map_err(index_error)?;
+        let generation: i64 = transaction
+            .query_row("SELECT COALESCE(MAX(generation), 0) + 1 FROM files", [], |row| row.get(0))
+            .map_err(index_error)?;
+        for row in &rows {
+            store(&transaction, row, generation)?;
+        }
+        let (low, high) = subtree_bounds(dir);
+        transaction
+            .execute(
+                "DELETE FROM texts WHERE path IN
+                 (SELECT path FROM files WHERE path > ?1 AND path < ?2 AND generation < ?3)",
+                params![low, high, generation],
+            )
+            .map_err(index_error)?;
+        transaction
+            .execute(
+                "DELETE FROM files WHERE path > ?1 AND path < ?2 AND generation < ?3",
+                params![low, high, generation],
+            )
+            .map_err(index_error)?;
+        transaction.commit().map_err(index_error)?;
+        debug!(dir = %dir.display(), entries = rows.len(), "Index scan completed");
+        Ok(rows.len())
+    }
+
+    /// Size, modification time, and hash of every entry beneath `dir`
+    fn known_under(&self, dir: &Path) -> Result<HashMap<String, IndexedFile>> {
+        let (low, high) = subtree_bounds(dir);
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare_cached(&format!("{} WHERE path > ?1 AND path < ?2", SELECT_FILES))
+            .map_err(index_error)?;
+        let rows = statement.query_map(params![low, high], indexed_file).map_err(index_error)?;
+        rows.map(|row| row.map(|file| (key(&file.path), file)).map_err(index_error)).collect()
+    }
+
+    /// The entry to store for `path`, reusing the previous hash and text
+    /// when the file looks unchanged
+    fn entry(&self, fs: &dyn FileSystem, path: &Path, metadata: &Metadata, previous: Option<&IndexedFile>) -> Entry {
+        let is_dir = metadata.kind == FileKind::Dir;
+        let modified_ns = metadata.modified
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos() as i64);
+        let file = |hash| IndexedFile { path: path.to_path_buf(), is_dir, size: metadata.len, modified_ns, hash };
+        let unchanged = previous.filter(|p| p.size == metadata.len && p.modified_ns == modified_ns && p.is_dir == is_dir);
+        if let Some(previous) = unchanged {
+            return Entry { file: file(previous.hash.clone()), changed: false, text: None };
+        }
+        // Reading a FIFO or device could wait forever
+        if metadata.kind != FileKind::File {
+            return Entry { file: file(None), changed: true, text: None };
+        }
+
+        let hashed = metadata.len <= self.hash_max_bytes && self.hash_max_bytes > 0;
+        if metadata.len <= self.content_max_bytes && self.content_max_bytes > 0 {
+            // Small enough to read whole, so hash and index the same bytes
+            let bytes = read_file(fs, path);
+            let hash = bytes.as_ref().filter(|_| hashed).map(|b| blake3::hash(b).to_hex().to_string());
+            let text = bytes.and_then(|b| String::from_utf8(b).ok()).filter(|t| !t.contains('\0'));
+            return Entry { file: file(hash), changed: true, text };
+        }
+        Entry { file: file(if hashed { hash_file(fs, path) } else { None }), changed: true, text: None }
+    }
+
+    /// Look again at every path marked dirty since the last flush
+    pub fn flush(&self, fs: &dyn FileSystem, options: &WalkOptions) -> Result<()> {
+        let dirty = std::mem::take(&mut *self.dirty.lock().unwrap_or_else(|e| e.into_inner()));
+        for path in dirty {
+            if !self.covers(&path) {
+                continue;
+            }
+            match fs.metadata(&path) {
+                Ok(metadata) => {
+                    let previous = self.get(&path)?;
+                    let row = self.entry(fs, &path, &metadata, previous.as_ref());
+                    let mut connection = self.connection();
+                    let transaction = connection.transaction().map_err(index_error)?;
+                    let generation: i64 = transaction
+                        .query_row("SELECT COALESCE(MAX(generation), 0) FROM files", [], |row| row.get(0))
+                        .map_err(index_error)?;
+                    store(&transaction, &row, generation)?;
+                    transaction.commit().map_err(index_error)?;
+                    drop(connection);
+                    if row.file.is_dir {
+                        self.scan(fs, &path, options)?;
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => self.remove(&path)?,
+                Err(e) => warn!(path = %path.display(), error = %e, "Could not refresh index entry"),
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&self, path: &Path) -> Result<Option<IndexedFile>> {
+        self.connection()
+            .query_row(&format!("{} WHERE path = ?1", SELECT_FILES), params![key(path)], indexed_file)
+            .optional()
+            .map_err(index_error)
+    }
+
+    /// Forget `path` and everything beneath it
+    fn remove(&self, path: &Path) -> Result<()> {
+        let (low, high) = subtree_bounds(path);
+        let connection = self.connection();
+        for table in ["texts", "files"] {
+            connection
+                .execute(
+                    &format!("DELETE FROM {} WHERE path = ?1 OR (path > ?2 AND path < ?3)", table),
+                    params![key(path), low, high],
+                )
+                .map_err(index_error)?;
+        }
+        Ok(())
+    }
+
+    /// Entries directly inside `dir`, or everything beneath it when
+    /// `recursive`, in path order
+    pub fn list(&self, dir: &Path, recursive: bool) -> Result<Vec<IndexedFile>> {
+        let connection = self.connection();
+        let rows = if recursive {
+            let (low, high) = subtree_bounds(dir);
+            let mut statement = connection
+                .prepare_cached(&format!("{} WHERE path > ?1 AND path < ?2 ORDER BY path", SELECT_FILES))
+                .map_err(index_error)?;
+            let rows = statement.query_map(params![low, high], indexed_file).map_err(index_error)?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()
+        } else {
+            let mut statement = connection
+                .prepare_cached(&format!("{} WHERE parent = ?1 ORDER BY path", SELECT_FILES))
+                .map_err(index_error)?;
+            let rows = statement.query_map(params![key(dir)], indexed_file).map_err(index_error)?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()
+        };
+        rows.map_err(index_error)
+    }
+
+    /// Up to `limit` files beneath `dir` accepted by `keep`, most recently
+    /// modified first
+    pub fn recent(&self, dir: &Path, limit: usize, mut keep: impl FnMut(&Path) -> bool) -> Result<Vec<IndexedFile>> {
+        let (low, high) = subtree_bounds(dir);
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare_cached(&format!(
+                "{} WHERE is_dir = 0 AND path > ?1 AND path < ?2 ORDER BY modified_ns DESC, path",
+                SELECT_FILES
+            ))
+            .map_err(index_error)?;
+        let mut rows = statement.query(params![low, high]).map_err(index_error)?;
+        let mut files = Vec::new();
+        while files.len() < limit {
+            let Some(row) = rows.next().map_err(index_error)? else { break };
+            let file = indexed_file(row).map_err(index_error)?;
+            if keep(&file.path) {
+                files.push(file);
+            }
+        }
+        Ok(files)
+    }
+
+    /// Files beneath `dir` accepted by `keep` whose text contains `query`,
+    /// ignoring case, best match first by BM25. Each file comes with its
+    /// indexed text. Stops after `limit` files.
+    pub fn search_text(
+        &self,
+        dir: &Path,
+        query: &str,
+        limit: usize,
+        mut keep: impl FnMut(&Path) -> bool,
+    ) -> Result<Vec<(PathBuf, String)>> {
+        if query.chars().count() < MIN_CONTENT_QUERY_CHARS {
+            return Err(FileJackError::InvalidParameters(format!(
+                "Content search needs at least {} characters",
+                MIN_CONTENT_QUERY_CHARS
+            )));
+        }
+        // A quoted phrase, so the query is matched literally
+        let phrase = format!("\"{}\"", query.replace('"', "\"\""));
+        let (low, high) = subtree_bounds(dir);
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare_cached(
+                "SELECT texts.path, texts.body FROM contents JOIN texts ON texts.id = contents.rowid
+                 WHERE contents MATCH ?1 AND texts.path > ?2 AND texts.path < ?3
+                 ORDER BY contents.rank",
+            )
+            .map_err(index_error)?;
+        let mut rows = statement.query(params![phrase, low, high]).map_err(index_error)?;
+        let mut files = Vec::new();
+        while files.len() < limit {
+            let Some(row) = rows.next().map_err(index_error)? else { break };
+            let path = PathBuf::from(row.get::<_, String>(0).map_err(index_error)?);
+            if keep(&path) {
+                files.push((path, row.get(1).map_err(index_error)?));
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// An entry about to be stored by a scan or flush
+struct Entry {
+    file: IndexedFile,
+    /// Whether the file differs from what the index held, so its text must
+    /// be replaced
+    changed: bool,
+    /// The file's text, when it is small enough and valid UTF-8
+    text: Option<String>,
+}
+
+const SELECT_FILES: &str = "SELECT path, is_dir, size, modified_ns, hash FROM files";
+
+fn indexed_file(row: &rusqlite::Row<'_>) -> rusqlite::Result<IndexedFile> {
+    Ok(IndexedFile {
+        path: PathBuf::from(row.get::<_, String>(0)?),
+        is_dir: row.get(1)?,
+        size: row.get::<_, i64>(2)? as u64,
+        modified_ns: row.get(3)?,
+        hash: row.get(4)?,
+    })
+}
+
+fn store(connection: &Connection, entry: &Entry, generation: i64) -> Result<()> {
+    upsert(connection, &entry.file, generation)?;
+    if !entry.changed {
+        return Ok(());
+    }
+    let path = key(&entry.file.path);
+    connection
+        .prepare_cached("DELETE FROM texts WHERE path = ?1")
+        .and_then(|mut statement| statement.execute(params![path]))
+        .map_err(index_error)?;
+    if let Some(text) = &entry.text {
+        connection
+            .prepare_cached("INSERT INTO texts (path, body) VALUES (?1, ?2)")
+            .and_then(|mut statement| statement.execute(params![path, text]))
+            .map_err(index_error)?;
+    }
+    Ok(())
+}
+
+fn upsert(connection: &Connection, file: &IndexedFile, generation: i64) -> Result<()> {
+    let parent = file.path.parent().map(key).unwrap_or_default();
+    connection
+        .prepare_cached(
+            "INSERT OR REPLACE INTO files (path, parent, is_dir, size, modified_ns, hash, generation)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .and_then(|mut statement| {
+            statement.execute(params![
+                key(&file.path),
+                parent,
+                file.is_dir,
+                file.size as i64,
+                file.modified_ns,
+                file.hash,
+                generation,
+            ])
+        })
+        .map_err(index_error)?;
+    Ok(())
+}
+
+fn key(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// Bounds that select exactly the paths beneath `dir` with
+/// `path > low AND path < high`: every such path continues with a separator,
+/// and the character after the separator sorts directly above it
+fn subtree_bounds(dir: &Path) -> (String, String) {
+    let dir = key(dir);
+    let dir = dir.trim_end_matches(MAIN_SEPARATOR);
+    let next = char::from(MAIN_SEPARATOR as u8 + 1);
+    (format!("{}{}", dir, MAIN_SEPARATOR), format!("{}{}", dir, next))
+}
+
+fn read_file(fs: &dyn FileSystem, path: &Path) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    fs.open(path).ok()?.read_to_end(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+fn hash_file(fs: &dyn FileSystem, path: &Path) -> Option<String> {
+    let mut handle = fs.open(path).ok()?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        match handle.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => {
+                hasher.update(&buffer[..n]);
+            }
+            Err(_) => return None,
+        }
+    }
+    Some(hasher.finalize().to_hex().to_string())
+}
+
+fn index_error(e: rusqlite::Error) -> FileJackError {
+    FileJackError::Io(std::io::Error::other(format!("File index: {}", e)))
+}
+
+/// Rescans the roots of a [`FileIndex`] on a background thread, once at
+/// startup and then every interval, so changes made outside FileJack are
+/// picked up. The thread stops when the refresher is dropped; a scan already
+/// under way is finished first.
+pub struct IndexRefresher {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl IndexRefresher {
+    pub fn spawn(
+        index: Arc<FileIndex>,
+        fs: Arc<dyn FileSystem>,
+        roots: Vec<PathBuf>,
+        options: WalkOptions,
+        interval: Duration,
+    ) -> Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let handle = std::thread::Builder::new()
+            .name("filejack-index".to_string())
+            .spawn(move || {
+                while !thread_stop.load(Ordering::Relaxed) {
+                    for root in &roots {
+                        if thread_stop.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        match index.rescan(fs.as_ref(), root, &options) {
+                            Ok(count) => info!(root = %root.display(), entries = count, "Indexed files"),
+                            Err(e) => warn!(root = %root.display(), error = %e, "Indexing failed"),
+                        }
+                    }
+                    let deadline = std::time::Instant::now() + interval;
+                    while std::time::Instant::now() < deadline && !thread_stop.load(Ordering::Relaxed) {
+                        std::thread::sleep(Duration::from_millis(100).min(interval));
+                    }
+                }
+            })?;
+        Ok(Self { stop, handle: Some(handle) })
+    }
+}
+
+impl Drop for IndexRefresher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Detach rather than join so a long scan never delays a reload
+        self.handle.take();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::StdFileSystem;
+    use std::fs;
+
+    fn workspace() -> (tempfile::TempDir, PathBuf) {
+        let temp_dir = tempfile::Builder::new().prefix("index").tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("README.md"), "# Readme").unwrap();
+        (temp_dir, root)
+    }
+
+    #[test]
+    fn test_rescan_tracks_changes() {
+        let (_temp_dir, root) = workspace();
+        let index = FileIndex::open_in_memory().unwrap();
+        let options = WalkOptions { skip_hidden: true, ..WalkOptions::default() };
+        assert!(!index.covers(&root));
+
+        assert_eq!(index.rescan(&StdFileSystem, &root, &options).unwrap(), 3);
+        assert!(index.covers(&root.join("src")));
+        let all = index.list(&root, true).unwrap();
+        assert_eq!(all.len(), 3);
+        let main = all.iter().find(|f| f.path.ends_with("main.rs")).unwrap();
+        assert_eq!(main.size, 12);
+        assert_eq!(main.hash.as_deref(), Some(blake3::hash(b"fn main() {}").to_hex().as_str()));
+        assert_eq!(index.list(&root, false).unwrap().len(), 2);
+
+        fs::remove_file(root.join("README.md")).unwrap();
+        fs::write(root.join("src/lib.rs"), "pub fn lib() {}").unwrap();
+        index.rescan(&StdFileSystem, &root, &options).unwrap();
+        let names: Vec<_> = index.list(&root, true).unwrap().into_iter()
+            .map(|f| f.path.strip_prefix(&root).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(names, [PathBuf::from("src"), Path::new("src").join("lib.rs"), Path::new("src").join("main.rs")]);
+    }
+
+    #[test]
+    fn test_flush_and_recent() {
+        let (_temp_dir, root) = workspace();
+        let index = FileIndex::open_in_memory().unwrap();
+        let options = WalkOptions::default();
+        index.rescan(&StdFileSystem, &root, &options).unwrap();
+
+        let notes = root.join("src/notes.txt");
+        fs::write(&notes, "new").unwrap();
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::options().write(true).open(root.join("README.md")).unwrap().set_modified(old).unwrap();
+        index.mark_dirty(&notes);
+        index.mark_dirty(&root.join("README.md"));
+        index.flush(&StdFileSystem, &options).unwrap();
+
+        let recent = index.recent(&root, 10, |_| true).unwrap();
+        assert_eq!(recent.len(), 3);
+        assert_eq!(recent.last().unwrap().path, root.join("README.md"));
+        assert!(recent.iter().any(|f| f.path == notes));
+        assert_eq!(index.recent(&root, 1, |p| !p.ends_with("notes.txt")).unwrap().len(), 1);
+
+        fs::remove_dir_all(root.join("src")).unwrap();
+        index.mark_dirty(&root.join("src"));
+        index.flush(&StdFileSystem, &options).unwrap();
+        assert_eq!(index.list(&root, true).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_search_text() {
+        let (_temp_dir, root) = workspace();
+        fs::write(root.join("src/lib.rs"), "// Parser\nfn parse() {}\nfn parse_all() { parse() }").unwrap();
+        fs::write(root.join("src/data.bin"), b"parse\0").unwrap();
+        let index = FileIndex::open_in_memory().unwrap();
+        let options = WalkOptions::default();
+        index.rescan(&StdFileSystem, &root, &options).unwrap();
+
+        let found = index.search_text(&root, "PARSE", 10, |_| true).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, root.join("src/lib.rs"));
+        assert!(index.search_text(&root, "pa", 10, |_| true).is_err());
+        assert!(index.search_text(&root, "main", 10, |p| !p.ends_with("main.rs")).unwrap().is_empty());
+
+        fs::write(root.join("README.md"), "How to parse \"quoted\" input").unwrap();
+        index.mark_dirty(&root.join("README.md"));
+        fs::remove_file(root.join("src/lib.rs")).unwrap();
+        index.mark_dirty(&root.join("src/lib.rs"));
+        index.flush(&StdFileSystem, &options).unwrap();
+        let found = index.search_text(&root, "parse \"quoted", 10, |_| true).unwrap();
+        assert_eq!(found, [(root.join("README.md"), "How to parse \"quoted\" input".to_string())]);
+        assert!(index.search_text(&root.join("src"), "parse", 10, |_| true).unwrap().is_empty());
+    }
+}
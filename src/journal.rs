@@ -0,0 +1,296 @@
+//! A session's record of the changes its tool calls made.
+//!
+//! Every successful call to a tool that modifies files adds an entry naming
+//! the tool, the paths it was given, and their sizes before and after.
+//! Small text files are also kept before and after the call, so that
+//! `get_operation_history` can show a diff when asked and a person can
+//! review what an agent did without reconstructing it from logs.
+
+use crate::file_ops::FileReader;
+use crate::mounts::PATH_ARGUMENTS;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Most entries a session keeps; older ones are dropped first
+pub const MAX_JOURNAL_ENTRIES: usize = 256;
+
+/// Largest text file whose contents are kept for diffs (32 KiB)
+pub const MAX_SNAPSHOT_BYTES: u64 = 32 * 1024;
+
+/// Most cells of the table compared to find a diff
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Lines of unchanged text shown around each change
+const DIFF_CONTEXT: usize = 3;
+
+/// One path a recorded call touched
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathChange {
+    pub path: String,
+    /// Size of the file before the call; `None` if it did not exist or is
+    /// a directory
+    pub size_before: Option<u64>,
+    pub size_after: Option<u64>,
+    /// Unified diff of the text, when asked for and both versions were kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+    #[serde(skip)]
+    before: Option<String>,
+    #[serde(skip)]
+    after: Option<String>,
+}
+
+impl PathChange {
+    /// Diff of the file's text, treating a missing file as empty. `None`
+    /// if either version was not kept or nothing changed.
+    fn text_diff(&self) -> Option<String> {
+        fn text(size: Option<u64>, text: Option<&str>) -> Option<&str> {
+            match size {
+                None => Some(""),
+                Some(_) => text,
+            }
+        }
+        let before = text(self.size_before, self.before.as_deref())?;
+        let after = text(self.size_after, self.after.as_deref())?;
+        diff(before, after).filter(|diff| !diff.is_empty())
+    }
+}
+
+/// One successful call to a tool that changes files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Position among all calls recorded for the session, from 1
+    pub sequence: u64,
+    /// Seconds since the Unix epoch when the call finished
+    pub timestamp: u64,
+    pub tool: String,
+    pub changes: Vec<PathChange>,
+}
+
+/// The state of a call's paths before it ran
+pub(crate) struct Before {
+    files: Vec<(&'static str, String, FileState)>,
+}
+
+/// Whether a recorded call's path was there before and after it, by the
+/// argument that named it
+pub(crate) struct Touched {
+    pub(crate) argument: &'static str,
+    pub(crate) path: String,
+    pub(crate) existed: bool,
+    pub(crate) exists: bool,
+}
+
+struct FileState {
+    exists: bool,
+    size: Option<u64>,
+    text: Option<String>,
+}
+
+impl Before {
+    /// Look at the paths named in `arguments`
+    pub(crate) fn take(reader: &FileReader, arguments: &Value) -> Self {
+        let files = PATH_ARGUMENTS.iter()
+            .filter_map(|key| arguments.get(key).and_then(Value::as_str).map(|path| (*key, path)))
+            .map(|(key, path)| (key, path.to_string(), FileState::read(reader, Path::new(path))))
+            .collect();
+        Self { files }
+    }
+}
+
+impl FileState {
+    fn read(reader: &FileReader, path: &Path) -> Self {
+        let metadata = reader.get_metadata(path).ok();
+        let size = metadata.as_ref().filter(|metadata| metadata.is_file).map(|metadata| metadata.size);
+        let text = size
+            .filter(|size| *size <= MAX_SNAPSHOT_BYTES)
+            .and_then(|_| reader.read_to_string(path).ok());
+        Self { exists: metadata.is_some(), size, text }
+    }
+}
+
+/// Entries recorded for one session
+#[derive(Default)]
+pub struct Journal {
+    entries: Mutex<VecDeque<JournalEntry>>,
+}
+
+impl Journal {
+    /// Record that `tool` succeeded, comparing its paths with `before`;
+    /// returns which of them existed before and after
+    pub(crate) fn record(&self, reader: &FileReader, tool: &str, before: Before) -> Vec<Touched> {
+        let mut touched = Vec::with_capacity(before.files.len());
+        let changes = before.files.into_iter()
+            .map(|(argument, path, before)| {
+                let after = FileState::read(reader, Path::new(&path));
+                touched.push(Touched { argument, path: path.clone(), existed: before.exists, exists: after.exists });
+                PathChange {
+                    path,
+                    size_before: before.size,
+                    size_after: after.size,
+                    diff: None,
+                    before: before.text,
+                    after: after.text,
+                }
+            })
+            .collect();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let sequence = entries.back().map_or(1, |last| last.sequence + 1);
+        if entries.len() == MAX_JOURNAL_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(JournalEntry { sequence, timestamp, tool: tool.to_string(), changes });
+        touched
+    }
+
+    /// The latest `limit` entries, oldest first, with diffs if `diffs`
+    pub fn entries(&self, limit: Option<usize>, diffs: bool) -> Vec<JournalEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let skip = limit.map_or(0, |limit| entries.len().saturating_sub(limit));
+        entries.iter()
+            .skip(skip)
+            .cloned()
+            .map(|mut entry| {
+                if diffs {
+                    for change in &mut entry.changes {
+                        change.diff = change.text_diff();
+                    }
+                }
+                entry
+            })
+            .collect()
+    }
+}
+
+/// Unified diff of `before` and `after` by line, or `None` if they are too
+/// large to compare. Empty when they are equal.
+pub fn diff(before: &str, after: &str) -> Option<String> {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    if (old.len() + 1).saturating_mul(new.len() + 1) > MAX_DIFF_CELLS {
+        return None;
+    }
+
+    // Length of the longest common subsequence of each pair of suffixes
+    let width = new.len() + 1;
+    let mut common = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i * width + j] = if old[i] == new[j] {
+                common[(i + 1) * width + j + 1] + 1
+            } else {
+                common[(i + 1) * width + j].max(common[i * width + j + 1])
+            };
+        }
+    }
+
+    // Each line as kept (' '), removed ('-'), or added ('+'), with its
+    // position in the old and new text
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push((' ', i, j, old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[(i + 1) * width + j] >= common[i * width + j + 1]) {
+            ops.push(('-', i, j, old[i]));
+            i += 1;
+        } else {
+            ops.push(('+', i, j, new[j]));
+            j += 1;
+        }
+    }
+
+    let mut out = String::new();
+    let mut start = 0;
+    while let Some(first) = ops[start..].iter().position(|op| op.0 != ' ').map(|offset| start + offset) {
+        // Extend the hunk while changes are close enough to share context
+        let mut end = first;
+        let mut last_change = first;
+        while end < ops.len() && end - last_change <= 2 * DIFF_CONTEXT {
+            if ops[end].0 != ' ' {
+                last_change = end;
+            }
+            end += 1;
+        }
+        let hunk_start = first.saturating_sub(DIFF_CONTEXT).max(start);
+        let hunk_end = (last_change + DIFF_CONTEXT + 1).min(ops.len());
+        let hunk = &ops[hunk_start..hunk_end];
+        let old_count = hunk.iter().filter(|op| op.0 != '+').count();
+        let new_count = hunk.iter().filter(|op| op.0 != '-').count();
+        let _ = writeln!(
+            out,
+            "@@ -{},{} +{},{} @@",
+            hunk[0].1 + usize::from(old_count > 0),
+            old_count,
+            hunk[0].2 + usize::from(new_count > 0),
+            new_count
+        );
+        for (kind, _, _, line) in hunk {
+            let _ = writeln!(out, "{}{}", kind, line);
+        }
+        start = hunk_end;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access_control::AccessPolicy;
+    use serde_json::json;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(diff("a\nb\n", "a\nb\n").unwrap(), "");
+        assert_eq!(diff("a\nb\nc\n", "a\nB\nc\n").unwrap(), "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n");
+        assert_eq!(diff("", "new\n").unwrap(), "@@ -0,0 +1,1 @@\n+new\n");
+
+        let before: String = (1..=20).map(|n| format!("{}\n", n)).collect();
+        let after = before.replacen("2\n", "two\n", 1).replace("19\n", "nineteen\n");
+        let diff = diff(&before, &after).unwrap();
+        assert_eq!(diff.matches("@@ -").count(), 2, "{}", diff);
+        assert!(diff.contains("-19\n+nineteen\n"));
+    }
+
+    #[test]
+    fn test_journal_records_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("notes.txt");
+        fs::write(&file, "one\n").unwrap();
+        let reader = FileReader::new(AccessPolicy::permissive());
+        let journal = Journal::default();
+
+        let arguments = json!({"path": file});
+        let before = Before::take(&reader, &arguments);
+        fs::write(&file, "one\ntwo\n").unwrap();
+        journal.record(&reader, "append_file", before);
+
+        let before = Before::take(&reader, &arguments);
+        fs::remove_file(&file).unwrap();
+        journal.record(&reader, "delete_file", before);
+
+        let entries = journal.entries(None, true);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].sequence, 1);
+        assert_eq!(entries[0].changes[0].size_before, Some(4));
+        assert_eq!(entries[0].changes[0].size_after, Some(8));
+        assert_eq!(entries[0].changes[0].diff.as_deref(), Some("@@ -1,1 +1,2 @@\n one\n+two\n"));
+        assert_eq!(entries[1].changes[0].size_after, None);
+
+        let latest = journal.entries(Some(1), false);
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].tool, "delete_file");
+        assert!(latest[0].changes[0].diff.is_none());
+    }
+}
@@ -0,0 +1,329 @@
+use crate::config::{merge_values, parse_value, resolve_profile, Config, ConfigFormat};
+use crate::error::{FileJackError, Result};
+use crate::schema;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// System-wide configuration directory, the lowest-priority file layer
+pub const SYSTEM_CONFIG_DIR: &str = "/etc/filejack";
+
+/// Label used for values nobody configured
+pub const DEFAULT_SOURCE: &str = "default";
+
+/// One source of configuration values, e.g. a file or an environment variable
+#[derive(Debug, Clone)]
+pub struct ConfigLayer {
+    /// Human-readable description of where the values came from
+    pub source: String,
+    /// The file this layer was read from, if any
+    pub path: Option<PathBuf>,
+    /// Partial configuration document
+    pub value: Value,
+}
+
+/// The effective configuration and where each of its values came from
+#[derive(Debug, Clone)]
+pub struct ResolvedConfig {
+    pub config: Config,
+    /// Source of every value, keyed by dotted path (e.g. `access_policy.read_only`)
+    pub sources: BTreeMap<String, String>,
+}
+
+/// An ordered chain of configuration layers; later layers override earlier ones.
+///
+/// The standard chain built by [`ConfigLayers::discover`] is
+/// `/etc/filejack/config.*` → `~/.config/filejack/config.*` → the project
+/// file (`FILEJACK_CONFIG` or `./filejack.*`) → environment variables, and
+/// the binary appends command-line flags on top.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLayers {
+    layers: Vec<ConfigLayer>,
+    /// Notes from migrating file layers, reported with the resolved config
+    warnings: Vec<String>,
+}
+
+impl ConfigLayers {
+    /// Create an empty chain
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the standard chain for a working directory.
+    ///
+    /// `explicit` replaces the project file lookup in `cwd`, and `env` looks
+    /// up environment variables. When no configuration file exists at all,
+    /// the bottom layer reproduces the environment-only defaults: a
+    /// restricted policy for `FILEJACK_BASE_PATH`, or a permissive one.
+    pub fn discover(
+        cwd: &Path,
+        explicit: Option<PathBuf>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let mut layers = Self::new();
+
+        if let Some(path) = find_with_stem(Path::new(SYSTEM_CONFIG_DIR), "config") {
+            layers.push_file("system config", &path)?;
+        }
+        if let Some(dir) = user_config_dir(&env) {
+            if let Some(path) = find_with_stem(&dir, "config") {
+                layers.push_file("user config", &path)?;
+            }
+        }
+        match explicit {
+            Some(path) => layers.push_file("project config", &path)?,
+            None => {
+                if let Some(path) = Config::find_in_dir(cwd) {
+                    layers.push_file("project config", &path)?;
+                }
+            }
+        }
+
+        let base_path = env("FILEJACK_BASE_PATH");
+        let read_only = match env("FILEJACK_READ_ONLY") {
+            Some(value) => Some(value.parse::<bool>().map_err(|_| FileJackError::ConfigError(
+                format!("FILEJACK_READ_ONLY must be true or false, got '{}'", value)
+            ))?),
+            None => None,
+        };
+
+        if layers.file_paths().is_empty() {
+            let defaults = match &base_path {
+                Some(base) => Config::default_restricted(PathBuf::from(base)),
+                None => Config::permissive(),
+            };
+            layers.layers.insert(0, ConfigLayer {
+                source: DEFAULT_SOURCE.to_string(),
+                path: None,
+                value: serde_json::to_value(defaults)?,
+            });
+        }
+
+        if let Some(base) = base_path {
+            layers.push("env FILEJACK_BASE_PATH", json!({"access_policy": {"allowed_paths": [base]}}));
+        }
+        if let Some(read_only) = read_only {
+            layers.push("env FILEJACK_READ_ONLY", json!({"access_policy": {"read_only": read_only}}));
+        }
+
+        Ok(layers)
+    }
+
+    /// Append a layer built from an in-memory document
+    pub fn push(&mut self, source: impl Into<String>, value: Value) {
+        self.layers.push(ConfigLayer {
+            source: source.into(),
+            path: None,
+            value,
+        });
+    }
+
+    /// Append a layer read from a configuration file in any supported format
+    pub fn push_file(&mut self, kind: &str, path: &Path) -> Result<()> {
+        let content = fs::read_to_string(path).map_err(|e| FileJackError::ConfigError(
+            format!("Cannot read {} {}: {}", kind, path.display(), e)
+        ))?;
+        let mut value = parse_value(&content, ConfigFormat::from_path(path))?;
+
+        // Migrate each file on its own so old and new layouts merge correctly
+        let notes = schema::migrate(&mut value).map_err(|e| FileJackError::ConfigError(
+            format!("{} {}: {}", kind, path.display(), e)
+        ))?;
+        self.warnings.extend(notes.into_iter().map(|note| format!("{}: {}", path.display(), note)));
+
+        self.layers.push(ConfigLayer {
+            source: format!("{} {}", kind, path.display()),
+            path: Some(path.to_path_buf()),
+            value,
+        });
+        Ok(())
+    }
+
+    /// The layers in order of increasing priority
+    pub fn layers(&self) -> &[ConfigLayer] {
+        &self.layers
+    }
+
+    /// Files contributing to the configuration, for watching
+    pub fn file_paths(&self) -> Vec<PathBuf> {
+        self.layers.iter().filter_map(|layer| layer.path.clone()).collect()
+    }
+
+    /// Merge every layer and record where each value came from.
+    ///
+    /// `profile` is looked up among the profiles defined by the files and
+    /// applied right after the file layers, so environment variables and
+    /// command-line flags still override it.
+    pub fn resolve(&self, profile: Option<&str>) -> Result<ResolvedConfig> {
+        let profile_overlay = match profile {
+            Some(name) => {
+                let mut profiles = Value::Object(Default::default());
+                for layer in &self.layers {
+                    if let Some(defined) = layer.value.get("profiles") {
+                        merge_values(&mut profiles, defined.clone());
+                    }
+                }
+                let profiles = profiles.as_object().cloned().unwrap_or_default();
+                Some((format!("profile {}", name), resolve_profile(&profiles, name, &mut Vec::new())?))
+            }
+            None => None,
+        };
+        let files_end = self.layers.iter()
+            .rposition(|layer| layer.path.is_some())
+            .map_or(0, |i| i + 1);
+
+        let mut merged = json!({"access_policy": {}});
+        let mut sources = BTreeMap::new();
+        for (i, layer) in self.layers.iter().enumerate() {
+            if let Some((source, overlay)) = profile_overlay.as_ref().filter(|_| i == files_end) {
+                apply_layer(&mut merged, &mut sources, source, overlay);
+            }
+            apply_layer(&mut merged, &mut sources, &layer.source, &layer.value);
+        }
+        if let Some((source, overlay)) = profile_overlay.as_ref().filter(|_| files_end == self.layers.len()) {
+            apply_layer(&mut merged, &mut sources, source, overlay);
+        }
+
+        let mut config = Config::from_value(merged, None)?;
+        config.active_profile = profile.map(str::to_string);
+        let mut warnings = self.warnings.clone();
+        for warning in std::mem::take(&mut config.load_warnings) {
+            if !warnings.iter().any(|w| w.ends_with(&warning)) {
+                warnings.push(warning);
+            }
+        }
+        config.load_warnings = warnings;
+
+        // Fill in built-in defaults for everything no layer set
+        let mut effective = serde_json::to_value(&config)?;
+        if let Some(map) = effective.as_object_mut() {
+            map.remove("profiles");
+        }
+        let mut all_sources = BTreeMap::new();
+        record_sources(&effective, "", DEFAULT_SOURCE, &mut all_sources);
+        for (key, source) in all_sources.iter_mut() {
+            if let Some(recorded) = sources.get(key) {
+                *source = recorded.clone();
+            }
+        }
+
+        Ok(ResolvedConfig {
+            config,
+            sources: all_sources,
+        })
+    }
+}
+
+/// Merge one layer into the document and note it as the source of its values
+fn apply_layer(merged: &mut Value, sources: &mut BTreeMap<String, String>, source: &str, value: &Value) {
+    record_sources(value, "", source, sources);
+    merge_values(merged, value.clone());
+}
+
+/// Record `source` for every leaf of `value` (objects are descended into;
+/// arrays and scalars are leaves). Profile definitions are not settings and
+/// are skipped.
+fn record_sources(value: &Value, prefix: &str, source: &str, sources: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                if prefix.is_empty() && key == "profiles" {
+                    continue;
+                }
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                record_sources(child, &path, source, sources);
+            }
+        }
+        _ if !prefix.is_empty() => {
+            sources.insert(prefix.to_string(), source.to_string());
+        }
+        _ => {}
+    }
+}
+
+/// `$XDG_CONFIG_HOME/filejack`, falling back to `~/.config/filejack`
+fn user_config_dir(env: &impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    env("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|dir| dir.join("filejack"))
+}
+
+/// Find `<stem>.json`, `.toml`, `.yaml`, or `.yml` in `dir`
+fn find_with_stem(dir: &Path, stem: &str) -> Option<PathBuf> {
+    ["json", "toml", "yaml", "yml"].iter()
+        .map(|ext| dir.join(format!("{}.{}", stem, ext)))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_layers_override_in_order_with_sources() {
+        let temp_dir = TempDir::new().unwrap();
+        let home = temp_dir.path().join("home");
+        let project = temp_dir.path().join("project");
+        fs::create_dir_all(home.join(".config/filejack")).unwrap();
+        fs::create_dir_all(&project).unwrap();
+
+        fs::write(
+            home.join(".config/filejack/config.toml"),
+            "[access_policy]\nallowed_paths = [\"/srv/user\"]\nmax_file_size = 100\n",
+        ).unwrap();
+        fs::write(
+            project.join("filejack.json"),
+            r#"{"access_policy": {"allowed_paths": ["/srv/project"]},
+                "profiles": {"ci": {"server": {"name": "CI"}, "access_policy": {"read_only": false}}}}"#,
+        ).unwrap();
+
+        let home_str = home.to_string_lossy().to_string();
+        let env = |name: &str| match name {
+            "HOME" => Some(home_str.clone()),
+            "FILEJACK_READ_ONLY" => Some("true".to_string()),
+            _ => None,
+        };
+        let mut layers = ConfigLayers::discover(&project, None, env).unwrap();
+        layers.push("--max-file-size", json!({"access_policy": {"max_file_size": 7}}));
+        assert_eq!(layers.file_paths().len(), 2);
+
+        let resolved = layers.resolve(Some("ci")).unwrap();
+        let policy = &resolved.config.access_policy;
+        assert_eq!(policy.allowed_paths, vec![PathBuf::from("/srv/project")]);
+        assert!(policy.read_only);
+        assert_eq!(policy.max_file_size, 7);
+        assert_eq!(resolved.config.server.name, "CI");
+
+        let source = |key: &str| resolved.sources.get(key).unwrap().as_str();
+        assert!(source("access_policy.allowed_paths").starts_with("project config"));
+        assert_eq!(source("access_policy.read_only"), "env FILEJACK_READ_ONLY");
+        assert_eq!(source("access_policy.max_file_size"), "--max-file-size");
+        assert_eq!(source("server.name"), "profile ci");
+        assert_eq!(source("performance.fsync"), DEFAULT_SOURCE);
+        assert!(!resolved.sources.keys().any(|k| k.starts_with("profiles")));
+    }
+
+    #[test]
+    fn test_env_only_defaults_match_legacy_behavior() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().to_string_lossy().to_string();
+
+        let no_env = |_: &str| None;
+        let resolved = ConfigLayers::discover(temp_dir.path(), None, no_env).unwrap().resolve(None).unwrap();
+        assert!(resolved.config.access_policy.allow_symlinks);
+        assert!(resolved.config.access_policy.allowed_paths.is_empty());
+
+        let env = |name: &str| (name == "FILEJACK_BASE_PATH").then(|| base.clone());
+        let resolved = ConfigLayers::discover(temp_dir.path(), None, env).unwrap().resolve(None).unwrap();
+        assert!(!resolved.config.access_policy.allow_symlinks);
+        assert_eq!(resolved.config.access_policy.max_file_size, 10 * 1024 * 1024);
+        assert_eq!(resolved.config.access_policy.allowed_paths, vec![temp_dir.path().to_path_buf()]);
+
+        let env = |name: &str| (name == "FILEJACK_READ_ONLY").then(|| "maybe".to_string());
+        assert!(ConfigLayers::discover(temp_dir.path(), None, env).is_err());
+    }
+}
@@ -1,15 +1,105 @@
 pub mod access_control;
+pub mod aliases;
+pub mod archive;
+pub mod audit;
+pub mod catalog;
+pub mod client;
+pub mod client_config;
+pub mod compression;
 pub mod config;
+pub mod content_cache;
 pub mod error;
 pub mod file_ops;
+pub mod follow;
+#[cfg(feature = "image")]
+pub mod images;
+#[cfg(feature = "git")]
+pub mod git;
+#[cfg(feature = "index")]
+pub mod index;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod journal;
+pub mod layers;
+pub mod line_edits;
+pub mod local_socket;
+pub mod locks;
+pub mod logging;
 pub mod mcp;
+pub mod metrics;
+pub mod middleware;
+pub mod mounts;
+pub mod overlay;
+pub mod overview;
+pub mod pack;
+#[cfg(feature = "wasm")]
+pub mod plugins;
 pub mod protocol;
 pub mod rate_limit;
+pub mod reload;
+pub mod schema;
+pub mod self_test;
+pub mod session;
+#[cfg(feature = "sftp")]
+pub mod sftp;
+pub mod shutdown;
+pub mod structured;
+pub mod text_style;
+pub mod tools;
+pub mod transactions;
+pub mod transport;
+pub mod usage;
+pub mod vfs;
+pub mod workspaces;
 
-pub use access_control::AccessPolicy;
-pub use config::{Config, ServerConfig};
-pub use error::{FileJackError, Result};
-pub use file_ops::{DirectoryEntry, FileMetadata, FileReader, FileWriter};
-pub use mcp::McpServer;
-pub use protocol::{JsonRpcRequest, JsonRpcResponse, McpTool, ToolCall};
-pub use rate_limit::RateLimiter;
+pub use access_control::{
+    AccessPolicy, AccessTrace, HiddenFileDetection, RootRedaction, TraceStep, UnicodeNormalization, WriteOperation,
+};
+pub use aliases::Aliases;
+pub use archive::ArchiveFileSystem;
+pub use audit::{AuditEvent, AuditLog, AuditMiddleware, JsonLinesAuditLog};
+pub use catalog::{ToolCatalog, ToolMetadata, ToolText};
+pub use client::McpClient;
+pub use client_config::{ClientKind, LaunchSpec};
+pub use compression::{CompressionFormat, CompressionSummary};
+pub use config::{ClientTools, Config, ConfigFormat, ConfigIssue, GitConfig, IndexConfig, IssueSeverity, LoggingConfig, MetricsConfig, PerformanceConfig, PluginsConfig, ServerConfig, SftpConfig, WorkspacesConfig};
+pub use content_cache::ContentCache;
+pub use error::{FileJackError, Result, Severity};
+pub use file_ops::{CopySummary, DirectoryEntry, FileMetadata, FileReader, FileWriter, RecentFile, UpdateLock};
+pub use follow::FollowInfo;
+#[cfg(feature = "image")]
+pub use images::{ImageInfo, Thumbnail};
+#[cfg(feature = "git")]
+pub use git::GitInspector;
+#[cfg(feature = "index")]
+pub use index::{FileIndex, IndexRefresher};
+#[cfg(feature = "http")]
+pub use http::HttpTransport;
+pub use journal::{Journal, JournalEntry, PathChange};
+pub use layers::{ConfigLayers, ResolvedConfig};
+pub use line_edits::{LineEdit, LineEditSummary};
+pub use local_socket::{LocalSocketListener, LocalSocketTransport};
+pub use locks::{ClaimInfo, LockManager};
+pub use logging::LogFormat;
+pub use mcp::{McpServer, McpServerBuilder};
+pub use metrics::{Metrics, MetricsEndpoint, MetricsSnapshot};
+pub use middleware::Middleware;
+pub use mounts::{MountConfig, MountTable};
+pub use overlay::DirectoryOverlay;
+pub use overview::{ExtensionCount, LargeFile, TopLevelEntry, WorkspaceOverview};
+pub use pack::{Pack, PackedFile, SkippedFile};
+pub use protocol::{CallToolResult, JsonRpcRequest, JsonRpcResponse, McpTool, ToolCall, ToolContent};
+pub use rate_limit::{RateLimitMiddleware, RateLimiter};
+pub use reload::ConfigWatcher;
+pub use session::{ClientInfo, Notifier, Progress, Session};
+pub use text_style::{LineEnding, TextStyle};
+pub use tools::{ToolContext, ToolHandler};
+pub use transactions::StagedCall;
+#[cfg(feature = "sftp")]
+pub use sftp::SftpFileSystem;
+pub use shutdown::ShutdownSignal;
+pub use structured::{EditOperation, StructuredEditSummary, StructuredFormat};
+pub use transport::{ChannelTransport, Framing, StreamTransport, Transport};
+pub use usage::UsageReport;
+pub use vfs::{DirectoryLock, FileSystem, MemoryFileSystem, StdFileSystem};
+pub use workspaces::{WorkspaceSummary, Workspaces};
@@ -0,0 +1,209 @@
+//! Line-number edits to text files.
+//!
+//! `edit_lines` inserts, deletes, and replaces lines by number instead of
+//! having a client rewrite the whole file. Line numbers are 1-based and
+//! refer to the file as it was before the call, so every edit in a call can
+//! be planned from a single `read_lines` result. An edit may also state the
+//! text it expects to find; if any expectation fails, nothing is written.
+
+use crate::error::{FileJackError, Result};
+use crate::text_style::{self, LineEnding, TextStyle, BOM};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// One change to a text file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum LineEdit {
+    /// Insert `content` before line `line`; one past the last line appends
+    InsertAt {
+        line: usize,
+        content: String,
+        /// Current text of line `line`, checked before inserting
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected: Option<String>,
+    },
+    /// Delete lines `start_line` through `end_line`, inclusive
+    DeleteRange {
+        start_line: usize,
+        end_line: usize,
+        /// Current text of the lines, checked before deleting them
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected: Option<String>,
+    },
+    /// Replace lines `start_line` through `end_line`, inclusive, with `content`
+    ReplaceRange {
+        start_line: usize,
+        end_line: usize,
+        content: String,
+        /// Current text of the lines, checked before replacing them
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected: Option<String>,
+    },
+}
+
+/// Result of `edit_lines`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LineEditSummary {
+    pub path: String,
+    pub operations: usize,
+    pub lines_before: usize,
+    pub lines_after: usize,
+    pub bytes_written: u64,
+}
+
+/// The lines an edit replaces, as a half-open range of 0-based indexes
+struct Span<'a> {
+    edit: usize,
+    start: usize,
+    end: usize,
+    content: &'a str,
+}
+
+/// Apply `edits` to `text`, keeping its byte order mark, its dominant line
+/// ending, and whether it ends with a newline. Returns the new text and its
+/// line count.
+pub fn apply_line_edits(text: &str, edits: &[LineEdit]) -> Result<(String, usize)> {
+    let style = TextStyle::detect(text);
+    let text = text_style::strip_bom(text);
+    let lines: Vec<&str> = text.lines().collect();
+    let mut spans = edits.iter()
+        .enumerate()
+        .map(|(index, edit)| span(&lines, index, edit))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Stable, so inserts at the same line keep the order they were given in
+    spans.sort_by_key(|span| (span.start, span.end));
+    for pair in spans.windows(2) {
+        if pair[1].start < pair[0].end {
+            return Err(FileJackError::InvalidParameters(format!(
+                "Edits {} and {} overlap; each line may be changed by only one edit",
+                pair[0].edit + 1,
+                pair[1].edit + 1
+            )));
+        }
+    }
+
+    let mut edited = Vec::with_capacity(lines.len());
+    let mut next = 0;
+    for span in &spans {
+        edited.extend_from_slice(&lines[next..span.start]);
+        edited.extend(span.content.lines());
+        next = span.end;
+    }
+    edited.extend_from_slice(&lines[next..]);
+
+    let newline = style.line_ending.unwrap_or(LineEnding::Lf).as_str();
+    let mut out = if style.bom { BOM.to_string() } else { String::new() };
+    out.push_str(&edited.join(newline));
+    if !edited.is_empty() && (text.is_empty() || text.ends_with('\n')) {
+        out.push_str(newline);
+    }
+    Ok((out, edited.len()))
+}
+
+fn span<'a>(lines: &[&str], index: usize, edit: &'a LineEdit) -> Result<Span<'a>> {
+    let invalid = |reason: String| {
+        FileJackError::InvalidParameters(format!("Edit {} ({}): {}", index + 1, op_name(edit), reason))
+    };
+    let (start, end, content, expected) = match edit {
+        LineEdit::InsertAt { line, content, expected } => {
+            if *line == 0 || *line > lines.len() + 1 {
+                return Err(invalid(format!("line {} is outside 1-{}", line, lines.len() + 1)));
+            }
+            (line - 1, line - 1, content.as_str(), expected)
+        }
+        LineEdit::DeleteRange { start_line, end_line, expected } => {
+            let (start, end) = range(lines.len(), *start_line, *end_line).map_err(invalid)?;
+            (start, end, "", expected)
+        }
+        LineEdit::ReplaceRange { start_line, end_line, content, expected } => {
+            let (start, end) = range(lines.len(), *start_line, *end_line).map_err(invalid)?;
+            (start, end, content.as_str(), expected)
+        }
+    };
+
+    if let Some(expected) = expected {
+        let found = match edit {
+            LineEdit::InsertAt { .. } => lines.get(start).copied().unwrap_or_default().to_string(),
+            _ => lines[start..end].join("\n"),
+        };
+        let expected = expected.replace("\r\n", "\n");
+        if expected.strip_suffix('\n').unwrap_or(&expected) != found {
+            return Err(invalid(format!("expected {:?} but found {:?}", expected, found)));
+        }
+    }
+    Ok(Span { edit: index, start, end, content })
+}
+
+/// Check an inclusive 1-based range against a file of `len` lines
+fn range(len: usize, start_line: usize, end_line: usize) -> std::result::Result<(usize, usize), String> {
+    if start_line == 0 || start_line > end_line || end_line > len {
+        return Err(format!("lines {}-{} are outside 1-{}", start_line, end_line, len));
+    }
+    Ok((start_line - 1, end_line))
+}
+
+fn op_name(edit: &LineEdit) -> &'static str {
+    match edit {
+        LineEdit::InsertAt { .. } => "insert_at",
+        LineEdit::DeleteRange { .. } => "delete_range",
+        LineEdit::ReplaceRange { .. } => "replace_range",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn edit(text: &str, edits: serde_json::Value) -> Result<String> {
+        let edits: Vec<LineEdit> = serde_json::from_value(edits).unwrap();
+        apply_line_edits(text, &edits).map(|(text, _)| text)
+    }
+
+    #[test]
+    fn test_edits_use_original_line_numbers() {
+        let text = "one\ntwo\nthree\nfour\nfive\n";
+        let (edited, lines) = apply_line_edits(text, &serde_json::from_value::<Vec<LineEdit>>(json!([
+            {"op": "replace_range", "start_line": 4, "end_line": 4, "content": "FOUR\nFOUR AND A HALF"},
+            {"op": "insert_at", "line": 1, "content": "zero"},
+            {"op": "delete_range", "start_line": 2, "end_line": 3, "expected": "two\nthree\n"},
+            {"op": "insert_at", "line": 6, "content": "six"}
+        ])).unwrap()).unwrap();
+        assert_eq!(edited, "zero\none\nFOUR\nFOUR AND A HALF\nfive\nsix\n");
+        assert_eq!(lines, 6);
+    }
+
+    #[test]
+    fn test_line_endings_are_kept() {
+        assert_eq!(edit("a\r\nb\r\n", json!([{"op": "insert_at", "line": 2, "content": "x"}])).unwrap(), "a\r\nx\r\nb\r\n");
+        assert_eq!(edit("a\nb", json!([{"op": "insert_at", "line": 3, "content": "c"}])).unwrap(), "a\nb\nc");
+        assert_eq!(edit("", json!([{"op": "insert_at", "line": 1, "content": "first"}])).unwrap(), "first\n");
+        assert_eq!(edit("only\n", json!([{"op": "delete_range", "start_line": 1, "end_line": 1}])).unwrap(), "");
+        assert_eq!(
+            edit("\u{feff}a\r\nb\r\n", json!([{"op": "replace_range", "start_line": 1, "end_line": 1, "content": "A", "expected": "a"}])).unwrap(),
+            "\u{feff}A\r\nb\r\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_edits_are_refused() {
+        let text = "a\nb\nc\n";
+        let cases = [
+            (json!([{"op": "insert_at", "line": 5, "content": "x"}]), "Edit 1 (insert_at): line 5 is outside 1-4"),
+            (json!([{"op": "delete_range", "start_line": 3, "end_line": 2}]), "lines 3-2 are outside 1-3"),
+            (json!([{"op": "replace_range", "start_line": 0, "end_line": 1, "content": ""}]), "lines 0-1"),
+            (json!([{"op": "replace_range", "start_line": 2, "end_line": 2, "content": "x", "expected": "B"}]), "expected \"B\" but found \"b\""),
+            (json!([{"op": "insert_at", "line": 4, "content": "x", "expected": "c"}]), "but found \"\""),
+            (
+                json!([{"op": "delete_range", "start_line": 1, "end_line": 2}, {"op": "insert_at", "line": 2, "content": "x"}]),
+                "Edits 1 and 2 overlap",
+            ),
+        ];
+        for (edits, message) in cases {
+            let err = edit(text, edits).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+    }
+}
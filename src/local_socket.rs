@@ -0,0 +1,444 @@
+use crate::error::Result;
+use crate::mcp::McpServer;
+use crate::shutdown::ShutdownSignal;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// How often the accept loop checks for a shutdown signal while idle
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Prefix every Windows named pipe path must start with
+#[cfg(windows)]
+pub const PIPE_PREFIX: &str = r"\\.\pipe\";
+
+/// Where the socket is created when `--socket` is not given: `filejack.sock`
+/// in `$XDG_RUNTIME_DIR` (or the temp directory) on Unix, and the
+/// `\\.\pipe\filejack` named pipe on Windows
+pub fn default_socket_path() -> PathBuf {
+    #[cfg(unix)]
+    {
+        std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir)
+            .join("filejack.sock")
+    }
+    #[cfg(windows)]
+    {
+        PathBuf::from(format!("{}filejack", PIPE_PREFIX))
+    }
+}
+
+/// A bound local socket: a Unix domain socket or a Windows named pipe.
+///
+/// On Unix the socket file is created readable and writable by its owner
+/// only, and connections from other users are refused even if the file
+/// permissions are loosened later; root may always connect. The socket file
+/// is removed when the listener is dropped. Named pipes reject remote
+/// clients and keep the default pipe security descriptor.
+pub struct LocalSocketListener {
+    path: PathBuf,
+    #[cfg(unix)]
+    listener: std::os::unix::net::UnixListener,
+}
+
+impl LocalSocketListener {
+    /// Create the socket at `path`, replacing a stale socket left behind by
+    /// a server that did not shut down cleanly
+    #[cfg(unix)]
+    pub fn bind(path: impl AsRef<Path>) -> Result<Self> {
+        use crate::error::FileJackError;
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+        use std::os::unix::net::{UnixListener, UnixStream};
+
+        let path = path.as_ref().to_path_buf();
+        if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+            if !metadata.file_type().is_socket() {
+                return Err(FileJackError::InvalidParameters(format!(
+                    "{} exists and is not a socket", path.display()
+                )));
+            }
+            if UnixStream::connect(&path).is_ok() {
+                return Err(FileJackError::InvalidParameters(format!(
+                    "Another server is already listening on {}", path.display()
+                )));
+            }
+            std::fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        // Clients that connect before this are still checked by peer UID
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { path, listener })
+    }
+
+    /// Claim the named pipe at `path`; a bare name is placed under `\\.\pipe\`
+    #[cfg(windows)]
+    pub fn bind(path: impl AsRef<Path>) -> Result<Self> {
+        let name = path.as_ref().to_string_lossy();
+        let path = if name.starts_with(PIPE_PREFIX) {
+            PathBuf::from(name.as_ref())
+        } else {
+            PathBuf::from(format!("{}{}", PIPE_PREFIX, name))
+        };
+        Ok(Self { path })
+    }
+
+    /// Path of the socket file or named pipe
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(unix)]
+impl Drop for LocalSocketListener {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            debug!("Cannot remove socket {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(unix)]
+type ClientSender = std::sync::mpsc::Sender<String>;
+#[cfg(windows)]
+type ClientSender = tokio::sync::mpsc::UnboundedSender<String>;
+
+/// Newline-delimited JSON-RPC over a local socket, for hosts that would
+/// rather connect to a running server than manage a child process's stdio.
+///
+/// Every connection is an independent client with its own [`Session`];
+/// server-initiated messages such as `notifications/tools/list_changed` are
+/// sent to all of them.
+///
+/// [`Session`]: crate::session::Session
+#[derive(Clone)]
+pub struct LocalSocketTransport {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    server: Arc<McpServer>,
+    clients: Mutex<HashMap<u64, ClientSender>>,
+    next_client: AtomicU64,
+}
+
+impl LocalSocketTransport {
+    pub fn new(server: Arc<McpServer>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                server,
+                clients: Mutex::new(HashMap::new()),
+                next_client: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Send a server-initiated message to every connected client
+    pub fn notify(&self, message: String) {
+        self.clients().retain(|_, client| client.send(message.clone()).is_ok());
+    }
+
+    fn clients(&self) -> std::sync::MutexGuard<'_, HashMap<u64, ClientSender>> {
+        self.inner.clients.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Register a connected client for notifications; returns its ID
+    fn add_client(&self, sender: ClientSender) -> u64 {
+        let id = self.inner.next_client.fetch_add(1, Ordering::Relaxed);
+        self.clients().insert(id, sender);
+        id
+    }
+
+    /// Accept connections until `shutdown` is requested. Connections already
+    /// open are left to finish on their own threads.
+    #[cfg(unix)]
+    pub fn serve(&self, listener: &LocalSocketListener, shutdown: &ShutdownSignal) -> Result<()> {
+        info!("Listening on {}", listener.path().display());
+        while !shutdown.is_requested() {
+            let stream = match listener.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(ACCEPT_POLL_INTERVAL);
+                    continue;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            match unix::peer_allowed(&stream) {
+                Ok(true) => {}
+                Ok(false) => {
+                    warn!("Refused a socket connection from another user");
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Cannot identify socket peer, refusing it: {}", e);
+                    continue;
+                }
+            }
+
+            let transport = self.clone();
+            let spawned = std::thread::Builder::new()
+                .name("filejack-socket-client".to_string())
+                .spawn(move || {
+                    if let Err(e) = transport.handle_connection(stream) {
+                        debug!("Socket client failed: {}", e);
+                    }
+                });
+            if let Err(e) = spawned {
+                warn!("Cannot start a socket client thread: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn handle_connection(&self, stream: std::os::unix::net::UnixStream) -> Result<()> {
+        use std::io::{BufRead, BufReader, Write};
+
+        stream.set_nonblocking(false)?;
+        let mut writer = stream.try_clone()?;
+        let (tx, rx) = std::sync::mpsc::channel::<String>();
+        let id = self.add_client(tx.clone());
+        let session = self.inner.server.open_session(format!("socket-{}", id));
+        let notifications = tx.clone();
+        session.set_notifier(move |message| {
+            let _ = notifications.send(message);
+        });
+
+        // Responses and notifications share one writer so lines never interleave
+        std::thread::Builder::new()
+            .name("filejack-socket-writer".to_string())
+            .spawn(move || {
+                for message in rx {
+                    if writeln!(writer, "{}", message).and_then(|_| writer.flush()).is_err() {
+                        break;
+                    }
+                }
+            })?;
+
+        let mut connection = unix::Connection {
+            requests: BufReader::new(stream).lines(),
+            replies: tx,
+        };
+        let served = crate::transport::serve_session(&mut connection, &self.inner.server, &session);
+        // Dropping the last sender stops the writer thread
+        drop(connection);
+        self.clients().remove(&id);
+        self.inner.server.report_usage(&session);
+        debug!(session = session.id(), "Socket client disconnected");
+        served
+    }
+
+    /// Accept connections until `shutdown` is requested
+    #[cfg(windows)]
+    pub fn serve(&self, listener: &LocalSocketListener, shutdown: &ShutdownSignal) -> Result<()> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        let name = listener.path().as_os_str().to_owned();
+        info!("Listening on {}", listener.path().display());
+        runtime.block_on(async {
+            let mut pipe = ServerOptions::new()
+                .first_pipe_instance(true)
+                .reject_remote_clients(true)
+                .create(&name)?;
+            while !shutdown.is_requested() {
+                match tokio::time::timeout(ACCEPT_POLL_INTERVAL, pipe.connect()).await {
+                    Err(_) => continue,
+                    Ok(connected) => connected?,
+                }
+                // Open the next instance before handing this one off so
+                // clients never find the pipe missing
+                let connected = std::mem::replace(
+                    &mut pipe,
+                    ServerOptions::new().reject_remote_clients(true).create(&name)?,
+                );
+                tokio::spawn(self.clone().handle_connection(connected));
+            }
+            Ok(())
+        })
+    }
+
+    #[cfg(windows)]
+    async fn handle_connection(self, pipe: tokio::net::windows::named_pipe::NamedPipeServer) {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let (reader, mut writer) = tokio::io::split(pipe);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let id = self.add_client(tx.clone());
+        let session = self.inner.server.open_session(format!("pipe-{}", id));
+        let notifications = tx.clone();
+        session.set_notifier(move |message| {
+            let _ = notifications.send(message);
+        });
+
+        tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                let line = format!("{}\n", message);
+                if writer.write_all(line.as_bytes()).await.is_err() || writer.flush().await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let server = Arc::clone(&self.inner.server);
+            let session = Arc::clone(&session);
+            let response = match tokio::task::spawn_blocking(move || server.process_session_request(&session, &line)).await {
+                Ok(response) => response,
+                Err(e) => {
+                    warn!("Pipe request handler failed: {}", e);
+                    break;
+                }
+            };
+            if !response.is_empty() && tx.send(response).is_err() {
+                break;
+            }
+        }
+        self.clients().remove(&id);
+        self.inner.server.report_usage(&session);
+        debug!(session = session.id(), "Pipe client disconnected");
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use crate::error::{FileJackError, Result};
+    use crate::transport::Transport;
+    use std::io::{BufReader, Lines};
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::UnixStream;
+    use std::sync::mpsc::Sender;
+
+    /// One socket client. Replies go through the writer thread it shares
+    /// with notifications.
+    pub(super) struct Connection {
+        pub(super) requests: Lines<BufReader<UnixStream>>,
+        pub(super) replies: Sender<String>,
+    }
+
+    impl Transport for Connection {
+        fn read_message(&mut self) -> Result<Option<String>> {
+            Ok(self.requests.next().transpose()?)
+        }
+
+        fn write_message(&mut self, message: &str) -> Result<()> {
+            // Fails only once the writer has given up on the client
+            self.replies.send(message.to_string()).map_err(|_| {
+                FileJackError::Io(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Socket client went away"))
+            })
+        }
+    }
+
+    /// Whether the connecting process runs as this user or as root
+    pub(super) fn peer_allowed(stream: &UnixStream) -> std::io::Result<bool> {
+        let ours = unsafe { libc::geteuid() };
+        Ok(match peer_uid(stream)? {
+            Some(uid) => uid == ours || uid == 0,
+            // No way to ask on this platform; rely on the file permissions
+            None => true,
+        })
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn peer_uid(stream: &UnixStream) -> std::io::Result<Option<libc::uid_t>> {
+        let mut cred = libc::ucred { pid: 0, uid: 0, gid: 0 };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        let rc = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut cred as *mut libc::ucred as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if rc == 0 { Ok(Some(cred.uid)) } else { Err(std::io::Error::last_os_error()) }
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "dragonfly"))]
+    fn peer_uid(stream: &UnixStream) -> std::io::Result<Option<libc::uid_t>> {
+        let mut uid = 0;
+        let mut gid = 0;
+        let rc = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+        if rc == 0 { Ok(Some(uid)) } else { Err(std::io::Error::last_os_error()) }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "dragonfly")))]
+    fn peer_uid(_stream: &UnixStream) -> std::io::Result<Option<libc::uid_t>> {
+        Ok(None)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::access_control::AccessPolicy;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::os::unix::net::UnixStream;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_socket_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("filejack.sock");
+        let listener = LocalSocketListener::bind(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let transport = LocalSocketTransport::new(Arc::new(McpServer::new(AccessPolicy::permissive())));
+        let shutdown = Arc::new(ShutdownSignal::register().unwrap());
+        let serving = {
+            let transport = transport.clone();
+            let shutdown = Arc::clone(&shutdown);
+            std::thread::spawn(move || {
+                transport.serve(&listener, &shutdown).unwrap();
+            })
+        };
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        let mut responses = BufReader::new(client.try_clone().unwrap()).lines();
+        writeln!(client, r#"{{"jsonrpc":"2.0","id":1,"method":"tools/list"}}"#).unwrap();
+        assert!(responses.next().unwrap().unwrap().contains("read_file"));
+
+        // Notifications reach clients once they are registered
+        writeln!(client, r#"{{"jsonrpc":"2.0","id":2,"method":"tools/list"}}"#).unwrap();
+        responses.next().unwrap().unwrap();
+        transport.notify(McpServer::tools_list_changed_notification());
+        assert!(responses.next().unwrap().unwrap().contains("list_changed"));
+
+        shutdown.request();
+        serving.join().unwrap();
+        assert!(!path.exists(), "socket file should be removed on shutdown");
+    }
+
+    #[test]
+    fn test_stale_socket_is_replaced() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("stale.sock");
+        let stale = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        drop(stale);
+        assert!(std::fs::symlink_metadata(&path).unwrap().file_type().is_socket());
+        let listener = LocalSocketListener::bind(&path).unwrap();
+
+        // A live server is never displaced
+        assert!(LocalSocketListener::bind(&path).is_err());
+        drop(listener);
+
+        let regular = dir.path().join("notes.txt");
+        std::fs::write(&regular, "keep me").unwrap();
+        assert!(LocalSocketListener::bind(&regular).is_err());
+        assert_eq!(std::fs::read_to_string(&regular).unwrap(), "keep me");
+    }
+}
@@ -0,0 +1,315 @@
+use crate::error::{FileJackError, Result};
+use crate::vfs;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Lease length used when a lock request does not specify one
+pub const DEFAULT_LEASE: Duration = Duration::from_secs(60);
+
+/// Longest lease a client may request
+pub const MAX_LEASE: Duration = Duration::from_secs(3600);
+
+/// Information about a held lock, returned to the client
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LockInfo {
+    pub path: String,
+    pub token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    pub expires_in_seconds: u64,
+}
+
+/// A path held by a session, returned by `claim_path`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ClaimInfo {
+    pub path: String,
+    pub expires_in_seconds: u64,
+}
+
+#[derive(Debug)]
+struct Claim {
+    session: String,
+    expires_at: Instant,
+}
+
+#[derive(Debug)]
+struct Lease {
+    token: String,
+    owner: Option<String>,
+    expires_at: Instant,
+}
+
+/// Lease-based file locks shared by all clients of a server.
+///
+/// A lock is identified by a token handed out by [`LockManager::acquire`].
+/// While a lease is live, modifications to the path must present that token.
+/// Leases expire on their own so a crashed client cannot block a file forever.
+///
+/// Sessions can also claim a file or directory with [`LockManager::claim`].
+/// A claim needs no token: it belongs to the session, and only that session
+/// may modify the path, or anything beneath a claimed directory, until the
+/// claim is released or expires.
+#[derive(Debug, Default)]
+pub struct LockManager {
+    leases: Mutex<HashMap<PathBuf, Lease>>,
+    claims: Mutex<HashMap<PathBuf, Claim>>,
+    counter: AtomicU64,
+}
+
+impl LockManager {
+    /// Create an empty lock manager
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Acquire (or, when `token` matches the current holder, renew) a lock on `path`
+    pub fn acquire(
+        &self,
+        path: &Path,
+        owner: Option<String>,
+        lease: Option<Duration>,
+        token: Option<&str>,
+    ) -> Result<LockInfo> {
+        let key = lock_key(path);
+        let lease = lease.unwrap_or(DEFAULT_LEASE).min(MAX_LEASE);
+        let mut leases = self.lock();
+        let now = Instant::now();
+
+        if let Some(existing) = leases.get(&key) {
+            if existing.expires_at > now && token != Some(existing.token.as_str()) {
+                return Err(FileJackError::PermissionDenied(format!(
+                    "{} is locked{}",
+                    path.display(),
+                    existing.owner.as_ref().map(|o| format!(" by {}", o)).unwrap_or_default()
+                ).into()));
+            }
+        }
+
+        let token = match token {
+            Some(token) if leases.get(&key).is_some_and(|l| l.token == token) => token.to_string(),
+            _ => self.new_token(),
+        };
+        leases.insert(key.clone(), Lease {
+            token: token.clone(),
+            owner: owner.clone(),
+            expires_at: now + lease,
+        });
+
+        Ok(LockInfo {
+            path: key.display().to_string(),
+            token,
+            owner,
+            expires_in_seconds: lease.as_secs(),
+        })
+    }
+
+    /// Release a lock; the token must match the current holder
+    pub fn release(&self, path: &Path, token: &str) -> Result<()> {
+        let key = lock_key(path);
+        let mut leases = self.lock();
+
+        match leases.get(&key) {
+            Some(lease) if lease.token == token => {
+                leases.remove(&key);
+                Ok(())
+            }
+            Some(lease) if lease.expires_at > Instant::now() => Err(FileJackError::PermissionDenied(
+                format!("Lock token does not match the holder of {}", path.display()).into()
+            )),
+            _ => Err(FileJackError::InvalidParameters(
+                format!("{} is not locked", path.display())
+            )),
+        }
+    }
+
+    /// Check that `path` may be modified by a caller presenting `token`
+    pub fn check(&self, path: &Path, token: Option<&str>) -> Result<()> {
+        let key = lock_key(path);
+        let mut leases = self.lock();
+
+        match leases.get(&key) {
+            Some(lease) if lease.expires_at <= Instant::now() => {
+                leases.remove(&key);
+                Ok(())
+            }
+            Some(lease) if token != Some(lease.token.as_str()) => Err(FileJackError::PermissionDenied(
+                format!(
+                    "{} is locked{}; pass its lock_token to modify it",
+                    path.display(),
+                    lease.owner.as_ref().map(|o| format!(" by {}", o)).unwrap_or_default()
+                ).into()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Claim (or, when `session` already holds it, renew) `path` for
+    /// `session`. Fails if another session holds a claim on the path, on a
+    /// directory above it, or on anything beneath it.
+    pub fn claim(&self, path: &Path, session: &str, lease: Option<Duration>) -> Result<ClaimInfo> {
+        let key = lock_key(path);
+        let lease = lease.unwrap_or(DEFAULT_LEASE).min(MAX_LEASE);
+        let mut claims = self.claims();
+        let now = Instant::now();
+        claims.retain(|_, claim| claim.expires_at > now);
+
+        let overlapping = claims.iter()
+            .find(|(claimed, claim)| claim.session != session && (key.starts_with(claimed) || claimed.starts_with(&key)));
+        if let Some((claimed, claim)) = overlapping {
+            return Err(claimed_by_other(claimed, claim, now));
+        }
+        claims.insert(key.clone(), Claim { session: session.to_string(), expires_at: now + lease });
+        Ok(ClaimInfo { path: key.display().to_string(), expires_in_seconds: lease.as_secs() })
+    }
+
+    /// Release `session`'s claim on `path`
+    pub fn release_claim(&self, path: &Path, session: &str) -> Result<()> {
+        let key = lock_key(path);
+        let mut claims = self.claims();
+        let now = Instant::now();
+        match claims.get(&key) {
+            Some(claim) if claim.session == session => {
+                claims.remove(&key);
+                Ok(())
+            }
+            Some(claim) if claim.expires_at > now => Err(claimed_by_other(&key, claim, now)),
+            _ => Err(FileJackError::InvalidParameters(format!("{} is not claimed", path.display()))),
+        }
+    }
+
+    /// Check that `session` may modify `path`: neither it nor a directory
+    /// above it is claimed by another session
+    pub fn check_claim(&self, path: &Path, session: &str) -> Result<()> {
+        let key = lock_key(path);
+        let claims = self.claims();
+        let now = Instant::now();
+        let other = key.ancestors()
+            .filter_map(|ancestor| claims.get_key_value(ancestor))
+            .find(|(_, claim)| claim.session != session && claim.expires_at > now);
+        match other {
+            Some((claimed, claim)) => Err(claimed_by_other(claimed, claim, now)),
+            None => Ok(()),
+        }
+    }
+
+    fn new_token(&self) -> String {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+        format!("{:x}-{:x}-{:x}", std::process::id(), nanos, count)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, Lease>> {
+        self.leases.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn claims(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, Claim>> {
+        self.claims.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn claimed_by_other(path: &Path, claim: &Claim, now: Instant) -> FileJackError {
+    FileJackError::PermissionDenied(format!(
+        "{} is claimed by other session {} for another {}s",
+        path.display(),
+        claim.session,
+        claim.expires_at.saturating_duration_since(now).as_secs()
+    ).into())
+}
+
+/// Normalize a path for use as a lock key. Files that do not exist yet are
+/// keyed by their canonical parent directory plus file name.
+fn lock_key(path: &Path) -> PathBuf {
+    if let Ok(canonical) = vfs::canonicalize(path) {
+        return canonical;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => vfs::canonicalize(parent)
+            .map(|p| p.join(name))
+            .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lock_blocks_other_holders() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("shared.txt");
+        let locks = LockManager::new();
+
+        let info = locks.acquire(&path, Some("agent-a".to_string()), None, None).unwrap();
+        assert_eq!(info.expires_in_seconds, DEFAULT_LEASE.as_secs());
+
+        assert!(locks.acquire(&path, None, None, None).is_err());
+        assert!(locks.check(&path, None).is_err());
+        assert!(locks.check(&path, Some("wrong")).is_err());
+        assert!(locks.check(&path, Some(&info.token)).is_ok());
+
+        assert!(locks.release(&path, "wrong").is_err());
+        locks.release(&path, &info.token).unwrap();
+        assert!(locks.check(&path, None).is_ok());
+    }
+
+    #[test]
+    fn test_lock_renewal_keeps_token() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("renew.txt");
+        let locks = LockManager::new();
+
+        let first = locks.acquire(&path, None, None, None).unwrap();
+        let renewed = locks.acquire(&path, None, Some(Duration::from_secs(5)), Some(&first.token)).unwrap();
+        assert_eq!(first.token, renewed.token);
+        assert_eq!(renewed.expires_in_seconds, 5);
+    }
+
+    #[test]
+    fn test_lock_lease_expires() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("expire.txt");
+        let locks = LockManager::new();
+
+        locks.acquire(&path, None, Some(Duration::from_millis(20)), None).unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+
+        assert!(locks.check(&path, None).is_ok());
+        assert!(locks.acquire(&path, None, None, None).is_ok());
+    }
+
+    #[test]
+    fn test_claims_belong_to_a_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("src");
+        std::fs::create_dir(&dir).unwrap();
+        let file = dir.join("main.rs");
+        let locks = LockManager::new();
+
+        locks.claim(&dir, "a", None).unwrap();
+        assert!(locks.check_claim(&file, "a").is_ok());
+        let err = locks.check_claim(&file, "b").unwrap_err();
+        assert!(err.to_string().contains("claimed by other session a"), "{}", err);
+        assert!(locks.claim(&file, "b", None).is_err());
+        assert!(locks.claim(temp_dir.path(), "b", None).is_err());
+        assert!(locks.release_claim(&dir, "b").is_err());
+        assert!(locks.check_claim(&temp_dir.path().join("other.txt"), "b").is_ok());
+
+        // Renewing keeps the claim with the new lease
+        assert_eq!(locks.claim(&dir, "a", Some(Duration::from_secs(5))).unwrap().expires_in_seconds, 5);
+        locks.release_claim(&dir, "a").unwrap();
+        assert!(locks.release_claim(&dir, "a").is_err());
+        assert!(locks.claim(&file, "b", Some(Duration::from_millis(20))).is_ok());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(locks.check_claim(&file, "a").is_ok());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::config::LoggingConfig;
+use crate::error::{FileJackError, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::IsTerminal;
+use tracing_subscriber::EnvFilter;
+
+/// Keys whose string values carry file data and are never logged
+const REDACTED_KEYS: &[&str] = &["content", "text", "blob"];
+
+/// How log records are written to stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Pretty,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+/// Install the global tracing subscriber.
+///
+/// Records go to stderr, since stdout carries the JSON-RPC stream. `RUST_LOG`
+/// overrides the configured level when it is set.
+pub fn init(config: &LoggingConfig) -> Result<()> {
+    let (source, directives) = match std::env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(directives) if !directives.is_empty() => (EnvFilter::DEFAULT_ENV, directives),
+        _ => ("logging.level", config.level.clone()),
+    };
+    let filter = EnvFilter::try_new(&directives).map_err(|e| {
+        FileJackError::ConfigError(format!("Invalid {} '{}': {}", source, directives, e))
+    })?;
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false)
+        .with_line_number(true);
+    let installed = match config.format {
+        LogFormat::Pretty => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+    installed.map_err(|e| FileJackError::ConfigError(format!("Cannot initialize logging: {}", e)))
+}
+
+/// Copy of `value` with file data replaced by its length, safe to log
+pub fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(map.iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(s) if REDACTED_KEYS.contains(&key.as_str()) => {
+                        Value::String(format!("<redacted {} bytes>", s.len()))
+                    }
+                    other => redact(other),
+                };
+                (key.clone(), value)
+            })
+            .collect()),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Redact a raw JSON-RPC message for logging; unparseable input is
+/// summarized by size only
+pub fn redact_message(message: &str) -> String {
+    match serde_json::from_str::<Value>(message) {
+        Ok(value) => redact(&value).to_string(),
+        Err(_) => format!("<{} bytes of invalid JSON>", message.len()),
+    }
+}
+
+/// Log text for a JSON-RPC message: redacted, or only its size when
+/// bodies are not logged
+pub fn loggable_message(message: &str, log_bodies: bool) -> String {
+    if log_bodies {
+        redact_message(message)
+    } else {
+        format!("<{} bytes>", message.len())
+    }
+}
+
+/// Log text for tool arguments: redacted, or withheld entirely when bodies
+/// are not logged
+pub fn loggable_value(value: &Value, log_bodies: bool) -> String {
+    if log_bodies {
+        redact(value).to_string()
+    } else {
+        "<withheld>".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact_file_data() {
+        let request = json!({
+            "method": "tools/call",
+            "params": {"name": "write_file", "arguments": {"path": "a.txt", "content": "secret"}}
+        });
+        let redacted = redact(&request);
+        assert_eq!(redacted["params"]["arguments"]["content"], "<redacted 6 bytes>");
+        assert_eq!(redacted["params"]["arguments"]["path"], "a.txt");
+
+        let response = json!({"result": {"content": [{"type": "text", "text": "hunter2"}]}});
+        let redacted = redact(&response);
+        assert_eq!(redacted["result"]["content"][0]["text"], "<redacted 7 bytes>");
+        assert_eq!(redacted["result"]["content"][0]["type"], "text");
+
+        assert!(!redact_message(&response.to_string()).contains("hunter2"));
+        assert_eq!(redact_message("{\"content\": \"x"), "<14 bytes of invalid JSON>");
+    }
+
+    #[test]
+    fn test_withheld_bodies() {
+        let arguments = json!({"path": "/home/user/diary.txt"});
+        assert!(loggable_value(&arguments, true).contains("diary.txt"));
+        assert_eq!(loggable_value(&arguments, false), "<withheld>");
+        assert_eq!(loggable_message(&arguments.to_string(), false), "<31 bytes>");
+    }
+
+    #[test]
+    fn test_log_format_names() {
+        assert_eq!(serde_json::from_value::<LogFormat>(json!("json")).unwrap(), LogFormat::Json);
+        assert_eq!(serde_json::to_value(LogFormat::Pretty).unwrap(), json!("pretty"));
+    }
+}
@@ -1,165 +1,1028 @@
-use filejack::{AccessPolicy, Config, McpServer};
+#[cfg(feature = "http")]
+use filejack::HttpTransport;
+use filejack::{ClientKind, Config, ConfigFormat, ConfigLayers, ConfigWatcher, LaunchSpec, LocalSocketListener, LocalSocketTransport, LoggingConfig, McpServer, MetricsEndpoint, ResolvedConfig, Severity, ShutdownSignal};
+use filejack::config::PROFILE_ENV_VAR;
+use filejack::reload::DEFAULT_POLL_INTERVAL;
+use filejack::self_test::{self, CheckOutcome};
+use filejack::transport::{self, Framing};
+use filejack::vfs;
 use serde_json::json;
-use std::io::{self, BufRead, Write};
+use std::io::{self, Write};
 use std::path::PathBuf;
-use tracing::{debug, error, info, warn};
-
-fn main() {
-    // Initialize tracing subscriber
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive(tracing::Level::INFO.into())
-        )
-        .with_target(false)
-        .with_thread_ids(false)
-        .with_line_number(true)
-        .init();
+use std::process::ExitCode;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+const USAGE: &str = "\
+Usage: filejack [OPTIONS] [COMMAND]
+
+Options:
+  --config PATH                  Use this project config file (or set FILEJACK_CONFIG)
+  --profile NAME                 Apply a profile from the config (or set FILEJACK_PROFILE)
+  --read-only                    Reject all write operations, whatever the config says
+  --transport stdio|http|socket  Serve over stdin/stdout (default), streamable HTTP, or a
+                                 Unix socket / named pipe
+  --host ADDR                    Address the HTTP transport binds (default: 127.0.0.1)
+  --port PORT                    Port the HTTP transport listens on (default: 8090)
+  --framing auto|newline|content-length
+                                 How stdio messages are delimited (default: detect from the
+                                 first message)
+  --socket PATH                  Socket path or pipe name (default: $XDG_RUNTIME_DIR/filejack.sock,
+                                 or \\\\.\\pipe\\filejack on Windows)
+
+Commands:
+  (none)                         Serve MCP requests over stdin/stdout
+  init [PATH] [--force]          Write a commented starter config (default: filejack.toml)
+  validate-config [PATH]         Check a config file and exit nonzero on problems
+  show-config                    Print the effective configuration and each value's source
+  print-client-config [CLIENT]   Print MCP client setup for claude, vscode, cursor, or generic
+  call TOOL [--arg KEY=VALUE]... [--arg-json KEY=JSON]...
+                                 Run one tool call with the configured policy, print its
+                                 result as JSON, and exit 0, or 1 if the call failed
+  check-access PATH [--write]    Print each policy rule's decision on reading (or writing)
+                                 PATH, and exit 0 if allowed or 1 if denied
+  self-test                      Call every tool in a temporary directory and report which
+                                 work, exit 0 if all do
+  --health-check                 Load the config, check allowed paths are readable, exit 0/1
+  help                           Show this message
+  version                        Show the version
+
+Exit status of the server:
+  0   input ended or a shutdown signal arrived
+  2   invalid command line
+  74  the connection to the client failed (restarting may help)
+  78  the configuration is unusable (restarting will not help)";
+
+/// How often the request loop checks for a shutdown signal while idle
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Framing of the stdio stream, fixed by `--framing` or by the first message
+static STDIO_FRAMING: OnceLock<Framing> = OnceLock::new();
+
+/// Address the HTTP transport binds when `--host` is not given
+#[cfg(feature = "http")]
+const DEFAULT_HTTP_HOST: &str = "127.0.0.1";
+
+/// Why the server stopped serving
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stopped {
+    EndOfInput,
+    ShutdownSignal,
+    InputError,
+    OutputClosed,
+    TransportError,
+}
+
+impl Stopped {
+    fn reason(self) -> &'static str {
+        match self {
+            Stopped::EndOfInput => "end of input",
+            Stopped::ShutdownSignal => "shutdown signal received",
+            Stopped::InputError => "input error",
+            Stopped::OutputClosed => "output closed",
+            Stopped::TransportError => "transport error",
+        }
+    }
+
+    fn exit_code(self) -> ExitCode {
+        match self {
+            Stopped::EndOfInput | Stopped::ShutdownSignal => ExitCode::SUCCESS,
+            Stopped::InputError | Stopped::OutputClosed | Stopped::TransportError => exit_code(Severity::Transport),
+        }
+    }
+}
+
+fn exit_code(severity: Severity) -> ExitCode {
+    ExitCode::from(severity.exit_code())
+}
+
+/// How the server talks to its client
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Transport {
+    #[default]
+    Stdio,
+    #[cfg(feature = "http")]
+    Http,
+    Socket,
+}
+
+impl std::str::FromStr for Transport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "stdio" => Ok(Transport::Stdio),
+            "socket" => Ok(Transport::Socket),
+            #[cfg(feature = "http")]
+            "http" => Ok(Transport::Http),
+            #[cfg(not(feature = "http"))]
+            "http" => Err("This build has no HTTP transport; rebuild with --features http".to_string()),
+            other => Err(format!("Unknown transport '{}', expected stdio, http, or socket", other)),
+        }
+    }
+}
+
+/// Options accepted anywhere on the command line
+#[derive(Debug, Clone, Default)]
+struct GlobalOptions {
+    config: Option<PathBuf>,
+    profile: Option<String>,
+    read_only: bool,
+    transport: Transport,
+    host: Option<String>,
+    port: Option<u16>,
+    socket: Option<PathBuf>,
+    framing: Framing,
+}
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match take_global_options(&mut args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match args.first().map(String::as_str) {
+        None => run_server(options),
+        Some("init") => run_init(&args[1..]),
+        Some("validate-config") => run_validate_config(&args[1..], &options),
+        Some("show-config") if args.len() == 1 => run_show_config(&options),
+        Some("--health-check" | "health-check") if args.len() == 1 => run_health_check(&options),
+        Some("print-client-config") => run_print_client_config(&args[1..], &options),
+        Some("call") => run_call(&args[1..], &options),
+        Some("check-access") => run_check_access(&args[1..], &options),
+        Some("self-test") if args.len() == 1 => run_self_test(),
+        Some("help" | "--help" | "-h") => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        Some("version" | "--version" | "-V") => {
+            println!("filejack {}", env!("CARGO_PKG_VERSION"));
+            ExitCode::SUCCESS
+        }
+        Some(_) => {
+            eprintln!("Unknown command '{}'\n\n{}", args.join(" "), USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Remove the global options from the arguments, falling back to the
+/// environment for the config path and profile
+fn take_global_options(args: &mut Vec<String>) -> Result<GlobalOptions, String> {
+    let mut options = GlobalOptions::default();
+    let mut i = 0;
+    while i < args.len() {
+        let (flag, inline) = match args[i].split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (args[i].clone(), None),
+        };
+        match flag.as_str() {
+            "--config" | "--profile" | "--transport" | "--host" | "--port" | "--socket" | "--framing" => {
+                args.remove(i);
+                let value = match inline {
+                    Some(value) => value,
+                    None if i < args.len() => args.remove(i),
+                    None => return Err(format!("{} requires a value", flag)),
+                };
+                match flag.as_str() {
+                    "--config" => options.config = Some(PathBuf::from(value)),
+                    "--profile" => options.profile = Some(value),
+                    "--transport" => options.transport = value.parse()?,
+                    "--host" => options.host = Some(value),
+                    "--socket" => options.socket = Some(PathBuf::from(value)),
+                    "--framing" => options.framing = value.parse().map_err(|e: filejack::FileJackError| e.to_string())?,
+                    _ => options.port = Some(value.parse()
+                        .map_err(|_| format!("Invalid port '{}'", value))?),
+                }
+            }
+            "--read-only" if inline.is_none() => {
+                args.remove(i);
+                options.read_only = true;
+            }
+            _ => i += 1,
+        }
+    }
+
+    options.config = options.config
+        .or_else(|| std::env::var("FILEJACK_CONFIG").ok().map(PathBuf::from));
+    options.profile = options.profile
+        .or_else(|| std::env::var(PROFILE_ENV_VAR).ok());
+    Ok(options)
+}
+
+/// Build the configuration chain: system, user, and project files,
+/// environment variables, then command-line flags
+fn load_layers(options: &GlobalOptions) -> filejack::Result<ConfigLayers> {
+    let cwd = std::env::current_dir()?;
+    let mut layers = ConfigLayers::discover(&cwd, options.config.clone(), |name| std::env::var(name).ok())?;
+    if options.read_only {
+        layers.push("--read-only", json!({"access_policy": {"read_only": true}}));
+    }
+    Ok(layers)
+}
+
+fn resolve_config(options: &GlobalOptions) -> filejack::Result<(ConfigLayers, ResolvedConfig)> {
+    let layers = load_layers(options)?;
+    let resolved = layers.resolve(options.profile.as_deref())?;
+    Ok((layers, resolved))
+}
+
+fn run_init(args: &[String]) -> ExitCode {
+    let mut force = false;
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "--force" | "-f" => force = true,
+            flag if flag.starts_with('-') => {
+                eprintln!("Unknown option '{}' for init\n\n{}", flag, USAGE);
+                return ExitCode::from(2);
+            }
+            other if path.is_none() => path = Some(PathBuf::from(other)),
+            other => {
+                eprintln!("Unexpected argument '{}' for init\n\n{}", other, USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let path = path.unwrap_or_else(|| PathBuf::from("filejack.toml"));
+    if path.exists() && !force {
+        eprintln!("{} already exists; pass --force to overwrite it", path.display());
+        return ExitCode::FAILURE;
+    }
+
+    let dir = match std::env::current_dir().and_then(|d| vfs::canonicalize(&d)) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Cannot determine the current directory: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let written = Config::starter_template(&dir, ConfigFormat::from_path(&path))
+        .and_then(|template| std::fs::write(&path, template).map_err(Into::into));
+    match written {
+        Ok(()) => {
+            println!("Wrote {} allowing access to {}", path.display(), dir.display());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Cannot write {}: {}", path.display(), e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_validate_config(args: &[String], options: &GlobalOptions) -> ExitCode {
+    let profile = options.profile.as_deref();
+    let loaded = match args {
+        [] => resolve_config(options).and_then(|(layers, resolved)| {
+            if layers.file_paths().is_empty() {
+                Err(filejack::FileJackError::ConfigError(
+                    "No config file found; pass a path or set FILEJACK_CONFIG".to_string()
+                ))
+            } else {
+                let names: Vec<String> = layers.file_paths().iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                Ok((names.join(" + "), resolved.config))
+            }
+        }),
+        [path] => Config::from_file_with_profile(path, profile).map(|config| (path.clone(), config)),
+        _ => {
+            eprintln!("validate-config takes at most one path\n\n{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let (name, config) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match profile {
+        Some(profile) => println!("{} (profile: {})", name, profile),
+        None => println!("{}", name),
+    }
+    print_paths("allowed", &config.access_policy.allowed_paths);
+    print_paths("denied", &config.access_policy.denied_paths);
+    print_paths("append-only", &config.access_policy.append_only_paths);
+
+    let issues = config.validate();
+    for issue in &issues {
+        println!("  {}", issue);
+    }
+
+    let errors = issues.iter().filter(|i| i.is_error()).count();
+    if errors > 0 {
+        println!("{} error(s), {} warning(s)", errors, issues.len() - errors);
+        ExitCode::FAILURE
+    } else {
+        println!("OK ({} warning(s))", issues.len());
+        ExitCode::SUCCESS
+    }
+}
+
+fn run_health_check(options: &GlobalOptions) -> ExitCode {
+    let config = match resolve_config(options) {
+        Ok((_, resolved)) => resolved.config,
+        Err(e) => {
+            println!("  error: {}", e);
+            println!("unhealthy");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let errors: Vec<_> = config.health_check().into_iter().filter(|i| i.is_error()).collect();
+    for issue in &errors {
+        println!("  {}", issue);
+    }
+    if errors.is_empty() {
+        println!("healthy");
+        ExitCode::SUCCESS
+    } else {
+        println!("unhealthy");
+        ExitCode::FAILURE
+    }
+}
+
+fn run_show_config(options: &GlobalOptions) -> ExitCode {
+    let (layers, resolved) = match resolve_config(options) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!("Layers (lowest priority first):");
+    for layer in layers.layers() {
+        println!("  {}", layer.source);
+    }
+    if let Some(profile) = &resolved.config.active_profile {
+        println!("  profile {}", profile);
+    }
+
+    if !resolved.config.load_warnings.is_empty() {
+        println!("\nWarnings:");
+        for warning in &resolved.config.load_warnings {
+            println!("  {}", warning);
+        }
+    }
+
+    println!("\nEffective configuration:");
+    let mut effective = resolved.config.clone();
+    effective.profiles.clear();
+    match serde_json::to_string_pretty(&effective) {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    println!("\nSources:");
+    let width = resolved.sources.keys().map(String::len).max().unwrap_or(0);
+    for (key, source) in &resolved.sources {
+        println!("  {:width$}  {}", key, source, width = width);
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_print_client_config(args: &[String], options: &GlobalOptions) -> ExitCode {
+    let clients = match args {
+        [] => ClientKind::ALL.to_vec(),
+        [client] => match client.parse::<ClientKind>() {
+            Ok(client) => vec![client],
+            Err(e) => {
+                eprintln!("{}\n\n{}", e, USAGE);
+                return ExitCode::from(2);
+            }
+        },
+        _ => {
+            eprintln!("print-client-config takes at most one client\n\n{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    // Refuse to hand out a launch command for a configuration that does not load
+    if let Err(e) = resolve_config(options) {
+        eprintln!("error: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    let spec = match launch_spec(options) {
+        Ok(spec) => spec,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    for (i, client) in clients.iter().enumerate() {
+        let snippet = serde_json::to_string_pretty(&spec.snippet(*client)).unwrap_or_default();
+        if clients.len() == 1 {
+            println!("{}", snippet);
+            continue;
+        }
+        if i > 0 {
+            println!();
+        }
+        println!("// {}: {}", client.display_name(), client.config_location());
+        println!("{}", snippet);
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_call(args: &[String], options: &GlobalOptions) -> ExitCode {
+    let (tool, arguments) = match parse_call_args(args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let config = match resolve_config(options) {
+        Ok((_, resolved)) => resolved.config,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return exit_code(Severity::Fatal);
+        }
+    };
+    for warning in &config.load_warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    // Through the full request path, so middleware such as the audit log sees the call
+    let server = McpServer::from_config(config);
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": {"name": tool, "arguments": arguments},
+    });
+    let response: serde_json::Value = match serde_json::from_str(&server.process_request(&request.to_string())) {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    server.remove_temp_workspaces();
+
+    let (output, status) = match response.get("error") {
+        Some(error) => (error, ExitCode::FAILURE),
+        None => (&response["result"], ExitCode::SUCCESS),
+    };
+    println!("{}", serde_json::to_string_pretty(output).unwrap_or_default());
+    status
+}
+
+/// The tool name and arguments object of `call`'s command line
+fn parse_call_args(args: &[String]) -> Result<(String, serde_json::Value), String> {
+    let (tool, rest) = match args.split_first() {
+        Some((tool, rest)) if !tool.starts_with('-') => (tool.clone(), rest),
+        _ => return Err("call requires a tool name".to_string()),
+    };
+
+    let mut arguments = serde_json::Map::new();
+    let mut rest = rest.iter();
+    while let Some(flag) = rest.next() {
+        let (flag, inline) = match flag.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (flag.as_str(), None),
+        };
+        if flag != "--arg" && flag != "--arg-json" {
+            return Err(format!("Unknown option '{}' for call", flag));
+        }
+        let pair = inline.or_else(|| rest.next().cloned()).ok_or_else(|| format!("{} requires KEY=VALUE", flag))?;
+        let (key, value) = pair.split_once('=').ok_or_else(|| format!("{} requires KEY=VALUE, got '{}'", flag, pair))?;
+        let value = if flag == "--arg-json" {
+            serde_json::from_str(value).map_err(|e| format!("Invalid JSON for {}: {}", key, e))?
+        } else {
+            serde_json::Value::String(value.to_string())
+        };
+        arguments.insert(key.to_string(), value);
+    }
+    Ok((tool, serde_json::Value::Object(arguments)))
+}
+
+fn run_check_access(args: &[String], options: &GlobalOptions) -> ExitCode {
+    let (path, write) = match args {
+        [path] if !path.starts_with('-') => (path, false),
+        [path, flag] | [flag, path] if flag == "--write" && !path.starts_with('-') => (path, true),
+        _ => {
+            eprintln!("check-access takes a path and optionally --write\n\n{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let config = match resolve_config(options) {
+        Ok((_, resolved)) => resolved.config,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    for warning in &config.load_warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    // The server resolves relative paths against its working directory too
+    let path = match std::env::current_dir() {
+        Ok(cwd) => cwd.join(path),
+        Err(_) => PathBuf::from(path),
+    };
+    let trace = config.access_policy.trace(&path, write);
+    println!("{} access to {}", if write { "Write" } else { "Read" }, trace.path.display());
+    match &trace.resolved {
+        Some(resolved) => println!("  resolved to {}", resolved.display()),
+        None => println!("  could not be resolved, so no rules were checked"),
+    }
+    let width = trace.steps.iter().map(|step| step.rule.len()).max().unwrap_or(0);
+    for step in &trace.steps {
+        match &step.outcome {
+            Ok(()) => println!("  {:width$}  allow", step.rule, width = width),
+            Err(e) => println!("  {:width$}  deny: {}", step.rule, e, width = width),
+        }
+    }
+    match &trace.decision {
+        Ok(_) => {
+            println!("allowed");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            println!("denied: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_self_test() -> ExitCode {
+    let checks = match self_test::run() {
+        Ok(checks) => checks,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let (mut passed, mut failed, mut not_run) = (0, 0, 0);
+    for check in &checks {
+        match &check.outcome {
+            CheckOutcome::Passed => {
+                passed += 1;
+                println!("  pass  {}", check.tool);
+            }
+            CheckOutcome::Failed(reason) => {
+                failed += 1;
+                println!("  FAIL  {}: {}", check.tool, reason);
+            }
+            CheckOutcome::NotRun => {
+                not_run += 1;
+                println!("  skip  {} (no self-test call)", check.tool);
+            }
+        }
+    }
+    println!("{} passed, {} failed, {} not run", passed, failed, not_run);
+    if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// How a client should start this binary with the current settings.
+///
+/// Clients launch servers from an unrelated working directory, so every path
+/// is made absolute. Without a project config the current directory becomes
+/// `FILEJACK_BASE_PATH` unless one is already set, rather than handing out an
+/// unrestricted server.
+fn launch_spec(options: &GlobalOptions) -> filejack::Result<LaunchSpec> {
+    let cwd = vfs::canonicalize(&std::env::current_dir()?)?;
+    let exe = std::env::current_exe()?;
+    let mut spec = LaunchSpec::new(vfs::canonicalize(&exe).unwrap_or(exe));
+
+    if let Some(profile) = &options.profile {
+        spec = spec.arg("--profile").arg(profile.as_str());
+    }
+    if options.read_only {
+        spec = spec.arg("--read-only");
+    }
+
+    let project = options.config.clone().or_else(|| Config::find_in_dir(&cwd));
+    match project {
+        Some(path) => {
+            let path = vfs::canonicalize(&cwd.join(path))?;
+            spec = spec.env("FILEJACK_CONFIG", path.display().to_string());
+        }
+        None => {
+            let base = match std::env::var("FILEJACK_BASE_PATH") {
+                Ok(base) => vfs::canonicalize(&cwd.join(base))?,
+                Err(_) => cwd,
+            };
+            spec = spec.env("FILEJACK_BASE_PATH", base.display().to_string());
+            if let Ok(read_only) = std::env::var("FILEJACK_READ_ONLY") {
+                spec = spec.env("FILEJACK_READ_ONLY", read_only);
+            }
+        }
+    }
+    Ok(spec)
+}
+
+fn print_paths(kind: &str, paths: &[PathBuf]) {
+    for path in paths {
+        match vfs::canonicalize(path) {
+            Ok(canonical) if canonical != *path => {
+                println!("  {} path: {} -> {}", kind, path.display(), canonical.display())
+            }
+            _ => println!("  {} path: {}", kind, path.display()),
+        }
+    }
+}
+
+fn run_server(options: GlobalOptions) -> ExitCode {
+    let loaded = resolve_config(&options);
+    let logging = loaded.as_ref()
+        .map(|(_, resolved)| resolved.config.logging.clone())
+        .unwrap_or_default();
+    if let Err(e) = filejack::logging::init(&logging) {
+        eprintln!("{}; falling back to the default log level", e);
+        let _ = filejack::logging::init(&LoggingConfig::default());
+    }
     
     info!("FileJack MCP Server v{}", env!("CARGO_PKG_VERSION"));
     info!("Starting server...");
 
-    // Try to load config file first
-    let config_path = std::env::var("FILEJACK_CONFIG")
-        .ok()
-        .map(PathBuf::from)
-        .or_else(|| {
-            // Try default config locations
-            let current_dir = std::env::current_dir().ok()?;
-            let config_file = current_dir.join("filejack.json");
-            if config_file.exists() {
-                Some(config_file)
-            } else {
+    let (layers, resolved) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            // Serving with some other policy than the one configured could
+            // expose files the configuration meant to protect
+            error!("Error loading configuration: {}", e);
+            return exit_code(Severity::Fatal);
+        }
+    };
+    let config = resolved.config;
+    for layer in layers.layers() {
+        info!("Configuration layer: {}", layer.source);
+    }
+    if layers.file_paths().is_empty() {
+        info!("No config file found, using environment-based configuration");
+    }
+    if let Some(profile) = &config.active_profile {
+        info!("Profile: {}", profile);
+    }
+    for warning in &config.load_warnings {
+        warn!("{}", warning);
+    }
+    info!("Server: {} v{}", config.server.name, config.server.version);
+    if let Some(sftp) = &config.sftp {
+        if !cfg!(feature = "sftp") {
+            error!("sftp is configured, but FileJack was built without the `sftp` feature");
+            return exit_code(Severity::Fatal);
+        }
+        info!("Serving {}@{}:{} over SFTP", sftp.user, sftp.host, sftp.port);
+    }
+    
+    // Log policy details
+    if !config.access_policy.allowed_paths.is_empty() {
+        info!("Allowed paths:");
+        for path in &config.access_policy.allowed_paths {
+            info!("  - {}", path.display());
+        }
+    } else {
+        warn!("Base path: unrestricted (permissive mode)");
+    }
+    
+    if !config.access_policy.denied_paths.is_empty() {
+        warn!("Denied paths:");
+        for path in &config.access_policy.denied_paths {
+            warn!("  - {}", path.display());
+        }
+    }
+    
+    if !config.access_policy.append_only_paths.is_empty() {
+        info!("Append-only paths:");
+        for path in &config.access_policy.append_only_paths {
+            info!("  - {}", path.display());
+        }
+    }
+
+    if !config.access_policy.protected_paths.is_empty() {
+        info!("Protected paths: {:?}", config.access_policy.protected_paths);
+    }
+
+    if !config.access_policy.allowed_extensions.is_empty() {
+        info!("Allowed extensions: {:?}", config.access_policy.allowed_extensions);
+    }
+    
+    if !config.access_policy.denied_extensions.is_empty() {
+        warn!("Denied extensions: {:?}", config.access_policy.denied_extensions);
+    }
+    
+    if config.access_policy.max_file_size > 0 {
+        info!("Max file size: {} bytes", config.access_policy.max_file_size);
+    }
+    
+    info!("Read-only mode: {}", config.access_policy.read_only);
+    info!("Allow symlinks: {}", config.access_policy.allow_symlinks);
+    info!("Allow hidden files: {}", config.access_policy.allow_hidden_files);
+    
+    let watched_paths = layers.file_paths();
+    let metrics_address = config.metrics.listen_address.clone();
+    let server = McpServer::from_config(config);
+                
+    let server = Arc::new(server);
+
+    // Held for the rest of the process; the listener is not restarted on reload
+    let _metrics_endpoint = metrics_address.and_then(|address| {
+        match MetricsEndpoint::start(&address, server.metrics()) {
+            Ok(endpoint) => {
+                info!("Serving metrics on http://{}/metrics", endpoint.local_addr());
+                Some(endpoint)
+            }
+            Err(e) => {
+                error!("Cannot serve metrics on {}: {}", address, e);
                 None
             }
-        });
+        }
+    });
 
-    let server = if let Some(config_path) = config_path {
-        info!("Loading configuration from: {}", config_path.display());
-        match Config::from_file(&config_path) {
-            Ok(config) => {
-                info!("Configuration loaded successfully");
-                info!("Server: {} v{}", config.server.name, config.server.version);
-                
-                // Log policy details
-                if !config.access_policy.allowed_paths.is_empty() {
-                    info!("Allowed paths:");
-                    for path in &config.access_policy.allowed_paths {
-                        info!("  - {}", path.display());
-                    }
-                }
-                
-                if !config.access_policy.denied_paths.is_empty() {
-                    warn!("Denied paths:");
-                    for path in &config.access_policy.denied_paths {
-                        warn!("  - {}", path.display());
-                    }
-                }
-                
-                if !config.access_policy.allowed_extensions.is_empty() {
-                    info!("Allowed extensions: {:?}", config.access_policy.allowed_extensions);
-                }
-                
-                if !config.access_policy.denied_extensions.is_empty() {
-                    warn!("Denied extensions: {:?}", config.access_policy.denied_extensions);
+    #[cfg(feature = "http")]
+    let http = HttpTransport::new(Arc::clone(&server));
+    let socket = LocalSocketTransport::new(Arc::clone(&server));
+
+    // Server-initiated messages go wherever the client is listening
+    let notify: Box<dyn Fn(String) + Send> = match options.transport {
+        Transport::Stdio => Box::new(write_notification),
+        #[cfg(feature = "http")]
+        Transport::Http => {
+            let transport = http.clone();
+            Box::new(move |message| transport.notify(message))
+        }
+        Transport::Socket => {
+            let transport = socket.clone();
+            Box::new(move |message| transport.notify(message))
+        }
+    };
+
+    // Reload the policy when a config file changes or on SIGHUP
+    let _watcher = (!watched_paths.is_empty()).then(|| {
+        let reload_server = Arc::clone(&server);
+        let reload_options = options.clone();
+        let watcher = ConfigWatcher::spawn(
+            watched_paths,
+            DEFAULT_POLL_INTERVAL,
+            move || resolve_config(&reload_options).map(|(_, resolved)| resolved.config),
+            move |config| {
+                for warning in &config.load_warnings {
+                    warn!("{}", warning);
                 }
-                
-                if config.access_policy.max_file_size > 0 {
-                    info!("Max file size: {} bytes", config.access_policy.max_file_size);
+                if reload_server.reload(config) {
+                    notify(McpServer::tools_list_changed_notification());
                 }
-                
-                info!("Read-only mode: {}", config.access_policy.read_only);
-                info!("Allow symlinks: {}", config.access_policy.allow_symlinks);
-                info!("Allow hidden files: {}", config.access_policy.allow_hidden_files);
-                
-                McpServer::new(config.access_policy)
-            }
+            },
+        );
+        match watcher {
+            Ok(watcher) => Some(watcher),
             Err(e) => {
-                error!("Error loading config file: {}", e);
-                warn!("Falling back to environment-based configuration");
-                create_server_from_env()
+                warn!("Configuration hot reload disabled: {}", e);
+                None
             }
         }
-    } else {
-        info!("No config file found, using environment-based configuration");
-        create_server_from_env()
+    }).flatten();
+
+    let stopped = match options.transport {
+        Transport::Stdio => serve_stdio(&server, options.framing),
+        #[cfg(feature = "http")]
+        Transport::Http => {
+            let host = options.host.as_deref().unwrap_or(DEFAULT_HTTP_HOST);
+            let port = options.port.unwrap_or(filejack::http::DEFAULT_HTTP_PORT);
+            serve_http(http, host, port)
+        }
+        Transport::Socket => {
+            let path = options.socket.clone().unwrap_or_else(filejack::local_socket::default_socket_path);
+            serve_socket(&socket, &path)
+        }
     };
-                
-    info!("Server initialized. Waiting for JSON-RPC requests on stdin...");
 
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
+    let stats = server.metrics().snapshot();
+    info!(
+        reason = stopped.reason(),
+        uptime_seconds = stats.uptime_seconds,
+        tool_calls = stats.calls.values().sum::<u64>(),
+        "Server shutting down"
+    );
+    server.report_all_usage();
+    // Other threads may still hold the server, so do not wait for it to drop
+    server.remove_temp_workspaces();
+    stopped.exit_code()
+}
+
+/// Answer JSON-RPC requests on stdin until input ends or a shutdown signal
+/// arrives; returns why serving stopped
+fn serve_stdio(server: &McpServer, framing: Framing) -> Stopped {
+    let shutdown = match ShutdownSignal::register() {
+        Ok(shutdown) => Some(shutdown),
+        Err(e) => {
+            warn!("Graceful shutdown on SIGTERM/SIGINT unavailable: {}", e);
+            None
+        }
+    };
 
-    for line in stdin.lock().lines() {
-        match line {
-            Ok(request_str) => {
-                if request_str.trim().is_empty() {
-                    continue;
-                }
+    if framing != Framing::Auto {
+        let _ = STDIO_FRAMING.set(framing);
+    }
+    server.default_session().set_notifier(write_notification);
 
-                debug!("Received request: {}", request_str);
-                
-                let response_str = server.process_request(&request_str);
-                
-                debug!("Sending response: {}", response_str);
-                
-                if let Err(e) = writeln!(stdout, "{}", response_str) {
-                    error!("Error writing response: {}", e);
-                    break;
+    // Read stdin on its own thread so the loop can notice shutdown signals
+    // while waiting for input
+    let (message_tx, messages) = mpsc::channel();
+    let reader = std::thread::Builder::new()
+        .name("filejack-stdin".to_string())
+        .spawn(move || {
+            let mut stdin = io::stdin().lock();
+            let mut framing = framing;
+            loop {
+                let message = transport::read_frame(&mut stdin, &mut framing);
+                // Replies and notifications follow the client's framing
+                if framing != Framing::Auto {
+                    let _ = STDIO_FRAMING.set(framing);
                 }
-                
-                if let Err(e) = stdout.flush() {
-                    error!("Error flushing stdout: {}", e);
+                let last = !matches!(message, Ok(Some(_)));
+                if message_tx.send(message).is_err() || last {
                     break;
                 }
             }
-            Err(e) => {
-                error!("Error reading from stdin: {}", e);
-                let error_response = json!({
-                    "jsonrpc": "2.0",
-                    "error": {
-                        "code": -32700,
-                        "message": format!("Failed to read input: {}", e)
-                    },
-                    "id": null
-                });
-                
-                if let Err(e) = writeln!(stdout, "{}", error_response) {
-                    error!("Error writing error response: {}", e);
+        });
+    if let Err(e) = reader {
+        error!("Cannot start the stdin reader: {}", e);
+        return Stopped::InputError;
+    }
+
+    info!("Server initialized. Waiting for JSON-RPC requests on stdin...");
+
+    let mut transport = StdioTransport {
+        messages,
+        shutdown: shutdown.as_ref(),
+        stdout: io::stdout(),
+        outcome: Stopped::EndOfInput,
+    };
+    if let Err(e) = transport::serve(&mut transport, server) {
+        error!("{}", e);
+    }
+
+    if let Err(e) = transport.stdout.flush() {
+        error!("Error flushing stdout: {}", e);
+    }
+    transport.outcome
+}
+
+/// Messages from the stdin reader thread in, stdout out. Ends when input
+/// ends, fails, or a shutdown signal arrives, and records which in `outcome`.
+struct StdioTransport<'a> {
+    messages: mpsc::Receiver<filejack::Result<Option<String>>>,
+    shutdown: Option<&'a ShutdownSignal>,
+    stdout: io::Stdout,
+    outcome: Stopped,
+}
+
+impl transport::Transport for StdioTransport<'_> {
+    fn read_message(&mut self) -> filejack::Result<Option<String>> {
+        loop {
+            if self.shutdown.is_some_and(ShutdownSignal::is_requested) {
+                self.outcome = Stopped::ShutdownSignal;
+                return Ok(None);
+            }
+            match self.messages.recv_timeout(SHUTDOWN_POLL_INTERVAL) {
+                Ok(Ok(Some(message))) => return Ok(Some(message)),
+                Ok(Ok(None)) | Err(RecvTimeoutError::Disconnected) => return Ok(None),
+                Ok(Err(e)) => {
+                    self.outcome = Stopped::InputError;
+                    let error_response = json!({
+                        "jsonrpc": "2.0",
+                        "error": {
+                            "code": -32700,
+                            "message": format!("Failed to read input: {}", e)
+                        },
+                        "id": null
+                    });
+                    if let Err(e) = self.write_message(&error_response.to_string()) {
+                        error!("Error writing error response: {}", e);
+                    }
+                    return Err(e);
                 }
-                break;
+                Err(RecvTimeoutError::Timeout) => continue,
             }
         }
     }
 
-    info!("Server shutting down...");
+    fn write_message(&mut self, message: &str) -> filejack::Result<()> {
+        let written = transport::write_frame(&mut self.stdout, stdio_framing(), message);
+        if written.is_err() {
+            self.outcome = Stopped::OutputClosed;
+        }
+        Ok(written?)
+    }
 }
 
-fn create_server_from_env() -> McpServer {
-    // Get base path from environment or use current directory
-    let base_path = std::env::var("FILEJACK_BASE_PATH")
-        .ok()
-        .map(PathBuf::from);
+/// Write a server-initiated message to the stdio client
+fn write_notification(message: String) {
+    if let Err(e) = transport::write_frame(&mut io::stdout().lock(), stdio_framing(), &message) {
+        error!("Error writing notification: {}", e);
+    }
+}
 
-    // Check for read-only mode
-    let read_only = std::env::var("FILEJACK_READ_ONLY")
-        .ok()
-        .and_then(|v| v.parse::<bool>().ok())
-        .unwrap_or(false);
+/// Framing for output on stdout: the client's, once known
+fn stdio_framing() -> Framing {
+    STDIO_FRAMING.get().copied().unwrap_or(Framing::Newline)
+}
 
-    if let Some(base_path) = base_path {
-        info!("Base path: {}", base_path.display());
-        info!("Read-only mode: {}", read_only);
-        
-        let policy = if read_only {
-            AccessPolicy::read_only(base_path)
-        } else {
-            AccessPolicy::restricted(base_path)
+/// Serve a Unix socket or named pipe until SIGTERM or SIGINT; returns why
+/// serving stopped
+fn serve_socket(transport: &LocalSocketTransport, path: &std::path::Path) -> Stopped {
+    let shutdown = match ShutdownSignal::register() {
+        Ok(shutdown) => shutdown,
+        Err(e) => {
+            error!("Cannot install shutdown signal handlers: {}", e);
+            return Stopped::TransportError;
+        }
+    };
+    let listener = match LocalSocketListener::bind(path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Cannot listen on {}: {}", path.display(), e);
+            return Stopped::TransportError;
+        }
+    };
+    match transport.serve(&listener, &shutdown) {
+        Ok(()) => Stopped::ShutdownSignal,
+        Err(e) => {
+            error!("Socket transport failed: {}", e);
+            Stopped::TransportError
+        }
+    }
+}
+
+/// Serve streamable HTTP until SIGTERM or SIGINT, letting requests in flight
+/// finish; returns why serving stopped
+#[cfg(feature = "http")]
+fn serve_http(transport: HttpTransport, host: &str, port: u16) -> Stopped {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            error!("Cannot start the HTTP runtime: {}", e);
+            return Stopped::TransportError;
+        }
+    };
+    runtime.block_on(async {
+        let listener = match tokio::net::TcpListener::bind((host, port)).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Cannot listen on {}:{}: {}", host, port, e);
+                return Stopped::TransportError;
+            }
         };
-        
-        McpServer::new(policy)
-    } else {
-        warn!("Base path: unrestricted (permissive mode)");
-        McpServer::new(AccessPolicy::permissive())
+        match transport.serve(listener, shutdown_requested()).await {
+            Ok(()) => Stopped::ShutdownSignal,
+            Err(e) => {
+                error!("HTTP transport failed: {}", e);
+                Stopped::TransportError
+            }
+        }
+    })
+}
+
+#[cfg(feature = "http")]
+async fn shutdown_requested() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
     }
 }
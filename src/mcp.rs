@@ -1,768 +1,2840 @@
-use crate::access_control::AccessPolicy;
+use crate::access_control::{AccessPolicy, WriteOperation};
+use crate::aliases::Aliases;
+use crate::archive::ArchiveFileSystem;
+use crate::audit::{AuditLog, AuditMiddleware};
+use crate::catalog::ToolCatalog;
+use crate::tools::{ToolContext, ToolHandler, ToolRegistry};
+use crate::compression::{self, CompressionSummary};
+use crate::config::{ClientTools, Config};
+use crate::content_cache::ContentCache;
 use crate::error::{FileJackError, Result};
-use crate::file_ops::{FileReader, FileWriter};
+use crate::file_ops::{self, CopySummary, DirectoryEntry, FileMetadata, FileReader, FileWriter, ListOptions, RecentFile};
+use crate::follow::{FollowInfo, Follower, DEFAULT_FOLLOW_INTERVAL};
 use crate::protocol::{
-    JsonRpcRequest, JsonRpcResponse, McpTool, ReadFileParams, WriteFileParams,
+    CallToolResult, ContentEncoding, JsonRpcRequest, JsonRpcResponse, McpTool, ReadFileParams, WriteFileParams,
     ListDirectoryParams, GetMetadataParams, DeleteFileParams, MoveFileParams, CopyFileParams,
-    AppendFileParams, FileExistsParams, CreateDirectoryParams, RemoveDirectoryParams,
-    ReadLinesParams, SearchFilesParams, GrepFileParams,
+    AppendFileParams, FileExistsParams, GetExtendedAttributesParams, ExtendedAttribute, CreateDirectoryParams, RemoveDirectoryParams,
+    ReadLinesParams, SearchFilesParams, GrepFileParams, RecentFilesParams, CompressFileParams,
+    DecompressFileParams, GrepMatch, ReadFileResult, WriteFileResult, ReadRangeParams, ReadRangeResult,
+    WriteRangeParams, WriteRangeResult, ServerInfo, PolicySummary, UpdateStructuredFileParams,
+    EditLinesParams, PackDirectoryParams, SummarizeWorkspaceParams, TailCancelParams, TailFollowParams, SetWorkingDirectoryParams,
+    json_nesting_depth,
 };
-use crate::rate_limit::RateLimiter;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+#[cfg(feature = "git")]
+use crate::config::GitConfig;
+#[cfg(feature = "index")]
+use crate::config::IndexConfig;
+#[cfg(feature = "index")]
+use crate::index::{FileIndex, IndexRefresher};
+use std::collections::BTreeMap;
+#[cfg(feature = "git")]
+use std::collections::BTreeSet;
+use crate::journal;
+use crate::line_edits::{self, LineEditSummary};
+use crate::locks::LockManager;
+use crate::logging;
+use crate::metrics::{Metrics, UNKNOWN_TOOL_LABEL};
+use crate::overview::{self, WorkspaceOverview, DEFAULT_LARGEST_FILES};
+use crate::pack::{self, Pack, DEFAULT_PACK_MAX_BYTES, DEFAULT_PACK_MAX_FILE_BYTES};
+use crate::middleware::{self, Call, Middleware};
+use crate::mounts::{MountTable, Route, PATH_ARGUMENTS};
+use crate::rate_limit::{RateLimitMiddleware, RateLimiter, DEFAULT_REQUESTS_PER_SECOND};
+use crate::session::{ClientInfo, Notifier, Progress, Session, DEFAULT_SESSION_ID};
+use crate::structured::{self, StructuredEditSummary, StructuredFormat};
+use crate::text_style::TextStyle;
+use crate::transactions::{self, StagedCall, Transaction};
+use crate::usage;
+use crate::vfs::{self, default_file_system, FileSystem};
+use crate::workspaces::{WorkspaceSummary, Workspaces};
+use arc_swap::{ArcSwap, Guard};
 use serde_json::{json, Value};
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
-/// MCP Server for file operations
-pub struct McpServer {
-    reader: FileReader,
-    writer: FileWriter,
+/// JSON-RPC methods the server answers
+const METHODS: [&str; 6] = [
+    "tools/list",
+    "tools/call",
+    "initialize",
+    "notifications/initialized",
+    "ping",
+    "notifications/cancelled",
+];
+
+/// Default upper bound on the size of a single request (16 MiB)
+pub const DEFAULT_MAX_REQUEST_BYTES: usize = 16 * 1024 * 1024;
+
+/// Default upper bound on JSON nesting depth in a request
+pub const DEFAULT_MAX_JSON_DEPTH: usize = 64;
+
+/// Default upper bound on the payload returned by a single tool call (16 MiB)
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Settings that a configuration reload replaces as a unit
+#[derive(Clone)]
+pub(crate) struct ServerState {
+    pub(crate) reader: FileReader,
+    pub(crate) writer: FileWriter,
     rate_limiter: RateLimiter,
+    /// Rate the limiter was built with, or `None` for a caller-supplied limiter
+    requests_per_second: Option<u32>,
+    max_request_bytes: usize,
+    max_json_depth: usize,
+    max_response_bytes: usize,
+    /// Whether tool arguments and message bodies may appear in debug logs
+    log_bodies: bool,
+    /// Whether the `get_metrics` tool is offered
+    metrics_tool: bool,
+    /// Whether `write_file` leaves files that already hold the content alone
+    skip_unchanged_writes: bool,
+    /// File each session's usage report is appended to when it ends
+    usage_report: Option<PathBuf>,
+    /// Backends served under virtual prefixes
+    pub(crate) mounts: Arc<MountTable>,
+    /// Names standing in for directories in arguments and responses
+    aliases: Arc<Aliases>,
+    /// Titles and descriptions shown in `tools/list`
+    catalog: Arc<ToolCatalog>,
+    /// Tools offered to each client by name, applied at `initialize`
+    client_tools: Arc<BTreeMap<String, ClientTools>>,
+    /// Identity for `git_snapshot` commits
+    #[cfg(feature = "git")]
+    pub(crate) git: GitConfig,
+    /// Rescans the file index while this state is in use
+    #[cfg(feature = "index")]
+    _index_refresher: Option<Arc<IndexRefresher>>,
+    /// Tools loaded from the configured plugins directory
+    #[cfg(feature = "wasm")]
+    plugin_tools: Vec<Arc<dyn ToolHandler>>,
 }
 
-impl McpServer {
-    /// Create a new MCP Server with an access policy
-    pub fn new(policy: AccessPolicy) -> Self {
-        Self {
+impl ServerState {
+    fn new(policy: AccessPolicy, rate_limiter: RateLimiter, requests_per_second: Option<u32>) -> Self {
+        let aliases = Arc::new(Aliases::new(&BTreeMap::new(), &policy));
+        let mut state = Self {
             reader: FileReader::new(policy.clone()),
             writer: FileWriter::new(policy, true),
-            rate_limiter: RateLimiter::moderate(),
+            rate_limiter,
+            requests_per_second,
+            max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+            max_json_depth: DEFAULT_MAX_JSON_DEPTH,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            log_bodies: true,
+            metrics_tool: false,
+            skip_unchanged_writes: false,
+            usage_report: None,
+            mounts: Arc::new(MountTable::default()),
+            aliases,
+            catalog: Arc::new(ToolCatalog::default()),
+            client_tools: Arc::new(BTreeMap::new()),
+            #[cfg(feature = "git")]
+            git: GitConfig::default(),
+            #[cfg(feature = "index")]
+            _index_refresher: None,
+            #[cfg(feature = "wasm")]
+            plugin_tools: Vec::new(),
+        };
+        if state.reader.policy().browse_archives {
+            state.use_file_system(default_file_system());
+        }
+        state
+    }
+
+    /// Serve files from `fs`, showing archives as read-only directories
+    /// when the policy asks for it
+    fn use_file_system(&mut self, fs: Arc<dyn FileSystem>) {
+        let fs: Arc<dyn FileSystem> = match self.reader.policy().browse_archives {
+            true => Arc::new(ArchiveFileSystem::new(fs)),
+            false => fs,
+        };
+        self.reader = self.reader.clone().with_file_system(Arc::clone(&fs));
+        self.writer = self.writer.clone().with_file_system(fs);
+    }
+
+    fn from_config(mut config: Config) -> Self {
+        // Local mounts are reached through their real paths, so a restricted
+        // policy must allow them; its other rules apply there as well
+        if !config.access_policy.allowed_paths.is_empty() {
+            let local = config.mounts.iter().filter(|mount| !mount.is_remote());
+            config.access_policy.allowed_paths.extend(local.map(|mount| mount.path.clone()));
+        }
+        let policy = config.access_policy.with_path_cache(
+            config.performance.path_cache_capacity,
+            Duration::from_millis(config.performance.path_cache_ttl_ms),
+        );
+        let rate = config.server.requests_per_second;
+        let rate_limiter = RateLimiter::new(rate).with_max_wait(Duration::from_millis(config.server.rate_limit_wait_ms));
+        let mut state = Self::new(policy, rate_limiter, Some(rate));
+        state.max_request_bytes = config.server.max_request_bytes;
+        state.max_json_depth = config.server.max_json_depth;
+        state.max_response_bytes = config.server.max_response_bytes;
+        state.log_bodies = config.logging.log_bodies;
+        state.metrics_tool = config.metrics.expose_tool;
+        state.skip_unchanged_writes = config.performance.skip_unchanged_writes;
+        state.usage_report = config.server.usage_report.clone();
+        state.mounts = Arc::new(MountTable::new(&config.mounts));
+        state.aliases = Arc::new(Aliases::new(&config.aliases, state.reader.policy()));
+        state.catalog = Arc::new(ToolCatalog::new(&config.tool_metadata, config.server.locale.as_deref()));
+        state.client_tools = Arc::new(config.client_tools.clone());
+        #[cfg(feature = "git")]
+        {
+            state.git = config.git.clone();
+        }
+        state.reader = state.reader
+            .with_mmap_threshold(config.performance.mmap_threshold)
+            .with_read_limit(config.performance.read_limit)
+            .with_search_threads(config.performance.search_threads);
+        state.writer = state.writer.with_fsync(config.performance.fsync);
+
+        #[cfg(feature = "sftp")]
+        if let Some(sftp) = &config.sftp {
+            state.use_file_system(Arc::new(crate::sftp::SftpFileSystem::new(sftp.clone())));
+        }
+
+        if config.performance.content_cache_bytes > 0 {
+            let cache = Arc::new(ContentCache::new(config.performance.content_cache_bytes));
+            state.reader = state.reader.with_content_cache(Arc::clone(&cache));
+            state.writer = state.writer.with_content_cache(cache);
+        }
+
+        #[cfg(feature = "index")]
+        if let Some(index) = config.index.as_ref().filter(|_| config.sftp.is_none()) {
+            state.use_index(index);
+        }
+
+        #[cfg(feature = "wasm")]
+        if let Some(plugins) = &config.plugins {
+            state.plugin_tools = crate::plugins::load_plugins(plugins);
+        }
+        state
+    }
+
+    /// Answer `search_files` and `recent_files` from a SQLite index of the
+    /// allowed paths, rescanned in the background. Without the index they
+    /// walk the tree as before.
+    #[cfg(feature = "index")]
+    fn use_index(&mut self, config: &IndexConfig) {
+        let index = match FileIndex::open(&config.database) {
+            Ok(index) => Arc::new(index.with_hash_limit(config.hash_max_bytes).with_content_limit(config.content_max_bytes)),
+            Err(e) => {
+                error!(database = %config.database.display(), error = %e, "Could not open file index, searching without it");
+                return;
+            }
+        };
+        let fs = Arc::clone(self.reader.file_system());
+        let roots: Vec<_> = self.reader.policy().allowed_paths.iter()
+            .filter_map(|path| fs.canonicalize(path).ok())
+            .collect();
+        if let Err(e) = index.retain_roots(&roots) {
+            warn!(error = %e, "Could not prune file index");
         }
+        let options = self.reader.walk_options(usize::MAX);
+        let interval = Duration::from_secs(config.refresh_interval_secs.max(1));
+        match IndexRefresher::spawn(Arc::clone(&index), fs, roots, options, interval) {
+            Ok(refresher) => self._index_refresher = Some(Arc::new(refresher)),
+            Err(e) => {
+                error!(error = %e, "Could not start file indexing, searching without the index");
+                return;
+            }
+        }
+        self.reader = self.reader.clone().with_index(Arc::clone(&index));
+        self.writer = self.writer.clone().with_index(index);
+    }
+}
+
+/// MCP Server for file operations
+///
+/// The policy, limits, and rate limiter live behind an [`ArcSwap`] so that
+/// [`McpServer::reload`] can replace them while requests are in flight; each
+/// request works against the snapshot it started with.
+///
+/// Per-client state lives in [`Session`]s. [`McpServer::process_request`]
+/// uses a single built-in session, which suits stdio; transports with many
+/// clients open one per connection and call
+/// [`McpServer::process_session_request`].
+///
+/// The server is a handle: clones share the same state, sessions, locks, and
+/// metrics, so it can be handed to each transport or worker thread as is.
+#[derive(Clone)]
+pub struct McpServer {
+    shared: Arc<Shared>,
+}
+
+/// Everything the clones of an [`McpServer`] share
+struct Shared {
+    state: ArcSwap<ServerState>,
+    locks: LockManager,
+    metrics: Arc<Metrics>,
+    default_session: Session,
+    /// Sessions opened for clients, for reporting their usage at shutdown
+    sessions: Mutex<Vec<Weak<Session>>>,
+    /// First link of `middleware`, updated when a reload changes the rate
+    rate_limit: Arc<RateLimitMiddleware>,
+    /// Hooks run around every JSON-RPC request, outermost first
+    middleware: Vec<Arc<dyn Middleware>>,
+    /// Backend chosen by the builder, kept across reloads
+    file_system: Option<Arc<dyn FileSystem>>,
+    /// Tools switched on (`true`) or off (`false`) by the builder
+    tool_overrides: BTreeMap<String, bool>,
+    /// Tools built into the server
+    registry: ToolRegistry,
+    /// Tools added with [`McpServer::register_tool`], in registration order
+    custom_tools: RwLock<Vec<Arc<dyn ToolHandler>>>,
+    /// Scratch directories for `create_temp_workspace`, set up from the
+    /// first configuration and kept across reloads
+    workspaces: Option<Workspaces>,
+    /// Paths modified through this server since their last `git_snapshot`
+    #[cfg(feature = "git")]
+    changes: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl McpServer {
+    /// Start building a server; see [`McpServerBuilder`]
+    pub fn builder() -> McpServerBuilder {
+        McpServerBuilder::default()
+    }
+
+    /// Create a new MCP Server with an access policy.
+    /// Shorthand for `McpServer::builder().policy(policy).build()`.
+    pub fn new(policy: AccessPolicy) -> Self {
+        Self::builder().policy(policy).build()
     }
 
     /// Create a new MCP Server with custom rate limiter
     pub fn with_rate_limiter(policy: AccessPolicy, rate_limiter: RateLimiter) -> Self {
-        Self {
-            reader: FileReader::new(policy.clone()),
-            writer: FileWriter::new(policy, true),
-            rate_limiter,
+        Self::builder().policy(policy).rate_limiter(rate_limiter).build()
+    }
+
+    /// Create a new MCP Server from a loaded configuration
+    pub fn from_config(config: Config) -> Self {
+        Self::builder().config(config).build()
+    }
+
+    /// Set the largest request (in bytes) and deepest JSON nesting accepted
+    pub fn with_request_limits(self, max_request_bytes: usize, max_json_depth: usize) -> Self {
+        self.update_state(|state| {
+            state.max_request_bytes = max_request_bytes;
+            state.max_json_depth = max_json_depth;
+        });
+        self
+    }
+
+    /// Set the largest tool result payload (in bytes) before truncation (0 disables)
+    pub fn with_max_response_bytes(self, max_response_bytes: usize) -> Self {
+        self.update_state(|state| state.max_response_bytes = max_response_bytes);
+        self
+    }
+
+    /// Allow or suppress tool arguments and message bodies in debug logs
+    pub fn with_body_logging(self, log_bodies: bool) -> Self {
+        self.update_state(|state| state.log_bodies = log_bodies);
+        self
+    }
+
+    /// Largest request (in bytes) currently accepted
+    pub fn max_request_bytes(&self) -> usize {
+        self.shared.state.load().max_request_bytes
+    }
+
+    /// Whether tool arguments and message bodies may appear in debug logs
+    pub fn logs_bodies(&self) -> bool {
+        self.shared.state.load().log_bodies
+    }
+
+    /// Offer or hide the `get_metrics` tool
+    pub fn with_metrics_tool(self, enabled: bool) -> Self {
+        self.update_state(|state| state.metrics_tool = enabled);
+        self
+    }
+
+    /// Counters for this server, e.g. to serve them over HTTP
+    pub fn metrics(&self) -> Arc<Metrics> {
+        Arc::clone(&self.shared.metrics)
+    }
+
+    /// Start a session for a newly connected client. It gets its own
+    /// rate-limit budget at the configured rate.
+    pub fn open_session(&self, id: impl Into<String>) -> Arc<Session> {
+        let session = Arc::new(Session::new(id));
+        debug!(session = session.id(), "Session opened");
+        let mut sessions = self.shared.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        sessions.retain(|open| open.strong_count() > 0);
+        sessions.push(Arc::downgrade(&session));
+        session
+    }
+
+    /// Report what `session` did, once it is over: a summary line on
+    /// stderr, and the full report appended to the configured
+    /// `server.usage_report` file. Sessions that made no tool calls, and
+    /// those already reported, are skipped.
+    pub fn report_usage(&self, session: &Session) {
+        if !session.usage_counters().finish() {
+            return;
+        }
+        let usage = session.usage();
+        if usage.is_empty() {
+            return;
+        }
+        eprintln!("{}", usage);
+        if let Some(path) = &self.shared.state.load().usage_report {
+            if let Err(e) = usage.append_to(path) {
+                warn!(path = %path.display(), error = %e, "Could not write usage report");
+            }
+        }
+    }
+
+    /// Report the usage of the default session and every session still
+    /// open, as is done when the server shuts down
+    pub fn report_all_usage(&self) {
+        self.report_usage(&self.shared.default_session);
+        let sessions: Vec<Arc<Session>> = self.shared.sessions.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+        for session in sessions {
+            self.report_usage(&session);
+        }
+    }
+
+    /// The session used by [`McpServer::process_request`] and
+    /// [`McpServer::handle_request`]
+    pub fn default_session(&self) -> &Session {
+        &self.shared.default_session
+    }
+
+    /// Settings in effect for a call that starts now
+    pub(crate) fn state(&self) -> Guard<Arc<ServerState>> {
+        self.shared.state.load()
+    }
+
+    pub(crate) fn locks(&self) -> &LockManager {
+        &self.shared.locks
+    }
+
+    /// Paths modified through this server since their last `git_snapshot`
+    #[cfg(feature = "git")]
+    pub(crate) fn changes(&self) -> &Mutex<BTreeSet<PathBuf>> {
+        &self.shared.changes
+    }
+
+    /// Whether `get_metrics` is offered, by the config or the builder
+    pub(crate) fn offers_metrics_tool(&self) -> bool {
+        self.shared.state.load().metrics_tool || self.shared.tool_overrides.get("get_metrics") == Some(&true)
+    }
+
+    /// Whether `create_temp_workspace` is offered, which the `workspaces`
+    /// section of the configuration decides
+    pub(crate) fn offers_workspaces(&self) -> bool {
+        self.shared.workspaces.is_some()
+    }
+
+    /// Whether `get_extended_attributes` is offered, which the policy's
+    /// `extended_attributes` setting decides
+    pub(crate) fn offers_extended_attributes(&self) -> bool {
+        self.shared.state.load().reader.policy().extended_attributes
+    }
+
+    fn update_state(&self, update: impl FnOnce(&mut ServerState)) {
+        let mut state = ServerState::clone(&self.shared.state.load());
+        update(&mut state);
+        self.shared.state.store(Arc::new(state));
+    }
+
+    /// Replace the policy, limits, and rate limiter with those of `config`.
+    ///
+    /// Requests already running finish under the previous settings. Locks and
+    /// metrics are kept, and so is the rate limiter's budget when the rate is unchanged.
+    /// Returns whether the set of tools changed, in which case clients should
+    /// be sent [`McpServer::tools_list_changed_notification`].
+    pub fn reload(&self, mut config: Config) -> bool {
+        let tools_before = self.tool_names();
+        let current = self.shared.state.load_full();
+        allow_workspaces(self.shared.workspaces.as_ref(), &mut config);
+        let mut state = ServerState::from_config(config);
+        if let Some(fs) = &self.shared.file_system {
+            state.use_file_system(Arc::clone(fs));
         }
+        if state.requests_per_second == current.requests_per_second {
+            let max_wait = state.rate_limiter.max_wait();
+            state.rate_limiter = current.rate_limiter.clone().with_max_wait(max_wait);
+        }
+        self.shared.rate_limit.set_limiter(state.rate_limiter.clone());
+        self.shared.state.store(Arc::new(state));
+        info!("Configuration reloaded");
+        self.tool_names() != tools_before
+    }
+
+    /// JSON-RPC notification telling clients to fetch `tools/list` again
+    pub fn tools_list_changed_notification() -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/tools/list_changed"
+        }).to_string()
+    }
+
+    fn tool_names(&self) -> Vec<String> {
+        self.list_tools().into_iter().map(|tool| tool.name).collect()
     }
 
     /// Get the list of available tools
     pub fn list_tools(&self) -> Vec<McpTool> {
-        vec![
-            McpTool {
-                name: "read_file".to_string(),
-                description: "Read contents from a file".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "Path to the file to read"
-                        }
-                    },
-                    "required": ["path"]
-                }),
-            },
-            McpTool {
-                name: "write_file".to_string(),
-                description: "Write contents to a file".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "Path to the file to write"
-                        },
-                        "content": {
-                            "type": "string",
-                            "description": "Content to write to the file"
-                        }
-                    },
-                    "required": ["path", "content"]
-                }),
-            },
-            McpTool {
-                name: "list_directory".to_string(),
-                description: "List contents of a directory".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "Path to the directory to list"
-                        },
-                        "recursive": {
-                            "type": "boolean",
-                            "description": "Whether to list recursively",
-                            "default": false
-                        }
-                    },
-                    "required": ["path"]
-                }),
-            },
-            McpTool {
-                name: "get_metadata".to_string(),
-                description: "Get metadata information about a file".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "Path to the file"
-                        }
-                    },
-                    "required": ["path"]
-                }),
-            },
-            McpTool {
-                name: "delete_file".to_string(),
-                description: "Delete a file".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "Path to the file to delete"
-                        }
-                    },
-                    "required": ["path"]
-                }),
-            },
-            McpTool {
-                name: "move_file".to_string(),
-                description: "Move or rename a file".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "from": {
-                            "type": "string",
-                            "description": "Source file path"
-                        },
-                        "to": {
-                            "type": "string",
-                            "description": "Destination file path"
-                        }
-                    },
-                    "required": ["from", "to"]
-                }),
-            },
-            McpTool {
-                name: "copy_file".to_string(),
-                description: "Copy a file".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "from": {
-                            "type": "string",
-                            "description": "Source file path"
-                        },
-                        "to": {
-                            "type": "string",
-                            "description": "Destination file path"
-                        }
-                    },
-                    "required": ["from", "to"]
-                }),
-            },
-            McpTool {
-                name: "append_file".to_string(),
-                description: "Append content to a file (creates if not exists)".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "Path to the file"
-                        },
-                        "content": {
-                            "type": "string",
-                            "description": "Content to append"
-                        }
-                    },
-                    "required": ["path", "content"]
-                }),
-            },
-            McpTool {
-                name: "file_exists".to_string(),
-                description: "Check if a file or directory exists".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "Path to check"
-                        }
-                    },
-                    "required": ["path"]
-                }),
-            },
-            McpTool {
-                name: "create_directory".to_string(),
-                description: "Create a new directory".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "Path to the directory to create"
-                        },
-                        "recursive": {
-                            "type": "boolean",
-                            "description": "Create parent directories if they don't exist",
-                            "default": false
-                        }
-                    },
-                    "required": ["path"]
-                }),
-            },
-            McpTool {
-                name: "remove_directory".to_string(),
-                description: "Remove a directory".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "Path to the directory to remove"
-                        },
-                        "recursive": {
-                            "type": "boolean",
-                            "description": "Remove directory and all its contents",
-                            "default": false
-                        }
-                    },
-                    "required": ["path"]
-                }),
-            },
-            McpTool {
-                name: "read_lines".to_string(),
-                description: "Read specific lines from a file".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "Path to the file"
-                        },
-                        "start_line": {
-                            "type": "number",
-                            "description": "Starting line number (1-based, inclusive)"
-                        },
-                        "end_line": {
-                            "type": "number",
-                            "description": "Ending line number (1-based, inclusive)"
-                        },
-                        "tail": {
-                            "type": "number",
-                            "description": "Read last N lines (overrides start_line/end_line)"
-                        }
-                    },
-                    "required": ["path"]
-                }),
-            },
-            McpTool {
-                name: "search_files".to_string(),
-                description: "Search for files matching a glob pattern".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "Base directory to search in"
-                        },
-                        "pattern": {
-                            "type": "string",
-                            "description": "Glob pattern (e.g., '*.log', 'test_*.rs')"
-                        },
-                        "recursive": {
-                            "type": "boolean",
-                            "description": "Search recursively in subdirectories",
-                            "default": true
-                        },
-                        "max_results": {
-                            "type": "number",
-                            "description": "Maximum number of results to return"
-                        }
-                    },
-                    "required": ["path", "pattern"]
-                }),
-            },
-            McpTool {
-                name: "grep_file".to_string(),
-                description: "Search for patterns in file contents using regex".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "Path to the file to search"
-                        },
-                        "pattern": {
-                            "type": "string",
-                            "description": "Regular expression pattern"
-                        },
-                        "max_matches": {
-                            "type": "number",
-                            "description": "Maximum number of matches to return"
-                        },
-                        "context_lines": {
-                            "type": "number",
-                            "description": "Number of context lines before and after each match"
-                        }
-                    },
-                    "required": ["path", "pattern"]
-                }),
-            },
-        ]
+        let mut tools = self.shared.registry.definitions(self);
+        tools.extend(self.extension_tools().iter().map(|tool| tool.definition()));
+        tools.retain(|tool| self.shared.tool_overrides.get(&tool.name) != Some(&false));
+        self.shared.state.load().catalog.apply(&mut tools);
+        tools
+    }
+
+    /// Add a tool provided by the embedding application. Fails if a
+    /// built-in or previously registered tool has the same name.
+    ///
+    /// Clients that already fetched `tools/list` only see the new tool
+    /// after [`McpServer::tools_list_changed_notification`] is sent.
+    pub fn register_tool(&self, handler: Arc<dyn ToolHandler>) -> Result<()> {
+        let name = handler.name().to_string();
+        let taken = self.shared.registry.contains(&name)
+            || self.extension_tools().iter().any(|tool| tool.name() == name);
+        let mut custom_tools = self.shared.custom_tools.write().unwrap_or_else(|e| e.into_inner());
+        if taken {
+            return Err(FileJackError::InvalidParameters(format!("A tool named {} already exists", name)));
+        }
+        info!(tool = %name, "Registered custom tool");
+        custom_tools.push(handler);
+        Ok(())
+    }
+
+    fn custom_tool(&self, name: &str) -> Option<Arc<dyn ToolHandler>> {
+        self.extension_tools().into_iter().find(|tool| tool.name() == name)
+    }
+
+    /// Tools from plugins, then those registered by the embedding
+    /// application. A plugin tool named like a built-in one is left out.
+    fn extension_tools(&self) -> Vec<Arc<dyn ToolHandler>> {
+        #[cfg_attr(not(feature = "wasm"), allow(unused_mut))]
+        let mut tools: Vec<Arc<dyn ToolHandler>> = Vec::new();
+        #[cfg(feature = "wasm")]
+        {
+            for tool in &self.shared.state.load().plugin_tools {
+                if self.shared.registry.contains(tool.name()) || tools.iter().any(|other| other.name() == tool.name()) {
+                    warn!(tool = tool.name(), "Ignoring plugin tool with the name of another tool");
+                } else {
+                    tools.push(Arc::clone(tool));
+                }
+            }
+        }
+        tools.extend(self.shared.custom_tools.read().unwrap_or_else(|e| e.into_inner()).iter().cloned());
+        tools
     }
 
-    /// Handle a tool call
+    /// Handle a tool call in the default session
     pub fn handle_tool_call(&self, name: &str, arguments: Value) -> Result<Value> {
-        // Log the arguments received for debugging
-        debug!(tool = name, "Tool called with arguments: {}", arguments);
-        
-        match name {
-            "read_file" => {
-                let params: ReadFileParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse read_file params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for read_file: {}. Expected: {{\"path\": \"string\"}}", e)
-                        )
-                    })?;
-                
-                info!(path = %params.path, "Reading file");
-                let content = self.reader.read_to_string(&params.path)?;
-                info!(path = %params.path, size = content.len(), "File read successfully");
-                Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": content
-                        }
-                    ]
-                }))
+        self.call_tool(&Progress::new(&self.shared.default_session, None), name, arguments)
+    }
+
+    /// Handle a tool call made in `progress`'s session
+    fn call_tool(&self, progress: &Progress<'_>, name: &str, arguments: Value) -> Result<Value> {
+        let state = self.shared.state.load();
+        debug!(tool = name, "Tool called with arguments: {}", logging::loggable_value(&arguments, state.log_bodies));
+        self.ensure_enabled(name)?;
+        if !progress.session().offers_tool(name) {
+            warn!(tool = name, session = progress.session().id(), "Tool not offered to this client");
+            return Err(FileJackError::ToolNotFound(name.to_string()));
+        }
+
+        let arguments = state.aliases.expand(arguments)?;
+        let arguments = self.resolve_relative_paths(progress.session(), arguments);
+        let arguments = if state.mounts.is_empty() {
+            arguments
+        } else {
+            match state.mounts.route(arguments)? {
+                Route::Local(arguments) => arguments,
+                Route::Remote(backend, arguments) => {
+                    if progress.session().in_transaction() && self.shared.registry.get(name).is_some_and(|tool| tool.mutates()) {
+                        return Err(FileJackError::InvalidParameters(
+                            "Changes to mounted backends cannot be staged in a transaction".to_string(),
+                        ));
+                    }
+                    debug!(tool = name, "Forwarding to mount backend");
+                    return backend.call_tool(name, arguments);
+                }
             }
-            "write_file" => {
-                let params: WriteFileParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse write_file params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for write_file: {}. Expected: {{\"path\": \"string\", \"content\": \"string\"}}", e)
-                        )
-                    })?;
-                
-                info!(path = %params.path, size = params.content.len(), "Writing file");
-                self.writer.write_string(&params.path, &params.content)?;
-                info!(path = %params.path, "File written successfully");
-                Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": format!("Successfully wrote {} bytes to {}", params.content.len(), params.path)
-                        }
-                    ]
-                }))
+        };
+        if let Some(workspaces) = &self.shared.workspaces {
+            for key in PATH_ARGUMENTS {
+                if let Some(path) = arguments.get(key).and_then(Value::as_str) {
+                    workspaces.check_access(progress.session().id(), Path::new(path))?;
+                }
             }
-            "list_directory" => {
-                let params: ListDirectoryParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse list_directory params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for list_directory: {}. Expected: {{\"path\": \"string\", \"recursive\": boolean}}", e)
-                        )
-                    })?;
-                
-                info!(path = %params.path, recursive = params.recursive, "Listing directory");
-                let entries = self.reader.list_directory(&params.path, params.recursive)?;
-                info!(path = %params.path, count = entries.len(), "Directory listed successfully");
-                Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": serde_json::to_string_pretty(&entries).unwrap()
-                        }
-                    ]
-                }))
+        }
+
+        match self.shared.registry.get(name).filter(|tool| tool.is_offered(self)) {
+            Some(tool) if tool.mutates() => {
+                for key in PATH_ARGUMENTS {
+                    if let Some(path) = arguments.get(key).and_then(Value::as_str) {
+                        self.shared.locks.check_claim(Path::new(path), progress.session().id())?;
+                    }
+                }
+                if let Some(transaction) = progress.session().transaction().as_mut() {
+                    let call = transaction.stage(&state.reader, &state.writer, name, arguments)?;
+                    debug!(tool = name, position = call.position, "Staged in transaction");
+                    return Ok(serde_json::to_value(CallToolResult::json(&call)?)?);
+                }
+                let before = journal::Before::take(&state.reader, &arguments);
+                let result = tool.call(self, progress, arguments)?;
+                progress.session().record_change(&state.reader, name, before);
+                Ok(result)
             }
-            "get_metadata" => {
-                let params: GetMetadataParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse get_metadata params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for get_metadata: {}. Expected: {{\"path\": \"string\"}}", e)
-                        )
-                    })?;
-                
-                info!(path = %params.path, "Getting metadata");
-                let metadata = self.reader.get_metadata(&params.path)?;
-                info!(path = %params.path, "Metadata retrieved successfully");
-                Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": serde_json::to_string_pretty(&metadata).unwrap()
-                        }
-                    ]
-                }))
+            Some(tool) => {
+                let paths = usage::paths_in(&arguments);
+                let result = tool.call(self, progress, arguments)?;
+                progress.session().usage_counters().record_reads(paths);
+                Ok(result)
             }
-            "delete_file" => {
-                let params: DeleteFileParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse delete_file params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for delete_file: {}. Expected: {{\"path\": \"string\"}}", e)
-                        )
-                    })?;
-                
-                info!(path = %params.path, "Deleting file");
-                self.writer.delete_file(&params.path)?;
-                info!(path = %params.path, "File deleted successfully");
-                Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": format!("Successfully deleted {}", params.path)
-                        }
-                    ]
-                }))
+            None => match self.custom_tool(name) {
+                Some(handler) => handler.call(&ToolContext::new(&state.reader, &state.writer), arguments),
+                None => {
+                    warn!(tool = name, "Tool not found");
+                    Err(FileJackError::ToolNotFound(name.to_string()))
+                }
+            },
+        }
+    }
+
+    /// `arguments` with each relative path joined to the session's working
+    /// directory, before policy, mounts, or claims look at it
+    fn resolve_relative_paths(&self, session: &Session, mut arguments: Value) -> Value {
+        let mut working_directory = None;
+        for key in PATH_ARGUMENTS {
+            if let Some(Value::String(path)) = arguments.get_mut(key) {
+                if !path.is_empty() && !Path::new(path.as_str()).has_root() {
+                    let base = working_directory.get_or_insert_with(|| self.working_directory(session));
+                    *path = vfs::escape_path(&base.join(path.as_str()));
+                }
             }
-            "move_file" => {
-                let params: MoveFileParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse move_file params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for move_file: {}. Expected: {{\"from\": \"string\", \"to\": \"string\"}}", e)
-                        )
-                    })?;
-                
-                info!(from = %params.from, to = %params.to, "Moving file");
-                self.writer.move_file(&params.from, &params.to)?;
-                info!(from = %params.from, to = %params.to, "File moved successfully");
-                Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": format!("Successfully moved {} to {}", params.from, params.to)
-                        }
-                    ]
-                }))
+        }
+        arguments
+    }
+
+    /// Remember paths a successful call modified, for `git_snapshot`
+    pub(crate) fn record_changed(&self, paths: &[&Path]) {
+        #[cfg(feature = "git")]
+        self.shared.changes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(paths.iter().map(|path| path.to_path_buf()));
+        #[cfg(not(feature = "git"))]
+        let _ = paths;
+    }
+
+    /// Handle a JSON-RPC request in the default session
+    pub fn handle_request(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+        self.handle_session_request(&self.shared.default_session, request)
+    }
+
+    /// Handle a JSON-RPC request on behalf of `session`, passing it through
+    /// the middleware chain
+    pub fn handle_session_request(&self, session: &Session, request: JsonRpcRequest) -> JsonRpcResponse {
+        debug!(session = session.id(), method = %request.method, id = ?request.id, "Handling request");
+        let JsonRpcRequest { method, params, id, .. } = request;
+        if !METHODS.contains(&method.as_str()) {
+            warn!(method = %method, "Method not found");
+            return JsonRpcResponse::error(id, -32601, format!("Method not found: {}", method));
+        }
+
+        let (tool, progress_token, params) = match method.as_str() {
+            "tools/call" => {
+                let mut params = params.unwrap_or(json!({}));
+                let tool = params.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string();
+                let progress_token = params.pointer_mut("/_meta/progressToken").map(Value::take);
+                let arguments = params.get_mut("arguments").map(Value::take).unwrap_or(json!({}));
+                (Some(tool), progress_token, arguments)
             }
-            "copy_file" => {
-                let params: CopyFileParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse copy_file params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for copy_file: {}. Expected: {{\"from\": \"string\", \"to\": \"string\"}}", e)
-                        )
-                    })?;
-                
-                info!(from = %params.from, to = %params.to, "Copying file");
-                let bytes_copied = self.writer.copy_file(&params.from, &params.to)?;
-                info!(from = %params.from, to = %params.to, bytes = bytes_copied, "File copied successfully");
-                Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": format!("Successfully copied {} to {} ({} bytes)", params.from, params.to, bytes_copied)
-                        }
-                    ]
-                }))
+            _ => (None, None, params.unwrap_or(Value::Null)),
+        };
+        let call = Call::new(session, &method, tool.as_deref());
+        let progress = Progress::new(session, progress_token);
+        let outcome = middleware::run(&self.shared.middleware, &call, params, |params| {
+            self.handle_method(&progress, &method, tool.as_deref(), params)
+        });
+
+        if tool.is_some() {
+            session.usage_counters().record_call(outcome.as_ref().err());
+        }
+
+        let aliases = &self.shared.state.load().aliases;
+        match outcome {
+            Ok(mut result) => {
+                if let Some(tool) = &tool {
+                    info!(tool = %tool, "Tool call successful");
+                }
+                if tool.as_deref().is_some_and(|tool| aliases.hides_result_of(tool)) {
+                    aliases.hide_in(&mut result);
+                }
+                JsonRpcResponse::success(id, result)
             }
-            "append_file" => {
-                let params: AppendFileParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse append_file params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for append_file: {}. Expected: {{\"path\": \"string\", \"content\": \"string\"}}", e)
-                        )
-                    })?;
-                
-                info!(path = %params.path, size = params.content.len(), "Appending to file");
-                self.writer.append_string(&params.path, &params.content)?;
-                info!(path = %params.path, "Content appended successfully");
-                Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": format!("Successfully appended {} bytes to {}", params.content.len(), params.path)
-                        }
-                    ]
-                }))
+            Err(e) => {
+                match &tool {
+                    Some(tool) => error!(tool = %tool, error = %e, "Tool call failed"),
+                    None => error!(method = %method, error = %e, "Request failed"),
+                }
+                let mut response = JsonRpcResponse::error(id, -32000, aliases.hide(&e.to_string()));
+                if let Some(error) = &mut response.error {
+                    error.data = e.data();
+                    if let Some(data) = &mut error.data {
+                        aliases.hide_in(data);
+                    }
+                }
+                response
             }
-            "file_exists" => {
-                let params: FileExistsParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse file_exists params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for file_exists: {}. Expected: {{\"path\": \"string\"}}", e)
-                        )
-                    })?;
-                
-                debug!(path = %params.path, "Checking if file exists");
-                let exists = self.reader.exists(&params.path);
-                debug!(path = %params.path, exists = exists, "File existence checked");
-                Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": exists.to_string()
-                        }
-                    ]
-                }))
+        }
+    }
+
+    /// Handle one of [`METHODS`] once the middleware let it through
+    fn handle_method(&self, progress: &Progress<'_>, method: &str, tool: Option<&str>, params: Value) -> Result<Value> {
+        let session = progress.session();
+        match method {
+            "tools/list" => {
+                debug!("Listing available tools");
+                let mut tools = self.list_tools();
+                tools.retain(|tool| session.offers_tool(&tool.name));
+                Ok(json!({"tools": tools}))
             }
-            "create_directory" => {
-                let params: CreateDirectoryParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse create_directory params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for create_directory: {}. Expected: {{\"path\": \"string\", \"recursive\": boolean}}", e)
-                        )
-                    })?;
-                
-                info!(path = %params.path, recursive = params.recursive, "Creating directory");
-                self.writer.create_directory(&params.path, params.recursive)?;
-                info!(path = %params.path, "Directory created successfully");
-                Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": format!("Successfully created directory {}", params.path)
-                        }
-                    ]
-                }))
+            "tools/call" => {
+                let tool_name = tool.unwrap_or_default();
+                let started = Instant::now();
+                let outcome = usage::attribute(session.usage_counters(), || self.call_tool(progress, tool_name, params));
+                let label = match &outcome {
+                    Err(FileJackError::ToolNotFound(_)) => UNKNOWN_TOOL_LABEL,
+                    _ => tool_name,
+                };
+                self.shared.metrics.record_call(label, started.elapsed(), outcome.is_ok());
+
+                let mut result = outcome?;
+                let max_response_bytes = self.shared.state.load().max_response_bytes;
+                if max_response_bytes > 0 {
+                    if let Some(total_size) = truncate_result(&mut result, max_response_bytes) {
+                        warn!(tool = tool_name, total_size, limit = max_response_bytes, "Tool result truncated");
+                    }
+                }
+                Ok(result)
             }
-            "remove_directory" => {
-                let params: RemoveDirectoryParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse remove_directory params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for remove_directory: {}. Expected: {{\"path\": \"string\", \"recursive\": boolean}}", e)
-                        )
-                    })?;
-                
-                info!(path = %params.path, recursive = params.recursive, "Removing directory");
-                self.writer.remove_directory(&params.path, params.recursive)?;
-                info!(path = %params.path, "Directory removed successfully");
+            "initialize" => {
+                let client = ClientInfo::from_initialize_params(&params);
+                info!(
+                    session = session.id(),
+                    client = client.name.as_deref().unwrap_or("unknown"),
+                    protocol_version = client.protocol_version.as_deref().unwrap_or("unspecified"),
+                    "Server initialized"
+                );
+                let tools = ClientTools::for_client(&self.shared.state.load().client_tools, client.name.as_deref()).cloned();
+                if tools.is_some() {
+                    info!(session = session.id(), "Tools limited by client_tools");
+                }
+                session.restrict_tools(tools);
+                session.begin_initialize(client);
                 Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": format!("Successfully removed directory {}", params.path)
+                    "protocolVersion": "1.0",
+                    "serverInfo": {
+                        "name": "FileJack",
+                        "version": "0.1.0"
+                    },
+                    "capabilities": {
+                        "tools": {
+                            "listChanged": true
                         }
-                    ]
+                    }
                 }))
             }
-            "read_lines" => {
-                let params: ReadLinesParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse read_lines params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for read_lines: {}. Expected: {{\"path\": \"string\", \"start_line\": number, \"end_line\": number, \"tail\": number}}", e)
-                        )
-                    })?;
-                
-                info!(path = %params.path, "Reading lines from file");
-                let lines = self.reader.read_lines(&params.path, params.start_line, params.end_line, params.tail)?;
-                info!(path = %params.path, line_count = lines.len(), "Lines read successfully");
-                Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": lines.join("\n")
-                        }
-                    ]
-                }))
+            "notifications/initialized" => {
+                session.finish_initialize();
+                Ok(json!({}))
             }
-            "search_files" => {
-                let params: SearchFilesParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse search_files params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for search_files: {}. Expected: {{\"path\": \"string\", \"pattern\": \"string\", \"recursive\": boolean, \"max_results\": number}}", e)
-                        )
-                    })?;
-                
-                info!(path = %params.path, pattern = %params.pattern, "Searching for files");
-                let results = self.reader.search_files(&params.path, &params.pattern, params.recursive, params.max_results)?;
-                info!(path = %params.path, count = results.len(), "Search completed");
-                Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": serde_json::to_string_pretty(&results).unwrap()
-                        }
-                    ]
-                }))
+            "ping" => Ok(json!({})),
+            "notifications/cancelled" => {
+                // Requests cannot be interrupted once started, and MCP lets a
+                // server ignore cancellations it cannot act on
+                debug!(session = session.id(), request_id = %params["requestId"], "Ignoring cancellation");
+                Ok(json!({}))
             }
-            "grep_file" => {
-                let params: GrepFileParams = serde_json::from_value(arguments.clone())
-                    .map_err(|e| {
-                        error!("Failed to parse grep_file params: {}", e);
-                        FileJackError::InvalidParameters(
-                            format!("Invalid parameters for grep_file: {}. Expected: {{\"path\": \"string\", \"pattern\": \"string\", \"max_matches\": number, \"context_lines\": number}}", e)
-                        )
-                    })?;
+            _ => Err(FileJackError::ProtocolError(format!("Method not found: {}", method))),
+        }
+    }
+
+    /// Process a JSON-RPC request from a string in the default session
+    pub fn process_request(&self, request_str: &str) -> String {
+        self.process_session_request(&self.shared.default_session, request_str)
+    }
+
+    /// Process a JSON-RPC request from a string on behalf of `session`.
+    /// Returns an empty string for notifications.
+    ///
+    /// This is where transports hand requests to the server, so it does not
+    /// panic: a panic while handling the request, such as one in a custom
+    /// tool, is logged and answered with a -32603 internal error.
+    pub fn process_session_request(&self, session: &Session, request_str: &str) -> String {
+        match panic::catch_unwind(AssertUnwindSafe(|| self.process_unguarded(session, request_str))) {
+            Ok(response) => response,
+            Err(payload) => {
+                error!(session = session.id(), panic = panic_message(payload.as_ref()), "Request handler panicked");
+                // The panic came after parsing, so the id can be recovered
+                let request = serde_json::from_str::<Value>(request_str).unwrap_or(Value::Null);
+                match request.get("id").filter(|id| !id.is_null()) {
+                    Some(id) => encode(&JsonRpcResponse::error(Some(id.clone()), -32603, "Internal error".to_string())),
+                    None if request.is_object() => String::new(),
+                    None => INTERNAL_ERROR_RESPONSE.to_string(),
+                }
+            }
+        }
+    }
+
+    fn process_unguarded(&self, session: &Session, request_str: &str) -> String {
+        let state = self.shared.state.load();
+
+        if request_str.len() > state.max_request_bytes {
+            warn!(size = request_str.len(), limit = state.max_request_bytes, "Request too large");
+            let error_response = JsonRpcResponse::error(
+                None,
+                -32600,
+                format!("Invalid request: size {} bytes exceeds limit of {} bytes", request_str.len(), state.max_request_bytes),
+            );
+            return encode(&error_response);
+        }
+
+        if json_nesting_depth(request_str, state.max_json_depth) > state.max_json_depth {
+            warn!(limit = state.max_json_depth, "Request nested too deeply");
+            let error_response = JsonRpcResponse::error(
+                None,
+                -32600,
+                format!("Invalid request: JSON nesting exceeds maximum depth of {}", state.max_json_depth),
+            );
+            return encode(&error_response);
+        }
+
+        match serde_json::from_str::<JsonRpcRequest>(request_str) {
+            Ok(request) => {
+                // JSON-RPC 2.0: If id is None, it's a notification and should not be responded to
+                if request.id.is_none() {
+                    // For notifications, we still process them but return empty string
+                    // (or could return empty to indicate no response needed)
+                    self.handle_session_request(session, request);
+                    return String::new();
+                }
                 
-                info!(path = %params.path, pattern = %params.pattern, "Searching file contents");
-                let matches = self.reader.grep_file(&params.path, &params.pattern, params.max_matches, params.context_lines)?;
-                info!(path = %params.path, match_count = matches.len(), "Search completed");
-                Ok(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": serde_json::to_string_pretty(&matches).unwrap()
-                        }
-                    ]
-                }))
+                encode(&self.handle_session_request(session, request))
+            }
+            Err(e) => {
+                error!("Failed to parse request: {}", e);
+                let error_response = JsonRpcResponse::error(
+                    None,
+                    -32700,
+                    format!("Parse error: {}", e),
+                );
+                encode(&error_response)
+            }
+        }
+    }
+}
+
+/// Typed counterparts of the built-in file tools, for crates that use the
+/// server as a library.
+///
+/// Each method applies the access policy, file locks, and byte counters just
+/// as the tool does over JSON-RPC, and returns the data the tool reports.
+/// Calls made this way skip the middleware chain and mount routing, and are
+/// not counted as tool calls in the metrics.
+impl McpServer {
+    fn ensure_enabled(&self, name: &str) -> Result<()> {
+        if self.shared.tool_overrides.get(name) == Some(&false) {
+            warn!(tool = name, "Tool disabled");
+            return Err(FileJackError::ToolNotFound(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Reject modification of a path locked by another client
+    pub(crate) fn check_lock_token(&self, path: &str, token: Option<&str>) -> Result<()> {
+        self.shared.locks.check(Path::new(path), token)
+    }
+
+    pub fn read_file(&self, params: ReadFileParams) -> Result<ReadFileResult> {
+        self.ensure_enabled("read_file")?;
+        let state = self.shared.state.load();
+        info!(path = %params.path, encoding = ?params.encoding, "Reading file");
+        // At most the reader's read limit, so a huge file cannot exhaust memory
+        let (mut bytes, mut next_offset) = state.reader.read_page(&params.path, 0)?;
+        self.shared.metrics.add_bytes_read(bytes.len() as u64);
+        let (content, style) = match params.encoding {
+            ContentEncoding::Text => {
+                // A page may end partway through a character; the rest of it starts the next page
+                if let (Some(offset), Err(e)) = (next_offset.as_mut(), std::str::from_utf8(&bytes)) {
+                    if e.error_len().is_none() {
+                        *offset = e.valid_up_to() as u64;
+                        bytes.truncate(e.valid_up_to());
+                    }
+                }
+                let content = String::from_utf8(bytes).map_err(|e| {
+                    FileJackError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+                })?;
+                let style = TextStyle::detect(&content);
+                (content, Some(style))
+            }
+            ContentEncoding::Base64 => (BASE64.encode(&bytes), None),
+        };
+        info!(path = %params.path, size = content.len(), next_offset, "File read successfully");
+        Ok(ReadFileResult { path: params.path, encoding: params.encoding, content, style, next_offset })
+    }
+
+    pub fn write_file(&self, params: WriteFileParams) -> Result<WriteFileResult> {
+        self.ensure_enabled("write_file")?;
+        let state = self.shared.state.load();
+        self.check_lock_token(&params.path, params.lock_token.as_deref())?;
+        // Held from the checks that read the file until it is written
+        let _lock = state.writer.lock_for_update(&params.path, WriteOperation::Write)?;
+        if let Some(if_match) = &params.if_match {
+            state.writer.check_if_match(&params.path, if_match)?;
+        }
+        let content = match self.existing_style(&params.path, params.preserve_style) {
+            Some(style) => style.apply(&params.content),
+            None => params.content,
+        };
+        let skip_unchanged = params.skip_unchanged.unwrap_or(state.skip_unchanged_writes);
+        if skip_unchanged && state.writer.holds(&params.path, content.as_bytes())? {
+            info!(path = %params.path, "File already holds this content, not writing");
+            return Ok(WriteFileResult { path: params.path, bytes_written: 0, unchanged: true, hash: None });
+        }
+        info!(path = %params.path, size = content.len(), atomic = params.atomic, "Writing file");
+        if params.atomic {
+            state.writer.write_string(&params.path, &content)?;
+        } else {
+            state.writer.write_bytes_in_place(&params.path, content.as_bytes())?;
+        }
+        let bytes_written = content.len() as u64;
+        self.shared.metrics.add_bytes_written(bytes_written);
+        self.record_changed(&[Path::new(&params.path)]);
+        info!(path = %params.path, "File written successfully");
+        // A verified write holds exactly the content
+        let hash = state.writer.policy().verify_writes.then(|| file_ops::content_hash(content.as_bytes()));
+        Ok(WriteFileResult { path: params.path, bytes_written, unchanged: false, hash })
+    }
+
+    pub fn append_file(&self, params: AppendFileParams) -> Result<WriteFileResult> {
+        self.ensure_enabled("append_file")?;
+        let state = self.shared.state.load();
+        self.check_lock_token(&params.path, params.lock_token.as_deref())?;
+        let _lock = state.writer.lock_for_update(&params.path, WriteOperation::Append)?;
+        let content = match self.existing_style(&params.path, params.preserve_style) {
+            // A byte order mark only belongs at the start of the file
+            Some(style) => TextStyle { bom: false, ..style }.apply(&params.content),
+            None => params.content,
+        };
+        info!(path = %params.path, size = content.len(), "Appending to file");
+        state.writer.append_string(&params.path, &content)?;
+        let bytes_written = content.len() as u64;
+        self.shared.metrics.add_bytes_written(bytes_written);
+        self.record_changed(&[Path::new(&params.path)]);
+        info!(path = %params.path, "Content appended successfully");
+        let hash = state.writer.verified_hash(&params.path)?;
+        Ok(WriteFileResult { path: params.path, bytes_written, unchanged: false, hash })
+    }
+
+    /// Style of the text file at `path` when `preserve` is set; `None` when
+    /// it is not set or the file is missing or not UTF-8 text
+    fn existing_style(&self, path: &str, preserve: bool) -> Option<TextStyle> {
+        if !preserve {
+            return None;
+        }
+        let text = self.shared.state.load().reader.read_to_string(path).ok()?;
+        self.shared.metrics.add_bytes_read(text.len() as u64);
+        Some(TextStyle::detect(&text))
+    }
+
+    pub fn read_range(&self, params: ReadRangeParams) -> Result<ReadRangeResult> {
+        self.ensure_enabled("read_range")?;
+        info!(path = %params.path, offset = params.offset, length = params.length, "Reading byte range");
+        let (bytes, eof) = self.shared.state.load().reader.read_range(&params.path, params.offset, params.length)?;
+        let bytes_read = bytes.len();
+        self.shared.metrics.add_bytes_read(bytes_read as u64);
+        let data = match params.encoding {
+            ContentEncoding::Base64 => BASE64.encode(&bytes),
+            ContentEncoding::Text => String::from_utf8(bytes).map_err(|_| {
+                FileJackError::InvalidParameters("Range is not valid UTF-8; use \"encoding\": \"base64\"".to_string())
+            })?,
+        };
+        info!(path = %params.path, bytes = bytes_read, eof = eof, "Byte range read successfully");
+        Ok(ReadRangeResult { offset: params.offset, bytes_read, eof, encoding: params.encoding, data })
+    }
+
+    pub fn write_range(&self, params: WriteRangeParams) -> Result<WriteRangeResult> {
+        self.ensure_enabled("write_range")?;
+        let bytes = match params.encoding {
+            ContentEncoding::Base64 => BASE64
+                .decode(&params.content)
+                .map_err(|e| FileJackError::InvalidParameters(format!("Invalid base64 content: {}", e)))?,
+            ContentEncoding::Text => params.content.into_bytes(),
+        };
+        self.check_lock_token(&params.path, params.lock_token.as_deref())?;
+        info!(path = %params.path, offset = params.offset, size = bytes.len(), "Writing byte range");
+        let state = self.shared.state.load();
+        let file_size = state.writer.write_range(&params.path, params.offset, &bytes)?;
+        self.shared.metrics.add_bytes_written(bytes.len() as u64);
+        self.record_changed(&[Path::new(&params.path)]);
+        info!(path = %params.path, file_size, "Byte range written successfully");
+        let hash = state.writer.verified_hash(&params.path)?;
+        Ok(WriteRangeResult { path: params.path, offset: params.offset, bytes_written: bytes.len(), file_size, hash })
+    }
+
+    pub fn list_directory(&self, params: ListDirectoryParams) -> Result<Vec<DirectoryEntry>> {
+        self.ensure_enabled("list_directory")?;
+        info!(path = %params.path, recursive = params.recursive, "Listing directory");
+        let options = ListOptions {
+            recursive: params.recursive,
+            pattern: params.pattern,
+            entry_type: params.entry_type,
+            sort_by: params.sort_by,
+            order: params.order,
+            offset: params.offset,
+            limit: params.limit,
+        };
+        let entries = self.shared.state.load().reader.list_directory_with(&params.path, &options)?;
+        info!(path = %params.path, count = entries.len(), "Directory listed successfully");
+        Ok(entries)
+    }
+
+    pub fn get_metadata(&self, params: GetMetadataParams) -> Result<FileMetadata> {
+        self.ensure_enabled("get_metadata")?;
+        info!(path = %params.path, "Getting metadata");
+        let state = self.shared.state.load();
+        let mut metadata = state.reader.get_metadata(&params.path)?;
+        if params.hash && metadata.is_file {
+            let bytes = state.reader.read_to_bytes(&params.path)?;
+            self.shared.metrics.add_bytes_read(bytes.len() as u64);
+            metadata.hash = Some(file_ops::content_hash(&bytes));
+            metadata.style = std::str::from_utf8(&bytes).ok().map(TextStyle::detect);
+        }
+        info!(path = %params.path, "Metadata retrieved successfully");
+        Ok(metadata)
+    }
+
+    pub fn get_extended_attributes(&self, params: GetExtendedAttributesParams) -> Result<Vec<ExtendedAttribute>> {
+        self.ensure_enabled("get_extended_attributes")?;
+        info!(path = %params.path, "Getting extended attributes");
+        let attributes = self.shared.state.load().reader.extended_attributes(&params.path)?;
+        let attributes = attributes.into_iter()
+            .map(|(name, value)| match String::from_utf8(value) {
+                Ok(value) => ExtendedAttribute { name, encoding: ContentEncoding::Text, value },
+                Err(e) => ExtendedAttribute { name, encoding: ContentEncoding::Base64, value: BASE64.encode(e.as_bytes()) },
+            })
+            .collect();
+        Ok(attributes)
+    }
+
+    pub fn file_exists(&self, params: FileExistsParams) -> Result<bool> {
+        self.ensure_enabled("file_exists")?;
+        let exists = self.shared.state.load().reader.exists(&params.path);
+        debug!(path = %params.path, exists = exists, "File existence checked");
+        Ok(exists)
+    }
+
+    pub fn delete_file(&self, params: DeleteFileParams) -> Result<()> {
+        self.ensure_enabled("delete_file")?;
+        self.check_lock_token(&params.path, params.lock_token.as_deref())?;
+        info!(path = %params.path, "Deleting file");
+        self.shared.state.load().writer.delete_file(&params.path)?;
+        self.record_changed(&[Path::new(&params.path)]);
+        info!(path = %params.path, "File deleted successfully");
+        Ok(())
+    }
+
+    pub fn move_file(&self, params: MoveFileParams) -> Result<()> {
+        self.ensure_enabled("move_file")?;
+        self.check_lock_token(&params.from, params.lock_token.as_deref())?;
+        self.check_lock_token(&params.to, params.lock_token.as_deref())?;
+        info!(from = %params.from, to = %params.to, "Moving file");
+        self.shared.state.load().writer.move_file(&params.from, &params.to)?;
+        self.record_changed(&[Path::new(&params.from), Path::new(&params.to)]);
+        info!(from = %params.from, to = %params.to, "File moved successfully");
+        Ok(())
+    }
+
+    /// Returns the number of bytes copied
+    pub fn copy_file(&self, params: CopyFileParams) -> Result<u64> {
+        let summary = self.copy_with_progress(params, &Progress::new(&self.shared.default_session, None))?;
+        Ok(summary.bytes)
+    }
+
+    /// Copy a file, or with `recursive` a directory tree, telling `progress`
+    /// about each file copied from a tree
+    pub fn copy_with_progress(&self, params: CopyFileParams, progress: &Progress<'_>) -> Result<CopySummary> {
+        self.ensure_enabled("copy_file")?;
+        self.check_lock_token(&params.to, params.lock_token.as_deref())?;
+        info!(from = %params.from, to = %params.to, recursive = params.recursive, "Copying file");
+        let writer = &self.shared.state.load().writer;
+        let summary = if params.recursive {
+            writer.copy_tree(&params.from, &params.to, params.preserve, &mut |summary, total| {
+                progress.report(summary.files, Some(total), None);
+            })?
+        } else {
+            let bytes = writer.copy_file(&params.from, &params.to)?;
+            if params.preserve {
+                writer.file_system().copy_attributes(Path::new(&params.from), Path::new(&params.to))?;
+            }
+            CopySummary { files: 1, directories: 0, bytes }
+        };
+        self.shared.metrics.add_bytes_written(summary.bytes);
+        self.record_changed(&[Path::new(&params.to)]);
+        info!(from = %params.from, to = %params.to, files = summary.files, bytes = summary.bytes, "File copied successfully");
+        Ok(summary)
+    }
+
+    pub fn compress_file(&self, params: CompressFileParams) -> Result<CompressionSummary> {
+        self.ensure_enabled("compress_file")?;
+        let state = self.shared.state.load();
+        let from = Path::new(&params.from);
+        let to = params.to.map(PathBuf::from).unwrap_or_else(|| compression::compressed_path(from, params.format));
+        self.check_lock_token(&to.to_string_lossy(), params.lock_token.as_deref())?;
+        info!(from = %params.from, to = %to.display(), format = ?params.format, "Compressing file");
+        let summary = compression::compress_file(&state.reader, &state.writer, from, Some(&to), params.format, params.level)?;
+        self.shared.metrics.add_bytes_read(summary.bytes_in);
+        self.shared.metrics.add_bytes_written(summary.bytes_out);
+        self.record_changed(&[&to]);
+        info!(from = %params.from, bytes_in = summary.bytes_in, bytes_out = summary.bytes_out, "File compressed");
+        Ok(summary)
+    }
+
+    pub fn edit_lines(&self, params: EditLinesParams) -> Result<LineEditSummary> {
+        self.ensure_enabled("edit_lines")?;
+        let state = self.shared.state.load();
+        let path = Path::new(&params.path);
+        self.check_lock_token(&params.path, params.lock_token.as_deref())?;
+        // Refuse before reading when the file could not be written back, and
+        // keep other updates out until it is
+        let _lock = state.writer.lock_for_update(path, WriteOperation::Write)?;
+        if let Some(if_match) = &params.if_match {
+            state.writer.check_if_match(path, if_match)?;
+        }
+        info!(path = %params.path, operations = params.operations.len(), "Editing lines");
+        let text = state.reader.read_to_string(path)?;
+        self.shared.metrics.add_bytes_read(text.len() as u64);
+        let (edited, lines_after) = line_edits::apply_line_edits(&text, &params.operations)?;
+        state.writer.write_string(path, &edited)?;
+        self.shared.metrics.add_bytes_written(edited.len() as u64);
+        self.record_changed(&[path]);
+        info!(path = %params.path, lines = lines_after, "Lines edited successfully");
+        Ok(LineEditSummary {
+            path: params.path,
+            operations: params.operations.len(),
+            lines_before: text.lines().count(),
+            lines_after,
+            bytes_written: edited.len() as u64,
+        })
+    }
+
+    pub fn update_structured_file(&self, params: UpdateStructuredFileParams) -> Result<StructuredEditSummary> {
+        self.ensure_enabled("update_structured_file")?;
+        let state = self.shared.state.load();
+        let path = Path::new(&params.path);
+        let format = params.format.or_else(|| StructuredFormat::from_path(path)).ok_or_else(|| {
+            FileJackError::InvalidParameters(format!("Cannot tell the format of {}; pass \"format\"", params.path))
+        })?;
+        self.check_lock_token(&params.path, params.lock_token.as_deref())?;
+        // Refuse before reading when the file could not be written back, and
+        // keep other updates out until it is
+        let _lock = state.writer.lock_for_update(path, WriteOperation::Write)?;
+        if let Some(if_match) = &params.if_match {
+            state.writer.check_if_match(path, if_match)?;
+        }
+        info!(path = %params.path, format = ?format, operations = params.operations.len(), "Updating structured file");
+        let text = state.reader.read_to_string(path)?;
+        self.shared.metrics.add_bytes_read(text.len() as u64);
+        let updated = structured::apply_edits(&text, format, &params.operations)?;
+        let changed = updated != text;
+        if changed {
+            state.writer.write_string(path, &updated)?;
+            self.shared.metrics.add_bytes_written(updated.len() as u64);
+            self.record_changed(&[path]);
+        }
+        info!(path = %params.path, changed, "Structured file updated");
+        Ok(StructuredEditSummary {
+            path: params.path,
+            format,
+            operations: params.operations.len(),
+            changed,
+            bytes_written: if changed { updated.len() as u64 } else { 0 },
+        })
+    }
+
+    pub fn decompress_file(&self, params: DecompressFileParams) -> Result<CompressionSummary> {
+        self.ensure_enabled("decompress_file")?;
+        let state = self.shared.state.load();
+        let from = Path::new(&params.from);
+        let to = params.to.map(PathBuf::from).or_else(|| compression::decompressed_path(from));
+        if let Some(to) = &to {
+            self.check_lock_token(&to.to_string_lossy(), params.lock_token.as_deref())?;
+        }
+        info!(from = %params.from, "Decompressing file");
+        let summary = compression::decompress_file(
+            &state.reader, &state.writer, from, to.as_deref(), params.format, params.max_size,
+        )?;
+        self.shared.metrics.add_bytes_read(summary.bytes_in);
+        self.shared.metrics.add_bytes_written(summary.bytes_out);
+        self.record_changed(&[Path::new(&summary.destination)]);
+        info!(from = %params.from, bytes_in = summary.bytes_in, bytes_out = summary.bytes_out, "File decompressed");
+        Ok(summary)
+    }
+
+    pub fn create_directory(&self, params: CreateDirectoryParams) -> Result<()> {
+        self.ensure_enabled("create_directory")?;
+        info!(path = %params.path, recursive = params.recursive, "Creating directory");
+        self.shared.state.load().writer.create_directory(&params.path, params.recursive)?;
+        info!(path = %params.path, "Directory created successfully");
+        Ok(())
+    }
+
+    /// Make a scratch directory that only `session` may use until it expires
+    pub fn create_temp_workspace(&self, session: &Session) -> Result<WorkspaceSummary> {
+        self.ensure_enabled("create_temp_workspace")?;
+        let workspaces = self.shared.workspaces.as_ref().ok_or_else(|| {
+            FileJackError::InvalidParameters("Temporary workspaces are not configured".to_string())
+        })?;
+        workspaces.create(session.id())
+    }
+
+    /// Send lines appended to a file to `session` as notifications until
+    /// cancelled
+    pub fn tail_follow(&self, session: &Session, params: TailFollowParams) -> Result<FollowInfo> {
+        self.ensure_enabled("tail_follow")?;
+        let notifier = session.notifier().ok_or_else(|| {
+            FileJackError::InvalidParameters("This transport cannot send notifications, so files cannot be followed".to_string())
+        })?;
+        let interval = params.interval_ms.map_or(DEFAULT_FOLLOW_INTERVAL, Duration::from_millis);
+        let state = self.shared.state.load();
+        let notifier: Notifier = match state.aliases.is_empty() {
+            true => notifier,
+            false => {
+                let aliases = Arc::clone(&state.aliases);
+                Arc::new(move |message: String| notifier(aliases.hide(&message)))
             }
-            _ => {
-                warn!(tool = name, "Tool not found");
-                Err(FileJackError::ToolNotFound(name.to_string()))
+        };
+        let reader = state.reader.clone();
+        let (follower, info) = Follower::spawn(reader, PathBuf::from(&params.path), interval, notifier)?;
+        session.add_follower(follower)?;
+        info!(session = session.id(), path = %params.path, follower = info.follower, "Following file");
+        Ok(info)
+    }
+
+    /// Stop a follower `session` started with `tail_follow`
+    pub fn tail_cancel(&self, session: &Session, params: TailCancelParams) -> Result<()> {
+        self.ensure_enabled("tail_cancel")?;
+        if !session.remove_follower(params.follower) {
+            return Err(FileJackError::InvalidParameters(format!("No follower {} in this session", params.follower)));
+        }
+        info!(session = session.id(), follower = params.follower, "Stopped following file");
+        Ok(())
+    }
+
+    /// The directory relative paths in `session`'s calls are resolved
+    /// against: the one it set, or else the first allowed root, or the
+    /// server's own when every path is allowed
+    pub fn working_directory(&self, session: &Session) -> PathBuf {
+        session.working_directory().unwrap_or_else(|| {
+            let state = self.shared.state.load();
+            match state.reader.policy().allowed_paths.first() {
+                Some(root) => root.clone(),
+                None => std::env::current_dir().unwrap_or_default(),
             }
+        })
+    }
+
+    /// Resolve `session`'s later relative paths against a directory;
+    /// returns it with links and `..` resolved
+    pub fn set_working_directory(&self, session: &Session, params: SetWorkingDirectoryParams) -> Result<PathBuf> {
+        self.ensure_enabled("set_working_directory")?;
+        let state = self.shared.state.load();
+        if !state.reader.get_metadata(&params.path)?.is_dir {
+            return Err(FileJackError::InvalidPath("Working directory must be a directory".to_string()));
+        }
+        let path = state.reader.file_system().canonicalize(Path::new(&params.path))?;
+        info!(session = session.id(), path = %path.display(), "Working directory set");
+        session.set_working_directory(path.clone());
+        Ok(path)
+    }
+
+    /// Start staging `session`'s changes until `commit` or `rollback`
+    pub fn begin_transaction(&self, session: &Session) -> Result<()> {
+        self.ensure_enabled("begin_transaction")?;
+        let mut transaction = session.transaction();
+        if transaction.is_some() {
+            return Err(FileJackError::InvalidParameters("A transaction is already open".to_string()));
+        }
+        info!(session = session.id(), "Transaction started");
+        *transaction = Some(Transaction::default());
+        Ok(())
+    }
+
+    /// Apply the calls `session` staged, in order. If one fails, the files
+    /// the transaction touched are restored and the error is returned; if
+    /// they cannot all be restored, that error is returned instead.
+    pub fn commit(&self, session: &Session) -> Result<Vec<StagedCall>> {
+        self.ensure_enabled("commit")?;
+        let transaction = session.transaction().take().ok_or_else(no_transaction)?;
+        let state = self.shared.state.load();
+        for path in transaction.paths() {
+            self.shared.locks.check_claim(Path::new(path), session.id())?;
+        }
+        let snapshot = transaction.snapshot(&state.reader)?;
+        info!(session = session.id(), calls = transaction.len(), "Committing transaction");
+
+        let progress = Progress::new(session, None);
+        let mut applied = Vec::new();
+        for (call, arguments) in transaction.into_calls() {
+            let tool = self.shared.registry.get(&call.tool).ok_or_else(|| FileJackError::ToolNotFound(call.tool.clone()))?;
+            let before = journal::Before::take(&state.reader, &arguments);
+            if let Err(e) = tool.call(self, &progress, arguments) {
+                let failed = format!("Call {} ({}) failed", call.position, call.tool);
+                return Err(match snapshot.restore(&state.writer) {
+                    Ok(()) => {
+                        warn!(session = session.id(), position = call.position, error = %e, "Transaction rolled back");
+                        transactions::rolled_back(e, &format!("{}, so the transaction was rolled back", failed))
+                    }
+                    Err(restore) => {
+                        error!(session = session.id(), position = call.position, error = %restore, "Transaction partly applied");
+                        let context = format!("{} ({}) and the transaction is partly applied", failed, e);
+                        transactions::rolled_back(restore, &context)
+                    }
+                });
+            }
+            applied.push((call, before));
+        }
+
+        // Journaled only once the whole transaction has been applied
+        Ok(applied.into_iter()
+            .map(|(call, before)| {
+                session.record_change(&state.reader, &call.tool, before);
+                call
+            })
+            .collect())
+    }
+
+    /// Discard the calls `session` staged; returns how many there were
+    pub fn rollback(&self, session: &Session) -> Result<usize> {
+        self.ensure_enabled("rollback")?;
+        let transaction = session.transaction().take().ok_or_else(no_transaction)?;
+        info!(session = session.id(), calls = transaction.len(), "Transaction discarded");
+        Ok(transaction.len())
+    }
+
+    /// Delete every temporary workspace, as is done when the server is dropped
+    pub fn remove_temp_workspaces(&self) {
+        if let Some(workspaces) = &self.shared.workspaces {
+            workspaces.remove_all();
+        }
+    }
+
+    pub fn remove_directory(&self, params: RemoveDirectoryParams) -> Result<()> {
+        self.ensure_enabled("remove_directory")?;
+        info!(path = %params.path, recursive = params.recursive, "Removing directory");
+        self.shared.state.load().writer.remove_directory(&params.path, params.recursive)?;
+        self.record_changed(&[Path::new(&params.path)]);
+        info!(path = %params.path, "Directory removed successfully");
+        Ok(())
+    }
+
+    pub fn read_lines(&self, params: ReadLinesParams) -> Result<Vec<String>> {
+        self.ensure_enabled("read_lines")?;
+        info!(path = %params.path, "Reading lines from file");
+        let lines = self.shared.state.load().reader.read_lines(&params.path, params.start_line, params.end_line, params.tail)?;
+        self.shared.metrics.add_bytes_read(lines.iter().map(|line| line.len() as u64 + 1).sum());
+        info!(path = %params.path, line_count = lines.len(), "Lines read successfully");
+        Ok(lines)
+    }
+
+    /// Returns the matching paths
+    pub fn search_files(&self, params: SearchFilesParams) -> Result<Vec<String>> {
+        self.ensure_enabled("search_files")?;
+        info!(path = %params.path, pattern = %params.pattern, "Searching for files");
+        let results = self.shared.state.load().reader.search_files(&params.path, &params.pattern, params.recursive, params.max_results)?;
+        info!(path = %params.path, count = results.len(), "Search completed");
+        Ok(results)
+    }
+
+    pub fn recent_files(&self, params: RecentFilesParams) -> Result<Vec<RecentFile>> {
+        self.ensure_enabled("recent_files")?;
+        info!(path = %params.path, "Listing recently modified files");
+        let files = self.shared.state.load().reader.recent_files(&params.path, params.max_results)?;
+        info!(path = %params.path, count = files.len(), "Listing completed");
+        Ok(files)
+    }
+
+    pub fn pack_directory(&self, params: PackDirectoryParams) -> Result<Pack> {
+        self.ensure_enabled("pack_directory")?;
+        info!(path = %params.path, pattern = ?params.pattern, "Packing directory");
+        let pack = pack::pack_directory(
+            &self.shared.state.load().reader,
+            Path::new(&params.path),
+            params.pattern.as_deref(),
+            params.max_bytes.unwrap_or(DEFAULT_PACK_MAX_BYTES),
+            params.max_file_bytes.unwrap_or(DEFAULT_PACK_MAX_FILE_BYTES),
+        )?;
+        self.shared.metrics.add_bytes_read(pack.total_bytes);
+        info!(path = %params.path, files = pack.files.len(), skipped = pack.skipped.len(), "Directory packed");
+        Ok(pack)
+    }
+
+    pub fn summarize_workspace(&self, params: SummarizeWorkspaceParams) -> Result<WorkspaceOverview> {
+        self.ensure_enabled("summarize_workspace")?;
+        info!(path = %params.path, "Summarizing workspace");
+        let overview = overview::summarize(
+            &self.shared.state.load().reader,
+            Path::new(&params.path),
+            params.largest.unwrap_or(DEFAULT_LARGEST_FILES),
+        )?;
+        info!(path = %params.path, files = overview.total_files, bytes = overview.total_bytes, "Workspace summarized");
+        Ok(overview)
+    }
+
+    pub fn grep_file(&self, params: GrepFileParams) -> Result<Vec<GrepMatch>> {
+        self.ensure_enabled("grep_file")?;
+        info!(path = %params.path, pattern = %params.pattern, "Searching file contents");
+        let matches = self.shared.state.load().reader.grep_file(&params.path, &params.pattern, params.max_matches, params.context_lines)?;
+        info!(path = %params.path, match_count = matches.len(), "Search completed");
+        Ok(matches)
+    }
+
+    pub fn server_info(&self) -> Result<ServerInfo> {
+        self.ensure_enabled("server_info")?;
+        let state = self.shared.state.load();
+        let policy = state.reader.policy();
+        let stats = self.shared.metrics.snapshot();
+        Ok(ServerInfo {
+            name: "FileJack".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime_seconds: stats.uptime_seconds,
+            operations: stats.calls,
+            errors: stats.errors,
+            bytes_read: stats.bytes_read,
+            bytes_written: stats.bytes_written,
+            bytes_transferred: stats.bytes_read + stats.bytes_written,
+            rate_limited: stats.rate_limited,
+            policy: PolicySummary {
+                allowed_paths: policy.allowed_paths.clone(),
+                denied_path_count: policy.denied_paths.len(),
+                append_only_paths: policy.append_only_paths.clone(),
+                protected_paths: policy.protected_paths.clone(),
+                sensitive_paths: policy.sensitive_paths.clone(),
+                allowed_extensions: policy.allowed_extensions.clone(),
+                denied_extensions: policy.denied_extensions.clone(),
+                max_file_size: policy.max_file_size,
+                read_only: policy.read_only,
+                allow_symlinks: policy.allow_symlinks,
+                allow_hidden_files: policy.allow_hidden_files,
+                hidden_file_detection: policy.hidden_file_detection,
+                unicode_normalization: policy.unicode_normalization,
+                deny_files_not_owned_by_user: policy.deny_files_not_owned_by_user,
+                allow_special_files: policy.allow_special_files,
+                directory_overlays: policy.directory_overlays,
+                browse_archives: policy.browse_archives,
+                verify_writes: policy.verify_writes,
+                redact_roots: policy.redact_roots,
+            },
+            mounts: state.mounts.describe(),
+        })
+    }
+}
+
+/// Assembles an [`McpServer`] from its parts.
+///
+/// Without [`McpServerBuilder::config`], the server starts from
+/// [`AccessPolicy::default`] and the built-in limits, like
+/// [`McpServer::new`]; each setter replaces one component.
+///
+/// ```
+/// use filejack::{AccessPolicy, McpServer, RateLimiter};
+///
+/// let server = McpServer::builder()
+///     .policy(AccessPolicy::restricted("/srv/docs".into()))
+///     .rate_limiter(RateLimiter::strict())
+///     .disable_tool("delete_file")
+///     .build();
+/// assert!(server.list_tools().iter().all(|tool| tool.name != "delete_file"));
+/// ```
+#[derive(Default)]
+pub struct McpServerBuilder {
+    config: Option<Config>,
+    policy: Option<AccessPolicy>,
+    rate_limiter: Option<RateLimiter>,
+    audit_log: Option<Arc<dyn AuditLog>>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    file_system: Option<Arc<dyn FileSystem>>,
+    tool_overrides: BTreeMap<String, bool>,
+    tools: Vec<Arc<dyn ToolHandler>>,
+}
+
+impl McpServerBuilder {
+    /// Take the policy, limits, and backends from a loaded configuration
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Access policy, replacing the configuration's if one was given
+    pub fn policy(mut self, policy: AccessPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Rate limiter, replacing the one built from the configured rate.
+    /// A reload that changes `requests_per_second` replaces it in turn.
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Record every tool call received over JSON-RPC, through an
+    /// [`AuditMiddleware`] that runs right after the rate limiter
+    pub fn audit_log(mut self, audit_log: Arc<dyn AuditLog>) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Run `middleware` around every JSON-RPC request, inside the rate
+    /// limiter, the audit log, and middlewares added before it
+    pub fn middleware(mut self, middleware: Arc<dyn Middleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Serve files from `fs` instead of the host file system or the
+    /// configured SFTP backend. Kept across reloads.
+    pub fn filesystem(mut self, fs: Arc<dyn FileSystem>) -> Self {
+        self.file_system = Some(fs);
+        self
+    }
+
+    /// Offer a tool that is off by default, such as `get_metrics`, or undo
+    /// [`McpServerBuilder::disable_tool`]
+    pub fn enable_tool(mut self, name: impl Into<String>) -> Self {
+        self.tool_overrides.insert(name.into(), true);
+        self
+    }
+
+    /// Leave a tool out of `tools/list` and refuse calls to it
+    pub fn disable_tool(mut self, name: impl Into<String>) -> Self {
+        self.tool_overrides.insert(name.into(), false);
+        self
+    }
+
+    /// Add a tool provided by the embedding application; see
+    /// [`McpServer::register_tool`]
+    pub fn tool(mut self, handler: Arc<dyn ToolHandler>) -> Self {
+        self.tools.push(handler);
+        self
+    }
+
+    pub fn build(self) -> McpServer {
+        let mut workspaces = None;
+        let mut state = match self.config {
+            Some(mut config) => {
+                if let Some(policy) = self.policy {
+                    config.access_policy = policy;
+                }
+                workspaces = config.workspaces.as_ref().and_then(|settings| match Workspaces::new(settings) {
+                    Ok(workspaces) => Some(workspaces),
+                    Err(e) => {
+                        error!(root = %settings.root.display(), error = %e, "Cannot set up temporary workspaces");
+                        None
+                    }
+                });
+                allow_workspaces(workspaces.as_ref(), &mut config);
+                ServerState::from_config(config)
+            }
+            None => ServerState::new(
+                self.policy.unwrap_or_default(),
+                RateLimiter::moderate(),
+                Some(DEFAULT_REQUESTS_PER_SECOND),
+            ),
+        };
+        if let Some(rate_limiter) = self.rate_limiter {
+            state.rate_limiter = rate_limiter;
+            state.requests_per_second = None;
+        }
+        if let Some(fs) = &self.file_system {
+            state.use_file_system(Arc::clone(fs));
+        }
+
+        let metrics = Arc::new(Metrics::new());
+        let rate_limit = Arc::new(RateLimitMiddleware::new(state.rate_limiter.clone()).with_metrics(Arc::clone(&metrics)));
+        let mut middleware: Vec<Arc<dyn Middleware>> = vec![Arc::clone(&rate_limit) as Arc<dyn Middleware>];
+        if let Some(audit_log) = self.audit_log {
+            middleware.push(Arc::new(AuditMiddleware::new(audit_log)));
+        }
+        middleware.extend(self.middleware);
+
+        let shared = Shared {
+            state: ArcSwap::from_pointee(state),
+            locks: LockManager::new(),
+            metrics,
+            default_session: Session::shared(DEFAULT_SESSION_ID),
+            sessions: Mutex::new(Vec::new()),
+            rate_limit,
+            middleware,
+            file_system: self.file_system,
+            tool_overrides: BTreeMap::new(),
+            registry: ToolRegistry::builtin(),
+            custom_tools: RwLock::new(Vec::new()),
+            workspaces,
+            #[cfg(feature = "git")]
+            changes: Mutex::new(BTreeSet::new()),
+        };
+        let mut server = McpServer { shared: Arc::new(shared) };
+        for handler in self.tools {
+            if let Err(e) = server.register_tool(handler) {
+                warn!(error = %e, "Custom tool not added");
+            }
+        }
+        let known = server.tool_names();
+        for name in self.tool_overrides.keys() {
+            if name != "get_metrics" && !known.contains(name) {
+                warn!(tool = %name, "Enabled or disabled tool does not exist");
+            }
+        }
+        // Not yet cloned, so the overrides can still be set in place
+        if let Some(shared) = Arc::get_mut(&mut server.shared) {
+            shared.tool_overrides = self.tool_overrides;
+        }
+        server
+    }
+}
+
+/// Let a restricted policy reach the workspaces root. Which session may
+/// use each workspace in it is checked per call.
+fn allow_workspaces(workspaces: Option<&Workspaces>, config: &mut Config) {
+    if let Some(workspaces) = workspaces {
+        if !config.access_policy.allowed_paths.is_empty() {
+            config.access_policy.allowed_paths.push(workspaces.root().to_path_buf());
+        }
+    }
+}
+
+/// Sent when a response cannot be built any other way
+const INTERNAL_ERROR_RESPONSE: &str = r#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"Internal error"},"id":null}"#;
+
+/// Serialize a response. serde_json only fails on maps with non-string
+/// keys, which a `Value` cannot hold, but a failure still must not panic.
+fn no_transaction() -> FileJackError {
+    FileJackError::InvalidParameters("No transaction is open; call begin_transaction first".to_string())
+}
+
+fn encode(response: &JsonRpcResponse) -> String {
+    serde_json::to_string(response).unwrap_or_else(|e| {
+        error!(error = %e, "Could not serialize response");
+        INTERNAL_ERROR_RESPONSE.to_string()
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+/// Trim the text payloads of a tool result so they fit within `max_bytes`.
+///
+/// Text is cut at the last line break that fits (or the last character
+/// boundary if a single line is too long); base64 blobs are cut to a whole
+/// number of 4-character groups. Content items are filled in order, so the
+/// same input always truncates the same way. On truncation the result gains
+/// `"truncated": true` and `"total_size"` (the untruncated payload size) plus a
+/// trailing text item telling the client how to fetch the rest, and loses its
+/// `structuredContent`, which would no longer match the text. Returns the
+/// total size when truncation happened.
+fn truncate_result(result: &mut Value, max_bytes: usize) -> Option<usize> {
+    let items = result.get_mut("content")?.as_array_mut()?;
+
+    fn payload(item: &Value) -> Option<(String, bool)> {
+        if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
+            return Some((text.to_string(), false));
+        }
+        item.get("resource")?.get("blob")?.as_str().map(|b| (b.to_string(), true))
+    }
+
+    let total_size: usize = items.iter().filter_map(payload).map(|(p, _)| p.len()).sum();
+    if total_size <= max_bytes {
+        return None;
+    }
+
+    let mut remaining = max_bytes;
+    for item in items.iter_mut() {
+        let Some((text, is_blob)) = payload(item) else { continue };
+        let kept = if text.len() <= remaining {
+            text.len()
+        } else if is_blob {
+            remaining - remaining % 4
+        } else {
+            let mut cut = remaining;
+            while !text.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            text[..cut].rfind('\n').map(|i| i + 1).unwrap_or(cut)
+        };
+        remaining -= kept;
+
+        let truncated = Value::String(text[..kept].to_string());
+        if is_blob {
+            item["resource"]["blob"] = truncated;
+        } else {
+            item["text"] = truncated;
+        }
+    }
+
+    items.push(json!({
+        "type": "text",
+        "text": format!(
+            "[Output truncated: returned {} of {} bytes. Use read_range or read_lines to fetch the rest.]",
+            max_bytes - remaining,
+            total_size
+        )
+    }));
+    if let Some(result) = result.as_object_mut() {
+        result.remove("structuredContent");
+    }
+    result["truncated"] = json!(true);
+    result["total_size"] = json!(total_size);
+    Some(total_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::AuditEvent;
+    use crate::usage::UsageReport;
+    #[cfg(feature = "image")]
+    use base64::Engine;
+    use tempfile::TempDir;
+    use std::fs;
+
+    #[test]
+    #[allow(clippy::len_zero)]
+    fn test_mcp_server_new() {
+        let policy = AccessPolicy::permissive();
+        let server = McpServer::new(policy);
+        assert!(server.list_tools().len() > 0);
+    }
+
+    #[test]
+    #[allow(clippy::len_zero)]
+    fn test_mcp_server_with_base_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let server = McpServer::new(policy);
+        assert!(server.list_tools().len() > 0);
+    }
+
+    #[test]
+    fn test_list_tools() {
+        let policy = AccessPolicy::permissive();
+        let server = McpServer::new(policy);
+        let tools = server.list_tools();
+        
+        let optional = if cfg!(feature = "git") { 5 } else { 0 }
+            + if cfg!(feature = "index") { 1 } else { 0 }
+            + if cfg!(feature = "image") { 1 } else { 0 };
+        assert_eq!(tools.len(), 37 + optional);
+        assert!(tools.iter().any(|t| t.name == "read_file"));
+        assert!(tools.iter().any(|t| t.name == "write_file"));
+        assert!(tools.iter().any(|t| t.name == "list_directory"));
+        assert!(tools.iter().any(|t| t.name == "get_metadata"));
+        assert!(tools.iter().any(|t| t.name == "delete_file"));
+        assert!(tools.iter().any(|t| t.name == "move_file"));
+        assert!(tools.iter().any(|t| t.name == "copy_file"));
+        assert!(tools.iter().any(|t| t.name == "append_file"));
+        assert!(tools.iter().any(|t| t.name == "file_exists"));
+        assert!(tools.iter().any(|t| t.name == "create_directory"));
+        assert!(tools.iter().any(|t| t.name == "remove_directory"));
+        assert!(tools.iter().any(|t| t.name == "read_lines"));
+        assert!(tools.iter().any(|t| t.name == "search_files"));
+        assert!(tools.iter().any(|t| t.name == "grep_file"));
+        assert!(tools.iter().any(|t| t.name == "read_range"));
+        assert!(tools.iter().any(|t| t.name == "write_range"));
+        assert!(tools.iter().any(|t| t.name == "lock_file"));
+        assert!(tools.iter().any(|t| t.name == "unlock_file"));
+    }
+
+    #[test]
+    fn test_tools_list_titles_follow_config_locale() {
+        let mut config = Config::permissive();
+        config.server.locale = Some("de-DE".to_string());
+        config.tool_metadata = serde_json::from_value(json!({
+            "read_file": {"locales": {"de": {"title": "Datei lesen", "description": "Liest eine Datei"}}}
+        }))
+        .unwrap();
+        let server = McpServer::from_config(config);
+
+        let response: Value = serde_json::from_str(&server.process_request(r#"{"jsonrpc":"2.0","method":"tools/list","id":1}"#)).unwrap();
+        let tools = response["result"]["tools"].as_array().unwrap();
+        let tool = |name: &str| tools.iter().find(|tool| tool["name"] == name).unwrap().clone();
+        assert_eq!(tool("read_file")["title"], "Datei lesen");
+        assert_eq!(tool("read_file")["description"], "Liest eine Datei");
+        assert_eq!(tool("write_file")["title"], "Write a file");
+        assert_eq!(tool("write_file")["description"], "Write contents to a file");
+    }
+
+    #[test]
+    fn test_clones_share_state_across_threads() {
+        fn assert_handle<T: Send + Sync + Clone + 'static>() {}
+        assert_handle::<McpServer>();
+
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("shared.txt");
+        let mut config = Config::permissive();
+        config.access_policy.allowed_paths = vec![temp_dir.path().to_path_buf()];
+        let server = McpServer::from_config(config.clone());
+        let args = json!({"path": file_path.to_str().unwrap(), "content": "x"});
+
+        let workers: Vec<_> = (0..4).map(|_| {
+            let server = server.clone();
+            let args = args.clone();
+            std::thread::spawn(move || server.handle_tool_call("write_file", args).is_ok())
+        }).collect();
+        assert!(workers.into_iter().all(|worker| worker.join().unwrap()));
+
+        // A reload through one handle applies to every clone
+        let clone = server.clone();
+        config.access_policy.read_only = true;
+        server.reload(config);
+        assert!(clone.handle_tool_call("write_file", args).is_err());
+        assert!(Arc::ptr_eq(&clone.metrics(), &server.metrics()));
+    }
+
+    #[test]
+    fn test_reload_swaps_policy_and_keeps_locks() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("notes.txt");
+        let mut config = Config::permissive();
+        config.access_policy.allowed_paths = vec![temp_dir.path().to_path_buf()];
+        let server = McpServer::from_config(config.clone());
+
+        let lock = server.handle_tool_call("lock_file", json!({"path": file_path.to_str().unwrap()})).unwrap();
+        let lock: Value = serde_json::from_str(lock["content"][0]["text"].as_str().unwrap()).unwrap();
+        let token = lock["token"].as_str().unwrap().to_string();
+        let write_args = json!({
+            "path": file_path.to_str().unwrap(),
+            "content": "v1",
+            "lock_token": token,
+        });
+        assert!(server.handle_tool_call("write_file", write_args.clone()).is_ok());
+
+        config.access_policy.read_only = true;
+        assert!(!server.reload(config.clone()));
+        assert!(server.handle_tool_call("write_file", write_args.clone()).is_err());
+        assert!(server.handle_tool_call("read_file", json!({"path": file_path.to_str().unwrap()})).is_ok());
+
+        // The lock survived the reload
+        config.access_policy.read_only = false;
+        server.reload(config);
+        assert!(server.handle_tool_call("write_file", json!({
+            "path": file_path.to_str().unwrap(),
+            "content": "v2",
+        })).is_err());
+        assert!(server.handle_tool_call("write_file", write_args).is_ok());
+    }
+
+    #[test]
+    fn test_body_logging_switch() {
+        let mut config = Config::permissive();
+        let server = McpServer::from_config(config.clone());
+        assert!(server.logs_bodies());
+
+        config.logging.log_bodies = false;
+        server.reload(config);
+        assert!(!server.logs_bodies());
+        assert!(!McpServer::new(AccessPolicy::permissive()).with_body_logging(false).logs_bodies());
+    }
+
+    #[test]
+    fn test_builder_components() {
+        #[derive(Default)]
+        struct Recorder(std::sync::Mutex<Vec<(String, bool)>>);
+        impl AuditLog for Recorder {
+            fn record(&self, event: &AuditEvent<'_>) {
+                self.0.lock().unwrap().push((event.tool.to_string(), event.success));
+            }
+        }
+
+        let fs = Arc::new(crate::vfs::MemoryFileSystem::new());
+        fs.insert_file("/work/notes.txt", "in memory").unwrap();
+        let recorder = Arc::new(Recorder::default());
+        let server = McpServer::builder()
+            .policy(AccessPolicy::restricted(PathBuf::from("/work")))
+            .filesystem(fs)
+            .audit_log(Arc::clone(&recorder) as Arc<dyn AuditLog>)
+            .disable_tool("delete_file")
+            .enable_tool("get_metrics")
+            .build();
+
+        let names = server.tool_names();
+        assert!(names.contains(&"get_metrics".to_string()));
+        assert!(!names.contains(&"delete_file".to_string()));
+        let read = r#"{"jsonrpc":"2.0","method":"tools/call","params":{"name":"read_file","arguments":{"path":"/work/notes.txt"}},"id":1}"#;
+        assert!(server.process_request(read).contains("in memory"));
+        let delete = r#"{"jsonrpc":"2.0","method":"tools/call","params":{"name":"delete_file","arguments":{"path":"/work/notes.txt"}},"id":2}"#;
+        assert!(server.process_request(delete).contains("error"));
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [("read_file".to_string(), true), ("delete_file".to_string(), false)]
+        );
+
+        // The chosen file system survives a reload
+        let mut config = Config::default_restricted(PathBuf::from("/work"));
+        config.server.requests_per_second = 50;
+        server.reload(config);
+        assert!(server.process_request(read).contains("in memory"));
+    }
+
+    #[test]
+    fn test_middleware_chain() {
+        /// Lets clients write `~/` for the project directory, and marks results
+        struct HomeAlias;
+        impl Middleware for HomeAlias {
+            fn before_call(&self, _: &Call<'_>, params: &mut Value) -> Result<Option<Value>> {
+                if let Some(path) = params["path"].as_str().and_then(|path| path.strip_prefix("~/")) {
+                    params["path"] = json!(format!("/work/{}", path));
+                }
+                Ok(None)
+            }
+            fn after_call(&self, call: &Call<'_>, _: &Value, result: &mut Value) -> Result<()> {
+                if call.tool().is_some() {
+                    result["_meta"] = json!({"via": "alias"});
+                }
+                Ok(())
+            }
+        }
+        #[derive(Default)]
+        struct Recorder(std::sync::Mutex<Vec<Value>>);
+        impl AuditLog for Recorder {
+            fn record(&self, event: &AuditEvent<'_>) {
+                self.0.lock().unwrap().push(event.arguments.clone());
+            }
+        }
+
+        let fs = Arc::new(crate::vfs::MemoryFileSystem::new());
+        fs.insert_file("/work/notes.txt", "in memory").unwrap();
+        let recorder = Arc::new(Recorder::default());
+        let server = McpServer::builder()
+            .policy(AccessPolicy::restricted(PathBuf::from("/work")))
+            .filesystem(fs)
+            .rate_limiter(RateLimiter::new(2))
+            .audit_log(Arc::clone(&recorder) as Arc<dyn AuditLog>)
+            .middleware(Arc::new(HomeAlias))
+            .build();
+
+        let read = r#"{"jsonrpc":"2.0","method":"tools/call","params":{"name":"read_file","arguments":{"path":"~/notes.txt"}},"id":1}"#;
+        let response: Value = serde_json::from_str(&server.process_request(read)).unwrap();
+        assert_eq!(response["result"]["content"][0]["text"], "in memory");
+        assert_eq!(response["result"]["_meta"]["via"], "alias");
+        // The audit log records the arguments the tool ran with
+        assert_eq!(*recorder.0.lock().unwrap(), [json!({"path": "/work/notes.txt"})]);
+
+        // Refused by the rate limiter before reaching the audit log
+        server.process_request(read);
+        let response: Value = serde_json::from_str(&server.process_request(read)).unwrap();
+        assert_eq!(response["error"]["message"], "Rate limit exceeded. Please slow down requests.");
+        assert!(response["error"]["data"]["retry_after_ms"].as_u64().is_some_and(|ms| ms > 0));
+        assert_eq!(response["id"], 1);
+        assert_eq!(recorder.0.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_register_custom_tool() {
+        struct Shout;
+        impl ToolHandler for Shout {
+            fn name(&self) -> &str {
+                "shout"
+            }
+            fn description(&self) -> &str {
+                "Read a file in upper case"
+            }
+            fn input_schema(&self) -> Value {
+                json!({"type": "object", "properties": {"path": {"type": "string"}}})
+            }
+            fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<Value> {
+                let path = arguments["path"].as_str().unwrap_or_default();
+                Ok(crate::tools::text_result(ctx.reader().read_to_string(path)?.to_uppercase()))
+            }
+        }
+        struct Impostor;
+        impl ToolHandler for Impostor {
+            fn name(&self) -> &str {
+                "read_file"
+            }
+            fn description(&self) -> &str {
+                ""
+            }
+            fn input_schema(&self) -> Value {
+                json!({})
+            }
+            fn call(&self, _: &ToolContext<'_>, _: Value) -> Result<Value> {
+                unreachable!()
+            }
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("a.txt");
+        fs::write(&path, "quiet").unwrap();
+        let server = McpServer::builder()
+            .policy(AccessPolicy::restricted(temp_dir.path().to_path_buf()))
+            .tool(Arc::new(Shout))
+            .build();
+
+        assert!(server.tool_names().contains(&"shout".to_string()));
+        let result = server.handle_tool_call("shout", json!({"path": path})).unwrap();
+        assert_eq!(result["content"][0]["text"], "QUIET");
+        // Custom tools get the same access policy as built-in ones
+        assert!(matches!(
+            server.handle_tool_call("shout", json!({"path": "/etc/passwd"})),
+            Err(FileJackError::PermissionDenied(_))
+        ));
+        assert!(server.register_tool(Arc::new(Shout)).is_err());
+        assert!(server.register_tool(Arc::new(Impostor)).is_err());
+    }
+
+    #[test]
+    fn test_panic_becomes_internal_error() {
+        struct Broken;
+        impl ToolHandler for Broken {
+            fn name(&self) -> &str {
+                "broken"
+            }
+            fn description(&self) -> &str {
+                "Always panics"
+            }
+            fn input_schema(&self) -> Value {
+                json!({"type": "object"})
+            }
+            fn call(&self, _: &ToolContext<'_>, _: Value) -> Result<Value> {
+                panic!("tool bug")
+            }
+        }
+
+        let server = McpServer::builder().policy(AccessPolicy::permissive()).tool(Arc::new(Broken)).build();
+        let response: Value = serde_json::from_str(&server.process_request(
+            r#"{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{"name":"broken","arguments":{}}}"#,
+        ))
+        .unwrap();
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["error"]["code"], -32603);
+        assert_eq!(response["error"]["message"], "Internal error");
+
+        let notification = r#"{"jsonrpc":"2.0","method":"tools/call","params":{"name":"broken"}}"#;
+        assert_eq!(server.process_request(notification), "");
+        // The server keeps answering after a panic
+        let response = server.process_request(r#"{"jsonrpc":"2.0","id":8,"method":"tools/list"}"#);
+        assert!(response.contains("broken"));
+    }
+
+    #[test]
+    fn test_ping_and_cancellation() {
+        let server = McpServer::new(AccessPolicy::permissive());
+        let response: Value = serde_json::from_str(&server.process_request(r#"{"jsonrpc":"2.0","id":"p","method":"ping"}"#)).unwrap();
+        assert_eq!(response["id"], "p");
+        assert_eq!(response["result"], json!({}));
+
+        let cancel = r#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":3,"reason":"user"}}"#;
+        assert_eq!(server.process_request(cancel), "");
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_plugin_tools_from_config() {
+        let temp_dir = tempfile::Builder::new().prefix("plugins").tempdir().unwrap();
+        let directory = temp_dir.path().join("plugins");
+        fs::create_dir(&directory).unwrap();
+        // Offers `hello`, answering "hi", and a `read_file` that must not
+        // replace the built-in tool
+        let tools = r#"[{\"name\":\"hello\"},{\"name\":\"read_file\"}]"#;
+        let module = format!(
+            r#"(module
+  (memory (export "memory") 1)
+  (data (i32.const 0) "{tools}")
+  (data (i32.const 1024) "hi")
+  (func (export "filejack_alloc") (param i32) (result i32) (i32.const 2048))
+  (func (export "filejack_tools") (result i64) (i64.const {len}))
+  (func (export "filejack_call") (param i32 i32 i32 i32) (result i64)
+    (i64.or (i64.shl (i64.const 1024) (i64.const 32)) (i64.const 2))))"#,
+            tools = tools,
+            len = tools.replace('\\', "").len(),
+        );
+        fs::write(directory.join("greeter.wat"), module).unwrap();
+
+        let mut config = Config::default_restricted(temp_dir.path().to_path_buf());
+        config.plugins = Some(crate::config::PluginsConfig { directory, ..Default::default() });
+        let server = McpServer::from_config(config);
+        let names = server.tool_names();
+        assert!(names.contains(&"hello".to_string()));
+        assert_eq!(names.iter().filter(|name| *name == "read_file").count(), 1);
+
+        let result = server.handle_tool_call("hello", json!({})).unwrap();
+        assert_eq!(result["content"][0]["text"], "hi");
+        let path = temp_dir.path().join("a.txt");
+        fs::write(&path, "built in").unwrap();
+        let result = server.handle_tool_call("read_file", json!({"path": path})).unwrap();
+        assert_eq!(result["content"][0]["text"], "built in");
+    }
+
+    #[test]
+    fn test_metrics_tool() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("data.txt");
+        let server = McpServer::new(AccessPolicy::permissive());
+        assert!(!server.tool_names().contains(&"get_metrics".to_string()));
+        assert!(server.handle_tool_call("get_metrics", json!({})).is_err());
+
+        let server = server.with_metrics_tool(true);
+        assert!(server.tool_names().contains(&"get_metrics".to_string()));
+
+        let call = |name: &str, arguments: Value| {
+            let request = json!({
+                "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+                "params": {"name": name, "arguments": arguments}
+            });
+            serde_json::from_str::<Value>(&server.process_request(&request.to_string())).unwrap()
+        };
+        call("write_file", json!({"path": file_path.to_str().unwrap(), "content": "12345"}));
+        call("read_file", json!({"path": file_path.to_str().unwrap(), "encoding": "base64"}));
+        call("no_such_tool", json!({}));
+
+        let response = call("get_metrics", json!({}));
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("filejack_tool_calls_total{tool=\"write_file\"} 1"), "{}", text);
+        assert!(text.contains("filejack_tool_errors_total{tool=\"unknown\"} 1"), "{}", text);
+        assert!(text.contains("filejack_bytes_written_total 5"), "{}", text);
+        assert!(text.contains("filejack_bytes_read_total 5"), "{}", text);
+    }
+
+    #[test]
+    fn test_server_info() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("a.txt");
+        let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        policy.denied_paths.push(temp_dir.path().join("private"));
+        let server = McpServer::new(policy);
+
+        let request = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": {"name": "write_file", "arguments": {"path": file_path.to_str().unwrap(), "content": "abc"}}
+        });
+        server.process_request(&request.to_string());
+        server.process_request(&request.to_string());
+
+        let result = server.handle_tool_call("server_info", json!({})).unwrap();
+        let info: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(info["operations"]["write_file"], 2);
+        assert_eq!(info["bytes_written"], 6);
+        assert_eq!(info["bytes_transferred"], 6);
+        assert_eq!(info["policy"]["read_only"], false);
+        assert_eq!(info["policy"]["denied_path_count"], 1);
+        assert_eq!(info["policy"]["allowed_paths"], json!([temp_dir.path()]));
+        assert!(info["uptime_seconds"].is_u64());
+    }
+
+    #[test]
+    fn test_sessions_are_independent() {
+        let server = McpServer::with_rate_limiter(AccessPolicy::permissive(), RateLimiter::new(2));
+        let first = server.open_session("first");
+        let second = server.open_session("second");
+
+        let initialize = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": {"protocolVersion": "2025-03-26", "clientInfo": {"name": "editor"}, "capabilities": {}}
+        });
+        server.process_session_request(&first, &initialize.to_string());
+        server.process_session_request(&first, r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#);
+        assert!(first.is_initialized());
+        assert_eq!(first.client().unwrap().name.as_deref(), Some("editor"));
+        assert!(!second.is_initialized());
+        assert!(second.client().is_none());
+        assert!(server.default_session().client().is_none());
+
+        // The first session has spent its budget of two; the others have not
+        let response = server.process_session_request(&first, r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#);
+        assert!(response.contains("Rate limit exceeded"));
+        let response = server.process_session_request(&second, r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#);
+        assert!(response.contains("read_file"));
+        let response = server.process_request(r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#);
+        assert!(response.contains("read_file"));
+    }
+
+    #[test]
+    fn test_client_tools_chosen_at_initialize() {
+        let mut config = Config::permissive();
+        config.client_tools = serde_json::from_value(json!({
+            "claude-desktop": {"allow": ["read_*", "list_directory", "get_*"], "deny": ["get_metrics"]},
+            "*": {"deny": ["delete_file"]}
+        }))
+        .unwrap();
+        let server = McpServer::from_config(config);
+        let initialize = |session: &Session, name: &str| {
+            let request = json!({
+                "jsonrpc": "2.0", "id": 1, "method": "initialize",
+                "params": {"protocolVersion": "2025-03-26", "clientInfo": {"name": name}, "capabilities": {}}
+            });
+            server.process_session_request(session, &request.to_string());
+        };
+        let tools = |session: &Session| -> Vec<String> {
+            let response: Value = serde_json::from_str(
+                &server.process_session_request(session, r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#),
+            )
+            .unwrap();
+            response["result"]["tools"].as_array().unwrap().iter().map(|tool| tool["name"].as_str().unwrap().to_string()).collect()
+        };
+
+        let desktop = server.open_session("desktop");
+        initialize(&desktop, "claude-desktop");
+        let offered = tools(&desktop);
+        assert!(offered.contains(&"read_file".to_string()) && offered.contains(&"get_metadata".to_string()));
+        assert!(!offered.iter().any(|tool| tool == "write_file" || tool == "get_metrics"));
+        let call = r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"write_file","arguments":{"path":"/tmp/x","content":""}}}"#;
+        assert!(server.process_session_request(&desktop, call).contains("Tool not found: write_file"));
+
+        let agent = server.open_session("agent");
+        initialize(&agent, "internal-agent");
+        let offered = tools(&agent);
+        assert!(offered.contains(&"write_file".to_string()));
+        assert!(!offered.contains(&"delete_file".to_string()));
+    }
+
+    #[test]
+    fn test_operation_history_is_kept_per_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let notes = temp_dir.path().join("notes.txt");
+        let server = McpServer::new(AccessPolicy::permissive());
+        let first = server.open_session("first");
+        let second = server.open_session("second");
+        let call = |session: &Session, name: &str, arguments: Value| {
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": name, "arguments": arguments}});
+            let response: Value = serde_json::from_str(&server.process_session_request(session, &request.to_string())).unwrap();
+            response["result"]["content"][0]["text"].as_str().unwrap_or_default().to_string()
+        };
+
+        call(&first, "write_file", json!({"path": notes, "content": "one\n"}));
+        call(&first, "read_file", json!({"path": notes}));
+        call(&first, "edit_lines", json!({"path": notes, "operations": [{"op": "insert_at", "line": 2, "content": "two"}]}));
+        call(&first, "delete_file", json!({"path": temp_dir.path().join("missing.txt")}));
+
+        let history: Value = serde_json::from_str(&call(&first, "get_operation_history", json!({"diffs": true}))).unwrap();
+        let tools: Vec<_> = history.as_array().unwrap().iter().map(|entry| entry["tool"].as_str().unwrap()).collect();
+        assert_eq!(tools, vec!["write_file", "edit_lines"]);
+        assert_eq!(history[0]["changes"][0]["size_before"], Value::Null);
+        assert_eq!(history[0]["changes"][0]["diff"], "@@ -0,0 +1,1 @@\n+one\n");
+        assert_eq!(history[1]["changes"][0]["size_after"], 8);
+        assert_eq!(history[1]["changes"][0]["diff"], "@@ -1,1 +1,2 @@\n one\n+two\n");
+
+        let latest: Value = serde_json::from_str(&call(&first, "get_operation_history", json!({"limit": 1}))).unwrap();
+        assert_eq!(latest.as_array().unwrap().len(), 1);
+        assert!(latest[0]["changes"][0].get("diff").is_none());
+        assert_eq!(call(&second, "get_operation_history", json!({})), "[]");
+    }
+
+    #[test]
+    fn test_transactions_apply_all_or_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let notes = temp_dir.path().join("notes.txt");
+        let created = temp_dir.path().join("drafts/plan.txt");
+        fs::write(&notes, "one\n").unwrap();
+        let server = McpServer::new(AccessPolicy::permissive());
+        let session = server.open_session("agent");
+        let call = |name: &str, arguments: Value| {
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": name, "arguments": arguments}});
+            let response: Value = serde_json::from_str(&server.process_session_request(&session, &request.to_string())).unwrap();
+            match response.get("error") {
+                Some(error) => Err(error["message"].as_str().unwrap().to_string()),
+                None => Ok(response["result"]["content"][0]["text"].as_str().unwrap().to_string()),
+            }
+        };
+
+        assert!(call("commit", json!({})).unwrap_err().contains("No transaction is open"));
+        call("begin_transaction", json!({})).unwrap();
+        assert!(call("begin_transaction", json!({})).is_err());
+        let staged: Value = serde_json::from_str(&call("append_file", json!({"path": notes, "content": "two\n"})).unwrap()).unwrap();
+        assert_eq!(staged["position"], 1);
+        call("write_file", json!({"path": created, "content": "draft"})).unwrap();
+        // Nothing happens until the commit
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "one\n");
+        assert!(!created.exists());
+
+        let applied: Value = serde_json::from_str(&call("commit", json!({})).unwrap()).unwrap();
+        assert_eq!(applied.as_array().unwrap().len(), 2);
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "one\ntwo\n");
+        assert_eq!(fs::read_to_string(&created).unwrap(), "draft");
+        assert_eq!(session.journal().entries(None, false).len(), 2);
+
+        // A failing call undoes the ones before it
+        call("begin_transaction", json!({})).unwrap();
+        call("write_file", json!({"path": notes, "content": "replaced"})).unwrap();
+        call("delete_file", json!({"path": temp_dir.path().join("missing.txt")})).unwrap();
+        let error = call("commit", json!({})).unwrap_err();
+        assert!(error.contains("Call 2 (delete_file) failed"), "{}", error);
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "one\ntwo\n");
+        assert_eq!(session.journal().entries(None, false).len(), 2);
+        assert!(!session.in_transaction());
+
+        // A file changed by someone else since it was staged is a conflict
+        call("begin_transaction", json!({})).unwrap();
+        call("write_file", json!({"path": notes, "content": "mine"})).unwrap();
+        fs::write(&notes, "theirs").unwrap();
+        assert!(call("commit", json!({})).unwrap_err().contains("Conflict"));
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "theirs");
+
+        call("begin_transaction", json!({})).unwrap();
+        assert!(call("remove_directory", json!({"path": temp_dir.path().join("drafts")})).is_err());
+        call("delete_file", json!({"path": created})).unwrap();
+        assert_eq!(call("rollback", json!({})).unwrap(), "Discarded 1 staged change(s)");
+        assert!(created.exists());
+    }
+
+    #[test]
+    fn test_claimed_paths_can_only_be_changed_by_their_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let notes = temp_dir.path().join("notes.txt");
+        let server = McpServer::new(AccessPolicy::permissive());
+        let first = server.open_session("first");
+        let second = server.open_session("second");
+        let call = |session: &Session, name: &str, arguments: Value| {
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": name, "arguments": arguments}});
+            let response: Value = serde_json::from_str(&server.process_session_request(session, &request.to_string())).unwrap();
+            match response.get("error") {
+                Some(error) => Err(error["message"].as_str().unwrap().to_string()),
+                None => Ok(response["result"]["content"][0]["text"].as_str().unwrap().to_string()),
+            }
+        };
+
+        let claim: Value = serde_json::from_str(&call(&first, "claim_path", json!({"path": temp_dir.path(), "ttl_seconds": 30})).unwrap()).unwrap();
+        assert_eq!(claim["expires_in_seconds"], 30);
+        call(&first, "write_file", json!({"path": notes, "content": "mine"})).unwrap();
+        let error = call(&second, "write_file", json!({"path": notes, "content": "theirs"})).unwrap_err();
+        assert!(error.contains("claimed by other session first"), "{}", error);
+        assert!(call(&second, "claim_path", json!({"path": notes})).is_err());
+        assert!(call(&second, "release_path", json!({"path": temp_dir.path()})).is_err());
+        // Reading is not affected
+        call(&second, "read_file", json!({"path": notes})).unwrap();
+
+        call(&first, "release_path", json!({"path": temp_dir.path()})).unwrap();
+        call(&second, "write_file", json!({"path": notes, "content": "theirs"})).unwrap();
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "theirs");
+    }
+
+    #[test]
+    fn test_tail_follow_sends_appended_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = temp_dir.path().join("app.log");
+        fs::write(&log, "before\n").unwrap();
+        let server = McpServer::new(AccessPolicy::permissive());
+        let session = server.open_session("watcher");
+        let call = |name: &str, arguments: Value| {
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": name, "arguments": arguments}});
+            serde_json::from_str::<Value>(&server.process_session_request(&session, &request.to_string())).unwrap()
+        };
+        assert!(call("tail_follow", json!({"path": log}))["error"]["message"].as_str().unwrap().contains("cannot send notifications"));
+
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&sent);
+        session.set_notifier(move |message| sink.lock().unwrap().push(message));
+        let response = call("tail_follow", json!({"path": log, "interval_ms": 50}));
+        let info: Value = serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(info["offset"], 7);
+        fs::write(&log, "before\nafter\n").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while sent.lock().unwrap().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
         }
+        let message: Value = serde_json::from_str(&sent.lock().unwrap()[0]).unwrap();
+        assert_eq!(message["method"], "notifications/message");
+        assert_eq!(message["params"]["data"]["lines"], json!(["after"]));
+        assert_eq!(message["params"]["data"]["follower"], info["follower"]);
+
+        assert!(call("tail_cancel", json!({"follower": info["follower"]}))["result"].is_object());
+        assert!(call("tail_cancel", json!({"follower": info["follower"]}))["error"].is_object());
     }
 
-    /// Handle a JSON-RPC request
-    pub fn handle_request(&self, request: JsonRpcRequest) -> JsonRpcResponse {
-        debug!(method = %request.method, id = ?request.id, "Handling request");
-        
-        match request.method.as_str() {
-            "tools/list" => {
-                debug!("Listing available tools");
-                let tools = self.list_tools();
-                let tools_value = serde_json::to_value(&tools).unwrap();
-                JsonRpcResponse::success(request.id, json!({"tools": tools_value}))
+    #[test]
+    fn test_relative_paths_use_the_session_working_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("project");
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::write(project.join("src/main.rs"), "fn main() {}").unwrap();
+        let server = McpServer::new(AccessPolicy::restricted(project.clone()));
+        let first = server.open_session("first");
+        let second = server.open_session("second");
+        let call = |session: &Session, name: &str, arguments: Value| {
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": name, "arguments": arguments}});
+            let response: Value = serde_json::from_str(&server.process_session_request(session, &request.to_string())).unwrap();
+            match response["result"]["content"][0]["text"].as_str() {
+                Some(text) => Ok(text.to_string()),
+                None => Err(response["error"]["message"].as_str().unwrap_or_default().to_string()),
             }
-            "tools/call" => {
-                let params = request.params.unwrap_or(json!({}));
-                
-                debug!("tools/call received params: {}", params);
-                
-                let tool_name = params.get("name")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("");
-                
-                let arguments = params.get("arguments")
-                    .cloned()
-                    .unwrap_or(json!({}));
-                
-                debug!("Extracted tool_name: '{}', arguments: {}", tool_name, arguments);
+        };
 
-                match self.handle_tool_call(tool_name, arguments) {
-                    Ok(result) => {
-                        info!(tool = tool_name, "Tool call successful");
-                        JsonRpcResponse::success(request.id, result)
-                    }
-                    Err(e) => {
-                        error!(tool = tool_name, error = %e, "Tool call failed");
-                        JsonRpcResponse::error(
-                            request.id,
-                            -32000,
-                            e.to_string(),
-                        )
-                    }
-                }
-            }
-            "initialize" => {
-                info!("Server initialized");
-                JsonRpcResponse::success(
-                    request.id,
-                    json!({
-                        "protocolVersion": "1.0",
-                        "serverInfo": {
-                            "name": "FileJack",
-                            "version": "0.1.0"
-                        },
-                        "capabilities": {
-                            "tools": {}
-                        }
-                    }),
-                )
-            }
-            _ => {
-                warn!(method = %request.method, "Method not found");
-                JsonRpcResponse::error(
-                    request.id,
-                    -32601,
-                    format!("Method not found: {}", request.method),
-                )
-            }
-        }
+        // Relative to the first allowed root until changed
+        assert_eq!(call(&first, "get_working_directory", json!({})).unwrap(), project.display().to_string());
+        assert_eq!(call(&first, "read_file", json!({"path": "src/main.rs"})).unwrap(), "fn main() {}");
+
+        let src = call(&first, "set_working_directory", json!({"path": "src"})).unwrap();
+        assert_eq!(Path::new(&src), project.join("src").canonicalize().unwrap());
+        call(&first, "copy_file", json!({"from": "main.rs", "to": "../lib.rs"})).unwrap();
+        assert!(project.join("lib.rs").exists());
+        assert!(call(&first, "set_working_directory", json!({"path": "main.rs"})).is_err());
+        assert!(call(&first, "set_working_directory", json!({"path": "../.."})).is_err());
+
+        // Each session has its own
+        assert_eq!(call(&second, "read_file", json!({"path": "lib.rs"})).unwrap(), "fn main() {}");
+        assert!(call(&second, "read_file", json!({"path": "main.rs"})).is_err());
     }
 
-    /// Process a JSON-RPC request from a string
-    pub fn process_request(&self, request_str: &str) -> String {
-        // Check rate limit
-        if !self.rate_limiter.check() {
-            warn!("Rate limit exceeded");
-            let error_response = JsonRpcResponse::error(
-                None,
-                -32000,
-                "Rate limit exceeded. Please slow down requests.".to_string(),
-            );
-            return serde_json::to_string(&error_response).unwrap();
-        }
+    #[test]
+    fn test_session_usage_report() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("project");
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join("a.txt"), "hello").unwrap();
+        let report_file = temp_dir.path().join("usage.jsonl");
+        let mut config = Config::default_restricted(project.clone());
+        config.server.usage_report = Some(report_file.clone());
+        let server = McpServer::from_config(config);
+        let session = server.open_session("agent");
+        let call = |name: &str, arguments: Value| {
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": name, "arguments": arguments}});
+            serde_json::from_str::<Value>(&server.process_session_request(&session, &request.to_string())).unwrap()
+        };
 
-        match serde_json::from_str::<JsonRpcRequest>(request_str) {
-            Ok(request) => {
-                // JSON-RPC 2.0: If id is None, it's a notification and should not be responded to
-                if request.id.is_none() {
-                    // For notifications, we still process them but return empty string
-                    // (or could return empty to indicate no response needed)
-                    self.handle_request(request);
-                    return String::new();
-                }
-                
-                let response = self.handle_request(request);
-                serde_json::to_string(&response).unwrap()
-            }
-            Err(e) => {
-                error!("Failed to parse request: {}", e);
-                let error_response = JsonRpcResponse::error(
-                    None,
-                    -32700,
-                    format!("Parse error: {}", e),
-                );
-                serde_json::to_string(&error_response).unwrap()
-            }
-        }
+        call("read_file", json!({"path": project.join("a.txt")}));
+        call("copy_file", json!({"from": project.join("a.txt"), "to": project.join("b.txt")}));
+        call("delete_file", json!({"path": project.join("a.txt")}));
+        assert!(call("read_file", json!({"path": "/etc/passwd"}))["error"].is_object());
+
+        let text = call("get_session_usage", json!({}))["result"]["content"][0]["text"].as_str().unwrap().to_string();
+        let usage: UsageReport = serde_json::from_str(&text).unwrap();
+        assert_eq!((usage.calls, usage.errors, usage.denials), (4, 1, 1));
+        let path = |name: &str| project.join(name).display().to_string();
+        assert_eq!(usage.files_read, [path("a.txt")]);
+        assert_eq!(usage.files_written, [path("b.txt")]);
+        assert_eq!(usage.files_deleted, [path("a.txt")]);
+        assert_eq!((usage.bytes_read, usage.bytes_written), (5, 5));
+
+        // Reported once, and the default session made no calls
+        server.report_usage(&session);
+        server.report_all_usage();
+        let lines = fs::read_to_string(&report_file).unwrap();
+        assert_eq!(lines.lines().count(), 1);
+        let reported: UsageReport = serde_json::from_str(lines.trim()).unwrap();
+        assert_eq!((reported.session.as_str(), reported.calls), ("agent", 5));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
-    use std::fs;
+    #[test]
+    fn test_aliases_hide_host_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("project");
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join("notes.txt"), "hello").unwrap();
+        let config = Config {
+            access_policy: AccessPolicy::restricted(project.clone()),
+            aliases: BTreeMap::from([("@work".to_string(), project.clone())]),
+            ..Default::default()
+        };
+        let server = McpServer::from_config(config);
+        let call = |name: &str, arguments: Value| {
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": name, "arguments": arguments}});
+            server.process_request(&request.to_string())
+        };
+        let host = project.display().to_string();
+
+        let response = call("read_file", json!({"path": "@work/notes.txt"}));
+        assert!(response.contains("hello"), "{}", response);
+        let response = call("list_directory", json!({"path": "@work"}));
+        assert!(response.contains("@work/notes.txt") && !response.contains(&host), "{}", response);
+        let response = call("server_info", json!({}));
+        assert!(response.contains("@work") && !response.contains(&host), "{}", response);
+        let response = call("read_file", json!({"path": "@work/missing.txt"}));
+        assert!(response.contains("error") && !response.contains(&host), "{}", response);
+    }
 
     #[test]
-    fn test_mcp_server_new() {
-        let policy = AccessPolicy::permissive();
+    fn test_redact_roots_strips_allowed_roots() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("project");
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join("notes.txt"), format!("see {}", project.display())).unwrap();
+        let mut policy = AccessPolicy::restricted(project.clone());
+        policy.redact_roots = crate::access_control::RootRedaction::Strip;
         let server = McpServer::new(policy);
-        assert!(server.list_tools().len() > 0);
+        let call = |name: &str, arguments: Value| {
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": name, "arguments": arguments}});
+            server.process_request(&request.to_string())
+        };
+        let host = project.display().to_string();
+
+        let response = call("list_directory", json!({"path": "."}));
+        assert!(response.contains(r#"\"path\": \"notes.txt\""#) && !response.contains(&host), "{}", response);
+        let response = call("get_metadata", json!({"path": "missing.txt"}));
+        assert!(response.contains("missing.txt") && !response.contains(&host), "{}", response);
+        // File contents are returned as they are
+        assert!(call("read_file", json!({"path": "notes.txt"})).contains(&host));
     }
 
     #[test]
-    fn test_mcp_server_with_base_path() {
+    fn test_temp_workspaces_are_private_to_their_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("project");
+        fs::create_dir(&project).unwrap();
+        let mut config = Config::default();
+        config.access_policy = AccessPolicy::restricted(project);
+        config.workspaces = Some(crate::config::WorkspacesConfig {
+            root: temp_dir.path().join("scratch"),
+            ..Default::default()
+        });
+        let server = McpServer::from_config(config);
+        assert!(server.list_tools().iter().any(|tool| tool.name == "create_temp_workspace"));
+        let first = server.open_session("first");
+        let second = server.open_session("second");
+
+        let call = |session: &Session, name: &str, arguments: Value| {
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": name, "arguments": arguments}});
+            serde_json::from_str::<Value>(&server.process_session_request(session, &request.to_string())).unwrap()
+        };
+        let response = call(&first, "create_temp_workspace", json!({}));
+        let summary: Value = serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(summary["expires_in_secs"], 3600);
+        let workspace = PathBuf::from(summary["path"].as_str().unwrap());
+        let file = workspace.join("notes.txt").display().to_string();
+
+        let response = call(&first, "write_file", json!({"path": file, "content": "draft"}));
+        assert!(response.get("result").is_some(), "{}", response);
+        let response = call(&second, "read_file", json!({"path": file}));
+        assert!(response["error"]["message"].as_str().unwrap().contains("not in a temporary workspace"));
+        assert!(server.handle_tool_call("read_file", json!({"path": file})).is_err());
+
+        // Unrelated paths outside the allowed ones are still refused
+        let response = call(&first, "read_file", json!({"path": temp_dir.path().join("other.txt")}));
+        assert!(response.get("error").is_some());
+
+        server.reload(Config {
+            access_policy: AccessPolicy::restricted(temp_dir.path().join("project")),
+            ..Config::default()
+        });
+        assert_eq!(call(&first, "read_file", json!({"path": file}))["result"]["content"][0]["text"], "draft");
+
+        drop(server);
+        assert!(!workspace.exists());
+    }
+
+    #[test]
+    fn test_recursive_copy_reports_progress() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("site");
+        fs::create_dir_all(source.join("css")).unwrap();
+        fs::write(source.join("index.html"), "<html></html>").unwrap();
+        fs::write(source.join("css/style.css"), "body {}").unwrap();
+
+        let server = McpServer::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()));
+        let session = server.open_session("copier");
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&sent);
+        session.set_notifier(move |message| sink.lock().unwrap().push(message));
+
+        let request = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": {
+                "name": "copy_file",
+                "arguments": {"from": source, "to": temp_dir.path().join("backup"), "recursive": true},
+                "_meta": {"progressToken": "copy-1"}
+            }
+        });
+        let response: Value = serde_json::from_str(&server.process_session_request(&session, &request.to_string())).unwrap();
+        assert!(response["result"]["content"][0]["text"].as_str().unwrap().contains("(2 files, 1 directories, 20 bytes)"));
+        assert_eq!(fs::read_to_string(temp_dir.path().join("backup/css/style.css")).unwrap(), "body {}");
+
+        let progress: Vec<Value> = sent.lock().unwrap().iter().map(|message| serde_json::from_str(message).unwrap()).collect();
+        assert_eq!(progress.len(), 2);
+        assert_eq!(progress[1]["method"], "notifications/progress");
+        assert_eq!(progress[1]["params"], json!({"progressToken": "copy-1", "progress": 2, "total": 2}));
+    }
+
+    #[test]
+    fn test_initialize_advertises_list_changed() {
+        let server = McpServer::new(AccessPolicy::permissive());
+        let response = server.process_request(r#"{"jsonrpc":"2.0","id":1,"method":"initialize"}"#);
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["result"]["capabilities"]["tools"]["listChanged"], json!(true));
+
+        let notification: Value = serde_json::from_str(&McpServer::tools_list_changed_notification()).unwrap();
+        assert_eq!(notification["method"], "notifications/tools/list_changed");
+        assert!(notification.get("id").is_none());
+    }
+
+    #[test]
+    fn test_handle_tool_call_range_roundtrip() {
         let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("blob.bin");
+        fs::write(&file_path, b"hello world").unwrap();
+        let path = file_path.to_str().unwrap();
+
         let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
         let server = McpServer::new(policy);
-        assert!(server.list_tools().len() > 0);
+        server.handle_tool_call(
+            "write_range",
+            json!({"path": path, "offset": 6, "content": "WORLD", "encoding": "text"})
+        ).unwrap();
+
+        let result = server.handle_tool_call(
+            "read_range",
+            json!({"path": path, "offset": 6, "length": 100})
+        ).unwrap();
+        let range: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(range["data"], "V09STEQ=");
+        assert_eq!(range["bytes_read"], 5);
+        assert_eq!(range["eof"], true);
     }
 
     #[test]
-    fn test_list_tools() {
-        let policy = AccessPolicy::permissive();
+    fn test_lock_file_guards_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("shared.txt");
+        let path = file_path.to_str().unwrap();
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
         let server = McpServer::new(policy);
-        let tools = server.list_tools();
-        
-        assert_eq!(tools.len(), 14); // Updated: all 14 tools including new ones
-        assert!(tools.iter().any(|t| t.name == "read_file"));
-        assert!(tools.iter().any(|t| t.name == "write_file"));
-        assert!(tools.iter().any(|t| t.name == "list_directory"));
-        assert!(tools.iter().any(|t| t.name == "get_metadata"));
-        assert!(tools.iter().any(|t| t.name == "delete_file"));
-        assert!(tools.iter().any(|t| t.name == "move_file"));
-        assert!(tools.iter().any(|t| t.name == "copy_file"));
-        assert!(tools.iter().any(|t| t.name == "append_file"));
-        assert!(tools.iter().any(|t| t.name == "file_exists"));
-        assert!(tools.iter().any(|t| t.name == "create_directory"));
-        assert!(tools.iter().any(|t| t.name == "remove_directory"));
-        assert!(tools.iter().any(|t| t.name == "read_lines"));
-        assert!(tools.iter().any(|t| t.name == "search_files"));
-        assert!(tools.iter().any(|t| t.name == "grep_file"));
+        let result = server.handle_tool_call(
+            "lock_file",
+            json!({"path": path, "owner": "agent-a"})
+        ).unwrap();
+        let lock: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        let token = lock["token"].as_str().unwrap();
+
+        let denied = server.handle_tool_call("write_file", json!({"path": path, "content": "x"}));
+        assert!(matches!(denied, Err(FileJackError::PermissionDenied(_))));
+
+        server.handle_tool_call(
+            "write_file",
+            json!({"path": path, "content": "x", "lock_token": token})
+        ).unwrap();
+
+        server.handle_tool_call("unlock_file", json!({"path": path, "token": token})).unwrap();
+        server.handle_tool_call("append_file", json!({"path": path, "content": "y"})).unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "xy");
+    }
+
+    #[test]
+    fn test_if_match_detects_conflicting_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("shared.md");
+        let path = file_path.to_str().unwrap();
+        fs::write(&file_path, "agent draft\n").unwrap();
+        let server = McpServer::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()));
+
+        let result = server.handle_tool_call("get_metadata", json!({"path": path, "hash": true})).unwrap();
+        let metadata: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        let hash = metadata["hash"].as_str().unwrap().to_string();
+        server.handle_tool_call("write_file", json!({"path": path, "content": "agent v2\n", "if_match": hash})).unwrap();
+
+        // A person edits the file; the agent's stale hash no longer matches
+        fs::write(&file_path, "human edit\n").unwrap();
+        let request = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": {"name": "write_file", "arguments": {"path": path, "content": "agent v3\n", "if_match": hash}}
+        });
+        let response: Value = serde_json::from_str(&server.process_request(&request.to_string())).unwrap();
+        assert!(response["error"]["message"].as_str().unwrap().starts_with("Conflict:"));
+        assert_eq!(response["error"]["data"]["hash"], file_ops::content_hash(b"human edit\n"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "human edit\n");
+
+        let stale = server.handle_tool_call("edit_lines", json!({
+            "path": path, "operations": [{"op": "delete_range", "start_line": 1, "end_line": 1}], "if_match": hash
+        }));
+        assert!(matches!(stale, Err(FileJackError::Conflict { .. })));
+        let stale = server.handle_tool_call("update_structured_file", json!({
+            "path": path, "format": "yaml", "operations": [], "if_match": hash
+        }));
+        assert!(matches!(stale, Err(FileJackError::Conflict { .. })));
+    }
+
+    #[test]
+    fn test_write_file_skips_unchanged_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("unchanged.txt");
+        let path = file_path.to_str().unwrap();
+        fs::write(&file_path, "same").unwrap();
+        let long_ago = std::time::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        fs::File::options().write(true).open(&file_path).unwrap().set_modified(long_ago).unwrap();
+        let modified = || fs::metadata(&file_path).unwrap().modified().unwrap();
+
+        let mut config = Config::permissive();
+        config.performance.skip_unchanged_writes = true;
+        let server = McpServer::from_config(config);
+        let result = server.handle_tool_call("write_file", json!({"path": path, "content": "same"})).unwrap();
+        assert!(result["content"][0]["text"].as_str().unwrap().contains("nothing was written"));
+        assert_eq!(modified(), long_ago);
+
+        let typed = server.write_file(WriteFileParams {
+            path: path.to_string(),
+            content: "same".to_string(),
+            atomic: true,
+            lock_token: None,
+            skip_unchanged: None,
+            if_match: None,
+            preserve_style: false,
+        }).unwrap();
+        assert!(typed.unchanged);
+        assert_eq!(typed.bytes_written, 0);
+
+        // Content of the same size is still compared byte for byte
+        server.handle_tool_call("write_file", json!({"path": path, "content": "diff"})).unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "diff");
+        assert_ne!(modified(), long_ago);
+
+        // The per-call flag overrides the server default
+        fs::File::options().write(true).open(&file_path).unwrap().set_modified(long_ago).unwrap();
+        server.handle_tool_call("write_file", json!({"path": path, "content": "diff", "skip_unchanged": false})).unwrap();
+        assert_ne!(modified(), long_ago);
+    }
+
+    #[test]
+    fn test_preserve_style_keeps_bom_and_line_endings() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("windows.txt");
+        let path = file_path.to_str().unwrap();
+        fs::write(&file_path, "\u{feff}one\r\ntwo\r\n").unwrap();
+        let server = McpServer::from_config(Config::permissive());
+
+        let metadata = server.handle_tool_call("get_metadata", json!({"path": path, "hash": true})).unwrap();
+        let metadata: Value = serde_json::from_str(metadata["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(metadata["style"], json!({"bom": true, "line_ending": "crlf"}));
+        let read = server.read_file(ReadFileParams { path: path.to_string(), encoding: ContentEncoding::Text }).unwrap();
+        assert_eq!(read.style, Some(TextStyle { bom: true, line_ending: Some(crate::LineEnding::Crlf) }));
+
+        server.handle_tool_call("write_file", json!({"path": path, "content": "one\nTWO\n", "preserve_style": true})).unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "\u{feff}one\r\nTWO\r\n");
+        server.handle_tool_call("append_file", json!({"path": path, "content": "three\n", "preserve_style": true})).unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "\u{feff}one\r\nTWO\r\nthree\r\n");
+
+        server.handle_tool_call("write_file", json!({"path": path, "content": "plain\n"})).unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "plain\n");
+        let new_file = temp_dir.path().join("new.txt");
+        server.handle_tool_call("write_file", json!({"path": new_file, "content": "a\r\n", "preserve_style": true})).unwrap();
+        assert_eq!(fs::read_to_string(&new_file).unwrap(), "a\r\n");
+    }
+
+    #[test]
+    fn test_edit_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("main.rs");
+        let path = file_path.to_str().unwrap();
+        fs::write(&file_path, "fn main() {\n    println!(\"hi\");\n}\n").unwrap();
+
+        let server = McpServer::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()));
+        let result = server.handle_tool_call("edit_lines", json!({
+            "path": path,
+            "operations": [
+                {"op": "replace_range", "start_line": 2, "end_line": 2, "content": "    run();", "expected": "    println!(\"hi\");"},
+                {"op": "insert_at", "line": 4, "content": "\nfn run() {}"}
+            ]
+        })).unwrap();
+        let summary: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(summary["lines_before"], 3);
+        assert_eq!(summary["lines_after"], 5);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "fn main() {\n    run();\n}\n\nfn run() {}\n");
+
+        // A stale expectation leaves the file alone
+        let stale = server.handle_tool_call("edit_lines", json!({
+            "path": path,
+            "operations": [{"op": "delete_range", "start_line": 2, "end_line": 2, "expected": "    println!(\"hi\");"}]
+        }));
+        assert!(matches!(stale, Err(FileJackError::InvalidParameters(_))));
+        assert!(fs::read_to_string(&file_path).unwrap().contains("run();"));
+    }
+
+    #[test]
+    fn test_concurrent_edit_lines_lose_no_update() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("log.txt");
+        fs::write(&file_path, "start\n").unwrap();
+        let server = McpServer::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()));
+
+        // Each edit reads the file and writes it back; without a lock held in
+        // between, one thread's edit would overwrite the other's
+        let workers: Vec<_> = (0..2).map(|worker| {
+            let server = server.clone();
+            let path = file_path.to_str().unwrap().to_string();
+            std::thread::spawn(move || {
+                for i in 0..50 {
+                    server.handle_tool_call("edit_lines", json!({
+                        "path": path,
+                        "operations": [{"op": "insert_at", "line": 1, "content": format!("{} {}", worker, i)}]
+                    })).unwrap();
+                }
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&file_path).unwrap().lines().count(), 101);
+        assert!(!temp_dir.path().join(crate::vfs::DIRECTORY_LOCK_FILE_NAME).exists());
+    }
+
+    #[test]
+    fn test_update_structured_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("settings.toml");
+        let path = file_path.to_str().unwrap();
+        fs::write(&file_path, "# Editor settings\n[editor]\ntab_width = 4 # spaces\n").unwrap();
+
+        let server = McpServer::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()));
+        let result = server.handle_tool_call("update_structured_file", json!({
+            "path": path,
+            "operations": [
+                {"op": "set", "pointer": "/editor/tab_width", "value": 2},
+                {"op": "set", "pointer": "/editor/rulers", "value": [80]}
+            ]
+        })).unwrap();
+        let summary: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(summary["format"], "toml");
+        assert_eq!(summary["changed"], true);
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "# Editor settings\n[editor]\ntab_width = 2 # spaces\nrulers = [80]\n"
+        );
+
+        // A failing edit leaves the file as it was
+        let err = server.handle_tool_call("update_structured_file", json!({
+            "path": path,
+            "operations": [
+                {"op": "remove", "pointer": "/editor/rulers"},
+                {"op": "append", "pointer": "/editor/tab_width", "value": 1}
+            ]
+        }));
+        assert!(matches!(err, Err(FileJackError::InvalidParameters(_))));
+        assert!(fs::read_to_string(&file_path).unwrap().contains("rulers"));
+
+        let same = server.update_structured_file(UpdateStructuredFileParams {
+            path: path.to_string(),
+            format: None,
+            operations: vec![structured::EditOperation::Set { pointer: "/editor/tab_width".to_string(), value: json!(2) }],
+            if_match: None,
+            lock_token: None,
+        }).unwrap();
+        assert!(!same.changed);
+
+        let read_only = McpServer::new(AccessPolicy::read_only(temp_dir.path().to_path_buf()));
+        let denied = read_only.handle_tool_call("update_structured_file", json!({"path": path, "operations": []}));
+        assert!(matches!(denied, Err(FileJackError::PermissionDenied(_))));
     }
 
     #[test]
@@ -782,6 +2854,23 @@ mod tests {
         assert_eq!(result["content"][0]["text"], "Hello, MCP!");
     }
 
+    #[test]
+    fn test_handle_tool_call_read_file_base64() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("data.bin");
+        fs::write(&file_path, [0u8, 159, 146, 150]).unwrap();
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let server = McpServer::new(policy);
+        let result = server.handle_tool_call(
+            "read_file",
+            json!({"path": file_path.to_str().unwrap(), "encoding": "base64"})
+        ).unwrap();
+
+        assert_eq!(result["content"][0]["type"], "resource");
+        assert_eq!(result["content"][0]["resource"]["blob"], "AJ+Slg==");
+    }
+
     #[test]
     fn test_handle_tool_call_write_file() {
         let temp_dir = TempDir::new().unwrap();
@@ -804,6 +2893,117 @@ mod tests {
         assert_eq!(content, "MCP write test");
     }
 
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_snapshot_commits_files_written_through_server() {
+        let temp_dir = tempfile::Builder::new().prefix("repo").tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        git2::Repository::init(&root).unwrap();
+        fs::write(root.join("untouched.txt"), "left alone").unwrap();
+
+        let server = McpServer::new(AccessPolicy::restricted(root.clone()));
+        let file_path = root.join("output.txt");
+        server.handle_tool_call("write_file", json!({"path": file_path, "content": "agent edit"})).unwrap();
+
+        let result = server.handle_tool_call("git_snapshot", json!({"path": root, "message": "Agent edits"})).unwrap();
+        let snapshot: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert!(snapshot["commit"].is_string());
+        assert_eq!(snapshot["files"], json!([file_path]));
+
+        let result = server.handle_tool_call("git_snapshot", json!({"path": root})).unwrap();
+        assert!(result["content"][0]["text"].as_str().unwrap().contains("\"commit\": null"));
+    }
+
+    #[cfg(feature = "index")]
+    #[test]
+    fn test_search_answered_from_index() {
+        let temp_dir = tempfile::Builder::new().prefix("indexed").tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+
+        let mut config = Config::default_restricted(root.join("src"));
+        config.index = Some(crate::config::IndexConfig {
+            database: root.join("index.db"),
+            ..Default::default()
+        });
+        let server = McpServer::from_config(config);
+        // Only answers from the index carry content hashes
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !server.handle_tool_call("recent_files", json!({"path": root.join("src")})).unwrap()["content"][0]["text"]
+            .as_str().unwrap().contains("\"hash\"")
+        {
+            assert!(Instant::now() < deadline, "initial index scan did not finish");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        // Files written after the scan are found without waiting for the next one
+        let lib = root.join("src/lib.rs");
+        server.handle_tool_call("write_file", json!({"path": lib, "content": "pub fn lib() {}"})).unwrap();
+        let result = server.handle_tool_call("search_files", json!({"path": root.join("src"), "pattern": "*.rs"})).unwrap();
+        let found: Vec<String> = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(found.len(), 2, "{:?}", found);
+
+        let result = server.handle_tool_call("recent_files", json!({"path": root.join("src"), "max_results": 1})).unwrap();
+        let recent: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(recent[0]["path"], json!(lib));
+        assert_eq!(recent[0]["hash"], json!(blake3::hash(b"pub fn lib() {}").to_hex().as_str()));
+
+        let result = server.handle_tool_call("search_content", json!({"path": root.join("src"), "query": "PUB FN"})).unwrap();
+        let matches: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(matches, json!([{"file": lib, "line_number": 1, "line_content": "pub fn lib() {}"}]));
+
+        server.handle_tool_call("delete_file", json!({"path": root.join("src/main.rs")})).unwrap();
+        let result = server.handle_tool_call("search_files", json!({"path": root.join("src"), "pattern": "main.*"})).unwrap();
+        assert_eq!(result["content"][0]["text"], "[]");
+    }
+
+    #[cfg(feature = "index")]
+    #[test]
+    fn test_search_content_without_index() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.txt"), "needle\nhay").unwrap();
+        fs::write(temp_dir.path().join("b.txt"), "Needle\nneedle again").unwrap();
+        let server = McpServer::new(AccessPolicy::permissive());
+
+        let result = server.handle_tool_call("search_content", json!({"path": temp_dir.path(), "query": "needle"})).unwrap();
+        let matches: Vec<Value> = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        let lines: Vec<_> = matches.iter()
+            .map(|m| (m["file"].as_str().unwrap().ends_with("b.txt"), m["line_number"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(lines, [(true, 1), (true, 2), (false, 1)]);
+
+        let result = server.handle_tool_call("search_content", json!({"path": temp_dir.path(), "query": "ne"}));
+        assert!(matches!(result, Err(FileJackError::InvalidParameters(_))));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_image_info() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("photo.png");
+        image::RgbImage::new(640, 480).save(&path).unwrap();
+        let server = McpServer::new(AccessPolicy::permissive());
+
+        let result = server.handle_tool_call("image_info", json!({"path": path})).unwrap();
+        let content = result["content"].as_array().unwrap();
+        assert_eq!(content.len(), 1);
+        let info: Value = serde_json::from_str(content[0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!((info["format"].as_str(), info["width"].as_u64(), info["height"].as_u64()), (Some("png"), Some(640), Some(480)));
+
+        let result = server.handle_tool_call("image_info", json!({"path": path, "thumbnail": true, "max_size": 64})).unwrap();
+        let content = result["content"].as_array().unwrap();
+        let info: Value = serde_json::from_str(content[0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(info["thumbnail"], json!({"width": 64, "height": 48}));
+        assert_eq!(content[1]["mimeType"], "image/png");
+        let png = base64::engine::general_purpose::STANDARD.decode(content[1]["data"].as_str().unwrap()).unwrap();
+        assert_eq!(image::load_from_memory(&png).unwrap().width(), 64);
+
+        fs::write(temp_dir.path().join("notes.txt"), "not an image").unwrap();
+        let result = server.handle_tool_call("image_info", json!({"path": temp_dir.path().join("notes.txt")}));
+        assert!(matches!(result, Err(FileJackError::InvalidParameters(_))));
+    }
+
     #[test]
     fn test_handle_tool_call_invalid_tool() {
         let policy = AccessPolicy::permissive();
@@ -921,6 +3121,102 @@ mod tests {
         assert_eq!(error.code, -32700);
     }
 
+    #[test]
+    fn test_process_request_rejects_oversized_and_deep_requests() {
+        let server = McpServer::new(AccessPolicy::permissive()).with_request_limits(256, 8);
+
+        let big = format!(
+            r#"{{"jsonrpc":"2.0","method":"tools/list","params":{{"pad":"{}"}},"id":1}}"#,
+            "x".repeat(300)
+        );
+        let response: JsonRpcResponse = serde_json::from_str(&server.process_request(&big)).unwrap();
+        let error = response.error.unwrap();
+        assert_eq!(error.code, -32600);
+        assert!(error.message.contains("exceeds limit"));
+
+        let deep = format!(
+            r#"{{"jsonrpc":"2.0","method":"tools/list","params":{}{},"id":1}}"#,
+            "[".repeat(20),
+            "]".repeat(20)
+        );
+        let response: JsonRpcResponse = serde_json::from_str(&server.process_request(&deep)).unwrap();
+        let error = response.error.unwrap();
+        assert_eq!(error.code, -32600);
+        assert!(error.message.contains("maximum depth"));
+
+        let ok = r#"{"jsonrpc":"2.0","method":"tools/list","id":1}"#;
+        let response: JsonRpcResponse = serde_json::from_str(&server.process_request(ok)).unwrap();
+        assert!(response.result.is_some());
+    }
+
+    #[test]
+    fn test_read_file_stops_at_the_read_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("big.txt");
+        // The tenth byte is the first half of an "é"
+        fs::write(&file_path, "aaaaaaaaaééé").unwrap();
+        let path = file_path.to_str().unwrap();
+        let server = McpServer::new(AccessPolicy::permissive());
+        server.update_state(|state| state.reader = state.reader.clone().with_read_limit(10));
+
+        let read = server.read_file(ReadFileParams { path: path.to_string(), encoding: ContentEncoding::Text }).unwrap();
+        assert_eq!(read.content, "aaaaaaaaa");
+        assert_eq!(read.next_offset, Some(9));
+        let read = server.read_file(ReadFileParams { path: path.to_string(), encoding: ContentEncoding::Base64 }).unwrap();
+        assert_eq!(BASE64.decode(&read.content).unwrap().len(), 10);
+        assert_eq!(read.next_offset, Some(10));
+
+        let result = server.handle_tool_call("read_file", json!({"path": path})).unwrap();
+        assert!(result["content"][1]["text"].as_str().unwrap().contains("read_range with offset 9"));
+        let rest = server.handle_tool_call("read_range", json!({"path": path, "offset": 9, "length": 100, "encoding": "text"})).unwrap();
+        assert!(rest["content"][0]["text"].as_str().unwrap().contains("éé"));
+    }
+
+    #[test]
+    fn test_truncate_result_cuts_at_line_boundary() {
+        let mut result = json!({
+            "content": [{"type": "text", "text": "line one\nline two\nline three\n"}],
+            "structuredContent": {"lines": 3}
+        });
+        assert_eq!(truncate_result(&mut result, 100), None);
+
+        let total = truncate_result(&mut result, 20).unwrap();
+        assert_eq!(total, 29);
+        assert_eq!(result["truncated"], true);
+        assert_eq!(result["total_size"], 29);
+        assert_eq!(result["content"][0]["text"], "line one\nline two\n");
+        assert!(result["content"][1]["text"].as_str().unwrap().contains("read_range"));
+        assert!(result.get("structuredContent").is_none());
+    }
+
+    #[test]
+    fn test_tools_call_response_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("big.txt");
+        let content: String = (0..100).map(|i| format!("row {}\n", i)).collect();
+        fs::write(&file_path, &content).unwrap();
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let server = McpServer::new(policy).with_max_response_bytes(50);
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/call".to_string(),
+            params: Some(json!({
+                "name": "read_file",
+                "arguments": {"path": file_path.to_str().unwrap()}
+            })),
+            id: Some(json!(1)),
+        };
+
+        let result = server.handle_request(request).result.unwrap();
+        assert_eq!(result["truncated"], true);
+        assert_eq!(result["total_size"], content.len());
+        let text = result["content"][0]["text"].as_str().unwrap();
+        assert!(text.len() <= 50);
+        assert!(content.starts_with(text));
+        assert!(text.ends_with('\n'));
+    }
+
     #[test]
     fn test_process_request_read_write_workflow() {
         let temp_dir = TempDir::new().unwrap();
@@ -0,0 +1,283 @@
+use crate::error::Result;
+use crate::usage;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Upper bounds (in seconds) of the tool latency histogram buckets
+pub const LATENCY_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Label used for calls to tools that do not exist, so clients cannot
+/// create unbounded label values
+pub const UNKNOWN_TOOL_LABEL: &str = "unknown";
+
+/// Content type of the Prometheus text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+#[derive(Debug, Clone, Default)]
+struct ToolMetrics {
+    calls: u64,
+    errors: u64,
+    /// Observations per bucket in [`LATENCY_BUCKETS`] (not cumulative)
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    latency_sum: f64,
+}
+
+/// Point-in-time copy of the counters in [`Metrics`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricsSnapshot {
+    pub uptime_seconds: u64,
+    /// Calls per tool, including failed ones
+    pub calls: BTreeMap<String, u64>,
+    pub errors: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub rate_limited: u64,
+}
+
+/// Counters describing what the server has done since it started.
+///
+/// Shared by the server and the `/metrics` listener; every method takes
+/// `&self` and is cheap enough to call on each request.
+#[derive(Debug)]
+pub struct Metrics {
+    started: Instant,
+    tools: Mutex<BTreeMap<String, ToolMetrics>>,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    rate_limited: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            tools: Mutex::new(BTreeMap::new()),
+            bytes_read: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+            rate_limited: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Metrics {
+    /// Create an empty registry; uptime is measured from this call
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one completed tool call
+    pub fn record_call(&self, tool: &str, elapsed: Duration, success: bool) {
+        let seconds = elapsed.as_secs_f64();
+        let mut tools = self.tools.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = tools.entry(tool.to_string()).or_default();
+        entry.calls += 1;
+        if !success {
+            entry.errors += 1;
+        }
+        entry.latency_sum += seconds;
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            entry.buckets[bucket] += 1;
+        }
+    }
+
+    /// Count bytes of file data returned to clients, also for the session
+    /// whose tool call is running on this thread
+    pub fn add_bytes_read(&self, bytes: u64) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+        usage::add_bytes_read(bytes);
+    }
+
+    /// Count bytes of file data written on behalf of clients, also for the
+    /// session whose tool call is running on this thread
+    pub fn add_bytes_written(&self, bytes: u64) {
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+        usage::add_bytes_written(bytes);
+    }
+
+    /// Count a request rejected by the rate limiter
+    pub fn record_rate_limited(&self) {
+        self.rate_limited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Time since the registry was created
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Copy the current counters
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let tools = self.tools.lock().unwrap_or_else(|e| e.into_inner());
+        MetricsSnapshot {
+            uptime_seconds: self.uptime().as_secs(),
+            calls: tools.iter().map(|(tool, m)| (tool.clone(), m.calls)).collect(),
+            errors: tools.values().map(|m| m.errors).sum(),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Render every metric in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let tools = self.tools.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let mut out = String::new();
+
+        header(&mut out, "filejack_tool_calls_total", "counter", "Tool calls handled, by tool");
+        for (tool, m) in &tools {
+            let _ = writeln!(out, "filejack_tool_calls_total{{tool=\"{}\"}} {}", tool, m.calls);
+        }
+
+        header(&mut out, "filejack_tool_errors_total", "counter", "Tool calls that returned an error, by tool");
+        for (tool, m) in &tools {
+            let _ = writeln!(out, "filejack_tool_errors_total{{tool=\"{}\"}} {}", tool, m.errors);
+        }
+
+        header(&mut out, "filejack_tool_duration_seconds", "histogram", "Tool call latency in seconds");
+        for (tool, m) in &tools {
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(m.buckets) {
+                cumulative += count;
+                let _ = writeln!(out, "filejack_tool_duration_seconds_bucket{{tool=\"{}\",le=\"{}\"}} {}", tool, bound, cumulative);
+            }
+            let _ = writeln!(out, "filejack_tool_duration_seconds_bucket{{tool=\"{}\",le=\"+Inf\"}} {}", tool, m.calls);
+            let _ = writeln!(out, "filejack_tool_duration_seconds_sum{{tool=\"{}\"}} {}", tool, m.latency_sum);
+            let _ = writeln!(out, "filejack_tool_duration_seconds_count{{tool=\"{}\"}} {}", tool, m.calls);
+        }
+
+        for (name, help, value) in [
+            ("filejack_bytes_read_total", "Bytes of file data returned to clients", &self.bytes_read),
+            ("filejack_bytes_written_total", "Bytes of file data written for clients", &self.bytes_written),
+            ("filejack_rate_limited_total", "Requests rejected by the rate limiter", &self.rate_limited),
+        ] {
+            header(&mut out, name, "counter", help);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+
+        header(&mut out, "filejack_uptime_seconds", "gauge", "Seconds since the server started");
+        let _ = writeln!(out, "filejack_uptime_seconds {}", self.uptime().as_secs_f64());
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Minimal HTTP listener answering `GET /metrics` for Prometheus scrapers
+pub struct MetricsEndpoint {
+    addr: SocketAddr,
+}
+
+impl MetricsEndpoint {
+    /// Bind `addr` and serve `metrics` from a background thread for the rest
+    /// of the process
+    pub fn start(addr: &str, metrics: Arc<Metrics>) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        std::thread::Builder::new()
+            .name("filejack-metrics".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => {
+                            if let Err(e) = respond(stream, &metrics) {
+                                debug!("Metrics request failed: {}", e);
+                            }
+                        }
+                        Err(e) => warn!("Metrics listener error: {}", e),
+                    }
+                }
+            })?;
+        Ok(Self { addr })
+    }
+
+    /// Address the listener is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the headers so the client sees a clean response
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", CONTENT_TYPE, metrics.render()),
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "Method not allowed\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_render_counts_and_histogram() {
+        let metrics = Metrics::new();
+        metrics.record_call("read_file", Duration::from_millis(3), true);
+        metrics.record_call("read_file", Duration::from_millis(40), false);
+        metrics.add_bytes_read(128);
+        metrics.record_rate_limited();
+
+        let text = metrics.render();
+        assert!(text.contains("filejack_tool_calls_total{tool=\"read_file\"} 2"));
+        assert!(text.contains("filejack_tool_errors_total{tool=\"read_file\"} 1"));
+        assert!(text.contains("filejack_tool_duration_seconds_bucket{tool=\"read_file\",le=\"0.001\"} 0"));
+        assert!(text.contains("filejack_tool_duration_seconds_bucket{tool=\"read_file\",le=\"0.005\"} 1"));
+        assert!(text.contains("filejack_tool_duration_seconds_bucket{tool=\"read_file\",le=\"0.05\"} 2"));
+        assert!(text.contains("filejack_tool_duration_seconds_bucket{tool=\"read_file\",le=\"+Inf\"} 2"));
+        assert!(text.contains("filejack_bytes_read_total 128"));
+        assert!(text.contains("filejack_bytes_written_total 0"));
+        assert!(text.contains("filejack_rate_limited_total 1"));
+        assert!(text.contains("filejack_uptime_seconds "));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.calls["read_file"], 2);
+        assert_eq!(snapshot.errors, 1);
+        assert_eq!(snapshot.bytes_read, 128);
+    }
+
+    #[test]
+    fn test_http_endpoint() {
+        let metrics = Arc::new(Metrics::new());
+        metrics.add_bytes_written(7);
+        let endpoint = MetricsEndpoint::start("127.0.0.1:0", Arc::clone(&metrics)).unwrap();
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(endpoint.local_addr()).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = get("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("filejack_bytes_written_total 7"));
+        assert!(get("/other").starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,220 @@
+//! Hooks around the requests a server handles.
+//!
+//! A [`Middleware`] sees every JSON-RPC request that reaches
+//! [`crate::McpServer::handle_session_request`] before and after it is
+//! handled, so embedders can add logging, caching, argument rewriting, or
+//! their own authorization without touching tool dispatch. The server's
+//! rate limiter ([`crate::rate_limit::RateLimitMiddleware`]) and audit log
+//! ([`crate::audit::AuditMiddleware`]) run as the first links of the chain;
+//! middlewares added with [`crate::McpServerBuilder::middleware`] follow.
+//!
+//! ```
+//! use filejack::middleware::{Call, Middleware};
+//! use filejack::{AccessPolicy, FileJackError, McpServer, Result};
+//! use serde_json::Value;
+//! use std::sync::Arc;
+//!
+//! /// Refuses every tool that could change a file
+//! struct ReadOnlyTools;
+//!
+//! impl Middleware for ReadOnlyTools {
+//!     fn before_call(&self, call: &Call<'_>, _params: &mut Value) -> Result<Option<Value>> {
+//!         match call.tool() {
+//!             Some(tool) if tool.starts_with("write") || tool.starts_with("delete") => {
+//!                 Err(FileJackError::PermissionDenied(format!("{} is not allowed here", tool).into()))
+//!             }
+//!             _ => Ok(None),
+//!         }
+//!     }
+//! }
+//!
+//! let server = McpServer::builder()
+//!     .policy(AccessPolicy::permissive())
+//!     .middleware(Arc::new(ReadOnlyTools))
+//!     .build();
+//! let request = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"delete_file","arguments":{"path":"/tmp/x"}}}"#;
+//! assert!(server.process_request(request).contains("not allowed here"));
+//! ```
+
+use crate::error::{FileJackError, Result};
+use crate::session::Session;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A request passing through the middleware chain
+pub struct Call<'a> {
+    session: &'a Session,
+    method: &'a str,
+    tool: Option<&'a str>,
+    started: Instant,
+}
+
+impl<'a> Call<'a> {
+    pub(crate) fn new(session: &'a Session, method: &'a str, tool: Option<&'a str>) -> Self {
+        Self { session, method, tool, started: Instant::now() }
+    }
+
+    /// Session the request arrived on
+    pub fn session(&self) -> &'a Session {
+        self.session
+    }
+
+    /// JSON-RPC method, such as `tools/call`
+    pub fn method(&self) -> &'a str {
+        self.method
+    }
+
+    /// Name of the tool being called, for `tools/call`
+    pub fn tool(&self) -> Option<&'a str> {
+        self.tool
+    }
+
+    /// Time since the request entered the chain
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+/// Code run around each request.
+///
+/// `params` are the tool's arguments for `tools/call` and the request's
+/// params (or null) for other methods. Every hook of a request sees the
+/// same `params`, as rewritten by the `before_call`s that ran. Hooks run on
+/// the request path, so they must not block for long.
+pub trait Middleware: Send + Sync {
+    /// Runs before the request is handled, in the order middlewares were
+    /// added, and may rewrite `params`. Returning a result answers the
+    /// request without handling it; returning an error refuses it. Either
+    /// way, later middlewares are skipped.
+    fn before_call(&self, call: &Call<'_>, params: &mut Value) -> Result<Option<Value>> {
+        let _ = (call, params);
+        Ok(None)
+    }
+
+    /// Runs after the request succeeded, in reverse order, and may change
+    /// the result or turn it into an error
+    fn after_call(&self, call: &Call<'_>, params: &Value, result: &mut Value) -> Result<()> {
+        let _ = (call, params, result);
+        Ok(())
+    }
+
+    /// Runs after the request failed, in reverse order
+    fn on_error(&self, call: &Call<'_>, params: &Value, error: &FileJackError) {
+        let _ = (call, params, error);
+    }
+}
+
+/// Handle a request with `handler` inside `chain`. Only middlewares whose
+/// `before_call` let the request continue see its outcome.
+pub(crate) fn run(
+    chain: &[Arc<dyn Middleware>],
+    call: &Call<'_>,
+    mut params: Value,
+    handler: impl FnOnce(Value) -> Result<Value>,
+) -> Result<Value> {
+    let mut entered = 0;
+    let mut answered = None;
+    for middleware in chain {
+        match middleware.before_call(call, &mut params) {
+            Ok(None) => entered += 1,
+            Ok(Some(result)) => {
+                answered = Some(Ok(result));
+                break;
+            }
+            Err(e) => {
+                answered = Some(Err(e));
+                break;
+            }
+        }
+    }
+
+    let mut outcome = match answered {
+        Some(outcome) => outcome,
+        None => handler(params.clone()),
+    };
+    for middleware in chain[..entered].iter().rev() {
+        outcome = match outcome {
+            Ok(mut result) => middleware.after_call(call, &params, &mut result).map(|()| result),
+            Err(e) => {
+                middleware.on_error(call, &params, &e);
+                Err(e)
+            }
+        };
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Records the hooks it sees, and answers or refuses calls to the
+    /// tools named `cached` and `forbidden`
+    struct Recorder {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for Recorder {
+        fn before_call(&self, call: &Call<'_>, params: &mut Value) -> Result<Option<Value>> {
+            self.log.lock().unwrap().push(format!("{} before", self.name));
+            params["seen_by"] = json!(self.name);
+            match call.tool() {
+                Some("cached") if self.name == "inner" => Ok(Some(json!("from cache"))),
+                Some("forbidden") if self.name == "inner" => Err(FileJackError::PermissionDenied("no".into())),
+                _ => Ok(None),
+            }
+        }
+
+        fn after_call(&self, _: &Call<'_>, _: &Value, result: &mut Value) -> Result<()> {
+            self.log.lock().unwrap().push(format!("{} after {}", self.name, result));
+            Ok(())
+        }
+
+        fn on_error(&self, _: &Call<'_>, _: &Value, error: &FileJackError) {
+            self.log.lock().unwrap().push(format!("{} error {}", self.name, error));
+        }
+    }
+
+    fn chain(log: &Arc<Mutex<Vec<String>>>) -> Vec<Arc<dyn Middleware>> {
+        ["outer", "inner"]
+            .into_iter()
+            .map(|name| Arc::new(Recorder { name, log: Arc::clone(log) }) as Arc<dyn Middleware>)
+            .collect()
+    }
+
+    #[test]
+    fn test_chain_order_and_rewrites() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let session = Session::new("s");
+        let call = Call::new(&session, "tools/call", Some("echo"));
+        let result = run(&chain(&log), &call, json!({}), |params| Ok(params["seen_by"].clone())).unwrap();
+        assert_eq!(result, "inner");
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["outer before", "inner before", "inner after \"inner\"", "outer after \"inner\""]
+        );
+    }
+
+    #[test]
+    fn test_chain_short_circuits() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let session = Session::new("s");
+        let handler = |_: Value| -> Result<Value> { panic!("handler must not run") };
+
+        let call = Call::new(&session, "tools/call", Some("cached"));
+        assert_eq!(run(&chain(&log), &call, json!({}), handler).unwrap(), "from cache");
+        assert_eq!(*log.lock().unwrap(), ["outer before", "inner before", "outer after \"from cache\""]);
+
+        log.lock().unwrap().clear();
+        let call = Call::new(&session, "tools/call", Some("forbidden"));
+        assert!(run(&chain(&log), &call, json!({}), handler).is_err());
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["outer before", "inner before", "outer error Permission denied: no"]
+        );
+    }
+}
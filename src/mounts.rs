@@ -0,0 +1,273 @@
+use crate::client::McpClient;
+use crate::error::{FileJackError, Result};
+use crate::protocol::MountSummary;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, MutexGuard};
+use tracing::{info, warn};
+
+/// Tool arguments that hold paths, and so decide where a call is routed
+pub(crate) const PATH_ARGUMENTS: &[&str] = &["path", "from", "to"];
+
+/// Settings a `[[mounts]]` entry may contain
+pub const MOUNT_FIELDS: &[&str] = &["prefix", "path", "command", "env"];
+
+/// A backend served under a virtual path prefix
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MountConfig {
+    /// Virtual path the backend appears under, e.g. `/workspace`
+    pub prefix: String,
+
+    /// Directory the prefix maps to: a local directory, or with `command`,
+    /// a directory on the backend server
+    pub path: PathBuf,
+
+    /// Program and arguments starting an MCP file server on stdio. Without
+    /// it the mount is a local directory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+
+    /// Extra environment for `command`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+impl MountConfig {
+    /// Whether the mount is served by another MCP server
+    pub fn is_remote(&self) -> bool {
+        !self.command.is_empty()
+    }
+
+    /// Problems that make the mount unusable, if any
+    pub fn problem(&self) -> Option<String> {
+        if !self.prefix.starts_with('/') || self.prefix.len() < 2 || self.prefix.ends_with('/') {
+            return Some(format!(
+                "mount prefix '{}' must start with '/', name a directory, and have no trailing '/'",
+                self.prefix
+            ));
+        }
+        if !self.is_remote() && !self.path.is_dir() {
+            return Some(format!("mount {} path {} is not a directory", self.prefix, self.path.display()));
+        }
+        None
+    }
+}
+
+/// Where a tool call goes after its paths are resolved against the mounts
+pub enum Route<'a> {
+    /// Handle here, with mounted paths rewritten to local paths
+    Local(Value),
+    /// Forward to another MCP server, with paths rewritten for it
+    Remote(&'a RemoteBackend, Value),
+}
+
+/// The configured mounts, longest prefix first
+#[derive(Default)]
+pub struct MountTable {
+    mounts: Vec<Mount>,
+}
+
+struct Mount {
+    config: MountConfig,
+    remote: Option<RemoteBackend>,
+}
+
+impl MountTable {
+    /// Build the table. Backend servers are not started until a call is
+    /// routed to them.
+    pub fn new(configs: &[MountConfig]) -> Self {
+        let mut mounts: Vec<Mount> = configs.iter()
+            .map(|config| Mount {
+                remote: config.is_remote().then(|| RemoteBackend::new(config)),
+                config: config.clone(),
+            })
+            .collect();
+        mounts.sort_by_key(|mount| std::cmp::Reverse(mount.config.prefix.len()));
+        Self { mounts }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mounts.is_empty()
+    }
+
+    /// Prefix and backend of each mount, for `server_info`
+    pub fn describe(&self) -> Vec<MountSummary> {
+        self.mounts.iter()
+            .map(|mount| MountSummary {
+                prefix: mount.config.prefix.clone(),
+                backend: if mount.config.is_remote() { "mcp" } else { "local" }.to_string(),
+            })
+            .collect()
+    }
+
+    /// Decide where a call with `arguments` goes. Paths under no mount are
+    /// left alone and handled by this server's own policy. All paths of a
+    /// call forwarded to another server must be on the same mount.
+    pub fn route(&self, arguments: Value) -> Result<Route<'_>> {
+        let mut arguments = arguments;
+        let mut remote: Option<&RemoteBackend> = None;
+        let mut local_only = false;
+
+        for key in PATH_ARGUMENTS {
+            let Some(path) = arguments.get(*key).and_then(Value::as_str) else {
+                continue;
+            };
+            let Some((mount, rest)) = self.resolve(path)? else {
+                local_only = true;
+                continue;
+            };
+            let target = mount.config.path.join(rest);
+            match &mount.remote {
+                Some(backend) => {
+                    if remote.is_some_and(|other| other.prefix != backend.prefix) {
+                        return Err(cross_mount());
+                    }
+                    remote = Some(backend);
+                }
+                None => local_only = true,
+            }
+            arguments[*key] = Value::String(target.to_string_lossy().into_owned());
+        }
+
+        match remote {
+            Some(_) if local_only => Err(cross_mount()),
+            Some(backend) => Ok(Route::Remote(backend, arguments)),
+            None => Ok(Route::Local(arguments)),
+        }
+    }
+
+    /// The mount `path` falls under and the remainder below its prefix
+    fn resolve<'p>(&self, path: &'p str) -> Result<Option<(&Mount, &'p str)>> {
+        for mount in &self.mounts {
+            let Some(rest) = path.strip_prefix(mount.config.prefix.as_str()) else {
+                continue;
+            };
+            if !rest.is_empty() && !rest.starts_with('/') {
+                continue;
+            }
+            let rest = rest.trim_start_matches('/');
+            if Path::new(rest).components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+                return Err(FileJackError::InvalidPath(format!("Path {} escapes mount {}", path, mount.config.prefix)));
+            }
+            return Ok(Some((mount, rest)));
+        }
+        Ok(None)
+    }
+}
+
+fn cross_mount() -> FileJackError {
+    FileJackError::InvalidParameters("Paths of one call must be on the same mount".to_string())
+}
+
+/// Another MCP server behind a mount, started on first use and restarted
+/// if it goes away
+pub struct RemoteBackend {
+    prefix: String,
+    command: Vec<String>,
+    env: BTreeMap<String, String>,
+    client: Mutex<Option<McpClient>>,
+}
+
+impl RemoteBackend {
+    fn new(config: &MountConfig) -> Self {
+        Self {
+            prefix: config.prefix.clone(),
+            command: config.command.clone(),
+            env: config.env.clone(),
+            client: Mutex::new(None),
+        }
+    }
+
+    /// Call `name` on the backend and return its result as sent, `isError`
+    /// included
+    pub fn call_tool(&self, name: &str, arguments: Value) -> Result<Value> {
+        let mut slot = self.lock();
+        let client = match slot.take() {
+            Some(client) => client,
+            None => self.start()?,
+        };
+        let client = slot.insert(client);
+
+        let result = client.request("tools/call", Some(json!({"name": name, "arguments": arguments})));
+        if let Err(FileJackError::Io(_) | FileJackError::Json(_)) = &result {
+            warn!(mount = %self.prefix, "Mount backend failed; it will be restarted on the next call");
+            *slot = None;
+        }
+        result
+    }
+
+    fn start(&self) -> Result<McpClient> {
+        let (program, args) = self.command.split_first()
+            .ok_or_else(|| FileJackError::ConfigError(format!("Mount {} has no command", self.prefix)))?;
+        info!(mount = %self.prefix, command = %program, "Starting mount backend");
+        let mut command = Command::new(program);
+        command.args(args).envs(&self.env);
+        let mut client = McpClient::spawn(command)?;
+        client.initialize()?;
+        Ok(client)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<McpClient>> {
+        self.client.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(prefix: &str, path: &str) -> MountConfig {
+        MountConfig { prefix: prefix.to_string(), path: PathBuf::from(path), command: Vec::new(), env: BTreeMap::new() }
+    }
+
+    fn routed_path(table: &MountTable, arguments: Value, key: &str) -> String {
+        match table.route(arguments).unwrap() {
+            Route::Local(arguments) => arguments[key].as_str().unwrap().to_string(),
+            Route::Remote(..) => panic!("expected a local route"),
+        }
+    }
+
+    #[test]
+    fn test_route_local_mounts() {
+        let table = MountTable::new(&[local("/docs", "/srv/docs"), local("/docs/api", "/srv/api")]);
+        let real = |dir: &str, rest: &str| Path::new(dir).join(rest).to_string_lossy().into_owned();
+
+        assert_eq!(routed_path(&table, json!({"path": "/docs/a.md"}), "path"), real("/srv/docs", "a.md"));
+        assert_eq!(routed_path(&table, json!({"path": "/docs/api/v1.md"}), "path"), real("/srv/api", "v1.md"));
+        assert_eq!(routed_path(&table, json!({"path": "/docs"}), "path"), real("/srv/docs", ""));
+        // Not a mount, just a similar name
+        assert_eq!(routed_path(&table, json!({"path": "/docsets/x"}), "path"), "/docsets/x");
+        assert_eq!(routed_path(&table, json!({"from": "/docs/a", "to": "/tmp/b"}), "to"), "/tmp/b");
+
+        let err = table.route(json!({"path": "/docs/../etc/passwd"})).err().unwrap();
+        assert!(matches!(err, FileJackError::InvalidPath(_)));
+    }
+
+    #[test]
+    fn test_remote_mount_paths_stay_on_one_mount() {
+        let mut remote = local("/remote", "/data");
+        remote.command = vec!["filejack".to_string()];
+        let table = MountTable::new(&[remote, local("/docs", "/srv/docs")]);
+
+        match table.route(json!({"from": "/remote/a", "to": "/remote/b"})).unwrap() {
+            Route::Remote(_, arguments) => {
+                assert_eq!(arguments["to"], Path::new("/data").join("b").to_string_lossy().as_ref());
+            }
+            Route::Local(_) => panic!("expected a remote route"),
+        }
+        assert!(table.route(json!({"from": "/remote/a", "to": "/docs/b"})).is_err());
+        assert!(table.route(json!({"from": "/remote/a", "to": "/tmp/b"})).is_err());
+    }
+
+    #[test]
+    fn test_mount_problems() {
+        assert!(local("/", "/").problem().is_some());
+        assert!(local("docs", "/").problem().is_some());
+        assert!(local("/docs/", "/").problem().is_some());
+        assert!(local("/docs", "/definitely/not/here").problem().is_some());
+        assert!(local("/root-dir", "/").problem().is_none());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::error::{Denial, FileJackError, PolicyRule, Result, RuleCategory};
+use crate::vfs::{FileSystem, StdFileSystem};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// File name of a per-directory policy overlay
+pub const OVERLAY_FILE_NAME: &str = ".filejack.json";
+
+/// Extra restrictions for one directory and everything beneath it.
+///
+/// Overlays can only tighten the server policy: every field either adds a
+/// restriction or leaves the inherited rules alone, so there is nothing an
+/// overlay could loosen. Unknown fields are rejected rather than ignored, so
+/// a misspelled restriction is never silently dropped.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirectoryOverlay {
+    /// Deny every read and write in this subtree
+    #[serde(default)]
+    pub deny_all: bool,
+
+    /// Deny writes in this subtree
+    #[serde(default)]
+    pub read_only: bool,
+
+    /// When non-empty, only files with these extensions are accessible here
+    #[serde(default)]
+    pub allowed_extensions: Vec<String>,
+
+    /// Extensions denied in this subtree, in addition to the server policy
+    #[serde(default)]
+    pub denied_extensions: Vec<String>,
+}
+
+impl DirectoryOverlay {
+    /// Load the overlay file in `dir`, if there is one
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        Self::load_from(&StdFileSystem, dir)
+    }
+
+    /// Load the overlay file in `dir` on `fs`, if there is one
+    pub fn load_from(fs: &dyn FileSystem, dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(OVERLAY_FILE_NAME);
+        let content = match fs.read(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) if fs.metadata(&path).is_ok_and(|m| m.is_dir()) => return Err(invalid(&path, e)),
+            Err(e) => return Err(FileJackError::Io(e)),
+        };
+        serde_json::from_slice(&content).map(Some).map_err(|e| invalid(&path, e))
+    }
+
+    fn check(&self, dir: &Path, target: &Path, is_dir: bool, write: bool) -> Result<()> {
+        let denied = |field: &str, reason: &str| Err(FileJackError::PermissionDenied(Denial::by(
+            PolicyRule::new(format!("{}:{}", OVERLAY_FILE_NAME, field), RuleCategory::Overlay),
+            format!("{} ({} in {})", reason, OVERLAY_FILE_NAME, dir.display()),
+        )));
+
+        if self.deny_all {
+            return denied("deny_all", &format!("Access to {} is denied", target.display()));
+        }
+        if write && self.read_only {
+            return denied("read_only", &format!("{} is read-only", target.display()));
+        }
+        if is_dir {
+            return Ok(());
+        }
+
+        let ext = target.extension().map(|e| e.to_string_lossy().to_lowercase());
+        if let Some(ext) = &ext {
+            if self.denied_extensions.iter().any(|d| d.eq_ignore_ascii_case(ext)) {
+                return denied("denied_extensions", &format!("File extension .{} is not allowed", ext));
+            }
+        }
+        if !self.allowed_extensions.is_empty()
+            && !ext.as_ref().is_some_and(|ext| self.allowed_extensions.iter().any(|a| a.eq_ignore_ascii_case(ext)))
+        {
+            return denied("allowed_extensions", "File extension is not in the allowed extensions");
+        }
+        Ok(())
+    }
+}
+
+/// Apply every overlay between `root` and `target`.
+///
+/// Directories from the target (or its parent, for files) up to and including
+/// `root` are checked; without a root the walk continues to the filesystem
+/// root. `target` must already be canonical.
+pub(crate) fn check_overlays(fs: &dyn FileSystem, target: &Path, root: Option<&Path>, write: bool) -> Result<()> {
+    let is_dir = fs.metadata(target).is_ok_and(|m| m.is_dir());
+    let start = if is_dir { Some(target) } else { target.parent() };
+
+    for dir in start.into_iter().flat_map(Path::ancestors) {
+        if let Some(overlay) = DirectoryOverlay::load_from(fs, dir)? {
+            overlay.check(dir, target, is_dir, write)?;
+        }
+        if root == Some(dir) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Whether `path` names an overlay file, which clients may not modify
+pub(crate) fn is_overlay_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == OVERLAY_FILE_NAME)
+}
+
+fn invalid(path: &Path, e: impl std::fmt::Display) -> FileJackError {
+    // Fail closed: a broken overlay must not lift its restrictions
+    FileJackError::PermissionDenied(Denial::by(
+        PolicyRule::new(OVERLAY_FILE_NAME, RuleCategory::Overlay),
+        format!("Invalid policy overlay {}: {}", path.display(), e),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_overlay_restrictions() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        let docs = root.join("docs");
+        let secrets = root.join("secrets");
+        fs::create_dir_all(docs.join("nested")).unwrap();
+        fs::create_dir_all(&secrets).unwrap();
+        fs::write(docs.join(OVERLAY_FILE_NAME), r#"{"read_only": true, "denied_extensions": ["key"]}"#).unwrap();
+        fs::write(secrets.join(OVERLAY_FILE_NAME), r#"{"deny_all": true}"#).unwrap();
+
+        let note = docs.join("nested/note.txt");
+        assert!(check_overlays(&StdFileSystem, &note, Some(&root), false).is_ok());
+        assert!(check_overlays(&StdFileSystem, &note, Some(&root), true).is_err());
+        assert!(check_overlays(&StdFileSystem, &docs.join("nested/id.KEY"), Some(&root), false).is_err());
+        assert!(check_overlays(&StdFileSystem, &secrets.join("a.txt"), Some(&root), false).is_err());
+        assert!(check_overlays(&StdFileSystem, &secrets, Some(&root), false).is_err());
+        assert!(check_overlays(&StdFileSystem, &root.join("free.txt"), Some(&root), true).is_ok());
+
+        // Overlays above the root are not consulted
+        fs::write(root.join(OVERLAY_FILE_NAME), r#"{"deny_all": true}"#).unwrap();
+        assert!(check_overlays(&StdFileSystem, &note, Some(&docs), false).is_ok());
+        assert!(check_overlays(&StdFileSystem, &note, Some(&root), false).is_err());
+    }
+
+    #[test]
+    fn test_invalid_overlay_fails_closed() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::write(root.join(OVERLAY_FILE_NAME), r#"{"read_only": true, "allow_symlinks": true}"#).unwrap();
+
+        let err = check_overlays(&StdFileSystem, &root.join("a.txt"), Some(&root), false).unwrap_err();
+        assert!(err.to_string().contains("Invalid policy overlay"), "{}", err);
+        assert!(is_overlay_file(&root.join(OVERLAY_FILE_NAME)));
+        assert!(!is_overlay_file(&root.join("filejack.json")));
+    }
+}
@@ -0,0 +1,218 @@
+//! One-call orientation in an unfamiliar directory tree.
+//!
+//! `summarize_workspace` walks a tree once and reports what an agent would
+//! otherwise piece together from many `list_directory` calls: the top-level
+//! layout, how many files of each language there are, the total size, and
+//! the largest files.
+
+use crate::error::{FileJackError, Result};
+use crate::file_ops::FileReader;
+use crate::vfs::FileKind;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Default number of largest files reported
+pub const DEFAULT_LARGEST_FILES: usize = 10;
+
+/// Language names of common file extensions
+const LANGUAGES: &[(&str, &str)] = &[
+    ("c", "C"),
+    ("h", "C"),
+    ("cc", "C++"),
+    ("cpp", "C++"),
+    ("cxx", "C++"),
+    ("hpp", "C++"),
+    ("cs", "C#"),
+    ("css", "CSS"),
+    ("go", "Go"),
+    ("html", "HTML"),
+    ("java", "Java"),
+    ("js", "JavaScript"),
+    ("mjs", "JavaScript"),
+    ("cjs", "JavaScript"),
+    ("jsx", "JavaScript"),
+    ("json", "JSON"),
+    ("kt", "Kotlin"),
+    ("md", "Markdown"),
+    ("php", "PHP"),
+    ("py", "Python"),
+    ("rb", "Ruby"),
+    ("rs", "Rust"),
+    ("scss", "SCSS"),
+    ("sh", "Shell"),
+    ("bash", "Shell"),
+    ("sql", "SQL"),
+    ("swift", "Swift"),
+    ("toml", "TOML"),
+    ("ts", "TypeScript"),
+    ("tsx", "TypeScript"),
+    ("xml", "XML"),
+    ("yaml", "YAML"),
+    ("yml", "YAML"),
+];
+
+/// An entry directly inside the summarized directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TopLevelEntry {
+    pub name: String,
+    pub is_dir: bool,
+    /// Files at or beneath the entry
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Files sharing an extension
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ExtensionCount {
+    /// Extension without the dot, lowercased; empty for files without one
+    pub extension: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// A file among the largest in the tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LargeFile {
+    /// Path relative to the summarized directory
+    pub path: String,
+    pub size: u64,
+}
+
+/// Result of `summarize_workspace`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WorkspaceOverview {
+    pub path: String,
+    pub total_files: u64,
+    pub total_directories: u64,
+    pub total_bytes: u64,
+    /// Directories first, then files, each by name
+    pub top_level: Vec<TopLevelEntry>,
+    /// Most files first
+    pub extensions: Vec<ExtensionCount>,
+    /// Largest first
+    pub largest_files: Vec<LargeFile>,
+}
+
+/// Summarize the tree at `dir`, listing its `largest` biggest files. Files
+/// and directories the policy does not allow, or that the walk skips (such
+/// as hidden ones when they are not allowed), are not counted.
+pub fn summarize(reader: &FileReader, dir: &Path, largest: usize) -> Result<WorkspaceOverview> {
+    if !reader.get_metadata(dir)?.is_dir {
+        return Err(FileJackError::InvalidPath("Path to summarize must be a directory".to_string()));
+    }
+
+    let mut overview = WorkspaceOverview {
+        path: dir.display().to_string(),
+        total_files: 0,
+        total_directories: 0,
+        total_bytes: 0,
+        top_level: Vec::new(),
+        extensions: Vec::new(),
+        largest_files: Vec::new(),
+    };
+    let mut top_level: BTreeMap<(bool, String), TopLevelEntry> = BTreeMap::new();
+    let mut extensions: BTreeMap<String, ExtensionCount> = BTreeMap::new();
+    let mut files = Vec::new();
+
+    for entry in reader.file_system().walk(dir, &reader.walk_options(usize::MAX))? {
+        let Ok(relative) = entry.path.strip_prefix(dir) else {
+            continue;
+        };
+        let Some(first) = relative.components().next() else {
+            continue;
+        };
+        let Ok(metadata) = reader.get_metadata(&entry.path) else {
+            continue;
+        };
+        let name = first.as_os_str().to_string_lossy().into_owned();
+        let is_top_dir = relative.components().count() > 1 || entry.kind == FileKind::Dir;
+        // Sorts directories before files
+        let top = top_level.entry((!is_top_dir, name.clone()))
+            .or_insert(TopLevelEntry { name, is_dir: is_top_dir, files: 0, bytes: 0 });
+
+        if metadata.is_dir {
+            overview.total_directories += 1;
+            continue;
+        }
+        if !metadata.is_file {
+            continue;
+        }
+        top.files += 1;
+        top.bytes += metadata.size;
+        overview.total_files += 1;
+        overview.total_bytes += metadata.size;
+
+        let extension = entry.path.extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let count = extensions.entry(extension.clone()).or_insert_with(|| ExtensionCount {
+            language: language(&extension).map(str::to_string),
+            extension,
+            files: 0,
+            bytes: 0,
+        });
+        count.files += 1;
+        count.bytes += metadata.size;
+        files.push(LargeFile { path: relative.to_string_lossy().replace('\\', "/"), size: metadata.size });
+    }
+
+    overview.top_level = top_level.into_values().collect();
+    overview.extensions = extensions.into_values().collect();
+    overview.extensions.sort_by(|a, b| b.files.cmp(&a.files).then(b.bytes.cmp(&a.bytes)));
+    files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    files.truncate(largest);
+    overview.largest_files = files;
+    Ok(overview)
+}
+
+/// Language usually written in files with this extension
+pub fn language(extension: &str) -> Option<&'static str> {
+    LANGUAGES.iter().find(|(known, _)| *known == extension).map(|(_, language)| *language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access_control::AccessPolicy;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_summarize_workspace() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("repo");
+        fs::create_dir_all(root.join("src/tools")).unwrap();
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::write(root.join("README.md"), "# Repo\n").unwrap();
+        fs::write(root.join("Makefile"), "all:\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "x".repeat(300)).unwrap();
+        fs::write(root.join("src/tools/mod.rs"), "x".repeat(100)).unwrap();
+        fs::write(root.join("src/tools/Read.RS"), "x".repeat(50)).unwrap();
+        fs::write(root.join(".env"), "SECRET=1").unwrap();
+
+        let reader = FileReader::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()));
+        let overview = summarize(&reader, &root, 2).unwrap();
+        assert_eq!(overview.total_files, 5);
+        assert_eq!(overview.total_directories, 3);
+        assert_eq!(overview.total_bytes, 462);
+
+        let names: Vec<_> = overview.top_level.iter().map(|entry| (entry.name.as_str(), entry.files)).collect();
+        assert_eq!(names, vec![("docs", 0), ("src", 3), ("Makefile", 1), ("README.md", 1)]);
+        assert_eq!(overview.extensions[0], ExtensionCount {
+            extension: "rs".to_string(),
+            language: Some("Rust".to_string()),
+            files: 3,
+            bytes: 450,
+        });
+        assert!(overview.extensions.iter().any(|count| count.extension.is_empty() && count.language.is_none()));
+        assert_eq!(overview.largest_files, vec![
+            LargeFile { path: "src/lib.rs".to_string(), size: 300 },
+            LargeFile { path: "src/tools/mod.rs".to_string(), size: 100 },
+        ]);
+        assert!(summarize(&reader, &root.join("README.md"), 2).is_err());
+    }
+}
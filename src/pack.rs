@@ -0,0 +1,181 @@
+//! Bundling a directory's text files into one result.
+//!
+//! `pack_directory` serves "load this module into context": instead of a
+//! `list_directory` followed by a `read_file` per file, an agent gets every
+//! matching text file in one response, each wrapped in a `<file path="...">`
+//! block. Binary files and files over the size caps are listed as skipped
+//! rather than silently left out, so the agent knows the bundle is partial.
+
+use crate::error::{FileJackError, Result};
+use crate::file_ops::{slash_path, FileReader, PathGlob};
+use crate::vfs::FileKind;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Default cap on the text of all files in a bundle (256 KiB)
+pub const DEFAULT_PACK_MAX_BYTES: u64 = 256 * 1024;
+
+/// Default cap on a single file in a bundle (64 KiB)
+pub const DEFAULT_PACK_MAX_FILE_BYTES: u64 = 64 * 1024;
+
+/// A file included in a bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackedFile {
+    /// Path relative to the packed directory, with `/` separators
+    pub path: String,
+    pub content: String,
+}
+
+/// A matching file left out of a bundle, and why
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Result of `pack_directory`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pack {
+    pub path: String,
+    pub files: Vec<PackedFile>,
+    pub skipped: Vec<SkippedFile>,
+    pub total_bytes: u64,
+}
+
+impl Pack {
+    /// The bundle as text: a summary line, each file in a `<file>` block,
+    /// then the files that were skipped
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "Packed {} files ({} bytes) from {}\n",
+            self.files.len(),
+            self.total_bytes,
+            self.path
+        );
+        for file in &self.files {
+            let _ = write!(out, "\n<file path=\"{}\">\n{}", file.path.replace('"', "&quot;"), file.content);
+            if !file.content.is_empty() && !file.content.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str("</file>\n");
+        }
+        if !self.skipped.is_empty() {
+            let _ = writeln!(out, "\nSkipped {} files:", self.skipped.len());
+            for file in &self.skipped {
+                let _ = writeln!(out, "- {}: {}", file.path, file.reason);
+            }
+        }
+        out
+    }
+}
+
+/// Collect the text files beneath `dir` whose relative path matches
+/// `pattern`, in path order, until `max_bytes` of text is gathered.
+///
+/// A pattern without `/` is matched against file names, so `*.rs` finds
+/// Rust files at any depth; one with `/`, such as `src/**/*.rs`, against
+/// the whole relative path. Files the policy does not allow are left out
+/// without mention, as `search_files` does.
+pub fn pack_directory(
+    reader: &FileReader,
+    dir: &Path,
+    pattern: Option<&str>,
+    max_bytes: u64,
+    max_file_bytes: u64,
+) -> Result<Pack> {
+    if !reader.get_metadata(dir)?.is_dir {
+        return Err(FileJackError::InvalidPath("Path to pack must be a directory".to_string()));
+    }
+    let pattern = pattern.map(PathGlob::new).transpose()?;
+
+    let mut pack = Pack { path: dir.display().to_string(), files: Vec::new(), skipped: Vec::new(), total_bytes: 0 };
+    for entry in reader.file_system().walk(dir, &reader.walk_options(usize::MAX))? {
+        if entry.kind != FileKind::File {
+            continue;
+        }
+        let Ok(relative) = entry.path.strip_prefix(dir) else {
+            continue;
+        };
+        let relative = slash_path(relative);
+        if pattern.as_ref().is_some_and(|pattern| !pattern.matches(&relative)) {
+            continue;
+        }
+
+        let mut skip = |reason: String| pack.skipped.push(SkippedFile { path: relative.clone(), reason });
+        let size = match reader.get_metadata(&entry.path) {
+            Ok(metadata) => metadata.size,
+            Err(FileJackError::PermissionDenied(_)) => continue,
+            Err(e) => {
+                skip(e.to_string());
+                continue;
+            }
+        };
+        if size > max_file_bytes {
+            skip(format!("{} bytes is over the {} byte file limit", size, max_file_bytes));
+            continue;
+        }
+        if pack.total_bytes + size > max_bytes {
+            skip(format!("the bundle reached its {} byte limit", max_bytes));
+            continue;
+        }
+        let bytes = match reader.read_to_bytes(&entry.path) {
+            Ok(bytes) => bytes,
+            Err(FileJackError::PermissionDenied(_)) => continue,
+            Err(e) => {
+                skip(e.to_string());
+                continue;
+            }
+        };
+        match String::from_utf8(bytes) {
+            Ok(content) if !content.contains('\0') => {
+                pack.total_bytes += content.len() as u64;
+                pack.files.push(PackedFile { path: relative, content });
+            }
+            _ => skip("binary".to_string()),
+        }
+    }
+    Ok(pack)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access_control::AccessPolicy;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_pack_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("crate");
+        fs::create_dir_all(root.join("src/bin")).unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "pub mod bin;").unwrap();
+        fs::write(root.join("src/bin/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join("src/logo.rs"), [0u8, 159, 146, 150]).unwrap();
+        fs::write(root.join("src/big.rs"), "x".repeat(100)).unwrap();
+
+        let reader = FileReader::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()));
+        let pack = pack_directory(&reader, &root, Some("*.rs"), 1000, 50).unwrap();
+        let paths: Vec<_> = pack.files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, vec!["src/bin/main.rs", "src/lib.rs"]);
+        assert_eq!(pack.total_bytes, 25);
+        assert_eq!(pack.skipped.len(), 2);
+        assert!(pack.skipped.iter().any(|file| file.path == "src/logo.rs" && file.reason == "binary"));
+
+        let text = pack.render();
+        assert!(text.starts_with("Packed 2 files (25 bytes)"));
+        assert!(text.contains("<file path=\"src/lib.rs\">\npub mod bin;\n</file>\n"));
+        assert!(text.contains("- src/big.rs: 100 bytes is over the 50 byte file limit"));
+
+        let top = pack_directory(&reader, &root, Some("src/*.rs"), 1000, 1000).unwrap();
+        assert_eq!(top.files.len(), 2);
+        assert!(top.files.iter().all(|file| !file.path.contains("bin/")));
+
+        let capped = pack_directory(&reader, &root, None, 30, 1000).unwrap();
+        assert_eq!(capped.files.len(), 2);
+        assert!(capped.skipped.iter().any(|file| file.reason.contains("reached its 30 byte limit")));
+        assert!(pack_directory(&reader, &root.join("Cargo.toml"), None, 20, 20).is_err());
+    }
+}
@@ -0,0 +1,408 @@
+//! Tools loaded from WebAssembly plugins.
+//!
+//! Every `.wasm` (or `.wat`) file in the configured directory is compiled
+//! as a core WebAssembly module and offered as one or more tools. A plugin
+//! has no WASI and no other imports than the two host functions below, so
+//! it can reach files only through the server's access policy. Each call
+//! runs in a fresh instance with a fuel budget and a memory limit.
+//!
+//! A plugin exports:
+//!
+//! - `memory`
+//! - `filejack_alloc(len: i32) -> i32`, returning space for `len` bytes
+//! - `filejack_tools() -> i64`, the JSON array of tool definitions
+//!   (`name`, `description`, `input_schema`)
+//! - `filejack_call(name_ptr, name_len, args_ptr, args_len: i32) -> i64`,
+//!   the result of calling the named tool with the JSON arguments
+//!
+//! An `i64` result packs a pointer into the high 32 bits and a length into
+//! the low 32 bits. A call's output is either an MCP tool result object,
+//! `{"error": "..."}`, or any other bytes, which are returned as text. A
+//! negative value means the call failed.
+//!
+//! The host provides, in module `filejack`:
+//!
+//! - `read_file(path_ptr, path_len: i32) -> i64`, the file's bytes
+//! - `write_file(path_ptr, path_len, data_ptr, data_len: i32) -> i64`,
+//!   0 once written
+//!
+//! Both return -1 when the path is refused or the operation fails.
+
+use crate::config::PluginsConfig;
+use crate::error::{FileJackError, Result};
+use crate::file_ops::{FileReader, FileWriter};
+use crate::tools::{text_result, ToolContext, ToolHandler};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
+use wasmtime::{
+    AsContextMut, Caller, Engine, Extern, Instance, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder, TypedFunc,
+};
+
+/// Load every plugin in the configured directory, skipping (and logging)
+/// those that fail to compile or describe their tools
+pub fn load_plugins(config: &PluginsConfig) -> Vec<Arc<dyn ToolHandler>> {
+    let mut paths = match std::fs::read_dir(&config.directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "wasm" || ext == "wat"))
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            warn!(directory = %config.directory.display(), error = %e, "Could not read plugins directory");
+            return Vec::new();
+        }
+    };
+    paths.sort();
+
+    let mut tools: Vec<Arc<dyn ToolHandler>> = Vec::new();
+    for path in paths {
+        match WasmPlugin::load(&path, config) {
+            Ok(plugin) => {
+                let plugin = Arc::new(plugin);
+                match plugin.tools() {
+                    Ok(definitions) => {
+                        info!(plugin = %plugin.name, tools = definitions.len(), "Loaded plugin");
+                        tools.extend(definitions.into_iter().map(|definition| {
+                            Arc::new(WasmTool { plugin: Arc::clone(&plugin), definition }) as Arc<dyn ToolHandler>
+                        }));
+                    }
+                    Err(e) => error!(plugin = %plugin.name, error = %e, "Plugin did not describe its tools"),
+                }
+            }
+            Err(e) => error!(path = %path.display(), error = %e, "Could not load plugin"),
+        }
+    }
+    tools
+}
+
+/// A compiled plugin module
+pub struct WasmPlugin {
+    /// File name without its extension, used in logs and errors
+    name: String,
+    engine: Engine,
+    module: Module,
+    linker: Linker<HostState>,
+    fuel: u64,
+    max_memory_bytes: usize,
+}
+
+/// What a plugin instance can reach while it runs
+struct HostState {
+    limits: StoreLimits,
+    reader: Option<FileReader>,
+    writer: Option<FileWriter>,
+}
+
+/// The exports every call goes through
+struct Guest {
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+}
+
+impl WasmPlugin {
+    /// Compile the module at `path` and link the host functions
+    pub fn load(path: &Path, config: &PluginsConfig) -> Result<Self> {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let mut wasm_config = wasmtime::Config::new();
+        wasm_config.consume_fuel(true);
+        let engine = wasmtime::Engine::new(&wasm_config).map_err(|e| plugin_error(&name, e))?;
+        let module = Module::from_file(&engine, path).map_err(|e| plugin_error(&name, e))?;
+
+        let mut linker = Linker::new(&engine);
+        linker
+            .func_wrap("filejack", "read_file", host_read_file)
+            .and_then(|linker| linker.func_wrap("filejack", "write_file", host_write_file))
+            .map_err(|e| plugin_error(&name, e))?;
+
+        Ok(Self {
+            name,
+            engine,
+            module,
+            linker,
+            fuel: config.fuel,
+            max_memory_bytes: usize::try_from(config.max_memory_bytes).unwrap_or(usize::MAX),
+        })
+    }
+
+    /// The tools the plugin says it provides
+    pub fn tools(&self) -> Result<Vec<ToolDefinition>> {
+        let (mut store, instance, guest) = self.instantiate(None, None)?;
+        let describe = instance
+            .get_typed_func::<(), i64>(&mut store, "filejack_tools")
+            .map_err(|e| plugin_error(&self.name, e))?;
+        let packed = describe.call(&mut store, ()).map_err(|e| plugin_error(&self.name, e))?;
+        let bytes = read_packed(&store, guest.memory, packed)
+            .ok_or_else(|| plugin_error(&self.name, "filejack_tools returned an invalid buffer"))?;
+        serde_json::from_slice(&bytes)
+            .map_err(|e| plugin_error(&self.name, format!("invalid tool definitions: {}", e)))
+    }
+
+    /// Run one tool call in a fresh instance
+    fn call(&self, tool: &str, ctx: &ToolContext<'_>, arguments: &Value) -> Result<Value> {
+        let (mut store, instance, guest) = self.instantiate(Some(ctx.reader().clone()), Some(ctx.writer().clone()))?;
+        let call = instance
+            .get_typed_func::<(i32, i32, i32, i32), i64>(&mut store, "filejack_call")
+            .map_err(|e| plugin_error(&self.name, e))?;
+        let arguments = serde_json::to_vec(arguments)?;
+        let (name_ptr, name_len) = write_guest(&mut store, &guest, tool.as_bytes()).map_err(|e| plugin_error(&self.name, e))?;
+        let (args_ptr, args_len) = write_guest(&mut store, &guest, &arguments).map_err(|e| plugin_error(&self.name, e))?;
+
+        let packed = call
+            .call(&mut store, (name_ptr, name_len, args_ptr, args_len))
+            .map_err(|e| plugin_error(&self.name, e))?;
+        debug!(plugin = %self.name, tool, fuel_left = store.get_fuel().unwrap_or(0), "Plugin call finished");
+        let output = read_packed(&store, guest.memory, packed)
+            .ok_or_else(|| plugin_error(&self.name, format!("{} failed", tool)))?;
+
+        match serde_json::from_slice::<Value>(&output) {
+            Ok(Value::Object(mut result)) => match result.remove("error") {
+                Some(message) => Err(plugin_error(&self.name, message.as_str().unwrap_or("call failed"))),
+                None => Ok(Value::Object(result)),
+            },
+            _ => Ok(text_result(String::from_utf8_lossy(&output))),
+        }
+    }
+
+    fn instantiate(&self, reader: Option<FileReader>, writer: Option<FileWriter>) -> Result<(Store<HostState>, Instance, Guest)> {
+        let limits = StoreLimitsBuilder::new().memory_size(self.max_memory_bytes).instances(1).build();
+        let mut store = Store::new(&self.engine, HostState { limits, reader, writer });
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(self.fuel).map_err(|e| plugin_error(&self.name, e))?;
+
+        let instance = self.linker.instantiate(&mut store, &self.module).map_err(|e| plugin_error(&self.name, e))?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| plugin_error(&self.name, "no exported memory"))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "filejack_alloc")
+            .map_err(|e| plugin_error(&self.name, e))?;
+        Ok((store, instance, Guest { memory, alloc }))
+    }
+}
+
+/// A tool as described by its plugin
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "empty_schema")]
+    pub input_schema: Value,
+}
+
+fn empty_schema() -> Value {
+    serde_json::json!({"type": "object"})
+}
+
+/// One tool of a plugin
+struct WasmTool {
+    plugin: Arc<WasmPlugin>,
+    definition: ToolDefinition,
+}
+
+impl ToolHandler for WasmTool {
+    fn name(&self) -> &str {
+        &self.definition.name
+    }
+
+    fn description(&self) -> &str {
+        &self.definition.description
+    }
+
+    fn input_schema(&self) -> Value {
+        self.definition.input_schema.clone()
+    }
+
+    fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<Value> {
+        self.plugin.call(&self.definition.name, ctx, &arguments)
+    }
+}
+
+fn plugin_error(plugin: &str, e: impl std::fmt::Display) -> FileJackError {
+    FileJackError::Io(std::io::Error::other(format!("Plugin {}: {}", plugin, e)))
+}
+
+/// Copy the buffer a packed pointer and length refer to, or `None` when it
+/// is negative or out of bounds
+fn read_packed(store: impl wasmtime::AsContext, memory: Memory, packed: i64) -> Option<Vec<u8>> {
+    if packed < 0 {
+        return None;
+    }
+    let ptr = (packed >> 32) as usize;
+    let len = (packed & 0xffff_ffff) as usize;
+    memory.data(&store).get(ptr..ptr.checked_add(len)?).map(<[u8]>::to_vec)
+}
+
+fn pack(ptr: i32, len: i32) -> i64 {
+    ((ptr as u32 as i64) << 32) | len as u32 as i64
+}
+
+/// Copy `bytes` into memory the guest allocates for them
+fn write_guest(mut store: impl AsContextMut, guest: &Guest, bytes: &[u8]) -> wasmtime::Result<(i32, i32)> {
+    let len = i32::try_from(bytes.len())?;
+    let ptr = guest.alloc.call(&mut store, len)?;
+    guest.memory.write(&mut store, ptr as u32 as usize, bytes)?;
+    Ok((ptr, len))
+}
+
+fn caller_guest(caller: &mut Caller<'_, HostState>) -> wasmtime::Result<Guest> {
+    let Some(Extern::Memory(memory)) = caller.get_export("memory") else {
+        anyhow::bail!("no exported memory");
+    };
+    let Some(Extern::Func(alloc)) = caller.get_export("filejack_alloc") else {
+        anyhow::bail!("no exported filejack_alloc");
+    };
+    let alloc = alloc.typed::<i32, i32>(&caller)?;
+    Ok(Guest { memory, alloc })
+}
+
+fn caller_bytes(caller: &mut Caller<'_, HostState>, memory: Memory, ptr: i32, len: i32) -> Option<Vec<u8>> {
+    read_packed(caller, memory, pack(ptr, len))
+}
+
+fn caller_path(caller: &mut Caller<'_, HostState>, memory: Memory, ptr: i32, len: i32) -> Option<String> {
+    String::from_utf8(caller_bytes(caller, memory, ptr, len)?).ok()
+}
+
+/// `filejack.read_file`: the file's bytes, copied into guest memory
+fn host_read_file(mut caller: Caller<'_, HostState>, path_ptr: i32, path_len: i32) -> wasmtime::Result<i64> {
+    let guest = caller_guest(&mut caller)?;
+    let Some(path) = caller_path(&mut caller, guest.memory, path_ptr, path_len) else {
+        return Ok(-1);
+    };
+    let Some(reader) = caller.data().reader.clone() else {
+        return Ok(-1);
+    };
+    match reader.read_to_bytes(&path) {
+        Ok(bytes) => {
+            let (ptr, len) = write_guest(&mut caller, &guest, &bytes)?;
+            Ok(pack(ptr, len))
+        }
+        Err(e) => {
+            debug!(path = %path, error = %e, "Plugin read refused");
+            Ok(-1)
+        }
+    }
+}
+
+/// `filejack.write_file`: replace the file's contents
+fn host_write_file(
+    mut caller: Caller<'_, HostState>,
+    path_ptr: i32,
+    path_len: i32,
+    data_ptr: i32,
+    data_len: i32,
+) -> wasmtime::Result<i64> {
+    let guest = caller_guest(&mut caller)?;
+    let Some(path) = caller_path(&mut caller, guest.memory, path_ptr, path_len) else {
+        return Ok(-1);
+    };
+    let Some(data) = caller_bytes(&mut caller, guest.memory, data_ptr, data_len) else {
+        return Ok(-1);
+    };
+    let Some(writer) = caller.data().writer.clone() else {
+        return Ok(-1);
+    };
+    match writer.write_bytes(&path, &data) {
+        Ok(()) => Ok(0),
+        Err(e) => {
+            debug!(path = %path, error = %e, "Plugin write refused");
+            Ok(-1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access_control::AccessPolicy;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    /// A plugin with three tools: `ping` answers with a tool result, `cat`
+    /// returns a file read through the host, and `spin` never returns
+    fn plugin_source(path: &Path) -> String {
+        let tools = json!([
+            {"name": "ping", "description": "Reply pong"},
+            {"name": "cat", "description": "Read a fixed file"},
+            {"name": "spin", "description": "Loop forever"}
+        ])
+        .to_string();
+        let pong = text_result("pong").to_string();
+        let path = path.display().to_string();
+        format!(
+            r#"(module
+  (import "filejack" "read_file" (func $read_file (param i32 i32) (result i64)))
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 8192))
+  (data (i32.const 0) "{tools}")
+  (data (i32.const 2048) "{pong}")
+  (data (i32.const 4096) "{path}")
+  (func (export "filejack_alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (func (export "filejack_tools") (result i64)
+    (i64.const {tools_len}))
+  (func (export "filejack_call") (param $name i32) (param $name_len i32) (param $args i32) (param $args_len i32) (result i64)
+    (if (i32.eq (local.get $name_len) (i32.const 3))
+      (then (return (call $read_file (i32.const 4096) (i32.const {path_len})))))
+    (if (i32.eq (i32.load8_u (local.get $name)) (i32.const 115))
+      (then (loop $forever (br $forever))))
+    (i64.or (i64.shl (i64.const 2048) (i64.const 32)) (i64.const {pong_len}))))"#,
+            tools = tools.replace('"', "\\\""),
+            tools_len = tools.len(),
+            pong = pong.replace('"', "\\\""),
+            pong_len = pong.len(),
+            path = path,
+            path_len = path.len(),
+        )
+    }
+
+    fn load(temp_dir: &TempDir, target: &Path) -> Vec<Arc<dyn ToolHandler>> {
+        let directory = temp_dir.path().join("plugins");
+        std::fs::create_dir(&directory).unwrap();
+        std::fs::write(directory.join("demo.wat"), plugin_source(target)).unwrap();
+        std::fs::write(directory.join("broken.wasm"), b"not wasm").unwrap();
+        load_plugins(&PluginsConfig { directory, fuel: 10_000_000, ..PluginsConfig::default() })
+    }
+
+    #[test]
+    fn test_plugin_tools() {
+        let temp_dir = tempfile::Builder::new().prefix("plugins").tempdir().unwrap();
+        let target = temp_dir.path().join("notes.txt");
+        std::fs::write(&target, "from the host").unwrap();
+        let tools = load(&temp_dir, &target);
+        let names: Vec<&str> = tools.iter().map(|tool| tool.name()).collect();
+        assert_eq!(names, ["ping", "cat", "spin"]);
+        assert_eq!(tools[0].input_schema(), json!({"type": "object"}));
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let (reader, writer) = (FileReader::new(policy.clone()), FileWriter::new(policy, false));
+        let ctx = ToolContext::new(&reader, &writer);
+        assert_eq!(tools[0].call(&ctx, json!({})).unwrap(), text_result("pong"));
+        assert_eq!(tools[1].call(&ctx, json!({})).unwrap(), text_result("from the host"));
+
+        // Running out of fuel stops the call instead of hanging the server
+        let error = tools[2].call(&ctx, json!({})).unwrap_err();
+        assert!(error.to_string().contains("Plugin demo"), "{}", error);
+    }
+
+    #[test]
+    fn test_plugin_reads_follow_policy() {
+        let temp_dir = tempfile::Builder::new().prefix("plugins").tempdir().unwrap();
+        let outside = tempfile::Builder::new().prefix("outside").tempdir().unwrap();
+        let target = outside.path().join("secret.txt");
+        std::fs::write(&target, "secret").unwrap();
+        let tools = load(&temp_dir, &target);
+
+        let policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+        let (reader, writer) = (FileReader::new(policy.clone()), FileWriter::new(policy, false));
+        let error = tools[1].call(&ToolContext::new(&reader, &writer), json!({})).unwrap_err();
+        assert!(error.to_string().contains("cat failed"), "{}", error);
+    }
+}
@@ -1,5 +1,15 @@
+use schemars::generate::SchemaSettings;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use crate::access_control::{HiddenFileDetection, RootRedaction, UnicodeNormalization};
+use crate::compression::CompressionFormat;
+use crate::file_ops::{EntryType, SortBy, SortOrder};
+use crate::line_edits::LineEdit;
+use crate::structured::{EditOperation, StructuredFormat};
+use crate::text_style::TextStyle;
 
 /// JSON-RPC 2.0 Request structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,8 +44,48 @@ pub struct JsonRpcError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpTool {
     pub name: String,
+    /// Name for host UIs to show, such as "Read a file"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
     pub description: String,
+    /// Sent as `inputSchema`, as MCP specifies; `input_schema` from older
+    /// FileJack servers is still accepted
+    #[serde(rename = "inputSchema", alias = "input_schema")]
     pub input_schema: Value,
+    /// JSON Schema of the `structuredContent` of the tool's results, for
+    /// tools that return a JSON object; sent as `outputSchema`
+    #[serde(rename = "outputSchema", alias = "output_schema", default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<Value>,
+}
+
+/// JSON Schema of a tool's parameters, for [`McpTool::input_schema`].
+///
+/// Generated from `T`'s `Deserialize` attributes and field doc comments, so
+/// the advertised schema always matches what the tool accepts. Subschemas
+/// are inlined because some clients cannot follow `$ref`.
+pub fn input_schema<T: JsonSchema>() -> Value {
+    schema_for::<T>()
+}
+
+/// JSON Schema of a tool's result, for [`McpTool::output_schema`].
+///
+/// Generated from the `Serialize` side of `T`, so it describes the
+/// `structuredContent` that [`CallToolResult::json`] sends.
+pub fn output_schema<T: JsonSchema>() -> Value {
+    schema_for::<T>()
+}
+
+fn schema_for<T: JsonSchema>() -> Value {
+    let mut schema = SchemaSettings::draft2020_12()
+        .with(|settings| {
+            settings.meta_schema = None;
+            settings.inline_subschemas = true;
+        })
+        .into_generator()
+        .into_root_schema_for::<T>();
+    schema.remove("title");
+    schema.remove("description");
+    schema.to_value()
 }
 
 /// Tool call parameters for file operations
@@ -45,123 +95,686 @@ pub struct ToolCall {
     pub arguments: Value,
 }
 
+/// Result of a `tools/call`: the content blocks shown to the client
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallToolResult {
+    pub content: Vec<ToolContent>,
+    /// The result as a JSON object, matching the tool's `outputSchema`
+    #[serde(rename = "structuredContent", default, skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<Value>,
+}
+
+impl CallToolResult {
+    /// A result holding a single piece of text
+    pub fn text(text: impl Into<String>) -> Self {
+        Self { content: vec![ToolContent::Text { text: text.into() }], structured_content: None }
+    }
+
+    /// A result holding `value` as pretty-printed JSON text. A value that
+    /// serializes to an object is also sent as `structuredContent`.
+    pub fn json<T: Serialize>(value: &T) -> crate::error::Result<Self> {
+        let value = serde_json::to_value(value)?;
+        let mut result = Self::text(serde_json::to_string_pretty(&value)?);
+        if value.is_object() {
+            result.structured_content = Some(value);
+        }
+        Ok(result)
+    }
+}
+
+/// One content block of a [`CallToolResult`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ToolContent {
+    Text {
+        text: String,
+    },
+    Image {
+        /// Base64 of the image bytes
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    Resource {
+        resource: EmbeddedResource,
+    },
+}
+
+/// Binary contents of a file embedded in a [`ToolContent::Resource`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddedResource {
+    /// `file://` URI of the file
+    pub uri: String,
+    #[serde(rename = "mimeType")]
+    pub mime_type: String,
+    /// Base64 of the bytes
+    pub blob: String,
+}
+
+/// Encoding used when returning file contents
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentEncoding {
+    /// UTF-8 text
+    #[default]
+    Text,
+    /// Base64-encoded bytes, for binary files
+    Base64,
+}
+
 /// File read parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ReadFileParams {
+    /// Path to the file to read
     pub path: String,
+    /// Return contents as UTF-8 text or as base64 for binary files
+    #[serde(default)]
+    pub encoding: ContentEncoding,
 }
 
 /// File write parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct WriteFileParams {
+    /// Path to the file to write
     pub path: String,
+    /// Content to write to the file
     pub content: String,
+    /// Write to a temporary file and rename it into place. Disable for
+    /// filesystems that do not support rename
+    #[serde(default = "default_true")]
+    pub atomic: bool,
+    /// Token from `lock_file`, needed while another client holds a lock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_token: Option<String>,
+    /// Leave the file alone, modification time included, when it already
+    /// holds `content`. Defaults to the server's `skip_unchanged_writes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_unchanged: Option<bool>,
+    /// Only write if the file still has this content hash or modification
+    /// time, as reported by `get_metadata`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub if_match: Option<String>,
+    /// Give `content` the byte order mark and dominant line ending of the
+    /// file being replaced
+    #[serde(default)]
+    pub preserve_style: bool,
+}
+
+/// Contents of a file, from [`crate::McpServer::read_file`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadFileResult {
+    pub path: String,
+    pub encoding: ContentEncoding,
+    /// The text, or base64 of the bytes when `encoding` is `base64`
+    pub content: String,
+    /// Byte order mark and line ending of a file read as text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<TextStyle>,
+    /// Set when the file is larger than the server's read limit: the byte
+    /// offset `read_range` can continue from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<u64>,
+}
+
+/// Outcome of [`crate::McpServer::write_file`] and
+/// [`crate::McpServer::append_file`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteFileResult {
+    pub path: String,
+    pub bytes_written: u64,
+    /// The file already held the content, so nothing was written
+    pub unchanged: bool,
+    /// BLAKE3 hash of the file as read back after writing, when the policy
+    /// sets `verify_writes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
 }
 
 /// List directory parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ListDirectoryParams {
+    /// Path to the directory to list
     pub path: String,
+    /// Whether to list recursively
     #[serde(default)]
     pub recursive: bool,
+    /// Glob for the entries to include: matched against names, or against
+    /// paths relative to `path` when it contains `/` (e.g. `src/*/mod.rs`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Only files or only directories
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub entry_type: Option<EntryType>,
+    /// Key to sort entries by
+    #[serde(default)]
+    pub sort_by: SortBy,
+    #[serde(default)]
+    pub order: SortOrder,
+    /// Entries to skip, for paging through a large directory
+    #[serde(default)]
+    pub offset: usize,
+    /// Most entries to return
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
 }
 
 /// Get metadata parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GetMetadataParams {
+    /// Path to the file
     pub path: String,
+    /// Include the BLAKE3 hash of a file's contents, for `if_match`, and
+    /// the byte order mark and line ending of a text file
+    #[serde(default)]
+    pub hash: bool,
 }
 
 /// Delete file parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DeleteFileParams {
+    /// Path to the file to delete
     pub path: String,
+    /// Token from `lock_file`, needed while another client holds a lock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_token: Option<String>,
 }
 
 /// Move file parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MoveFileParams {
+    /// Source file or directory path
     pub from: String,
+    /// Destination path
     pub to: String,
+    /// Token from `lock_file`, needed while another client holds a lock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_token: Option<String>,
 }
 
 /// Copy file parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CopyFileParams {
+    /// Source file path
     pub from: String,
+    /// Destination file path
     pub to: String,
+    /// Copy a directory and everything beneath it; the destination must not exist
+    #[serde(default)]
+    pub recursive: bool,
+    /// Give copies the permissions and modification times of their sources
+    #[serde(default)]
+    pub preserve: bool,
+    /// Token from `lock_file`, needed while another client holds a lock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_token: Option<String>,
 }
 
 /// Append file parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AppendFileParams {
+    /// Path to the file
     pub path: String,
+    /// Content to append
     pub content: String,
+    /// Token from `lock_file`, needed while another client holds a lock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_token: Option<String>,
+    /// Give `content` the file's dominant line ending
+    #[serde(default)]
+    pub preserve_style: bool,
 }
 
 /// File exists parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FileExistsParams {
+    /// Path to check
+    pub path: String,
+}
+
+/// Get extended attributes parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GetExtendedAttributesParams {
+    /// Path to the file
     pub path: String,
 }
 
 /// Create directory parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CreateDirectoryParams {
+    /// Path to the directory to create
     pub path: String,
+    /// Create parent directories if they don't exist
     #[serde(default)]
     pub recursive: bool,
 }
 
 /// Remove directory parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RemoveDirectoryParams {
+    /// Path to the directory to remove
     pub path: String,
+    /// Remove directory and all its contents
     #[serde(default)]
     pub recursive: bool,
 }
 
 /// Read lines parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ReadLinesParams {
+    /// Path to the file
     pub path: String,
+    /// Starting line number (1-based, inclusive)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub start_line: Option<usize>,
+    /// Ending line number (1-based, inclusive)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub end_line: Option<usize>,
+    /// Read last N lines (overrides start_line/end_line)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tail: Option<usize>,
 }
 
+/// Tail follow parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TailFollowParams {
+    /// File to follow
+    pub path: String,
+    /// How often to check the file for new lines, in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend(
+        "default" = crate::follow::DEFAULT_FOLLOW_INTERVAL.as_millis() as u64,
+        "minimum" = crate::follow::MIN_FOLLOW_INTERVAL.as_millis() as u64,
+        "maximum" = crate::follow::MAX_FOLLOW_INTERVAL.as_millis() as u64
+    ))]
+    pub interval_ms: Option<u64>,
+}
+
+/// Tail cancel parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TailCancelParams {
+    /// Follower ID returned by tail_follow
+    pub follower: u64,
+}
+
+/// Set working directory parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SetWorkingDirectoryParams {
+    /// Directory later relative paths are resolved against; itself
+    /// resolved against the current one when relative
+    pub path: String,
+}
+
 /// Search files parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SearchFilesParams {
+    /// Base directory to search in
     pub path: String,
+    /// Glob pattern (e.g., '*.log', 'test_*.rs')
     pub pattern: String,
+    /// Search recursively in subdirectories
     #[serde(default = "default_true")]
     pub recursive: bool,
+    /// Maximum number of results to return
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_results: Option<usize>,
 }
 
+/// Pack directory parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PackDirectoryParams {
+    /// Directory whose text files are bundled
+    pub path: String,
+    /// Glob for the files to include: matched against file names, or
+    /// against paths relative to `path` when it contains `/` (e.g. `src/**/*.rs`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Most bytes of file text in the bundle
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("default" = crate::pack::DEFAULT_PACK_MAX_BYTES))]
+    pub max_bytes: Option<u64>,
+    /// Larger files are skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("default" = crate::pack::DEFAULT_PACK_MAX_FILE_BYTES))]
+    pub max_file_bytes: Option<u64>,
+}
+
+/// Operation history parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GetOperationHistoryParams {
+    /// Only the most recent entries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// Include a unified diff for each text file that changed
+    #[serde(default)]
+    pub diffs: bool,
+}
+
+/// Summarize workspace parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SummarizeWorkspaceParams {
+    /// Directory to summarize
+    pub path: String,
+    /// Number of largest files to list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("default" = crate::overview::DEFAULT_LARGEST_FILES))]
+    pub largest: Option<usize>,
+}
+
+/// Recent files parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RecentFilesParams {
+    /// Base directory to search in
+    pub path: String,
+    /// Maximum number of files to return
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("default" = crate::file_ops::DEFAULT_RECENT_LIMIT))]
+    pub max_results: Option<usize>,
+}
+
+/// Full-text content search parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SearchContentParams {
+    /// Base directory to search in
+    pub path: String,
+    /// Text to find, matched literally (at least 3 characters)
+    pub query: String,
+    /// Maximum number of matching lines to return
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "index", schemars(extend("default" = crate::file_ops::DEFAULT_CONTENT_RESULTS)))]
+    pub max_results: Option<usize>,
+}
+
+/// File compression parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CompressFileParams {
+    /// File to compress
+    pub from: String,
+    /// Destination (default: the source path plus .gz or .zst)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    #[serde(default)]
+    pub format: CompressionFormat,
+    /// Compression level: 0-9 for gzip (default 6), 1-22 for zstd (default 3)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<i32>,
+    /// Token from `lock_file`, needed while another client holds a lock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_token: Option<String>,
+}
+
+/// Structured file edit parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UpdateStructuredFileParams {
+    /// JSON, TOML, or YAML file to edit
+    pub path: String,
+    /// Detected from the file extension when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<StructuredFormat>,
+    /// Edits applied in order; if any fails, the file is left unchanged
+    pub operations: Vec<EditOperation>,
+    /// Only edit if the file still has this content hash or modification
+    /// time, as reported by `get_metadata`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub if_match: Option<String>,
+    /// Token from `lock_file`, needed while another client holds a lock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_token: Option<String>,
+}
+
+/// Line edit parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EditLinesParams {
+    /// Text file to edit
+    pub path: String,
+    /// Edits numbered against the file as it is now; they must not overlap,
+    /// and if any fails, the file is left unchanged
+    pub operations: Vec<LineEdit>,
+    /// Only edit if the file still has this content hash or modification
+    /// time, as reported by `get_metadata`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub if_match: Option<String>,
+    /// Token from `lock_file`, needed while another client holds a lock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_token: Option<String>,
+}
+
+/// File decompression parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DecompressFileParams {
+    /// Compressed file
+    pub from: String,
+    /// Destination (default: the source path without .gz or .zst)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Detected from the file's contents when omitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<CompressionFormat>,
+    /// Largest decompressed size in bytes; the policy's max_file_size also applies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("default" = crate::compression::DEFAULT_MAX_DECOMPRESSED_BYTES))]
+    pub max_size: Option<u64>,
+    /// Token from `lock_file`, needed while another client holds a lock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_token: Option<String>,
+}
+
+/// Image metadata parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImageInfoParams {
+    /// Path to the image (PNG, JPEG, GIF, WebP, or BMP)
+    pub path: String,
+    /// Also return a thumbnail as image content
+    #[serde(default)]
+    pub thumbnail: bool,
+    /// Longest side of the thumbnail in pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(
+        feature = "image",
+        schemars(extend(
+            "default" = crate::images::DEFAULT_THUMBNAIL_SIZE,
+            "maximum" = crate::images::MAX_THUMBNAIL_SIZE
+        ))
+    )]
+    pub max_size: Option<u32>,
+}
+
 fn default_true() -> bool {
     true
 }
 
 /// Grep file parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GrepFileParams {
+    /// Path to the file to search, or a directory to search all files beneath it
     pub path: String,
+    /// Regular expression pattern
     pub pattern: String,
+    /// Maximum number of matches to return
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_matches: Option<usize>,
+    /// Number of context lines before and after each match
     #[serde(skip_serializing_if = "Option::is_none")]
     pub context_lines: Option<usize>,
 }
 
+/// Byte-range read parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ReadRangeParams {
+    /// Path to the file to read
+    pub path: String,
+    /// Byte offset to start reading at
+    pub offset: u64,
+    /// Maximum number of bytes to read
+    pub length: usize,
+    /// Encoding of the returned data
+    #[serde(default = "default_base64")]
+    pub encoding: ContentEncoding,
+}
+
+/// Byte-range write parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WriteRangeParams {
+    /// Path to the file to patch (created if missing)
+    pub path: String,
+    /// Byte offset to start writing at
+    pub offset: u64,
+    /// Data to write
+    pub content: String,
+    /// Encoding of content
+    #[serde(default = "default_base64")]
+    pub encoding: ContentEncoding,
+    /// Token from `lock_file`, needed while another client holds a lock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_token: Option<String>,
+}
+
+fn default_base64() -> ContentEncoding {
+    ContentEncoding::Base64
+}
+
+/// Bytes read by [`crate::McpServer::read_range`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ReadRangeResult {
+    pub offset: u64,
+    pub bytes_read: usize,
+    /// Whether the range reached the end of the file
+    pub eof: bool,
+    pub encoding: ContentEncoding,
+    /// The bytes, as text or base64 according to `encoding`
+    pub data: String,
+}
+
+/// One attribute returned by [`crate::McpServer::get_extended_attributes`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ExtendedAttribute {
+    pub name: String,
+    /// Text when the value is UTF-8, base64 otherwise
+    pub encoding: ContentEncoding,
+    pub value: String,
+}
+
+/// Outcome of [`crate::McpServer::write_range`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteRangeResult {
+    pub path: String,
+    pub offset: u64,
+    pub bytes_written: usize,
+    /// Size of the file after the write
+    pub file_size: u64,
+    /// BLAKE3 hash of the file as read back after writing, when the policy
+    /// sets `verify_writes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+/// Lock file parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LockFileParams {
+    /// Path to the file to lock
+    pub path: String,
+    /// Lease length in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend(
+        "default" = crate::locks::DEFAULT_LEASE.as_secs(),
+        "maximum" = crate::locks::MAX_LEASE.as_secs()
+    ))]
+    pub ttl_seconds: Option<u64>,
+    /// Name of the lock holder, shown to other clients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Token of a lock you already hold, to renew its lease
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// Unlock file parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UnlockFileParams {
+    /// Path to the locked file
+    pub path: String,
+    /// Token returned by lock_file
+    pub token: String,
+}
+
+/// Claim path parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ClaimPathParams {
+    /// File or directory to claim; a directory claim covers everything beneath it
+    pub path: String,
+    /// Lease length in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend(
+        "default" = crate::locks::DEFAULT_LEASE.as_secs(),
+        "maximum" = crate::locks::MAX_LEASE.as_secs()
+    ))]
+    pub ttl_seconds: Option<u64>,
+}
+
+/// Release path parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ReleasePathParams {
+    /// Path claimed with claim_path
+    pub path: String,
+}
+
+/// Git status parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GitStatusParams {
+    /// File or directory inside a git working tree
+    pub path: String,
+}
+
+/// Git log parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GitLogParams {
+    /// File or directory inside a git working tree
+    pub path: String,
+    /// Maximum number of commits to return
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "git", schemars(extend("default" = crate::git::DEFAULT_LOG_LIMIT)))]
+    pub max_count: Option<usize>,
+}
+
+/// Git show parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GitShowParams {
+    /// Path of the file in the working tree
+    pub path: String,
+    /// Commit, branch, tag, or expression such as HEAD~2
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("default" = "HEAD"))]
+    pub revision: Option<String>,
+}
+
+/// Git snapshot parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GitSnapshotParams {
+    /// Any path inside the git working tree
+    pub path: String,
+    /// Commit message, after the configured prefix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Git blame parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GitBlameParams {
+    /// Path of the file in the working tree
+    pub path: String,
+    /// First line to blame (1-based)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<usize>,
+    /// Last line to blame (inclusive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+}
+
 /// Grep match result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GrepMatch {
+    /// File containing the match, set when searching a directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
     pub line_number: usize,
     pub line_content: String,
     #[serde(skip_serializing_if = "Vec::is_empty")]
@@ -170,6 +783,98 @@ pub struct GrepMatch {
     pub context_after: Vec<String>,
 }
 
+/// What the server has done and the policy it applies, from
+/// [`crate::McpServer::server_info`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ServerInfo {
+    pub name: String,
+    pub version: String,
+    pub uptime_seconds: u64,
+    /// Calls per tool, including failed ones
+    pub operations: BTreeMap<String, u64>,
+    pub errors: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub bytes_transferred: u64,
+    pub rate_limited: u64,
+    pub policy: PolicySummary,
+    pub mounts: Vec<MountSummary>,
+}
+
+/// The parts of the access policy a client may see
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PolicySummary {
+    pub allowed_paths: Vec<PathBuf>,
+    /// Only the count: the list itself would tell clients where the secrets are
+    pub denied_path_count: usize,
+    pub append_only_paths: Vec<PathBuf>,
+    pub protected_paths: Vec<String>,
+    pub sensitive_paths: Vec<String>,
+    pub allowed_extensions: Vec<String>,
+    pub denied_extensions: Vec<String>,
+    pub max_file_size: u64,
+    pub read_only: bool,
+    pub allow_symlinks: bool,
+    pub allow_hidden_files: bool,
+    pub hidden_file_detection: HiddenFileDetection,
+    pub unicode_normalization: UnicodeNormalization,
+    pub deny_files_not_owned_by_user: bool,
+    pub allow_special_files: bool,
+    pub directory_overlays: bool,
+    pub browse_archives: bool,
+    pub verify_writes: bool,
+    pub redact_roots: RootRedaction,
+}
+
+/// A mount prefix and whether it is served locally or by another server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MountSummary {
+    pub prefix: String,
+    /// `local` or `mcp`
+    pub backend: String,
+}
+
+/// Maximum nesting depth of arrays and objects in a JSON document.
+///
+/// Scans the raw text without building a value, so it is safe to call on
+/// adversarial input before handing it to the parser. Brackets inside string
+/// literals are ignored. Stops early once `limit` is exceeded.
+pub fn json_nesting_depth(input: &str, limit: usize) -> usize {
+    let mut depth = 0usize;
+    let mut max_depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for byte in input.bytes() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max_depth {
+                    max_depth = depth;
+                    if max_depth > limit {
+                        break;
+                    }
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    max_depth
+}
+
 impl JsonRpcResponse {
     pub fn success(id: Option<Value>, result: Value) -> Self {
         Self {
@@ -247,6 +952,7 @@ mod tests {
     fn test_mcp_tool_serialization() {
         let tool = McpTool {
             name: "read_file".to_string(),
+            title: None,
             description: "Read a file".to_string(),
             input_schema: json!({
                 "type": "object",
@@ -254,30 +960,64 @@ mod tests {
                     "path": {"type": "string"}
                 }
             }),
+            output_schema: None,
         };
 
         let serialized = serde_json::to_string(&tool).unwrap();
         assert!(serialized.contains("read_file"));
+        assert!(!serialized.contains("outputSchema"));
+    }
+
+    #[test]
+    fn test_output_schema_and_structured_content() {
+        let schema = output_schema::<ReadRangeResult>();
+        assert_eq!(schema["type"], "object");
+        assert!(schema["properties"]["eof"].is_object());
+
+        let result = ReadRangeResult { offset: 0, bytes_read: 2, eof: true, encoding: ContentEncoding::Text, data: "hi".to_string() };
+        let wire = serde_json::to_value(CallToolResult::json(&result).unwrap()).unwrap();
+        assert_eq!(wire["structuredContent"]["data"], "hi");
+        assert_eq!(serde_json::from_str::<Value>(wire["content"][0]["text"].as_str().unwrap()).unwrap(), wire["structuredContent"]);
+        // Only objects are structured content
+        let wire = serde_json::to_value(CallToolResult::json(&vec![1, 2]).unwrap()).unwrap();
+        assert!(wire.get("structuredContent").is_none());
     }
 
     #[test]
     fn test_read_file_params() {
         let params = ReadFileParams {
             path: "/test/file.txt".to_string(),
+            encoding: ContentEncoding::Text,
         };
         
         let json = serde_json::to_value(&params).unwrap();
         assert_eq!(json["path"], "/test/file.txt");
+        assert_eq!(json["encoding"], "text");
         
         let deserialized: ReadFileParams = serde_json::from_value(json).unwrap();
         assert_eq!(deserialized.path, "/test/file.txt");
     }
 
+    #[test]
+    fn test_read_file_params_encoding_default() {
+        let params: ReadFileParams = serde_json::from_value(json!({"path": "a.bin"})).unwrap();
+        assert_eq!(params.encoding, ContentEncoding::Text);
+
+        let params: ReadFileParams =
+            serde_json::from_value(json!({"path": "a.bin", "encoding": "base64"})).unwrap();
+        assert_eq!(params.encoding, ContentEncoding::Base64);
+    }
+
     #[test]
     fn test_write_file_params() {
         let params = WriteFileParams {
             path: "/test/file.txt".to_string(),
             content: "Hello, World!".to_string(),
+            atomic: true,
+            lock_token: None,
+            skip_unchanged: None,
+            if_match: None,
+            preserve_style: false,
         };
         
         let json = serde_json::to_value(&params).unwrap();
@@ -287,6 +1027,48 @@ mod tests {
         let deserialized: WriteFileParams = serde_json::from_value(json).unwrap();
         assert_eq!(deserialized.path, "/test/file.txt");
         assert_eq!(deserialized.content, "Hello, World!");
+        assert!(deserialized.atomic);
+
+        let in_place: WriteFileParams =
+            serde_json::from_value(json!({"path": "a.txt", "content": "", "atomic": false})).unwrap();
+        assert!(!in_place.atomic);
+    }
+
+    #[test]
+    fn test_input_schema_matches_deserializer() {
+        let schema = input_schema::<SearchFilesParams>();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["required"], json!(["path", "pattern"]));
+        assert!(schema.get("title").is_none() && schema.get("$schema").is_none());
+        let params: SearchFilesParams = serde_json::from_value(json!({"path": "a", "pattern": "*"})).unwrap();
+        assert_eq!(schema["properties"]["recursive"]["default"], json!(params.recursive));
+
+        let schema = input_schema::<ListDirectoryParams>();
+        let params: ListDirectoryParams = serde_json::from_value(json!({"path": "a"})).unwrap();
+        assert_eq!(schema["properties"]["recursive"]["default"], json!(params.recursive));
+        assert_eq!(schema["properties"]["sort_by"]["default"], json!(params.sort_by));
+        assert_eq!(schema["properties"]["order"]["default"], "asc");
+        assert!(schema["properties"].get("type").is_some());
+
+        let schema = input_schema::<ReadRangeParams>();
+        let params: ReadRangeParams = serde_json::from_value(json!({"path": "a", "offset": 0, "length": 1})).unwrap();
+        assert_eq!(schema["properties"]["encoding"]["default"], json!(params.encoding));
+
+        let schema = input_schema::<WriteFileParams>();
+        assert_eq!(schema["properties"]["atomic"]["default"], true);
+        assert!(schema["properties"]["lock_token"]["description"].is_string());
+    }
+
+    #[test]
+    fn test_json_nesting_depth() {
+        assert_eq!(json_nesting_depth(r#"{"a": 1}"#, 64), 1);
+        assert_eq!(json_nesting_depth(r#"{"a": [{"b": []}]}"#, 64), 4);
+        assert_eq!(json_nesting_depth(r#"{"a": "[[[{{{"}"#, 64), 1);
+        assert_eq!(json_nesting_depth(r#"{"a": "\"[[["}"#, 64), 1);
+        assert_eq!(json_nesting_depth("42", 64), 0);
+
+        let deep = "[".repeat(100_000);
+        assert_eq!(json_nesting_depth(&deep, 10), 11);
     }
 
     #[test]
@@ -299,4 +1081,31 @@ mod tests {
         assert_eq!(call.name, "read_file");
         assert_eq!(call.arguments["path"], "test.txt");
     }
+
+    #[test]
+    fn test_call_tool_result_wire_format() {
+        let result = CallToolResult {
+            content: vec![
+                ToolContent::Text { text: "hello".to_string() },
+                ToolContent::Image { data: "AAAA".to_string(), mime_type: "image/png".to_string() },
+                ToolContent::Resource {
+                    resource: EmbeddedResource {
+                        uri: "file:///a.bin".to_string(),
+                        mime_type: "application/octet-stream".to_string(),
+                        blob: "AQI=".to_string(),
+                    },
+                },
+            ],
+            structured_content: None,
+        };
+        let wire = json!({
+            "content": [
+                {"type": "text", "text": "hello"},
+                {"type": "image", "data": "AAAA", "mimeType": "image/png"},
+                {"type": "resource", "resource": {"uri": "file:///a.bin", "mimeType": "application/octet-stream", "blob": "AQI="}}
+            ]
+        });
+        assert_eq!(serde_json::to_value(&result).unwrap(), wire);
+        assert_eq!(serde_json::from_value::<CallToolResult>(wire).unwrap(), result);
+    }
 }
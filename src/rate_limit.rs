@@ -1,24 +1,64 @@
+use crate::error::{FileJackError, Result};
+use crate::metrics::Metrics;
+use crate::middleware::{Call, Middleware};
+use arc_swap::ArcSwap;
+use governor::clock::{Clock, DefaultClock};
 use governor::{Quota, RateLimiter as GovernorLimiter};
 use nonzero_ext::nonzero;
+use serde_json::Value;
 use std::num::NonZeroU32;
 use std::sync::Arc;
 use std::time::Duration;
+use tracing::warn;
+
+/// Requests per second allowed by [`RateLimiter::moderate`]
+pub const DEFAULT_REQUESTS_PER_SECOND: u32 = 100;
 
 /// Rate limiter for MCP requests
 #[derive(Clone)]
 pub struct RateLimiter {
-    limiter: Arc<GovernorLimiter<governor::state::direct::NotKeyed, governor::state::InMemoryState, governor::clock::DefaultClock>>,
+    requests_per_second: u32,
+    max_wait: Duration,
+    clock: DefaultClock,
+    limiter: Arc<GovernorLimiter<governor::state::direct::NotKeyed, governor::state::InMemoryState, DefaultClock>>,
 }
 
 impl RateLimiter {
     /// Create a new rate limiter with specified requests per second
     pub fn new(requests_per_second: u32) -> Self {
-        let quota = Quota::per_second(NonZeroU32::new(requests_per_second).unwrap_or(nonzero!(10u32)));
+        let rate = NonZeroU32::new(requests_per_second).unwrap_or(nonzero!(10u32));
+        let clock = DefaultClock::default();
         Self {
-            limiter: Arc::new(GovernorLimiter::direct(quota)),
+            requests_per_second: rate.get(),
+            max_wait: Duration::ZERO,
+            limiter: Arc::new(GovernorLimiter::direct_with_clock(Quota::per_second(rate), &clock)),
+            clock,
         }
     }
 
+    /// Hold an over-limit request for up to `max_wait` until the budget
+    /// allows it, instead of refusing it at once
+    pub fn with_max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    /// How long an over-limit request is held before it is refused
+    pub fn max_wait(&self) -> Duration {
+        self.max_wait
+    }
+
+    /// Requests per second this limiter allows
+    pub fn requests_per_second(&self) -> u32 {
+        self.requests_per_second
+    }
+
+    /// A limiter with the same rate and a full budget of its own; clones
+    /// share one budget
+    pub fn fresh(&self) -> Self {
+        Self::new(self.requests_per_second).with_max_wait(self.max_wait)
+    }
+
     /// Create a permissive rate limiter (1000 req/s)
     pub fn permissive() -> Self {
         Self::new(1000)
@@ -26,7 +66,7 @@ impl RateLimiter {
 
     /// Create a moderate rate limiter (100 req/s)
     pub fn moderate() -> Self {
-        Self::new(100)
+        Self::new(DEFAULT_REQUESTS_PER_SECOND)
     }
 
     /// Create a strict rate limiter (10 req/s)
@@ -39,6 +79,32 @@ impl RateLimiter {
         self.limiter.check().is_ok()
     }
 
+    /// Take a request from the budget, or return how long until one is
+    /// available
+    pub fn try_acquire(&self) -> std::result::Result<(), Duration> {
+        self.limiter.check().map_err(|not_until| not_until.wait_time_from(self.clock.now()))
+    }
+
+    /// Take a request from the budget, blocking while the wait is within
+    /// `max_wait`. A refusal carries how long the client should back off.
+    pub fn acquire(&self, max_wait: Duration) -> Result<()> {
+        let mut waited = Duration::ZERO;
+        loop {
+            match self.try_acquire() {
+                Ok(()) => return Ok(()),
+                Err(wait) if waited + wait <= max_wait => {
+                    std::thread::sleep(wait);
+                    waited += wait;
+                }
+                Err(wait) => {
+                    // Round up, so a client retrying after this long is let in
+                    let retry_after_ms = wait.as_nanos().div_ceil(1_000_000).max(1) as u64;
+                    return Err(FileJackError::RateLimited { retry_after_ms });
+                }
+            }
+        }
+    }
+
     /// Wait until a request can be processed (blocking)
     pub fn wait(&self) {
         while self.limiter.check().is_err() {
@@ -53,6 +119,51 @@ impl Default for RateLimiter {
     }
 }
 
+/// Refuses requests beyond a [`RateLimiter`]'s rate. Each session draws on
+/// its own bucket, except the shared default session, which uses the
+/// limiter itself.
+pub struct RateLimitMiddleware {
+    limiter: ArcSwap<RateLimiter>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl RateLimitMiddleware {
+    pub fn new(limiter: RateLimiter) -> Self {
+        Self { limiter: ArcSwap::from_pointee(limiter), metrics: None }
+    }
+
+    /// Count refused requests in `metrics`
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// The limiter requests are currently checked against
+    pub fn limiter(&self) -> RateLimiter {
+        RateLimiter::clone(&self.limiter.load())
+    }
+
+    /// Check later requests against `limiter`; sessions get fresh buckets
+    /// when its rate differs from the previous one
+    pub fn set_limiter(&self, limiter: RateLimiter) {
+        self.limiter.store(Arc::new(limiter));
+    }
+}
+
+impl Middleware for RateLimitMiddleware {
+    fn before_call(&self, call: &Call<'_>, _params: &mut Value) -> Result<Option<Value>> {
+        let configured = self.limiter.load();
+        let Err(err) = call.session().rate_limiter(&configured).acquire(configured.max_wait()) else {
+            return Ok(None);
+        };
+        warn!(session = call.session().id(), "Rate limit exceeded");
+        if let Some(metrics) = &self.metrics {
+            metrics.record_rate_limited();
+        }
+        Err(err)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -97,4 +208,39 @@ mod tests {
         let limiter = RateLimiter::strict();
         assert!(limiter.check());
     }
+
+    #[test]
+    fn test_rate_limit_middleware() {
+        let metrics = Arc::new(Metrics::new());
+        let middleware = RateLimitMiddleware::new(RateLimiter::new(1)).with_metrics(Arc::clone(&metrics));
+        let session = crate::session::Session::new("s");
+        let call = Call::new(&session, "tools/list", None);
+        assert!(middleware.before_call(&call, &mut Value::Null).unwrap().is_none());
+        assert!(matches!(middleware.before_call(&call, &mut Value::Null), Err(FileJackError::RateLimited { .. })));
+        assert!(metrics.render().contains("filejack_rate_limited_total 1"));
+
+        middleware.set_limiter(RateLimiter::new(5));
+        assert_eq!(middleware.limiter().requests_per_second(), 5);
+        assert!(middleware.before_call(&call, &mut Value::Null).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_refusal_reports_retry_after() {
+        let limiter = RateLimiter::new(2);
+        assert!(limiter.acquire(Duration::ZERO).is_ok());
+        assert!(limiter.acquire(Duration::ZERO).is_ok());
+        match limiter.acquire(Duration::ZERO) {
+            Err(FileJackError::RateLimited { retry_after_ms }) => assert!((1..=500).contains(&retry_after_ms)),
+            other => panic!("expected a rate limit error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_max_wait_queues_over_limit_requests() {
+        let limiter = RateLimiter::new(20).with_max_wait(Duration::from_millis(200));
+        assert_eq!(limiter.fresh().max_wait(), Duration::from_millis(200));
+        for _ in 0..25 {
+            assert!(limiter.acquire(limiter.max_wait()).is_ok());
+        }
+    }
 }
@@ -0,0 +1,136 @@
+use crate::config::Config;
+use crate::error::Result;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+
+/// How often the config file is checked for changes by default
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Watches configuration files and reloads them when they change.
+///
+/// The files are polled for modification time and size changes; on Unix a
+/// SIGHUP forces a reload even if nothing looks changed. A configuration that
+/// fails to load is logged and skipped, so the previous one stays in effect.
+/// The watcher thread stops when the watcher is dropped.
+pub struct ConfigWatcher {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    #[cfg(unix)]
+    signal: Option<signal_hook::SigId>,
+}
+
+impl ConfigWatcher {
+    /// Start watching `paths`. When any of them changes, `load` builds the new
+    /// configuration and `on_reload` receives it.
+    pub fn spawn<L, F>(
+        paths: Vec<PathBuf>,
+        poll_interval: Duration,
+        load: L,
+        mut on_reload: F,
+    ) -> Result<Self>
+    where
+        L: Fn() -> Result<Config> + Send + 'static,
+        F: FnMut(Config) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let hangup = Arc::new(AtomicBool::new(false));
+
+        #[cfg(unix)]
+        let signal = Some(signal_hook::flag::register(signal_hook::consts::SIGHUP, Arc::clone(&hangup))?);
+
+        let thread_stop = Arc::clone(&stop);
+        let handle = std::thread::Builder::new()
+            .name("filejack-config-watcher".to_string())
+            .spawn(move || {
+                let mut last_seen: Vec<_> = paths.iter().map(|p| fingerprint(p)).collect();
+                while !thread_stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(poll_interval);
+
+                    let current: Vec<_> = paths.iter().map(|p| fingerprint(p)).collect();
+                    let signalled = hangup.swap(false, Ordering::Relaxed);
+                    if current == last_seen && !signalled {
+                        continue;
+                    }
+
+                    if signalled {
+                        info!("SIGHUP received, reloading configuration");
+                    }
+                    for (path, _) in paths.iter().zip(&current).zip(&last_seen)
+                        .filter(|((_, now), before)| now != before)
+                        .map(|((path, now), _)| (path, now))
+                    {
+                        info!("Configuration file {} changed, reloading", path.display());
+                    }
+                    last_seen = current;
+
+                    match load() {
+                        Ok(config) => on_reload(config),
+                        Err(e) => error!("Keeping previous configuration, reload failed: {}", e),
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+            #[cfg(unix)]
+            signal,
+        })
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(id) = self.signal.take() {
+            signal_hook::low_level::unregister(id);
+        }
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Modification time and size used to detect edits to the config file
+fn fingerprint(path: &Path) -> Option<(Option<SystemTime>, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok(), metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_watcher_reloads_changed_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("filejack.json");
+        Config::default_restricted(temp_dir.path().to_path_buf()).to_file(&path).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let load_path = path.clone();
+        let _watcher = ConfigWatcher::spawn(
+            vec![path.clone()],
+            Duration::from_millis(10),
+            move || Config::from_file(&load_path),
+            move |config| {
+                let _ = tx.send(config);
+            },
+        ).unwrap();
+
+        // Invalid contents are skipped
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+        Config::read_only(temp_dir.path().to_path_buf()).to_file(&path).unwrap();
+        let config = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(config.access_policy.read_only);
+    }
+}
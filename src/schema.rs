@@ -0,0 +1,214 @@
+use crate::config::{Config, IndexConfig, PluginsConfig, SftpConfig, WorkspacesConfig};
+use crate::error::{FileJackError, Result};
+use crate::mounts::MOUNT_FIELDS;
+use serde_json::{Map, Value};
+
+/// Version of the configuration layout written by this build
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
+/// Policy settings that the pre-versioning layout kept at the top level
+const LEGACY_POLICY_KEYS: &[&str] = &[
+    "allowed_paths",
+    "denied_paths",
+    "allowed_extensions",
+    "denied_extensions",
+    "max_file_size",
+    "allow_symlinks",
+    "allow_hidden_files",
+    "read_only",
+];
+
+/// Bring a configuration document up to [`CURRENT_SCHEMA_VERSION`] in place.
+///
+/// Documents without `schema_version` may use the pre-versioning layout with
+/// policy settings (and the old `base_path`) at the top level; those are moved
+/// under `access_policy`. Documents from a newer FileJack are left alone and
+/// parsed as far as this build understands them. Returns notes describing
+/// what was changed or could not be understood.
+pub fn migrate(value: &mut Value) -> Result<Vec<String>> {
+    let mut notes = Vec::new();
+    let Some(root) = value.as_object_mut() else {
+        return Err(FileJackError::ConfigError("Configuration must be an object".to_string()));
+    };
+
+    let version = match root.get("schema_version") {
+        None => 0,
+        Some(Value::Number(n)) => n.as_u64()
+            .and_then(|n| u32::try_from(n).ok())
+            .ok_or_else(|| FileJackError::ConfigError(format!("Invalid schema_version {}", n)))?,
+        Some(other) => return Err(FileJackError::ConfigError(
+            format!("schema_version must be a number, got {}", other)
+        )),
+    };
+
+    if version > CURRENT_SCHEMA_VERSION {
+        notes.push(format!(
+            "Configuration uses schema_version {}, newer than the {} supported by this build; \
+             settings it does not recognize are ignored",
+            version, CURRENT_SCHEMA_VERSION
+        ));
+        return Ok(notes);
+    }
+
+    if version == 0 {
+        migrate_flat_policy(root, &mut notes);
+    }
+    root.insert("schema_version".to_string(), Value::from(CURRENT_SCHEMA_VERSION));
+    Ok(notes)
+}
+
+/// Move top-level policy settings from the pre-versioning layout into `access_policy`
+fn migrate_flat_policy(root: &mut Map<String, Value>, notes: &mut Vec<String>) {
+    // (old key, new key under access_policy, value)
+    let mut moved = Vec::new();
+    if let Some(base_path) = root.remove("base_path") {
+        moved.push(("base_path", "allowed_paths", Value::Array(vec![base_path])));
+    }
+    for key in LEGACY_POLICY_KEYS {
+        if let Some(value) = root.remove(*key) {
+            moved.push((key, key, value));
+        }
+    }
+    if moved.is_empty() {
+        return;
+    }
+
+    let policy = root.entry("access_policy")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if let Some(policy) = policy.as_object_mut() {
+        for (old, new, value) in moved {
+            if policy.contains_key(new) {
+                notes.push(format!(
+                    "Top-level '{}' conflicts with access_policy.{}; keeping access_policy.{}",
+                    old, new, new
+                ));
+            } else {
+                notes.push(format!("Moved top-level '{}' to access_policy.{}", old, new));
+                policy.insert(new.to_string(), value);
+            }
+        }
+    }
+}
+
+/// List keys in a configuration document that no setting corresponds to,
+/// as dotted paths (profiles are checked with the same rules)
+pub fn unknown_fields(value: &Value) -> Vec<String> {
+    let mut known = serde_json::to_value(Config::default()).unwrap_or(Value::Null);
+    // Optional sections are left out of the default configuration
+    known["sftp"] = serde_json::to_value(SftpConfig::default()).unwrap_or(Value::Null);
+    known["index"] = serde_json::to_value(IndexConfig::default()).unwrap_or(Value::Null);
+    known["plugins"] = serde_json::to_value(PluginsConfig::default()).unwrap_or(Value::Null);
+    known["workspaces"] = serde_json::to_value(WorkspacesConfig::default()).unwrap_or(Value::Null);
+    let mut unknown = Vec::new();
+    let Some(root) = value.as_object() else {
+        return unknown;
+    };
+
+    for (key, section) in root {
+        match key.as_str() {
+            "schema_version" => {}
+            "profiles" => {
+                for (name, profile) in section.as_object().into_iter().flatten() {
+                    for (key, section) in profile.as_object().into_iter().flatten() {
+                        if key != "extends" {
+                            check_section(&known, key, section, &format!("profiles.{}.", name), &mut unknown);
+                        }
+                    }
+                }
+            }
+            _ => check_section(&known, key, section, "", &mut unknown),
+        }
+    }
+    unknown.sort();
+    unknown
+}
+
+fn check_section(known: &Value, key: &str, section: &Value, prefix: &str, unknown: &mut Vec<String>) {
+    // Keys of `aliases` are the aliases themselves
+    if key == "aliases" {
+        return;
+    }
+    if key == "mounts" {
+        for (i, mount) in section.as_array().into_iter().flatten().enumerate() {
+            for field in mount.as_object().into_iter().flat_map(|m| m.keys()) {
+                if !MOUNT_FIELDS.contains(&field.as_str()) {
+                    unknown.push(format!("{}mounts.{}.{}", prefix, i, field));
+                }
+            }
+        }
+        return;
+    }
+    let Some(fields) = known.get(key).and_then(Value::as_object) else {
+        unknown.push(format!("{}{}", prefix, key));
+        return;
+    };
+    for field in section.as_object().into_iter().flat_map(|s| s.keys()) {
+        if !fields.contains_key(field) {
+            unknown.push(format!("{}{}.{}", prefix, key, field));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_migrate_flat_layout() {
+        let mut value = json!({
+            "base_path": "/workspace",
+            "read_only": true,
+            "access_policy": {"max_file_size": 10},
+            "server": {"name": "Old"}
+        });
+        let notes = migrate(&mut value).unwrap();
+        assert_eq!(notes, vec![
+            "Moved top-level 'base_path' to access_policy.allowed_paths",
+            "Moved top-level 'read_only' to access_policy.read_only",
+        ]);
+        assert_eq!(value["schema_version"], json!(CURRENT_SCHEMA_VERSION));
+        assert_eq!(value["access_policy"]["allowed_paths"], json!(["/workspace"]));
+        assert_eq!(value["access_policy"]["read_only"], json!(true));
+        assert_eq!(value["access_policy"]["max_file_size"], json!(10));
+        assert!(value.get("base_path").is_none());
+
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert!(config.access_policy.read_only);
+    }
+
+    #[test]
+    fn test_migrate_current_and_newer_versions() {
+        let mut current = json!({"schema_version": 1, "access_policy": {}, "read_only": true});
+        assert!(migrate(&mut current).unwrap().is_empty());
+        assert!(current.get("read_only").is_some(), "versioned documents are not rewritten");
+
+        let mut newer = json!({"schema_version": 99, "access_policy": {}});
+        let notes = migrate(&mut newer).unwrap();
+        assert!(notes[0].contains("newer"));
+        assert_eq!(newer["schema_version"], json!(99));
+
+        assert!(migrate(&mut json!({"schema_version": "two"})).is_err());
+        assert!(migrate(&mut json!([])).is_err());
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let value = json!({
+            "schema_version": 1,
+            "access_policy": {"allowed_path": ["/typo"], "read_only": true},
+            "server": {"name": "x"},
+            "logging": {"level": "debug"},
+            "telemetry": {},
+            "aliases": {"@work": "/srv/work"},
+            "mounts": [{"prefix": "/docs", "path": "/srv/docs"}, {"prefix": "/x", "path": "/x", "cmd": []}],
+            "profiles": {"ci": {"extends": "dev", "access_policy": {"readonly": true}}}
+        });
+        assert_eq!(unknown_fields(&value), vec![
+            "access_policy.allowed_path",
+            "mounts.1.cmd",
+            "profiles.ci.access_policy.readonly",
+            "telemetry",
+        ]);
+    }
+}
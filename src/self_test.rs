@@ -0,0 +1,248 @@
+//! `filejack self-test`: every tool called against a scratch directory.
+//!
+//! A server restricted to a fresh directory, with the tools that are off by
+//! default switched on, is sent representative `tools/call` requests the way
+//! a client would send them. A tool passes when all of its calls succeed and
+//! return what they should, so a build or platform that misbehaves shows up
+//! as the tools it breaks. Tools it has no call for, such as plugin tools,
+//! are reported as not run.
+
+use crate::config::{Config, WorkspacesConfig};
+use crate::error::Result;
+use crate::mcp::McpServer;
+use crate::session::Session;
+use crate::vfs;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// What the self-test found for one tool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    Passed,
+    /// The first call that failed, and why
+    Failed(String),
+    /// The self-test has no call for the tool
+    NotRun,
+}
+
+/// The outcome for one tool offered by the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolCheck {
+    pub tool: String,
+    pub outcome: CheckOutcome,
+}
+
+/// Run the self-test in a new directory under the system temp directory,
+/// removed again afterwards
+pub fn run() -> Result<Vec<ToolCheck>> {
+    let sandbox = std::env::temp_dir().join(format!("filejack-self-test-{}", std::process::id()));
+    fs::create_dir_all(&sandbox)?;
+    let checks = run_in(&sandbox);
+    let _ = fs::remove_dir_all(&sandbox);
+    checks
+}
+
+/// Run the self-test in `sandbox`, an empty directory it may fill, and
+/// report on every tool in the order `tools/list` gives them
+pub fn run_in(sandbox: &Path) -> Result<Vec<ToolCheck>> {
+    // Canonical, since the policy refuses paths that pass through symlinks
+    let root = vfs::canonicalize(sandbox)?;
+    let mut config = Config::default_restricted(root.clone());
+    config.metrics.expose_tool = true;
+    // Where the host has them
+    config.access_policy.extended_attributes = cfg!(any(target_os = "linux", target_os = "macos"));
+    config.workspaces = Some(WorkspacesConfig {
+        root: root.join("workspaces"),
+        ..Default::default()
+    });
+    let server = McpServer::from_config(config);
+    let session = server.open_session("self-test");
+    session.set_notifier(|_| {});
+
+    let mut run = SelfTest::new(&server, session);
+    run.exercise(&root);
+    let checks = run.checks();
+    server.remove_temp_workspaces();
+    Ok(checks)
+}
+
+struct SelfTest<'a> {
+    server: &'a McpServer,
+    session: Arc<Session>,
+    offered: Vec<String>,
+    outcomes: BTreeMap<String, CheckOutcome>,
+}
+
+impl<'a> SelfTest<'a> {
+    fn new(server: &'a McpServer, session: Arc<Session>) -> Self {
+        let offered = server.list_tools().into_iter().map(|tool| tool.name).collect();
+        Self { server, session, offered, outcomes: BTreeMap::new() }
+    }
+
+    /// The representative calls, in an order where each finds the files
+    /// earlier ones made
+    fn exercise(&mut self, root: &Path) {
+        let path = |name: &str| root.join(name).display().to_string();
+        let notes = path("src/notes.txt");
+
+        self.call("server_info", json!({}));
+        self.call("create_directory", json!({"path": path("src")}));
+        self.call("write_file", json!({"path": notes, "content": "alpha\nbeta\n"}));
+        self.call("append_file", json!({"path": notes, "content": "gamma\n"}));
+        self.call("write_range", json!({"path": notes, "offset": 0, "content": "ALPHA", "encoding": "text"}));
+        let read = self.call("read_file", json!({"path": notes}));
+        self.expect("read_file", read.as_ref().map(text) == Some("ALPHA\nbeta\ngamma\n"), "returned the wrong content");
+        let lines = self.call("read_lines", json!({"path": notes, "start_line": 2, "end_line": 2}));
+        self.expect("read_lines", lines.as_ref().is_some_and(|result| text(result).contains("beta")), "did not return line 2");
+        self.call("read_range", json!({"path": notes, "offset": 0, "length": 5}));
+        let metadata = self.call("get_metadata", json!({"path": notes, "hash": true}));
+        self.expect("get_metadata", metadata.is_some_and(|result| result["structuredContent"]["size"] == 17), "reported the wrong size");
+        let exists = self.call("file_exists", json!({"path": notes}));
+        self.expect("file_exists", exists.as_ref().is_some_and(|result| text(result).contains("true")), "did not find the file");
+        self.call("get_extended_attributes", json!({"path": notes}));
+        self.call("list_directory", json!({"path": path("src")}));
+
+        self.call("edit_lines", json!({"path": notes, "operations": [{"op": "insert_at", "line": 1, "content": "zero"}]}));
+        self.call("write_file", json!({"path": path("src/settings.json"), "content": "{\"tab_width\": 4}\n"}));
+        self.call("update_structured_file", json!({
+            "path": path("src/settings.json"),
+            "operations": [{"op": "set", "pointer": "/tab_width", "value": 2}],
+        }));
+
+        self.call("copy_file", json!({"from": notes, "to": path("src/copy.txt")}));
+        self.call("move_file", json!({"from": path("src/copy.txt"), "to": path("src/moved.txt")}));
+        self.call("compress_file", json!({"from": path("src/moved.txt")}));
+        self.call("delete_file", json!({"path": path("src/moved.txt")}));
+        self.call("decompress_file", json!({"from": path("src/moved.txt.gz")}));
+        let restored = self.call("read_file", json!({"path": path("src/moved.txt")}));
+        self.expect("decompress_file", restored.as_ref().map(text) == Some("zero\nALPHA\nbeta\ngamma\n"), "did not restore the file");
+
+        let found = self.call("search_files", json!({"path": path("src"), "pattern": "*.txt"}));
+        self.expect("search_files", found.as_ref().is_some_and(|result| text(result).contains("notes.txt")), "did not find notes.txt");
+        self.call("recent_files", json!({"path": path("src")}));
+        let matched = self.call("grep_file", json!({"path": notes, "pattern": "gam+a"}));
+        self.expect("grep_file", matched.as_ref().is_some_and(|result| text(result).contains("gamma")), "did not match gamma");
+        self.call("search_content", json!({"path": path("src"), "query": "gamma"}));
+        self.call("pack_directory", json!({"path": path("src")}));
+        self.call("summarize_workspace", json!({"path": root}));
+
+        self.call("set_working_directory", json!({"path": path("src")}));
+        let working = self.call("get_working_directory", json!({}));
+        self.expect("get_working_directory", working.as_ref().map(text) == Some(path("src").as_str()), "did not follow set_working_directory");
+
+        if let Some(lock) = self.call("lock_file", json!({"path": notes})) {
+            self.call("unlock_file", json!({"path": notes, "token": lock["structuredContent"]["token"]}));
+        }
+        self.call("claim_path", json!({"path": notes}));
+        self.call("release_path", json!({"path": notes}));
+        if let Some(follow) = self.call("tail_follow", json!({"path": notes})) {
+            let follower = serde_json::from_str::<Value>(text(&follow)).unwrap_or_default()["follower"].take();
+            self.call("tail_cancel", json!({"follower": follower}));
+        }
+
+        self.call("begin_transaction", json!({}));
+        self.call("write_file", json!({"path": path("src/discarded.txt"), "content": "staged"}));
+        self.call("rollback", json!({}));
+        self.call("begin_transaction", json!({}));
+        self.call("write_file", json!({"path": path("src/kept.txt"), "content": "staged"}));
+        self.call("commit", json!({}));
+        let kept = root.join("src/kept.txt").exists() && !root.join("src/discarded.txt").exists();
+        self.expect("commit", kept, "did not apply only the committed changes");
+
+        self.call("create_directory", json!({"path": path("empty")}));
+        self.call("remove_directory", json!({"path": path("empty")}));
+        self.call("create_temp_workspace", json!({}));
+
+        #[cfg(feature = "git")]
+        if git2::Repository::init(root).is_ok() {
+            self.call("git_snapshot", json!({"path": root, "message": "Self-test"}));
+            self.call("git_status", json!({"path": root}));
+            self.call("git_log", json!({"path": notes}));
+            self.call("git_show", json!({"path": notes}));
+            self.call("git_blame", json!({"path": notes}));
+        }
+
+        #[cfg(feature = "image")]
+        if image::RgbImage::new(4, 4).save(root.join("pixel.png")).is_ok() {
+            self.call("image_info", json!({"path": path("pixel.png"), "thumbnail": true}));
+        }
+
+        self.call("get_operation_history", json!({}));
+        self.call("get_session_usage", json!({}));
+        self.call("get_metrics", json!({}));
+    }
+
+    /// Call `tool` if the server offers it, and return its result if it
+    /// succeeded
+    fn call(&mut self, tool: &str, arguments: Value) -> Option<Value> {
+        if !self.offered.iter().any(|offered| offered == tool) {
+            return None;
+        }
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {"name": tool, "arguments": arguments},
+        });
+        let response = self.server.process_session_request(&self.session, &request.to_string());
+        let mut response: Value = serde_json::from_str(&response).unwrap_or_default();
+        match response.get("error") {
+            Some(error) => {
+                let message = error["message"].as_str().unwrap_or("the call failed").to_string();
+                self.record(tool, CheckOutcome::Failed(message));
+                None
+            }
+            None => {
+                self.record(tool, CheckOutcome::Passed);
+                Some(response["result"].take())
+            }
+        }
+    }
+
+    /// Fail `tool` with `message` unless `passed`
+    fn expect(&mut self, tool: &str, passed: bool, message: &str) {
+        if !passed && self.outcomes.contains_key(tool) {
+            self.record(tool, CheckOutcome::Failed(message.to_string()));
+        }
+    }
+
+    /// Keep the first failure of each tool
+    fn record(&mut self, tool: &str, outcome: CheckOutcome) {
+        let recorded = self.outcomes.entry(tool.to_string()).or_insert(CheckOutcome::Passed);
+        if *recorded == CheckOutcome::Passed {
+            *recorded = outcome;
+        }
+    }
+
+    fn checks(mut self) -> Vec<ToolCheck> {
+        self.offered.iter()
+            .map(|tool| ToolCheck {
+                tool: tool.clone(),
+                outcome: self.outcomes.remove(tool).unwrap_or(CheckOutcome::NotRun),
+            })
+            .collect()
+    }
+}
+
+/// Text of a tool result's first content item
+fn text(result: &Value) -> &str {
+    result["content"][0]["text"].as_str().unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_builtin_tool_passes() {
+        let temp_dir = tempfile::Builder::new().prefix("self-test").tempdir().unwrap();
+        let checks = run_in(temp_dir.path()).unwrap();
+        assert!(!checks.is_empty());
+        for check in &checks {
+            assert_eq!(check.outcome, CheckOutcome::Passed, "{}", check.tool);
+        }
+    }
+}
@@ -0,0 +1,371 @@
+use crate::config::ClientTools;
+use crate::error::{FileJackError, Result};
+use crate::file_ops::FileReader;
+use crate::follow::{Follower, MAX_FOLLOWERS_PER_SESSION};
+use crate::journal::{Before, Journal};
+use crate::rate_limit::RateLimiter;
+use crate::transactions::Transaction;
+use crate::usage::{SessionUsage, UsageReport};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// ID of the session used by [`McpServer::process_request`](crate::McpServer::process_request)
+pub const DEFAULT_SESSION_ID: &str = "default";
+
+/// Delivers a server-initiated JSON-RPC message to one client
+pub type Notifier = Arc<dyn Fn(String) + Send + Sync>;
+
+/// What a client told the server about itself in `initialize`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClientInfo {
+    pub protocol_version: Option<String>,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// The client's `capabilities` object, as sent
+    pub capabilities: Value,
+}
+
+impl ClientInfo {
+    /// Read the client details from the params of an `initialize` request
+    pub fn from_initialize_params(params: &Value) -> Self {
+        let text = |value: Option<&Value>| value.and_then(Value::as_str).map(str::to_string);
+        Self {
+            protocol_version: text(params.get("protocolVersion")),
+            name: text(params.pointer("/clientInfo/name")),
+            version: text(params.pointer("/clientInfo/version")),
+            capabilities: params.get("capabilities").cloned().unwrap_or_else(|| Value::Object(Default::default())),
+        }
+    }
+}
+
+/// State belonging to one connected client.
+///
+/// Transports that serve several clients at once open a session per
+/// connection with [`McpServer::open_session`](crate::McpServer::open_session),
+/// so each client has its own `initialize` handshake, subscriptions, and
+/// rate-limit budget. Policy, locks, and metrics stay shared by the server.
+pub struct Session {
+    id: String,
+    /// Whether this session draws on the server-wide rate limiter instead
+    /// of a bucket of its own
+    shares_server_bucket: bool,
+    rate_limiter: Mutex<Option<RateLimiter>>,
+    client: Mutex<Option<ClientInfo>>,
+    initialized: AtomicBool,
+    subscriptions: Mutex<BTreeSet<String>>,
+    notifier: Mutex<Option<Notifier>>,
+    journal: Journal,
+    /// Calls staged since `begin_transaction`, if a transaction is open
+    transaction: Mutex<Option<Transaction>>,
+    /// Files followed with `tail_follow`; dropping one stops it
+    followers: Mutex<Vec<Follower>>,
+    /// Directory set with `set_working_directory`
+    working_directory: Mutex<Option<PathBuf>>,
+    /// Tools offered to this client, chosen by its name at `initialize`;
+    /// `None` offers all of them
+    tools: Mutex<Option<ClientTools>>,
+    usage: Arc<SessionUsage>,
+}
+
+impl Session {
+    /// A session with its own rate-limit bucket
+    pub(crate) fn new(id: impl Into<String>) -> Self {
+        Self::build(id.into(), false)
+    }
+
+    /// The session single-client transports share, which uses the server's
+    /// own rate limiter
+    pub(crate) fn shared(id: impl Into<String>) -> Self {
+        Self::build(id.into(), true)
+    }
+
+    fn build(id: String, shares_server_bucket: bool) -> Self {
+        Self {
+            id,
+            shares_server_bucket,
+            rate_limiter: Mutex::new(None),
+            client: Mutex::new(None),
+            initialized: AtomicBool::new(false),
+            subscriptions: Mutex::new(BTreeSet::new()),
+            notifier: Mutex::new(None),
+            journal: Journal::default(),
+            transaction: Mutex::new(None),
+            followers: Mutex::new(Vec::new()),
+            working_directory: Mutex::new(None),
+            tools: Mutex::new(None),
+            usage: Arc::new(SessionUsage::default()),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The client's `initialize` details, once it has sent them
+    pub fn client(&self) -> Option<ClientInfo> {
+        lock(&self.client).clone()
+    }
+
+    /// Whether the client has completed the handshake with
+    /// `notifications/initialized`
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::Relaxed)
+    }
+
+    /// Start receiving updates for `key`; returns false if already subscribed
+    pub fn subscribe(&self, key: impl Into<String>) -> bool {
+        lock(&self.subscriptions).insert(key.into())
+    }
+
+    /// Stop receiving updates for `key`; returns false if not subscribed
+    pub fn unsubscribe(&self, key: &str) -> bool {
+        lock(&self.subscriptions).remove(key)
+    }
+
+    pub fn is_subscribed(&self, key: &str) -> bool {
+        lock(&self.subscriptions).contains(key)
+    }
+
+    /// Current subscriptions, sorted
+    pub fn subscriptions(&self) -> Vec<String> {
+        lock(&self.subscriptions).iter().cloned().collect()
+    }
+
+    /// Changes this session's tool calls have made
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    /// Journal a successful call to `tool` that modified files, and count
+    /// what it did in the session's usage
+    pub(crate) fn record_change(&self, reader: &FileReader, tool: &str, before: Before) {
+        let touched = self.journal.record(reader, tool, before);
+        self.usage.record_changes(&touched);
+    }
+
+    /// What this session has done so far
+    pub fn usage(&self) -> UsageReport {
+        self.usage.report(&self.id, self.client().and_then(|client| client.name))
+    }
+
+    pub(crate) fn usage_counters(&self) -> &Arc<SessionUsage> {
+        &self.usage
+    }
+
+    /// The open transaction, if any
+    pub(crate) fn transaction(&self) -> MutexGuard<'_, Option<Transaction>> {
+        lock(&self.transaction)
+    }
+
+    /// Whether calls that modify files are being staged
+    pub fn in_transaction(&self) -> bool {
+        self.transaction().is_some()
+    }
+
+    /// Directory this session set for resolving relative paths, if any
+    pub fn working_directory(&self) -> Option<PathBuf> {
+        lock(&self.working_directory).clone()
+    }
+
+    pub(crate) fn set_working_directory(&self, path: PathBuf) {
+        *lock(&self.working_directory) = Some(path);
+    }
+
+    /// Send messages for this client only, such as progress notifications,
+    /// through `notifier`. Transports that can reach the client while one of
+    /// its requests runs set this when the session opens.
+    pub fn set_notifier(&self, notifier: impl Fn(String) + Send + Sync + 'static) {
+        *lock(&self.notifier) = Some(Arc::new(notifier));
+    }
+
+    /// Where messages for this client go, if its transport can deliver them
+    pub(crate) fn notifier(&self) -> Option<Notifier> {
+        lock(&self.notifier).clone()
+    }
+
+    /// Keep `follower` running until it is cancelled or the session closes
+    pub(crate) fn add_follower(&self, follower: Follower) -> Result<()> {
+        let mut followers = lock(&self.followers);
+        if followers.len() >= MAX_FOLLOWERS_PER_SESSION {
+            return Err(FileJackError::InvalidParameters(format!(
+                "A session can follow at most {} files at once",
+                MAX_FOLLOWERS_PER_SESSION
+            )));
+        }
+        followers.push(follower);
+        Ok(())
+    }
+
+    /// Stop the follower with ID `id`; returns false if there is none
+    pub(crate) fn remove_follower(&self, id: u64) -> bool {
+        let mut followers = lock(&self.followers);
+        let before = followers.len();
+        followers.retain(|follower| follower.id() != id);
+        followers.len() < before
+    }
+
+    /// Send a message to this client; returns false if its transport
+    /// cannot deliver one
+    pub fn notify(&self, message: String) -> bool {
+        // Not called under the lock, so a notifier may itself use the session
+        let notifier = self.notifier();
+        match notifier {
+            Some(notifier) => {
+                notifier(message);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Record an `initialize` request. A repeated `initialize` starts the
+    /// handshake over.
+    pub(crate) fn begin_initialize(&self, client: ClientInfo) {
+        *lock(&self.client) = Some(client);
+        self.initialized.store(false, Ordering::Relaxed);
+    }
+
+    /// Offer this client only the tools `tools` permits, or every tool
+    pub(crate) fn restrict_tools(&self, tools: Option<ClientTools>) {
+        *lock(&self.tools) = tools;
+    }
+
+    /// Whether this client may list and call the tool `name`
+    pub fn offers_tool(&self, name: &str) -> bool {
+        lock(&self.tools).as_ref().is_none_or(|tools| tools.permits(name))
+    }
+
+    pub(crate) fn finish_initialize(&self) {
+        self.initialized.store(true, Ordering::Relaxed);
+    }
+
+    /// The limiter this session's requests are checked against. A private
+    /// bucket is created on first use and again whenever the configured rate
+    /// changes.
+    pub(crate) fn rate_limiter(&self, configured: &RateLimiter) -> RateLimiter {
+        if self.shares_server_bucket {
+            return configured.clone();
+        }
+        let mut slot = lock(&self.rate_limiter);
+        match slot.as_ref() {
+            Some(limiter) if limiter.requests_per_second() == configured.requests_per_second() => limiter.clone(),
+            _ => slot.insert(configured.fresh()).clone(),
+        }
+    }
+}
+
+/// Reports how far a long-running tool call has got, as MCP
+/// `notifications/progress` sent to the session that made it. Clients opt
+/// in by giving the request a `_meta.progressToken`; without one, reports
+/// go nowhere.
+pub struct Progress<'a> {
+    session: &'a Session,
+    token: Option<Value>,
+}
+
+impl<'a> Progress<'a> {
+    pub fn new(session: &'a Session, token: Option<Value>) -> Self {
+        Self { session, token }
+    }
+
+    /// Session the call was made in
+    pub fn session(&self) -> &'a Session {
+        self.session
+    }
+
+    /// Whether the client asked for progress
+    pub fn is_requested(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// Tell the client that `progress` of `total` units are done
+    pub fn report(&self, progress: u64, total: Option<u64>, message: Option<&str>) {
+        let Some(token) = &self.token else {
+            return;
+        };
+        let mut params = json!({"progressToken": token, "progress": progress});
+        if let Some(total) = total {
+            params["total"] = json!(total);
+        }
+        if let Some(message) = message {
+            params["message"] = json!(message);
+        }
+        self.session.notify(json!({"jsonrpc": "2.0", "method": "notifications/progress", "params": params}).to_string());
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_client_info_from_initialize() {
+        let client = ClientInfo::from_initialize_params(&json!({
+            "protocolVersion": "2025-03-26",
+            "clientInfo": {"name": "inspector", "version": "0.9"},
+            "capabilities": {"roots": {"listChanged": true}}
+        }));
+        assert_eq!(client.protocol_version.as_deref(), Some("2025-03-26"));
+        assert_eq!(client.name.as_deref(), Some("inspector"));
+        assert_eq!(client.capabilities["roots"]["listChanged"], true);
+
+        let bare = ClientInfo::from_initialize_params(&json!({}));
+        assert_eq!(bare.name, None);
+        assert_eq!(bare.capabilities, json!({}));
+    }
+
+    #[test]
+    fn test_private_rate_limit_buckets() {
+        let configured = RateLimiter::new(2);
+        let first = Session::new("a");
+        let second = Session::new("b");
+
+        let limiter = first.rate_limiter(&configured);
+        assert!(limiter.check() && limiter.check());
+        assert!(!first.rate_limiter(&configured).check());
+        // Another session's budget is untouched, and so is the server's
+        assert!(second.rate_limiter(&configured).check());
+        assert!(configured.check());
+
+        // A new configured rate gives the session a fresh bucket
+        assert!(first.rate_limiter(&RateLimiter::new(5)).check());
+    }
+
+    #[test]
+    fn test_subscriptions() {
+        let session = Session::new("s");
+        assert!(session.subscribe("file:///tmp/app.log"));
+        assert!(!session.subscribe("file:///tmp/app.log"));
+        assert!(session.is_subscribed("file:///tmp/app.log"));
+        assert_eq!(session.subscriptions(), vec!["file:///tmp/app.log"]);
+        assert!(session.unsubscribe("file:///tmp/app.log"));
+        assert!(!session.unsubscribe("file:///tmp/app.log"));
+    }
+
+    #[test]
+    fn test_progress_notifications() {
+        let session = Session::new("s");
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        Progress::new(&session, Some(json!("t"))).report(1, None, None);
+
+        let sink = Arc::clone(&sent);
+        session.set_notifier(move |message| lock(&sink).push(message));
+        Progress::new(&session, None).report(1, Some(2), None);
+        Progress::new(&session, Some(json!(7))).report(1, Some(2), Some("copying"));
+
+        let sent = lock(&sent);
+        assert_eq!(sent.len(), 1);
+        let notification: Value = serde_json::from_str(&sent[0]).unwrap();
+        assert_eq!(notification["method"], "notifications/progress");
+        assert_eq!(notification["params"], json!({"progressToken": 7, "progress": 1, "total": 2, "message": "copying"}));
+    }
+}
@@ -0,0 +1,386 @@
+use crate::config::SftpConfig;
+use crate::vfs::{self, DirEntry, FileHandle, FileKind, FileSystem, Metadata, WalkOptions};
+use ssh2::{CheckResult, ErrorCode, FileStat, FileType, KnownHostFileKind, OpenFlags, OpenType, RenameFlags, Session, Sftp};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// How long a single SSH operation may take before it fails
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// SFTP status codes (draft-ietf-secsh-filexfer-02 and later)
+const FX_NO_SUCH_FILE: i32 = 2;
+const FX_PERMISSION_DENIED: i32 = 3;
+const FX_OP_UNSUPPORTED: i32 = 8;
+const FX_NO_SUCH_PATH: i32 = 10;
+const FX_FILE_ALREADY_EXISTS: i32 = 11;
+const FX_WRITE_PROTECT: i32 = 12;
+
+/// A directory tree on another host, reached over SFTP.
+///
+/// The connection is opened on first use and reopened after it fails. The
+/// server's host key must be listed in the configured `known_hosts` file;
+/// login uses the configured private key or, without one, the SSH agent.
+pub struct SftpFileSystem {
+    config: SftpConfig,
+    connection: Mutex<Option<Sftp>>,
+}
+
+impl std::fmt::Debug for SftpFileSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SftpFileSystem")
+            .field("host", &self.config.host)
+            .field("port", &self.config.port)
+            .field("user", &self.config.user)
+            .finish()
+    }
+}
+
+impl SftpFileSystem {
+    /// Set up the backend; nothing is connected until the first operation
+    pub fn new(config: SftpConfig) -> Self {
+        Self { config, connection: Mutex::new(None) }
+    }
+
+    /// Run `op` on the SFTP channel, connecting first if needed. Failures
+    /// other than an SFTP status drop the connection so the next call
+    /// reconnects.
+    fn with_sftp<T>(&self, op: impl FnOnce(&Sftp) -> io::Result<T>) -> io::Result<T> {
+        let mut slot = self.lock();
+        if slot.is_none() {
+            *slot = Some(self.connect()?);
+        }
+        let result = op(slot.as_ref().expect("connection just opened"));
+        if let Err(e) = &result {
+            let status = e.get_ref()
+                .and_then(|inner| inner.downcast_ref::<ssh2::Error>())
+                .is_some_and(|inner| matches!(inner.code(), ErrorCode::SFTP(_)));
+            if !status {
+                warn!(host = %self.config.host, "SFTP connection failed; reconnecting on the next call: {}", e);
+                *slot = None;
+            }
+        }
+        result
+    }
+
+    fn connect(&self) -> io::Result<Sftp> {
+        let config = &self.config;
+        info!(host = %config.host, port = config.port, user = %config.user, "Connecting to SFTP server");
+        let tcp = TcpStream::connect((config.host.as_str(), config.port))?;
+        let mut session = Session::new().map_err(sftp_error)?;
+        session.set_timeout(TIMEOUT.as_millis() as u32);
+        session.set_tcp_stream(tcp);
+        session.handshake().map_err(sftp_error)?;
+        self.verify_host_key(&session)?;
+
+        let login = match &config.identity_file {
+            Some(key) => session.userauth_pubkey_file(&config.user, None, key, None),
+            None => session.userauth_agent(&config.user),
+        };
+        if let Err(e) = login.and_then(|_| match session.authenticated() {
+            true => Ok(()),
+            false => Err(ssh2::Error::unknown()),
+        }) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("SSH login to {} as {} failed: {}", config.host, config.user, e),
+            ));
+        }
+        session.sftp().map_err(sftp_error)
+    }
+
+    /// Refuse hosts whose key is missing from, or differs from, known_hosts
+    fn verify_host_key(&self, session: &Session) -> io::Result<()> {
+        let config = &self.config;
+        let refuse = |reason: String| Err(io::Error::new(io::ErrorKind::PermissionDenied, reason));
+
+        let mut known_hosts = session.known_hosts().map_err(sftp_error)?;
+        if let Err(e) = known_hosts.read_file(&config.known_hosts, KnownHostFileKind::OpenSSH) {
+            return refuse(format!("Cannot read known hosts file {}: {}", config.known_hosts.display(), e));
+        }
+        let Some((key, _)) = session.host_key() else {
+            return refuse(format!("{} sent no host key", config.host));
+        };
+        match known_hosts.check_port(&config.host, config.port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::Mismatch => refuse(format!(
+                "Host key of {} does not match {}; refusing to connect",
+                config.host,
+                config.known_hosts.display()
+            )),
+            CheckResult::NotFound | CheckResult::Failure => refuse(format!(
+                "{} is not listed in {}; add its key with ssh-keyscan",
+                config.host,
+                config.known_hosts.display()
+            )),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Sftp>> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// An open remote file, with the metadata it had when opened
+struct SftpFile {
+    file: ssh2::File,
+    metadata: Metadata,
+}
+
+impl Read for SftpFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Seek for SftpFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl FileHandle for SftpFile {
+    fn metadata(&self) -> io::Result<Metadata> {
+        Ok(self.metadata.clone())
+    }
+}
+
+impl FileSystem for SftpFileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+        let (file, metadata) = self.with_sftp(|sftp| {
+            let mut file = sftp.open(path).map_err(sftp_error)?;
+            let metadata = metadata_from(&file.stat().map_err(sftp_error)?);
+            Ok((file, metadata))
+        })?;
+        Ok(Box::new(SftpFile { file, metadata }))
+    }
+
+    /// Writes a temporary file beside the target and renames it into place.
+    /// Servers that cannot rename over an existing file (OpenSSH among
+    /// them) get the target removed first, so a reader may briefly find it
+    /// missing but never partially written.
+    fn write(&self, path: &Path, contents: &[u8], sync: bool) -> io::Result<()> {
+        let temp_path = vfs::temp_path_beside(path)?;
+        self.with_sftp(|sftp| {
+            // Keep the permissions of the file being replaced
+            let mode = sftp.stat(path).ok().and_then(|stat| stat.perm).map_or(0o644, |perm| perm & 0o7777);
+            let flags = OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::EXCLUSIVE;
+            let mut file = sftp.open_mode(&temp_path, flags, mode as i32, OpenType::File).map_err(sftp_error)?;
+
+            let result = write_all(&mut file, contents, sync).and_then(|_| {
+                drop(file);
+                replace(sftp, &temp_path, path)
+            });
+            if result.is_err() {
+                let _ = sftp.unlink(&temp_path);
+            }
+            result
+        })
+    }
+
+    fn write_in_place(&self, path: &Path, contents: &[u8], sync: bool) -> io::Result<()> {
+        self.with_sftp(|sftp| {
+            let flags = OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE;
+            let mut file = open_regular(sftp, path, flags)?;
+            write_all(&mut file, contents, sync)
+        })
+    }
+
+    fn write_at(&self, path: &Path, offset: u64, contents: &[u8], sync: bool) -> io::Result<u64> {
+        self.with_sftp(|sftp| {
+            let mut file = open_regular(sftp, path, OpenFlags::WRITE | OpenFlags::CREATE)?;
+            file.seek(SeekFrom::Start(offset))?;
+            write_all(&mut file, contents, sync)?;
+            Ok(file.stat().map_err(sftp_error)?.size.unwrap_or_default())
+        })
+    }
+
+    fn append(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.with_sftp(|sftp| {
+            let flags = OpenFlags::WRITE | OpenFlags::APPEND | OpenFlags::CREATE;
+            let mut file = open_regular(sftp, path, flags)?;
+            write_all(&mut file, contents, false)
+        })
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.with_sftp(|sftp| sftp.stat(path).map(|stat| metadata_from(&stat)).map_err(sftp_error))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.with_sftp(|sftp| sftp.lstat(path).map(|stat| metadata_from(&stat)).map_err(sftp_error))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.with_sftp(|sftp| {
+            let canonical = sftp.realpath(path).map_err(sftp_error)?;
+            // Some servers resolve paths that do not exist
+            sftp.stat(&canonical).map_err(sftp_error)?;
+            Ok(canonical)
+        })
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        self.with_sftp(|sftp| {
+            let entries = sftp.readdir(path).map_err(sftp_error)?;
+            Ok(entries.into_iter()
+                .map(|(path, stat)| DirEntry { path, kind: kind_of(&stat) })
+                .collect())
+        })
+    }
+
+    fn create_dir(&self, path: &Path, recursive: bool) -> io::Result<()> {
+        self.with_sftp(|sftp| {
+            if !recursive {
+                return sftp.mkdir(path, 0o755).map_err(sftp_error);
+            }
+            let missing: Vec<&Path> = path.ancestors()
+                .take_while(|dir| !dir.as_os_str().is_empty() && sftp.stat(dir).is_err())
+                .collect();
+            for dir in missing.into_iter().rev() {
+                sftp.mkdir(dir, 0o755).map_err(sftp_error)?;
+            }
+            if !sftp.stat(path).map_err(sftp_error)?.is_dir() {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} is not a directory", path.display())));
+            }
+            Ok(())
+        })
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+        let stat = FileStat { size: None, uid: None, gid: None, perm: Some(mode), atime: None, mtime: None };
+        self.with_sftp(|sftp| sftp.setstat(path, stat).map_err(sftp_error))
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.with_sftp(|sftp| sftp.unlink(path).map_err(sftp_error))
+    }
+
+    fn remove_dir(&self, path: &Path, recursive: bool) -> io::Result<()> {
+        if recursive {
+            // Deepest entries first, so directories are empty when removed
+            let entries = self.walk(path, &WalkOptions::default())?;
+            self.with_sftp(|sftp| {
+                for entry in entries.iter().rev() {
+                    match entry.kind {
+                        FileKind::Dir => sftp.rmdir(&entry.path),
+                        _ => sftp.unlink(&entry.path),
+                    }
+                    .map_err(sftp_error)?;
+                }
+                Ok(())
+            })?;
+        }
+        self.with_sftp(|sftp| sftp.rmdir(path).map_err(sftp_error))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.with_sftp(|sftp| replace(sftp, from, to))
+    }
+}
+
+/// Rename `from` over `to`, removing `to` first when the server will not
+/// replace it
+fn replace(sftp: &Sftp, from: &Path, to: &Path) -> io::Result<()> {
+    let flags = Some(RenameFlags::OVERWRITE | RenameFlags::ATOMIC | RenameFlags::NATIVE);
+    match sftp.rename(from, to, flags) {
+        Ok(()) => Ok(()),
+        Err(_) if sftp.lstat(to).is_ok_and(|stat| !stat.is_dir()) => {
+            sftp.unlink(to).map_err(sftp_error)?;
+            sftp.rename(from, to, flags).map_err(sftp_error)
+        }
+        Err(e) => Err(sftp_error(e)),
+    }
+}
+
+/// Open a file for writing, refusing anything but a regular file
+fn open_regular(sftp: &Sftp, path: &Path, flags: OpenFlags) -> io::Result<ssh2::File> {
+    let mut file = sftp.open_mode(path, flags, 0o644, OpenType::File).map_err(sftp_error)?;
+    if !file.stat().map_err(sftp_error)?.is_file() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot write to non-regular file"));
+    }
+    Ok(file)
+}
+
+/// Write everything and, with `sync`, ask the server to flush it to disk.
+/// Servers without the fsync extension are taken at their word.
+fn write_all(file: &mut ssh2::File, contents: &[u8], sync: bool) -> io::Result<()> {
+    file.write_all(contents)?;
+    if sync {
+        match file.fsync() {
+            Err(e) if e.code() != ErrorCode::SFTP(FX_OP_UNSUPPORTED) => return Err(sftp_error(e)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn kind_of(stat: &FileStat) -> FileKind {
+    match stat.file_type() {
+        FileType::Directory => FileKind::Dir,
+        FileType::RegularFile => FileKind::File,
+        FileType::Symlink => FileKind::Symlink,
+        _ => FileKind::Other,
+    }
+}
+
+fn metadata_from(stat: &FileStat) -> Metadata {
+    Metadata {
+        kind: kind_of(stat),
+        len: stat.size.unwrap_or_default(),
+        modified: stat.mtime.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+        created: None,
+        readonly: stat.perm.is_some_and(|perm| perm & 0o222 == 0),
+        hidden: false,
+        owner: stat.uid,
+        group: stat.gid,
+        mode: stat.perm.map(|perm| perm & 0o7777),
+    }
+}
+
+/// Turn an SSH error into an I/O error of the matching kind, keeping the
+/// original as its source
+fn sftp_error(e: ssh2::Error) -> io::Error {
+    let kind = match e.code() {
+        ErrorCode::SFTP(FX_NO_SUCH_FILE | FX_NO_SUCH_PATH) => io::ErrorKind::NotFound,
+        ErrorCode::SFTP(FX_PERMISSION_DENIED | FX_WRITE_PROTECT) => io::ErrorKind::PermissionDenied,
+        ErrorCode::SFTP(FX_FILE_ALREADY_EXISTS) => io::ErrorKind::AlreadyExists,
+        ErrorCode::SFTP(_) => io::ErrorKind::Other,
+        ErrorCode::Session(_) => io::ErrorKind::ConnectionAborted,
+    };
+    io::Error::new(kind, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_unreachable_host_fails_each_call() {
+        // Bind and drop to find a port nothing listens on
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let remote = SftpFileSystem::new(SftpConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            user: "nobody".to_string(),
+            ..SftpConfig::default()
+        });
+
+        assert!(remote.metadata(Path::new("/")).is_err());
+        // No connection is kept, so the next call tries again
+        assert!(remote.lock().is_none());
+        assert!(remote.list(Path::new("/")).is_err());
+    }
+
+    #[test]
+    fn test_sftp_status_codes_map_to_io_kinds() {
+        let missing = sftp_error(ssh2::Error::from_errno(ErrorCode::SFTP(FX_NO_SUCH_FILE)));
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+        let denied = sftp_error(ssh2::Error::from_errno(ErrorCode::SFTP(FX_PERMISSION_DENIED)));
+        assert_eq!(denied.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::error::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Tracks whether the server has been asked to stop.
+///
+/// On Unix, SIGTERM and SIGINT set the flag so the request loop can finish
+/// the request it is working on and exit cleanly. A second SIGINT while the
+/// flag is already set terminates the process immediately, so an impatient
+/// Ctrl-C still works. The handlers are removed when this is dropped.
+pub struct ShutdownSignal {
+    requested: Arc<AtomicBool>,
+    #[cfg(unix)]
+    signals: Vec<signal_hook::SigId>,
+}
+
+impl ShutdownSignal {
+    /// Install the signal handlers
+    pub fn register() -> Result<Self> {
+        let requested = Arc::new(AtomicBool::new(false));
+
+        #[cfg(unix)]
+        let signals = {
+            use signal_hook::consts::{SIGINT, SIGTERM};
+            // Registered first so it sees the flag before this SIGINT sets it
+            let force = signal_hook::flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(&requested))?;
+            vec![
+                force,
+                signal_hook::flag::register(SIGINT, Arc::clone(&requested))?,
+                signal_hook::flag::register(SIGTERM, Arc::clone(&requested))?,
+            ]
+        };
+
+        Ok(Self {
+            requested,
+            #[cfg(unix)]
+            signals,
+        })
+    }
+
+    /// Whether a shutdown signal has arrived
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    /// Request a shutdown as if a signal had arrived
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for ShutdownSignal {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        for id in self.signals.drain(..) {
+            signal_hook::low_level::unregister(id);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sigterm_requests_shutdown() {
+        let shutdown = ShutdownSignal::register().unwrap();
+        assert!(!shutdown.is_requested());
+        signal_hook::low_level::raise(signal_hook::consts::SIGTERM).unwrap();
+        assert!(shutdown.is_requested());
+    }
+}
@@ -0,0 +1,608 @@
+//! Pointer-based edits to JSON, TOML, and YAML documents.
+//!
+//! `update_structured_file` changes individual values in place instead of
+//! having a client regenerate a whole configuration file. TOML is edited
+//! with `toml_edit`, so comments and layout around the changed values
+//! survive. JSON and YAML keep their key order, and JSON keeps its
+//! indentation, but both are written back from the parsed document, so YAML
+//! comments are lost. JSON relies on serde_json's `preserve_order` feature
+//! for its key order.
+
+use crate::error::{FileJackError, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_yaml::Value as YamlValue;
+use std::path::Path;
+use toml_edit::{DocumentMut, InlineTable, Item, Table};
+
+/// A document format `update_structured_file` can edit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StructuredFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl StructuredFormat {
+    /// Detect the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+            "json" => Some(StructuredFormat::Json),
+            "toml" => Some(StructuredFormat::Toml),
+            "yaml" | "yml" => Some(StructuredFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// One change to a document. Pointers follow RFC 6901, such as
+/// `/server/ports/0`; `~1` stands for `/` and `~0` for `~` within a key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum EditOperation {
+    /// Replace the value at `pointer`, or add it. Missing parent objects are
+    /// created, and `-` as the last token appends to an array.
+    Set { pointer: String, value: Value },
+    /// Remove the key or array element at `pointer`
+    Remove { pointer: String },
+    /// Append `value` to the array at `pointer`
+    Append { pointer: String, value: Value },
+}
+
+impl EditOperation {
+    fn pointer(&self) -> &str {
+        match self {
+            EditOperation::Set { pointer, .. }
+            | EditOperation::Remove { pointer }
+            | EditOperation::Append { pointer, .. } => pointer,
+        }
+    }
+}
+
+/// Result of `update_structured_file`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct StructuredEditSummary {
+    pub path: String,
+    pub format: StructuredFormat,
+    pub operations: usize,
+    /// False when the edits left the document as it was, in which case the
+    /// file was not written
+    pub changed: bool,
+    pub bytes_written: u64,
+}
+
+/// Apply `edits` in order to the document in `text`. Either every edit
+/// applies or an error names the first that did not.
+pub fn apply_edits(text: &str, format: StructuredFormat, edits: &[EditOperation]) -> Result<String> {
+    match format {
+        StructuredFormat::Json => {
+            // serde_json's preserve_order feature keeps objects in key order
+            let mut document: Value = serde_json::from_str(text)
+                .map_err(|e| unparsable("JSON", e))?;
+            apply_all(&mut document, edits)?;
+            write_json(&document, text)
+        }
+        StructuredFormat::Yaml => {
+            let mut document: YamlValue = serde_yaml::from_str(text)
+                .map_err(|e| unparsable("YAML", e))?;
+            apply_all(&mut document, edits)?;
+            let yaml = serde_yaml::to_string(&document)
+                .map_err(|e| FileJackError::InvalidParameters(format!("Could not write YAML: {}", e)))?;
+            match text.trim_start().starts_with("---") {
+                true => Ok(format!("---\n{}", yaml)),
+                false => Ok(yaml),
+            }
+        }
+        StructuredFormat::Toml => {
+            let mut document: DocumentMut = text.parse().map_err(|e| unparsable("TOML", e))?;
+            apply_all(document.as_item_mut(), edits)?;
+            Ok(document.to_string())
+        }
+    }
+}
+
+fn unparsable(format: &str, error: impl std::fmt::Display) -> FileJackError {
+    FileJackError::InvalidParameters(format!("File is not valid {}: {}", format, error))
+}
+
+fn apply_all<N: Node>(document: &mut N, edits: &[EditOperation]) -> Result<()> {
+    for edit in edits {
+        apply(document, edit).map_err(|reason| {
+            FileJackError::InvalidParameters(format!("Cannot apply {} to {:?}: {}", op_name(edit), edit.pointer(), reason))
+        })?;
+    }
+    Ok(())
+}
+
+fn op_name(edit: &EditOperation) -> &'static str {
+    match edit {
+        EditOperation::Set { .. } => "set",
+        EditOperation::Remove { .. } => "remove",
+        EditOperation::Append { .. } => "append",
+    }
+}
+
+fn apply<N: Node>(document: &mut N, edit: &EditOperation) -> std::result::Result<(), String> {
+    let tokens = parse_pointer(edit.pointer())?;
+    match edit {
+        EditOperation::Set { value, .. } => {
+            let (last, parents) = tokens.split_last().ok_or("the whole document cannot be replaced")?;
+            descend(document, parents, true)?.set_child(last, value)
+        }
+        EditOperation::Remove { .. } => {
+            let (last, parents) = tokens.split_last().ok_or("the whole document cannot be removed")?;
+            descend(document, parents, false)?.remove_child(last)
+        }
+        EditOperation::Append { value, .. } => descend(document, &tokens, false)?.push(value),
+    }
+}
+
+fn parse_pointer(pointer: &str) -> std::result::Result<Vec<String>, String> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let tokens = pointer.strip_prefix('/').ok_or("a pointer must be empty or start with '/'")?;
+    Ok(tokens.split('/').map(|token| token.replace("~1", "/").replace("~0", "~")).collect())
+}
+
+/// Follow `tokens` from `node`, adding empty objects for missing keys when
+/// `create` is set
+fn descend<'a, N: Node>(mut node: &'a mut N, tokens: &[String], create: bool) -> std::result::Result<&'a mut N, String> {
+    for (depth, token) in tokens.iter().enumerate() {
+        node = if node.has_child(token) {
+            node.child_mut(token).expect("child exists")
+        } else if create {
+            node.add_object(token)?
+        } else {
+            return Err(format!("nothing at /{}", tokens[..=depth].join("/")));
+        };
+    }
+    Ok(node)
+}
+
+fn array_index(token: &str, len: usize) -> Option<usize> {
+    token.parse().ok().filter(|&index| index < len)
+}
+
+/// A value in a parsed document that pointer edits can navigate and change
+trait Node: Sized {
+    fn has_child(&self, token: &str) -> bool;
+    fn child_mut(&mut self, token: &str) -> Option<&mut Self>;
+    /// Add an empty object under the missing key `token`
+    fn add_object(&mut self, token: &str) -> std::result::Result<&mut Self, String>;
+    fn set_child(&mut self, token: &str, value: &Value) -> std::result::Result<(), String>;
+    fn remove_child(&mut self, token: &str) -> std::result::Result<(), String>;
+    fn push(&mut self, value: &Value) -> std::result::Result<(), String>;
+}
+
+impl Node for YamlValue {
+    fn has_child(&self, token: &str) -> bool {
+        match self {
+            YamlValue::Mapping(map) => map.contains_key(token),
+            YamlValue::Sequence(items) => array_index(token, items.len()).is_some(),
+            _ => false,
+        }
+    }
+
+    fn child_mut(&mut self, token: &str) -> Option<&mut Self> {
+        match self {
+            YamlValue::Mapping(map) => map.get_mut(token),
+            YamlValue::Sequence(items) => {
+                let index = array_index(token, items.len())?;
+                items.get_mut(index)
+            }
+            _ => None,
+        }
+    }
+
+    fn add_object(&mut self, token: &str) -> std::result::Result<&mut Self, String> {
+        if self.is_null() {
+            *self = YamlValue::Mapping(Default::default());
+        }
+        match self {
+            YamlValue::Mapping(map) => Ok(map.entry(token.into()).or_insert(YamlValue::Mapping(Default::default()))),
+            YamlValue::Sequence(_) => Err(format!("array has no element {}", token)),
+            _ => Err(format!("cannot add {} inside a {}", token, yaml_kind(self))),
+        }
+    }
+
+    fn set_child(&mut self, token: &str, value: &Value) -> std::result::Result<(), String> {
+        let value = serde_yaml::to_value(value).map_err(|e| e.to_string())?;
+        match self {
+            YamlValue::Mapping(map) => {
+                map.insert(token.into(), value);
+                Ok(())
+            }
+            YamlValue::Sequence(items) if token == "-" || token == items.len().to_string() => {
+                items.push(value);
+                Ok(())
+            }
+            YamlValue::Sequence(items) => {
+                let index = array_index(token, items.len()).ok_or_else(|| format!("array has no element {}", token))?;
+                items[index] = value;
+                Ok(())
+            }
+            _ => Err(format!("cannot set {} inside a {}", token, yaml_kind(self))),
+        }
+    }
+
+    fn remove_child(&mut self, token: &str) -> std::result::Result<(), String> {
+        let removed = match self {
+            YamlValue::Mapping(map) => map.shift_remove(token).is_some(),
+            YamlValue::Sequence(items) => array_index(token, items.len()).map(|index| items.remove(index)).is_some(),
+            _ => false,
+        };
+        removed.then_some(()).ok_or_else(|| format!("nothing named {} to remove", token))
+    }
+
+    fn push(&mut self, value: &Value) -> std::result::Result<(), String> {
+        match self {
+            YamlValue::Sequence(items) => {
+                items.push(serde_yaml::to_value(value).map_err(|e| e.to_string())?);
+                Ok(())
+            }
+            _ => Err(format!("not an array but a {}", yaml_kind(self))),
+        }
+    }
+}
+
+impl Node for Value {
+    fn has_child(&self, token: &str) -> bool {
+        match self {
+            Value::Object(map) => map.contains_key(token),
+            Value::Array(items) => array_index(token, items.len()).is_some(),
+            _ => false,
+        }
+    }
+
+    fn child_mut(&mut self, token: &str) -> Option<&mut Self> {
+        match self {
+            Value::Object(map) => map.get_mut(token),
+            Value::Array(items) => {
+                let index = array_index(token, items.len())?;
+                items.get_mut(index)
+            }
+            _ => None,
+        }
+    }
+
+    fn add_object(&mut self, token: &str) -> std::result::Result<&mut Self, String> {
+        if self.is_null() {
+            *self = Value::Object(Default::default());
+        }
+        match self {
+            Value::Object(map) => Ok(map.entry(token).or_insert(Value::Object(Default::default()))),
+            Value::Array(_) => Err(format!("array has no element {}", token)),
+            _ => Err(format!("cannot add {} inside a {}", token, json_kind(self))),
+        }
+    }
+
+    fn set_child(&mut self, token: &str, value: &Value) -> std::result::Result<(), String> {
+        match self {
+            Value::Object(map) => {
+                map.insert(token.to_string(), value.clone());
+                Ok(())
+            }
+            Value::Array(items) if token == "-" || token == items.len().to_string() => {
+                items.push(value.clone());
+                Ok(())
+            }
+            Value::Array(items) => {
+                let index = array_index(token, items.len()).ok_or_else(|| format!("array has no element {}", token))?;
+                items[index] = value.clone();
+                Ok(())
+            }
+            _ => Err(format!("cannot set {} inside a {}", token, json_kind(self))),
+        }
+    }
+
+    fn remove_child(&mut self, token: &str) -> std::result::Result<(), String> {
+        let removed = match self {
+            Value::Object(map) => map.shift_remove(token).is_some(),
+            Value::Array(items) => array_index(token, items.len()).map(|index| items.remove(index)).is_some(),
+            _ => false,
+        };
+        removed.then_some(()).ok_or_else(|| format!("nothing named {} to remove", token))
+    }
+
+    fn push(&mut self, value: &Value) -> std::result::Result<(), String> {
+        match self {
+            Value::Array(items) => {
+                items.push(value.clone());
+                Ok(())
+            }
+            _ => Err(format!("not an array but a {}", json_kind(self))),
+        }
+    }
+}
+
+fn json_kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn yaml_kind(value: &YamlValue) -> &'static str {
+    match value {
+        YamlValue::Null => "null",
+        YamlValue::Bool(_) => "boolean",
+        YamlValue::Number(_) => "number",
+        YamlValue::String(_) => "string",
+        YamlValue::Sequence(_) => "array",
+        YamlValue::Mapping(_) => "object",
+        YamlValue::Tagged(_) => "tagged value",
+    }
+}
+
+/// Write a JSON document with the indentation and trailing newline of the
+/// text it was parsed from
+fn write_json(document: &Value, original: &str) -> Result<String> {
+    let mut out = Vec::new();
+    let written = match original.trim().contains('\n') {
+        true => {
+            let indent = original.lines()
+                .map(|line| &line[..line.len() - line.trim_start().len()])
+                .find(|indent| !indent.is_empty())
+                .unwrap_or("  ");
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+            document.serialize(&mut serde_json::Serializer::with_formatter(&mut out, formatter))
+        }
+        false => document.serialize(&mut serde_json::Serializer::new(&mut out)),
+    };
+    written.map_err(|e| FileJackError::InvalidParameters(format!("Could not write JSON: {}", e)))?;
+    let mut json = String::from_utf8(out).expect("serde_json writes UTF-8");
+    if original.ends_with('\n') {
+        json.push('\n');
+    }
+    Ok(json)
+}
+
+impl Node for Item {
+    fn has_child(&self, token: &str) -> bool {
+        match toml_len(self) {
+            Some(len) => array_index(token, len).is_some(),
+            None => self.get(token).is_some(),
+        }
+    }
+
+    fn child_mut(&mut self, token: &str) -> Option<&mut Self> {
+        match toml_len(self) {
+            Some(len) => self.get_mut(array_index(token, len)?),
+            // Indexing a table by a missing key would insert it
+            None if self.get(token).is_some() => self.get_mut(token),
+            None => None,
+        }
+    }
+
+    fn add_object(&mut self, token: &str) -> std::result::Result<&mut Self, String> {
+        match self {
+            Item::Table(table) => {
+                let mut child = Table::new();
+                child.set_implicit(true);
+                table.insert(token, Item::Table(child));
+            }
+            Item::Value(toml_edit::Value::InlineTable(table)) => {
+                table.insert(token, InlineTable::new().into());
+            }
+            _ if toml_len(self).is_some() => return Err(format!("array has no element {}", token)),
+            _ => return Err(format!("cannot add {} inside a {}", token, self.type_name())),
+        }
+        Ok(self.get_mut(token).expect("just inserted"))
+    }
+
+    fn set_child(&mut self, token: &str, value: &Value) -> std::result::Result<(), String> {
+        let new = toml_value(value)?;
+        match self {
+            Item::Table(table) => match (table.get_mut(token), new) {
+                (Some(Item::Value(old)), new) if !value.is_object() => replace_keeping_decor(old, new),
+                (_, toml_edit::Value::InlineTable(inline)) => {
+                    table.insert(token, Item::Table(inline.into_table()));
+                }
+                (_, new) => {
+                    table.insert(token, Item::Value(new));
+                }
+            },
+            Item::Value(toml_edit::Value::InlineTable(table)) => match table.get_mut(token) {
+                Some(old) => replace_keeping_decor(old, new),
+                None => {
+                    table.insert(token, new);
+                }
+            },
+            Item::Value(toml_edit::Value::Array(items)) => {
+                if token == "-" || token == items.len().to_string() {
+                    items.push(new);
+                } else {
+                    let index = array_index(token, items.len()).ok_or_else(|| format!("array has no element {}", token))?;
+                    replace_keeping_decor(items.get_mut(index).expect("index in range"), new);
+                }
+            }
+            Item::ArrayOfTables(tables) => {
+                let toml_edit::Value::InlineTable(inline) = new else {
+                    return Err("an array of tables only holds objects".to_string());
+                };
+                if token == "-" || token == tables.len().to_string() {
+                    tables.push(inline.into_table());
+                } else {
+                    let index = array_index(token, tables.len()).ok_or_else(|| format!("array has no element {}", token))?;
+                    *tables.get_mut(index).expect("index in range") = inline.into_table();
+                }
+            }
+            _ => return Err(format!("cannot set {} inside a {}", token, self.type_name())),
+        }
+        Ok(())
+    }
+
+    fn remove_child(&mut self, token: &str) -> std::result::Result<(), String> {
+        let removed = match self {
+            Item::Table(table) => table.remove(token).is_some(),
+            Item::Value(toml_edit::Value::InlineTable(table)) => table.remove(token).is_some(),
+            Item::Value(toml_edit::Value::Array(items)) => {
+                array_index(token, items.len()).map(|index| items.remove(index)).is_some()
+            }
+            Item::ArrayOfTables(tables) => {
+                array_index(token, tables.len()).map(|index| tables.remove(index)).is_some()
+            }
+            _ => false,
+        };
+        removed.then_some(()).ok_or_else(|| format!("nothing named {} to remove", token))
+    }
+
+    fn push(&mut self, value: &Value) -> std::result::Result<(), String> {
+        match self {
+            Item::Value(toml_edit::Value::Array(items)) => items.push(toml_value(value)?),
+            Item::ArrayOfTables(tables) => match toml_value(value)? {
+                toml_edit::Value::InlineTable(inline) => tables.push(inline.into_table()),
+                _ => return Err("an array of tables only holds objects".to_string()),
+            },
+            _ => return Err(format!("not an array but a {}", self.type_name())),
+        }
+        Ok(())
+    }
+}
+
+/// Length of a TOML array or array of tables
+fn toml_len(item: &Item) -> Option<usize> {
+    match item {
+        Item::Value(toml_edit::Value::Array(items)) => Some(items.len()),
+        Item::ArrayOfTables(tables) => Some(tables.len()),
+        _ => None,
+    }
+}
+
+/// Swap in a new value, keeping the whitespace and comments around the old one
+fn replace_keeping_decor(old: &mut toml_edit::Value, new: toml_edit::Value) {
+    let decor = old.decor().clone();
+    *old = new;
+    *old.decor_mut() = decor;
+}
+
+fn toml_value(value: &Value) -> std::result::Result<toml_edit::Value, String> {
+    Ok(match value {
+        Value::Null => return Err("TOML has no null; remove the key instead".to_string()),
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => n.as_f64().ok_or_else(|| format!("{} does not fit in a TOML number", n))?.into(),
+        },
+        Value::String(s) => s.as_str().into(),
+        Value::Array(items) => items.iter().map(toml_value).collect::<std::result::Result<toml_edit::Array, _>>()?.into(),
+        Value::Object(map) => {
+            let mut table = InlineTable::new();
+            for (key, value) in map {
+                table.insert(key, toml_value(value)?);
+            }
+            table.into()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn edits(value: Value) -> Vec<EditOperation> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(StructuredFormat::from_path(Path::new("Cargo.toml")), Some(StructuredFormat::Toml));
+        assert_eq!(StructuredFormat::from_path(Path::new("ci.YML")), Some(StructuredFormat::Yaml));
+        assert_eq!(StructuredFormat::from_path(Path::new("package.json")), Some(StructuredFormat::Json));
+        assert_eq!(StructuredFormat::from_path(Path::new("notes.txt")), None);
+    }
+
+    #[test]
+    fn test_json_edits_keep_order_and_indentation() {
+        let text = "{\n    \"name\": \"app\",\n    \"version\": \"1.0.0\",\n    \"scripts\": {\"test\": \"jest\"},\n    \"files\": [\"dist\"]\n}\n";
+        let updated = apply_edits(text, StructuredFormat::Json, &edits(json!([
+            {"op": "set", "pointer": "/version", "value": "1.1.0"},
+            {"op": "set", "pointer": "/scripts/build", "value": "tsc"},
+            {"op": "append", "pointer": "/files", "value": "README.md"},
+            {"op": "remove", "pointer": "/name"},
+            {"op": "set", "pointer": "/publishConfig/access", "value": "public"}
+        ]))).unwrap();
+        assert_eq!(updated, concat!(
+            "{\n",
+            "    \"version\": \"1.1.0\",\n",
+            "    \"scripts\": {\n        \"test\": \"jest\",\n        \"build\": \"tsc\"\n    },\n",
+            "    \"files\": [\n        \"dist\",\n        \"README.md\"\n    ],\n",
+            "    \"publishConfig\": {\n        \"access\": \"public\"\n    }\n",
+            "}\n",
+        ));
+
+        let compact = apply_edits(r#"{"a":[1,2]}"#, StructuredFormat::Json, &edits(json!([
+            {"op": "set", "pointer": "/a/-", "value": 3},
+            {"op": "set", "pointer": "/a/0", "value": 0}
+        ]))).unwrap();
+        assert_eq!(compact, r#"{"a":[0,2,3]}"#);
+    }
+
+    #[test]
+    fn test_toml_edits_keep_comments() {
+        let text = "# Build settings\n[package]\nname = \"app\" # the crate name\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1\"\n\n[[bin]]\nname = \"app\"\n";
+        let updated = apply_edits(text, StructuredFormat::Toml, &edits(json!([
+            {"op": "set", "pointer": "/package/name", "value": "tool"},
+            {"op": "set", "pointer": "/package/keywords", "value": ["cli"]},
+            {"op": "append", "pointer": "/package/keywords", "value": "files"},
+            {"op": "set", "pointer": "/dependencies/tokio", "value": {"version": "1", "features": ["rt"]}},
+            {"op": "remove", "pointer": "/dependencies/serde"},
+            {"op": "append", "pointer": "/bin", "value": {"name": "helper"}},
+            {"op": "set", "pointer": "/profile/release/lto", "value": true}
+        ]))).unwrap();
+        assert_eq!(updated, concat!(
+            "# Build settings\n[package]\nname = \"tool\" # the crate name\nversion = \"0.1.0\"\n",
+            "keywords = [\"cli\", \"files\"]\n",
+            // New tables keep the key order of the value they were given
+            "\n[dependencies]\n\n[dependencies.tokio]\nversion = \"1\"\nfeatures = [\"rt\"]\n",
+            "\n[[bin]]\nname = \"app\"\n\n[[bin]]\nname = \"helper\"\n",
+            "\n[profile.release]\nlto = true\n",
+        ));
+    }
+
+    #[test]
+    fn test_yaml_edits() {
+        let text = "---\nname: ci\non:\n  push:\n    branches: [main]\njobs: {}\n";
+        let updated = apply_edits(text, StructuredFormat::Yaml, &edits(json!([
+            {"op": "append", "pointer": "/on/push/branches", "value": "release"},
+            {"op": "set", "pointer": "/jobs/test~1unit", "value": {"runs-on": "ubuntu-latest"}}
+        ]))).unwrap();
+        assert_eq!(
+            updated,
+            "---\nname: ci\non:\n  push:\n    branches:\n    - main\n    - release\njobs:\n  test/unit:\n    runs-on: ubuntu-latest\n"
+        );
+    }
+
+    #[test]
+    fn test_failed_edits_name_the_operation() {
+        let text = r#"{"list": [1], "n": 1}"#;
+        let cases = [
+            (json!({"op": "remove", "pointer": "/missing"}), "Cannot apply remove to \"/missing\": nothing named missing to remove"),
+            (json!({"op": "set", "pointer": "/n/x", "value": 1}), "cannot set x inside a number"),
+            (json!({"op": "set", "pointer": "/n/x/y", "value": 1}), "cannot add x inside a number"),
+            (json!({"op": "set", "pointer": "/list/5", "value": 1}), "array has no element 5"),
+            (json!({"op": "append", "pointer": "/n", "value": 1}), "not an array but a number"),
+            (json!({"op": "append", "pointer": "/a/b", "value": 1}), "nothing at /a"),
+            (json!({"op": "set", "pointer": "", "value": 1}), "the whole document cannot be replaced"),
+            (json!({"op": "remove", "pointer": "list"}), "must be empty or start with '/'"),
+        ];
+        for (edit, message) in cases {
+            let err = apply_edits(text, StructuredFormat::Json, &edits(json!([edit]))).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+
+        let err = apply_edits("a = 1\n", StructuredFormat::Toml, &edits(json!([
+            {"op": "set", "pointer": "/b", "value": null}
+        ]))).unwrap_err();
+        assert!(err.to_string().contains("TOML has no null"));
+        assert!(apply_edits("{", StructuredFormat::Json, &[]).unwrap_err().to_string().contains("not valid JSON"));
+    }
+}
@@ -0,0 +1,94 @@
+//! Byte order marks and line endings of text files.
+//!
+//! Clients write text with `\n` line endings and no byte order mark. Saving
+//! that over a CRLF or BOM-prefixed file changes every line, so a one-line
+//! edit shows up as a whole-file diff. [`TextStyle`] records how a file is
+//! laid out so a write can put new text back the same way.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The UTF-8 byte order mark, as it appears at the start of decoded text
+pub const BOM: char = '\u{feff}';
+
+/// How lines of a text file end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+/// Byte order mark and dominant line ending of a text file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TextStyle {
+    pub bom: bool,
+    /// The ending most lines use; `None` for text without line breaks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_ending: Option<LineEnding>,
+}
+
+impl TextStyle {
+    /// The style of `text`. Ties between CRLF and LF count as LF.
+    pub fn detect(text: &str) -> Self {
+        let crlf = text.matches("\r\n").count();
+        let lf = text.matches('\n').count() - crlf;
+        let line_ending = match (crlf, lf) {
+            (0, 0) => None,
+            (crlf, lf) if crlf > lf => Some(LineEnding::Crlf),
+            _ => Some(LineEnding::Lf),
+        };
+        Self { bom: text.starts_with(BOM), line_ending }
+    }
+
+    /// `text` in this style: every line break turned into the style's
+    /// ending, and the byte order mark added or removed to match
+    pub fn apply(&self, text: &str) -> String {
+        let text = strip_bom(text);
+        let mut out = String::with_capacity(text.len() + 3);
+        if self.bom {
+            out.push(BOM);
+        }
+        match self.line_ending {
+            Some(LineEnding::Crlf) => out.push_str(&text.replace("\r\n", "\n").replace('\n', "\r\n")),
+            Some(LineEnding::Lf) => out.push_str(&text.replace("\r\n", "\n")),
+            None => out.push_str(text),
+        }
+        out
+    }
+}
+
+/// `text` without a leading byte order mark
+pub fn strip_bom(text: &str) -> &str {
+    text.strip_prefix(BOM).unwrap_or(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_and_apply() {
+        let style = TextStyle::detect("\u{feff}a\r\nb\r\nc\n");
+        assert_eq!(style, TextStyle { bom: true, line_ending: Some(LineEnding::Crlf) });
+        assert_eq!(style.apply("x\ny\r\nz\n"), "\u{feff}x\r\ny\r\nz\r\n");
+        assert_eq!(style.apply("\u{feff}x"), "\u{feff}x");
+
+        let plain = TextStyle::detect("a\nb\r\n");
+        assert_eq!(plain, TextStyle { bom: false, line_ending: Some(LineEnding::Lf) });
+        assert_eq!(plain.apply("\u{feff}x\r\ny"), "x\ny");
+
+        assert_eq!(TextStyle::detect("one line"), TextStyle::default());
+        assert_eq!(TextStyle::default().apply("a\r\nb\n"), "a\r\nb\n");
+        assert_eq!(serde_json::to_value(style).unwrap(), serde_json::json!({"bom": true, "line_ending": "crlf"}));
+    }
+}
@@ -0,0 +1,34 @@
+//! Tools that compress and decompress single files
+
+use super::ToolRegistry;
+use crate::compression::CompressionSummary;
+use crate::error::Result;
+use crate::mcp::McpServer;
+use crate::protocol::{CallToolResult, CompressFileParams, DecompressFileParams};
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.add(
+        "compress_file",
+        "Compress a single file with gzip or zstd, keeping the original",
+        compress_file,
+    )
+    .returns::<CompressionSummary>()
+    .mutating();
+    registry.add(
+        "decompress_file",
+        "Decompress a gzip or zstd file, refusing output larger than a size limit",
+        decompress_file,
+    )
+    .returns::<CompressionSummary>()
+    .mutating();
+}
+
+fn compress_file(server: &McpServer, params: CompressFileParams) -> Result<CallToolResult> {
+    let summary = server.compress_file(params)?;
+    CallToolResult::json(&summary)
+}
+
+fn decompress_file(server: &McpServer, params: DecompressFileParams) -> Result<CallToolResult> {
+    let summary = server.decompress_file(params)?;
+    CallToolResult::json(&summary)
+}
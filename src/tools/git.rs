@@ -0,0 +1,63 @@
+//! Tools that inspect and commit to git repositories (`git` feature)
+
+use super::ToolRegistry;
+use crate::error::Result;
+use crate::git::{GitInspector, Snapshot, DEFAULT_LOG_LIMIT};
+use crate::mcp::McpServer;
+use crate::protocol::{CallToolResult, GitBlameParams, GitLogParams, GitShowParams, GitSnapshotParams, GitStatusParams};
+use std::path::Path;
+use tracing::info;
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.add(
+        "git_status",
+        "List uncommitted changes (staged, unstaged, and untracked) at or beneath a path in a git repository",
+        git_status,
+    );
+    registry.add("git_log", "List the commits that changed a file or directory, newest first", git_log);
+    registry.add("git_show", "Read a file as it was at a given revision", git_show);
+    registry.add(
+        "git_snapshot",
+        "Stage and commit the files changed through FileJack in the repository holding a path, for an audit trail of edits that is easy to revert",
+        git_snapshot,
+    )
+    .returns::<Snapshot>();
+    registry.add("git_blame", "Show which commit last changed each line of a committed file", git_blame);
+}
+
+fn git_status(server: &McpServer, params: GitStatusParams) -> Result<CallToolResult> {
+    info!(path = %params.path, "Reading git status");
+    let result = GitInspector::new(server.state().reader.policy()).status(Path::new(&params.path))?;
+    CallToolResult::json(&result)
+}
+
+fn git_log(server: &McpServer, params: GitLogParams) -> Result<CallToolResult> {
+    info!(path = %params.path, "Reading git log");
+    let result = GitInspector::new(server.state().reader.policy())
+        .log(Path::new(&params.path), params.max_count.unwrap_or(DEFAULT_LOG_LIMIT))?;
+    CallToolResult::json(&result)
+}
+
+fn git_show(server: &McpServer, params: GitShowParams) -> Result<CallToolResult> {
+    info!(path = %params.path, "Reading file at git revision");
+    let result = GitInspector::new(server.state().reader.policy())
+        .show(Path::new(&params.path), params.revision.as_deref().unwrap_or("HEAD"))?;
+    Ok(CallToolResult::text(result))
+}
+
+fn git_blame(server: &McpServer, params: GitBlameParams) -> Result<CallToolResult> {
+    info!(path = %params.path, "Reading git blame");
+    let result = GitInspector::new(server.state().reader.policy())
+        .blame(Path::new(&params.path), params.start_line, params.end_line)?;
+    CallToolResult::json(&result)
+}
+
+fn git_snapshot(server: &McpServer, params: GitSnapshotParams) -> Result<CallToolResult> {
+    info!(path = %params.path, "Committing snapshot of changed files");
+    let state = server.state();
+    let mut changes = server.changes().lock().unwrap_or_else(|e| e.into_inner());
+    let snapshot = GitInspector::new(state.reader.policy())
+        .snapshot(Path::new(&params.path), &mut changes, &state.git, params.message.as_deref())?;
+    info!(commit = ?snapshot.commit, file_count = snapshot.files.len(), "Snapshot completed");
+    CallToolResult::json(&snapshot)
+}
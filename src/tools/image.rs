@@ -0,0 +1,37 @@
+//! Tools that inspect images (`image` feature)
+
+use super::ToolRegistry;
+use crate::error::Result;
+use crate::images::{ImageInfo, Thumbnail, DEFAULT_THUMBNAIL_SIZE};
+use crate::mcp::McpServer;
+use crate::protocol::{CallToolResult, ImageInfoParams, ToolContent};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use tracing::info;
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.add(
+        "image_info",
+        "Get an image's format and dimensions, and optionally a downscaled PNG thumbnail to preview it without reading the full-resolution file",
+        image_info,
+    )
+    .returns::<ImageInfo>();
+}
+
+fn image_info(server: &McpServer, params: ImageInfoParams) -> Result<CallToolResult> {
+    info!(path = %params.path, thumbnail = params.thumbnail, "Inspecting image");
+    let bytes = server.state().reader.read_to_bytes(&params.path)?;
+    server.metrics().add_bytes_read(bytes.len() as u64);
+    let thumbnail_size = params.thumbnail.then(|| params.max_size.unwrap_or(DEFAULT_THUMBNAIL_SIZE));
+    let image = crate::images::inspect(&bytes, thumbnail_size)?;
+    info!(path = %params.path, width = image.width, height = image.height, "Image inspected");
+
+    let mut result = CallToolResult::json(&image)?;
+    if let Some(thumbnail) = &image.thumbnail {
+        result.content.push(ToolContent::Image {
+            data: BASE64.encode(&thumbnail.png),
+            mime_type: Thumbnail::MIME_TYPE.to_string(),
+        });
+    }
+    Ok(result)
+}
@@ -0,0 +1,63 @@
+//! Tools that take and release advisory file locks and session claims
+
+use super::ToolRegistry;
+use crate::access_control::WriteOperation;
+use crate::error::Result;
+use crate::locks::{ClaimInfo, LockInfo};
+use crate::mcp::McpServer;
+use crate::protocol::{CallToolResult, ClaimPathParams, LockFileParams, ReleasePathParams, UnlockFileParams};
+use crate::session::Session;
+use std::path::Path;
+use std::time::Duration;
+use tracing::info;
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.add(
+        "lock_file",
+        "Take a lease-based lock on a file so other clients cannot modify it. Modifying tools must then pass the returned token as lock_token",
+        lock_file,
+    )
+    .returns::<LockInfo>();
+    registry.add("unlock_file", "Release a lock taken with lock_file", unlock_file);
+    registry.add_for_session(
+        "claim_path",
+        "Claim a file or directory for this session so other sessions cannot modify it until it is released or the claim expires",
+        claim_path,
+    )
+    .returns::<ClaimInfo>();
+    registry.add_for_session("release_path", "Release a claim taken with claim_path", release_path);
+}
+
+fn lock_file(server: &McpServer, params: LockFileParams) -> Result<CallToolResult> {
+    // Appending is the least a lock holder may do, so append-only files can be locked
+    server.state().writer.validate_operation(Path::new(&params.path), WriteOperation::Append)?;
+    info!(path = %params.path, owner = ?params.owner, "Locking file");
+    let lock = server.locks().acquire(
+        Path::new(&params.path),
+        params.owner,
+        params.ttl_seconds.map(Duration::from_secs),
+        params.token.as_deref(),
+    )?;
+    info!(path = %params.path, expires_in = lock.expires_in_seconds, "File locked");
+    CallToolResult::json(&lock)
+}
+
+fn unlock_file(server: &McpServer, params: UnlockFileParams) -> Result<CallToolResult> {
+    info!(path = %params.path, "Unlocking file");
+    server.locks().release(Path::new(&params.path), &params.token)?;
+    info!(path = %params.path, "File unlocked");
+    Ok(CallToolResult::text(format!("Successfully unlocked {}", params.path)))
+}
+
+fn claim_path(server: &McpServer, session: &Session, params: ClaimPathParams) -> Result<CallToolResult> {
+    server.state().writer.validate_operation(Path::new(&params.path), WriteOperation::Append)?;
+    info!(path = %params.path, session = session.id(), "Claiming path");
+    let claim = server.locks().claim(Path::new(&params.path), session.id(), params.ttl_seconds.map(Duration::from_secs))?;
+    CallToolResult::json(&claim)
+}
+
+fn release_path(server: &McpServer, session: &Session, params: ReleasePathParams) -> Result<CallToolResult> {
+    server.locks().release_claim(Path::new(&params.path), session.id())?;
+    info!(path = %params.path, session = session.id(), "Claim released");
+    Ok(CallToolResult::text(format!("Successfully released {}", params.path)))
+}
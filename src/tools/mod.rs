@@ -0,0 +1,328 @@
+//! Tools a server offers.
+//!
+//! The built-in tools live in the submodules, grouped by what they do, and
+//! are collected into a [`ToolRegistry`] that generates `tools/list` and
+//! dispatches `tools/call`. Each one is registered with the params type it
+//! deserializes, so its advertised schema comes from the same type.
+//!
+//! Crates that embed the server add their own tools by implementing
+//! [`ToolHandler`] and passing it to [`crate::McpServer::register_tool`] or
+//! [`crate::McpServerBuilder::tool`]. Handlers reach files through the
+//! [`ToolContext`], whose reader and writer apply the server's access policy
+//! just as the built-in tools do.
+//!
+//! ```
+//! use filejack::tools::{text_result, ToolContext, ToolHandler};
+//! use filejack::{AccessPolicy, McpServer, Result};
+//! use serde_json::{json, Value};
+//! use std::sync::Arc;
+//!
+//! struct WordCount;
+//!
+//! impl ToolHandler for WordCount {
+//!     fn name(&self) -> &str {
+//!         "word_count"
+//!     }
+//!
+//!     fn description(&self) -> &str {
+//!         "Count the words in a text file"
+//!     }
+//!
+//!     fn input_schema(&self) -> Value {
+//!         json!({"type": "object", "properties": {"path": {"type": "string"}}, "required": ["path"]})
+//!     }
+//!
+//!     fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<Value> {
+//!         let path = arguments["path"].as_str().unwrap_or_default();
+//!         let words = ctx.reader().read_to_string(path)?.split_whitespace().count();
+//!         Ok(text_result(words.to_string()))
+//!     }
+//! }
+//!
+//! let server = McpServer::new(AccessPolicy::permissive());
+//! server.register_tool(Arc::new(WordCount)).unwrap();
+//! assert!(server.list_tools().iter().any(|tool| tool.name == "word_count"));
+//! ```
+
+mod compress;
+#[cfg(feature = "git")]
+mod git;
+#[cfg(feature = "image")]
+mod image;
+mod lock;
+mod read;
+mod search;
+mod server;
+mod write;
+
+use crate::access_control::AccessPolicy;
+use crate::error::{FileJackError, Result};
+use crate::file_ops::{FileReader, FileWriter};
+use crate::mcp::McpServer;
+use crate::protocol::{input_schema, output_schema, CallToolResult, McpTool};
+use crate::session::{Progress, Session};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::error;
+
+/// A tool provided by code outside this crate
+pub trait ToolHandler: Send + Sync {
+    /// Name clients call the tool by; must not clash with another tool
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    /// JSON Schema of the tool's arguments; [`crate::protocol::input_schema`]
+    /// derives one from a `JsonSchema` type
+    fn input_schema(&self) -> Value;
+
+    /// JSON Schema of the `structuredContent` of the tool's results, if it
+    /// returns any; [`crate::protocol::output_schema`] derives one from a
+    /// `JsonSchema` type
+    fn output_schema(&self) -> Option<Value> {
+        None
+    }
+
+    /// Run the tool, returning an MCP tool result such as
+    /// [`text_result`] builds or a serialized [`CallToolResult`]
+    fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<Value>;
+
+    /// The `tools/list` entry for this tool
+    fn definition(&self) -> McpTool {
+        McpTool {
+            name: self.name().to_string(),
+            title: None,
+            description: self.description().to_string(),
+            input_schema: self.input_schema(),
+            output_schema: self.output_schema(),
+        }
+    }
+}
+
+/// What a [`ToolHandler`] may use while handling a call: the server's
+/// current reader and writer, with its access policy and file system
+pub struct ToolContext<'a> {
+    reader: &'a FileReader,
+    writer: &'a FileWriter,
+}
+
+impl<'a> ToolContext<'a> {
+    pub(crate) fn new(reader: &'a FileReader, writer: &'a FileWriter) -> Self {
+        Self { reader, writer }
+    }
+
+    pub fn reader(&self) -> &'a FileReader {
+        self.reader
+    }
+
+    pub fn writer(&self) -> &'a FileWriter {
+        self.writer
+    }
+
+    pub fn policy(&self) -> &'a AccessPolicy {
+        self.reader.policy()
+    }
+}
+
+/// A tool result holding a single piece of text
+pub fn text_result(text: impl Into<String>) -> Value {
+    json!(CallToolResult::text(text))
+}
+
+/// Runs a built-in tool once its arguments have been parsed
+type BuiltinCall = Box<dyn Fn(&McpServer, &Progress<'_>, Value) -> Result<Value> + Send + Sync>;
+
+/// A tool built into the server
+pub(crate) struct Builtin {
+    name: &'static str,
+    description: &'static str,
+    input_schema: fn() -> Value,
+    output_schema: Option<fn() -> Value>,
+    offered: fn(&McpServer) -> bool,
+    /// Whether successful calls are recorded in the session's journal
+    mutates: bool,
+    call: BuiltinCall,
+}
+
+impl Builtin {
+    /// Offer the tool only while `offered` holds for the server
+    pub(crate) fn offered_when(&mut self, offered: fn(&McpServer) -> bool) -> &mut Self {
+        self.offered = offered;
+        self
+    }
+
+    /// Advertise `T` as the shape of the tool's structured results
+    pub(crate) fn returns<T: JsonSchema>(&mut self) -> &mut Self {
+        self.output_schema = Some(output_schema::<T>);
+        self
+    }
+
+    /// Record successful calls in the calling session's journal
+    pub(crate) fn mutating(&mut self) -> &mut Self {
+        self.mutates = true;
+        self
+    }
+
+    pub(crate) fn mutates(&self) -> bool {
+        self.mutates
+    }
+
+    pub(crate) fn is_offered(&self, server: &McpServer) -> bool {
+        (self.offered)(server)
+    }
+
+    pub(crate) fn call(&self, server: &McpServer, progress: &Progress<'_>, arguments: Value) -> Result<Value> {
+        (self.call)(server, progress, arguments)
+    }
+
+    fn definition(&self) -> McpTool {
+        McpTool {
+            name: self.name.to_string(),
+            title: None,
+            description: self.description.to_string(),
+            input_schema: (self.input_schema)(),
+            output_schema: self.output_schema.map(|schema| schema()),
+        }
+    }
+}
+
+/// The built-in tools, in `tools/list` order
+pub(crate) struct ToolRegistry {
+    tools: Vec<Builtin>,
+}
+
+impl ToolRegistry {
+    /// Every built-in tool of this build
+    pub(crate) fn builtin() -> Self {
+        let mut registry = Self { tools: Vec::new() };
+        read::register(&mut registry);
+        write::register(&mut registry);
+        search::register(&mut registry);
+        compress::register(&mut registry);
+        lock::register(&mut registry);
+        server::register(&mut registry);
+        #[cfg(feature = "git")]
+        git::register(&mut registry);
+        #[cfg(feature = "image")]
+        image::register(&mut registry);
+        registry
+    }
+
+    /// Add a tool whose arguments deserialize into `P`, which also
+    /// provides its input schema
+    pub(crate) fn add<P>(
+        &mut self,
+        name: &'static str,
+        description: &'static str,
+        call: fn(&McpServer, P) -> Result<CallToolResult>,
+    ) -> &mut Builtin
+    where
+        P: DeserializeOwned + JsonSchema + 'static,
+    {
+        self.push::<P>(name, description, Box::new(move |server, _, arguments| {
+            let result = call(server, parse_arguments(name, arguments)?)?;
+            Ok(serde_json::to_value(result)?)
+        }))
+    }
+
+    /// Add a tool that also needs the session calling it
+    pub(crate) fn add_for_session<P>(
+        &mut self,
+        name: &'static str,
+        description: &'static str,
+        call: fn(&McpServer, &Session, P) -> Result<CallToolResult>,
+    ) -> &mut Builtin
+    where
+        P: DeserializeOwned + JsonSchema + 'static,
+    {
+        self.push::<P>(name, description, Box::new(move |server, progress, arguments| {
+            let result = call(server, progress.session(), parse_arguments(name, arguments)?)?;
+            Ok(serde_json::to_value(result)?)
+        }))
+    }
+
+    /// Add a tool that reports its progress to the client calling it
+    pub(crate) fn add_with_progress<P>(
+        &mut self,
+        name: &'static str,
+        description: &'static str,
+        call: fn(&McpServer, &Progress<'_>, P) -> Result<CallToolResult>,
+    ) -> &mut Builtin
+    where
+        P: DeserializeOwned + JsonSchema + 'static,
+    {
+        self.push::<P>(name, description, Box::new(move |server, progress, arguments| {
+            let result = call(server, progress, parse_arguments(name, arguments)?)?;
+            Ok(serde_json::to_value(result)?)
+        }))
+    }
+
+    fn push<P: JsonSchema>(&mut self, name: &'static str, description: &'static str, call: BuiltinCall) -> &mut Builtin {
+        debug_assert!(!self.contains(name), "tool {} registered twice", name);
+        self.tools.push(Builtin {
+            name,
+            description,
+            input_schema: input_schema::<P>,
+            output_schema: None,
+            offered: |_| true,
+            mutates: false,
+            call,
+        });
+        self.tools.last_mut().expect("tool was just added")
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&Builtin> {
+        self.tools.iter().find(|tool| tool.name == name)
+    }
+
+    /// Whether a built-in tool has this name, offered or not
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// `tools/list` entries of the tools `server` currently offers
+    pub(crate) fn definitions(&self, server: &McpServer) -> Vec<McpTool> {
+        self.tools.iter().filter(|tool| tool.is_offered(server)).map(Builtin::definition).collect()
+    }
+}
+
+/// Arguments of a tool that takes none
+#[derive(Deserialize, JsonSchema)]
+pub(crate) struct NoParams {}
+
+fn parse_arguments<P: DeserializeOwned>(tool: &str, arguments: Value) -> Result<P> {
+    serde_json::from_value(arguments).map_err(|e| {
+        error!("Failed to parse {} params: {}", tool, e);
+        FileJackError::InvalidParameters(format!("Invalid parameters for {}: {}", tool, e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_dispatch() {
+        let server = McpServer::new(AccessPolicy::permissive());
+        let registry = ToolRegistry::builtin();
+        let names: Vec<_> = registry.tools.iter().map(|tool| tool.name).collect();
+        assert!(names.iter().enumerate().all(|(i, name)| !names[..i].contains(name)));
+
+        let definitions = registry.definitions(&server);
+        assert_eq!(definitions.len(), names.len() - 3);
+        let hidden = ["get_metrics", "create_temp_workspace", "get_extended_attributes"];
+        assert!(!definitions.iter().any(|tool| hidden.contains(&tool.name.as_str())));
+        assert!(registry.contains("get_metrics"));
+        let info = definitions.iter().find(|tool| tool.name == "server_info").unwrap();
+        assert_eq!(info.input_schema["type"], "object");
+        assert_eq!(info.output_schema.as_ref().unwrap()["properties"]["policy"]["type"], "object");
+        let exists = definitions.iter().find(|tool| tool.name == "file_exists").unwrap();
+        assert!(exists.output_schema.is_none());
+
+        let error = registry.get("read_file").unwrap().call(&server, &Progress::new(server.default_session(), None), json!({"encoding": "text"})).unwrap_err();
+        assert!(error.to_string().contains("Invalid parameters for read_file: missing field `path`"));
+        assert!(registry.get("server_info").unwrap().call(&server, &Progress::new(server.default_session(), None), json!({})).is_ok());
+    }
+}
@@ -0,0 +1,136 @@
+//! Tools that read files and directories
+
+use super::{NoParams, ToolRegistry};
+use crate::error::Result;
+use crate::file_ops::FileMetadata;
+use crate::follow::FollowInfo;
+use crate::mcp::McpServer;
+use crate::overview::WorkspaceOverview;
+use crate::protocol::{
+    CallToolResult, ContentEncoding, EmbeddedResource, FileExistsParams, GetExtendedAttributesParams, GetMetadataParams, ListDirectoryParams,
+    PackDirectoryParams, ReadFileParams, ReadRangeResult, ReadLinesParams, ReadRangeParams, SetWorkingDirectoryParams,
+    SummarizeWorkspaceParams, TailCancelParams, TailFollowParams, ToolContent,
+};
+use crate::session::Session;
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.add("read_file", "Read contents from a file", read_file);
+    registry.add("list_directory", "List contents of a directory", list_directory);
+    registry.add("get_metadata", "Get metadata information about a file", get_metadata).returns::<FileMetadata>();
+    registry.add(
+        "get_extended_attributes",
+        "List a file's extended attributes and their values",
+        get_extended_attributes,
+    )
+    .offered_when(McpServer::offers_extended_attributes);
+    registry.add("file_exists", "Check if a file or directory exists", file_exists);
+    registry.add("read_lines", "Read specific lines from a file", read_lines);
+    registry.add(
+        "read_range",
+        "Read a byte range from a file, for resumable transfers and large binary files",
+        read_range,
+    )
+    .returns::<ReadRangeResult>();
+    registry.add(
+        "pack_directory",
+        "Read the text files of a directory that match a glob as one bundle, each under a header naming it",
+        pack_directory,
+    );
+    registry.add(
+        "summarize_workspace",
+        "Summarize a directory tree: top-level entries, file counts per extension and language, total size, and the largest files",
+        summarize_workspace,
+    )
+    .returns::<WorkspaceOverview>();
+    registry.add_for_session(
+        "tail_follow",
+        "Follow a file like tail -f: lines appended to it are sent as notifications/message until tail_cancel",
+        tail_follow,
+    )
+    .returns::<FollowInfo>();
+    registry.add_for_session("tail_cancel", "Stop following a file started with tail_follow", tail_cancel);
+    registry.add_for_session(
+        "set_working_directory",
+        "Set the directory this session's relative paths, such as src/main.rs, are resolved against",
+        set_working_directory,
+    );
+    registry.add_for_session(
+        "get_working_directory",
+        "Get the directory this session's relative paths are resolved against",
+        get_working_directory,
+    );
+}
+
+fn read_file(server: &McpServer, params: ReadFileParams) -> Result<CallToolResult> {
+    let result = server.read_file(params)?;
+    let next_offset = result.next_offset;
+    let mut result = match result.encoding {
+        ContentEncoding::Text => CallToolResult::text(result.content),
+        ContentEncoding::Base64 => CallToolResult {
+            content: vec![ToolContent::Resource {
+                resource: EmbeddedResource {
+                    uri: format!("file://{}", result.path),
+                    mime_type: "application/octet-stream".to_string(),
+                    blob: result.content,
+                },
+            }],
+            structured_content: None,
+        },
+    };
+    if let Some(offset) = next_offset {
+        result.content.push(ToolContent::Text {
+            text: format!("[The file continues past byte {}. Use read_range with offset {} to read the rest.]", offset, offset),
+        });
+    }
+    Ok(result)
+}
+
+fn list_directory(server: &McpServer, params: ListDirectoryParams) -> Result<CallToolResult> {
+    CallToolResult::json(&server.list_directory(params)?)
+}
+
+fn get_metadata(server: &McpServer, params: GetMetadataParams) -> Result<CallToolResult> {
+    CallToolResult::json(&server.get_metadata(params)?)
+}
+
+fn get_extended_attributes(server: &McpServer, params: GetExtendedAttributesParams) -> Result<CallToolResult> {
+    CallToolResult::json(&server.get_extended_attributes(params)?)
+}
+
+fn file_exists(server: &McpServer, params: FileExistsParams) -> Result<CallToolResult> {
+    Ok(CallToolResult::text(server.file_exists(params)?.to_string()))
+}
+
+fn read_lines(server: &McpServer, params: ReadLinesParams) -> Result<CallToolResult> {
+    Ok(CallToolResult::text(server.read_lines(params)?.join("\n")))
+}
+
+fn read_range(server: &McpServer, params: ReadRangeParams) -> Result<CallToolResult> {
+    CallToolResult::json(&server.read_range(params)?)
+}
+
+fn pack_directory(server: &McpServer, params: PackDirectoryParams) -> Result<CallToolResult> {
+    Ok(CallToolResult::text(server.pack_directory(params)?.render()))
+}
+
+fn summarize_workspace(server: &McpServer, params: SummarizeWorkspaceParams) -> Result<CallToolResult> {
+    CallToolResult::json(&server.summarize_workspace(params)?)
+}
+
+fn tail_follow(server: &McpServer, session: &Session, params: TailFollowParams) -> Result<CallToolResult> {
+    CallToolResult::json(&server.tail_follow(session, params)?)
+}
+
+fn tail_cancel(server: &McpServer, session: &Session, params: TailCancelParams) -> Result<CallToolResult> {
+    let follower = params.follower;
+    server.tail_cancel(session, params)?;
+    Ok(CallToolResult::text(format!("Stopped follower {}", follower)))
+}
+
+fn set_working_directory(server: &McpServer, session: &Session, params: SetWorkingDirectoryParams) -> Result<CallToolResult> {
+    Ok(CallToolResult::text(server.set_working_directory(session, params)?.display().to_string()))
+}
+
+fn get_working_directory(server: &McpServer, session: &Session, _: NoParams) -> Result<CallToolResult> {
+    Ok(CallToolResult::text(server.working_directory(session).display().to_string()))
+}
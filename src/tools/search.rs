@@ -0,0 +1,53 @@
+//! Tools that find files by name, age, or contents
+
+use super::ToolRegistry;
+use crate::error::Result;
+use crate::mcp::McpServer;
+#[cfg(feature = "index")]
+use crate::protocol::SearchContentParams;
+use crate::protocol::{CallToolResult, GrepFileParams, RecentFilesParams, SearchFilesParams};
+#[cfg(feature = "index")]
+use tracing::info;
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.add("search_files", "Search for files matching a glob pattern", search_files);
+    registry.add(
+        "recent_files",
+        "List the most recently modified files beneath a directory, newest first",
+        recent_files,
+    );
+    registry.add(
+        "grep_file",
+        "Search for patterns in file contents using regex, across a single file or a whole directory",
+        grep_file,
+    );
+    #[cfg(feature = "index")]
+    registry.add(
+        "search_content",
+        "Find lines containing a phrase (ignoring case) in files beneath a directory, best-matching files first; answered from the full-text index when one is configured",
+        search_content,
+    );
+}
+
+fn search_files(server: &McpServer, params: SearchFilesParams) -> Result<CallToolResult> {
+    let results = server.search_files(params)?;
+    CallToolResult::json(&results)
+}
+
+fn recent_files(server: &McpServer, params: RecentFilesParams) -> Result<CallToolResult> {
+    let files = server.recent_files(params)?;
+    CallToolResult::json(&files)
+}
+
+fn grep_file(server: &McpServer, params: GrepFileParams) -> Result<CallToolResult> {
+    let matches = server.grep_file(params)?;
+    CallToolResult::json(&matches)
+}
+
+#[cfg(feature = "index")]
+fn search_content(server: &McpServer, params: SearchContentParams) -> Result<CallToolResult> {
+    info!(path = %params.path, query = %params.query, "Searching indexed contents");
+    let matches = server.state().reader.search_content(&params.path, &params.query, params.max_results)?;
+    info!(path = %params.path, match_count = matches.len(), "Search completed");
+    CallToolResult::json(&matches)
+}
@@ -0,0 +1,51 @@
+//! Tools that report on the server itself
+
+use super::{NoParams, ToolRegistry};
+use crate::error::Result;
+use crate::mcp::McpServer;
+use crate::protocol::{CallToolResult, GetOperationHistoryParams, ServerInfo};
+use crate::session::Session;
+use crate::usage::UsageReport;
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.add(
+        "server_info",
+        "Get server uptime, operation counts and bytes transferred this session, and a summary of the active access policy",
+        server_info,
+    )
+    .returns::<ServerInfo>();
+    registry
+        .add(
+            "get_metrics",
+            "Get server metrics (request counts, errors, latency, bytes transferred) in Prometheus text format",
+            get_metrics,
+        )
+        .offered_when(McpServer::offers_metrics_tool);
+    registry.add_for_session(
+        "get_operation_history",
+        "List the changes this session's tool calls made to files, oldest first, with sizes and optionally diffs",
+        get_operation_history,
+    );
+    registry.add_for_session(
+        "get_session_usage",
+        "Get this session's usage so far: tool calls, denials, rate-limit hits, files read, written, and deleted, and bytes moved",
+        get_session_usage,
+    )
+    .returns::<UsageReport>();
+}
+
+fn server_info(server: &McpServer, _: NoParams) -> Result<CallToolResult> {
+    CallToolResult::json(&server.server_info()?)
+}
+
+fn get_metrics(server: &McpServer, _: NoParams) -> Result<CallToolResult> {
+    Ok(CallToolResult::text(server.metrics().render()))
+}
+
+fn get_operation_history(_: &McpServer, session: &Session, params: GetOperationHistoryParams) -> Result<CallToolResult> {
+    CallToolResult::json(&session.journal().entries(params.limit, params.diffs))
+}
+
+fn get_session_usage(_: &McpServer, session: &Session, _: NoParams) -> Result<CallToolResult> {
+    CallToolResult::json(&session.usage())
+}
@@ -0,0 +1,141 @@
+//! Tools that create, change, and remove files and directories
+
+use super::{NoParams, ToolRegistry};
+use crate::error::Result;
+use crate::line_edits::LineEditSummary;
+use crate::mcp::McpServer;
+use crate::protocol::{
+    AppendFileParams, CallToolResult, CopyFileParams, CreateDirectoryParams, DeleteFileParams, EditLinesParams, MoveFileParams,
+    RemoveDirectoryParams, UpdateStructuredFileParams, WriteFileParams, WriteRangeParams,
+};
+use crate::session::{Progress, Session};
+use crate::structured::StructuredEditSummary;
+use crate::workspaces::WorkspaceSummary;
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.add("write_file", "Write contents to a file", write_file).mutating();
+    registry.add("append_file", "Append content to a file (creates if not exists)", append_file).mutating();
+    registry.add("write_range", "Write bytes at an offset without truncating the rest of the file", write_range).mutating();
+    registry.add(
+        "edit_lines",
+        "Insert, delete, or replace lines by number, optionally checking their current text first",
+        edit_lines,
+    )
+    .returns::<LineEditSummary>()
+    .mutating();
+    registry.add(
+        "update_structured_file",
+        "Set, remove, or append values at JSON pointers in a JSON, TOML, or YAML file, keeping the rest of it as written",
+        update_structured_file,
+    )
+    .returns::<StructuredEditSummary>()
+    .mutating();
+    registry.add("delete_file", "Delete a file", delete_file).mutating();
+    registry.add("move_file", "Move or rename a file or directory", move_file).mutating();
+    registry.add_with_progress("copy_file", "Copy a file, or a directory with recursive", copy_file).mutating();
+    registry.add("create_directory", "Create a new directory", create_directory).mutating();
+    registry.add("remove_directory", "Remove a directory", remove_directory).mutating();
+    registry.add_for_session(
+        "create_temp_workspace",
+        "Create a scratch directory that only this session may use; it is deleted after a while and on shutdown",
+        create_temp_workspace,
+    )
+    .returns::<WorkspaceSummary>()
+    .offered_when(McpServer::offers_workspaces);
+    registry.add_for_session(
+        "begin_transaction",
+        "Stage this session's file changes instead of making them, until commit applies them all or rollback discards them",
+        begin_transaction,
+    );
+    registry.add_for_session(
+        "commit",
+        "Apply the changes staged since begin_transaction, in order; if one fails, the earlier ones are undone where possible",
+        commit,
+    );
+    registry.add_for_session("rollback", "Discard the changes staged since begin_transaction", rollback);
+}
+
+fn write_file(server: &McpServer, params: WriteFileParams) -> Result<CallToolResult> {
+    let result = server.write_file(params)?;
+    if result.unchanged {
+        return Ok(CallToolResult::text(format!("{} already has this content; nothing was written", result.path)));
+    }
+    Ok(CallToolResult::text(format!("Successfully wrote {} bytes to {}", result.bytes_written, result.path)))
+}
+
+fn append_file(server: &McpServer, params: AppendFileParams) -> Result<CallToolResult> {
+    let result = server.append_file(params)?;
+    Ok(CallToolResult::text(format!("Successfully appended {} bytes to {}", result.bytes_written, result.path)))
+}
+
+fn write_range(server: &McpServer, params: WriteRangeParams) -> Result<CallToolResult> {
+    let result = server.write_range(params)?;
+    Ok(CallToolResult::text(format!(
+        "Successfully wrote {} bytes at offset {} to {} (file size {})",
+        result.bytes_written, result.offset, result.path, result.file_size
+    )))
+}
+
+fn edit_lines(server: &McpServer, params: EditLinesParams) -> Result<CallToolResult> {
+    let summary = server.edit_lines(params)?;
+    CallToolResult::json(&summary)
+}
+
+fn update_structured_file(server: &McpServer, params: UpdateStructuredFileParams) -> Result<CallToolResult> {
+    let summary = server.update_structured_file(params)?;
+    CallToolResult::json(&summary)
+}
+
+fn delete_file(server: &McpServer, params: DeleteFileParams) -> Result<CallToolResult> {
+    let message = format!("Successfully deleted {}", params.path);
+    server.delete_file(params)?;
+    Ok(CallToolResult::text(message))
+}
+
+fn move_file(server: &McpServer, params: MoveFileParams) -> Result<CallToolResult> {
+    let message = format!("Successfully moved {} to {}", params.from, params.to);
+    server.move_file(params)?;
+    Ok(CallToolResult::text(message))
+}
+
+fn copy_file(server: &McpServer, progress: &Progress<'_>, params: CopyFileParams) -> Result<CallToolResult> {
+    let (from, to, recursive) = (params.from.clone(), params.to.clone(), params.recursive);
+    let summary = server.copy_with_progress(params, progress)?;
+    if recursive {
+        return Ok(CallToolResult::text(format!(
+            "Successfully copied {} to {} ({} files, {} directories, {} bytes)",
+            from, to, summary.files, summary.directories, summary.bytes
+        )));
+    }
+    Ok(CallToolResult::text(format!("Successfully copied {} to {} ({} bytes)", from, to, summary.bytes)))
+}
+
+fn create_directory(server: &McpServer, params: CreateDirectoryParams) -> Result<CallToolResult> {
+    let message = format!("Successfully created directory {}", params.path);
+    server.create_directory(params)?;
+    Ok(CallToolResult::text(message))
+}
+
+fn remove_directory(server: &McpServer, params: RemoveDirectoryParams) -> Result<CallToolResult> {
+    let message = format!("Successfully removed directory {}", params.path);
+    server.remove_directory(params)?;
+    Ok(CallToolResult::text(message))
+}
+
+fn create_temp_workspace(server: &McpServer, session: &Session, _: NoParams) -> Result<CallToolResult> {
+    CallToolResult::json(&server.create_temp_workspace(session)?)
+}
+
+fn begin_transaction(server: &McpServer, session: &Session, _: NoParams) -> Result<CallToolResult> {
+    server.begin_transaction(session)?;
+    Ok(CallToolResult::text("Transaction started; file changes are staged until commit or rollback"))
+}
+
+fn commit(server: &McpServer, session: &Session, _: NoParams) -> Result<CallToolResult> {
+    CallToolResult::json(&server.commit(session)?)
+}
+
+fn rollback(server: &McpServer, session: &Session, _: NoParams) -> Result<CallToolResult> {
+    let discarded = server.rollback(session)?;
+    Ok(CallToolResult::text(format!("Discarded {} staged change(s)", discarded)))
+}
@@ -0,0 +1,398 @@
+//! Staged changes that are applied in order and undone on failure.
+//!
+//! After `begin_transaction`, a session's calls to tools that modify files
+//! are checked against the access policy and queued instead of run. Reads
+//! in the meantime see the files as they are, not the queued changes.
+//! `commit` first makes sure no queued path changed since it was staged,
+//! then runs the calls in order; if one fails, the files the earlier ones
+//! touched are put back as they were. `rollback` drops the queue.
+//!
+//! This is best effort, not atomic. The calls write the files one by one,
+//! so other clients can see a partly applied transaction, a put-back that
+//! fails leaves it partly applied (and `commit` says so), and a crash
+//! during `commit` leaves it partly applied with nothing to undo it.
+//!
+//! Files are restored from copies taken just before the calls run, so only
+//! changes that such copies can undo are staged: removing a directory or
+//! moving one is refused, and so is a call that leaves its destination to
+//! the tool to derive. Copies are taken through the session's reader and
+//! put back through its writer, so a rollback touches only paths the policy
+//! let the staged calls change.
+
+use crate::access_control::WriteOperation;
+use crate::error::{Denial, FileJackError, Result};
+use crate::file_ops::{content_hash, FileReader, FileWriter};
+use crate::mounts::PATH_ARGUMENTS;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A call queued in a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StagedCall {
+    /// Position in the transaction, from 1
+    pub position: usize,
+    pub tool: String,
+    /// The paths the call names
+    pub paths: Vec<String>,
+}
+
+/// Calls queued by one session, with the state of each path they name as
+/// it was when first staged
+#[derive(Default)]
+pub struct Transaction {
+    calls: Vec<(StagedCall, Value)>,
+    seen: BTreeMap<String, Staged>,
+}
+
+/// A path named by the queued calls
+struct Staged {
+    fingerprint: Fingerprint,
+    /// The first change a call makes to the path, or `None` if the calls
+    /// only read it
+    operation: Option<WriteOperation>,
+}
+
+impl Transaction {
+    /// Queue a call to `tool`, refusing changes the policy does not allow
+    /// and changes a rollback could not undo
+    pub(crate) fn stage(&mut self, reader: &FileReader, writer: &FileWriter, tool: &str, arguments: Value) -> Result<StagedCall> {
+        let path = |key: &str| arguments.get(key).and_then(Value::as_str);
+        if tool == "remove_directory" || (tool == "move_file" && path("from").is_some_and(|from| is_dir(reader, from))) {
+            return Err(FileJackError::InvalidParameters(
+                "Directories cannot be removed or moved inside a transaction".to_string(),
+            ));
+        }
+        if path("from").is_some() && path("to").is_none() {
+            return Err(FileJackError::InvalidParameters(format!(
+                "{} needs an explicit 'to' inside a transaction",
+                tool
+            )));
+        }
+
+        let mut paths = Vec::new();
+        for key in PATH_ARGUMENTS {
+            let Some(path) = path(key) else {
+                continue;
+            };
+            let operation = operation(tool, key);
+            if let Some(operation) = operation {
+                writer.validate_operation(Path::new(path), operation)?;
+            }
+            let fingerprint = Saved::take(reader, Path::new(path))?.fingerprint();
+            let staged = self.seen.entry(path.to_string()).or_insert(Staged { fingerprint, operation });
+            staged.operation = staged.operation.or(operation);
+            paths.push(path.to_string());
+        }
+        let call = StagedCall { position: self.calls.len() + 1, tool: tool.to_string(), paths };
+        self.calls.push((call.clone(), arguments));
+        Ok(call)
+    }
+
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Every path the queued calls name
+    pub(crate) fn paths(&self) -> impl Iterator<Item = &str> {
+        self.seen.keys().map(String::as_str)
+    }
+
+    /// Copies of every path the queued calls name, after checking that none
+    /// of them changed since it was staged
+    pub(crate) fn snapshot(&self, reader: &FileReader) -> Result<Snapshot> {
+        let mut saved = Vec::with_capacity(self.seen.len());
+        for (path, staged) in &self.seen {
+            let current = Saved::take(reader, Path::new(path))?;
+            if current.fingerprint() != staged.fingerprint {
+                return Err(match &current {
+                    Saved::File(bytes) => FileJackError::Conflict {
+                        path: path.clone(),
+                        hash: content_hash(bytes),
+                        modified: None,
+                    },
+                    _ => FileJackError::InvalidPath(format!("{} changed since it was staged", path)),
+                });
+            }
+            // Paths the calls only read are never put back
+            if let Some(operation) = staged.operation {
+                saved.push((PathBuf::from(path), operation, current));
+            }
+        }
+        Ok(Snapshot { saved })
+    }
+
+    /// The queued calls, in order
+    pub(crate) fn into_calls(self) -> Vec<(StagedCall, Value)> {
+        self.calls
+    }
+}
+
+/// The change a call to `tool` makes to the path given as `key`, or `None`
+/// if it only reads it
+fn operation(tool: &str, key: &str) -> Option<WriteOperation> {
+    match (tool, key) {
+        ("append_file", "path") => Some(WriteOperation::Append),
+        ("delete_file", "path") | ("move_file", "from") => Some(WriteOperation::Delete),
+        (_, "from") => None,
+        _ => Some(WriteOperation::Write),
+    }
+}
+
+/// How a path looked, enough to tell whether it changed
+#[derive(Debug, PartialEq, Eq)]
+enum Fingerprint {
+    Missing,
+    Directory,
+    File(String),
+}
+
+/// How a path looked, enough to put it back
+enum Saved {
+    /// The path did not exist, nor did these ancestors, deepest first
+    Missing(Vec<PathBuf>),
+    Directory,
+    File(Vec<u8>),
+}
+
+impl Saved {
+    /// Copy `path`, which the reader's policy must allow reading
+    fn take(reader: &FileReader, path: &Path) -> Result<Self> {
+        match reader.get_metadata(path) {
+            Ok(metadata) if metadata.is_dir => Ok(Saved::Directory),
+            Ok(metadata) if metadata.is_file => Ok(Saved::File(reader.read_to_bytes(path)?)),
+            Ok(_) => Err(FileJackError::InvalidPath(format!("{} is not a regular file", path.display()))),
+            Err(FileJackError::FileNotFound(_)) => {
+                let fs = reader.file_system();
+                let missing = path.ancestors()
+                    .skip(1)
+                    .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !fs.exists(ancestor))
+                    .map(Path::to_path_buf)
+                    .collect();
+                Ok(Saved::Missing(missing))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn fingerprint(&self) -> Fingerprint {
+        match self {
+            Saved::Missing(_) => Fingerprint::Missing,
+            Saved::Directory => Fingerprint::Directory,
+            Saved::File(bytes) => Fingerprint::File(content_hash(bytes)),
+        }
+    }
+
+    /// Put `path` back through `writer`. Undoing `operation` writes nothing
+    /// the policy did not let that change write, so the path is validated
+    /// for it.
+    fn restore(&self, writer: &FileWriter, path: &Path, operation: WriteOperation) -> Result<()> {
+        let fs = writer.file_system();
+        let path = writer.validate_operation(path, operation)?;
+        writer.invalidate(&path);
+        match self {
+            Saved::Missing(ancestors) => {
+                match fs.symlink_metadata(&path) {
+                    Ok(metadata) if metadata.is_dir() => fs.remove_dir(&path, true)?,
+                    Ok(_) => fs.remove_file(&path)?,
+                    Err(_) => {}
+                }
+                // Directories created on the way; left alone if something else is now in them
+                for ancestor in ancestors {
+                    if let Ok(ancestor) = writer.validate_operation(ancestor, operation) {
+                        let _ = fs.remove_dir(&ancestor, false);
+                    }
+                }
+                Ok(())
+            }
+            Saved::Directory if fs.exists(&path) => Ok(()),
+            Saved::Directory => Ok(fs.create_dir(&path, true)?),
+            Saved::File(bytes) => {
+                if fs.symlink_metadata(&path).is_ok_and(|metadata| metadata.is_dir()) {
+                    fs.remove_dir(&path, true)?;
+                }
+                Ok(fs.write(&path, bytes, true)?)
+            }
+        }
+    }
+}
+
+/// Copies of the paths a transaction touches, taken before it is applied
+pub(crate) struct Snapshot {
+    saved: Vec<(PathBuf, WriteOperation, Saved)>,
+}
+
+impl Snapshot {
+    /// Put every path back as it was. Carries on past failures; if any path
+    /// could not be restored, returns the first error, naming every such path.
+    pub(crate) fn restore(&self, writer: &FileWriter) -> Result<()> {
+        let mut first = None;
+        let mut unrestored = Vec::new();
+        // Deepest paths first, so a restored parent is not removed again
+        for (path, operation, saved) in self.saved.iter().rev() {
+            if let Err(e) = saved.restore(writer, path, *operation) {
+                first.get_or_insert(e);
+                unrestored.push(path.display().to_string());
+            }
+        }
+        match first {
+            Some(e) => Err(rolled_back(e, &format!("Could not restore {}", unrestored.join(", ")))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `error`, with what became of the transaction put in front of its
+/// message. The variant is kept, so a denial still names its rule.
+pub(crate) fn rolled_back(error: FileJackError, context: &str) -> FileJackError {
+    let prefixed = |message: String| format!("{}: {}", context, message);
+    match error {
+        FileJackError::Io(e) => FileJackError::Io(io::Error::new(e.kind(), prefixed(e.to_string()))),
+        FileJackError::FileNotFound(message) => FileJackError::FileNotFound(prefixed(message)),
+        FileJackError::PermissionDenied(Denial { message, rule }) => {
+            FileJackError::PermissionDenied(Denial { message: prefixed(message), rule })
+        }
+        FileJackError::InvalidPath(message) => FileJackError::InvalidPath(prefixed(message)),
+        FileJackError::ProtocolError(message) => FileJackError::ProtocolError(prefixed(message)),
+        FileJackError::InvalidParameters(message) => FileJackError::InvalidParameters(prefixed(message)),
+        FileJackError::ConfigError(message) => FileJackError::ConfigError(prefixed(message)),
+        FileJackError::Transport(message) => FileJackError::Transport(prefixed(message)),
+        // Their messages are fixed, and clients read their fields
+        error => error,
+    }
+}
+
+fn is_dir(reader: &FileReader, path: &str) -> bool {
+    reader.get_metadata(path).is_ok_and(|metadata| metadata.is_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access_control::AccessPolicy;
+    use crate::error::RuleCategory;
+    use serde_json::json;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn reader_and_writer(policy: AccessPolicy) -> (FileReader, FileWriter) {
+        (FileReader::new(policy.clone()), FileWriter::new(policy, true))
+    }
+
+    #[test]
+    fn test_snapshot_restores_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let existing = temp_dir.path().join("a.txt");
+        let created = temp_dir.path().join("new/dir/b.txt");
+        fs::write(&existing, "before").unwrap();
+        let (reader, writer) = reader_and_writer(AccessPolicy::permissive());
+
+        let mut transaction = Transaction::default();
+        transaction.stage(&reader, &writer, "write_file", json!({"path": existing, "content": "after"})).unwrap();
+        let call = transaction.stage(&reader, &writer, "copy_file", json!({"from": existing, "to": created})).unwrap();
+        assert_eq!(call.position, 2);
+        assert_eq!(transaction.len(), 2);
+
+        let snapshot = transaction.snapshot(&reader).unwrap();
+        fs::write(&existing, "after").unwrap();
+        fs::create_dir_all(created.parent().unwrap()).unwrap();
+        fs::write(&created, "after").unwrap();
+        snapshot.restore(&writer).unwrap();
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "before");
+        assert!(!temp_dir.path().join("new").exists());
+
+        // A path changed by someone else after staging is a conflict
+        fs::write(&existing, "elsewhere").unwrap();
+        assert!(matches!(transaction.snapshot(&reader), Err(FileJackError::Conflict { .. })));
+    }
+
+    #[test]
+    fn test_snapshot_restore_fails_when_a_path_cannot_be_put_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("dir");
+        let file = dir.join("a.txt");
+        fs::create_dir(&dir).unwrap();
+        fs::write(&file, "before").unwrap();
+        let (reader, writer) = reader_and_writer(AccessPolicy::permissive());
+
+        let mut transaction = Transaction::default();
+        transaction.stage(&reader, &writer, "write_file", json!({"path": file, "content": "after"})).unwrap();
+        let snapshot = transaction.snapshot(&reader).unwrap();
+
+        // The file's directory is now a file, so it cannot be written back
+        fs::remove_dir_all(&dir).unwrap();
+        fs::write(&dir, "in the way").unwrap();
+        let error = snapshot.restore(&writer).unwrap_err();
+        assert!(error.to_string().contains(&format!("Could not restore {}", file.display())), "{}", error);
+    }
+
+    #[test]
+    fn test_stage_refuses_what_cannot_be_undone() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+        let (reader, writer) = reader_and_writer(AccessPolicy::permissive());
+        let mut transaction = Transaction::default();
+        assert!(transaction.stage(&reader, &writer, "remove_directory", json!({"path": dir})).is_err());
+        assert!(transaction.stage(&reader, &writer, "move_file", json!({"from": dir, "to": temp_dir.path().join("x")})).is_err());
+        assert!(transaction.stage(&reader, &writer, "compress_file", json!({"from": dir.join("a.txt")})).is_err());
+        assert!(transaction.is_empty());
+    }
+
+    #[test]
+    fn test_stage_refuses_what_the_policy_denies() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let outside = temp_dir.path().join("outside.txt");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("notes.txt"), "mine").unwrap();
+        fs::write(&outside, "theirs").unwrap();
+        let mut policy = AccessPolicy::restricted(root.clone());
+        policy.allow_hidden_files = true;
+        policy.protected_paths = vec!["notes.txt".to_string()];
+        let (reader, writer) = reader_and_writer(policy);
+
+        let mut transaction = Transaction::default();
+        for (tool, arguments) in [
+            ("write_file", json!({"path": outside, "content": "x"})),
+            ("copy_file", json!({"from": outside, "to": root.join("copy.txt")})),
+            ("write_file", json!({"path": root.join("notes.txt"), "content": "x"})),
+        ] {
+            assert!(matches!(
+                transaction.stage(&reader, &writer, tool, arguments),
+                Err(FileJackError::PermissionDenied(_))
+            ), "{}", tool);
+        }
+        assert!(transaction.is_empty());
+
+        // Copying from a file that cannot be written only reads it, and a
+        // rollback leaves it alone
+        transaction.stage(&reader, &writer, "copy_file", json!({"from": root.join("notes.txt"), "to": root.join("copy.txt")})).unwrap();
+        let snapshot = transaction.snapshot(&reader).unwrap();
+        fs::write(root.join("copy.txt"), "mine").unwrap();
+        snapshot.restore(&writer).unwrap();
+        assert!(!root.join("copy.txt").exists());
+        assert_eq!(fs::read_to_string(&outside).unwrap(), "theirs");
+    }
+
+    #[test]
+    fn test_rolled_back_keeps_the_variant() {
+        let denial = FileJackError::PermissionDenied(Denial::by(
+            crate::error::PolicyRule::new("read_only", RuleCategory::ReadOnly),
+            "Write operations are not allowed",
+        ));
+        match rolled_back(denial, "Call 1 (write_file) failed") {
+            FileJackError::PermissionDenied(Denial { message, rule: Some(rule) }) => {
+                assert_eq!(message, "Call 1 (write_file) failed: Write operations are not allowed");
+                assert_eq!(rule.id, "read_only");
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use crate::error::{FileJackError, Result};
+use crate::logging::loggable_message;
+use crate::mcp::McpServer;
+use crate::session::Session;
+use std::io::{BufRead, Read, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use tracing::debug;
+
+/// Header that starts every message in Content-Length framing
+const CONTENT_LENGTH: &str = "content-length";
+
+/// How messages are delimited on a byte stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Framing {
+    /// Decide from the first message, then reply the same way
+    #[default]
+    Auto,
+    /// One JSON text per line
+    Newline,
+    /// LSP-style `Content-Length: N` headers, a blank line, then N bytes
+    ContentLength,
+}
+
+impl std::str::FromStr for Framing {
+    type Err = FileJackError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(Framing::Auto),
+            "newline" | "ndjson" => Ok(Framing::Newline),
+            "content-length" | "lsp" => Ok(Framing::ContentLength),
+            other => Err(FileJackError::InvalidParameters(format!(
+                "Unknown framing '{}', expected auto, newline, or content-length", other
+            ))),
+        }
+    }
+}
+
+/// Read one message from `reader`. When `framing` is [`Framing::Auto`] it
+/// is replaced by the framing the message arrived in. Returns `None` at end
+/// of input.
+pub fn read_frame<R: BufRead>(reader: &mut R, framing: &mut Framing) -> Result<Option<String>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    let is_header = header_value(&line, CONTENT_LENGTH).is_some();
+    match *framing {
+        Framing::Auto if is_header => *framing = Framing::ContentLength,
+        Framing::Auto => *framing = Framing::Newline,
+        _ => {}
+    }
+    if *framing == Framing::Newline {
+        let trimmed = line.trim_end_matches(['\r', '\n']).len();
+        line.truncate(trimmed);
+        return Ok(Some(line));
+    }
+
+    // Headers run to the first blank line; only Content-Length matters
+    let mut length = None;
+    loop {
+        if let Some(value) = header_value(&line, CONTENT_LENGTH) {
+            length = Some(value.parse::<u64>().map_err(|_| {
+                FileJackError::InvalidParameters(format!("Invalid Content-Length header '{}'", value))
+            })?);
+        }
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(truncated());
+        }
+        if line.trim().is_empty() {
+            break;
+        }
+    }
+    let length = length.ok_or_else(|| {
+        FileJackError::InvalidParameters("Message headers lack Content-Length".to_string())
+    })?;
+
+    // Read incrementally rather than trusting the header with an allocation
+    let mut body = Vec::new();
+    reader.take(length).read_to_end(&mut body)?;
+    if body.len() as u64 != length {
+        return Err(truncated());
+    }
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|_| FileJackError::InvalidParameters("Message body is not valid UTF-8".to_string()))
+}
+
+/// Write one message to `writer` in `framing` (newline-delimited unless
+/// Content-Length was chosen or detected) and flush it
+pub fn write_frame<W: Write>(writer: &mut W, framing: Framing, message: &str) -> std::io::Result<()> {
+    match framing {
+        Framing::ContentLength => write!(writer, "Content-Length: {}\r\n\r\n{}", message.len(), message)?,
+        Framing::Auto | Framing::Newline => writeln!(writer, "{}", message)?,
+    }
+    writer.flush()
+}
+
+/// Value of header `name` if `line` is that header (names are case-insensitive)
+fn header_value<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let (key, value) = line.split_once(':')?;
+    key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+}
+
+fn truncated() -> FileJackError {
+    FileJackError::Io(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Input ended inside a message"))
+}
+
+/// A bidirectional channel carrying one JSON-RPC message at a time.
+///
+/// Implement this to serve FileJack over a channel it does not support out
+/// of the box, then hand it to [`serve`]. Framing is the transport's job:
+/// messages passed in and out are complete JSON texts without delimiters.
+pub trait Transport {
+    /// Wait for the next message from the client; `None` means the client
+    /// has gone away and serving should stop
+    fn read_message(&mut self) -> Result<Option<String>>;
+
+    /// Send one message to the client
+    fn write_message(&mut self, message: &str) -> Result<()>;
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn read_message(&mut self) -> Result<Option<String>> {
+        (**self).read_message()
+    }
+
+    fn write_message(&mut self, message: &str) -> Result<()> {
+        (**self).write_message(message)
+    }
+}
+
+/// Answer requests from `transport` in the server's default session until
+/// the client goes away
+pub fn serve<T: Transport + ?Sized>(transport: &mut T, server: &McpServer) -> Result<()> {
+    serve_session(transport, server, server.default_session())
+}
+
+/// Answer requests from `transport` on behalf of `session` until the client
+/// goes away. Notifications get no reply. Returns the first error from the
+/// transport, as a [`FileJackError::Transport`].
+pub fn serve_session<T: Transport + ?Sized>(transport: &mut T, server: &McpServer, session: &Session) -> Result<()> {
+    while let Some(message) = transport.read_message().map_err(FileJackError::into_transport)? {
+        if message.trim().is_empty() {
+            continue;
+        }
+        debug!(session = session.id(), "Received request: {}", loggable_message(&message, server.logs_bodies()));
+
+        let response = server.process_session_request(session, &message);
+        if response.is_empty() {
+            continue;
+        }
+        debug!(session = session.id(), "Sending response: {}", loggable_message(&response, server.logs_bodies()));
+        transport.write_message(&response).map_err(FileJackError::into_transport)?;
+    }
+    Ok(())
+}
+
+/// JSON-RPC over any reader and writer, as used on stdio. The framing is
+/// detected from the first message unless set with
+/// [`StreamTransport::with_framing`].
+pub struct StreamTransport<R, W> {
+    reader: R,
+    writer: W,
+    framing: Framing,
+}
+
+impl<R: BufRead, W: Write> StreamTransport<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Self { reader, writer, framing: Framing::Auto }
+    }
+
+    /// Use `framing` instead of detecting it
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// The framing in use; still [`Framing::Auto`] before the first message
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Give back the reader and writer
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+}
+
+impl<R: BufRead, W: Write> Transport for StreamTransport<R, W> {
+    fn read_message(&mut self) -> Result<Option<String>> {
+        read_frame(&mut self.reader, &mut self.framing)
+    }
+
+    fn write_message(&mut self, message: &str) -> Result<()> {
+        Ok(write_frame(&mut self.writer, self.framing, message)?)
+    }
+}
+
+/// One end of an in-process message channel, e.g. for tests or for
+/// bridging from another runtime. Messages written on one end are read on
+/// the other.
+pub struct ChannelTransport {
+    incoming: Receiver<String>,
+    outgoing: Sender<String>,
+}
+
+impl ChannelTransport {
+    /// Two connected ends: give one to [`serve`] and drive the other as the
+    /// client
+    pub fn pair() -> (Self, Self) {
+        let (to_server, from_client) = mpsc::channel();
+        let (to_client, from_server) = mpsc::channel();
+        (
+            Self { incoming: from_client, outgoing: to_client },
+            Self { incoming: from_server, outgoing: to_server },
+        )
+    }
+}
+
+impl Transport for ChannelTransport {
+    fn read_message(&mut self) -> Result<Option<String>> {
+        Ok(self.incoming.recv().ok())
+    }
+
+    fn write_message(&mut self, message: &str) -> Result<()> {
+        self.outgoing.send(message.to_string()).map_err(|_| {
+            FileJackError::Io(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Channel transport closed"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access_control::AccessPolicy;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_stream_transport_newline() {
+        let server = McpServer::new(AccessPolicy::permissive());
+        let input = concat!(
+            "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"initialize\"}\r\n",
+            "\n",
+            "{\"jsonrpc\":\"2.0\",\"method\":\"notifications/initialized\"}\n",
+            "{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"tools/list\"}",
+        );
+        let mut transport = StreamTransport::new(Cursor::new(input), Vec::new());
+        serve(&mut transport, &server).unwrap();
+        assert_eq!(transport.framing(), Framing::Newline);
+
+        let (_, output) = transport.into_inner();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2, "notifications get no reply: {}", output);
+        assert!(lines[0].contains("serverInfo"));
+        assert!(lines[1].contains("read_file"));
+        assert!(server.default_session().is_initialized());
+    }
+
+    #[test]
+    fn test_stream_transport_content_length() {
+        let server = McpServer::new(AccessPolicy::permissive());
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#;
+        let input = format!(
+            "Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n{}content-length: {}\r\n\r\n{}",
+            request.len(), request, request.len(), request
+        );
+        let mut transport = StreamTransport::new(Cursor::new(input), Vec::new());
+        serve(&mut transport, &server).unwrap();
+        assert_eq!(transport.framing(), Framing::ContentLength);
+
+        let (_, output) = transport.into_inner();
+        let mut output = Cursor::new(output);
+        let mut framing = Framing::ContentLength;
+        for _ in 0..2 {
+            let reply = read_frame(&mut output, &mut framing).unwrap().unwrap();
+            assert!(reply.contains("read_file"));
+        }
+        assert!(read_frame(&mut output, &mut framing).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_content_length_errors() {
+        let mut framing = Framing::ContentLength;
+        let err = read_frame(&mut Cursor::new("Content-Length: 10\r\n\r\n{}"), &mut framing).unwrap_err();
+        assert!(matches!(err, FileJackError::Io(_)));
+        let err = read_frame(&mut Cursor::new("Content-Length: ten\r\n\r\n{}"), &mut framing).unwrap_err();
+        assert!(err.to_string().contains("Invalid Content-Length"));
+        let err = read_frame(&mut Cursor::new("Content-Type: json\r\n\r\n{}"), &mut framing).unwrap_err();
+        assert!(err.to_string().contains("lack Content-Length"));
+        assert_eq!("LSP".parse::<Framing>().unwrap(), Framing::ContentLength);
+    }
+
+    #[test]
+    fn test_channel_transport() {
+        let server = std::sync::Arc::new(McpServer::new(AccessPolicy::permissive()));
+        let (mut server_end, mut client) = ChannelTransport::pair();
+        let serving = {
+            let server = std::sync::Arc::clone(&server);
+            std::thread::spawn(move || serve(&mut server_end, &server))
+        };
+
+        client.write_message(r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#).unwrap();
+        assert!(client.read_message().unwrap().unwrap().contains("read_file"));
+        drop(client);
+        serving.join().unwrap().unwrap();
+    }
+}
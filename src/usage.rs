@@ -0,0 +1,273 @@
+//! What each session did, for review once it is over.
+//!
+//! Every session counts its tool calls, the files they read, wrote, and
+//! deleted, the bytes moved, and the calls refused by a permission check or
+//! the rate limiter. `get_session_usage` returns the counts so far. When a
+//! connection closes or the server shuts down, each session's report is
+//! written to stderr and, with `server.usage_report` set, appended to that
+//! file as one line of JSON.
+
+use crate::error::{FileJackError, Result};
+use crate::journal::Touched;
+use crate::mounts::PATH_ARGUMENTS;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+thread_local! {
+    /// Usage of the session whose tool call this thread is running
+    static CURRENT: RefCell<Option<Arc<SessionUsage>>> = const { RefCell::new(None) };
+}
+
+/// Counters for one session
+#[derive(Debug)]
+pub struct SessionUsage {
+    started: SystemTime,
+    clock: Instant,
+    calls: AtomicU64,
+    errors: AtomicU64,
+    denials: AtomicU64,
+    rate_limited: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    files: Mutex<Files>,
+    reported: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+struct Files {
+    read: BTreeSet<String>,
+    written: BTreeSet<String>,
+    deleted: BTreeSet<String>,
+}
+
+/// A session's usage, as `get_session_usage` returns it and as it is
+/// reported when the session ends
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct UsageReport {
+    pub session: String,
+    /// Name the client gave in `initialize`
+    pub client: Option<String>,
+    /// Seconds since the Unix epoch when the session started
+    pub started: u64,
+    pub duration_seconds: u64,
+    /// Tool calls, including failed ones
+    pub calls: u64,
+    pub errors: u64,
+    /// Calls refused by the access policy, a lock, or a claim
+    pub denials: u64,
+    pub rate_limited: u64,
+    /// Each path given to a tool that only reads, and each source of a copy
+    /// or move, sorted
+    pub files_read: Vec<String>,
+    /// Each path a tool that modifies files left in place
+    pub files_written: Vec<String>,
+    /// Each path a call removed
+    pub files_deleted: Vec<String>,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+impl Default for SessionUsage {
+    fn default() -> Self {
+        Self {
+            started: SystemTime::now(),
+            clock: Instant::now(),
+            calls: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            denials: AtomicU64::new(0),
+            rate_limited: AtomicU64::new(0),
+            bytes_read: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+            files: Mutex::new(Files::default()),
+            reported: AtomicBool::new(false),
+        }
+    }
+}
+
+impl SessionUsage {
+    /// Count a tool call that ended with `error`, or succeeded without one
+    pub(crate) fn record_call(&self, error: Option<&FileJackError>) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let Some(error) = error else {
+            return;
+        };
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        match error {
+            FileJackError::PermissionDenied(_) => self.denials.fetch_add(1, Ordering::Relaxed),
+            FileJackError::RateLimited { .. } => self.rate_limited.fetch_add(1, Ordering::Relaxed),
+            _ => 0,
+        };
+    }
+
+    /// Count `paths`, given to a successful call that only reads
+    pub(crate) fn record_reads(&self, paths: Vec<String>) {
+        self.files().read.extend(paths);
+    }
+
+    /// Count the paths of a successful call that modified files: those it
+    /// removed as deleted, sources it left alone as read, and the rest as
+    /// written
+    pub(crate) fn record_changes(&self, touched: &[Touched]) {
+        let mut files = self.files();
+        for path in touched {
+            let set = match path {
+                Touched { existed: true, exists: false, .. } => &mut files.deleted,
+                Touched { argument: "from", .. } => &mut files.read,
+                Touched { exists: true, .. } => &mut files.written,
+                _ => continue,
+            };
+            set.insert(path.path.clone());
+        }
+    }
+
+    fn files(&self) -> std::sync::MutexGuard<'_, Files> {
+        self.files.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The counts so far for the session `session`
+    pub(crate) fn report(&self, session: &str, client: Option<String>) -> UsageReport {
+        let files = self.files();
+        UsageReport {
+            session: session.to_string(),
+            client,
+            started: self.started.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            duration_seconds: self.clock.elapsed().as_secs(),
+            calls: self.calls.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            denials: self.denials.load(Ordering::Relaxed),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            files_read: files.read.iter().cloned().collect(),
+            files_written: files.written.iter().cloned().collect(),
+            files_deleted: files.deleted.iter().cloned().collect(),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Note that the final report was made; returns false if it already was
+    pub(crate) fn finish(&self) -> bool {
+        !self.reported.swap(true, Ordering::Relaxed)
+    }
+}
+
+impl UsageReport {
+    /// Whether the session made any calls worth reporting
+    pub fn is_empty(&self) -> bool {
+        self.calls == 0
+    }
+
+    /// Append this report to `path` as one line of JSON, creating the file
+    /// if missing
+    pub fn append_to(&self, path: &Path) -> Result<()> {
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(&line)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for UsageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Session {}", self.session)?;
+        if let Some(client) = &self.client {
+            write!(f, " ({})", client)?;
+        }
+        write!(
+            f,
+            ": {} calls in {}s, {} failed, {} denied, {} rate limited; {} files read, {} written, {} deleted; {} bytes read, {} written",
+            self.calls,
+            self.duration_seconds,
+            self.errors,
+            self.denials,
+            self.rate_limited,
+            self.files_read.len(),
+            self.files_written.len(),
+            self.files_deleted.len(),
+            self.bytes_read,
+            self.bytes_written,
+        )
+    }
+}
+
+/// The paths named in `arguments`
+pub(crate) fn paths_in(arguments: &Value) -> Vec<String> {
+    PATH_ARGUMENTS.iter()
+        .filter_map(|key| arguments.get(key).and_then(Value::as_str))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Run `f` with the bytes it counts in [`crate::Metrics`] also counted for
+/// `usage`
+pub(crate) fn attribute<T>(usage: &Arc<SessionUsage>, f: impl FnOnce() -> T) -> T {
+    /// Puts back the previous session, even if `f` panics
+    struct Restore(Option<Arc<SessionUsage>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+        }
+    }
+
+    let _restore = Restore(CURRENT.with(|current| current.replace(Some(Arc::clone(usage)))));
+    f()
+}
+
+/// Count bytes read for the session whose call this thread is running
+pub(crate) fn add_bytes_read(bytes: u64) {
+    with_current(|usage| usage.bytes_read.fetch_add(bytes, Ordering::Relaxed));
+}
+
+/// Count bytes written for the session whose call this thread is running
+pub(crate) fn add_bytes_written(bytes: u64) {
+    with_current(|usage| usage.bytes_written.fetch_add(bytes, Ordering::Relaxed));
+}
+
+fn with_current(f: impl FnOnce(&SessionUsage) -> u64) {
+    CURRENT.with(|current| {
+        if let Some(usage) = current.borrow().as_ref() {
+            f(usage);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_counts() {
+        let usage = Arc::new(SessionUsage::default());
+        usage.record_call(None);
+        usage.record_call(Some(&FileJackError::PermissionDenied("no".into())));
+        usage.record_call(Some(&FileJackError::RateLimited { retry_after_ms: 1 }));
+        usage.record_reads(paths_in(&serde_json::json!({"path": "/a.txt", "pattern": "*.md"})));
+        let touched = |argument, path: &str, existed, exists| Touched { argument, path: path.to_string(), existed, exists };
+        usage.record_changes(&[touched("from", "/a.txt", true, true), touched("to", "/b.txt", false, true)]);
+        usage.record_changes(&[touched("path", "/c.txt", true, false)]);
+        attribute(&usage, || crate::Metrics::new().add_bytes_read(5));
+        add_bytes_written(7);
+
+        let report = usage.report("s1", Some("inspector".to_string()));
+        assert_eq!((report.calls, report.errors, report.denials, report.rate_limited), (3, 2, 1, 1));
+        assert_eq!(report.files_read, ["/a.txt"]);
+        assert_eq!(report.files_written, ["/b.txt"]);
+        assert_eq!(report.files_deleted, ["/c.txt"]);
+        // Bytes outside the session's calls are not its own
+        assert_eq!((report.bytes_read, report.bytes_written), (5, 0));
+        assert!(report.to_string().starts_with("Session s1 (inspector): 3 calls"));
+
+        assert!(usage.finish());
+        assert!(!usage.finish());
+    }
+}
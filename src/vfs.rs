@@ -0,0 +1,1195 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+/// Counter used to give concurrent temp files distinct names
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Name of the file [`StdFileSystem::lock_directory`] locks in a directory
+pub const DIRECTORY_LOCK_FILE_NAME: &str = ".filejack.lock";
+
+/// What a path refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Dir,
+    Symlink,
+    /// Sockets, devices, and the like
+    Other,
+}
+
+impl From<fs::FileType> for FileKind {
+    fn from(file_type: fs::FileType) -> Self {
+        if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_dir() {
+            FileKind::Dir
+        } else if file_type.is_file() {
+            FileKind::File
+        } else {
+            FileKind::Other
+        }
+    }
+}
+
+/// Metadata of a file or directory, as reported by a [`FileSystem`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub kind: FileKind,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    pub readonly: bool,
+    /// Whether the file system marks the entry hidden, apart from its name
+    pub hidden: bool,
+    /// User and group ids of the owner, and the permission bits, on file
+    /// systems with Unix ownership
+    pub owner: Option<u32>,
+    pub group: Option<u32>,
+    pub mode: Option<u32>,
+}
+
+impl Metadata {
+    pub fn is_file(&self) -> bool {
+        self.kind == FileKind::File
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Dir
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.kind == FileKind::Symlink
+    }
+}
+
+impl From<fs::Metadata> for Metadata {
+    fn from(metadata: fs::Metadata) -> Self {
+        let (owner, group, mode) = match unix_ownership(&metadata) {
+            Some((owner, group, mode)) => (Some(owner), Some(group), Some(mode)),
+            None => (None, None, None),
+        };
+        Self {
+            kind: metadata.file_type().into(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            created: metadata.created().ok(),
+            readonly: metadata.permissions().readonly(),
+            hidden: hidden_flag(&metadata),
+            owner,
+            group,
+            mode,
+        }
+    }
+}
+
+/// Owner, group, and permission bits on Unix
+fn unix_ownership(metadata: &fs::Metadata) -> Option<(u32, u32, u32)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some((metadata.uid(), metadata.gid(), metadata.mode() & 0o7777))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// `FILE_ATTRIBUTE_HIDDEN` on Windows and `UF_HIDDEN` on macOS; other hosts
+/// have no hidden flag
+fn hidden_flag(metadata: &fs::Metadata) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+    }
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::macos::fs::MetadataExt;
+        const UF_HIDDEN: u32 = 0x8000;
+        metadata.st_flags() & UF_HIDDEN != 0
+    }
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+        let _ = metadata;
+        false
+    }
+}
+
+/// An entry found while listing or walking a directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub path: PathBuf,
+    /// Kind of the entry; symlinks are reported as their target when the
+    /// walk follows links
+    pub kind: FileKind,
+}
+
+/// How [`FileSystem::walk`] traverses a tree
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    /// Levels below the root to visit; 1 lists only the root's entries
+    pub max_depth: usize,
+    pub follow_links: bool,
+    /// Leave out entries whose names start with `.`, and everything beneath
+    /// such directories
+    pub skip_hidden: bool,
+    /// Pool to walk with, where supported, instead of the global rayon pool
+    pub pool: Option<Arc<rayon::ThreadPool>>,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self { max_depth: usize::MAX, follow_links: false, skip_hidden: false, pool: None }
+    }
+}
+
+/// An open file, read from its current position
+pub trait FileHandle: Read + Seek + Send {
+    fn metadata(&self) -> io::Result<Metadata>;
+
+    /// The operating system file behind the handle, if any, so large reads
+    /// can be memory-mapped
+    fn as_file(&self) -> Option<&File> {
+        None
+    }
+}
+
+impl FileHandle for File {
+    fn metadata(&self) -> io::Result<Metadata> {
+        File::metadata(self).map(Metadata::from)
+    }
+
+    fn as_file(&self) -> Option<&File> {
+        Some(self)
+    }
+}
+
+/// Storage that [`FileReader`](crate::FileReader) and
+/// [`FileWriter`](crate::FileWriter) operate on.
+///
+/// Policy checks run against the same file system, so an implementation
+/// only has to store and retrieve files. [`StdFileSystem`] is the host's
+/// file system; [`MemoryFileSystem`] keeps everything in memory for tests.
+pub trait FileSystem: Send + Sync + std::fmt::Debug {
+    /// Open a file for reading
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileHandle>>;
+
+    /// Open a FIFO, device, or other special file for reading without
+    /// waiting for a writer; reads that would wait fail with `WouldBlock`
+    fn open_special(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+        self.open(path)
+    }
+
+    /// Read a whole file
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.open(path)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Replace a file's contents, creating it if missing. Readers should
+    /// never observe a partial write; `sync` asks for the data to be durable
+    /// before returning.
+    fn write(&self, path: &Path, contents: &[u8], sync: bool) -> io::Result<()>;
+
+    /// Truncate and rewrite a file without replacing it
+    fn write_in_place(&self, path: &Path, contents: &[u8], sync: bool) -> io::Result<()> {
+        self.write(path, contents, sync)
+    }
+
+    /// Write at `offset`, creating the file if missing and zero-filling any
+    /// gap; returns the resulting size
+    fn write_at(&self, path: &Path, offset: u64, contents: &[u8], sync: bool) -> io::Result<u64>;
+
+    /// Append to a file, creating it if missing
+    fn append(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    /// Metadata of `path`, following symlinks
+    fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+    /// Metadata of `path` itself, even if it is a symlink
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.metadata(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    /// Absolute form of an existing path with links and `..` resolved
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Entries directly inside a directory, in no particular order
+    fn list(&self, path: &Path) -> io::Result<Vec<DirEntry>>;
+
+    /// Entries beneath `root` (excluding the root itself), sorted by path.
+    /// Entries that cannot be read are skipped. File systems with symlinks
+    /// should override this to guard against link cycles.
+    fn walk(&self, root: &Path, options: &WalkOptions) -> io::Result<Vec<DirEntry>> {
+        let mut entries = Vec::new();
+        let mut pending = vec![(root.to_path_buf(), 1)];
+        while let Some((dir, depth)) = pending.pop() {
+            let Ok(listed) = self.list(&dir) else {
+                continue;
+            };
+            for mut entry in listed {
+                if options.skip_hidden && is_hidden(&entry.path) {
+                    continue;
+                }
+                if options.follow_links && entry.kind == FileKind::Symlink {
+                    entry.kind = self.metadata(&entry.path).map(|m| m.kind).unwrap_or(FileKind::Symlink);
+                }
+                if entry.kind == FileKind::Dir && depth < options.max_depth {
+                    pending.push((entry.path.clone(), depth + 1));
+                }
+                entries.push(entry);
+            }
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+
+    /// Create a directory; `recursive` also creates missing parents and
+    /// accepts a directory that already exists
+    fn create_dir(&self, path: &Path, recursive: bool) -> io::Result<()>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Remove a directory, which must be empty unless `recursive`
+    fn remove_dir(&self, path: &Path, recursive: bool) -> io::Result<()>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Copy a file; returns the number of bytes copied
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        let contents = self.read(from)?;
+        self.write(to, &contents, false)?;
+        Ok(contents.len() as u64)
+    }
+
+    /// Give `to` the permissions and modification time of `from`, where the
+    /// file system keeps them
+    fn copy_attributes(&self, _from: &Path, _to: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// The archive `path` lies in, or is, for file systems that present
+    /// archives as directories
+    fn containing_archive(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+
+    /// Names and values of the extended attributes of `path`, sorted by
+    /// name, on file systems that have them
+    fn extended_attributes(&self, _path: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "Extended attributes are not supported on this file system"))
+    }
+
+    /// The user id this file system acts as, to compare with
+    /// [`Metadata::owner`], where it is known
+    fn user_id(&self) -> Option<u32> {
+        None
+    }
+
+    /// Set the Unix permission bits of `path`; file systems without them
+    /// ignore it
+    fn set_mode(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Take a lock on `dir` that other processes updating files in it wait
+    /// for, held until the returned lock is dropped. `None` when the file
+    /// system has no such lock.
+    fn lock_directory(&self, _dir: &Path) -> io::Result<Option<DirectoryLock>> {
+        Ok(None)
+    }
+}
+
+/// An exclusive advisory lock on a directory's
+/// [`DIRECTORY_LOCK_FILE_NAME`], which is removed when the lock is dropped
+#[derive(Debug)]
+pub struct DirectoryLock {
+    path: PathBuf,
+    _file: File,
+}
+
+impl Drop for DirectoryLock {
+    fn drop(&mut self) {
+        // Removed while still locked; a process waiting on the old file
+        // notices it is gone and locks a new one
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The file system used when none is configured
+pub fn default_file_system() -> Arc<dyn FileSystem> {
+    Arc::new(StdFileSystem)
+}
+
+/// `path` made absolute with symlinks resolved, like [`Path::canonicalize`].
+///
+/// On Windows that returns verbatim paths such as `\\?\C:\srv\data`, which
+/// never start with the `C:\srv` a config names. The prefix is dropped
+/// whenever the path means the same without it, so every path the policy
+/// compares is in the form users write.
+pub fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+    dunce::canonicalize(path)
+}
+
+/// `path` as text that [`unescape_path`] turns back into the same path.
+///
+/// Unix file names are bytes and need not be UTF-8. Bytes that are not are
+/// written as `%XX` escapes; everything else is kept as it is. Elsewhere
+/// names are Unicode and this is the path's display form.
+pub fn escape_path(path: &Path) -> String {
+    #[cfg(unix)]
+    {
+        use std::fmt::Write as _;
+        use std::os::unix::ffi::OsStrExt;
+        let mut escaped = String::new();
+        for chunk in path.as_os_str().as_bytes().utf8_chunks() {
+            escaped.push_str(chunk.valid());
+            for byte in chunk.invalid() {
+                let _ = write!(escaped, "%{:02X}", byte);
+            }
+        }
+        escaped
+    }
+    #[cfg(not(unix))]
+    {
+        path.display().to_string()
+    }
+}
+
+/// `path` with the escapes of [`escape_path`] turned back into bytes.
+///
+/// A run of `%80` to `%FF` escapes is only read as bytes when those bytes
+/// are not UTF-8, so a name that really contains `%C3%A9` keeps it. Paths
+/// that are not text already, and paths on hosts other than Unix, are
+/// returned as they are.
+pub fn unescape_path(path: &Path) -> Cow<'_, Path> {
+    #[cfg(unix)]
+    if let Some(text) = path.to_str().filter(|text| text.contains('%')) {
+        use std::os::unix::ffi::OsStringExt;
+        let bytes = text.as_bytes();
+        let mut unescaped = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let mut run = Vec::new();
+            let mut end = i;
+            while let Some(byte) = escaped_byte(&bytes[end..]) {
+                run.push(byte);
+                end += 3;
+            }
+            if run.is_empty() || std::str::from_utf8(&run).is_ok() {
+                let end = end.max(i + 1);
+                unescaped.extend_from_slice(&bytes[i..end]);
+                i = end;
+            } else {
+                unescaped.extend(run);
+                i = end;
+            }
+        }
+        if unescaped != bytes {
+            return Cow::Owned(PathBuf::from(std::ffi::OsString::from_vec(unescaped)));
+        }
+    }
+    Cow::Borrowed(path)
+}
+
+/// The byte of a `%80` to `%FF` escape at the start of `text`
+#[cfg(unix)]
+fn escaped_byte(text: &[u8]) -> Option<u8> {
+    match text {
+        [b'%', high, low, ..] => {
+            let hex = std::str::from_utf8(&[*high, *low]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())?;
+            (hex >= 0x80).then_some(hex)
+        }
+        _ => None,
+    }
+}
+
+/// The host file system, through `std::fs`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdFileSystem;
+
+impl FileSystem for StdFileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    #[cfg(unix)]
+    fn open_special(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+        use std::os::unix::fs::OpenOptionsExt;
+        Ok(Box::new(OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(path)?))
+    }
+
+    /// Writes a temporary file in the same directory and renames it over the
+    /// target, keeping the target's permissions
+    fn write(&self, path: &Path, contents: &[u8], sync: bool) -> io::Result<()> {
+        let temp_path = temp_path_beside(path)?;
+        let parent = temp_path.parent().map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+
+        let result = write_temp_and_rename(&temp_path, path, contents, sync);
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result?;
+
+        if sync {
+            sync_directory(&parent)?;
+        }
+        Ok(())
+    }
+
+    fn write_in_place(&self, path: &Path, contents: &[u8], sync: bool) -> io::Result<()> {
+        // Truncation waits until the advisory lock is held so concurrent
+        // writers cannot interleave
+        let mut file = open_regular_for_write(path)?;
+        file.lock()?;
+        file.set_len(0)?;
+        file.write_all(contents)?;
+        if sync {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn write_at(&self, path: &Path, offset: u64, contents: &[u8], sync: bool) -> io::Result<u64> {
+        let mut file = open_regular_for_write(path)?;
+        file.lock()?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(contents)?;
+        if sync {
+            file.sync_all()?;
+        }
+        Ok(file.metadata()?.len())
+    }
+
+    fn append(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        // Hold an advisory lock so concurrent appends are not interleaved
+        file.lock()?;
+        file.write_all(contents)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        fs::metadata(path).map(Metadata::from)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        fs::symlink_metadata(path).map(Metadata::from)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        canonicalize(path)
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        fs::read_dir(path)?
+            .map(|entry| {
+                let entry = entry?;
+                Ok(DirEntry { path: entry.path(), kind: entry.file_type()?.into() })
+            })
+            .collect()
+    }
+
+    /// Walks directories in parallel
+    fn walk(&self, root: &Path, options: &WalkOptions) -> io::Result<Vec<DirEntry>> {
+        let busy_timeout = std::time::Duration::from_secs(1);
+        let parallelism = match &options.pool {
+            Some(pool) => jwalk::Parallelism::RayonExistingPool { pool: pool.clone(), busy_timeout: Some(busy_timeout) },
+            None => jwalk::Parallelism::RayonDefaultPool { busy_timeout },
+        };
+        let walker = jwalk::WalkDir::new(root)
+            .follow_links(options.follow_links)
+            .skip_hidden(options.skip_hidden)
+            .max_depth(options.max_depth)
+            .sort(true)
+            .parallelism(parallelism);
+        Ok(walker.into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.depth() > 0)
+            .map(|entry| DirEntry { path: entry.path(), kind: entry.file_type().into() })
+            .collect())
+    }
+
+    fn create_dir(&self, path: &Path, recursive: bool) -> io::Result<()> {
+        if recursive {
+            fs::create_dir_all(path)
+        } else {
+            fs::create_dir(path)
+        }
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path, recursive: bool) -> io::Result<()> {
+        if recursive {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_dir(path)
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    /// Uses the fastest mechanism available: a copy-on-write clone where the
+    /// filesystem supports it, then `std::fs::copy`, which uses
+    /// copy_file_range/sendfile on Linux and clonefile/fcopyfile on macOS
+    /// before falling back to a userspace copy.
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        #[cfg(target_os = "linux")]
+        if let Some(bytes) = reflink(from, to)? {
+            return Ok(bytes);
+        }
+        fs::copy(from, to)
+    }
+
+    fn copy_attributes(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let metadata = fs::metadata(from)?;
+        // Before the permissions, which may leave `to` read-only
+        if let Ok(modified) = metadata.modified() {
+            open_for_attributes(to, metadata.is_dir())?.set_modified(modified)?;
+        }
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        xattr::copy(from, to);
+        fs::set_permissions(to, metadata.permissions())
+    }
+
+    #[cfg(unix)]
+    fn user_id(&self) -> Option<u32> {
+        Some(unsafe { libc::geteuid() })
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+
+    /// Locks `dir`'s [`DIRECTORY_LOCK_FILE_NAME`], creating it if needed.
+    /// Whoever drops the lock removes the file, so the lock only counts once
+    /// the locked file is still the one at the path.
+    #[cfg(unix)]
+    fn lock_directory(&self, dir: &Path) -> io::Result<Option<DirectoryLock>> {
+        use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+
+        let path = dir.join(DIRECTORY_LOCK_FILE_NAME);
+        loop {
+            let file = match OpenOptions::new().write(true).create(true).truncate(false).mode(0o600).open(&path) {
+                Ok(file) => file,
+                // The directory is missing or cannot hold the lock file; the
+                // writers of this process still take turns
+                Err(e) if matches!(
+                    e.kind(),
+                    io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem
+                ) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            file.lock()?;
+            let locked = file.metadata()?;
+            if fs::metadata(&path).is_ok_and(|current| current.dev() == locked.dev() && current.ino() == locked.ino()) {
+                return Ok(Some(DirectoryLock { path, _file: file }));
+            }
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    fn extended_attributes(&self, path: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let mut attributes: Vec<_> = xattr::list(path)?
+            .into_iter()
+            .map(|(name, value)| (String::from_utf8_lossy(&name).into_owned(), value))
+            .collect();
+        attributes.sort();
+        Ok(attributes)
+    }
+}
+
+/// Open a file or directory so its times can be set
+fn open_for_attributes(path: &Path, is_dir: bool) -> io::Result<File> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        // Directories can only be opened with backup semantics
+        const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+        let flags = if is_dir { FILE_FLAG_BACKUP_SEMANTICS } else { 0 };
+        OpenOptions::new().write(true).custom_flags(flags).open(path)
+    }
+    #[cfg(not(windows))]
+    {
+        if is_dir {
+            File::open(path)
+        } else {
+            OpenOptions::new().write(true).open(path)
+        }
+    }
+}
+
+/// A fresh name in the same directory as `path`, for writing its
+/// replacement before renaming it into place
+pub(crate) fn temp_path_beside(path: &Path) -> io::Result<PathBuf> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+    Ok(parent.join(format!(
+        ".{}.filejack-{}-{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed),
+    )))
+}
+
+fn write_temp_and_rename(temp_path: &Path, target: &Path, contents: &[u8], sync: bool) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temp_path)?;
+
+    // Keep the permissions of the file being replaced
+    if let Ok(existing) = fs::metadata(target) {
+        file.set_permissions(existing.permissions())?;
+    }
+
+    file.write_all(contents)?;
+    if sync {
+        file.sync_all()?;
+    }
+    drop(file);
+
+    fs::rename(temp_path, target)
+}
+
+/// Open a file for writing without truncating it, refusing anything but a
+/// regular file
+fn open_regular_for_write(path: &Path) -> io::Result<File> {
+    // Open with explicit options and check the handle to prevent TOCTOU
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    if !file.metadata()?.is_file() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot write to non-regular file"));
+    }
+    Ok(file)
+}
+
+/// Clone a file's extents with FICLONE (Btrfs, XFS, bcachefs). Returns None
+/// when the filesystem cannot share extents between the two files.
+#[cfg(target_os = "linux")]
+fn reflink(from: &Path, to: &Path) -> io::Result<Option<u64>> {
+    use std::os::unix::io::AsRawFd;
+
+    let src = File::open(from)?;
+    let metadata = src.metadata()?;
+    let dst = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(to)?;
+
+    // SAFETY: both descriptors stay open for the duration of the call and
+    // FICLONE takes the source descriptor by value.
+    let rc = unsafe { libc::ioctl(dst.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) };
+    if rc != 0 {
+        return Ok(None);
+    }
+
+    dst.set_permissions(metadata.permissions())?;
+    Ok(Some(metadata.len()))
+}
+
+/// Extended attributes through the host's `listxattr`, `getxattr`, and
+/// `setxattr`, whose signatures differ between Linux and macOS
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod xattr {
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    /// Names and values of the attributes of `path`, as raw bytes
+    pub(super) fn list(path: &Path) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let path = c_string(path.as_os_str().as_bytes())?;
+        let names = read_sized(|buf, size| unsafe { listxattr(&path, buf.cast(), size) })?;
+        names.split(|&byte| byte == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let c_name = c_string(name)?;
+                let value = read_sized(|buf, size| unsafe { getxattr(&path, &c_name, buf, size) })?;
+                Ok((name.to_vec(), value))
+            })
+            .collect()
+    }
+
+    /// Give `to` the attributes of `from`, as far as it can take them:
+    /// namespaces such as `security.` need privileges, and some file
+    /// systems have no attributes at all
+    pub(super) fn copy(from: &Path, to: &Path) {
+        let (Ok(attributes), Ok(to)) = (list(from), c_string(to.as_os_str().as_bytes())) else { return };
+        for (name, value) in attributes {
+            if let Ok(name) = c_string(&name) {
+                unsafe { setxattr(&to, &name, value.as_ptr().cast(), value.len()) };
+            }
+        }
+    }
+
+    fn c_string(bytes: &[u8]) -> io::Result<CString> {
+        CString::new(bytes).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Name contains a NUL byte"))
+    }
+
+    /// Ask `read` for the size it needs, then read into a buffer that
+    /// large, again if the value grew in between
+    fn read_sized(mut read: impl FnMut(*mut libc::c_void, usize) -> libc::ssize_t) -> io::Result<Vec<u8>> {
+        loop {
+            let size = read(std::ptr::null_mut(), 0);
+            if size < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut buf = vec![0u8; size as usize];
+            let read_len = read(buf.as_mut_ptr().cast(), buf.len());
+            if read_len >= 0 {
+                buf.truncate(read_len as usize);
+                return Ok(buf);
+            }
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(libc::ERANGE) {
+                return Err(e);
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn listxattr(path: &CStr, buf: *mut libc::c_char, size: usize) -> libc::ssize_t {
+        libc::listxattr(path.as_ptr(), buf, size)
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn listxattr(path: &CStr, buf: *mut libc::c_char, size: usize) -> libc::ssize_t {
+        libc::listxattr(path.as_ptr(), buf, size, 0)
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn getxattr(path: &CStr, name: &CStr, buf: *mut libc::c_void, size: usize) -> libc::ssize_t {
+        libc::getxattr(path.as_ptr(), name.as_ptr(), buf, size)
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn getxattr(path: &CStr, name: &CStr, buf: *mut libc::c_void, size: usize) -> libc::ssize_t {
+        libc::getxattr(path.as_ptr(), name.as_ptr(), buf, size, 0, 0)
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn setxattr(path: &CStr, name: &CStr, value: *const libc::c_void, size: usize) -> libc::c_int {
+        libc::setxattr(path.as_ptr(), name.as_ptr(), value, size, 0)
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn setxattr(path: &CStr, name: &CStr, value: *const libc::c_void, size: usize) -> libc::c_int {
+        libc::setxattr(path.as_ptr(), name.as_ptr(), value, size, 0, 0)
+    }
+}
+
+/// Flush a directory entry update (such as a rename) to disk
+#[cfg(unix)]
+fn sync_directory(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// Directories cannot be opened for syncing on this platform
+#[cfg(not(unix))]
+fn sync_directory(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+enum MemoryNode {
+    File { contents: Vec<u8>, modified: SystemTime },
+    Dir,
+}
+
+/// A file system held in memory, for tests and scratch workspaces.
+///
+/// Paths must be absolute; `.` and `..` are resolved lexically and there
+/// are no symlinks. The root directory always exists.
+#[derive(Debug, Default)]
+pub struct MemoryFileSystem {
+    nodes: Mutex<BTreeMap<PathBuf, MemoryNode>>,
+}
+
+/// A snapshot of a file's contents taken when it was opened
+pub(crate) struct MemoryHandle {
+    contents: Cursor<Vec<u8>>,
+    metadata: Metadata,
+}
+
+impl MemoryHandle {
+    pub(crate) fn new(contents: Vec<u8>, metadata: Metadata) -> Self {
+        Self { contents: Cursor::new(contents), metadata }
+    }
+}
+
+impl Read for MemoryHandle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.contents.read(buf)
+    }
+}
+
+impl Seek for MemoryHandle {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.contents.seek(pos)
+    }
+}
+
+impl FileHandle for MemoryHandle {
+    fn metadata(&self) -> io::Result<Metadata> {
+        Ok(self.metadata.clone())
+    }
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn nodes(&self) -> MutexGuard<'_, BTreeMap<PathBuf, MemoryNode>> {
+        self.nodes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Store a file outside any policy, creating its parent directories
+    pub fn insert_file(&self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> io::Result<()> {
+        let path = normalize(path.as_ref())?;
+        if let Some(parent) = path.parent() {
+            self.create_dir(parent, true)?;
+        }
+        self.nodes().insert(path, MemoryNode::File { contents: contents.into(), modified: SystemTime::now() });
+        Ok(())
+    }
+
+    fn node_metadata(node: Option<&MemoryNode>) -> io::Result<Metadata> {
+        match node {
+            Some(MemoryNode::File { contents, modified }) => Ok(Metadata {
+                kind: FileKind::File,
+                len: contents.len() as u64,
+                modified: Some(*modified),
+                created: None,
+                readonly: false,
+                hidden: false,
+                owner: None,
+                group: None,
+                mode: None,
+            }),
+            Some(MemoryNode::Dir) => Ok(Metadata {
+                kind: FileKind::Dir,
+                len: 0,
+                modified: None,
+                created: None,
+                readonly: false,
+                hidden: false,
+                owner: None,
+                group: None,
+                mode: None,
+            }),
+            None => Err(not_found()),
+        }
+    }
+
+    /// Run `f` on the contents of a file, creating it empty if missing
+    fn update_file<T>(&self, path: &Path, f: impl FnOnce(&mut Vec<u8>) -> T) -> io::Result<T> {
+        let path = normalize(path)?;
+        let mut nodes = self.nodes();
+        check_parent(&nodes, &path)?;
+        let node = nodes.entry(path)
+            .or_insert_with(|| MemoryNode::File { contents: Vec::new(), modified: SystemTime::now() });
+        match node {
+            MemoryNode::File { contents, modified } => {
+                *modified = SystemTime::now();
+                Ok(f(contents))
+            }
+            MemoryNode::Dir => Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot write to non-regular file")),
+        }
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+        let path = normalize(path)?;
+        let nodes = self.nodes();
+        let node = nodes.get(&path);
+        let metadata = Self::node_metadata(node.or(is_root(&path).then_some(&MemoryNode::Dir)))?;
+        let contents = match node {
+            Some(MemoryNode::File { contents, .. }) => contents.clone(),
+            _ => Vec::new(),
+        };
+        Ok(Box::new(MemoryHandle::new(contents, metadata)))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8], _sync: bool) -> io::Result<()> {
+        self.update_file(path, |existing| *existing = contents.to_vec())
+    }
+
+    fn write_at(&self, path: &Path, offset: u64, contents: &[u8], _sync: bool) -> io::Result<u64> {
+        let start = usize::try_from(offset).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Offset out of range"))?;
+        self.update_file(path, |existing| {
+            let end = start + contents.len();
+            if existing.len() < end {
+                existing.resize(end, 0);
+            }
+            existing[start..end].copy_from_slice(contents);
+            existing.len() as u64
+        })
+    }
+
+    fn append(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.update_file(path, |existing| existing.extend_from_slice(contents))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let path = normalize(path)?;
+        if is_root(&path) {
+            return Self::node_metadata(Some(&MemoryNode::Dir));
+        }
+        Self::node_metadata(self.nodes().get(&path))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.metadata(path)?;
+        normalize(path)
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        if !self.metadata(path)?.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::NotADirectory, "Not a directory"));
+        }
+        let path = normalize(path)?;
+        Ok(self.nodes().iter()
+            .filter(|(child, _)| child.parent() == Some(path.as_path()))
+            .map(|(child, node)| DirEntry {
+                path: child.clone(),
+                kind: if matches!(node, MemoryNode::Dir) { FileKind::Dir } else { FileKind::File },
+            })
+            .collect())
+    }
+
+    fn create_dir(&self, path: &Path, recursive: bool) -> io::Result<()> {
+        let path = normalize(path)?;
+        let mut nodes = self.nodes();
+        match nodes.get(&path) {
+            Some(MemoryNode::Dir) if recursive => return Ok(()),
+            Some(_) => return Err(io::Error::new(io::ErrorKind::AlreadyExists, "Path already exists")),
+            None if is_root(&path) => return Ok(()),
+            None => {}
+        }
+        if recursive {
+            for ancestor in path.ancestors().skip(1).filter(|a| !is_root(a)) {
+                match nodes.get(ancestor) {
+                    Some(MemoryNode::Dir) => break,
+                    Some(_) => return Err(io::Error::new(io::ErrorKind::NotADirectory, "Not a directory")),
+                    None => {
+                        nodes.insert(ancestor.to_path_buf(), MemoryNode::Dir);
+                    }
+                }
+            }
+        } else {
+            check_parent(&nodes, &path)?;
+        }
+        nodes.insert(path, MemoryNode::Dir);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let path = normalize(path)?;
+        let mut nodes = self.nodes();
+        match nodes.get(&path) {
+            Some(MemoryNode::File { .. }) => {
+                nodes.remove(&path);
+                Ok(())
+            }
+            Some(MemoryNode::Dir) => Err(io::Error::new(io::ErrorKind::IsADirectory, "Is a directory")),
+            None => Err(not_found()),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path, recursive: bool) -> io::Result<()> {
+        let path = normalize(path)?;
+        let mut nodes = self.nodes();
+        if !matches!(nodes.get(&path), Some(MemoryNode::Dir)) {
+            return Err(not_found());
+        }
+        let descendants: Vec<PathBuf> = nodes.keys()
+            .filter(|child| child.starts_with(&path) && **child != path)
+            .cloned()
+            .collect();
+        if !descendants.is_empty() && !recursive {
+            return Err(io::Error::new(io::ErrorKind::DirectoryNotEmpty, "Directory not empty"));
+        }
+        for child in descendants {
+            nodes.remove(&child);
+        }
+        nodes.remove(&path);
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let (from, to) = (normalize(from)?, normalize(to)?);
+        let mut nodes = self.nodes();
+        if !nodes.contains_key(&from) {
+            return Err(not_found());
+        }
+        check_parent(&nodes, &to)?;
+        let moved: Vec<PathBuf> = nodes.keys().filter(|path| path.starts_with(&from)).cloned().collect();
+        for path in moved {
+            if let Some(node) = nodes.remove(&path) {
+                let rest = path.strip_prefix(&from).unwrap_or(Path::new(""));
+                let target = if rest.as_os_str().is_empty() { to.clone() } else { to.join(rest) };
+                nodes.insert(target, node);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolve `.` and `..` in an absolute path without touching storage
+fn normalize(path: &Path) -> io::Result<PathBuf> {
+    if !path.has_root() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not absolute", path.display())));
+    }
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    Ok(normalized)
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+fn is_root(path: &Path) -> bool {
+    path.parent().is_none()
+}
+
+fn check_parent(nodes: &BTreeMap<PathBuf, MemoryNode>, path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !is_root(parent) && !matches!(nodes.get(parent), Some(MemoryNode::Dir)) => Err(not_found()),
+        _ => Ok(()),
+    }
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "No such file or directory")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_file_system_basics() {
+        let fs = MemoryFileSystem::new();
+        let file = Path::new("/work/notes.txt");
+        assert_eq!(fs.write(file, b"x", false).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        fs.create_dir(Path::new("/work"), false).unwrap();
+        fs.write(file, b"hello", false).unwrap();
+        fs.append(file, b" world").unwrap();
+        assert_eq!(fs.read(file).unwrap(), b"hello world");
+        assert_eq!(fs.write_at(file, 13, b"!", false).unwrap(), 14);
+        assert_eq!(fs.read(file).unwrap(), b"hello world\0\0!");
+        assert_eq!(fs.metadata(file).unwrap().len, 14);
+        assert_eq!(fs.canonicalize(Path::new("/work/./sub/../notes.txt")).unwrap(), file);
+        assert!(fs.metadata(Path::new("/")).unwrap().is_dir());
+
+        fs.rename(Path::new("/work"), Path::new("/moved")).unwrap();
+        assert!(!fs.exists(file));
+        assert_eq!(fs.copy(Path::new("/moved/notes.txt"), Path::new("/copy.txt")).unwrap(), 14);
+        assert_eq!(fs.list(Path::new("/")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_memory_file_system_walk_and_remove() {
+        let fs = MemoryFileSystem::new();
+        fs.insert_file("/a/b/c.txt", "c").unwrap();
+        fs.insert_file("/a/d.txt", "d").unwrap();
+
+        let walked: Vec<PathBuf> = fs.walk(Path::new("/a"), &WalkOptions::default()).unwrap()
+            .into_iter().map(|entry| entry.path).collect();
+        assert_eq!(walked, vec![
+            PathBuf::from("/a/b"),
+            PathBuf::from("/a/b/c.txt"),
+            PathBuf::from("/a/d.txt"),
+        ]);
+        let shallow = fs.walk(Path::new("/a"), &WalkOptions { max_depth: 1, ..Default::default() }).unwrap();
+        assert_eq!(shallow.len(), 2);
+
+        let err = fs.remove_dir(Path::new("/a"), false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::DirectoryNotEmpty);
+        fs.remove_dir(Path::new("/a"), true).unwrap();
+        assert!(fs.list(Path::new("/")).unwrap().is_empty());
+    }
+    #[cfg(unix)]
+    #[test]
+    fn test_escape_path_round_trip() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let raw = Path::new(std::ffi::OsStr::from_bytes(b"/data/caf\xe9/\xff\xfe.txt"));
+        assert_eq!(escape_path(raw), "/data/caf%E9/%FF%FE.txt");
+        assert_eq!(unescape_path(Path::new("/data/caf%E9/%FF%FE.txt")), raw);
+
+        // Escapes of UTF-8, and of ASCII, are part of the name
+        for literal in ["/data/caf%C3%A9.txt", "/data/100%25.txt", "/data/50%", "/data/caf\u{e9}.txt"] {
+            assert_eq!(escape_path(Path::new(literal)), literal);
+            assert!(matches!(unescape_path(Path::new(literal)), Cow::Borrowed(_)));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_std_file_system_extended_attributes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file = temp_dir.path().join("tagged.txt");
+        std::fs::write(&file, "tagged").unwrap();
+        let path = std::ffi::CString::new(file.as_os_str().as_encoded_bytes()).unwrap();
+        let value = b"alice";
+        let set = unsafe { libc::setxattr(path.as_ptr(), c"user.owner".as_ptr(), value.as_ptr().cast(), value.len(), 0) };
+        if set != 0 {
+            // Not every file system under the temp directory has user attributes
+            return;
+        }
+
+        let fs = StdFileSystem;
+        assert_eq!(fs.extended_attributes(&file).unwrap(), vec![("user.owner".to_string(), b"alice".to_vec())]);
+        let copy = temp_dir.path().join("copy.txt");
+        fs.copy(&file, &copy).unwrap();
+        fs.copy_attributes(&file, &copy).unwrap();
+        assert_eq!(fs.extended_attributes(&copy).unwrap(), fs.extended_attributes(&file).unwrap());
+        assert_eq!(MemoryFileSystem::new().extended_attributes(Path::new("/")).unwrap_err().kind(), io::ErrorKind::Unsupported);
+    }
+}
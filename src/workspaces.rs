@@ -0,0 +1,258 @@
+//! Scratch directories handed out to sessions.
+//!
+//! `create_temp_workspace` makes a fresh directory under the configured
+//! root for the calling session. The root is allowed by the access policy,
+//! but each directory in it may only be used by the session that created
+//! it, and only until its TTL runs out. Expired directories are removed the
+//! next time a workspace is created or checked, and all of them when the
+//! server shuts down.
+
+use crate::config::WorkspacesConfig;
+use crate::error::{FileJackError, Result};
+use crate::vfs;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+/// Name prefix of the directories created under the root
+const DIRECTORY_PREFIX: &str = "filejack-ws-";
+
+/// Result of `create_temp_workspace`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WorkspaceSummary {
+    pub path: String,
+    pub expires_in_secs: u64,
+}
+
+struct Workspace {
+    path: PathBuf,
+    session: String,
+    expires: Instant,
+}
+
+/// The workspaces handed out by one server
+pub struct Workspaces {
+    root: PathBuf,
+    ttl: Duration,
+    max_per_session: usize,
+    counter: AtomicU64,
+    live: Mutex<Vec<Workspace>>,
+}
+
+impl Workspaces {
+    /// Create the root if needed and remove workspaces an earlier run left
+    /// behind once they are older than the TTL
+    pub fn new(config: &WorkspacesConfig) -> Result<Self> {
+        fs::create_dir_all(&config.root)?;
+        let root = vfs::canonicalize(&config.root)?;
+        let ttl = Duration::from_secs(config.ttl_secs);
+        for entry in fs::read_dir(&root)?.flatten() {
+            let stale = entry.file_name().to_string_lossy().starts_with(DIRECTORY_PREFIX)
+                && entry.metadata().and_then(|m| m.modified()).is_ok_and(|modified| {
+                    modified.elapsed().is_ok_and(|age| age > ttl)
+                });
+            if stale {
+                remove(&entry.path());
+            }
+        }
+        Ok(Self {
+            root,
+            ttl,
+            max_per_session: config.max_per_session,
+            counter: AtomicU64::new(0),
+            live: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Directory the workspaces are created in
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Make a new workspace owned by `session`
+    pub fn create(&self, session: &str) -> Result<WorkspaceSummary> {
+        let mut live = self.live();
+        self.expire(&mut live);
+        if live.iter().filter(|workspace| workspace.session == session).count() >= self.max_per_session {
+            return Err(FileJackError::InvalidParameters(format!(
+                "Session already has {} temporary workspaces; wait for one to expire",
+                self.max_per_session
+            )));
+        }
+
+        let path = self.root.join(format!("{}{}", DIRECTORY_PREFIX, self.unique_name()));
+        fs::create_dir(&path)?;
+        info!(session, path = %path.display(), "Temporary workspace created");
+        live.push(Workspace {
+            path: path.clone(),
+            session: session.to_string(),
+            expires: Instant::now() + self.ttl,
+        });
+        Ok(WorkspaceSummary {
+            path: path.display().to_string(),
+            expires_in_secs: self.ttl.as_secs(),
+        })
+    }
+
+    /// Refuse `path` if it lies under the root but outside the live
+    /// workspaces of `session`. Paths elsewhere are left to the policy.
+    pub fn check_access(&self, session: &str, path: &Path) -> Result<()> {
+        let resolved = resolve(path);
+        if !resolved.starts_with(&self.root) {
+            return Ok(());
+        }
+        let mut live = self.live();
+        self.expire(&mut live);
+        let owned = live.iter()
+            .any(|workspace| workspace.session == session && resolved.starts_with(&workspace.path));
+        if owned {
+            Ok(())
+        } else {
+            Err(FileJackError::PermissionDenied(format!(
+                "{} is not in a temporary workspace of this session",
+                path.display()
+            ).into()))
+        }
+    }
+
+    /// Delete every workspace, live or expired
+    pub fn remove_all(&self) {
+        let removed: Vec<Workspace> = self.live().drain(..).collect();
+        for workspace in &removed {
+            remove(&workspace.path);
+        }
+        if !removed.is_empty() {
+            info!(count = removed.len(), "Temporary workspaces removed");
+        }
+    }
+
+    fn expire(&self, live: &mut Vec<Workspace>) {
+        let now = Instant::now();
+        live.retain(|workspace| {
+            if workspace.expires > now {
+                return true;
+            }
+            debug!(session = %workspace.session, path = %workspace.path.display(), "Temporary workspace expired");
+            remove(&workspace.path);
+            false
+        });
+    }
+
+    fn unique_name(&self) -> String {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+        let seed = format!("{}-{}-{}", std::process::id(), nanos, count);
+        blake3::hash(seed.as_bytes()).to_hex()[..16].to_string()
+    }
+
+    fn live(&self) -> MutexGuard<'_, Vec<Workspace>> {
+        self.live.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for Workspaces {
+    fn drop(&mut self) {
+        self.remove_all();
+    }
+}
+
+fn remove(path: &Path) {
+    match fs::remove_dir_all(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => warn!(path = %path.display(), error = %e, "Could not remove temporary workspace"),
+    }
+}
+
+/// `path` made absolute with `.` and `..` resolved, and symlinks resolved
+/// in the longest part of it that exists
+fn resolve(path: &Path) -> PathBuf {
+    let absolute = match std::env::current_dir() {
+        Ok(cwd) if path.is_relative() => cwd.join(path),
+        _ => path.to_path_buf(),
+    };
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+
+    let mut existing = normalized.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = vfs::canonicalize(existing) {
+            return rest.iter().rev().fold(canonical, |path, name| path.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return normalized,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn workspaces(root: &Path, ttl_secs: u64) -> Workspaces {
+        Workspaces::new(&WorkspacesConfig { root: root.to_path_buf(), ttl_secs, max_per_session: 2 }).unwrap()
+    }
+
+    #[test]
+    fn test_workspaces_belong_to_their_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspaces = workspaces(&temp_dir.path().join("scratch"), 60);
+        let first = PathBuf::from(workspaces.create("a").unwrap().path);
+        assert!(first.is_dir());
+        assert!(first.starts_with(workspaces.root()));
+
+        let file = first.join("sub/../out.txt");
+        assert!(workspaces.check_access("a", &file).is_ok());
+        assert!(matches!(workspaces.check_access("b", &file), Err(FileJackError::PermissionDenied(_))));
+        assert!(workspaces.check_access("a", workspaces.root()).is_err());
+        assert!(workspaces.check_access("b", temp_dir.path()).is_ok());
+
+        workspaces.create("a").unwrap();
+        assert!(workspaces.create("a").unwrap_err().to_string().contains("already has 2"));
+        assert!(workspaces.create("b").is_ok());
+
+        drop(workspaces);
+        assert!(!first.exists());
+    }
+
+    #[test]
+    fn test_expired_workspaces_are_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspaces = workspaces(temp_dir.path(), 0);
+        let path = PathBuf::from(workspaces.create("a").unwrap().path);
+        assert!(workspaces.check_access("a", &path).is_err());
+        assert!(!path.exists());
+
+        let leftover = temp_dir.path().join(format!("{}old", DIRECTORY_PREFIX));
+        fs::create_dir(&leftover).unwrap();
+        let kept = temp_dir.path().join("other");
+        fs::create_dir(&kept).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        let _restarted = self::workspaces(temp_dir.path(), 0);
+        assert!(!leftover.exists());
+        assert!(kept.exists());
+    }
+}
@@ -0,0 +1,424 @@
+//! MCP conformance tests: run the `filejack` binary as a subprocess and talk
+//! to it over its real stdin and stdout, the way an MCP client does. These
+//! catch transport regressions the in-process tests cannot see, such as log
+//! lines leaking onto stdout, framing mistakes, or replies to notifications.
+
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// How long to wait for a reply before failing the test
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a notification is given to (wrongly) produce a reply
+const SILENCE: Duration = Duration::from_millis(300);
+
+/// A `filejack` process serving a temporary directory over stdio
+struct Server {
+    dir: TempDir,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    replies: Receiver<Frame>,
+}
+
+/// A message read from stdout
+struct Frame {
+    /// Whether it came with `Content-Length` headers rather than a newline
+    content_length: bool,
+    body: String,
+}
+
+impl Server {
+    fn start() -> Self {
+        Self::start_with(|_| {})
+    }
+
+    fn start_with(configure: impl FnOnce(&mut Command)) -> Self {
+        // The default temp dir prefix is hidden, which the policy rejects
+        let dir = tempfile::Builder::new().prefix("conformance").tempdir().unwrap();
+        let mut command = Command::new(env!("CARGO_BIN_EXE_filejack"));
+        command
+            .current_dir(dir.path())
+            .env_remove("FILEJACK_CONFIG")
+            .env("FILEJACK_BASE_PATH", dir.path())
+            .env("RUST_LOG", "error")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        configure(&mut command);
+        let mut child = command.spawn().unwrap();
+        let stdin = child.stdin.take();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+
+        // Read on a thread so a missing reply fails the test instead of hanging it
+        let (sender, replies) = mpsc::channel();
+        std::thread::spawn(move || read_messages(stdout, sender));
+        Self { dir, child, stdin, replies }
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.dir.path().join(name).display().to_string()
+    }
+
+    fn send_raw(&mut self, bytes: &str) {
+        let stdin = self.stdin.as_mut().expect("stdin closed");
+        stdin.write_all(bytes.as_bytes()).unwrap();
+        stdin.flush().unwrap();
+    }
+
+    fn send(&mut self, message: Value) {
+        self.send_raw(&format!("{}\n", message));
+    }
+
+    fn recv_frame(&self) -> Frame {
+        match self.replies.recv_timeout(REPLY_TIMEOUT) {
+            Ok(frame) => frame,
+            Err(RecvTimeoutError::Timeout) => panic!("no reply within {:?}", REPLY_TIMEOUT),
+            Err(RecvTimeoutError::Disconnected) => panic!("server closed stdout"),
+        }
+    }
+
+    /// Next message on stdout, which must be a JSON-RPC 2.0 message
+    fn recv(&self) -> Value {
+        self.parse(self.recv_frame())
+    }
+
+    fn parse(&self, frame: Frame) -> Value {
+        let raw = frame.body;
+        let message: Value = serde_json::from_str(&raw).unwrap_or_else(|e| panic!("not JSON ({}): {:?}", e, raw));
+        assert_eq!(message["jsonrpc"], "2.0", "{}", raw);
+        message
+    }
+
+    fn request(&mut self, id: Value, method: &str, params: Value) -> Value {
+        self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}));
+        let reply = self.recv();
+        assert_eq!(reply["id"], id, "reply to {} has the wrong id: {}", method, reply);
+        assert!(
+            reply.get("result").is_some() != reply.get("error").is_some(),
+            "reply must carry exactly one of result and error: {}",
+            reply
+        );
+        reply
+    }
+
+    fn call_tool(&mut self, id: u64, name: &str, arguments: Value) -> Value {
+        self.request(json!(id), "tools/call", json!({"name": name, "arguments": arguments}))
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({"jsonrpc": "2.0", "method": method, "params": params}));
+    }
+
+    fn assert_silent(&self) {
+        if let Ok(frame) = self.replies.recv_timeout(SILENCE) {
+            panic!("unexpected message: {}", frame.body);
+        }
+    }
+
+    fn initialize(&mut self) -> Value {
+        let params = json!({
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": {"name": "conformance", "version": "1.0"}
+        });
+        let reply = self.request(json!(0), "initialize", params);
+        self.notify("notifications/initialized", json!({}));
+        reply
+    }
+
+    /// Close stdin and wait for the process to exit
+    fn finish(mut self) -> std::process::ExitStatus {
+        drop(self.stdin.take());
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        loop {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                return status;
+            }
+            assert!(Instant::now() < deadline, "server did not exit after stdin closed");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Split stdout into messages, framed either by newlines or by
+/// `Content-Length` headers
+fn read_messages(mut stdout: impl BufRead, sender: mpsc::Sender<Frame>) {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if stdout.read_line(&mut line).unwrap_or(0) == 0 {
+            return;
+        }
+        let content_length = line.starts_with("Content-Length:");
+        let body = match line.strip_prefix("Content-Length:") {
+            Some(length) => {
+                let length: usize = length.trim().parse().expect("bad Content-Length");
+                // Skip any further headers up to the blank line
+                while line.trim() != "" {
+                    line.clear();
+                    stdout.read_line(&mut line).unwrap();
+                }
+                let mut body = vec![0; length];
+                stdout.read_exact(&mut body).unwrap();
+                String::from_utf8(body).unwrap()
+            }
+            None => line.trim_end_matches(['\r', '\n']).to_string(),
+        };
+        if sender.send(Frame { content_length, body }).is_err() {
+            return;
+        }
+    }
+}
+
+#[test]
+fn test_handshake() {
+    let mut server = Server::start();
+    let reply = server.initialize();
+    let result = &reply["result"];
+    assert!(result["protocolVersion"].is_string(), "{}", reply);
+    assert_eq!(result["serverInfo"]["name"], "FileJack");
+    assert!(result["capabilities"]["tools"].is_object(), "{}", reply);
+    // notifications/initialized is a notification, so it gets no reply
+    server.assert_silent();
+
+    let reply = server.request(json!("ping-1"), "ping", json!({}));
+    assert_eq!(reply["result"], json!({}));
+    assert!(server.finish().success());
+}
+
+#[test]
+fn test_tools_list() {
+    let mut server = Server::start();
+    server.initialize();
+    let reply = server.request(json!(1), "tools/list", json!({}));
+    let tools = reply["result"]["tools"].as_array().expect("tools array");
+    for name in ["read_file", "write_file", "list_directory", "delete_file"] {
+        assert!(tools.iter().any(|tool| tool["name"] == name), "{} missing", name);
+    }
+    for tool in tools {
+        assert!(tool["name"].is_string(), "{}", tool);
+        assert!(tool["description"].is_string(), "{}", tool);
+        assert_eq!(tool["inputSchema"]["type"], "object", "{}", tool);
+    }
+}
+
+#[test]
+fn test_tool_calls() {
+    let mut server = Server::start();
+    server.initialize();
+    let path = server.path("notes.txt");
+
+    let reply = server.call_tool(1, "write_file", json!({"path": path, "content": "über stdio\n"}));
+    assert!(reply["result"]["content"].is_array(), "{}", reply);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "über stdio\n");
+
+    let reply = server.call_tool(2, "read_file", json!({"path": path}));
+    assert_eq!(reply["result"]["content"][0]["type"], "text");
+    assert!(reply["result"]["content"][0]["text"].as_str().unwrap().contains("über stdio"), "{}", reply);
+
+    // Failures are JSON-RPC errors with a code and a message
+    let reply = server.call_tool(3, "read_file", json!({"path": "/etc/passwd"}));
+    assert!(reply["error"]["code"].is_i64(), "{}", reply);
+    assert!(reply["error"]["message"].is_string(), "{}", reply);
+    let reply = server.call_tool(4, "no_such_tool", json!({}));
+    assert!(reply["error"]["message"].as_str().unwrap().contains("no_such_tool"), "{}", reply);
+    let reply = server.call_tool(5, "read_file", json!({"wrong": true}));
+    assert!(reply["error"].is_object(), "{}", reply);
+}
+
+#[test]
+fn test_request_ids_are_echoed() {
+    let mut server = Server::start();
+    server.initialize();
+    for id in [json!(0), json!(-7), json!(9_007_199_254_740_991u64), json!("abc"), json!("")] {
+        server.request(id, "ping", json!({}));
+    }
+}
+
+#[test]
+fn test_notifications_get_no_reply() {
+    let mut server = Server::start();
+    server.initialize();
+    let path = server.path("from-notification.txt");
+
+    // A tool call without an id still runs, but is not answered
+    server.notify("tools/call", json!({"name": "write_file", "arguments": {"path": path, "content": "x"}}));
+    server.notify("notifications/unknown", json!({}));
+    server.notify("tools/call", json!({"name": "no_such_tool"}));
+    server.assert_silent();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "x");
+    server.request(json!(1), "ping", json!({}));
+}
+
+#[test]
+fn test_cancellation() {
+    let mut server = Server::start();
+    server.initialize();
+
+    // Cancelling a request that is in flight or already answered is allowed
+    // to be ignored, but must never produce a reply of its own
+    server.send(json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}));
+    server.notify("notifications/cancelled", json!({"requestId": 1, "reason": "user abort"}));
+    assert_eq!(server.recv()["id"], 1);
+    server.notify("notifications/cancelled", json!({"requestId": 99}));
+    server.notify("notifications/cancelled", json!({}));
+    server.assert_silent();
+    server.request(json!(2), "ping", json!({}));
+}
+
+#[test]
+fn test_pipelined_requests_are_answered_in_order() {
+    let mut server = Server::start();
+    server.initialize();
+    let batch: String = (1..=20)
+        .map(|id| format!("{}\n", json!({"jsonrpc": "2.0", "id": id, "method": "ping"})))
+        .collect();
+    server.send_raw(&batch);
+    for id in 1..=20 {
+        assert_eq!(server.recv()["id"], id);
+    }
+}
+
+#[test]
+fn test_malformed_input_keeps_the_connection() {
+    let mut server = Server::start();
+    server.initialize();
+
+    server.send_raw("{not json\n");
+    let reply = server.recv();
+    assert_eq!(reply["error"]["code"], -32700);
+    assert!(reply["id"].is_null(), "{}", reply);
+
+    server.send(json!({"jsonrpc": "2.0", "id": 1, "method": "no/such/method"}));
+    assert_eq!(server.recv()["error"]["code"], -32601);
+
+    // Blank lines between messages are skipped
+    server.send_raw("\n\r\n");
+    server.assert_silent();
+    server.request(json!(2), "ping", json!({}));
+}
+
+#[test]
+fn test_stdout_carries_only_protocol_messages() {
+    // Verbose logging must go to stderr, never into the protocol stream
+    let mut server = Server::start_with(|command| {
+        command.env("RUST_LOG", "trace");
+    });
+    server.initialize();
+    let path = server.path("logged.txt");
+    server.call_tool(1, "write_file", json!({"path": path, "content": "x"}));
+    server.call_tool(2, "read_file", json!({"path": "/nonexistent"}));
+    server.request(json!(3), "tools/list", json!({}));
+    server.assert_silent();
+    assert!(server.finish().success());
+}
+
+#[test]
+fn test_content_length_framing() {
+    let mut server = Server::start();
+    let body = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}).to_string();
+    server.send_raw(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+    // Replies follow the client's framing
+    let frame = server.recv_frame();
+    assert!(frame.content_length, "reply not framed: {}", frame.body);
+    assert_eq!(server.parse(frame)["id"], 1);
+
+    // The length counts bytes, not characters
+    let body = json!({"jsonrpc": "2.0", "id": "é", "method": "ping"}).to_string();
+    server.send_raw(&format!("Content-Length: {}\r\nContent-Type: application/json\r\n\r\n{}", body.len(), body));
+    let frame = server.recv_frame();
+    assert!(frame.content_length);
+    assert_eq!(server.parse(frame)["id"], "é");
+}
+
+#[test]
+fn test_exits_cleanly_when_stdin_closes() {
+    let mut server = Server::start();
+    server.initialize();
+    // A request sent just before closing stdin is still answered
+    server.send(json!({"jsonrpc": "2.0", "id": 1, "method": "ping"}));
+    drop(server.stdin.take());
+    assert_eq!(server.recv()["id"], 1);
+    assert!(server.finish().success());
+}
+
+#[test]
+fn test_exit_status_for_unusable_config() {
+    let dir = tempfile::Builder::new().prefix("conformance").tempdir().unwrap();
+    let config = dir.path().join("filejack.json");
+    fs::write(&config, "{ not a config").unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_filejack"))
+        .current_dir(dir.path())
+        .env("FILEJACK_CONFIG", &config)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    // EX_CONFIG: restarting with the same configuration would fail again
+    assert_eq!(status.code(), Some(78));
+}
+
+#[test]
+fn test_exit_status_when_client_goes_away() {
+    let dir = tempfile::Builder::new().prefix("conformance").tempdir().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_filejack"))
+        .current_dir(dir.path())
+        .env_remove("FILEJACK_CONFIG")
+        .env("FILEJACK_BASE_PATH", dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    // Nobody reads the reply, so writing it fails
+    drop(child.stdout.take());
+    let mut stdin = child.stdin.take().unwrap();
+    writeln!(stdin, "{}", json!({"jsonrpc": "2.0", "id": 1, "method": "ping"})).unwrap();
+    drop(stdin);
+    // EX_IOERR: the connection failed, but a restarted server could serve a new one
+    assert_eq!(child.wait().unwrap().code(), Some(74));
+}
+
+#[test]
+fn test_call_runs_one_tool() {
+    let dir = tempfile::Builder::new().prefix("conformance").tempdir().unwrap();
+    let call = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_filejack"))
+            .current_dir(dir.path())
+            .env_remove("FILEJACK_CONFIG")
+            .env("FILEJACK_BASE_PATH", dir.path())
+            .arg("call")
+            .args(args)
+            .stderr(Stdio::null())
+            .output()
+            .unwrap();
+        let printed: Value = serde_json::from_slice(&output.stdout).unwrap();
+        (output.status.code(), printed)
+    };
+
+    let (status, result) = call(&["write_file", "--arg", "path=notes.txt", "--arg", "content=hello"]);
+    assert_eq!(status, Some(0));
+    assert!(result["content"][0]["text"].as_str().unwrap().starts_with("Successfully wrote 5 bytes"));
+    assert_eq!(fs::read_to_string(dir.path().join("notes.txt")).unwrap(), "hello");
+
+    let (status, result) = call(&["get_metadata", "--arg=path=notes.txt", "--arg-json", "hash=true"]);
+    assert_eq!(status, Some(0));
+    assert_eq!(result["structuredContent"]["size"], 5);
+
+    let (status, error) = call(&["read_file", "--arg", "path=/etc/passwd"]);
+    assert_eq!(status, Some(1));
+    assert!(error["message"].as_str().unwrap().starts_with("Permission denied"));
+}
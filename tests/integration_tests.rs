@@ -201,6 +201,7 @@ fn test_special_characters_in_content() {
 }
 
 #[test]
+#[allow(clippy::useless_vec)]
 fn test_concurrent_operations_simulation() {
     let temp_dir = TempDir::new().unwrap();
     let server = McpServer::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()));
@@ -466,6 +467,37 @@ fn test_search_files_recursive() {
     assert!(response.contains("nested.log"));
 }
 
+#[test]
+fn test_recent_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut policy = AccessPolicy::permissive();
+    policy.allowed_paths = vec![temp_dir.path().to_path_buf()];
+    let server = McpServer::new(policy);
+
+    let subdir = temp_dir.path().join("logs");
+    fs::create_dir(&subdir).unwrap();
+    let old = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+    for (name, modified) in [("old.txt", old), ("logs/new.log", std::time::SystemTime::now())] {
+        let path = temp_dir.path().join(name);
+        fs::write(&path, "content").unwrap();
+        fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+    }
+
+    let result = server.handle_tool_call(
+        "recent_files",
+        serde_json::json!({"path": temp_dir.path(), "max_results": 5}),
+    ).unwrap();
+    let files: serde_json::Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+    let files = files.as_array().unwrap();
+    assert_eq!(files.len(), 2);
+    assert!(files[0]["path"].as_str().unwrap().ends_with("new.log"));
+    assert!(files[1]["path"].as_str().unwrap().ends_with("old.txt"));
+    assert_eq!(files[1]["size"], 7);
+
+    let result = server.handle_tool_call("recent_files", serde_json::json!({"path": temp_dir.path(), "max_results": 1})).unwrap();
+    assert!(!result["content"][0]["text"].as_str().unwrap().contains("old.txt"));
+}
+
 #[test]
 fn test_grep_file() {
     let temp_dir = TempDir::new().unwrap();
@@ -520,3 +552,195 @@ fn test_tools_list_includes_new_tools() {
     assert!(response.contains("search_files"));
     assert!(response.contains("grep_file"));
 }
+
+#[test]
+fn test_client_drives_spawned_server() {
+    // The default temp dir prefix is hidden, which would block listing it
+    let temp_dir = tempfile::Builder::new().prefix("client").tempdir().unwrap();
+    let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_filejack"));
+    command
+        .current_dir(temp_dir.path())
+        .env_remove("FILEJACK_CONFIG")
+        .env("FILEJACK_BASE_PATH", temp_dir.path())
+        .env("RUST_LOG", "error");
+    let mut client = filejack::McpClient::spawn(command).unwrap();
+
+    client.initialize().unwrap();
+    let path = temp_dir.path().join("spawned.txt");
+    let path = path.to_str().unwrap();
+    client.write_file(path, "from a child process").unwrap();
+    assert_eq!(client.read_file(path).unwrap(), "from a child process");
+    assert_eq!(fs::read_to_string(path).unwrap(), "from a child process");
+
+    let listing = client.list_directory(temp_dir.path().to_str().unwrap(), false).unwrap();
+    assert_eq!(listing.as_array().unwrap().len(), 1);
+    client.close().unwrap();
+}
+
+#[test]
+fn test_mounts_route_to_local_and_remote_backends() {
+    let home = tempfile::Builder::new().prefix("home").tempdir().unwrap();
+    let docs = tempfile::Builder::new().prefix("docs").tempdir().unwrap();
+    let remote = tempfile::Builder::new().prefix("remote").tempdir().unwrap();
+    fs::write(docs.path().join("guide.md"), "local docs").unwrap();
+    fs::write(remote.path().join("build.log"), "remote log").unwrap();
+
+    let mut config = filejack::Config::default_restricted(home.path().to_path_buf());
+    config.mounts = vec![
+        filejack::MountConfig {
+            prefix: "/docs".to_string(),
+            path: docs.path().to_path_buf(),
+            command: Vec::new(),
+            env: Default::default(),
+        },
+        filejack::MountConfig {
+            prefix: "/build".to_string(),
+            path: remote.path().to_path_buf(),
+            command: vec![env!("CARGO_BIN_EXE_filejack").to_string()],
+            env: [
+                ("FILEJACK_BASE_PATH".to_string(), remote.path().display().to_string()),
+                ("RUST_LOG".to_string(), "error".to_string()),
+            ].into_iter().collect(),
+        },
+    ];
+    assert!(config.validate().iter().all(|issue| !issue.is_error()), "{:?}", config.validate());
+    let server = std::sync::Arc::new(McpServer::from_config(config));
+    let mut client = filejack::McpClient::in_process(server);
+    client.initialize().unwrap();
+
+    assert_eq!(client.read_file("/docs/guide.md").unwrap(), "local docs");
+    assert_eq!(client.read_file("/build/build.log").unwrap(), "remote log");
+    client.write_file("/build/out/result.txt", "done").unwrap();
+    assert_eq!(fs::read_to_string(remote.path().join("out/result.txt")).unwrap(), "done");
+
+    let err = client.call_tool("copy_file", serde_json::json!({"from": "/build/build.log", "to": "/docs/build.log"}))
+        .unwrap_err();
+    assert!(err.to_string().contains("same mount"), "{}", err);
+    assert!(client.read_file("/docs/../etc/passwd").is_err());
+    client.close().unwrap();
+}
+
+#[test]
+fn test_browse_archive_as_directory() {
+    let temp_dir = tempfile::Builder::new().prefix("archives").tempdir().unwrap();
+    let archive = temp_dir.path().join("release.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+    zip.start_file("docs/notes.txt", zip::write::SimpleFileOptions::default()).unwrap();
+    std::io::Write::write_all(&mut zip, b"first line\nsecond line\n").unwrap();
+    zip.finish().unwrap();
+
+    let mut policy = AccessPolicy::restricted(temp_dir.path().to_path_buf());
+    policy.browse_archives = true;
+    let mut client = filejack::McpClient::in_process(std::sync::Arc::new(McpServer::new(policy)));
+    client.initialize().unwrap();
+
+    let inside = archive.join("docs/notes.txt");
+    let inside = inside.to_str().unwrap();
+    assert_eq!(client.read_file(inside).unwrap(), "first line\nsecond line\n");
+
+    let listing = client.list_directory(archive.to_str().unwrap(), true).unwrap();
+    assert_eq!(listing.as_array().unwrap().len(), 2, "{}", listing);
+
+    let matches = client.call_tool_text("grep_file", serde_json::json!({
+        "path": temp_dir.path().to_str().unwrap(),
+        "pattern": "second",
+    })).unwrap();
+    assert!(matches.contains("notes.txt"), "{}", matches);
+
+    let err = client.write_file(inside, "changed").unwrap_err();
+    assert!(err.to_string().contains("read-only archive"), "{}", err);
+    client.close().unwrap();
+}
+
+#[test]
+fn test_compress_and_decompress_file() {
+    let temp_dir = tempfile::Builder::new().prefix("logs").tempdir().unwrap();
+    let log = temp_dir.path().join("app.log");
+    let text = "GET /health 200\n".repeat(500);
+    fs::write(&log, &text).unwrap();
+
+    let server = std::sync::Arc::new(McpServer::new(AccessPolicy::restricted(temp_dir.path().to_path_buf())));
+    let mut client = filejack::McpClient::in_process(server);
+    client.initialize().unwrap();
+
+    let summary = client.call_tool_text("compress_file", serde_json::json!({"from": log.to_str().unwrap()})).unwrap();
+    let summary: serde_json::Value = serde_json::from_str(&summary).unwrap();
+    let compressed = temp_dir.path().join("app.log.gz");
+    assert_eq!(summary["destination"], compressed.to_str().unwrap());
+    assert_eq!(summary["bytes_in"], text.len());
+    assert!(fs::metadata(&compressed).unwrap().len() < text.len() as u64 / 10);
+
+    fs::remove_file(&log).unwrap();
+    client.call_tool_text("decompress_file", serde_json::json!({"from": compressed.to_str().unwrap()})).unwrap();
+    assert_eq!(fs::read_to_string(&log).unwrap(), text);
+
+    let restored = temp_dir.path().join("restored.txt");
+    client.call_tool_text("decompress_file", serde_json::json!({
+        "from": compressed.to_str().unwrap(),
+        "to": restored.to_str().unwrap(),
+        "format": "gzip",
+    })).unwrap();
+    assert_eq!(fs::read_to_string(&restored).unwrap(), text);
+
+    let err = client.call_tool_text("decompress_file", serde_json::json!({"from": log.to_str().unwrap()})).unwrap_err();
+    assert!(err.to_string().contains(".gz or .zst"), "{}", err);
+    client.close().unwrap();
+}
+
+#[test]
+fn test_typed_tool_calls() {
+    use filejack::protocol::{
+        ContentEncoding, ListDirectoryParams, MoveFileParams, ReadFileParams, SearchFilesParams, WriteFileParams,
+    };
+    use filejack::FileJackError;
+
+    let temp_dir = tempfile::Builder::new().prefix("typed").tempdir().unwrap();
+    let server = McpServer::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()));
+    let path = temp_dir.path().join("notes.md");
+    let path_str = path.to_str().unwrap().to_string();
+
+    let written = server.write_file(WriteFileParams {
+        path: path_str.clone(),
+        content: "# Notes".to_string(),
+        atomic: true,
+        lock_token: None,
+        skip_unchanged: None,
+        if_match: None,
+        preserve_style: false,
+    }).unwrap();
+    assert_eq!(written.bytes_written, 7);
+
+    let read = server.read_file(ReadFileParams { path: path_str.clone(), encoding: ContentEncoding::Text }).unwrap();
+    assert_eq!(read.content, "# Notes");
+    let read = server.read_file(ReadFileParams { path: path_str.clone(), encoding: ContentEncoding::Base64 }).unwrap();
+    assert_eq!(read.content, "IyBOb3Rlcw==");
+
+    let renamed = temp_dir.path().join("README.md");
+    server.move_file(MoveFileParams {
+        from: path_str.clone(),
+        to: renamed.to_str().unwrap().to_string(),
+        lock_token: None,
+    }).unwrap();
+    let found = server.search_files(SearchFilesParams {
+        path: temp_dir.path().to_str().unwrap().to_string(),
+        pattern: "*.md".to_string(),
+        recursive: true,
+        max_results: None,
+    }).unwrap();
+    assert_eq!(found, [renamed.to_str().unwrap()]);
+    let entries = server.list_directory(ListDirectoryParams {
+        path: temp_dir.path().to_str().unwrap().to_string(),
+        recursive: false,
+        pattern: None,
+        entry_type: None,
+        sort_by: Default::default(),
+        order: Default::default(),
+        offset: 0,
+        limit: None,
+    }).unwrap();
+    assert_eq!(entries.len(), 1);
+
+    // The access policy applies to typed calls as it does over JSON-RPC
+    let denied = server.read_file(ReadFileParams { path: "/etc/passwd".to_string(), encoding: ContentEncoding::Text });
+    assert!(matches!(denied, Err(FileJackError::PermissionDenied(_))), "{:?}", denied);
+}
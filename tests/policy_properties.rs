@@ -0,0 +1,160 @@
+//! Property tests for the invariants `AccessPolicy` promises whatever the
+//! path and policy settings: accepted paths stay inside an allowed root,
+//! denied paths win over allowed ones, and a read-only policy never
+//! validates a write.
+
+#[path = "../fuzz/oracle.rs"]
+mod oracle;
+
+use filejack::{AccessPolicy, WriteOperation};
+use oracle::real_location;
+use proptest::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Two allowed roots with a denied subdirectory, a symlink out, and
+/// siblings whose names share the first root's prefix
+struct Tree {
+    _dir: TempDir,
+    base: PathBuf,
+    roots: [PathBuf; 2],
+}
+
+impl Tree {
+    fn new() -> Self {
+        let dir = tempfile::Builder::new().prefix("policy-props").tempdir().unwrap();
+        let base = dir.path().canonicalize().unwrap();
+        let roots = [base.join("allowed"), base.join("second")];
+        for sub in ["allowed/sub", "allowed/denied", "allowed-evil", "second"] {
+            fs::create_dir_all(base.join(sub)).unwrap();
+        }
+        let files = [
+            "allowed/a.txt", "allowed/sub/b.txt", "allowed/denied/secret.txt", "allowed/.hidden",
+            "allowed-evil/x.txt", "second/c.txt", "outside.txt",
+        ];
+        for file in files {
+            fs::write(base.join(file), file).unwrap();
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(base.join("outside.txt"), base.join("allowed/escape")).unwrap();
+        Self { _dir: dir, base, roots }
+    }
+
+    fn policy(&self, settings: &Settings) -> AccessPolicy {
+        let mut policy = AccessPolicy::restricted(self.roots[0].clone());
+        policy.allowed_paths.push(self.roots[1].clone());
+        policy.denied_paths = settings.denied.iter().map(|denied| self.base.join(denied)).collect();
+        policy.allow_symlinks = settings.allow_symlinks;
+        policy.allow_hidden_files = settings.allow_hidden_files;
+        policy.read_only = settings.read_only;
+        policy
+    }
+
+    fn path(&self, (start, parts): &(&str, Vec<&str>)) -> PathBuf {
+        let start = match *start {
+            "" => PathBuf::new(),
+            "/" => PathBuf::from("/"),
+            start => self.base.join(start),
+        };
+        PathBuf::from(format!("{}/{}", start.display(), parts.join("/")))
+    }
+}
+
+#[derive(Debug)]
+struct Settings {
+    denied: Vec<&'static str>,
+    allow_symlinks: bool,
+    allow_hidden_files: bool,
+    read_only: bool,
+}
+
+fn settings() -> impl Strategy<Value = Settings> {
+    let denied = proptest::sample::subsequence(vec!["allowed/denied", "allowed/sub", "second", "outside.txt"], 0..=4);
+    (denied, any::<bool>(), any::<bool>(), any::<bool>()).prop_map(|(denied, allow_symlinks, allow_hidden_files, read_only)| {
+        Settings { denied, allow_symlinks, allow_hidden_files, read_only }
+    })
+}
+
+/// Paths built from names in the tree, `.`/`..`, and names that do not exist
+fn paths() -> impl Strategy<Value = (&'static str, Vec<&'static str>)> {
+    let start = prop::sample::select(vec!["", "/", "allowed", "second", "allowed-evil", "allowed/sub"]);
+    let part = prop::sample::select(vec![
+        "a.txt", "sub", "b.txt", "denied", "secret.txt", "escape", ".hidden", "c.txt", "..", ".", "", "missing",
+        "allowed", "allowed-evil", "second", "outside.txt", "x.txt",
+    ]);
+    (start, prop::collection::vec(part, 0..8))
+}
+
+fn accepted(policy: &AccessPolicy, path: &Path) -> Vec<(&'static str, PathBuf)> {
+    let mut accepted = Vec::new();
+    if policy.validate_read(path).is_ok() {
+        accepted.push(("read", path.to_path_buf()));
+    }
+    if policy.validate_write(path, WriteOperation::Write).is_ok() {
+        accepted.push(("write", path.to_path_buf()));
+    }
+    accepted
+}
+
+#[test]
+fn test_accepted_paths_stay_inside_allowed_roots() {
+    let tree = Tree::new();
+    proptest!(|(settings in settings(), path in paths())| {
+        let policy = tree.policy(&settings);
+        let path = tree.path(&path);
+        for (access, accepted) in accepted(&policy, &path) {
+            let Some(location) = real_location(&accepted) else { continue };
+            prop_assert!(
+                tree.roots.iter().any(|root| location.starts_with(root)),
+                "{} of {} allowed, but it resolves to {}", access, path.display(), location.display()
+            );
+        }
+    });
+}
+
+#[test]
+fn test_denied_beats_allowed() {
+    let tree = Tree::new();
+    proptest!(|(settings in settings(), path in paths())| {
+        let policy = tree.policy(&settings);
+        let path = tree.path(&path);
+        for (access, accepted) in accepted(&policy, &path) {
+            let Some(location) = real_location(&accepted) else { continue };
+            for denied in &settings.denied {
+                prop_assert!(
+                    !location.starts_with(tree.base.join(denied)),
+                    "{} of {} allowed inside denied {}", access, path.display(), denied
+                );
+            }
+        }
+    });
+}
+
+#[test]
+fn test_validate_write_never_succeeds_when_read_only() {
+    let tree = Tree::new();
+    proptest!(|(mut settings in settings(), path in paths())| {
+        settings.read_only = true;
+        let read_only = tree.policy(&settings);
+        settings.read_only = false;
+        let writable = tree.policy(&settings);
+        let path = tree.path(&path);
+
+        prop_assert!(read_only.validate_write(&path, WriteOperation::Write).is_err(), "read-only policy allowed writing {}", path.display());
+        // Reads are unaffected by read-only mode
+        prop_assert_eq!(read_only.validate_read(&path).ok(), writable.validate_read(&path).ok());
+    });
+}
+
+#[test]
+fn test_validate_read_returns_canonical_path() {
+    let tree = Tree::new();
+    proptest!(|(settings in settings(), path in paths())| {
+        let policy = tree.policy(&settings);
+        let path = tree.path(&path);
+        if let Ok(validated) = policy.validate_read(&path) {
+            prop_assert_eq!(Some(validated), path.canonicalize().ok());
+        }
+    });
+}
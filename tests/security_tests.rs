@@ -26,6 +26,7 @@ fn test_path_traversal_attack_attempt() {
 }
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_absolute_path_outside_allowed() {
     let temp_dir = TempDir::new().unwrap();
     let allowed_dir = temp_dir.path().join("allowed");
@@ -242,6 +243,7 @@ fn test_malicious_json_input() {
 }
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_null_byte_injection() {
     let temp_dir = TempDir::new().unwrap();
     let server = McpServer::new(AccessPolicy::restricted(temp_dir.path().to_path_buf()));
@@ -371,3 +373,217 @@ fn test_directory_listing_respects_policy() {
     // The .exe file won't be in the list because it doesn't pass the policy validation
     assert!(!response.contains("denied.exe"), "Should not contain denied.exe");
 }
+
+#[test]
+fn test_directory_overlay_tightens_subtree() {
+    let temp_dir = TempDir::new().unwrap();
+    let workspace = temp_dir.path().join("workspace");
+    let secrets = workspace.join("secrets");
+    fs::create_dir_all(&secrets).unwrap();
+    fs::write(secrets.join("token.txt"), "s3cr3t").unwrap();
+    fs::write(workspace.join("notes.txt"), "hello").unwrap();
+    fs::write(secrets.join(".filejack.json"), r#"{"deny_all": true}"#).unwrap();
+
+    let mut policy = AccessPolicy::permissive();
+    policy.allowed_paths = vec![workspace.clone()];
+    let server = McpServer::new(policy);
+
+    let call = |tool: &str, args: serde_json::Value| {
+        server.process_request(&serde_json::json!({
+            "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": {"name": tool, "arguments": args}
+        }).to_string())
+    };
+
+    // The rest of the workspace is unaffected
+    assert!(call("read_file", serde_json::json!({"path": workspace.join("notes.txt")})).contains("hello"));
+
+    // Nothing inside the overlay's subtree is reachable
+    let response = call("read_file", serde_json::json!({"path": secrets.join("token.txt")}));
+    assert!(response.contains("error") && !response.contains("s3cr3t"), "{}", response);
+    assert!(call("list_directory", serde_json::json!({"path": secrets})).contains("error"));
+
+    // The overlay cannot be removed or overwritten to lift the restriction
+    let response = call("write_file", serde_json::json!({"path": workspace.join(".filejack.json"), "content": "{}"}));
+    assert!(response.contains("cannot be modified"), "{}", response);
+    let response = call("remove_directory", serde_json::json!({"path": workspace, "recursive": true}));
+    assert!(response.contains("policy overlay"), "{}", response);
+    assert!(secrets.join(".filejack.json").exists());
+}
+
+#[test]
+fn test_append_only_paths_can_only_grow() {
+    let temp_dir = TempDir::new().unwrap();
+    let logs = temp_dir.path().join("logs");
+    fs::create_dir(&logs).unwrap();
+    let log = logs.join("agent.log");
+    fs::write(&log, "started\n").unwrap();
+    let changelog = temp_dir.path().join("CHANGELOG.md");
+    fs::write(&changelog, "# Changes\n").unwrap();
+
+    let mut policy = AccessPolicy::permissive();
+    policy.append_only_paths = vec![logs.clone(), changelog.clone()];
+    let server = McpServer::new(policy);
+    let call = |tool: &str, args: serde_json::Value| server.handle_tool_call(tool, args).map_err(|e| e.to_string());
+
+    call("append_file", serde_json::json!({"path": log, "content": "done\n"})).unwrap();
+    call("append_file", serde_json::json!({"path": logs.join("new.log"), "content": "x"})).unwrap();
+    assert_eq!(fs::read_to_string(&log).unwrap(), "started\ndone\n");
+
+    let refused = [
+        call("write_file", serde_json::json!({"path": changelog, "content": ""})),
+        call("write_file", serde_json::json!({"path": logs.join("other.log"), "content": ""})),
+        call("delete_file", serde_json::json!({"path": log})),
+        call("move_file", serde_json::json!({"from": log, "to": temp_dir.path().join("moved.log")})),
+        call("move_file", serde_json::json!({"from": temp_dir.path().join("CHANGELOG.md"), "to": logs.join("c.md")})),
+        call("remove_directory", serde_json::json!({"path": logs, "recursive": true})),
+    ];
+    for result in refused {
+        assert!(result.as_ref().unwrap_err().contains("append-only"), "{:?}", result);
+    }
+    assert_eq!(fs::read_to_string(&changelog).unwrap(), "# Changes\n");
+
+    // Reading and copying out are unaffected
+    call("copy_file", serde_json::json!({"from": log, "to": temp_dir.path().join("copy.log")})).unwrap();
+    assert_eq!(fs::read_to_string(temp_dir.path().join("copy.log")).unwrap(), "started\ndone\n");
+}
+
+#[test]
+fn test_protected_files_cannot_change() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = temp_dir.path().join("repo");
+    fs::create_dir_all(repo.join(".github/workflows")).unwrap();
+    fs::write(repo.join(".github/workflows/ci.yml"), "on: push\n").unwrap();
+    fs::write(repo.join("LICENSE"), "MIT\n").unwrap();
+
+    let mut policy = AccessPolicy::permissive();
+    policy.protected_paths = vec!["LICENSE".to_string(), ".github/**".to_string()];
+    let server = McpServer::new(policy);
+    let call = |tool: &str, args: serde_json::Value| server.handle_tool_call(tool, args).map_err(|e| e.to_string());
+
+    let refused = [
+        call("write_file", serde_json::json!({"path": repo.join("LICENSE"), "content": ""})),
+        call("append_file", serde_json::json!({"path": repo.join("LICENSE"), "content": "x"})),
+        call("delete_file", serde_json::json!({"path": repo.join(".github/workflows/ci.yml")})),
+        call("write_file", serde_json::json!({"path": repo.join(".github/workflows/new.yml"), "content": ""})),
+        call("move_file", serde_json::json!({"from": repo.join(".github"), "to": repo.join("github")})),
+        call("remove_directory", serde_json::json!({"path": repo, "recursive": true})),
+    ];
+    for result in refused {
+        assert!(result.as_ref().unwrap_err().contains("is a protected file"), "{:?}", result);
+    }
+    assert_eq!(fs::read_to_string(repo.join("LICENSE")).unwrap(), "MIT\n");
+    assert!(call("read_file", serde_json::json!({"path": repo.join("LICENSE")})).unwrap().to_string().contains("MIT"));
+    call("write_file", serde_json::json!({"path": repo.join("README.md"), "content": "hi"})).unwrap();
+}
+
+#[test]
+fn test_decompression_bomb_is_refused() {
+    let temp_dir = TempDir::new().unwrap();
+    let bomb = temp_dir.path().join("bomb.gz");
+    let zeros = vec![0u8; 8 * 1024 * 1024];
+    fs::write(&bomb, filejack::compression::compress(&zeros, filejack::CompressionFormat::Gzip, 9).unwrap()).unwrap();
+
+    let mut policy = AccessPolicy::permissive();
+    policy.max_file_size = 1024 * 1024;
+    let server = McpServer::new(policy);
+    let err = server.handle_tool_call("decompress_file", serde_json::json!({"from": bomb})).unwrap_err();
+    assert!(err.to_string().contains("exceeds the limit of 1048576 bytes"), "{}", err);
+    assert!(!temp_dir.path().join("bomb").exists());
+
+    // A caller can ask for a tighter limit than the policy's
+    let small = temp_dir.path().join("small.gz");
+    fs::write(&small, filejack::compression::compress(&[b'x'; 4096], filejack::CompressionFormat::Gzip, 6).unwrap()).unwrap();
+    let err = server.handle_tool_call("decompress_file", serde_json::json!({"from": small, "max_size": 100})).unwrap_err();
+    assert!(err.to_string().contains("exceeds the limit of 100 bytes"), "{}", err);
+}
+
+/// Assert that `response` is empty (a notification) or a JSON-RPC message
+/// that is not the internal error a caught panic turns into
+fn assert_answered_without_panic(request: &str, response: &str) {
+    if response.is_empty() {
+        return;
+    }
+    let response: serde_json::Value = serde_json::from_str(response)
+        .unwrap_or_else(|e| panic!("invalid response to {:?}: {}", request, e));
+    assert_eq!(response["jsonrpc"], "2.0", "{}", request);
+    assert_ne!(response["error"]["code"], -32603, "request panicked: {}", request);
+}
+
+#[test]
+fn test_malformed_requests_never_panic() {
+    use filejack::RateLimiter;
+    use serde_json::json;
+
+    let temp_dir = tempfile::Builder::new().prefix("fuzz").tempdir().unwrap();
+    let file = temp_dir.path().join("a.txt");
+    fs::write(&file, "one\ntwo\n").unwrap();
+    let server = McpServer::with_rate_limiter(
+        AccessPolicy::restricted(temp_dir.path().to_path_buf()),
+        RateLimiter::new(1_000_000),
+    );
+    let send = |request: String| {
+        let response = server.process_request(&request);
+        assert_answered_without_panic(&request, &response);
+    };
+
+    // Every tool, with arguments of every shape and each parameter set to
+    // values of the wrong type or range
+    let values = [
+        json!(null), json!(true), json!(-1), json!(1.5), json!(1e308), json!(u64::MAX), json!(""),
+        json!("\u{0}"), json!("é".repeat(5)), json!("../../../etc/passwd"), json!([]), json!({}),
+        json!([file]), json!({"path": file}), json!(file),
+    ];
+    let keys = [
+        "path", "from", "to", "content", "encoding", "offset", "length", "pattern", "query", "recursive",
+        "max_results", "max_matches", "context_lines", "start_line", "end_line", "tail", "format", "level",
+        "max_size", "token", "ttl_seconds", "lock_token",
+    ];
+    for tool in server.list_tools() {
+        for value in &values {
+            send(json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": tool.name, "arguments": value}}).to_string());
+            for key in keys {
+                let mut arguments = json!({"path": file, "from": file, "content": "x", "pattern": "o", "offset": 0, "length": 4});
+                arguments[key] = value.clone();
+                send(json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": tool.name, "arguments": arguments}}).to_string());
+            }
+        }
+    }
+
+    // Envelopes with odd ids, params, and methods
+    for value in &values {
+        for method in ["tools/list", "tools/call", "initialize", "notifications/initialized", "nope"] {
+            send(json!({"jsonrpc": "2.0", "id": value, "method": method, "params": value}).to_string());
+            send(json!({"jsonrpc": value, "method": method, "params": {"name": value}}).to_string());
+        }
+    }
+
+    // Truncations and byte-level mutations of a valid request
+    let valid = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": "read_lines", "arguments": {"path": file, "tail": 1}}}).to_string();
+    for end in (0..valid.len()).filter(|end| valid.is_char_boundary(*end)) {
+        send(valid[..end].to_string());
+    }
+    const MUTATIONS: &[u8] = b"{}[]\",:\\0-e9 aZ\x7f\xff";
+    let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    for _ in 0..2000 {
+        let mut bytes = valid.clone().into_bytes();
+        for _ in 0..1 + next() % 4 {
+            let at = (next() % bytes.len() as u64) as usize;
+            let byte = MUTATIONS[(next() % MUTATIONS.len() as u64) as usize];
+            match next() % 3 {
+                0 => bytes[at] = byte,
+                1 => bytes.insert(at, byte),
+                _ => {
+                    bytes.remove(at);
+                }
+            }
+        }
+        send(String::from_utf8_lossy(&bytes).into_owned());
+    }
+}